@@ -0,0 +1,22 @@
+//! Writing a buffer byte by byte through fresh references splits its Stacked Borrows stacks into
+//! one stack per byte. Once the provenance GC has removed the tags of those references, the stacks
+//! are equal again and get merged, so the accesses to the whole buffer afterwards only have to
+//! visit a few stacks instead of one per byte.
+//! To make this benchmark demonstrate the effectiveness, run with MIRIFLAGS="-Zmiri-provenance-gc=100"
+
+const N: usize = 64 * 1024;
+
+fn main() {
+    let mut buf = vec![0u8; N];
+    for i in 0..N {
+        let byte = &mut buf[i];
+        *byte = i as u8;
+    }
+    let mut sum = 0u64;
+    for _ in 0..100 {
+        let slice = &mut buf[..];
+        slice[0] = slice[0].wrapping_add(1);
+        sum += u64::from(slice[N - 1]);
+    }
+    assert_eq!(sum, 100 * u64::from((N - 1) as u8));
+}
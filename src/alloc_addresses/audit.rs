@@ -0,0 +1,52 @@
+//! The record of `-Zmiri-provenance-audit`: where the program exposes provenance and casts
+//! integers to pointers.
+
+use rustc_data_structures::fx::{FxHashSet, FxIndexMap};
+use rustc_span::Span;
+
+use crate::*;
+
+/// One location in the program that exposes provenance or casts integers to pointers.
+#[derive(Debug)]
+pub struct AuditSite<'tcx> {
+    /// How often this happened here.
+    pub count: u64,
+    /// The allocations that were exposed here. Always empty for integer-to-pointer casts.
+    pub allocs: FxHashSet<AllocId>,
+    /// The stack at the first time this happened here.
+    pub backtrace: Vec<FrameInfo<'tcx>>,
+}
+
+/// Everything `-Zmiri-provenance-audit` has seen so far, keyed by the location in the
+/// program (as given by `MiriMachine::current_span`) and in order of first occurrence.
+#[derive(Debug, Default)]
+pub struct ProvenanceAudit<'tcx> {
+    pub exposes: FxIndexMap<Span, AuditSite<'tcx>>,
+    pub int2ptr_casts: FxIndexMap<Span, AuditSite<'tcx>>,
+}
+
+impl<'tcx> ProvenanceAudit<'tcx> {
+    fn record(
+        sites: &mut FxIndexMap<Span, AuditSite<'tcx>>,
+        ecx: &MiriInterpCx<'tcx>,
+        alloc_id: Option<AllocId>,
+    ) {
+        let site = sites.entry(ecx.machine.current_span()).or_insert_with(|| {
+            AuditSite {
+                count: 0,
+                allocs: FxHashSet::default(),
+                backtrace: ecx.generate_stacktrace(),
+            }
+        });
+        site.count += 1;
+        site.allocs.extend(alloc_id);
+    }
+
+    pub fn record_expose(&mut self, ecx: &MiriInterpCx<'tcx>, alloc_id: AllocId) {
+        Self::record(&mut self.exposes, ecx, Some(alloc_id));
+    }
+
+    pub fn record_int2ptr_cast(&mut self, ecx: &MiriInterpCx<'tcx>) {
+        Self::record(&mut self.int2ptr_casts, ecx, None);
+    }
+}
@@ -1,16 +1,22 @@
 //! This module is responsible for managing the absolute addresses that allocations are located at,
 //! and for casting between pointers and integers based on those addresses.
 
+mod audit;
 mod reuse_pool;
 
 use std::cell::RefCell;
 use std::cmp::max;
 
+use either::Either;
 use rand::Rng;
 use rustc_abi::{Align, Size};
 use rustc_data_structures::fx::{FxHashMap, FxHashSet};
-use rustc_span::Span;
+use rustc_middle::mir;
+use rustc_middle::ty::layout::HasTypingEnv;
+use rustc_middle::ty::{self, Ty};
+use rustc_span::{Span, sym};
 
+pub use self::audit::{AuditSite, ProvenanceAudit};
 use self::reuse_pool::ReusePool;
 use crate::concurrency::VClock;
 use crate::*;
@@ -22,6 +28,9 @@ pub enum ProvenanceMode {
     Default,
     /// Like `Default`, but without the warning.
     Permissive,
+    /// Like `Permissive`, but we error on transmutes between pointers and integers, which
+    /// unlike `expose_provenance`/`with_exposed_provenance` lose the provenance.
+    StrictTransmutes,
     /// We error on `with_exposed_provenance`, ensuring no precision loss.
     Strict,
 }
@@ -288,6 +297,11 @@ impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
 pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     fn expose_ptr(&mut self, alloc_id: AllocId, tag: BorTag) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
+        if let Some(audit) = &this.machine.provenance_audit
+            && this.is_alloc_live(alloc_id)
+        {
+            audit.borrow_mut().record_expose(this, alloc_id);
+        }
         let global_state = this.machine.alloc_addresses.get_mut();
         // In strict mode, we don't need this, so we can save some cycles by not tracking it.
         if global_state.provenance_mode == ProvenanceMode::Strict {
@@ -307,12 +321,148 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(())
     }
 
+    /// Whether `-Zmiri-strict-transmutes` applies to code in the given function. The standard
+    /// library is exempt, it implements `addr` and `without_provenance` with transmutes.
+    fn checks_transmutes_in(&self, instance: ty::Instance<'tcx>) -> bool {
+        let this = self.eval_context_ref();
+        if this.machine.alloc_addresses.borrow().provenance_mode != ProvenanceMode::StrictTransmutes
+        {
+            return false;
+        }
+        let krate = this.tcx.crate_name(instance.def_id().krate);
+        !matches!(krate.as_str(), "core" | "alloc" | "std")
+    }
+
+    /// With `-Zmiri-strict-transmutes`, called when `local` is read in the current frame. Rejects
+    /// the current statement if that read turns a pointer with provenance into an integer: by
+    /// transmuting it, by reading an integer field of a union that holds it, or by passing it to
+    /// `mem::transmute_copy`. Pointers without provenance have nothing to lose, and transmuting
+    /// an integer to a pointer is the same as `ptr::without_provenance`, so both are fine.
+    fn check_ptr_int_transmute(&self, local: mir::Local) -> InterpResult<'tcx> {
+        let this = self.eval_context_ref();
+        let frame = this.frame();
+        let Either::Left(loc) = frame.current_loc() else { return interp_ok(()) };
+        let instantiate = |ty: Ty<'tcx>| {
+            frame.instance().instantiate_mir_and_normalize_erasing_regions(
+                *this.tcx,
+                this.typing_env(),
+                ty::EarlyBinder::bind(ty),
+            )
+        };
+        let block = &frame.body().basic_blocks[loc.block];
+        let (deref, from, union_field) = match block.statements.get(loc.statement_index) {
+            Some(stmt) => {
+                let mir::StatementKind::Assign(assign) = &stmt.kind else { return interp_ok(()) };
+                match &assign.1 {
+                    mir::Rvalue::Cast(
+                        mir::CastKind::Transmute,
+                        mir::Operand::Copy(place) | mir::Operand::Move(place),
+                        to,
+                    ) if place.as_local() == Some(local) => {
+                        let from = instantiate(place.ty(frame.body(), *this.tcx).ty);
+                        if !from.is_any_ptr() || !instantiate(*to).is_integral() {
+                            return interp_ok(());
+                        }
+                        (false, from, false)
+                    }
+                    mir::Rvalue::Use(mir::Operand::Copy(place) | mir::Operand::Move(place))
+                        if place.local == local =>
+                    {
+                        let Some((base, mir::ProjectionElem::Field(_, field_ty))) =
+                            place.iter_projections().last()
+                        else {
+                            return interp_ok(());
+                        };
+                        let deref = match base.projection {
+                            [] => false,
+                            [mir::ProjectionElem::Deref] => true,
+                            _ => return interp_ok(()),
+                        };
+                        let field_ty = instantiate(field_ty);
+                        if !base.ty(frame.body(), *this.tcx).ty.is_union()
+                            || !field_ty.is_integral()
+                        {
+                            return interp_ok(());
+                        }
+                        (deref, field_ty, true)
+                    }
+                    _ => return interp_ok(()),
+                }
+            }
+            None => {
+                // `mem::transmute_copy::<Src, Dst>(src)` reads `*src` as a `Dst`.
+                let mir::TerminatorKind::Call { func, args, .. } = &block.terminator().kind else {
+                    return interp_ok(());
+                };
+                let ty::FnDef(def_id, generic_args) =
+                    *instantiate(func.ty(frame.body(), *this.tcx)).kind()
+                else {
+                    return interp_ok(());
+                };
+                let [arg] = &args[..] else { return interp_ok(()) };
+                if arg.node.place().and_then(|place| place.as_local()) != Some(local)
+                    || this.tcx.crate_name(def_id.krate) != sym::core
+                    || this.tcx.def_path(def_id).to_string_no_crate_verbose()
+                        != "::mem::transmute_copy"
+                {
+                    return interp_ok(());
+                }
+                let (from, to) = (generic_args.type_at(0), generic_args.type_at(1));
+                if !from.is_any_ptr() || !to.is_integral() {
+                    return interp_ok(());
+                }
+                (true, from, false)
+            }
+        };
+        let size = this.layout_of(from)?.size;
+        if !this.local_has_provenance(local, deref, size)? {
+            return interp_ok(());
+        }
+        throw_machine_stop!(TerminationInfo::PtrIntTransmute { union_field })
+    }
+
+    /// Whether the value of `local` in the current frame carries provenance, or if `deref` is set,
+    /// the first `size` bytes it points to. This reads the local directly, so that the read does
+    /// not end up in `check_ptr_int_transmute` again.
+    fn local_has_provenance(
+        &self,
+        local: mir::Local,
+        deref: bool,
+        size: Size,
+    ) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_ref();
+        let ptr = match this.frame().locals[local].as_mplace_or_imm() {
+            None => return interp_ok(false),
+            Some(Either::Right(imm)) if !deref => return interp_ok(imm.has_provenance()),
+            Some(Either::Right(Immediate::Scalar(ptr))) => ptr.to_pointer(this)?,
+            Some(Either::Right(_)) => return interp_ok(false),
+            Some(Either::Left((ptr, _meta))) if !deref => ptr,
+            Some(Either::Left((ptr, _meta))) =>
+                this.read_pointer(&this.ptr_to_mplace(ptr, this.machine.layouts.const_raw_ptr))?,
+        };
+        // Anything unusual is left to the actual read.
+        let Ok((alloc_id, offset, _)) = this.ptr_try_get_alloc_id(ptr, 0) else {
+            return interp_ok(false);
+        };
+        let Some(alloc) = this.get_alloc_raw(alloc_id).discard_err() else {
+            return interp_ok(false);
+        };
+        if offset + size > alloc.size() {
+            return interp_ok(false);
+        }
+        interp_ok(!alloc.provenance().range_empty(alloc_range(offset, size), this))
+    }
+
     fn ptr_from_addr_cast(&self, addr: u64) -> InterpResult<'tcx, Pointer> {
         trace!("Casting {:#x} to a pointer", addr);
 
         let this = self.eval_context_ref();
         let global_state = this.machine.alloc_addresses.borrow();
 
+        if let Some(audit) = &this.machine.provenance_audit {
+            audit.borrow_mut().record_int2ptr_cast(this);
+        }
+
         // Potentially emit a warning.
         match global_state.provenance_mode {
             ProvenanceMode::Default => {
@@ -332,7 +482,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             ProvenanceMode::Strict => {
                 throw_machine_stop!(TerminationInfo::Int2PtrWithStrictProvenance);
             }
-            ProvenanceMode::Permissive => {}
+            ProvenanceMode::Permissive | ProvenanceMode::StrictTransmutes => {}
         }
 
         // We do *not* look up the `AllocId` here! This is a `ptr as usize` cast, and it is
@@ -29,7 +29,10 @@ use std::num::NonZero;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use miri::{BacktraceStyle, BorrowTrackerMethod, ProvenanceMode, RetagFields, ValidationMode};
+use miri::{
+    BacktraceStyle, BorrowTrackerMethod, EpollWakeupOrder, ProvenanceMode, RaceReportFormat,
+    RetagFields, ValidationMode, WeakMemoryModel,
+};
 use rustc_abi::ExternAbi;
 use rustc_data_structures::sync::Lrc;
 use rustc_driver::Compilation;
@@ -473,8 +476,16 @@ fn main() {
             miri_config.borrow_tracker = None;
         } else if arg == "-Zmiri-tree-borrows" {
             miri_config.borrow_tracker = Some(BorrowTrackerMethod::TreeBorrows);
+        } else if arg == "-Zmiri-compare-borrow-trackers" {
+            miri_config.compare_borrow_trackers = true;
         } else if arg == "-Zmiri-unique-is-unique" {
             miri_config.unique_is_unique = true;
+        } else if arg == "-Zmiri-not-unpin-is-unique" {
+            miri_config.not_unpin_is_unique = true;
+        } else if arg == "-Zmiri-tree-borrows-print-tree" {
+            miri_config.tree_borrows_print_tree = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-tree-borrows-dot-on-error=") {
+            miri_config.tree_borrows_dot_on_error = Some(PathBuf::from(param));
         } else if arg == "-Zmiri-disable-data-race-detector" {
             miri_config.data_race_detector = false;
             miri_config.weak_memory_emulation = false;
@@ -502,6 +513,13 @@ fn main() {
             miri_config.weak_memory_emulation = false;
         } else if arg == "-Zmiri-track-weak-memory-loads" {
             miri_config.track_outdated_loads = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-weak-memory-model=") {
+            miri_config.weak_memory_model = match param {
+                "c++" => WeakMemoryModel::Cpp,
+                "x86-tso" => WeakMemoryModel::X86Tso,
+                "arm" => WeakMemoryModel::Arm,
+                _ => show_error!("-Zmiri-weak-memory-model may only be c++, x86-tso, or arm"),
+            };
         } else if let Some(param) = arg.strip_prefix("-Zmiri-isolation-error=") {
             if matches!(isolation_enabled, Some(false)) {
                 show_error!(
@@ -529,6 +547,12 @@ fn main() {
             miri_config.provenance_mode = ProvenanceMode::Strict;
         } else if arg == "-Zmiri-permissive-provenance" {
             miri_config.provenance_mode = ProvenanceMode::Permissive;
+        } else if arg == "-Zmiri-strict-transmutes" {
+            miri_config.provenance_mode = ProvenanceMode::StrictTransmutes;
+        } else if arg == "-Zmiri-provenance-audit" {
+            miri_config.provenance_audit = true;
+        } else if arg == "-Zmiri-provenance-gc-stats" {
+            miri_config.provenance_gc_stats = true;
         } else if arg == "-Zmiri-mute-stdout-stderr" {
             miri_config.mute_stdout_stderr = true;
         } else if arg == "-Zmiri-retag-fields" {
@@ -540,6 +564,10 @@ fn main() {
                 "scalar" => RetagFields::OnlyScalar,
                 _ => show_error!("`-Zmiri-retag-fields` can only be `all`, `none`, or `scalar`"),
             };
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-retag-depth=") {
+            miri_config.retag_depth = param
+                .parse::<u32>()
+                .unwrap_or_else(|err| show_error!("-Zmiri-retag-depth requires a `u32`: {}", err));
         } else if let Some(param) = arg.strip_prefix("-Zmiri-seed=") {
             if miri_config.seed.is_some() {
                 show_error!("Cannot specify -Zmiri-seed multiple times!");
@@ -559,15 +587,44 @@ fn main() {
                 show_error!("-Zmiri-env-set requires an argument of the form <name>=<value>");
             };
             miri_config.set_env_vars.insert(name.to_owned(), value.to_owned());
-        } else if let Some(param) = arg.strip_prefix("-Zmiri-track-pointer-tag=") {
-            let ids: Vec<u64> = parse_comma_list(param).unwrap_or_else(|err| {
-                show_error!("-Zmiri-track-pointer-tag requires a comma separated list of valid `u64` arguments: {err}")
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-resolve=") {
+            let Some((name, addr)) = param.split_once('=') else {
+                show_error!("-Zmiri-resolve requires an argument of the form <name>=<address>");
+            };
+            let addr = addr.parse().unwrap_or_else(|err| {
+                show_error!("-Zmiri-resolve requires a valid IP address: {err}")
             });
-            for id in ids.into_iter().map(miri::BorTag::new) {
-                if let Some(id) = id {
-                    miri_config.tracked_pointer_tags.insert(id);
+            miri_config.resolve.push((name.to_owned(), addr));
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-command-script=") {
+            let script = std::fs::read_to_string(param).unwrap_or_else(|err| {
+                show_error!("-Zmiri-command-script: failed to read `{param}`: {err}")
+            });
+            let script = script.parse::<miri::CommandScript>().unwrap_or_else(|err| {
+                show_error!("-Zmiri-command-script: invalid command script in `{param}`: {err}")
+            });
+            miri_config.command_script = Some(script);
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-track-pointer-tag=") {
+            for item in param.split(',') {
+                if let Some((start, end)) = item.split_once("..") {
+                    let (Ok(start), Ok(end)) = (start.parse::<u64>(), end.parse::<u64>()) else {
+                        show_error!(
+                            "-Zmiri-track-pointer-tag requires ranges of the form `<start>..<end>` with valid `u64` bounds"
+                        );
+                    };
+                    if start == 0 || start >= end {
+                        show_error!(
+                            "-Zmiri-track-pointer-tag requires ranges with a nonzero start that is below the end"
+                        );
+                    }
+                    miri_config.tracked_pointer_tags.insert_range(start..end);
                 } else {
-                    show_error!("-Zmiri-track-pointer-tag requires nonzero arguments");
+                    let id = item.parse::<u64>().unwrap_or_else(|err| {
+                        show_error!("-Zmiri-track-pointer-tag requires a comma separated list of valid `u64` arguments or ranges: {err}")
+                    });
+                    let Some(id) = miri::BorTag::new(id) else {
+                        show_error!("-Zmiri-track-pointer-tag requires nonzero arguments");
+                    };
+                    miri_config.tracked_pointer_tags.insert(id);
                 }
             }
         } else if let Some(param) = arg.strip_prefix("-Zmiri-track-alloc-id=") {
@@ -575,6 +632,24 @@ fn main() {
                 show_error!("-Zmiri-track-alloc-id requires a comma separated list of valid non-zero `u64` arguments: {err}")
             });
             miri_config.tracked_alloc_ids.extend(ids.into_iter().map(miri::AllocId));
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-aliasing-warn=") {
+            let (krate, method) = match param.split_once(':') {
+                None => (param, None),
+                Some((krate, "stacked-borrows")) =>
+                    (krate, Some(BorrowTrackerMethod::StackedBorrows)),
+                Some((krate, "tree-borrows")) => (krate, Some(BorrowTrackerMethod::TreeBorrows)),
+                Some(_) =>
+                    show_error!(
+                        "-Zmiri-aliasing-warn requires `<crate>` or `<crate>:<stacked-borrows|tree-borrows>`"
+                    ),
+            };
+            miri_config.aliasing_warnings.push((krate.to_owned(), method));
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-borrow-log=") {
+            miri_config.borrow_log = Some(PathBuf::from(param));
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-track-only-crate=") {
+            miri_config.retag_filter_crates.extend(param.split(',').map(String::from));
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-retag-filter=") {
+            miri_config.retag_filter_types.extend(param.split(',').map(String::from));
         } else if arg == "-Zmiri-track-alloc-accesses" {
             miri_config.track_alloc_accesses = true;
         } else if let Some(param) = arg.strip_prefix("-Zmiri-address-reuse-rate=") {
@@ -587,9 +662,103 @@ fn main() {
             miri_config.cmpxchg_weak_failure_rate = parse_rate(param).unwrap_or_else(|err| {
                 show_error!("-Zmiri-compare-exchange-weak-failure-rate {err}")
             });
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-rdrand-failure-rate=") {
+            miri_config.rdrand_failure_rate = parse_rate(param)
+                .unwrap_or_else(|err| show_error!("-Zmiri-rdrand-failure-rate {err}"));
         } else if let Some(param) = arg.strip_prefix("-Zmiri-preemption-rate=") {
             miri_config.preemption_rate =
                 parse_rate(param).unwrap_or_else(|err| show_error!("-Zmiri-preemption-rate {err}"));
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-spurious-wakeup-rate=") {
+            miri_config.spurious_wakeup_rate = parse_rate(param)
+                .unwrap_or_else(|err| show_error!("-Zmiri-spurious-wakeup-rate {err}"));
+        } else if arg == "-Zmiri-exhaustive-schedules" {
+            miri_config.exhaustive_schedules = Some(2);
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-exhaustive-schedules=") {
+            let bound = param.parse::<u32>().unwrap_or_else(|err| {
+                show_error!("-Zmiri-exhaustive-schedules requires a `u32`: {}", err)
+            });
+            miri_config.exhaustive_schedules = Some(bound);
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-record-schedule=") {
+            miri_config.record_schedule = Some(PathBuf::from(param));
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-replay-schedule=") {
+            let trace = std::fs::read_to_string(param).unwrap_or_else(|err| {
+                show_error!("-Zmiri-replay-schedule: failed to read `{param}`: {err}")
+            });
+            let trace = trace.parse::<miri::ScheduleTrace>().unwrap_or_else(|err| {
+                show_error!("-Zmiri-replay-schedule: invalid schedule trace in `{param}`: {err}")
+            });
+            miri_config.replay_schedule = Some(trace);
+        } else if arg == "-Zmiri-minimize-schedule" {
+            miri_config.minimize_schedule = true;
+        } else if arg == "-Zmiri-disable-partial-order-reduction" {
+            miri_config.partial_order_reduction = false;
+        } else if arg == "-Zmiri-exhaustive-weak-memory" {
+            miri_config.exhaustive_weak_memory = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-epoll-wakeup-order=") {
+            miri_config.epoll_wakeup_order = match param {
+                "fixed" => EpollWakeupOrder::Fixed,
+                "random" => EpollWakeupOrder::Random,
+                "explore" => EpollWakeupOrder::Explore,
+                _ => show_error!("-Zmiri-epoll-wakeup-order may only be fixed, random, or explore"),
+            };
+        } else if arg == "-Zmiri-report-thread-leaks" {
+            miri_config.report_thread_leaks = true;
+        } else if arg == "-Zmiri-detect-blocking-in-async" {
+            miri_config.detect_blocking_in_async = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-race-report-format=") {
+            miri_config.race_report_format = match param {
+                "miri" => RaceReportFormat::Miri,
+                "tsan" => RaceReportFormat::Tsan,
+                "json" => RaceReportFormat::Json,
+                _ => show_error!("-Zmiri-race-report-format may only be miri, tsan, or json"),
+            };
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-race-suppressions=") {
+            let suppressions = std::fs::read_to_string(param).unwrap_or_else(|err| {
+                show_error!("-Zmiri-race-suppressions: failed to read `{param}`: {err}")
+            });
+            let suppressions =
+                suppressions.parse::<miri::RaceSuppressions>().unwrap_or_else(|err| {
+                    show_error!(
+                        "-Zmiri-race-suppressions: invalid suppression file `{param}`: {err}"
+                    )
+                });
+            miri_config.race_suppressions = Some(suppressions);
+        } else if arg == "-Zmiri-race-timeline" {
+            miri_config.race_timeline = true;
+        } else if arg == "-Zmiri-race-witness" {
+            miri_config.race_witness = true;
+        } else if arg == "-Zmiri-deterministic-floats" {
+            miri_config.deterministic_floats = true;
+        } else if arg == "-Zmiri-spin-loop-warnings" {
+            miri_config.spin_loop_warnings = true;
+        } else if arg == "-Zmiri-mixed-access-warnings" {
+            miri_config.mixed_access_warnings = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-race-granularity=") {
+            let (granularity, min_size) = match param.split_once(':') {
+                Some((granularity, min_size)) => (granularity, Some(min_size)),
+                None => (param, None),
+            };
+            let granularity = match granularity.parse::<u64>() {
+                Ok(granularity) if granularity.is_power_of_two() => granularity,
+                _ =>
+                    show_error!(
+                        "-Zmiri-race-granularity requires a power of two as the granularity"
+                    ),
+            };
+            let min_size = match min_size.map(str::parse::<u64>) {
+                // By default, only allocations of at least a page are tracked coarsely.
+                None => 4096,
+                Some(Ok(min_size)) => min_size,
+                Some(Err(err)) =>
+                    show_error!(
+                        "-Zmiri-race-granularity requires a `u64` as the minimum allocation size: {err}"
+                    ),
+            };
+            miri_config.race_granularity = Some((granularity, min_size));
+        } else if arg == "-Zmiri-preempt-on-atomics" {
+            miri_config.preempt_on_atomics = true;
+        } else if arg == "-Zmiri-priority-scheduling" {
+            miri_config.priority_scheduling = true;
         } else if arg == "-Zmiri-report-progress" {
             // This makes it take a few seconds between progress reports on my laptop.
             miri_config.report_progress = Some(1_000_000);
@@ -630,6 +799,19 @@ fn main() {
                 show_error!("-Zmiri-num-cpus must be in the range 1..={}", miri::MAX_CPUS);
             }
             miri_config.num_cpus = num_cpus;
+        } else if arg == "-Zmiri-virtual-clock" {
+            miri_config.virtual_clock = true;
+        } else if arg == "-Zmiri-virtual-time" {
+            miri_config.virtual_clock = true;
+            miri_config.virtual_time = true;
+        } else if let Some(param) = arg.strip_prefix("-Zmiri-qpc-frequency=") {
+            let qpc_frequency = param.parse::<u64>().unwrap_or_else(|err| {
+                show_error!("-Zmiri-qpc-frequency requires a `u64`: {}", err)
+            });
+            if qpc_frequency == 0 || i64::try_from(qpc_frequency).is_err() {
+                show_error!("-Zmiri-qpc-frequency must be in the range 1..={}", i64::MAX);
+            }
+            miri_config.qpc_frequency = qpc_frequency;
         } else if let Some(param) = arg.strip_prefix("-Zmiri-force-page-size=") {
             let page_size = param.parse::<u64>().unwrap_or_else(|err| {
                 show_error!("-Zmiri-force-page-size requires a `u64`: {}", err)
@@ -654,15 +836,98 @@ fn main() {
             "-Zmiri-unique-is-unique only has an effect when -Zmiri-tree-borrows is also used"
         );
     }
+    // `-Zmiri-not-unpin-is-unique` should only be used with `-Zmiri-tree-borrows`
+    if miri_config.not_unpin_is_unique
+        && !matches!(miri_config.borrow_tracker, Some(BorrowTrackerMethod::TreeBorrows))
+    {
+        show_error!(
+            "-Zmiri-not-unpin-is-unique only has an effect when -Zmiri-tree-borrows is also used"
+        );
+    }
+    // `-Zmiri-tree-borrows-print-tree` should only be used with `-Zmiri-tree-borrows`
+    if miri_config.tree_borrows_print_tree
+        && !matches!(miri_config.borrow_tracker, Some(BorrowTrackerMethod::TreeBorrows))
+    {
+        show_error!(
+            "-Zmiri-tree-borrows-print-tree only has an effect when -Zmiri-tree-borrows is also used"
+        );
+    }
+    // `-Zmiri-tree-borrows-dot-on-error` should only be used with `-Zmiri-tree-borrows`
+    if miri_config.tree_borrows_dot_on_error.is_some()
+        && !matches!(miri_config.borrow_tracker, Some(BorrowTrackerMethod::TreeBorrows))
+    {
+        show_error!(
+            "-Zmiri-tree-borrows-dot-on-error only has an effect when -Zmiri-tree-borrows is also used"
+        );
+    }
+    // `-Zmiri-compare-borrow-trackers` needs a borrow tracker, and Tree Borrows does not work
+    // with permissive provenance. Running the program twice is only invisible to the host with
+    // isolation.
+    if miri_config.compare_borrow_trackers {
+        if miri_config.isolated_op == miri::IsolatedOp::Allow {
+            show_error!(
+                "-Zmiri-compare-borrow-trackers requires isolation, since the second run would repeat the program's effects on the host"
+            );
+        }
+        if miri_config.borrow_tracker.is_none() {
+            show_error!(
+                "-Zmiri-compare-borrow-trackers cannot be used together with -Zmiri-disable-stacked-borrows"
+            );
+        }
+        if matches!(
+            miri_config.provenance_mode,
+            ProvenanceMode::Permissive | ProvenanceMode::StrictTransmutes
+        ) {
+            show_error!(
+                "-Zmiri-compare-borrow-trackers is not compatible with permissive provenance, since Tree Borrows does not support integer-to-pointer casts"
+            );
+        }
+    }
     // Tree Borrows + permissive provenance does not work.
-    if miri_config.provenance_mode == ProvenanceMode::Permissive
-        && matches!(miri_config.borrow_tracker, Some(BorrowTrackerMethod::TreeBorrows))
+    if matches!(
+        miri_config.provenance_mode,
+        ProvenanceMode::Permissive | ProvenanceMode::StrictTransmutes
+    ) && matches!(miri_config.borrow_tracker, Some(BorrowTrackerMethod::TreeBorrows))
     {
         show_error!(
             "Tree Borrows does not support integer-to-pointer casts, and is hence not compatible with permissive provenance"
         );
     }
 
+    // Following a schedule trace and exploring schedules both take over the scheduler.
+    if miri_config.replay_schedule.is_some() && miri_config.exhaustive_schedules.is_some() {
+        show_error!("-Zmiri-replay-schedule cannot be used along with -Zmiri-exhaustive-schedules");
+    }
+
+    // Exploring weak memory behaviors is part of schedule exploration.
+    if miri_config.exhaustive_weak_memory {
+        if !miri_config.weak_memory_emulation {
+            show_error!(
+                "-Zmiri-exhaustive-weak-memory cannot be used along with -Zmiri-disable-weak-memory-emulation"
+            );
+        }
+        if miri_config.replay_schedule.is_some() {
+            show_error!(
+                "-Zmiri-exhaustive-weak-memory cannot be used along with -Zmiri-replay-schedule"
+            );
+        }
+        if miri_config.exhaustive_schedules.is_none() {
+            miri_config.exhaustive_schedules = Some(2);
+        }
+    }
+
+    // Exploring epoll wakeup orders is part of schedule exploration as well.
+    if miri_config.epoll_wakeup_order == EpollWakeupOrder::Explore {
+        if miri_config.replay_schedule.is_some() {
+            show_error!(
+                "-Zmiri-epoll-wakeup-order=explore cannot be used along with -Zmiri-replay-schedule"
+            );
+        }
+        if miri_config.exhaustive_schedules.is_none() {
+            miri_config.exhaustive_schedules = Some(2);
+        }
+    }
+
     debug!("rustc arguments: {:?}", rustc_args);
     debug!("crate arguments: {:?}", miri_config.args);
     run_compiler(
@@ -0,0 +1,145 @@
+//! A structured log of borrow tracker events, see `-Zmiri-borrow-log`.
+//! Every event is written as one JSON object per line, so that external tools can
+//! reconstruct and visualize the lifetime of pointers.
+
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, LineWriter, Write as _};
+use std::path::Path;
+
+use rustc_abi::Size;
+
+use crate::borrow_tracker::{BorTag, ProtectorKind};
+use crate::diagnostics::json_string;
+use crate::*;
+
+/// A borrow tracker event that can be logged.
+pub enum BorrowEvent<'a> {
+    /// `tag` was created by a retag of `parent`.
+    Retag {
+        tag: BorTag,
+        parent: Option<BorTag>,
+        alloc_id: Option<AllocId>,
+        range: Option<AllocRange>,
+        perm: &'a str,
+        pointee: &'a str,
+    },
+    /// A read or write access through `tag`.
+    Access { tag: BorTag, alloc_id: AllocId, range: AllocRange, kind: AccessKind },
+    /// A deallocation through `tag`.
+    Dealloc { tag: BorTag, alloc_id: AllocId, size: Size },
+    /// `tag` is protected until the current function returns.
+    Protect { tag: BorTag, alloc_id: AllocId, kind: ProtectorKind },
+    /// The function call that protected `tag` returned.
+    Unprotect { tag: BorTag, alloc_id: AllocId },
+}
+
+impl BorrowEvent<'_> {
+    pub fn tag(&self) -> BorTag {
+        match *self {
+            BorrowEvent::Retag { tag, .. }
+            | BorrowEvent::Access { tag, .. }
+            | BorrowEvent::Dealloc { tag, .. }
+            | BorrowEvent::Protect { tag, .. }
+            | BorrowEvent::Unprotect { tag, .. } => tag,
+        }
+    }
+}
+
+/// The file the events are streamed to. Every line is flushed immediately so that the
+/// log is complete even if the interpreted program aborts.
+#[derive(Debug)]
+pub struct BorrowLog {
+    out: RefCell<LineWriter<File>>,
+}
+
+impl BorrowLog {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(BorrowLog { out: RefCell::new(LineWriter::new(File::create(path)?)) })
+    }
+
+    pub fn log(&self, machine: &MiriMachine<'_>, event: BorrowEvent<'_>) {
+        let mut line = String::new();
+        let range_json = |line: &mut String, range: AllocRange| {
+            write!(line, ",\"offset\":{},\"size\":{}", range.start.bytes(), range.size.bytes())
+                .unwrap();
+        };
+        match event {
+            BorrowEvent::Retag { tag, parent, alloc_id, range, perm, pointee } => {
+                write!(line, "{{\"event\":\"retag\",\"tag\":{}", tag.get()).unwrap();
+                if let Some(parent) = parent {
+                    write!(line, ",\"parent\":{}", parent.get()).unwrap();
+                }
+                if let Some(alloc_id) = alloc_id {
+                    write!(line, ",\"alloc\":{}", alloc_id.0).unwrap();
+                }
+                if let Some(range) = range {
+                    range_json(&mut line, range);
+                }
+                write!(
+                    line,
+                    ",\"perm\":{},\"pointee\":{}",
+                    json_string(perm),
+                    json_string(pointee)
+                )
+                .unwrap();
+            }
+            BorrowEvent::Access { tag, alloc_id, range, kind } => {
+                let kind = match kind {
+                    AccessKind::Read => "read",
+                    AccessKind::Write => "write",
+                };
+                write!(
+                    line,
+                    "{{\"event\":\"{kind}\",\"tag\":{},\"alloc\":{}",
+                    tag.get(),
+                    alloc_id.0
+                )
+                .unwrap();
+                range_json(&mut line, range);
+            }
+            BorrowEvent::Dealloc { tag, alloc_id, size } => {
+                write!(
+                    line,
+                    "{{\"event\":\"dealloc\",\"tag\":{},\"alloc\":{},\"size\":{}",
+                    tag.get(),
+                    alloc_id.0,
+                    size.bytes()
+                )
+                .unwrap();
+            }
+            BorrowEvent::Protect { tag, alloc_id, kind } => {
+                let kind = match kind {
+                    ProtectorKind::WeakProtector => "weak",
+                    ProtectorKind::StrongProtector => "strong",
+                };
+                write!(
+                    line,
+                    "{{\"event\":\"protect\",\"tag\":{},\"alloc\":{},\"kind\":\"{kind}\"",
+                    tag.get(),
+                    alloc_id.0
+                )
+                .unwrap();
+            }
+            BorrowEvent::Unprotect { tag, alloc_id } => {
+                write!(
+                    line,
+                    "{{\"event\":\"unprotect\",\"tag\":{},\"alloc\":{}",
+                    tag.get(),
+                    alloc_id.0
+                )
+                .unwrap();
+            }
+        }
+        let span = machine.tcx.sess.source_map().span_to_diagnostic_string(machine.current_span());
+        writeln!(
+            line,
+            ",\"thread\":{},\"span\":{}}}",
+            machine.threads.active_thread().to_u32(),
+            json_string(&span)
+        )
+        .unwrap();
+        self.out.borrow_mut().write_all(line.as_bytes()).expect("failed to write the borrow log");
+    }
+}
@@ -1,13 +1,23 @@
 use std::cell::RefCell;
 use std::fmt;
 use std::num::NonZero;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
 
 use rustc_abi::Size;
-use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_data_structures::fx::{FxHashMap, FxHashSet, FxIndexMap};
 use rustc_middle::mir::RetagKind;
+use rustc_middle::ty::{self, Ty};
+use rustc_span::def_id::DefId;
+use rustc_span::{Span, Symbol, sym};
 use smallvec::SmallVec;
 
+use self::event_log::{BorrowEvent, BorrowLog};
+use self::model::{AliasingModel, AllocAliasingState};
 use crate::*;
+pub mod event_log;
+pub mod model;
 pub mod stacked_borrows;
 pub mod tree_borrows;
 
@@ -87,6 +97,8 @@ impl VisitProvenance for FrameState {
 pub struct GlobalStateInner {
     /// Borrow tracker method currently in use.
     borrow_tracker_method: BorrowTrackerMethod,
+    /// The implementation of that method.
+    model: Rc<dyn AliasingModel>,
     /// Next unused pointer ID (tag).
     next_ptr_tag: BorTag,
     /// Table storing the "root" tag for each allocation.
@@ -99,11 +111,29 @@ pub struct GlobalStateInner {
     /// `GlobalStateInner::end_call`. See `Stack::item_invalidated` for more details.
     protected_tags: FxHashMap<BorTag, ProtectorKind>,
     /// The pointer ids to trace
-    tracked_pointer_tags: FxHashSet<BorTag>,
+    tracked_pointer_tags: TrackedTags,
     /// Whether to recurse into datatypes when searching for pointers to retag.
     retag_fields: RetagFields,
+    /// How many levels of mutable references and boxes to follow when retagging a place.
+    retag_depth: u32,
     /// Whether `core::ptr::Unique` gets special (`Box`-like) handling.
     unique_is_unique: bool,
+    /// Whether `&mut` to `!Unpin` types gets the same uniqueness assumptions as other `&mut`.
+    not_unpin_is_unique: bool,
+    /// Which retags actually happen.
+    retag_filter: RetagFilter,
+    /// Cache of whether a function is marked `#[miri::no_borrow_tracking]`.
+    untracked_fns: FxHashMap<DefId, bool>,
+    /// Where to stream the events of the tracked tags to, if anywhere.
+    borrow_log: Option<BorrowLog>,
+    /// Crates whose aliasing violations are only reported as warnings, optionally restricted to
+    /// the violations of one borrow tracker.
+    aliasing_warnings: Vec<(String, Option<BorrowTrackerMethod>)>,
+    /// The aliasing violations that were reported as warnings, grouped by `AliasingWarningKey`,
+    /// with how often each of them happened.
+    aliasing_warning_counts: FxIndexMap<AliasingWarningKey, u64>,
+    /// The borrow states captured by `miri_snapshot_borrows`, indexed by snapshot ID minus 1.
+    borrow_snapshots: Vec<(AllocId, Vec<String>)>,
 }
 
 impl VisitProvenance for GlobalStateInner {
@@ -114,6 +144,19 @@ impl VisitProvenance for GlobalStateInner {
     }
 }
 
+/// Aliasing violations reported by `-Zmiri-aliasing-warn` are considered the same if they agree
+/// in all of these. Only the first violation of each group is reported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct AliasingWarningKey {
+    method: BorrowTrackerMethod,
+    /// Whether a protector was violated.
+    protector: bool,
+    /// Where the affected allocation was created, if known.
+    allocated_at: Option<Span>,
+    /// Where the violation happened.
+    accessed_at: Span,
+}
+
 /// We need interior mutable access to the global state.
 pub type GlobalState = RefCell<GlobalStateInner>;
 
@@ -126,6 +169,55 @@ impl fmt::Display for AccessKind {
     }
 }
 
+/// The pointer tags to report about, see `-Zmiri-track-pointer-tag`.
+/// Tags that are created by retagging a tracked tag are tracked as well.
+#[derive(Clone, Debug, Default)]
+pub struct TrackedTags {
+    tags: FxHashSet<BorTag>,
+    ranges: Vec<Range<u64>>,
+}
+
+impl TrackedTags {
+    pub fn insert(&mut self, tag: BorTag) {
+        self.tags.insert(tag);
+    }
+
+    pub fn insert_range(&mut self, range: Range<u64>) {
+        self.ranges.push(range);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, tag: &BorTag) -> bool {
+        self.tags.contains(tag) || self.ranges.iter().any(|range| range.contains(&tag.get()))
+    }
+}
+
+/// Restricts retagging to some crates and types, so that all other references
+/// are treated like raw pointers.
+#[derive(Debug)]
+pub struct RetagFilter {
+    /// Only retag in functions defined in one of these crates; empty means all crates.
+    crates: Vec<String>,
+    /// Only retag values whose type mentions an ADT whose path contains one of these
+    /// strings; empty means all types.
+    types: Vec<String>,
+    /// Cache of whether the path of an ADT matches `types`.
+    matching_adts: FxHashMap<DefId, bool>,
+}
+
+impl RetagFilter {
+    pub fn new(crates: Vec<String>, types: Vec<String>) -> Self {
+        RetagFilter { crates, types, matching_adts: FxHashMap::default() }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.crates.is_empty() && self.types.is_empty()
+    }
+}
+
 /// Policy on whether to recurse into fields to retag
 #[derive(Copy, Clone, Debug)]
 pub enum RetagFields {
@@ -138,6 +230,10 @@ pub enum RetagFields {
     OnlyScalar,
 }
 
+/// With `-Zmiri-retag-depth`, arrays and slices behind a retagged reference are only searched
+/// for references to retag if they have at most this many elements.
+const MAX_RETAG_DEPTH_ARRAY_LEN: u64 = 64;
+
 /// The flavor of the protector.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ProtectorKind {
@@ -162,18 +258,32 @@ pub enum ProtectorKind {
 impl GlobalStateInner {
     pub fn new(
         borrow_tracker_method: BorrowTrackerMethod,
-        tracked_pointer_tags: FxHashSet<BorTag>,
+        tracked_pointer_tags: TrackedTags,
         retag_fields: RetagFields,
+        retag_depth: u32,
         unique_is_unique: bool,
+        not_unpin_is_unique: bool,
+        retag_filter: RetagFilter,
+        borrow_log: Option<BorrowLog>,
+        aliasing_warnings: Vec<(String, Option<BorrowTrackerMethod>)>,
     ) -> Self {
         GlobalStateInner {
             borrow_tracker_method,
+            model: borrow_tracker_method.model(),
             next_ptr_tag: BorTag::one(),
             root_ptr_tags: FxHashMap::default(),
             protected_tags: FxHashMap::default(),
             tracked_pointer_tags,
             retag_fields,
+            retag_depth,
             unique_is_unique,
+            not_unpin_is_unique,
+            retag_filter,
+            untracked_fns: FxHashMap::default(),
+            borrow_log,
+            aliasing_warnings,
+            aliasing_warning_counts: FxIndexMap::default(),
+            borrow_snapshots: Vec::new(),
         }
     }
 
@@ -222,10 +332,46 @@ impl GlobalStateInner {
     pub fn borrow_tracker_method(&self) -> BorrowTrackerMethod {
         self.borrow_tracker_method
     }
+
+    /// For every group of aliasing violations that were reported as warnings, where the first of
+    /// them happened and how many there were in total.
+    pub fn aliasing_warning_counts(&self) -> impl Iterator<Item = (Span, u64)> + '_ {
+        self.aliasing_warning_counts.iter().map(|(key, count)| (key.accessed_at, *count))
+    }
+
+    /// The aliasing model the machine hooks are dispatched to. The model is reference-counted
+    /// so that it can be called while the global state is borrowed again.
+    pub fn model(&self) -> Rc<dyn AliasingModel> {
+        Rc::clone(&self.model)
+    }
+
+    /// `new_tag` was created by a retag of `orig_tag`: if the latter is tracked,
+    /// start tracking the former as well.
+    pub fn track_derived_tag(&mut self, orig_tag: ProvenanceExtra, new_tag: BorTag) {
+        if let ProvenanceExtra::Concrete(orig_tag) = orig_tag
+            && self.tracked_pointer_tags.contains(&orig_tag)
+        {
+            self.tracked_pointer_tags.insert(new_tag);
+        }
+    }
+
+    /// Whether events of `tag` go to the borrow log: if any tags are tracked,
+    /// only their events are logged.
+    pub fn logs_tag(&self, tag: BorTag) -> bool {
+        self.borrow_log.is_some()
+            && (self.tracked_pointer_tags.is_empty() || self.tracked_pointer_tags.contains(&tag))
+    }
+
+    /// Write `event` to the borrow log, if its tag is logged.
+    pub fn log_event(&self, machine: &MiriMachine<'_>, event: BorrowEvent<'_>) {
+        if self.logs_tag(event.tag()) {
+            self.borrow_log.as_ref().unwrap().log(machine, event);
+        }
+    }
 }
 
 /// Which borrow tracking method to use
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum BorrowTrackerMethod {
     /// Stacked Borrows, as implemented in borrow_tracker/stacked_borrows
     StackedBorrows,
@@ -234,12 +380,35 @@ pub enum BorrowTrackerMethod {
 }
 
 impl BorrowTrackerMethod {
+    /// The aliasing model implementing this method.
+    /// This is the only place where a new model has to be registered.
+    fn model(self) -> Rc<dyn AliasingModel> {
+        match self {
+            BorrowTrackerMethod::StackedBorrows => Rc::new(stacked_borrows::StackedBorrows),
+            BorrowTrackerMethod::TreeBorrows => Rc::new(tree_borrows::TreeBorrows),
+        }
+    }
+
+    /// The human-readable name of this method, used in diagnostics.
+    pub fn name(self) -> &'static str {
+        self.model().name()
+    }
+
     pub fn instantiate_global_state(self, config: &MiriConfig) -> GlobalState {
         RefCell::new(GlobalStateInner::new(
             self,
             config.tracked_pointer_tags.clone(),
             config.retag_fields,
+            config.retag_depth,
             config.unique_is_unique,
+            config.not_unpin_is_unique,
+            RetagFilter::new(config.retag_filter_crates.clone(), config.retag_filter_types.clone()),
+            config.borrow_log.as_ref().map(|path| {
+                BorrowLog::create(path).unwrap_or_else(|err| {
+                    panic!("failed to create the borrow log `{}`: {err}", path.display())
+                })
+            }),
+            config.aliasing_warnings.clone(),
         ))
     }
 }
@@ -252,17 +421,119 @@ impl GlobalStateInner {
         kind: MemoryKind,
         machine: &MiriMachine<'_>,
     ) -> AllocState {
-        match self.borrow_tracker_method {
-            BorrowTrackerMethod::StackedBorrows =>
-                AllocState::StackedBorrows(Box::new(RefCell::new(Stacks::new_allocation(
-                    id, alloc_size, self, kind, machine,
-                )))),
-            BorrowTrackerMethod::TreeBorrows =>
-                AllocState::TreeBorrows(Box::new(RefCell::new(Tree::new_allocation(
-                    id, alloc_size, self, kind, machine,
-                )))),
+        let model = self.model();
+        AllocState(model.new_allocation(id, alloc_size, self, kind, machine))
+    }
+}
+
+/// For protector violations: describe the calls from the one whose protector was violated down
+/// to the current one. Returns `None` if the access happened directly in the protecting call,
+/// or if that call is not on the stack of the active thread.
+pub fn protector_call_path(machine: &MiriMachine<'_>, tag: BorTag) -> Option<String> {
+    let stack = machine.threads.active_thread_stack();
+    let protecting = stack.iter().rposition(|frame| {
+        frame.extra.borrow_tracker.as_ref().is_some_and(|state| {
+            state.protected_tags.iter().any(|&(_, protected)| protected == tag)
+        })
+    })?;
+    if protecting + 1 == stack.len() {
+        return None;
+    }
+    let path = stack[protecting..]
+        .iter()
+        .map(|frame| format!("`{}`", frame.instance()))
+        .collect::<Vec<_>>()
+        .join(" -> ");
+    Some(format!(
+        "{tag:?} stays protected until the call to `{}` returns, which led to this access via {path}",
+        stack[protecting].instance()
+    ))
+}
+
+/// A line-based diff from `old` to `new`, based on their longest common subsequence:
+/// removed lines start with `-`, added lines with `+`, and unchanged lines with a space.
+fn diff_lines(old: &[String], new: &[String]) -> Vec<String> {
+    // `lcs[i][j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", new[j]));
+            j += 1;
         }
     }
+    diff
+}
+
+/// Turn an aliasing violation into a warning if `-Zmiri-aliasing-warn` asks for this for the
+/// crate it happened in, and continue with `fallback()`. Any other error is passed through.
+///
+/// The violation is attributed to `MiriMachine::responsible_crate`.
+fn downgrade_aliasing_error<'tcx, T>(
+    machine: &MiriMachine<'tcx>,
+    res: InterpResult<'tcx, T>,
+    fallback: impl FnOnce() -> T,
+) -> InterpResult<'tcx, T> {
+    let err = match res.report_err() {
+        Ok(val) => return interp_ok(val),
+        Err(err) => err,
+    };
+    let Some(borrow_tracker) = &machine.borrow_tracker else { return Err(err).into() };
+    let InterpErrorKind::MachineStop(info) = err.kind() else { return Err(err).into() };
+    let Some(info) = info.downcast_ref::<TerminationInfo>() else { return Err(err).into() };
+    let (method, alloc_id, protector) = match info {
+        TerminationInfo::StackedBorrowsUb { alloc_id, protected_tag, .. } =>
+            (BorrowTrackerMethod::StackedBorrows, *alloc_id, protected_tag.is_some()),
+        TerminationInfo::TreeBorrowsUb { alloc_id, protected_tag, .. } =>
+            (BorrowTrackerMethod::TreeBorrows, *alloc_id, protected_tag.is_some()),
+        _ => return Err(err).into(),
+    };
+    let Some(krate) = machine.responsible_crate().map(|krate| machine.tcx.crate_name(krate)) else {
+        return Err(err).into();
+    };
+    let mut global = borrow_tracker.borrow_mut();
+    let downgrade = global
+        .aliasing_warnings
+        .iter()
+        .any(|(name, only)| name == krate.as_str() && only.is_none_or(|only| only == method));
+    if !downgrade {
+        return Err(err).into();
+    }
+    // The same violation tends to happen over and over again, so we only warn the first time
+    // and count the rest.
+    let key = AliasingWarningKey {
+        method,
+        protector,
+        allocated_at: machine.allocated_span(alloc_id).map(|span| span.span()),
+        accessed_at: machine.current_span(),
+    };
+    let count = global.aliasing_warning_counts.entry(key).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        drop(global);
+        machine.emit_diagnostic(NonHaltingDiagnostic::DowngradedAliasingError {
+            msg: info.to_string(),
+            krate: krate.to_string(),
+        });
+    }
+    interp_ok(fallback())
 }
 
 impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
@@ -273,11 +544,13 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         val: &ImmTy<'tcx>,
     ) -> InterpResult<'tcx, ImmTy<'tcx>> {
         let this = self.eval_context_mut();
-        let method = this.machine.borrow_tracker.as_ref().unwrap().borrow().borrow_tracker_method;
-        match method {
-            BorrowTrackerMethod::StackedBorrows => this.sb_retag_ptr_value(kind, val),
-            BorrowTrackerMethod::TreeBorrows => this.tb_retag_ptr_value(kind, val),
+        if !this.retag_filter_allows(val.layout.ty) {
+            return interp_ok(val.clone());
         }
+        let model = this.machine.borrow_tracker.as_ref().unwrap().borrow().model();
+        let res = model.retag_ptr_value(this, kind, val);
+        // If the violation is downgraded, the pointer keeps its old tag.
+        downgrade_aliasing_error(&this.machine, res, || val.clone())
     }
 
     fn retag_place_contents(
@@ -286,29 +559,64 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         place: &PlaceTy<'tcx>,
     ) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
-        let method = this.machine.borrow_tracker.as_ref().unwrap().borrow().borrow_tracker_method;
-        match method {
-            BorrowTrackerMethod::StackedBorrows => this.sb_retag_place_contents(kind, place),
-            BorrowTrackerMethod::TreeBorrows => this.tb_retag_place_contents(kind, place),
+        if !this.retag_filter_allows(place.layout.ty) {
+            return interp_ok(());
+        }
+        let model = this.machine.borrow_tracker.as_ref().unwrap().borrow().model();
+        let res = model.retag_place_contents(this, kind, place);
+        downgrade_aliasing_error(&this.machine, res, || ())
+    }
+
+    /// Whether a value of type `ty` should be retagged in the current function,
+    /// according to `#[miri::no_borrow_tracking]`, `-Zmiri-track-only-crate` and
+    /// `-Zmiri-retag-filter`.
+    fn retag_filter_allows(&self, ty: Ty<'tcx>) -> bool {
+        let this = self.eval_context_ref();
+        let mut global = this.machine.borrow_tracker.as_ref().unwrap().borrow_mut();
+        // Closures are covered by the attribute of the function they are defined in.
+        let def_id = this.tcx.typeck_root_def_id(this.frame().instance().def_id());
+        let untracked = *global.untracked_fns.entry(def_id).or_insert_with(|| {
+            let no_borrow_tracking = Symbol::intern("no_borrow_tracking");
+            this.tcx.get_attrs_by_path(def_id, &[sym::miri, no_borrow_tracking]).next().is_some()
+        });
+        if untracked {
+            return false;
         }
+        let filter = &mut global.retag_filter;
+        if filter.is_empty() {
+            return true;
+        }
+        if !filter.crates.is_empty() {
+            let krate = this.frame().instance().def_id().krate;
+            let crate_name = this.tcx.crate_name(krate);
+            if !filter.crates.iter().any(|name| name == crate_name.as_str()) {
+                return false;
+            }
+        }
+        if !filter.types.is_empty() {
+            let tcx = *this.tcx;
+            return ty.walk().any(|arg| {
+                let ty::GenericArgKind::Type(ty) = arg.unpack() else { return false };
+                let ty::Adt(adt, _) = ty.kind() else { return false };
+                *filter.matching_adts.entry(adt.did()).or_insert_with(|| {
+                    let path = tcx.def_path_str(adt.did());
+                    filter.types.iter().any(|pattern| path.contains(pattern.as_str()))
+                })
+            });
+        }
+        true
     }
 
     fn protect_place(&mut self, place: &MPlaceTy<'tcx>) -> InterpResult<'tcx, MPlaceTy<'tcx>> {
         let this = self.eval_context_mut();
-        let method = this.machine.borrow_tracker.as_ref().unwrap().borrow().borrow_tracker_method;
-        match method {
-            BorrowTrackerMethod::StackedBorrows => this.sb_protect_place(place),
-            BorrowTrackerMethod::TreeBorrows => this.tb_protect_place(place),
-        }
+        let model = this.machine.borrow_tracker.as_ref().unwrap().borrow().model();
+        model.protect_place(this, place)
     }
 
     fn expose_tag(&mut self, alloc_id: AllocId, tag: BorTag) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
-        let method = this.machine.borrow_tracker.as_ref().unwrap().borrow().borrow_tracker_method;
-        match method {
-            BorrowTrackerMethod::StackedBorrows => this.sb_expose_tag(alloc_id, tag),
-            BorrowTrackerMethod::TreeBorrows => this.tb_expose_tag(alloc_id, tag),
-        }
+        let model = this.machine.borrow_tracker.as_ref().unwrap().borrow().model();
+        model.expose_tag(this, alloc_id, tag)
     }
 
     fn give_pointer_debug_name(
@@ -318,15 +626,8 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         name: &str,
     ) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
-        let method = this.machine.borrow_tracker.as_ref().unwrap().borrow().borrow_tracker_method;
-        match method {
-            BorrowTrackerMethod::StackedBorrows => {
-                this.tcx.tcx.dcx().warn("Stacked Borrows does not support named pointers; `miri_pointer_name` is a no-op");
-                interp_ok(())
-            }
-            BorrowTrackerMethod::TreeBorrows =>
-                this.tb_give_pointer_debug_name(ptr, nth_parent, name),
-        }
+        let model = this.machine.borrow_tracker.as_ref().unwrap().borrow().model();
+        model.give_pointer_debug_name(this, ptr, nth_parent, name)
     }
 
     fn print_borrow_state(&mut self, alloc_id: AllocId, show_unnamed: bool) -> InterpResult<'tcx> {
@@ -335,11 +636,81 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             eprintln!("attempted to print borrow state, but no borrow state is being tracked");
             return interp_ok(());
         };
-        let method = borrow_tracker.borrow().borrow_tracker_method;
-        match method {
-            BorrowTrackerMethod::StackedBorrows => this.print_stacks(alloc_id),
-            BorrowTrackerMethod::TreeBorrows => this.print_tree(alloc_id, show_unnamed),
+        let model = borrow_tracker.borrow().model();
+        model.print_borrow_state(this, alloc_id, show_unnamed)
+    }
+
+    /// Capture the borrow state of `alloc_id` and return the ID of the snapshot,
+    /// for `miri_snapshot_borrows`. Returns 0 if no borrow state is being tracked.
+    fn snapshot_borrow_state(&mut self, alloc_id: AllocId) -> InterpResult<'tcx, u64> {
+        let this = self.eval_context_mut();
+        let Some(borrow_tracker) = &this.machine.borrow_tracker else {
+            eprintln!("attempted to snapshot borrow state, but no borrow state is being tracked");
+            return interp_ok(0);
+        };
+        let model = borrow_tracker.borrow().model();
+        let lines = model.borrow_state_lines(this, alloc_id)?;
+        let mut global = this.machine.borrow_tracker.as_ref().unwrap().borrow_mut();
+        global.borrow_snapshots.push((alloc_id, lines));
+        interp_ok(u64::try_from(global.borrow_snapshots.len()).unwrap())
+    }
+
+    /// Write the borrow state of `alloc_id` as a DOT graph to the host file `path`, for
+    /// `miri_dump_borrow_tree`.
+    fn dump_borrow_state_dot(&mut self, alloc_id: AllocId, path: &Path) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let Some(borrow_tracker) = &this.machine.borrow_tracker else {
+            eprintln!("attempted to dump borrow state, but no borrow state is being tracked");
+            return interp_ok(());
+        };
+        let model = borrow_tracker.borrow().model();
+        let Some(dot) = model.borrow_state_dot(this, alloc_id)? else {
+            this.tcx.tcx.dcx().warn(format!(
+                "{} does not keep a borrow tree; `miri_dump_borrow_tree` is a no-op",
+                model.name()
+            ));
+            return interp_ok(());
+        };
+        if let Err(err) = std::fs::write(path, dot) {
+            this.tcx
+                .tcx
+                .dcx()
+                .warn(format!("failed to write the borrow tree to `{}`: {err}", path.display()));
+        }
+        interp_ok(())
+    }
+
+    /// Print how the borrow state of the allocation captured by snapshot `id` changed
+    /// since then, for `miri_diff_borrows`.
+    fn diff_borrow_state(&mut self, id: u64) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let Some(borrow_tracker) = &this.machine.borrow_tracker else {
+            eprintln!("attempted to diff borrow state, but no borrow state is being tracked");
+            return interp_ok(());
+        };
+        let snapshot = usize::try_from(id)
+            .ok()
+            .and_then(|id| id.checked_sub(1))
+            .and_then(|idx| borrow_tracker.borrow().borrow_snapshots.get(idx).cloned());
+        let Some((alloc_id, old)) = snapshot else {
+            eprintln!("{id} is not the ID of a borrow state snapshot");
+            return interp_ok(());
+        };
+        if this.get_alloc_info(alloc_id).kind != AllocKind::LiveData {
+            eprintln!("the allocation of snapshot {id} ({alloc_id:?}) is no longer live");
+            return interp_ok(());
+        }
+        let model = borrow_tracker.borrow().model();
+        let new = model.borrow_state_lines(this, alloc_id)?;
+        if old == new {
+            eprintln!("borrow state of {alloc_id:?} is unchanged since snapshot {id}");
+            return interp_ok(());
+        }
+        eprintln!("borrow state of {alloc_id:?} changed since snapshot {id}:");
+        for line in diff_lines(&old, &new) {
+            eprintln!("{line}");
         }
+        interp_ok(())
     }
 
     fn on_stack_pop(
@@ -363,16 +734,21 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             // If it does exist, then we have the guarantee that the
             // pointer is readable, and the implicit read access inserted
             // will never cause UB on the pointer itself.
+            borrow_tracker.borrow().log_event(&this.machine, BorrowEvent::Unprotect {
+                tag: *tag,
+                alloc_id: *alloc_id,
+            });
             let kind = this.get_alloc_info(*alloc_id).kind;
             if matches!(kind, AllocKind::LiveData) {
                 let alloc_extra = this.get_alloc_extra(*alloc_id)?; // can still fail for `extern static`
                 let alloc_borrow_tracker = &alloc_extra.borrow_tracker.as_ref().unwrap();
-                alloc_borrow_tracker.release_protector(
+                let res = alloc_borrow_tracker.release_protector(
                     &this.machine,
                     borrow_tracker,
                     *tag,
                     *alloc_id,
-                )?;
+                );
+                downgrade_aliasing_error(&this.machine, res, || ())?;
             }
         }
         borrow_tracker.borrow_mut().end_call(&frame.extra);
@@ -380,42 +756,54 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     }
 }
 
-/// Extra per-allocation data for borrow tracking
-#[derive(Debug, Clone)]
-pub enum AllocState {
-    /// Data corresponding to Stacked Borrows
-    StackedBorrows(Box<RefCell<stacked_borrows::AllocState>>),
-    /// Data corresponding to Tree Borrows
-    TreeBorrows(Box<RefCell<tree_borrows::AllocState>>),
+/// Extra per-allocation data for borrow tracking, as created by the aliasing model in use.
+#[derive(Debug)]
+pub struct AllocState(Box<dyn AllocAliasingState>);
+
+impl Clone for AllocState {
+    fn clone(&self) -> Self {
+        AllocState(self.0.clone_box())
+    }
 }
 
 impl machine::AllocExtra<'_> {
     #[track_caller]
     pub fn borrow_tracker_sb(&self) -> &RefCell<stacked_borrows::AllocState> {
-        match self.borrow_tracker {
-            Some(AllocState::StackedBorrows(ref sb)) => sb,
-            _ => panic!("expected Stacked Borrows borrow tracking, got something else"),
+        match self.borrow_tracker.as_ref().and_then(|bt| bt.0.as_any().downcast_ref()) {
+            Some(sb) => sb,
+            None => panic!("expected Stacked Borrows borrow tracking, got something else"),
         }
     }
 
     #[track_caller]
     pub fn borrow_tracker_sb_mut(&mut self) -> &mut RefCell<stacked_borrows::AllocState> {
-        match self.borrow_tracker {
-            Some(AllocState::StackedBorrows(ref mut sb)) => sb,
-            _ => panic!("expected Stacked Borrows borrow tracking, got something else"),
+        match self.borrow_tracker.as_mut().and_then(|bt| bt.0.as_any_mut().downcast_mut()) {
+            Some(sb) => sb,
+            None => panic!("expected Stacked Borrows borrow tracking, got something else"),
         }
     }
 
     #[track_caller]
     pub fn borrow_tracker_tb(&self) -> &RefCell<tree_borrows::AllocState> {
-        match self.borrow_tracker {
-            Some(AllocState::TreeBorrows(ref tb)) => tb,
-            _ => panic!("expected Tree Borrows borrow tracking, got something else"),
+        match self.borrow_tracker.as_ref().and_then(|bt| bt.0.as_any().downcast_ref()) {
+            Some(tb) => tb,
+            None => panic!("expected Tree Borrows borrow tracking, got something else"),
         }
     }
 }
 
 impl AllocState {
+    /// Write an access to the borrow log, if it was done through a concrete tag.
+    fn log_access<'tcx>(
+        machine: &MiriMachine<'tcx>,
+        prov_extra: ProvenanceExtra,
+        event: impl FnOnce(BorTag) -> BorrowEvent<'static>,
+    ) {
+        if let ProvenanceExtra::Concrete(tag) = prov_extra {
+            machine.borrow_tracker.as_ref().unwrap().borrow().log_event(machine, event(tag));
+        }
+    }
+
     pub fn before_memory_read<'tcx>(
         &self,
         alloc_id: AllocId,
@@ -423,18 +811,11 @@ impl AllocState {
         range: AllocRange,
         machine: &MiriMachine<'tcx>,
     ) -> InterpResult<'tcx> {
-        match self {
-            AllocState::StackedBorrows(sb) =>
-                sb.borrow_mut().before_memory_read(alloc_id, prov_extra, range, machine),
-            AllocState::TreeBorrows(tb) =>
-                tb.borrow_mut().before_memory_access(
-                    AccessKind::Read,
-                    alloc_id,
-                    prov_extra,
-                    range,
-                    machine,
-                ),
-        }
+        Self::log_access(machine, prov_extra, |tag| {
+            BorrowEvent::Access { tag, alloc_id, range, kind: AccessKind::Read }
+        });
+        let res = self.0.before_memory_read(alloc_id, prov_extra, range, machine);
+        downgrade_aliasing_error(machine, res, || ())
     }
 
     pub fn before_memory_write<'tcx>(
@@ -444,18 +825,11 @@ impl AllocState {
         range: AllocRange,
         machine: &MiriMachine<'tcx>,
     ) -> InterpResult<'tcx> {
-        match self {
-            AllocState::StackedBorrows(sb) =>
-                sb.get_mut().before_memory_write(alloc_id, prov_extra, range, machine),
-            AllocState::TreeBorrows(tb) =>
-                tb.get_mut().before_memory_access(
-                    AccessKind::Write,
-                    alloc_id,
-                    prov_extra,
-                    range,
-                    machine,
-                ),
-        }
+        Self::log_access(machine, prov_extra, |tag| {
+            BorrowEvent::Access { tag, alloc_id, range, kind: AccessKind::Write }
+        });
+        let res = self.0.before_memory_write(alloc_id, prov_extra, range, machine);
+        downgrade_aliasing_error(machine, res, || ())
     }
 
     pub fn before_memory_deallocation<'tcx>(
@@ -465,19 +839,13 @@ impl AllocState {
         size: Size,
         machine: &MiriMachine<'tcx>,
     ) -> InterpResult<'tcx> {
-        match self {
-            AllocState::StackedBorrows(sb) =>
-                sb.get_mut().before_memory_deallocation(alloc_id, prov_extra, size, machine),
-            AllocState::TreeBorrows(tb) =>
-                tb.get_mut().before_memory_deallocation(alloc_id, prov_extra, size, machine),
-        }
+        Self::log_access(machine, prov_extra, |tag| BorrowEvent::Dealloc { tag, alloc_id, size });
+        let res = self.0.before_memory_deallocation(alloc_id, prov_extra, size, machine);
+        downgrade_aliasing_error(machine, res, || ())
     }
 
-    pub fn remove_unreachable_tags(&self, tags: &FxHashSet<BorTag>) {
-        match self {
-            AllocState::StackedBorrows(sb) => sb.borrow_mut().remove_unreachable_tags(tags),
-            AllocState::TreeBorrows(tb) => tb.borrow_mut().remove_unreachable_tags(tags),
-        }
+    pub fn remove_unreachable_tags(&self, tags: &FxHashSet<BorTag>) -> u64 {
+        self.0.remove_unreachable_tags(tags)
     }
 
     /// Tree Borrows needs to be told when a tag stops being protected.
@@ -488,19 +856,12 @@ impl AllocState {
         tag: BorTag,
         alloc_id: AllocId, // diagnostics
     ) -> InterpResult<'tcx> {
-        match self {
-            AllocState::StackedBorrows(_sb) => interp_ok(()),
-            AllocState::TreeBorrows(tb) =>
-                tb.borrow_mut().release_protector(machine, global, tag, alloc_id),
-        }
+        self.0.release_protector(machine, global, tag, alloc_id)
     }
 }
 
 impl VisitProvenance for AllocState {
     fn visit_provenance(&self, visit: &mut VisitWith<'_>) {
-        match self {
-            AllocState::StackedBorrows(sb) => sb.visit_provenance(visit),
-            AllocState::TreeBorrows(tb) => tb.visit_provenance(visit),
-        }
+        self.0.visit_provenance(visit)
     }
 }
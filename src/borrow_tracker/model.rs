@@ -0,0 +1,156 @@
+//! The interface between the machine and an aliasing model.
+//!
+//! Everything outside of `borrow_tracker` goes through these two traits, so a new
+//! (experimental) model only has to implement them and be returned by
+//! `BorrowTrackerMethod::model`; none of the call sites need to change.
+
+use std::any::Any;
+use std::fmt;
+
+use rustc_abi::Size;
+use rustc_data_structures::fx::FxHashSet;
+use rustc_middle::mir::RetagKind;
+
+use crate::borrow_tracker::{GlobalState, GlobalStateInner};
+use crate::*;
+
+/// The global part of an aliasing model: how pointers get new tags, and how the
+/// per-allocation state is created.
+pub trait AliasingModel: fmt::Debug {
+    /// The human-readable name of the model, used in diagnostics.
+    fn name(&self) -> &'static str;
+
+    /// Create the state of a new allocation.
+    fn new_allocation(
+        &self,
+        id: AllocId,
+        size: Size,
+        global: &mut GlobalStateInner,
+        kind: MemoryKind,
+        machine: &MiriMachine<'_>,
+    ) -> Box<dyn AllocAliasingState>;
+
+    /// Retag hook: give a new tag to the reference or box `val`.
+    fn retag_ptr_value<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        kind: RetagKind,
+        val: &ImmTy<'tcx>,
+    ) -> InterpResult<'tcx, ImmTy<'tcx>>;
+
+    /// Retag hook: give new tags to all references and boxes stored in `place`.
+    fn retag_place_contents<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        kind: RetagKind,
+        place: &PlaceTy<'tcx>,
+    ) -> InterpResult<'tcx>;
+
+    /// Protector hook: protect `place`, which is passed in-place to a function,
+    /// for the duration of the call.
+    fn protect_place<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        place: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx, MPlaceTy<'tcx>>;
+
+    /// `tag` was found on a pointer that got exposed.
+    fn expose_tag<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+        tag: BorTag,
+    ) -> InterpResult<'tcx>;
+
+    /// Name a pointer for the output of `print_borrow_state`.
+    fn give_pointer_debug_name<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        _ptr: Pointer,
+        _nth_parent: u8,
+        _name: &str,
+    ) -> InterpResult<'tcx> {
+        ecx.tcx.tcx.dcx().warn(format!(
+            "{} does not support named pointers; `miri_pointer_name` is a no-op",
+            self.name()
+        ));
+        interp_ok(())
+    }
+
+    /// Print the state of an allocation, for debugging.
+    fn print_borrow_state<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+        show_unnamed: bool,
+    ) -> InterpResult<'tcx>;
+
+    /// Render the state of an allocation line by line, for `miri_snapshot_borrows` and
+    /// `miri_diff_borrows`.
+    fn borrow_state_lines<'tcx>(
+        &self,
+        ecx: &MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+    ) -> InterpResult<'tcx, Vec<String>>;
+
+    /// Render the state of an allocation as a graph in the DOT language of Graphviz, for
+    /// `miri_dump_borrow_tree`. Returns `None` if the model has no tree to show.
+    fn borrow_state_dot<'tcx>(
+        &self,
+        _ecx: &MiriInterpCx<'tcx>,
+        _alloc_id: AllocId,
+    ) -> InterpResult<'tcx, Option<String>> {
+        interp_ok(None)
+    }
+}
+
+/// The per-allocation part of an aliasing model.
+pub trait AllocAliasingState: fmt::Debug + VisitProvenance + Any {
+    /// Access hook: `range` is read through a pointer with the given provenance.
+    fn before_memory_read<'tcx>(
+        &self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        range: AllocRange,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx>;
+
+    /// Access hook: `range` is written through a pointer with the given provenance.
+    fn before_memory_write<'tcx>(
+        &mut self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        range: AllocRange,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx>;
+
+    /// Access hook: the allocation is freed through a pointer with the given provenance.
+    fn before_memory_deallocation<'tcx>(
+        &mut self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        size: Size,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx>;
+
+    /// Forget about all tags that are not in `live_tags`. Returns how many tags were removed;
+    /// a model that keeps a tag in several places may count it once per place.
+    fn remove_unreachable_tags(&self, live_tags: &FxHashSet<BorTag>) -> u64;
+
+    /// Protector hook: `tag` just lost its protector.
+    fn release_protector<'tcx>(
+        &self,
+        _machine: &MiriMachine<'tcx>,
+        _global: &GlobalState,
+        _tag: BorTag,
+        _alloc_id: AllocId,
+    ) -> InterpResult<'tcx> {
+        interp_ok(())
+    }
+
+    fn clone_box(&self) -> Box<dyn AllocAliasingState>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
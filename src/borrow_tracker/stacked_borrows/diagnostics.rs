@@ -2,6 +2,7 @@ use std::fmt;
 
 use rustc_abi::Size;
 use rustc_data_structures::fx::FxHashSet;
+use rustc_span::def_id::CrateNum;
 use rustc_span::{Span, SpanData};
 use smallvec::SmallVec;
 
@@ -13,8 +14,16 @@ fn err_sb_ub<'tcx>(
     msg: String,
     help: Vec<String>,
     history: Option<TagHistory>,
+    alloc_id: AllocId,
+    protected_tag: Option<BorTag>,
 ) -> InterpErrorKind<'tcx> {
-    err_machine_stop!(TerminationInfo::StackedBorrowsUb { msg, help, history })
+    err_machine_stop!(TerminationInfo::StackedBorrowsUb {
+        msg,
+        help,
+        history,
+        alloc_id,
+        protected_tag,
+    })
 }
 
 #[derive(Clone, Debug)]
@@ -30,6 +39,8 @@ pub struct AllocHistory {
 struct Creation {
     retag: RetagOp,
     span: Span,
+    /// The crate responsible for the retag, see `MiriMachine::responsible_crate`.
+    krate: Option<CrateNum>,
 }
 
 impl Creation {
@@ -61,6 +72,8 @@ struct Invalidation {
     tag: BorTag,
     range: AllocRange,
     span: Span,
+    /// The crate responsible for the invalidation, see `MiriMachine::responsible_crate`.
+    krate: Option<CrateNum>,
     cause: InvalidationCause,
 }
 
@@ -113,6 +126,9 @@ pub struct TagHistory {
     pub created: (String, SpanData),
     pub invalidated: Option<(String, SpanData)>,
     pub protected: Option<(String, SpanData)>,
+    /// The crates responsible for the creation and invalidation of the tag, if known.
+    pub created_by: Option<CrateNum>,
+    pub invalidated_by: Option<CrateNum>,
 }
 
 pub struct DiagnosticCxBuilder<'ecx, 'tcx> {
@@ -267,9 +283,11 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
         let Operation::Retag(op) = &self.operation else {
             unreachable!("log_creation must only be called during a retag")
         };
-        self.history
-            .creations
-            .push(Creation { retag: op.clone(), span: self.machine.current_span() });
+        self.history.creations.push(Creation {
+            retag: op.clone(),
+            span: self.machine.current_span(),
+            krate: self.machine.responsible_crate(),
+        });
     }
 
     pub fn log_invalidation(&mut self, tag: BorTag) {
@@ -289,7 +307,8 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
                 return;
             }
         };
-        self.history.invalidations.push(Invalidation { tag, range, span, cause });
+        let krate = self.machine.responsible_crate();
+        self.history.invalidations.push(Invalidation { tag, range, span, krate, cause });
     }
 
     pub fn log_protector(&mut self) {
@@ -306,7 +325,7 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
         tag: BorTag,
         protector_tag: Option<BorTag>,
     ) -> Option<TagHistory> {
-        let Some(created) = self
+        let Some((created, created_by)) = self
             .history
             .creations
             .iter()
@@ -320,7 +339,7 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
                     && self.offset >= range.start
                     && self.offset < (range.start + range.size)
                 {
-                    Some(event.generate_diagnostic())
+                    Some((event.generate_diagnostic(), event.krate))
                 } else {
                     None
                 }
@@ -331,7 +350,7 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
                 // doesn't have the tag.
                 self.history.creations.iter().rev().find_map(|event| {
                     if event.retag.new_tag == tag {
-                        Some(event.generate_diagnostic())
+                        Some((event.generate_diagnostic(), event.krate))
                     } else {
                         None
                     }
@@ -342,11 +361,14 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
                 // this allocation.
                 if self.history.root.0.tag() == tag {
                     Some((
-                        format!(
-                            "{tag:?} was created here, as the root tag for {:?}",
-                            self.history.id
+                        (
+                            format!(
+                                "{tag:?} was created here, as the root tag for {:?}",
+                                self.history.id
+                            ),
+                            self.history.root.1.data(),
                         ),
-                        self.history.root.1.data(),
+                        None,
                     ))
                 } else {
                     None
@@ -358,9 +380,9 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
             return None;
         };
 
-        let invalidated = self.history.invalidations.iter().rev().find_map(|event| {
-            if event.tag == tag { Some(event.generate_diagnostic()) } else { None }
-        });
+        let invalidation = self.history.invalidations.iter().rev().find(|event| event.tag == tag);
+        let invalidated = invalidation.map(|event| event.generate_diagnostic());
+        let invalidated_by = invalidation.and_then(|event| event.krate);
 
         let protected = protector_tag
             .and_then(|protector| {
@@ -371,7 +393,7 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
                 (format!("{protected_tag:?} is this argument"), protection.span.data())
             });
 
-        Some(TagHistory { created, invalidated, protected })
+        Some(TagHistory { created, invalidated, protected, created_by, invalidated_by })
     }
 
     /// Report a descriptive error when `new` could not be granted from `derived_from`.
@@ -397,6 +419,8 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
             format!("{action}{}", error_cause(stack, op.orig_tag)),
             helps,
             op.orig_tag.and_then(|orig_tag| self.get_logs_relevant_to(orig_tag, None)),
+            self.history.id,
+            None,
         )
     }
 
@@ -420,6 +444,8 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
             format!("{action}{}", error_cause(stack, op.tag)),
             vec![operation_summary("an access", self.history.id, op.range)],
             op.tag.and_then(|tag| self.get_logs_relevant_to(tag, None)),
+            self.history.id,
+            None,
         )
     }
 
@@ -435,7 +461,13 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
         };
         match self.operation {
             Operation::Dealloc(_) =>
-                err_sb_ub(format!("deallocating while item {item:?} is {protected}",), vec![], None),
+                err_sb_ub(
+                    format!("deallocating while item {item:?} is {protected}",),
+                    vec![],
+                    self.get_logs_relevant_to(item.tag(), Some(item.tag())),
+                    self.history.id,
+                    Some(item.tag()),
+                ),
             Operation::Retag(RetagOp { orig_tag: tag, .. })
             | Operation::Access(AccessOp { tag, .. }) =>
                 err_sb_ub(
@@ -444,6 +476,8 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
                     ),
                     vec![],
                     tag.and_then(|tag| self.get_logs_relevant_to(tag, Some(item.tag()))),
+                    self.history.id,
+                    Some(item.tag()),
                 ),
         }
     }
@@ -462,6 +496,8 @@ impl<'history, 'ecx, 'tcx> DiagnosticCx<'history, 'ecx, 'tcx> {
             ),
             vec![],
             op.tag.and_then(|tag| self.get_logs_relevant_to(tag, None)),
+            self.history.id,
+            None,
         )
     }
 
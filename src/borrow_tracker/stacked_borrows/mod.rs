@@ -5,6 +5,7 @@ pub mod diagnostics;
 mod item;
 mod stack;
 
+use std::any::Any;
 use std::cell::RefCell;
 use std::fmt::Write;
 use std::{cmp, mem};
@@ -18,10 +19,12 @@ use rustc_middle::ty::{self, Ty};
 use self::diagnostics::{RetagCause, RetagInfo};
 pub use self::item::{Item, Permission};
 pub use self::stack::Stack;
+use crate::borrow_tracker::event_log::BorrowEvent;
+use crate::borrow_tracker::model::{AliasingModel, AllocAliasingState};
 use crate::borrow_tracker::stacked_borrows::diagnostics::{
     AllocHistory, DiagnosticCx, DiagnosticCxBuilder,
 };
-use crate::borrow_tracker::{GlobalStateInner, ProtectorKind};
+use crate::borrow_tracker::{GlobalStateInner, MAX_RETAG_DEPTH_ARRAY_LEN, ProtectorKind};
 use crate::concurrency::data_race::{NaReadType, NaWriteType};
 use crate::*;
 
@@ -438,11 +441,20 @@ impl<'tcx> Stack {
 
 /// Integration with the BorTag garbage collector
 impl Stacks {
-    pub fn remove_unreachable_tags(&mut self, live_tags: &FxHashSet<BorTag>) {
+    /// Returns the number of removed items, summed over all stacks.
+    pub fn remove_unreachable_tags(&mut self, live_tags: &FxHashSet<BorTag>) -> u64 {
+        let mut removed = 0;
         for (_stack_range, stack) in self.stacks.iter_mut_all() {
-            stack.retain(live_tags);
+            removed += stack.retain(live_tags);
         }
+        // Accesses and retags of parts of an allocation split its stacks, and `iter_mut` only
+        // merges them again opportunistically. Removing tags often makes the stacks of adjacent
+        // ranges equal again, so this is a good moment to undo the fragmentation: otherwise a
+        // large buffer that was once accessed byte by byte keeps one stack per byte forever,
+        // and every later access to all of it has to visit each of them.
+        self.stacks.merge_adjacent_thorough();
         self.history.retain(live_tags);
+        removed
     }
 }
 
@@ -601,24 +613,39 @@ trait EvalContextPrivExt<'tcx, 'ecx>: crate::MiriInterpCxExt<'tcx> {
         let log_creation = |this: &MiriInterpCx<'tcx>,
                             loc: Option<(AllocId, Size, ProvenanceExtra)>| // alloc_id, base_offset, orig_tag
          -> InterpResult<'tcx> {
+            if let Some((_, _, orig_tag)) = loc {
+                let mut global = this.machine.borrow_tracker.as_ref().unwrap().borrow_mut();
+                global.track_derived_tag(orig_tag, new_tag);
+            }
             let global = this.machine.borrow_tracker.as_ref().unwrap().borrow();
             let ty = place.layout.ty;
-            if global.tracked_pointer_tags.contains(&new_tag) {
+            let tracked = global.tracked_pointer_tags.contains(&new_tag);
+            if tracked || global.logs_tag(new_tag) {
                 let mut kind_str = String::new();
                 match new_perm {
                     NewPermission::Uniform { perm, .. } =>
                         write!(kind_str, "{perm:?} permission").unwrap(),
-                    NewPermission::FreezeSensitive { freeze_perm, .. } if ty.is_freeze(*this.tcx, this.typing_env()) =>
+                    NewPermission::FreezeSensitive { freeze_perm, .. } if this.type_is_freeze_for_aliasing(ty) =>
                         write!(kind_str, "{freeze_perm:?} permission").unwrap(),
                     NewPermission::FreezeSensitive { freeze_perm, nonfreeze_perm, .. }  =>
                         write!(kind_str, "{freeze_perm:?}/{nonfreeze_perm:?} permission for frozen/non-frozen parts").unwrap(),
                 }
-                write!(kind_str, " (pointee type {ty})").unwrap();
-                this.emit_diagnostic(NonHaltingDiagnostic::CreatedPointerTag(
-                    new_tag.inner(),
-                    Some(kind_str),
-                    loc.map(|(alloc_id, base_offset, orig_tag)| (alloc_id, alloc_range(base_offset, size), orig_tag)),
-                ));
+                global.log_event(&this.machine, BorrowEvent::Retag {
+                    tag: new_tag,
+                    parent: loc.and_then(|(_, _, orig_tag)| orig_tag.and_then(Some)),
+                    alloc_id: loc.map(|(alloc_id, _, _)| alloc_id),
+                    range: loc.map(|(_, base_offset, _)| alloc_range(base_offset, size)),
+                    perm: &kind_str,
+                    pointee: &ty.to_string(),
+                });
+                if tracked {
+                    write!(kind_str, " (pointee type {ty})").unwrap();
+                    this.emit_diagnostic(NonHaltingDiagnostic::CreatedPointerTag(
+                        new_tag.inner(),
+                        Some(kind_str),
+                        loc.map(|(alloc_id, base_offset, orig_tag)| (alloc_id, alloc_range(base_offset, size), orig_tag)),
+                    ));
+                }
             }
             drop(global); // don't hold that reference any longer than we have to
 
@@ -713,6 +740,10 @@ trait EvalContextPrivExt<'tcx, 'ecx>: crate::MiriInterpCxExt<'tcx> {
                 .get_mut()
                 .protected_tags
                 .insert(new_tag, protect);
+            this.machine.borrow_tracker.as_ref().unwrap().borrow().log_event(
+                &this.machine,
+                BorrowEvent::Protect { tag: new_tag, alloc_id, kind: protect },
+            );
         }
 
         // Update the stacks, according to the new permission information we are given.
@@ -886,14 +917,23 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         place: &PlaceTy<'tcx>,
     ) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
-        let retag_fields = this.machine.borrow_tracker.as_mut().unwrap().get_mut().retag_fields;
+        let options = this.machine.borrow_tracker.as_mut().unwrap().get_mut();
+        let retag_fields = options.retag_fields;
+        let depth = options.retag_depth;
         let retag_cause = match kind {
             RetagKind::TwoPhase { .. } => unreachable!(), // can only happen in `retag_ptr_value`
             RetagKind::FnEntry => RetagCause::FnEntry,
             RetagKind::Default | RetagKind::Raw => RetagCause::Normal,
         };
-        let mut visitor =
-            RetagVisitor { ecx: this, kind, retag_cause, retag_fields, in_field: false };
+        let mut visitor = RetagVisitor {
+            ecx: this,
+            kind,
+            retag_cause,
+            retag_fields,
+            in_field: false,
+            depth,
+            in_pointee: false,
+        };
         return visitor.visit_value(place);
 
         // The actual visitor.
@@ -903,6 +943,10 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             retag_cause: RetagCause,
             retag_fields: RetagFields,
             in_field: bool,
+            /// How many more levels of mutable references and boxes to follow.
+            depth: u32,
+            /// Whether we are visiting the pointee of a mutable reference or box.
+            in_pointee: bool,
         }
         impl<'ecx, 'tcx> RetagVisitor<'ecx, 'tcx> {
             #[inline(always)] // yes this helps in our benchmarks
@@ -910,6 +954,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 &mut self,
                 place: &PlaceTy<'tcx>,
                 new_perm: NewPermission,
+                is_unique: bool,
             ) -> InterpResult<'tcx> {
                 let val = self.ecx.read_immediate(&self.ecx.place_to_op(place)?)?;
                 let val = self.ecx.sb_retag_reference(&val, new_perm, RetagInfo {
@@ -917,8 +962,28 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     in_field: self.in_field,
                 })?;
                 self.ecx.write_immediate(*val, place)?;
+                if is_unique && self.depth > 0 {
+                    self.retag_pointee(&val)?;
+                }
                 interp_ok(())
             }
+
+            /// Retag the references stored behind a freshly retagged mutable reference or box.
+            /// We have unique access to the pointee, so writing the new tags back is fine.
+            fn retag_pointee(&mut self, val: &ImmTy<'tcx>) -> InterpResult<'tcx> {
+                let pointee = self.ecx.ref_to_mplace(val)?;
+                // Only the place itself gets protected on function entry.
+                let kind = mem::replace(&mut self.kind, RetagKind::Default);
+                let in_field = mem::replace(&mut self.in_field, true);
+                let in_pointee = mem::replace(&mut self.in_pointee, true);
+                self.depth -= 1;
+                let res = self.visit_value(&pointee.into());
+                self.depth += 1;
+                self.in_pointee = in_pointee;
+                self.in_field = in_field;
+                self.kind = kind;
+                res
+            }
         }
         impl<'ecx, 'tcx> ValueVisitor<'tcx, MiriMachine<'tcx>> for RetagVisitor<'ecx, 'tcx> {
             type V = PlaceTy<'tcx>;
@@ -933,7 +998,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 if box_ty.is_box_global(*self.ecx.tcx) {
                     // Boxes get a weak protectors, since they may be deallocated.
                     let new_perm = NewPermission::from_box_ty(place.layout.ty, self.kind, self.ecx);
-                    self.retag_ptr_inplace(place, new_perm)?;
+                    self.retag_ptr_inplace(place, new_perm, /* is_unique */ true)?;
                 }
                 interp_ok(())
             }
@@ -955,7 +1020,9 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                         {
                             let new_perm =
                                 NewPermission::from_ref_ty(place.layout.ty, self.kind, self.ecx);
-                            self.retag_ptr_inplace(place, new_perm)?;
+                            let is_unique =
+                                matches!(place.layout.ty.kind(), ty::Ref(_, _, Mutability::Mut));
+                            self.retag_ptr_inplace(place, new_perm, is_unique)?;
                         }
                     }
                     ty::Adt(adt, _) if adt.is_box() => {
@@ -966,7 +1033,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     }
                     _ => {
                         // Not a reference/pointer/box. Only recurse if configured appropriately.
-                        let recurse = match self.retag_fields {
+                        let mut recurse = match self.retag_fields {
                             RetagFields::No => false,
                             RetagFields::Yes => true,
                             RetagFields::OnlyScalar => {
@@ -978,6 +1045,12 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                                 )
                             }
                         };
+                        if recurse
+                            && self.in_pointee
+                            && matches!(place.layout.ty.kind(), ty::Array(..) | ty::Slice(..))
+                        {
+                            recurse = place.len(self.ecx)? <= MAX_RETAG_DEPTH_ARRAY_LEN;
+                        }
                         if recurse {
                             let in_field = mem::replace(&mut self.in_field, true); // remember and restore old value
                             self.walk_value(place)?;
@@ -1036,19 +1109,152 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
     fn print_stacks(&mut self, alloc_id: AllocId) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
+        for line in this.stack_lines(alloc_id)? {
+            println!("{line}");
+        }
+        interp_ok(())
+    }
+
+    /// Render the stacks of `alloc_id`, one line per range.
+    fn stack_lines(&self, alloc_id: AllocId) -> InterpResult<'tcx, Vec<String>> {
+        let this = self.eval_context_ref();
         let alloc_extra = this.get_alloc_extra(alloc_id)?;
         let stacks = alloc_extra.borrow_tracker_sb().borrow();
+        let mut lines = Vec::new();
         for (range, stack) in stacks.stacks.iter_all() {
-            print!("{range:?}: [");
+            let mut line = format!("{range:?}: [");
             if let Some(bottom) = stack.unknown_bottom() {
-                print!(" unknown-bottom(..{bottom:?})");
+                write!(line, " unknown-bottom(..{bottom:?})").unwrap();
             }
             for i in 0..stack.len() {
                 let item = stack.get(i).unwrap();
-                print!(" {:?}{:?}", item.perm(), item.tag());
+                write!(line, " {:?}{:?}", item.perm(), item.tag()).unwrap();
             }
-            println!(" ]");
+            line.push_str(" ]");
+            lines.push(line);
         }
-        interp_ok(())
+        interp_ok(lines)
+    }
+}
+
+/// The Stacked Borrows aliasing model.
+#[derive(Debug)]
+pub struct StackedBorrows;
+
+impl AliasingModel for StackedBorrows {
+    fn name(&self) -> &'static str {
+        "Stacked Borrows"
+    }
+
+    fn new_allocation(
+        &self,
+        id: AllocId,
+        size: Size,
+        global: &mut GlobalStateInner,
+        kind: MemoryKind,
+        machine: &MiriMachine<'_>,
+    ) -> Box<dyn AllocAliasingState> {
+        Box::new(RefCell::new(Stacks::new_allocation(id, size, global, kind, machine)))
+    }
+
+    fn retag_ptr_value<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        kind: RetagKind,
+        val: &ImmTy<'tcx>,
+    ) -> InterpResult<'tcx, ImmTy<'tcx>> {
+        ecx.sb_retag_ptr_value(kind, val)
+    }
+
+    fn retag_place_contents<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        kind: RetagKind,
+        place: &PlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        ecx.sb_retag_place_contents(kind, place)
+    }
+
+    fn protect_place<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        place: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx, MPlaceTy<'tcx>> {
+        ecx.sb_protect_place(place)
+    }
+
+    fn expose_tag<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+        tag: BorTag,
+    ) -> InterpResult<'tcx> {
+        ecx.sb_expose_tag(alloc_id, tag)
+    }
+
+    fn print_borrow_state<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+        _show_unnamed: bool,
+    ) -> InterpResult<'tcx> {
+        ecx.print_stacks(alloc_id)
+    }
+
+    fn borrow_state_lines<'tcx>(
+        &self,
+        ecx: &MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+    ) -> InterpResult<'tcx, Vec<String>> {
+        ecx.stack_lines(alloc_id)
+    }
+}
+
+// Even reading memory can have effects on the stack, hence the `RefCell`.
+impl AllocAliasingState for RefCell<Stacks> {
+    fn before_memory_read<'tcx>(
+        &self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        range: AllocRange,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx> {
+        self.borrow_mut().before_memory_read(alloc_id, prov_extra, range, machine)
+    }
+
+    fn before_memory_write<'tcx>(
+        &mut self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        range: AllocRange,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx> {
+        self.get_mut().before_memory_write(alloc_id, prov_extra, range, machine)
+    }
+
+    fn before_memory_deallocation<'tcx>(
+        &mut self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        size: Size,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx> {
+        self.get_mut().before_memory_deallocation(alloc_id, prov_extra, size, machine)
+    }
+
+    fn remove_unreachable_tags(&self, live_tags: &FxHashSet<BorTag>) -> u64 {
+        self.borrow_mut().remove_unreachable_tags(live_tags)
+    }
+
+    fn clone_box(&self) -> Box<dyn AllocAliasingState> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
@@ -42,7 +42,8 @@ pub struct Stack {
 }
 
 impl Stack {
-    pub fn retain(&mut self, tags: &FxHashSet<BorTag>) {
+    /// Removes the items of unreachable tags. Returns the number of removed items.
+    pub fn retain(&mut self, tags: &FxHashSet<BorTag>) -> u64 {
         let mut first_removed = None;
 
         // We never consider removing the bottom-most tag. For stacks without an unknown
@@ -79,6 +80,7 @@ impl Stack {
 
             read_idx += 1;
         }
+        let removed = self.borrows.len() - write_idx;
         self.borrows.truncate(write_idx);
 
         #[cfg(not(feature = "stack-cache"))]
@@ -100,6 +102,8 @@ impl Stack {
                 }
             }
         }
+
+        removed.try_into().unwrap()
     }
 }
 
@@ -1,13 +1,16 @@
 use std::fmt;
+use std::fmt::Write as _;
 use std::ops::Range;
 
+use rustc_abi::Size;
 use rustc_data_structures::fx::FxHashMap;
+use rustc_span::def_id::CrateNum;
 use rustc_span::{Span, SpanData};
 
 use crate::borrow_tracker::ProtectorKind;
 use crate::borrow_tracker::tree_borrows::perms::{PermTransition, Permission};
 use crate::borrow_tracker::tree_borrows::tree::LocationState;
-use crate::borrow_tracker::tree_borrows::unimap::UniIndex;
+use crate::borrow_tracker::tree_borrows::unimap::{UniIndex, UniValMap};
 use crate::*;
 
 /// Cause of an access: either a real access or one
@@ -75,6 +78,8 @@ pub struct Event {
     pub transition_range: Range<u64>,
     /// Line of code that triggered this event.
     pub span: Span,
+    /// The crate responsible for this event, see `MiriMachine::responsible_crate`.
+    pub krate: Option<CrateNum>,
 }
 
 /// List of all events that affected a tag.
@@ -85,6 +90,8 @@ pub struct Event {
 pub struct History {
     tag: BorTag,
     created: (Span, Permission),
+    /// The crate responsible for the creation of the tag.
+    created_by: Option<CrateNum>,
     events: Vec<Event>,
 }
 
@@ -95,6 +102,11 @@ pub struct History {
 #[derive(Debug, Clone, Default)]
 pub struct HistoryData {
     pub events: Vec<(Option<SpanData>, String)>, // includes creation
+    /// The crate responsible for the creation of the accessed tag.
+    pub created_by: Option<CrateNum>,
+    /// The crate responsible for the last event that changed the conflicting tag in a way
+    /// relevant to the error.
+    pub changed_by: Option<CrateNum>,
 }
 
 impl History {
@@ -109,7 +121,7 @@ impl HistoryData {
     //
     // NOTE: also converts `Span` to `SpanData`.
     fn extend(&mut self, new_history: History, tag_name: &'static str, show_initial_state: bool) {
-        let History { tag, created, events } = new_history;
+        let History { tag, created, created_by: _, events } = new_history;
         let this = format!("the {tag_name} tag {tag:?}");
         let msg_initial_state = format!(", in the initial state {}", created.1);
         let msg_creation = format!(
@@ -125,6 +137,7 @@ impl HistoryData {
             access_range,
             span,
             transition_range: _,
+            krate: _,
         } in &events
         {
             // NOTE: `transition_range` is explicitly absent from the error message, it has no significance
@@ -171,8 +184,9 @@ pub struct NodeDebugInfo {
 impl NodeDebugInfo {
     /// Information for a new node. By default it has no
     /// name and an empty history.
-    pub fn new(tag: BorTag, initial: Permission, span: Span) -> Self {
-        let history = History { tag, created: (span, initial), events: Vec::new() };
+    pub fn new(tag: BorTag, initial: Permission, span: Span, krate: Option<CrateNum>) -> Self {
+        let history =
+            History { tag, created: (span, initial), created_by: krate, events: Vec::new() };
         Self { tag, name: None, history }
     }
 
@@ -256,7 +270,12 @@ pub(super) enum TransitionError {
 impl History {
     /// Keep only the tag and creation
     fn forget(&self) -> Self {
-        History { events: Vec::new(), created: self.created, tag: self.tag }
+        History {
+            events: Vec::new(),
+            created: self.created,
+            created_by: self.created_by,
+            tag: self.tag,
+        }
     }
 
     /// Reconstruct the history relevant to `error_offset` by filtering
@@ -271,6 +290,7 @@ impl History {
                 .cloned()
                 .collect::<Vec<_>>(),
             created: self.created,
+            created_by: self.created_by,
             tag: self.tag,
         }
     }
@@ -309,6 +329,10 @@ impl TbError<'_> {
             alloc_id = self.alloc_id,
             offset = self.error_offset
         );
+        let protected_tag = match self.error_kind {
+            ChildAccessForbidden(_) => None,
+            ProtectedDisabled(_) | ProtectedDealloc => Some(conflicting.tag),
+        };
         let (title, details, conflicting_tag_name) = match self.error_kind {
             ChildAccessForbidden(perm) => {
                 let conflicting_tag_name =
@@ -354,12 +378,21 @@ impl TbError<'_> {
         if !accessed_is_conflicting {
             history.extend(self.accessed_info.history.forget(), "accessed", false);
         }
-        history.extend(
-            self.conflicting_info.history.extract_relevant(self.error_offset, self.error_kind),
-            conflicting_tag_name,
-            true,
-        );
-        err_machine_stop!(TerminationInfo::TreeBorrowsUb { title, details, history })
+        let relevant =
+            self.conflicting_info.history.extract_relevant(self.error_offset, self.error_kind);
+        history.created_by = self.accessed_info.history.created_by;
+        history.changed_by = relevant.events.last().and_then(|event| event.krate);
+        history.extend(relevant, conflicting_tag_name, true);
+        err_machine_stop!(TerminationInfo::TreeBorrowsUb {
+            title,
+            details,
+            history,
+            alloc_id: self.alloc_id,
+            error_offset: self.error_offset,
+            accessed: accessed.tag,
+            conflicting: conflicting.tag,
+            protected_tag,
+        })
     }
 }
 
@@ -569,6 +602,12 @@ impl DisplayIndent {
     }
 }
 
+/// Escape `s` for use in a double-quoted DOT string. Backslashes are kept as they are,
+/// since they introduce the DOT escapes like `\l`.
+fn dot_escape(s: &str) -> String {
+    s.replace('"', "\\\"")
+}
+
 /// Repeat a character a number of times.
 fn char_repeat(c: char, n: usize) -> String {
     std::iter::once(c).cycle().take(n).collect::<String>()
@@ -582,6 +621,8 @@ struct DisplayRepr {
     name: Option<String>,
     rperm: Vec<Option<LocationState>>,
     children: Vec<DisplayRepr>,
+    /// How the tag is involved in an error, if it is.
+    highlight: Option<&'static str>,
 }
 
 impl DisplayRepr {
@@ -634,32 +675,180 @@ impl DisplayRepr {
                 for child_idx in children_sorted {
                     extraction_aux(tree, child_idx, show_unnamed, &mut children);
                 }
-                acc.push(DisplayRepr { tag: node.tag, name, rperm, children });
+                acc.push(DisplayRepr { tag: node.tag, name, rperm, children, highlight: None });
+            }
+        }
+    }
+
+    /// Extract the part of the tree that is relevant to an error at `offset`:
+    /// the paths from the closest common ancestor of `accessed` and `conflicting`
+    /// down to each of them, including unnamed tags, together with the permissions
+    /// of the range that contains `offset`.
+    fn from_error(
+        tree: &Tree,
+        accessed: BorTag,
+        conflicting: BorTag,
+        offset: u64,
+    ) -> Option<(Self, Range<u64>)> {
+        let path_from_root = |tag: BorTag| -> Option<Vec<UniIndex>> {
+            let mut idx = tree.tag_mapping.get(&tag)?;
+            let mut path = vec![idx];
+            while let Some(parent) = tree.nodes.get(idx).unwrap().parent {
+                idx = parent;
+                path.push(idx);
+            }
+            path.reverse();
+            Some(path)
+        };
+        let accessed_path = path_from_root(accessed)?;
+        let conflicting_path = path_from_root(conflicting)?;
+        // Both paths start at the root, so they have at least one node in common.
+        let common =
+            accessed_path.iter().zip(&conflicting_path).take_while(|(a, c)| a == c).count();
+        let relevant = accessed_path[common - 1..]
+            .iter()
+            .chain(&conflicting_path[common..])
+            .copied()
+            .collect::<Vec<_>>();
+        let (range, perms) =
+            tree.rperms.iter(Size::from_bytes(offset), Size::from_bytes(1)).next()?;
+        let repr = extraction_aux(tree, relevant[0], &relevant, perms, accessed, conflicting);
+        return Some((repr, range));
+
+        fn extraction_aux(
+            tree: &Tree,
+            idx: UniIndex,
+            relevant: &[UniIndex],
+            perms: &UniValMap<LocationState>,
+            accessed: BorTag,
+            conflicting: BorTag,
+        ) -> DisplayRepr {
+            let node = tree.nodes.get(idx).unwrap();
+            let mut children_sorted = node
+                .children
+                .iter()
+                .copied()
+                .filter(|idx| relevant.contains(idx))
+                .collect::<Vec<_>>();
+            children_sorted.sort_by_key(|idx| tree.nodes.get(*idx).unwrap().tag);
+            let children = children_sorted
+                .into_iter()
+                .map(|child_idx| {
+                    extraction_aux(tree, child_idx, relevant, perms, accessed, conflicting)
+                })
+                .collect();
+            DisplayRepr {
+                tag: node.tag,
+                name: node.debug_info.name.clone(),
+                rperm: vec![perms.get(idx).cloned()],
+                children,
+                highlight: DisplayRepr::involvement(node.tag, accessed, conflicting),
+            }
+        }
+    }
+
+    /// How `tag` is involved in an error between `accessed` and `conflicting`, if at all.
+    fn involvement(tag: BorTag, accessed: BorTag, conflicting: BorTag) -> Option<&'static str> {
+        match (tag == accessed, tag == conflicting) {
+            (true, true) => Some("accessed, conflicting"),
+            (true, false) => Some("accessed"),
+            (false, true) => Some("conflicting"),
+            (false, false) => None,
+        }
+    }
+
+    /// Point out `accessed` and `conflicting` wherever they occur in the tree.
+    fn highlight_involved(&mut self, accessed: BorTag, conflicting: BorTag) {
+        self.highlight = DisplayRepr::involvement(self.tag, accessed, conflicting);
+        for child in &mut self.children {
+            child.highlight_involved(accessed, conflicting);
+        }
+    }
+
+    /// Lay out the tree as a graph in the DOT language of Graphviz: one node per tag,
+    /// labelled with its permission on each of the `ranges`, and an edge from every tag
+    /// to each of its children. Involved tags are drawn in bold red.
+    fn render_dot(
+        &self,
+        fmt: &DisplayFmt,
+        protected_tags: &FxHashMap<BorTag, ProtectorKind>,
+        ranges: &[Range<u64>],
+        title: &str,
+    ) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph \"{}\" {{", dot_escape(title)).unwrap();
+        writeln!(out, "    label=\"{}\";", dot_escape(title)).unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        dot_aux(self, fmt, protected_tags, ranges, &mut out);
+        writeln!(out, "}}").unwrap();
+        return out;
+
+        fn dot_aux(
+            tree: &DisplayRepr,
+            fmt: &DisplayFmt,
+            protected_tags: &FxHashMap<BorTag, ProtectorKind>,
+            ranges: &[Range<u64>],
+            out: &mut String,
+        ) {
+            // Every line of the label is left-aligned by ending it with `\l`.
+            let mut label = fmt.print_tag(tree.tag, &tree.name);
+            label.push_str(fmt.print_protector(protected_tags.get(&tree.tag)));
+            label.push_str("\\l");
+            for (range, perm) in ranges.iter().zip(&tree.rperm) {
+                let perm = fmt.print_perm(*perm);
+                label.push_str(&format!(
+                    "{}..{}: {}\\l",
+                    range.start,
+                    range.end,
+                    perm.trim_start()
+                ));
+            }
+            let style = if let Some(highlight) = tree.highlight {
+                label.push_str(&format!("({highlight})\\l"));
+                ", style=bold, color=red"
+            } else {
+                ""
+            };
+            writeln!(out, "    t{} [label=\"{}\"{style}];", tree.tag.get(), dot_escape(&label))
+                .unwrap();
+            for child in &tree.children {
+                writeln!(out, "    t{} -> t{};", tree.tag.get(), child.tag.get()).unwrap();
+                dot_aux(child, fmt, protected_tags, ranges, out);
             }
         }
     }
-    fn print(
+
+    /// Lay out the tree, framed by a border of dashes. `title`, if any, is shown
+    /// right below the top border.
+    fn render(
         &self,
         fmt: &DisplayFmt,
         indenter: &mut DisplayIndent,
         protected_tags: &FxHashMap<BorTag, ProtectorKind>,
         ranges: Vec<Range<u64>>,
-        print_warning: bool,
-    ) {
+        title: Option<&str>,
+    ) -> Vec<String> {
         let mut block = Vec::new();
         // Push the header and compute the required paddings for the body.
         // Header looks like this: `0.. 1.. 2.. 3.. 4.. 5.. 6.. 7.. 8`,
         // and is properly aligned with the `|` of the body.
         let (range_header, range_padding) = {
             let mut header_top = String::new();
-            header_top.push_str("0..");
+            let start = format!("{}{}", ranges[0].start, fmt.perm.range_sep);
+            // The body is laid out for a header starting with `0..`; a longer start
+            // shifts the first column.
+            let start_overflow = start.chars().count().saturating_sub(3);
+            header_top.push_str(&start);
             let mut padding = Vec::new();
             for (i, range) in ranges.iter().enumerate() {
                 if i > 0 {
                     header_top.push_str(fmt.perm.range_sep);
                 }
                 let s = range.end.to_string();
-                let l = s.chars().count() + fmt.perm.range_sep.chars().count();
+                let mut l = s.chars().count() + fmt.perm.range_sep.chars().count();
+                if i == 0 {
+                    l += start_overflow;
+                }
                 {
                     let target_len =
                         fmt.perm.uninit.chars().count() + fmt.accessed.yes.chars().count() + 1;
@@ -686,25 +875,25 @@ impl DisplayRepr {
             &mut block,
         );
         // Then it's just prettifying it with a border of dashes.
+        let mut lines = Vec::new();
         {
             let wr = &fmt.wrapper;
             let max_width = {
                 let block_width = block.iter().map(|s| s.chars().count()).max().unwrap();
-                if print_warning {
-                    block_width.max(wr.warning_text.chars().count())
+                if let Some(title) = title {
+                    block_width.max(title.chars().count())
                 } else {
                     block_width
                 }
             };
-            eprintln!("{}", char_repeat(wr.top, max_width));
-            if print_warning {
-                eprintln!("{}", wr.warning_text,);
-            }
-            for line in block {
-                eprintln!("{line}");
+            lines.push(char_repeat(wr.top, max_width));
+            if let Some(title) = title {
+                lines.push(title.to_string());
             }
-            eprintln!("{}", char_repeat(wr.bot, max_width));
+            lines.extend(block);
+            lines.push(char_repeat(wr.bot, max_width));
         }
+        return lines;
 
         // Here is the function that does the heavy lifting
         fn print_aux(
@@ -750,6 +939,9 @@ impl DisplayRepr {
             line.push_str(&fmt.print_tag(tree.tag, &tree.name));
             let protector = protected_tags.get(&tree.tag);
             line.push_str(fmt.print_protector(protector));
+            if let Some(highlight) = tree.highlight {
+                line.push_str(&format!(" <- {highlight}"));
+            }
             // Push the line to the accumulator then recurse.
             acc.push(line);
             let nb_children = tree.children.len();
@@ -787,17 +979,67 @@ impl<'tcx> Tree {
         protected_tags: &FxHashMap<BorTag, ProtectorKind>,
         show_unnamed: bool,
     ) -> InterpResult<'tcx> {
+        for line in self.render_tree(protected_tags, show_unnamed) {
+            eprintln!("{line}");
+        }
+        interp_ok(())
+    }
+
+    /// Render the contents of the tree as `print_tree` displays them.
+    pub fn render_tree(
+        &self,
+        protected_tags: &FxHashMap<BorTag, ProtectorKind>,
+        show_unnamed: bool,
+    ) -> Vec<String> {
         let mut indenter = DisplayIndent::new();
         let ranges = self.rperms.iter_all().map(|(range, _perms)| range).collect::<Vec<_>>();
-        if let Some(repr) = DisplayRepr::from(self, show_unnamed) {
-            repr.print(
-                &DEFAULT_FORMATTER,
-                &mut indenter,
-                protected_tags,
-                ranges,
-                /* print warning message about tags not shown */ !show_unnamed,
-            );
+        let Some(repr) = DisplayRepr::from(self, show_unnamed) else {
+            return Vec::new();
+        };
+        repr.render(
+            &DEFAULT_FORMATTER,
+            &mut indenter,
+            protected_tags,
+            ranges,
+            // Warn about tags not shown.
+            (!show_unnamed).then_some(DEFAULT_FORMATTER.wrapper.warning_text),
+        )
+    }
+
+    /// Render the whole tree, with all tags, as a DOT graph named `title`. If `involved`
+    /// is `Some((accessed, conflicting))`, the tags involved in an error are highlighted.
+    pub fn render_dot(
+        &self,
+        protected_tags: &FxHashMap<BorTag, ProtectorKind>,
+        title: &str,
+        involved: Option<(BorTag, BorTag)>,
+    ) -> String {
+        let ranges = self.rperms.iter_all().map(|(range, _perms)| range).collect::<Vec<_>>();
+        let mut repr =
+            DisplayRepr::from(self, /* show_unnamed */ true).expect("the root is always shown");
+        if let Some((accessed, conflicting)) = involved {
+            repr.highlight_involved(accessed, conflicting);
         }
-        interp_ok(())
+        repr.render_dot(&DEFAULT_FORMATTER, protected_tags, &ranges, title)
+    }
+
+    /// Render the part of the tree involved in an error at `offset` between the
+    /// `accessed` and the `conflicting` tag, see `DisplayRepr::from_error`.
+    pub fn render_error_subtree(
+        &self,
+        protected_tags: &FxHashMap<BorTag, ProtectorKind>,
+        accessed: BorTag,
+        conflicting: BorTag,
+        offset: u64,
+    ) -> Option<Vec<String>> {
+        let mut indenter = DisplayIndent::new();
+        let (repr, range) = DisplayRepr::from_error(self, accessed, conflicting, offset)?;
+        Some(repr.render(
+            &DEFAULT_FORMATTER,
+            &mut indenter,
+            protected_tags,
+            vec![range],
+            Some("The relevant part of the tree at the time of the error:"),
+        ))
     }
 }
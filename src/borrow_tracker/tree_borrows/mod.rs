@@ -1,10 +1,19 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::mem;
+
 use rustc_abi::{BackendRepr, Size};
+use rustc_data_structures::fx::FxHashSet;
 use rustc_middle::mir::{Mutability, RetagKind};
 use rustc_middle::ty::layout::HasTypingEnv;
 use rustc_middle::ty::{self, Ty};
 use rustc_span::def_id::DefId;
 
-use crate::borrow_tracker::{GlobalState, GlobalStateInner, ProtectorKind};
+use crate::borrow_tracker::event_log::BorrowEvent;
+use crate::borrow_tracker::model::{AliasingModel, AllocAliasingState};
+use crate::borrow_tracker::{
+    GlobalState, GlobalStateInner, MAX_RETAG_DEPTH_ARRAY_LEN, ProtectorKind,
+};
 use crate::concurrency::data_race::NaReadType;
 use crate::*;
 
@@ -32,7 +41,7 @@ impl<'tcx> Tree {
     ) -> Self {
         let tag = state.root_ptr_tag(id, machine); // Fresh tag for the root
         let span = machine.current_span();
-        Tree::new(tag, size, span)
+        Tree::new(tag, size, span, machine.responsible_crate())
     }
 
     /// Check that an access on the entire range is permitted, and update
@@ -66,6 +75,7 @@ impl<'tcx> Tree {
             global,
             alloc_id,
             span,
+            machine.responsible_crate(),
         )
     }
 
@@ -85,7 +95,8 @@ impl<'tcx> Tree {
         };
         let global = machine.borrow_tracker.as_ref().unwrap();
         let span = machine.current_span();
-        self.dealloc(tag, alloc_range(Size::ZERO, size), global, alloc_id, span)
+        let krate = machine.responsible_crate();
+        self.dealloc(tag, alloc_range(Size::ZERO, size), global, alloc_id, span, krate)
     }
 
     pub fn expose_tag(&mut self, _tag: BorTag) {
@@ -107,7 +118,7 @@ impl<'tcx> Tree {
     ) -> InterpResult<'tcx> {
         let span = machine.current_span();
         // `None` makes it the magic on-protector-end operation
-        self.perform_access(tag, None, global, alloc_id, span)
+        self.perform_access(tag, None, global, alloc_id, span, machine.responsible_crate())
     }
 }
 
@@ -132,8 +143,11 @@ impl<'tcx> NewPermission {
         kind: RetagKind,
         cx: &crate::MiriInterpCx<'tcx>,
     ) -> Option<Self> {
-        let ty_is_freeze = pointee.is_freeze(*cx.tcx, cx.typing_env());
+        let ty_is_freeze = cx.type_is_freeze_for_aliasing(pointee);
         let ty_is_unpin = pointee.is_unpin(*cx.tcx, cx.typing_env());
+        // With `-Zmiri-not-unpin-is-unique`, `!Unpin` does not opt out of uniqueness.
+        let mut_is_unique =
+            ty_is_unpin || cx.machine.borrow_tracker.as_ref().unwrap().borrow().not_unpin_is_unique;
         let is_protected = kind == RetagKind::FnEntry;
         // As demonstrated by `tests/fail/tree_borrows/reservedim_spurious_write.rs`,
         // interior mutability and protectors interact poorly.
@@ -141,12 +155,13 @@ impl<'tcx> NewPermission {
         // in the case of a protected reference: protected references are always considered
         // "freeze" in their reservation phase.
         let initial_state = match mutability {
-            Mutability::Mut if ty_is_unpin => Permission::new_reserved(ty_is_freeze, is_protected),
+            Mutability::Mut if mut_is_unique =>
+                Permission::new_reserved(ty_is_freeze, is_protected),
             Mutability::Not if ty_is_freeze => Permission::new_frozen(),
             // Raw pointers never enter this function so they are not handled.
             // However raw pointers are not the only pointers that take the parent
-            // tag, this also happens for `!Unpin` `&mut`s and interior mutable
-            // `&`s, which are excluded above.
+            // tag, this also happens for `!Unpin` `&mut`s (unless `-Zmiri-not-unpin-is-unique`)
+            // and interior mutable `&`s, which are excluded above.
             _ => return None,
         };
 
@@ -167,7 +182,7 @@ impl<'tcx> NewPermission {
         pointee.is_unpin(*cx.tcx, cx.typing_env()).then_some(()).map(|()| {
             // Regular `Unpin` box, give it `noalias` but only a weak protector
             // because it is valid to deallocate it within the function.
-            let ty_is_freeze = ty.is_freeze(*cx.tcx, cx.typing_env());
+            let ty_is_freeze = cx.type_is_freeze_for_aliasing(ty);
             let protected = kind == RetagKind::FnEntry;
             let initial_state = Permission::new_reserved(ty_is_freeze, protected);
             Self {
@@ -202,8 +217,20 @@ trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let log_creation = |this: &MiriInterpCx<'tcx>,
                             loc: Option<(AllocId, Size, ProvenanceExtra)>| // alloc_id, base_offset, orig_tag
          -> InterpResult<'tcx> {
+            if let Some((_, _, orig_tag)) = loc {
+                let mut global = this.machine.borrow_tracker.as_ref().unwrap().borrow_mut();
+                global.track_derived_tag(orig_tag, new_tag);
+            }
             let global = this.machine.borrow_tracker.as_ref().unwrap().borrow();
             let ty = place.layout.ty;
+            global.log_event(&this.machine, BorrowEvent::Retag {
+                tag: new_tag,
+                parent: loc.and_then(|(_, _, orig_tag)| orig_tag.and_then(Some)),
+                alloc_id: loc.map(|(alloc_id, _, _)| alloc_id),
+                range: loc.map(|(_, base_offset, _)| alloc_range(base_offset, ptr_size)),
+                perm: &format!("initial state {}", new_perm.initial_state),
+                pointee: &ty.to_string(),
+            });
             if global.tracked_pointer_tags.contains(&new_tag) {
                 let kind_str = format!("initial state {} (pointee type {ty})", new_perm.initial_state);
                 this.emit_diagnostic(NonHaltingDiagnostic::CreatedPointerTag(
@@ -272,6 +299,10 @@ trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 .get_mut()
                 .protected_tags
                 .insert(new_tag, protect);
+            this.machine.borrow_tracker.as_ref().unwrap().borrow().log_event(
+                &this.machine,
+                BorrowEvent::Protect { tag: new_tag, alloc_id, kind: protect },
+            );
         }
 
         let alloc_kind = this.get_alloc_info(alloc_id).kind;
@@ -283,6 +314,7 @@ trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
 
         let span = this.machine.current_span();
+        let krate = this.machine.responsible_crate();
         let alloc_extra = this.get_alloc_extra(alloc_id)?;
         let range = alloc_range(base_offset, ptr_size);
         let mut tree_borrows = alloc_extra.borrow_tracker_tb().borrow_mut();
@@ -293,10 +325,11 @@ trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             Some((range, AccessKind::Read, diagnostics::AccessCause::Reborrow)),
             this.machine.borrow_tracker.as_ref().unwrap(),
             alloc_id,
-            this.machine.current_span(),
+            span,
+            krate,
         )?;
         // Record the parent-child pair in the tree.
-        tree_borrows.new_child(orig_tag, new_tag, new_perm.initial_state, range, span)?;
+        tree_borrows.new_child(orig_tag, new_tag, new_perm.initial_state, range, span, krate)?;
         drop(tree_borrows);
 
         // Also inform the data race model (but only if any bytes are actually affected).
@@ -397,9 +430,11 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let this = self.eval_context_mut();
         let options = this.machine.borrow_tracker.as_mut().unwrap().get_mut();
         let retag_fields = options.retag_fields;
+        let depth = options.retag_depth;
         let unique_did =
             options.unique_is_unique.then(|| this.tcx.lang_items().ptr_unique()).flatten();
-        let mut visitor = RetagVisitor { ecx: this, kind, retag_fields, unique_did };
+        let mut visitor =
+            RetagVisitor { ecx: this, kind, retag_fields, unique_did, depth, in_pointee: false };
         return visitor.visit_value(place);
 
         // The actual visitor.
@@ -408,6 +443,10 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             kind: RetagKind,
             retag_fields: RetagFields,
             unique_did: Option<DefId>,
+            /// How many more levels of mutable references and boxes to follow.
+            depth: u32,
+            /// Whether we are visiting the pointee of a mutable reference or box.
+            in_pointee: bool,
         }
         impl<'ecx, 'tcx> RetagVisitor<'ecx, 'tcx> {
             #[inline(always)] // yes this helps in our benchmarks
@@ -415,14 +454,33 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 &mut self,
                 place: &PlaceTy<'tcx>,
                 new_perm: Option<NewPermission>,
+                is_unique: bool,
             ) -> InterpResult<'tcx> {
                 if let Some(new_perm) = new_perm {
                     let val = self.ecx.read_immediate(&self.ecx.place_to_op(place)?)?;
                     let val = self.ecx.tb_retag_reference(&val, new_perm)?;
                     self.ecx.write_immediate(*val, place)?;
+                    if is_unique && self.depth > 0 {
+                        self.retag_pointee(&val)?;
+                    }
                 }
                 interp_ok(())
             }
+
+            /// Retag the references stored behind a freshly retagged mutable reference or box.
+            /// We have unique access to the pointee, so writing the new tags back is fine.
+            fn retag_pointee(&mut self, val: &ImmTy<'tcx>) -> InterpResult<'tcx> {
+                let pointee = self.ecx.ref_to_mplace(val)?;
+                // Only the place itself gets protected on function entry.
+                let kind = mem::replace(&mut self.kind, RetagKind::Default);
+                let in_pointee = mem::replace(&mut self.in_pointee, true);
+                self.depth -= 1;
+                let res = self.visit_value(&pointee.into());
+                self.depth += 1;
+                self.in_pointee = in_pointee;
+                self.kind = kind;
+                res
+            }
         }
         impl<'ecx, 'tcx> ValueVisitor<'tcx, MiriMachine<'tcx>> for RetagVisitor<'ecx, 'tcx> {
             type V = PlaceTy<'tcx>;
@@ -444,7 +502,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                         self.ecx,
                         /* zero_size */ false,
                     );
-                    self.retag_ptr_inplace(place, new_perm)?;
+                    self.retag_ptr_inplace(place, new_perm, /* is_unique */ true)?;
                 }
                 interp_ok(())
             }
@@ -463,7 +521,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     &ty::Ref(_, pointee, mutability) => {
                         let new_perm =
                             NewPermission::from_ref_ty(pointee, mutability, self.kind, self.ecx);
-                        self.retag_ptr_inplace(place, new_perm)?;
+                        self.retag_ptr_inplace(place, new_perm, mutability.is_mut())?;
                     }
                     ty::RawPtr(_, _) => {
                         // We definitely do *not* want to recurse into raw pointers -- wide raw
@@ -485,11 +543,11 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                             self.ecx,
                             /* zero_size */ true,
                         );
-                        self.retag_ptr_inplace(&place, new_perm)?;
+                        self.retag_ptr_inplace(&place, new_perm, /* is_unique */ false)?;
                     }
                     _ => {
                         // Not a reference/pointer/box. Only recurse if configured appropriately.
-                        let recurse = match self.retag_fields {
+                        let mut recurse = match self.retag_fields {
                             RetagFields::No => false,
                             RetagFields::Yes => true,
                             RetagFields::OnlyScalar => {
@@ -501,6 +559,12 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                                 )
                             }
                         };
+                        if recurse
+                            && self.in_pointee
+                            && matches!(place.layout.ty.kind(), ty::Array(..) | ty::Slice(..))
+                        {
+                            recurse = place.len(self.ecx)? <= MAX_RETAG_DEPTH_ARRAY_LEN;
+                        }
                         if recurse {
                             self.walk_value(place)?;
                         }
@@ -521,7 +585,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         // Note: if we were to inline `new_reserved` below we would find out that
         // `ty_is_freeze` is eventually unused because it appears in a `ty_is_freeze || true`.
         // We are nevertheless including it here for clarity.
-        let ty_is_freeze = place.layout.ty.is_freeze(*this.tcx, this.typing_env());
+        let ty_is_freeze = this.type_is_freeze_for_aliasing(place.layout.ty);
         // Retag it. With protection! That is the entire point.
         let new_perm = NewPermission {
             initial_state: Permission::new_reserved(ty_is_freeze, /* protected */ true),
@@ -564,6 +628,56 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         tree_borrows.print_tree(&borrow_tracker.protected_tags, show_unnamed)
     }
 
+    /// Render the tree of `alloc_id` with all tags, for borrow state snapshots.
+    fn tree_lines(&self, alloc_id: AllocId) -> InterpResult<'tcx, Vec<String>> {
+        let this = self.eval_context_ref();
+        let alloc_extra = this.get_alloc_extra(alloc_id)?;
+        let tree_borrows = alloc_extra.borrow_tracker_tb().borrow();
+        let borrow_tracker = &this.machine.borrow_tracker.as_ref().unwrap().borrow();
+        interp_ok(tree_borrows.render_tree(&borrow_tracker.protected_tags, /* show_unnamed */ true))
+    }
+
+    /// Render the tree of `alloc_id` with all tags as a DOT graph, for `miri_dump_borrow_tree`
+    /// and `-Zmiri-tree-borrows-dot-on-error`. If `involved` is `Some((accessed, conflicting))`,
+    /// the tags involved in an error are highlighted.
+    fn tree_dot(
+        &self,
+        alloc_id: AllocId,
+        involved: Option<(BorTag, BorTag)>,
+    ) -> InterpResult<'tcx, String> {
+        let this = self.eval_context_ref();
+        let alloc_extra = this.get_alloc_extra(alloc_id)?;
+        let tree_borrows = alloc_extra.borrow_tracker_tb().borrow();
+        let borrow_tracker = &this.machine.borrow_tracker.as_ref().unwrap().borrow();
+        interp_ok(tree_borrows.render_dot(
+            &borrow_tracker.protected_tags,
+            &format!("{alloc_id:?}"),
+            involved,
+        ))
+    }
+
+    /// Render the part of the tree of `alloc_id` involved in an error between the
+    /// `accessed` and the `conflicting` tag at `offset`.
+    /// Returns `None` if the allocation or one of the tags no longer exists.
+    fn tb_error_subtree(
+        &self,
+        alloc_id: AllocId,
+        offset: u64,
+        accessed: BorTag,
+        conflicting: BorTag,
+    ) -> Option<Vec<String>> {
+        let this = self.eval_context_ref();
+        let alloc_extra = this.get_alloc_extra(alloc_id).discard_err()?;
+        let tree_borrows = alloc_extra.borrow_tracker_tb().borrow();
+        let borrow_tracker = &this.machine.borrow_tracker.as_ref()?.borrow();
+        tree_borrows.render_error_subtree(
+            &borrow_tracker.protected_tags,
+            accessed,
+            conflicting,
+            offset,
+        )
+    }
+
     /// Give a name to the pointer, usually the name it has in the source code (for debugging).
     /// The name given is `name` and the pointer that receives it is the `nth_parent`
     /// of `ptr` (with 0 representing `ptr` itself)
@@ -610,3 +724,158 @@ fn inner_ptr_of_unique<'tcx>(
     // Finally a plain `*mut`
     interp_ok(ptr)
 }
+
+/// The Tree Borrows aliasing model.
+#[derive(Debug)]
+pub struct TreeBorrows;
+
+impl AliasingModel for TreeBorrows {
+    fn name(&self) -> &'static str {
+        "Tree Borrows"
+    }
+
+    fn new_allocation(
+        &self,
+        id: AllocId,
+        size: Size,
+        global: &mut GlobalStateInner,
+        kind: MemoryKind,
+        machine: &MiriMachine<'_>,
+    ) -> Box<dyn AllocAliasingState> {
+        Box::new(RefCell::new(Tree::new_allocation(id, size, global, kind, machine)))
+    }
+
+    fn retag_ptr_value<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        kind: RetagKind,
+        val: &ImmTy<'tcx>,
+    ) -> InterpResult<'tcx, ImmTy<'tcx>> {
+        ecx.tb_retag_ptr_value(kind, val)
+    }
+
+    fn retag_place_contents<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        kind: RetagKind,
+        place: &PlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        ecx.tb_retag_place_contents(kind, place)
+    }
+
+    fn protect_place<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        place: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx, MPlaceTy<'tcx>> {
+        ecx.tb_protect_place(place)
+    }
+
+    fn expose_tag<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+        tag: BorTag,
+    ) -> InterpResult<'tcx> {
+        ecx.tb_expose_tag(alloc_id, tag)
+    }
+
+    fn give_pointer_debug_name<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        ptr: Pointer,
+        nth_parent: u8,
+        name: &str,
+    ) -> InterpResult<'tcx> {
+        ecx.tb_give_pointer_debug_name(ptr, nth_parent, name)
+    }
+
+    fn print_borrow_state<'tcx>(
+        &self,
+        ecx: &mut MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+        show_unnamed: bool,
+    ) -> InterpResult<'tcx> {
+        ecx.print_tree(alloc_id, show_unnamed)
+    }
+
+    fn borrow_state_lines<'tcx>(
+        &self,
+        ecx: &MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+    ) -> InterpResult<'tcx, Vec<String>> {
+        ecx.tree_lines(alloc_id)
+    }
+
+    fn borrow_state_dot<'tcx>(
+        &self,
+        ecx: &MiriInterpCx<'tcx>,
+        alloc_id: AllocId,
+    ) -> InterpResult<'tcx, Option<String>> {
+        ecx.tree_dot(alloc_id, None).map(Some)
+    }
+}
+
+impl AllocAliasingState for RefCell<Tree> {
+    fn before_memory_read<'tcx>(
+        &self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        range: AllocRange,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx> {
+        self.borrow_mut().before_memory_access(
+            AccessKind::Read,
+            alloc_id,
+            prov_extra,
+            range,
+            machine,
+        )
+    }
+
+    fn before_memory_write<'tcx>(
+        &mut self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        range: AllocRange,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx> {
+        self.get_mut().before_memory_access(AccessKind::Write, alloc_id, prov_extra, range, machine)
+    }
+
+    fn before_memory_deallocation<'tcx>(
+        &mut self,
+        alloc_id: AllocId,
+        prov_extra: ProvenanceExtra,
+        size: Size,
+        machine: &MiriMachine<'tcx>,
+    ) -> InterpResult<'tcx> {
+        self.get_mut().before_memory_deallocation(alloc_id, prov_extra, size, machine)
+    }
+
+    fn remove_unreachable_tags(&self, live_tags: &FxHashSet<BorTag>) -> u64 {
+        self.borrow_mut().remove_unreachable_tags(live_tags)
+    }
+
+    fn release_protector<'tcx>(
+        &self,
+        machine: &MiriMachine<'tcx>,
+        global: &GlobalState,
+        tag: BorTag,
+        alloc_id: AllocId,
+    ) -> InterpResult<'tcx> {
+        self.borrow_mut().release_protector(machine, global, tag, alloc_id)
+    }
+
+    fn clone_box(&self) -> Box<dyn AllocAliasingState> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -15,6 +15,7 @@ use std::{fmt, mem};
 use rustc_abi::Size;
 use rustc_data_structures::fx::FxHashSet;
 use rustc_span::Span;
+use rustc_span::def_id::CrateNum;
 use smallvec::SmallVec;
 
 use crate::borrow_tracker::tree_borrows::Permission;
@@ -575,7 +576,7 @@ impl<'tree> TreeVisitor<'tree> {
 
 impl Tree {
     /// Create a new tree, with only a root pointer.
-    pub fn new(root_tag: BorTag, size: Size, span: Span) -> Self {
+    pub fn new(root_tag: BorTag, size: Size, span: Span, krate: Option<CrateNum>) -> Self {
         // The root has `Disabled` as the default permission,
         // so that any access out of bounds is invalid.
         let root_default_perm = Permission::new_disabled();
@@ -583,7 +584,7 @@ impl Tree {
         let root_idx = tag_mapping.insert(root_tag);
         let nodes = {
             let mut nodes = UniValMap::<Node>::default();
-            let mut debug_info = NodeDebugInfo::new(root_tag, root_default_perm, span);
+            let mut debug_info = NodeDebugInfo::new(root_tag, root_default_perm, span, krate);
             // name the root so that all allocations contain one named pointer
             debug_info.add_name("root of the allocation");
             nodes.insert(root_idx, Node {
@@ -616,7 +617,8 @@ impl<'tcx> Tree {
         new_tag: BorTag,
         default_initial_perm: Permission,
         reborrow_range: AllocRange,
-        span: Span,
+        span: Span,              // diagnostics
+        krate: Option<CrateNum>, // diagnostics
     ) -> InterpResult<'tcx> {
         assert!(!self.tag_mapping.contains_key(&new_tag));
         let idx = self.tag_mapping.insert(new_tag);
@@ -627,7 +629,7 @@ impl<'tcx> Tree {
             parent: Some(parent_idx),
             children: SmallVec::default(),
             default_initial_perm,
-            debug_info: NodeDebugInfo::new(new_tag, default_initial_perm, span),
+            debug_info: NodeDebugInfo::new(new_tag, default_initial_perm, span, krate),
         });
         // Register new_tag as a child of parent_tag
         self.nodes.get_mut(parent_idx).unwrap().children.push(idx);
@@ -648,8 +650,9 @@ impl<'tcx> Tree {
         tag: BorTag,
         access_range: AllocRange,
         global: &GlobalState,
-        alloc_id: AllocId, // diagnostics
-        span: Span,        // diagnostics
+        alloc_id: AllocId,       // diagnostics
+        span: Span,              // diagnostics
+        krate: Option<CrateNum>, // diagnostics
     ) -> InterpResult<'tcx> {
         self.perform_access(
             tag,
@@ -657,6 +660,7 @@ impl<'tcx> Tree {
             global,
             alloc_id,
             span,
+            krate,
         )?;
         for (perms_range, perms) in self.rperms.iter_mut(access_range.start, access_range.size) {
             TreeVisitor { nodes: &mut self.nodes, tag_mapping: &self.tag_mapping, perms }
@@ -714,8 +718,9 @@ impl<'tcx> Tree {
         tag: BorTag,
         access_range_and_kind: Option<(AllocRange, AccessKind, diagnostics::AccessCause)>,
         global: &GlobalState,
-        alloc_id: AllocId, // diagnostics
-        span: Span,        // diagnostics
+        alloc_id: AllocId,       // diagnostics
+        span: Span,              // diagnostics
+        krate: Option<CrateNum>, // diagnostics
     ) -> InterpResult<'tcx> {
         use std::ops::Range;
         // Performs the per-node work:
@@ -762,6 +767,7 @@ impl<'tcx> Tree {
                     access_range: access_range_and_kind.map(|x| x.0),
                     transition_range: perms_range,
                     span,
+                    krate,
                 });
             }
             Ok(())
@@ -833,13 +839,15 @@ impl<'tcx> Tree {
 
 /// Integration with the BorTag garbage collector
 impl Tree {
-    pub fn remove_unreachable_tags(&mut self, live_tags: &FxHashSet<BorTag>) {
-        self.remove_useless_children(self.root, live_tags);
+    /// Returns the number of removed nodes.
+    pub fn remove_unreachable_tags(&mut self, live_tags: &FxHashSet<BorTag>) -> u64 {
+        let removed = self.remove_useless_children(self.root, live_tags);
         // Right after the GC runs is a good moment to check if we can
         // merge some adjacent ranges that were made equal by the removal of some
         // tags (this does not necessarily mean that they have identical internal representations,
         // see the `PartialEq` impl for `UniValMap`)
         self.rperms.merge_adjacent_thorough();
+        removed
     }
 
     /// Checks if a node is useless and should be GC'ed.
@@ -906,7 +914,7 @@ impl Tree {
 
     /// Traverses the entire tree looking for useless tags.
     /// Removes from the tree all useless child nodes of root.
-    /// It will not delete the root itself.
+    /// It will not delete the root itself. Returns the number of removed nodes.
     ///
     /// NOTE: This leaves in the middle of the tree tags that are unreachable but have
     /// reachable children. There is a potential for compacting the tree by reassigning
@@ -917,7 +925,8 @@ impl Tree {
     /// `child: Reserved`. This tree can exist. If we blindly delete `parent` and reassign
     /// `child` to be a direct child of `root` then Writes to `child` are now permitted
     /// whereas they were not when `parent` was still there.
-    fn remove_useless_children(&mut self, root: UniIndex, live: &FxHashSet<BorTag>) {
+    fn remove_useless_children(&mut self, root: UniIndex, live: &FxHashSet<BorTag>) -> u64 {
+        let mut removed = 0;
         // To avoid stack overflows, we roll our own stack.
         // Each element in the stack consists of the current tag, and the number of the
         // next child to be processed.
@@ -949,6 +958,7 @@ impl Tree {
                     if self.is_useless(*idx, live) {
                         // Delete `idx` node everywhere else.
                         self.remove_useless_node(*idx);
+                        removed += 1;
                         // And delete it from children_of_node.
                         false
                     } else {
@@ -956,6 +966,7 @@ impl Tree {
                             // `nextchild` is our grandchild, and will become our direct child.
                             // Delete the in-between node, `idx`.
                             self.remove_useless_node(*idx);
+                            removed += 1;
                             // Set the new child's parent.
                             self.nodes.get_mut(nextchild).unwrap().parent = Some(*tag);
                             // Save the new child in children_of_node.
@@ -973,6 +984,7 @@ impl Tree {
                 continue;
             }
         }
+        removed
     }
 }
 
@@ -1,5 +1,5 @@
 use std::cell::Cell;
-use std::time::{Duration, Instant as StdInstant};
+use std::time::{Duration, Instant as StdInstant, SystemTime};
 
 /// When using a virtual clock, this defines how many nanoseconds we pretend are passing for each
 /// basic block.
@@ -64,6 +64,9 @@ impl Instant {
 #[derive(Debug)]
 pub struct Clock {
     kind: ClockKind,
+    /// If set, the system time does not follow the host but advances along with this clock,
+    /// starting at the given time.
+    system_epoch: Option<SystemTime>,
 }
 
 #[derive(Debug)]
@@ -81,14 +84,17 @@ enum ClockKind {
 
 impl Clock {
     /// Create a new clock based on the availability of communication with the host.
-    pub fn new(communicate: bool) -> Self {
+    /// If `virtual_system_time` is set, the system time starts at the current host time and then
+    /// advances along with the monotone clock.
+    pub fn new(communicate: bool, virtual_system_time: bool) -> Self {
         let kind = if communicate {
             ClockKind::Host { epoch: StdInstant::now() }
         } else {
             ClockKind::Virtual { nanoseconds: 0.into() }
         };
+        let system_epoch = virtual_system_time.then(SystemTime::now);
 
-        Self { kind }
+        Self { kind, system_epoch }
     }
 
     /// Let the time pass for a small interval.
@@ -133,4 +139,15 @@ impl Clock {
                 Instant { kind: InstantKind::Virtual { nanoseconds: nanoseconds.get() } },
         }
     }
+
+    /// The current system time, i.e., the time since the Unix epoch.
+    pub fn system_now(&self) -> SystemTime {
+        match self.system_epoch {
+            Some(system_epoch) => {
+                let elapsed = self.now().duration_since(self.epoch());
+                system_epoch.checked_add(elapsed).expect("system time overflowed")
+            }
+            None => SystemTime::now(),
+        }
+    }
 }
@@ -41,6 +41,7 @@
 //! on the data-race detection code.
 
 use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::mem;
 
@@ -49,11 +50,11 @@ use rustc_ast::Mutability;
 use rustc_data_structures::fx::{FxHashMap, FxHashSet};
 use rustc_index::{Idx, IndexVec};
 use rustc_middle::mir;
-use rustc_middle::ty::Ty;
-use rustc_span::Span;
+use rustc_middle::ty::{Ty, TyCtxt};
+use rustc_span::{Span, SpanData};
 
+use super::schedule::SyncObject;
 use super::vector_clock::{VClock, VTimestamp, VectorIdx};
-use super::weak_memory::EvalContextExt as _;
 use crate::diagnostics::RacingOp;
 use crate::*;
 
@@ -122,8 +123,16 @@ pub(super) struct ThreadClockSet {
     /// Timestamps of the last SC fence performed by each
     /// thread, updated when this thread performs an SC read
     pub(super) read_seqcst: VClock,
+
+    /// The most recent timestamps of this thread, oldest first, each with the span of the
+    /// synchronization event that started it. This is only used for `-Zmiri-race-timeline`,
+    /// and at most `SYNC_HISTORY_LEN` timestamps are kept.
+    sync_history: VecDeque<VTimestamp>,
 }
 
+/// How many synchronization events of each thread are remembered for `-Zmiri-race-timeline`.
+const SYNC_HISTORY_LEN: usize = 16;
+
 impl ThreadClockSet {
     /// Apply the effects of a release fence to this
     /// set of thread vector clocks.
@@ -144,6 +153,10 @@ impl ThreadClockSet {
     #[inline]
     fn increment_clock(&mut self, index: VectorIdx, current_span: Span) {
         self.clock.increment_index(index, current_span);
+        if self.sync_history.len() == SYNC_HISTORY_LEN {
+            self.sync_history.pop_front();
+        }
+        self.sync_history.push_back(self.clock[index]);
     }
 
     /// Join the happens-before clock with that of
@@ -287,6 +300,16 @@ impl AccessType {
         msg
     }
 
+    /// The kind of access as named in ThreadSanitizer reports.
+    fn tsan_kind(self) -> &'static str {
+        match self {
+            AccessType::NaRead(_) => "read",
+            AccessType::NaWrite(_) => "write",
+            AccessType::AtomicLoad => "atomic read",
+            AccessType::AtomicStore | AccessType::AtomicRmw => "atomic write",
+        }
+    }
+
     fn is_atomic(self) -> bool {
         match self {
             AccessType::AtomicLoad | AccessType::AtomicStore | AccessType::AtomicRmw => true,
@@ -322,6 +345,14 @@ struct MemoryCellClocks {
     /// a deallocation of memory.
     write_type: NaWriteType,
 
+    /// How many non-atomic writes (not counting retags) happened since the allocation, saturating
+    /// at `u8::MAX`. This is only used to recognize racy lazy initialization in reports.
+    write_count: u8,
+
+    /// Whether the thread that did the first non-atomic write read this location before, like
+    /// the check of a lazy initialization does.
+    first_write_checked: bool,
+
     /// The vector-clock of all non-atomic reads that happened since the last non-atomic write
     /// (i.e., we join together the "singleton" clocks corresponding to each read). It is reset to
     /// zero on each write operation.
@@ -352,6 +383,8 @@ impl MemoryCellClocks {
             read: VClock::default(),
             write: (alloc_index, alloc),
             write_type: NaWriteType::Allocate,
+            write_count: 0,
+            first_write_checked: false,
             atomic_ops: None,
         }
     }
@@ -381,12 +414,14 @@ impl MemoryCellClocks {
     }
 
     /// Load or create the internal atomic memory metadata if it does not exist. Also ensures we do
-    /// not do mixed-size atomic accesses, and updates the recorded atomic access size.
+    /// not do mixed-size atomic accesses (unless `mixed_ok` is set), and updates the recorded
+    /// atomic access size.
     fn atomic_access(
         &mut self,
         thread_clocks: &ThreadClockSet,
         size: Size,
         write: bool,
+        mixed_ok: bool,
     ) -> Result<&mut AtomicMemoryCellClocks, DataRace> {
         match self.atomic_ops {
             Some(ref mut atomic) => {
@@ -404,6 +439,10 @@ impl MemoryCellClocks {
                     // sizes to mismatch, as long as no writes with a different size occur later.
                     atomic.size = None;
                     Ok(atomic)
+                } else if mixed_ok {
+                    // The race has already been reported as a warning; continue with the new size.
+                    atomic.size = Some(size);
+                    Ok(atomic)
                 } else {
                     Err(DataRace)
                 }
@@ -415,6 +454,12 @@ impl MemoryCellClocks {
         }
     }
 
+    /// The clock that a load reading from the store with the given `reads_from` clock
+    /// synchronizes with. `None` means the load reads from the latest store.
+    fn load_sync_clock<'a>(&'a self, reads_from: Option<&'a VClock>) -> Option<&'a VClock> {
+        reads_from.or_else(|| self.atomic().map(|atomic| &atomic.sync_vector))
+    }
+
     /// Update memory cell data-race tracking for atomic
     /// load acquire semantics, is a no-op if this memory was
     /// not used previously as atomic memory.
@@ -423,10 +468,12 @@ impl MemoryCellClocks {
         thread_clocks: &mut ThreadClockSet,
         index: VectorIdx,
         access_size: Size,
+        reads_from: Option<&VClock>,
+        mixed_ok: bool,
     ) -> Result<(), DataRace> {
-        self.atomic_read_detect(thread_clocks, index, access_size)?;
-        if let Some(atomic) = self.atomic() {
-            thread_clocks.clock.join(&atomic.sync_vector);
+        self.atomic_read_detect(thread_clocks, index, access_size, mixed_ok)?;
+        if let Some(sync_clock) = self.load_sync_clock(reads_from) {
+            thread_clocks.clock.join(sync_clock);
         }
         Ok(())
     }
@@ -434,15 +481,19 @@ impl MemoryCellClocks {
     /// Update memory cell data-race tracking for atomic
     /// load relaxed semantics, is a no-op if this memory was
     /// not used previously as atomic memory.
+    /// The clock is remembered so that a later acquire fence
+    /// synchronizes with the release fence preceding the store.
     fn load_relaxed(
         &mut self,
         thread_clocks: &mut ThreadClockSet,
         index: VectorIdx,
         access_size: Size,
+        reads_from: Option<&VClock>,
+        mixed_ok: bool,
     ) -> Result<(), DataRace> {
-        self.atomic_read_detect(thread_clocks, index, access_size)?;
-        if let Some(atomic) = self.atomic() {
-            thread_clocks.fence_acquire.join(&atomic.sync_vector);
+        self.atomic_read_detect(thread_clocks, index, access_size, mixed_ok)?;
+        if let Some(sync_clock) = self.load_sync_clock(reads_from) {
+            thread_clocks.fence_acquire.join(sync_clock);
         }
         Ok(())
     }
@@ -454,8 +505,9 @@ impl MemoryCellClocks {
         thread_clocks: &ThreadClockSet,
         index: VectorIdx,
         access_size: Size,
+        mixed_ok: bool,
     ) -> Result<(), DataRace> {
-        self.atomic_write_detect(thread_clocks, index, access_size)?;
+        self.atomic_write_detect(thread_clocks, index, access_size, mixed_ok)?;
         let atomic = self.atomic_mut_unwrap(); // initialized by `atomic_write_detect`
         atomic.sync_vector.clone_from(&thread_clocks.clock);
         Ok(())
@@ -468,8 +520,9 @@ impl MemoryCellClocks {
         thread_clocks: &ThreadClockSet,
         index: VectorIdx,
         access_size: Size,
+        mixed_ok: bool,
     ) -> Result<(), DataRace> {
-        self.atomic_write_detect(thread_clocks, index, access_size)?;
+        self.atomic_write_detect(thread_clocks, index, access_size, mixed_ok)?;
 
         // The handling of release sequences was changed in C++20 and so
         // the code here is different to the paper since now all relaxed
@@ -487,8 +540,9 @@ impl MemoryCellClocks {
         thread_clocks: &ThreadClockSet,
         index: VectorIdx,
         access_size: Size,
+        mixed_ok: bool,
     ) -> Result<(), DataRace> {
-        self.atomic_write_detect(thread_clocks, index, access_size)?;
+        self.atomic_write_detect(thread_clocks, index, access_size, mixed_ok)?;
         let atomic = self.atomic_mut_unwrap();
         atomic.sync_vector.join(&thread_clocks.clock);
         Ok(())
@@ -501,41 +555,49 @@ impl MemoryCellClocks {
         thread_clocks: &ThreadClockSet,
         index: VectorIdx,
         access_size: Size,
+        mixed_ok: bool,
     ) -> Result<(), DataRace> {
-        self.atomic_write_detect(thread_clocks, index, access_size)?;
+        self.atomic_write_detect(thread_clocks, index, access_size, mixed_ok)?;
         let atomic = self.atomic_mut_unwrap();
         atomic.sync_vector.join(&thread_clocks.fence_release);
         Ok(())
     }
 
     /// Detect data-races with an atomic read, caused by a non-atomic write that does
-    /// not happen-before the atomic-read.
+    /// not happen-before the atomic-read. If `mixed_ok` is set, races with non-atomic accesses
+    /// and mixed-size accesses have already been reported and are ignored.
     fn atomic_read_detect(
         &mut self,
         thread_clocks: &ThreadClockSet,
         index: VectorIdx,
         access_size: Size,
+        mixed_ok: bool,
     ) -> Result<(), DataRace> {
         trace!("Atomic read with vectors: {:#?} :: {:#?}", self, thread_clocks);
-        let atomic = self.atomic_access(thread_clocks, access_size, /*write*/ false)?;
+        let atomic =
+            self.atomic_access(thread_clocks, access_size, /*write*/ false, mixed_ok)?;
         atomic.read_vector.set_at_index(&thread_clocks.clock, index);
         // Make sure the last non-atomic write was before this access.
-        if self.write_was_before(&thread_clocks.clock) { Ok(()) } else { Err(DataRace) }
+        if mixed_ok || self.write_was_before(&thread_clocks.clock) { Ok(()) } else { Err(DataRace) }
     }
 
     /// Detect data-races with an atomic write, either with a non-atomic read or with
-    /// a non-atomic write.
+    /// a non-atomic write. `mixed_ok` works like for `atomic_read_detect`.
     fn atomic_write_detect(
         &mut self,
         thread_clocks: &ThreadClockSet,
         index: VectorIdx,
         access_size: Size,
+        mixed_ok: bool,
     ) -> Result<(), DataRace> {
         trace!("Atomic write with vectors: {:#?} :: {:#?}", self, thread_clocks);
-        let atomic = self.atomic_access(thread_clocks, access_size, /*write*/ true)?;
+        let atomic =
+            self.atomic_access(thread_clocks, access_size, /*write*/ true, mixed_ok)?;
         atomic.write_vector.set_at_index(&thread_clocks.clock, index);
         // Make sure the last non-atomic write and all non-atomic reads were before this access.
-        if self.write_was_before(&thread_clocks.clock) && self.read <= thread_clocks.clock {
+        if mixed_ok
+            || (self.write_was_before(&thread_clocks.clock) && self.read <= thread_clocks.clock)
+        {
             Ok(())
         } else {
             Err(DataRace)
@@ -556,20 +618,22 @@ impl MemoryCellClocks {
             thread_clocks.clock.index_mut(index).span = current_span;
         }
         thread_clocks.clock.index_mut(index).set_read_type(read_type);
-        if self.write_was_before(&thread_clocks.clock) {
-            // We must be ordered-after all atomic writes.
-            let race_free = if let Some(atomic) = self.atomic() {
-                atomic.write_vector <= thread_clocks.clock
-            } else {
-                true
-            };
-            self.read.set_at_index(&thread_clocks.clock, index);
-            if race_free { Ok(()) } else { Err(DataRace) }
+        // We must be ordered-after the last write and all atomic writes.
+        let race_free = self.write_was_before(&thread_clocks.clock)
+            && self.atomic().is_none_or(|atomic| atomic.write_vector <= thread_clocks.clock);
+        if race_free {
+            self.record_read(thread_clocks, index);
+            Ok(())
         } else {
             Err(DataRace)
         }
     }
 
+    /// Record a non-atomic read at the current memory cell, as if it did not race.
+    fn record_read(&mut self, thread_clocks: &ThreadClockSet, index: VectorIdx) {
+        self.read.set_at_index(&thread_clocks.clock, index);
+    }
+
     /// Detect races for non-atomic write operations at the current memory cell
     /// returns true if a data-race is detected.
     fn write_race_detect(
@@ -583,25 +647,40 @@ impl MemoryCellClocks {
         if !current_span.is_dummy() {
             thread_clocks.clock.index_mut(index).span = current_span;
         }
-        if self.write_was_before(&thread_clocks.clock) && self.read <= thread_clocks.clock {
-            let race_free = if let Some(atomic) = self.atomic() {
+        // We must be ordered-after the last write, all reads, and all atomic accesses.
+        let race_free = self.write_was_before(&thread_clocks.clock)
+            && self.read <= thread_clocks.clock
+            && self.atomic().is_none_or(|atomic| {
                 atomic.write_vector <= thread_clocks.clock
                     && atomic.read_vector <= thread_clocks.clock
-            } else {
-                true
-            };
-            self.write = (index, thread_clocks.clock[index]);
-            self.write_type = write_type;
-            if race_free {
-                self.read.set_zero_vector();
-                Ok(())
-            } else {
-                Err(DataRace)
-            }
+            });
+        if race_free {
+            self.record_write(thread_clocks, index, write_type);
+            Ok(())
         } else {
             Err(DataRace)
         }
     }
+
+    /// Record a non-atomic write at the current memory cell, as if it did not race.
+    fn record_write(
+        &mut self,
+        thread_clocks: &ThreadClockSet,
+        index: VectorIdx,
+        write_type: NaWriteType,
+    ) {
+        self.write = (index, thread_clocks.clock[index]);
+        self.write_type = write_type;
+        if write_type == NaWriteType::Write {
+            if self.write_count == 0 {
+                self.first_write_checked = self.read[index] != VTimestamp::ZERO;
+            }
+            self.write_count = self.write_count.saturating_add(1);
+        }
+        // The non-atomic reads either happen-before this write or raced with it, which has been
+        // reported already, so we can forget about them.
+        self.read.set_zero_vector();
+    }
 }
 
 /// Evaluation context extensions.
@@ -621,9 +700,18 @@ pub trait EvalContextExt<'tcx>: MiriInterpCxExt<'tcx> {
         // the *value* (including the associated provenance if this is an AtomicPtr) at this location.
         // Only metadata on the location itself is used.
         let scalar = this.allow_data_races_ref(move |this| this.read_scalar(place))?;
-        let buffered_scalar = this.buffered_atomic_read(place, atomic, scalar, || {
-            this.validate_atomic_load(place, atomic)
+        let buffered_scalar = this.buffered_atomic_read(place, atomic, scalar, |reads_from| {
+            this.validate_atomic_load(place, atomic, reads_from)
         })?;
+        if let Some(data_race) = &this.machine.data_race {
+            data_race.note_spin_load(
+                &this.machine.threads,
+                place.ptr().addr(),
+                this.machine.current_span(),
+                atomic,
+                buffered_scalar,
+            );
+        }
         interp_ok(buffered_scalar.ok_or_else(|| err_ub!(InvalidUninitBytes(None)))?)
     }
 
@@ -768,7 +856,7 @@ pub trait EvalContextExt<'tcx>: MiriInterpCxExt<'tcx> {
             this.validate_atomic_rmw(place, success)?;
             this.buffered_atomic_rmw(new, place, success, old.to_scalar())?;
         } else {
-            this.validate_atomic_load(place, fail)?;
+            this.validate_atomic_load(place, fail, None)?;
             // A failed compare exchange is equivalent to a load, reading from the latest store
             // in the modification order.
             // Since `old` is only a value and not the store element, we need to separately
@@ -783,8 +871,12 @@ pub trait EvalContextExt<'tcx>: MiriInterpCxExt<'tcx> {
     /// Update the data-race detector for an atomic fence on the current thread.
     fn atomic_fence(&mut self, atomic: AtomicFenceOrd) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
+        this.note_sync_access(SyncObject::Global, /* write */ true);
         let current_span = this.machine.current_span();
         if let Some(data_race) = &mut this.machine.data_race {
+            if atomic != AtomicFenceOrd::Release {
+                data_race.note_spin_acquire(&this.machine.threads);
+            }
             data_race.maybe_perform_sync_operation(
                 &this.machine.threads,
                 current_span,
@@ -806,6 +898,13 @@ pub trait EvalContextExt<'tcx>: MiriInterpCxExt<'tcx> {
                         data_race.last_sc_fence.borrow_mut().set_at_index(&clocks.clock, index);
                         clocks.fence_seqcst.join(&data_race.last_sc_fence.borrow());
                         clocks.write_seqcst.join(&data_race.last_sc_write.borrow());
+                        // On x86, an SC fence is an `mfence`, which drains the store buffer.
+                        if data_race.weak_memory_model == WeakMemoryModel::X86Tso {
+                            data_race
+                                .flushed_stores
+                                .borrow_mut()
+                                .set_at_index(&clocks.clock, index);
+                        }
                     }
 
                     // Increment timestamp in case of release semantics.
@@ -835,6 +934,7 @@ pub trait EvalContextExt<'tcx>: MiriInterpCxExt<'tcx> {
     /// The closure will only be invoked if data race handling is on.
     fn release_clock<R>(&self, callback: impl FnOnce(&VClock) -> R) -> Option<R> {
         let this = self.eval_context_ref();
+        this.note_sync_access(SyncObject::Global, /* write */ true);
         Some(this.machine.data_race.as_ref()?.release_clock(&this.machine.threads, callback))
     }
 
@@ -842,10 +942,25 @@ pub trait EvalContextExt<'tcx>: MiriInterpCxExt<'tcx> {
     /// the moment when that clock snapshot was taken via `release_clock`.
     fn acquire_clock(&self, clock: &VClock) {
         let this = self.eval_context_ref();
+        this.note_sync_access(SyncObject::Global, /* write */ true);
         if let Some(data_race) = &this.machine.data_race {
             data_race.acquire_clock(clock, &this.machine.threads);
         }
     }
+
+    /// Tell the schedule explorer that the active thread synchronized on `object`.
+    fn note_sync_access(&self, object: SyncObject, write: bool) {
+        let this = self.eval_context_ref();
+        if !this.machine.threads.is_exploring_schedules() {
+            return;
+        }
+        let clock = this
+            .machine
+            .data_race
+            .as_ref()
+            .map(|data_race| data_race.active_thread_clock(&this.machine.threads));
+        this.machine.threads.note_sync_access(object, write, clock);
+    }
 }
 
 /// Vector clock metadata for a logical memory allocation.
@@ -853,6 +968,14 @@ pub trait EvalContextExt<'tcx>: MiriInterpCxExt<'tcx> {
 pub struct VClockAlloc {
     /// Assigning each byte a MemoryCellClocks.
     alloc_ranges: RefCell<RangeMap<MemoryCellClocks>>,
+
+    /// The size of the chunks that non-atomic accesses are tracked in, see
+    /// `-Zmiri-race-granularity`. This is 1 unless the allocation is large enough to be tracked
+    /// coarsely.
+    granularity: u64,
+
+    /// The size of the allocation.
+    len: Size,
 }
 
 impl VisitProvenance for VClockAlloc {
@@ -899,12 +1022,43 @@ impl VClockAlloc {
             | MemoryKind::CallerLocation =>
                 (VTimestamp::ZERO, global.thread_index(ThreadId::MAIN_THREAD)),
         };
+        let granularity = match global.race_granularity {
+            Some((granularity, min_size)) if len.bytes() >= min_size => granularity,
+            _ => 1,
+        };
         VClockAlloc {
             alloc_ranges: RefCell::new(RangeMap::new(
                 len,
                 MemoryCellClocks::new(alloc_timestamp, alloc_index),
             )),
+            granularity,
+            len,
+        }
+    }
+
+    /// The range whose clocks a non-atomic access to `range` uses: with a coarse granularity,
+    /// every chunk it touches is tracked as a whole.
+    fn tracked_range(&self, range: AllocRange) -> AllocRange {
+        if self.granularity == 1 || range.size == Size::ZERO {
+            return range;
         }
+        let start = range.start.bytes() / self.granularity * self.granularity;
+        let end = range.end().bytes().div_ceil(self.granularity) * self.granularity;
+        let end = end.min(self.len.bytes());
+        alloc_range(Size::from_bytes(start), Size::from_bytes(end - start))
+    }
+
+    /// The clock that an acquire of the latest store to the given atomic location synchronizes
+    /// with. This is recorded by the weak memory emulation for each store, so that loads which
+    /// read an older store synchronize with that store instead of the latest one.
+    pub(super) fn sync_clock(&self, range: AllocRange) -> VClock {
+        let mut clock = VClock::default();
+        for (_offset, mem_clocks) in self.alloc_ranges.borrow().iter(range.start, range.size) {
+            if let Some(atomic) = mem_clocks.atomic() {
+                clock.join(&atomic.sync_vector);
+            }
+        }
+        clock
     }
 
     // Find an index, if one exists where the value
@@ -944,17 +1098,28 @@ impl VClockAlloc {
     /// return info about the memory location the data-race
     /// occurred in. The `ty` parameter is used for diagnostics, letting
     /// the user know which type was involved in the access.
+    ///
+    /// With `-Zmiri-mixed-access-warnings`, races between mixed-size atomic accesses and between
+    /// atomic and non-atomic accesses are not an error; instead, this returns the warning to emit,
+    /// or `None` if the same pair of accesses was already reported.
     #[cold]
     #[inline(never)]
     fn report_data_race<'tcx>(
         global: &GlobalState,
         thread_mgr: &ThreadManager<'_>,
+        tcx: TyCtxt<'_>,
         mem_clocks: &MemoryCellClocks,
         access: AccessType,
         access_size: Size,
         ptr_dbg: interpret::Pointer<AllocId>,
         ty: Option<Ty<'_>>,
-    ) -> InterpResult<'tcx> {
+    ) -> InterpResult<'tcx, Option<NonHaltingDiagnostic>> {
+        if let Some(suppressions) = &global.race_suppressions
+            && let Some(rule) = Self::find_suppression(suppressions, thread_mgr, tcx)
+        {
+            *global.suppressed_races.borrow_mut().entry(rule).or_default() += 1;
+            return interp_ok(None);
+        }
         let (active_index, active_clocks) = global.active_thread_state(thread_mgr);
         let mut other_size = None; // if `Some`, this was a size-mismatch race
         let write_clock;
@@ -1000,7 +1165,31 @@ impl VClockAlloc {
         // Load elaborated thread information about the racing thread actions.
         let active_thread_info = global.print_thread_metadata(thread_mgr, active_index);
         let other_thread_info = global.print_thread_metadata(thread_mgr, other_thread);
+        let other_thread_id = global.vector_info.borrow()[other_thread];
         let involves_non_atomic = !access.is_atomic() || !other_access.is_atomic();
+        let witness = if global.witness_log.is_some() {
+            global.race_witness(
+                thread_mgr,
+                other_thread_id,
+                other_clock[other_thread],
+                &active_thread_info,
+                &other_thread_info,
+            )
+        } else {
+            Vec::new()
+        };
+        let timeline = if global.race_timeline {
+            let vector_clocks = global.vector_clocks.borrow();
+            Self::race_timeline(
+                &vector_clocks[other_thread].sync_history,
+                active_clocks.clock[other_thread],
+                other_clock[other_thread],
+                &active_thread_info,
+                &other_thread_info,
+            )
+        } else {
+            Vec::new()
+        };
 
         // Throw the data-race detection.
         let extra = if other_size.is_some() {
@@ -1011,22 +1200,135 @@ impl VClockAlloc {
         } else {
             None
         };
-        Err(err_machine_stop!(TerminationInfo::DataRace {
+        // A read racing with the only write to a location that is not used atomically, where the
+        // writing thread checked the location first, is typically a value that is lazily
+        // initialized without synchronization.
+        let lazy_init = matches!(access, AccessType::NaRead(NaReadType::Read))
+            && matches!(other_access, AccessType::NaWrite(NaWriteType::Write))
+            && mem_clocks.write_count == 1
+            && mem_clocks.first_write_checked
+            && mem_clocks.atomic().is_none();
+        let info = TerminationInfo::DataRace {
             involves_non_atomic,
             extra,
+            lazy_init,
             retag_explain: access.is_retag() || other_access.is_retag(),
             ptr: ptr_dbg,
             op1: RacingOp {
                 action: other_access.description(None, other_size),
                 thread_info: other_thread_info,
                 span: other_clock.as_slice()[other_thread.index()].span_data(),
+                kind: other_access.tsan_kind(),
+                size: other_size.filter(|size| *size != Size::ZERO),
+                thread: other_thread_id,
             },
             op2: RacingOp {
                 action: access.description(ty, other_size.map(|_| access_size)),
                 thread_info: active_thread_info,
                 span: active_clocks.clock.as_slice()[active_index.index()].span_data(),
+                kind: access.tsan_kind(),
+                size: Some(access_size),
+                thread: thread_mgr.active_thread(),
             },
-        }))?
+            timeline,
+            witness,
+        };
+        // A non-atomic access that races with both atomic and non-atomic accesses is reported as a
+        // race with the atomic access above, but it must still stop the program.
+        let na_race = !access.is_atomic()
+            && (mem_clocks.write.1 > active_clocks.clock[mem_clocks.write.0]
+                || (!access.is_read()
+                    && Self::find_gt_index(&mem_clocks.read, &active_clocks.clock).is_some()));
+        let mixed = other_size.is_some() || access.is_atomic() != other_access.is_atomic();
+        if global.mixed_access_warnings && mixed && !na_race {
+            let TerminationInfo::DataRace { op1, op2, .. } = &info else { unreachable!() };
+            if !global.reported_mixed_accesses.borrow_mut().insert((op1.span, op2.span)) {
+                return interp_ok(None);
+            }
+            let msg = info.to_string();
+            let TerminationInfo::DataRace { op1, extra, .. } = info else { unreachable!() };
+            return interp_ok(Some(NonHaltingDiagnostic::MixedAccessRace {
+                msg,
+                earlier: op1.span,
+                extra,
+            }));
+        }
+        throw_machine_stop!(info)
+    }
+
+    /// The first rule of `-Zmiri-race-suppressions` that matches the path of a function or the
+    /// name of a crate on the stack of the active thread.
+    fn find_suppression(
+        suppressions: &RaceSuppressions,
+        thread_mgr: &ThreadManager<'_>,
+        tcx: TyCtxt<'_>,
+    ) -> Option<usize> {
+        let names: Vec<String> = thread_mgr
+            .active_thread_stack()
+            .iter()
+            .flat_map(|frame| {
+                let def_id = frame.instance().def_id();
+                [tcx.def_path_str(def_id), tcx.crate_name(def_id.krate).to_string()]
+            })
+            .collect();
+        suppressions.find(names.iter().map(String::as_str))
+    }
+
+    /// Explain, for `-Zmiri-race-timeline`, why nothing orders the earlier access of the other
+    /// thread, at `other_time` of that thread, before the access of the active thread, which has
+    /// seen the other thread up to `seen`. `other_history` are the most recent synchronization
+    /// events of the other thread.
+    fn race_timeline(
+        other_history: &VecDeque<VTimestamp>,
+        seen: VTimestamp,
+        other_time: VTimestamp,
+        active_thread_info: &str,
+        other_thread_info: &str,
+    ) -> Vec<(Option<SpanData>, String)> {
+        // Every synchronization event of a thread starts a new timestamp of that thread. So the
+        // event that the active thread last synchronized with started `seen + 1`, and the first
+        // event after the other access started `other_time + 1`.
+        let event_starting =
+            |time: u32| other_history.iter().find(|timestamp| timestamp.time() == time);
+        let mut timeline = Vec::new();
+        if seen.time() == 0 {
+            timeline.push((
+                None,
+                format!(
+                    "{active_thread_info} has not synchronized with {other_thread_info} at all, so nothing orders (1) before (2)"
+                ),
+            ));
+        } else if let Some(event) = event_starting(seen.time() + 1) {
+            timeline.push((
+                Some(event.span_data()),
+                format!(
+                    "before (2), {active_thread_info} last synchronized (directly or through other threads) with this event of {other_thread_info}, which happened before (1)"
+                ),
+            ));
+        } else {
+            timeline.push((
+                None,
+                format!(
+                    "before (2), {active_thread_info} last synchronized with an event of {other_thread_info} that happened before (1), too long ago to be shown"
+                ),
+            ));
+        }
+        if let Some(event) = event_starting(other_time.time() + 1) {
+            timeline.push((
+                Some(event.span_data()),
+                format!(
+                    "after (1), {other_thread_info} synchronized here, but {active_thread_info} did not synchronize with this event or any later one before (2)"
+                ),
+            ));
+        } else {
+            timeline.push((
+                None,
+                format!(
+                    "{other_thread_info} did not perform any synchronization event after (1), so no event of {active_thread_info} can be ordered after (1)"
+                ),
+            ));
+        }
+        timeline
     }
 
     /// Detect data-races for an unsynchronized read operation. It will not perform
@@ -1048,25 +1350,50 @@ impl VClockAlloc {
         if !global.race_detecting() {
             return interp_ok(());
         }
+        global.note_witness_access(&machine.threads, current_span);
+        let tracked_range = self.tracked_range(access_range);
+        if let Some(spin_loop) = global.ended_spin_loop(&machine.threads) {
+            // Reading data that another thread wrote right after a spin loop with relaxed loads
+            // is a typical sign of missing synchronization.
+            let index = global.active_thread_index(&machine.threads);
+            let written_by_other_thread = self
+                .alloc_ranges
+                .borrow()
+                .iter(tracked_range.start, tracked_range.size)
+                .any(|(_, mem_clocks)| mem_clocks.write.0 != index);
+            if written_by_other_thread && global.reported_spin_loops.borrow_mut().insert(spin_loop)
+            {
+                machine.emit_diagnostic(NonHaltingDiagnostic::RelaxedSpinLoop {
+                    spin_loop: spin_loop.data(),
+                });
+            }
+        }
         let (index, mut thread_clocks) = global.active_thread_state_mut(&machine.threads);
         let mut alloc_ranges = self.alloc_ranges.borrow_mut();
         for (mem_clocks_range, mem_clocks) in
-            alloc_ranges.iter_mut(access_range.start, access_range.size)
+            alloc_ranges.iter_mut(tracked_range.start, tracked_range.size)
         {
             if let Err(DataRace) =
                 mem_clocks.read_race_detect(&mut thread_clocks, index, read_type, current_span)
             {
                 drop(thread_clocks);
                 // Report data-race.
-                return Self::report_data_race(
+                if let Some(warning) = Self::report_data_race(
                     global,
                     &machine.threads,
+                    machine.tcx,
                     mem_clocks,
                     AccessType::NaRead(read_type),
                     access_range.size,
                     interpret::Pointer::new(alloc_id, Size::from_bytes(mem_clocks_range.start)),
                     ty,
-                );
+                )? {
+                    machine.emit_diagnostic(warning);
+                }
+                // We only get here for a race that was reported as a warning or suppressed, so
+                // perform the read as if it was fine.
+                thread_clocks = global.active_thread_state_mut(&machine.threads).1;
+                mem_clocks.record_read(&thread_clocks, index);
             }
         }
         interp_ok(())
@@ -1086,28 +1413,37 @@ impl VClockAlloc {
         machine: &mut MiriMachine<'_>,
     ) -> InterpResult<'tcx> {
         let current_span = machine.current_span();
-        let global = machine.data_race.as_mut().unwrap();
+        let global = machine.data_race.as_ref().unwrap();
         if !global.race_detecting() {
             return interp_ok(());
         }
+        global.note_witness_access(&machine.threads, current_span);
         let (index, mut thread_clocks) = global.active_thread_state_mut(&machine.threads);
+        let tracked_range = self.tracked_range(access_range);
         for (mem_clocks_range, mem_clocks) in
-            self.alloc_ranges.get_mut().iter_mut(access_range.start, access_range.size)
+            self.alloc_ranges.get_mut().iter_mut(tracked_range.start, tracked_range.size)
         {
             if let Err(DataRace) =
                 mem_clocks.write_race_detect(&mut thread_clocks, index, write_type, current_span)
             {
                 drop(thread_clocks);
                 // Report data-race
-                return Self::report_data_race(
+                if let Some(warning) = Self::report_data_race(
                     global,
                     &machine.threads,
+                    machine.tcx,
                     mem_clocks,
                     AccessType::NaWrite(write_type),
                     access_range.size,
                     interpret::Pointer::new(alloc_id, Size::from_bytes(mem_clocks_range.start)),
                     ty,
-                );
+                )? {
+                    machine.emit_diagnostic(warning);
+                }
+                // We only get here for a race that was reported as a warning or suppressed, so
+                // perform the write as if it was fine.
+                thread_clocks = global.active_thread_state_mut(&machine.threads).1;
+                mem_clocks.record_write(&thread_clocks, index, write_type);
             }
         }
         interp_ok(())
@@ -1209,6 +1545,10 @@ impl FrameState {
             // Convert the local's clocks into memory clocks.
             mem_clocks.write = (index, local_clocks.write);
             mem_clocks.write_type = local_clocks.write_type;
+            // We do not know how often the local was written.
+            if local_clocks.write_type == NaWriteType::Write {
+                mem_clocks.write_count = u8::MAX;
+            }
             mem_clocks.read = VClock::new_with_index(index, local_clocks.read);
         }
     }
@@ -1273,9 +1613,16 @@ trait EvalContextPrivExt<'tcx>: MiriInterpCxExt<'tcx> {
         // <https://github.com/rust-lang/miri/pull/2464#discussion_r939636130> for details.
         // We avoid `get_ptr_alloc` since we do *not* want to run the access hooks -- the actual
         // access will happen later.
-        let (alloc_id, _offset, _prov) = this
+        let (alloc_id, offset, _prov) = this
             .ptr_try_get_alloc_id(place.ptr(), 0)
             .expect("there are no zero-sized atomic accesses");
+        this.note_sync_access(
+            SyncObject::Location(alloc_id, offset),
+            !matches!(access_type, AtomicAccessType::Load(_)),
+        );
+        if this.machine.preempt_on_atomics {
+            this.machine.threads.yield_active_thread();
+        }
         if this.get_alloc_mutability(alloc_id)? == Mutability::Not {
             // See if this is fine.
             match access_type {
@@ -1311,21 +1658,36 @@ trait EvalContextPrivExt<'tcx>: MiriInterpCxExt<'tcx> {
 
     /// Update the data-race detector for an atomic read occurring at the
     /// associated memory-place and on the current thread.
+    /// `reads_from` is the sync clock of the store that is read, if that is
+    /// not the latest store to this location.
     fn validate_atomic_load(
         &self,
         place: &MPlaceTy<'tcx>,
         atomic: AtomicReadOrd,
+        reads_from: Option<&VClock>,
     ) -> InterpResult<'tcx> {
         let this = self.eval_context_ref();
         this.validate_atomic_op(
             place,
             atomic,
             AccessType::AtomicLoad,
-            move |memory, clocks, index, atomic| {
+            move |memory, clocks, index, atomic, mixed_ok| {
                 if atomic == AtomicReadOrd::Relaxed {
-                    memory.load_relaxed(&mut *clocks, index, place.layout.size)
+                    memory.load_relaxed(
+                        &mut *clocks,
+                        index,
+                        place.layout.size,
+                        reads_from,
+                        mixed_ok,
+                    )
                 } else {
-                    memory.load_acquire(&mut *clocks, index, place.layout.size)
+                    memory.load_acquire(
+                        &mut *clocks,
+                        index,
+                        place.layout.size,
+                        reads_from,
+                        mixed_ok,
+                    )
                 }
             },
         )
@@ -1343,11 +1705,11 @@ trait EvalContextPrivExt<'tcx>: MiriInterpCxExt<'tcx> {
             place,
             atomic,
             AccessType::AtomicStore,
-            move |memory, clocks, index, atomic| {
+            move |memory, clocks, index, atomic, mixed_ok| {
                 if atomic == AtomicWriteOrd::Relaxed {
-                    memory.store_relaxed(clocks, index, place.layout.size)
+                    memory.store_relaxed(clocks, index, place.layout.size, mixed_ok)
                 } else {
-                    memory.store_release(clocks, index, place.layout.size)
+                    memory.store_release(clocks, index, place.layout.size, mixed_ok)
                 }
             },
         )
@@ -1364,20 +1726,23 @@ trait EvalContextPrivExt<'tcx>: MiriInterpCxExt<'tcx> {
         let acquire = matches!(atomic, Acquire | AcqRel | SeqCst);
         let release = matches!(atomic, Release | AcqRel | SeqCst);
         let this = self.eval_context_mut();
+        if acquire && let Some(data_race) = &this.machine.data_race {
+            data_race.note_spin_acquire(&this.machine.threads);
+        }
         this.validate_atomic_op(
             place,
             atomic,
             AccessType::AtomicRmw,
-            move |memory, clocks, index, _| {
+            move |memory, clocks, index, _, mixed_ok| {
                 if acquire {
-                    memory.load_acquire(clocks, index, place.layout.size)?;
+                    memory.load_acquire(clocks, index, place.layout.size, None, mixed_ok)?;
                 } else {
-                    memory.load_relaxed(clocks, index, place.layout.size)?;
+                    memory.load_relaxed(clocks, index, place.layout.size, None, mixed_ok)?;
                 }
                 if release {
-                    memory.rmw_release(clocks, index, place.layout.size)
+                    memory.rmw_release(clocks, index, place.layout.size, mixed_ok)
                 } else {
-                    memory.rmw_relaxed(clocks, index, place.layout.size)
+                    memory.rmw_relaxed(clocks, index, place.layout.size, mixed_ok)
                 }
             },
         )
@@ -1394,6 +1759,7 @@ trait EvalContextPrivExt<'tcx>: MiriInterpCxExt<'tcx> {
             &mut ThreadClockSet,
             VectorIdx,
             A,
+            bool,
         ) -> Result<(), DataRace>,
     ) -> InterpResult<'tcx> {
         let this = self.eval_context_ref();
@@ -1416,19 +1782,31 @@ trait EvalContextPrivExt<'tcx>: MiriInterpCxExt<'tcx> {
         );
 
         let current_span = this.machine.current_span();
+        if data_race.race_detecting() {
+            data_race.note_witness_access(&this.machine.threads, current_span);
+        }
         // Perform the atomic operation.
         data_race.maybe_perform_sync_operation(
             &this.machine.threads,
             current_span,
-            |index, mut thread_clocks| {
+            |index, thread_clocks| {
+                // Rebind the clocks, so that they can be released and re-acquired around reporting
+                // a race.
+                let mut thread_clocks = thread_clocks;
                 for (mem_clocks_range, mem_clocks) in
                     alloc_meta.alloc_ranges.borrow_mut().iter_mut(base_offset, size)
                 {
-                    if let Err(DataRace) = op(mem_clocks, &mut thread_clocks, index, atomic) {
+                    if let Err(DataRace) =
+                        op(mem_clocks, &mut thread_clocks, index, atomic, /*mixed_ok*/ false)
+                    {
                         mem::drop(thread_clocks);
-                        return VClockAlloc::report_data_race(
+                        // Races of atomic accesses always mix sizes or atomicity, so with
+                        // `-Zmiri-mixed-access-warnings` they do not stop the program. Neither do
+                        // suppressed races.
+                        if let Some(warning) = VClockAlloc::report_data_race(
                             data_race,
                             &this.machine.threads,
+                            *this.tcx,
                             mem_clocks,
                             access,
                             place.layout.size,
@@ -1437,8 +1815,13 @@ trait EvalContextPrivExt<'tcx>: MiriInterpCxExt<'tcx> {
                                 Size::from_bytes(mem_clocks_range.start),
                             ),
                             None,
-                        )
-                        .map(|_| true);
+                        )? {
+                            this.machine.emit_diagnostic(warning);
+                        }
+                        // Perform the access as if it was fine.
+                        thread_clocks = data_race.active_thread_state_mut(&this.machine.threads).1;
+                        op(mem_clocks, &mut thread_clocks, index, atomic, /*mixed_ok*/ true)
+                            .expect("atomic accesses only race with mixed accesses");
                     }
                 }
 
@@ -1529,6 +1912,75 @@ pub struct GlobalState {
 
     /// Track when an outdated (weak memory) load happens.
     pub track_outdated_loads: bool,
+
+    /// Which weak memory behaviors the weak memory emulation may produce.
+    pub(super) weak_memory_model: WeakMemoryModel,
+
+    /// Under `WeakMemoryModel::X86Tso`, the timestamp of the last store of each thread
+    /// that has left the store buffer of that thread and is visible to all threads.
+    flushed_stores: RefCell<VClock>,
+
+    /// Explain in data race reports which synchronization failed to order the accesses.
+    pub race_timeline: bool,
+
+    /// If `Some((granularity, min_size))`, non-atomic accesses to allocations of at least
+    /// `min_size` bytes are tracked in chunks of `granularity` bytes.
+    race_granularity: Option<(u64, u64)>,
+
+    /// Report races between mixed-size atomic accesses and between atomic and non-atomic
+    /// accesses as warnings instead of errors.
+    mixed_access_warnings: bool,
+
+    /// The pairs of (earlier, later) accesses that were already reported as warnings.
+    reported_mixed_accesses: RefCell<FxHashSet<(SpanData, SpanData)>>,
+
+    /// Data races that are not reported.
+    race_suppressions: Option<RaceSuppressions>,
+
+    /// For `-Zmiri-race-witness`, the most recent stretches of the execution in which a single
+    /// thread performed memory accesses, oldest first.
+    witness_log: Option<RefCell<VecDeque<WitnessSegment>>>,
+
+    /// How many races each rule of `race_suppressions`, by index, suppressed.
+    suppressed_races: RefCell<FxHashMap<usize, u64>>,
+
+    /// For `-Zmiri-spin-loop-warnings`, the latest relaxed loads of each thread.
+    spin_loops: Option<RefCell<FxHashMap<ThreadId, SpinLoop>>>,
+
+    /// The spin loops that were already reported, by the location of their load.
+    reported_spin_loops: RefCell<FxHashSet<Span>>,
+}
+
+/// The number of segments that `-Zmiri-race-witness` remembers.
+const WITNESS_LOG_LIMIT: usize = 1024;
+
+/// The number of segments between the two racing accesses that a witness shows at most.
+const WITNESS_MAX_STEPS: usize = 8;
+
+/// A stretch of the execution in which a single thread performed memory accesses, without
+/// accesses of other threads in between.
+#[derive(Clone, Debug)]
+struct WitnessSegment {
+    thread: ThreadId,
+    /// The timestamps of the thread at its first and last access in this segment.
+    first_time: VTimestamp,
+    last_time: VTimestamp,
+    /// Where the thread performed accesses in this segment.
+    spans: FxHashSet<Span>,
+    /// Where the thread performed its last access in this segment.
+    last_span: Span,
+}
+
+/// The relaxed loads that the same code performs on the same location one after the other, like
+/// a loop that waits for the value to change.
+#[derive(Clone, Debug)]
+struct SpinLoop {
+    addr: Size,
+    span: Span,
+    /// The value returned by the last load.
+    value: Option<Scalar>,
+    /// Whether a load returned a different value than the one before, which ends the loop.
+    ended: bool,
 }
 
 impl VisitProvenance for GlobalState {
@@ -1551,6 +2003,17 @@ impl GlobalState {
             last_sc_fence: RefCell::new(VClock::default()),
             last_sc_write: RefCell::new(VClock::default()),
             track_outdated_loads: config.track_outdated_loads,
+            weak_memory_model: config.weak_memory_model,
+            flushed_stores: RefCell::new(VClock::default()),
+            race_timeline: config.race_timeline,
+            race_granularity: config.race_granularity,
+            mixed_access_warnings: config.mixed_access_warnings,
+            reported_mixed_accesses: RefCell::new(FxHashSet::default()),
+            race_suppressions: config.race_suppressions.clone(),
+            suppressed_races: RefCell::new(FxHashMap::default()),
+            witness_log: config.race_witness.then(RefCell::default),
+            spin_loops: config.spin_loop_warnings.then(RefCell::default),
+            reported_spin_loops: RefCell::new(FxHashSet::default()),
         };
 
         // Setup the main-thread since it is not explicitly created:
@@ -1631,7 +2094,9 @@ impl GlobalState {
         let created_index = if let Some(reuse_index) = self.find_vector_index_reuse_candidate() {
             // Now re-configure the re-use candidate, increment the clock
             // for the new sync use of the vector.
+            // The history of the old thread is of no use for the new one.
             let vector_clocks = self.vector_clocks.get_mut();
+            vector_clocks[reuse_index].sync_history.clear();
             vector_clocks[reuse_index].increment_clock(reuse_index, current_span);
 
             // Locate the old thread the vector was associated with and update
@@ -1761,7 +2226,11 @@ impl GlobalState {
 
     /// Internal utility to identify a thread stored internally
     /// returns the id and the name for better diagnostics.
-    fn print_thread_metadata(&self, thread_mgr: &ThreadManager<'_>, vector: VectorIdx) -> String {
+    pub(super) fn print_thread_metadata(
+        &self,
+        thread_mgr: &ThreadManager<'_>,
+        vector: VectorIdx,
+    ) -> String {
         let thread = self.vector_info.borrow()[vector];
         let thread_name = thread_mgr.get_thread_display_name(thread);
         format!("thread `{thread_name}`")
@@ -1772,6 +2241,7 @@ impl GlobalState {
     /// As this is an acquire operation, the thread timestamp is not
     /// incremented.
     pub fn acquire_clock<'tcx>(&self, clock: &VClock, threads: &ThreadManager<'tcx>) {
+        self.note_spin_acquire(threads);
         let thread = threads.active_thread();
         let (_, mut clocks) = self.thread_state_mut(thread);
         clocks.clock.join(clock);
@@ -1813,13 +2283,18 @@ impl GlobalState {
     /// Load the vector index used by the given thread as well as the set of vector clocks
     /// used by the thread.
     #[inline]
-    fn thread_state(&self, thread: ThreadId) -> (VectorIdx, Ref<'_, ThreadClockSet>) {
+    pub(super) fn thread_state(&self, thread: ThreadId) -> (VectorIdx, Ref<'_, ThreadClockSet>) {
         let index = self.thread_index(thread);
         let ref_vector = self.vector_clocks.borrow();
         let clocks = Ref::map(ref_vector, |vec| &vec[index]);
         (index, clocks)
     }
 
+    /// A snapshot of the vector clock of the active thread.
+    pub(super) fn active_thread_clock(&self, thread_mgr: &ThreadManager<'_>) -> VClock {
+        self.active_thread_state(thread_mgr).1.clock.clone()
+    }
+
     /// Load the current vector clock in use and the current set of thread clocks
     /// in use for the vector.
     #[inline]
@@ -1848,6 +2323,152 @@ impl GlobalState {
         self.thread_index(active_thread_id)
     }
 
+    /// The rules of `-Zmiri-race-suppressions` that suppressed races, in the order of the
+    /// suppression file, with the number of races they suppressed.
+    pub fn suppressed_races(&self) -> Vec<(String, u64)> {
+        let Some(suppressions) = &self.race_suppressions else {
+            return Vec::new();
+        };
+        let suppressed_races = self.suppressed_races.borrow();
+        let mut rules: Vec<_> =
+            suppressed_races.iter().map(|(&rule, &count)| (rule, count)).collect();
+        rules.sort();
+        rules.into_iter().map(|(rule, count)| (suppressions.rule(rule), count)).collect()
+    }
+
+    /// For `-Zmiri-spin-loop-warnings`, record that the active thread loaded `value` from `addr`.
+    fn note_spin_load(
+        &self,
+        thread_mgr: &ThreadManager<'_>,
+        addr: Size,
+        span: Span,
+        atomic: AtomicReadOrd,
+        value: Option<Scalar>,
+    ) {
+        let Some(spin_loops) = &self.spin_loops else {
+            return;
+        };
+        let mut spin_loops = spin_loops.borrow_mut();
+        let thread = thread_mgr.active_thread();
+        if atomic != AtomicReadOrd::Relaxed {
+            spin_loops.remove(&thread);
+            return;
+        }
+        match spin_loops.get_mut(&thread) {
+            Some(spin_loop)
+                if spin_loop.addr == addr && spin_loop.span == span && !spin_loop.ended =>
+            {
+                spin_loop.ended = spin_loop.value != value;
+                spin_loop.value = value;
+            }
+            _ => {
+                spin_loops.insert(thread, SpinLoop { addr, span, value, ended: false });
+            }
+        }
+    }
+
+    /// For `-Zmiri-spin-loop-warnings`, record that the active thread performed an acquire
+    /// operation, which properly synchronizes after a spin loop.
+    fn note_spin_acquire(&self, thread_mgr: &ThreadManager<'_>) {
+        if let Some(spin_loops) = &self.spin_loops {
+            spin_loops.borrow_mut().remove(&thread_mgr.active_thread());
+        }
+    }
+
+    /// For `-Zmiri-spin-loop-warnings`, where the active thread loaded the value that ended its
+    /// last spin loop, if it did not acquire anything since.
+    fn ended_spin_loop(&self, thread_mgr: &ThreadManager<'_>) -> Option<Span> {
+        let spin_loops = self.spin_loops.as_ref()?.borrow();
+        let spin_loop = spin_loops.get(&thread_mgr.active_thread())?;
+        spin_loop.ended.then_some(spin_loop.span)
+    }
+
+    /// For `-Zmiri-race-witness`, record that the active thread performs a memory access.
+    fn note_witness_access(&self, thread_mgr: &ThreadManager<'_>, current_span: Span) {
+        let Some(witness_log) = &self.witness_log else {
+            return;
+        };
+        let mut witness_log = witness_log.borrow_mut();
+        let thread = thread_mgr.active_thread();
+        let (index, clocks) = self.active_thread_state(thread_mgr);
+        let time = clocks.clock[index];
+        if witness_log.back().is_none_or(|segment| segment.thread != thread) {
+            if witness_log.len() >= WITNESS_LOG_LIMIT {
+                witness_log.pop_front();
+            }
+            witness_log.push_back(WitnessSegment {
+                thread,
+                first_time: time,
+                last_time: time,
+                spans: FxHashSet::default(),
+                last_span: current_span,
+            });
+        }
+        let segment = witness_log.back_mut().unwrap();
+        segment.last_time = time;
+        if !current_span.is_dummy() {
+            segment.spans.insert(current_span);
+            segment.last_span = current_span;
+        }
+    }
+
+    /// For `-Zmiri-race-witness`, the steps of the execution from the earlier access of the
+    /// `other_thread`, at `other_time` of that thread, to the current access of the active thread.
+    fn race_witness(
+        &self,
+        thread_mgr: &ThreadManager<'_>,
+        other_thread: ThreadId,
+        other_time: VTimestamp,
+        active_thread_info: &str,
+        other_thread_info: &str,
+    ) -> Vec<(Option<SpanData>, String)> {
+        let witness_log = self.witness_log.as_ref().unwrap().borrow();
+        // The current segment is the one of the active thread. The earlier access happened in a
+        // segment of the other thread that covers its time and its location; if several do, we
+        // take the last one, so the steps we show may be a suffix of the actual ones.
+        let Some(start) = witness_log.iter().rposition(|segment| {
+            segment.thread == other_thread
+                && segment.first_time.time() <= other_time.time()
+                && other_time.time() <= segment.last_time.time()
+                && segment.spans.contains(&other_time.span)
+        }) else {
+            return vec![(
+                None,
+                "the steps of the execution that led to this race are too long ago to be shown"
+                    .to_owned(),
+            )];
+        };
+        // Leave out the current segment, which is the one of (2).
+        let end = (witness_log.len() - 1).max(start + 1);
+        let middle = witness_log.range(start + 1..end);
+        let mut witness = vec![(
+            None,
+            format!(
+                "this execution exhibits the race in these steps: first, {other_thread_info} performed (1)"
+            ),
+        )];
+        let step = |segment: &WitnessSegment| {
+            let thread_name = thread_mgr.get_thread_display_name(segment.thread);
+            (
+                (!segment.last_span.is_dummy()).then(|| segment.last_span.data()),
+                format!("then thread `{thread_name}` ran up to here"),
+            )
+        };
+        if middle.len() <= WITNESS_MAX_STEPS {
+            witness.extend(middle.clone().map(step));
+        } else {
+            let half = WITNESS_MAX_STEPS / 2;
+            witness.extend(middle.clone().take(half).map(step));
+            witness.push((
+                None,
+                format!("then {} more steps that are not shown", middle.len() - 2 * half),
+            ));
+            witness.extend(middle.clone().skip(middle.len() - half).map(step));
+        }
+        witness.push((None, format!("finally, {active_thread_info} performed (2)")));
+        witness
+    }
+
     // SC ATOMIC STORE rule in the paper.
     pub(super) fn sc_write(&self, thread_mgr: &ThreadManager<'_>) {
         let (index, clocks) = self.active_thread_state(thread_mgr);
@@ -1859,4 +2480,28 @@ impl GlobalState {
         let (.., mut clocks) = self.active_thread_state_mut(thread_mgr);
         clocks.read_seqcst.join(&self.last_sc_fence.borrow());
     }
+
+    /// Under `WeakMemoryModel::X86Tso`, make the stores of the active thread so far visible
+    /// to all threads, like a locked instruction does on x86.
+    pub(super) fn flush_stores(&self, thread_mgr: &ThreadManager<'_>) {
+        if self.weak_memory_model == WeakMemoryModel::X86Tso {
+            let (index, clocks) = self.active_thread_state(thread_mgr);
+            self.flushed_stores.borrow_mut().set_at_index(&clocks.clock, index);
+        }
+    }
+
+    /// Under `WeakMemoryModel::X86Tso`, record that the store of thread `index` at `timestamp`
+    /// was observed by another thread, so it and all earlier stores of that thread are visible
+    /// to all threads.
+    pub(super) fn store_observed(&self, index: VectorIdx, timestamp: VTimestamp) {
+        if self.weak_memory_model == WeakMemoryModel::X86Tso {
+            self.flushed_stores.borrow_mut().join(&VClock::new_with_index(index, timestamp));
+        }
+    }
+
+    /// Under `WeakMemoryModel::X86Tso`, whether the store of thread `index` at `timestamp`
+    /// has left the store buffer of that thread.
+    pub(super) fn store_flushed(&self, index: VectorIdx, timestamp: VTimestamp) -> bool {
+        timestamp <= self.flushed_stores.borrow()[index]
+    }
 }
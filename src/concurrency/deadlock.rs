@@ -0,0 +1,102 @@
+//! Reconstruction of the cycle of threads waiting for each other when the program deadlocks, and
+//! of wakeups that were lost because they happened before a thread started waiting.
+
+use rustc_data_structures::fx::FxHashSet;
+use rustc_span::Span;
+
+use crate::*;
+
+/// An edge of the wait-for graph: `waiter` cannot continue until `holder` does something.
+#[derive(Clone, Debug)]
+pub struct WaitFor {
+    /// The blocked thread.
+    pub waiter: ThreadId,
+    /// The thread the waiter is waiting for.
+    pub holder: ThreadId,
+    /// The kind of lock that `holder` holds and where it acquired it, or `None` if `waiter` is
+    /// waiting for `holder` to terminate.
+    pub lock: Option<(&'static str, Span)>,
+}
+
+/// A thread that is blocked on a condition variable or futex that was woken up earlier, while no
+/// thread was waiting on it. This usually means that the thread did not check its condition
+/// before waiting, or that the condition was changed and signaled in the wrong order.
+#[derive(Clone, Debug)]
+pub struct LostWakeup {
+    /// The blocked thread.
+    pub waiter: ThreadId,
+    /// The kind of primitive the thread is blocked on.
+    pub primitive: &'static str,
+    /// The thread that woke up the primitive while no thread was waiting on it.
+    pub waker: ThreadId,
+    /// Where the wakeup happened.
+    pub span: Span,
+}
+
+/// Find a cycle in the wait-for graph that goes through `thread`, or through a thread that
+/// `thread` transitively waits for.
+fn find_cycle<'tcx>(
+    ecx: &MiriInterpCx<'tcx>,
+    thread: ThreadId,
+    path: &mut Vec<WaitFor>,
+    done: &mut FxHashSet<ThreadId>,
+) -> Option<Vec<WaitFor>> {
+    if let Some(start) = path.iter().position(|edge| edge.waiter == thread) {
+        return Some(path[start..].to_vec());
+    }
+    if !done.insert(thread) {
+        return None;
+    }
+    for edge in ecx.wait_for(thread) {
+        path.push(edge);
+        let holder = path.last().unwrap().holder;
+        if let Some(cycle) = find_cycle(ecx, holder, path, done) {
+            return Some(cycle);
+        }
+        path.pop();
+    }
+    None
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// The edges of the wait-for graph that start at the given thread. Threads that are blocked on
+    /// something without an owner, like a condition variable or a futex, have no edges.
+    fn wait_for(&self, thread: ThreadId) -> Vec<WaitFor> {
+        let this = self.eval_context_ref();
+        match this.machine.threads.block_reason(thread) {
+            Some(BlockReason::Join(holder)) => vec![WaitFor { waiter: thread, holder, lock: None }],
+            _ => this.lock_wait_for(thread),
+        }
+    }
+
+    /// A cycle of threads that wait for each other, if there is one. Cycles through the active
+    /// thread are preferred, since that is where the deadlock is reported.
+    fn deadlock_cycle(&self) -> Option<Vec<WaitFor>> {
+        let this = self.eval_context_ref();
+        let mut done = FxHashSet::default();
+        let active = this.active_thread();
+        std::iter::once(active)
+            .chain(this.machine.threads.all_stacks().map(|(thread, _)| thread))
+            .find_map(|thread| find_cycle(this, thread, &mut Vec::new(), &mut done))
+    }
+
+    /// The blocked threads whose wakeup was lost, starting with the active thread.
+    fn lost_wakeups(&self) -> Vec<LostWakeup> {
+        let this = self.eval_context_ref();
+        let active = this.active_thread();
+        std::iter::once(active)
+            .chain(
+                this.machine
+                    .threads
+                    .all_stacks()
+                    .map(|(thread, _)| thread)
+                    .filter(|&t| t != active),
+            )
+            .filter_map(|waiter| {
+                let (primitive, waker, span) = this.lost_wakeup(waiter)?;
+                Some(LostWakeup { waiter, primitive, waker, span })
+            })
+            .collect()
+    }
+}
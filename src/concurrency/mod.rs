@@ -1,7 +1,10 @@
 pub mod cpu_affinity;
 pub mod data_race;
+pub mod deadlock;
 pub mod init_once;
+pub mod race_suppressions;
 mod range_object_map;
+pub mod schedule;
 pub mod sync;
 pub mod thread;
 mod vector_clock;
@@ -0,0 +1,109 @@
+//! Suppressions for data races, in the spirit of ThreadSanitizer's suppression files.
+//!
+//! A suppression file has one rule per line, of the form `race:<pattern>`. Empty lines and lines
+//! starting with `#` are ignored. A data race is suppressed if the pattern matches the path of a
+//! function (like `my_crate::module::function`) or the name of a crate on the stack of the access
+//! that completes the race. In patterns, `*` matches any sequence of characters.
+
+use std::str::FromStr;
+
+/// The rules of a suppression file.
+#[derive(Debug, Clone, Default)]
+pub struct RaceSuppressions {
+    patterns: Vec<String>,
+}
+
+impl RaceSuppressions {
+    /// The rule of the given index, as written in the suppression file.
+    pub fn rule(&self, idx: usize) -> String {
+        format!("race:{}", self.patterns[idx])
+    }
+
+    /// The index of the first rule that matches any of the given function paths or crate names.
+    pub fn find<'a>(&self, names: impl Iterator<Item = &'a str> + Clone) -> Option<usize> {
+        self.patterns
+            .iter()
+            .position(|pattern| names.clone().any(|name| glob_matches(pattern, name)))
+    }
+}
+
+impl FromStr for RaceSuppressions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut patterns = Vec::new();
+        for (line_number, line) in s.lines().enumerate().map(|(i, line)| (i.strict_add(1), line)) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some(pattern) = line.strip_prefix("race:") else {
+                return Err(format!(
+                    "line {line_number}: `{line}` is not of the form `race:<pattern>`"
+                ));
+            };
+            if pattern.is_empty() {
+                return Err(format!("line {line_number}: the pattern must not be empty"));
+            }
+            patterns.push(pattern.to_owned());
+        }
+        Ok(RaceSuppressions { patterns })
+    }
+}
+
+/// Whether `text` matches `pattern` as a whole, where `*` in `pattern` matches any sequence of
+/// characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // There is always a first part; it has to be a prefix of `text`.
+    let first = parts.next().unwrap();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The last part has to be a suffix of what remains.
+            return rest.ends_with(part);
+        }
+        // Match every other part as early as possible.
+        let Some(pos) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[pos + part.len()..];
+    }
+    // There was no `*`, so the pattern has to match exactly.
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_matches("foo", "foo"));
+        assert!(!glob_matches("foo", "foobar"));
+        assert!(!glob_matches("foo", "barfoo"));
+        assert!(glob_matches("foo::*", "foo::bar::baz"));
+        assert!(!glob_matches("foo::*", "foobar::baz"));
+        assert!(glob_matches("*::baz", "foo::bar::baz"));
+        assert!(glob_matches("*bar*", "foo::bar::baz"));
+        assert!(glob_matches("foo*bar*baz", "foo::bar::baz"));
+        assert!(!glob_matches("a*a", "a"));
+        assert!(glob_matches("a*a", "aa"));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn parse() {
+        let suppressions: RaceSuppressions =
+            "# known races\n\nrace:foo::*\n  race:bar  \n".parse().unwrap();
+        assert_eq!(suppressions.patterns, ["foo::*", "bar"]);
+        assert_eq!(suppressions.find(["baz", "foo::f"].into_iter()), Some(0));
+        assert_eq!(suppressions.find(["bar"].into_iter()), Some(1));
+        assert_eq!(suppressions.find(["baz"].into_iter()), None);
+        assert!("foo".parse::<RaceSuppressions>().is_err());
+        assert!("race:".parse::<RaceSuppressions>().is_err());
+    }
+}
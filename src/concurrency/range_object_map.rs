@@ -125,6 +125,10 @@ impl<T> RangeObjectMap<T> {
     pub fn iter(&self) -> impl Iterator<Item = &T> {
         self.v.iter().map(|e| &e.data)
     }
+
+    pub fn iter_with_range(&self) -> impl Iterator<Item = (AllocRange, &T)> {
+        self.v.iter().map(|e| (e.range, &e.data))
+    }
 }
 
 impl<T> Index<Position> for RangeObjectMap<T> {
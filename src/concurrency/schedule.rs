@@ -0,0 +1,579 @@
+//! Systematic exploration of thread interleavings.
+//!
+//! Normally, the scheduler follows a single schedule that is determined by the random preemption
+//! rate. With `-Zmiri-exhaustive-schedules`, the program is instead run over and over again, and
+//! every run takes a different path through the tree of scheduling decisions, in the style of
+//! stateless model checkers like loom. A decision is made whenever more than one thread could run
+//! next after a synchronization operation (an atomic access, a fence, acquiring or releasing a
+//! lock, ...), or when the active thread blocks, yields, or terminates. The number of preemptions,
+//! i.e. switching away from a thread that could have kept running, is bounded, which keeps the
+//! number of schedules manageable while still finding most concurrency bugs.
+//!
+//! Many schedules only differ in the order of operations that do not affect each other, e.g.
+//! atomic accesses to different locations. Dynamic partial-order reduction (DPOR) avoids exploring
+//! more than one of them: instead of trying every thread at every decision, a decision only tries
+//! another thread if that thread later performs an operation that conflicts with an operation that
+//! was scheduled after the decision, and that is not ordered before it by the vector clocks of the
+//! data race detector. Reversing such a pair could change the outcome; everything else cannot.
+//!
+//! With `-Zmiri-exhaustive-weak-memory`, the exploration also covers the weak memory emulation:
+//! whenever an atomic load could read from more than one store in the store buffer of a location,
+//! that is a decision as well, and every store the load may read from gets tried. Reading from an
+//! outdated store counts against the preemption bound. These decisions are not pruned by
+//! partial-order reduction, since a load that reads a different value can change everything that
+//! the thread does afterwards.
+//!
+//! With `-Zmiri-epoll-wakeup-order=explore`, the order in which an epoll instance reports ready
+//! events, and which of the threads blocked on it gets woken up, are decisions as well. Deviating
+//! from the fixed order also counts against the preemption bound.
+//!
+//! Every run, explored or not, can be recorded as a [`ScheduleTrace`] of its thread switches, fired
+//! timeouts, loads from outdated stores, and epoll wakeups out of the fixed order, and replayed
+//! from that trace with `-Zmiri-replay-schedule`.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use rustc_abi::Size;
+use rustc_data_structures::fx::FxHashMap;
+
+use super::VClock;
+use crate::*;
+
+/// The choices to make at the scheduling decisions of a run. Decisions that are not listed take
+/// the default choice, which is the one the scheduler would make without exploration.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schedule {
+    choices: Vec<u32>,
+}
+
+impl Schedule {
+    fn choice(&self, decision: usize) -> u32 {
+        self.choices.get(decision).copied().unwrap_or(0)
+    }
+}
+
+/// A scheduling decision that was made during a run.
+#[derive(Clone, Debug)]
+pub struct Decision {
+    /// The threads that could run next, in the order of the choices. This is empty for a decision
+    /// about which store an atomic load reads from, or about the order of epoll wakeups.
+    candidates: Vec<ThreadId>,
+    /// The number of choices that exploration may take here. This is smaller than the number of
+    /// candidates if taking another choice would exceed the preemption bound.
+    alternatives: u32,
+    /// The choice that was taken.
+    chosen: u32,
+    /// The choices that exploration has to try here. Without partial-order reduction, these are
+    /// all alternatives.
+    backtrack: BTreeSet<u32>,
+}
+
+impl Decision {
+    /// Make exploration also try running `thread` first at this decision.
+    fn add_backtrack(&mut self, thread: ThreadId) {
+        match self.candidates.iter().position(|&candidate| candidate == thread) {
+            Some(choice) => {
+                let choice = u32::try_from(choice).unwrap();
+                if choice < self.alternatives {
+                    self.backtrack.insert(choice);
+                }
+            }
+            // The thread could not run here, so we do not know which thread has to run first to
+            // let it get ahead. Try all of them.
+            None => self.backtrack.extend(0..self.alternatives),
+        }
+    }
+}
+
+/// Something that threads synchronize on, for finding conflicting operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyncObject {
+    /// An atomic location, identified by its allocation and offset.
+    Location(AllocId, Size),
+    /// Synchronization whose object we do not track, such as fences, thread joins, and locks.
+    /// This conflicts with every other operation.
+    Global,
+}
+
+/// The last access of a thread to a synchronization object.
+#[derive(Debug)]
+struct Access {
+    /// The decision after which the access was scheduled.
+    decision: usize,
+    /// Whether the access modified the object. Two accesses only conflict if one of them does.
+    write: bool,
+    /// The vector clock of the thread at the time of the access, if the data race detector is
+    /// enabled.
+    clock: Option<VClock>,
+}
+
+/// The state of schedule exploration for a single run.
+#[derive(Debug)]
+pub struct ScheduleExplorer {
+    /// The choices to take in this run.
+    schedule: Schedule,
+    /// The maximal number of preemptions in a run, or `None` if it is unbounded.
+    preemption_bound: Option<u32>,
+    /// Whether to prune schedules with dynamic partial-order reduction.
+    partial_order_reduction: bool,
+    /// Whether atomic loads let exploration decide which store they read from.
+    explore_loads: bool,
+    /// Whether epoll lets exploration decide the order of ready events and woken threads.
+    explore_wakeups: bool,
+    /// The number of preemptions made so far, including loads from outdated stores and
+    /// reordered epoll wakeups.
+    preemptions: Cell<u32>,
+    /// The decisions made so far.
+    decisions: RefCell<Vec<Decision>>,
+    /// The last access of each thread to each synchronization object.
+    accesses: RefCell<FxHashMap<SyncObject, FxHashMap<ThreadId, Access>>>,
+    /// Whether the active thread performed a synchronization operation in its last step.
+    sync_point: Cell<bool>,
+}
+
+impl ScheduleExplorer {
+    pub fn new(
+        schedule: Schedule,
+        preemption_bound: Option<u32>,
+        partial_order_reduction: bool,
+        explore_loads: bool,
+        explore_wakeups: bool,
+    ) -> Self {
+        ScheduleExplorer {
+            schedule,
+            preemption_bound,
+            partial_order_reduction,
+            explore_loads,
+            explore_wakeups,
+            preemptions: Cell::new(0),
+            decisions: RefCell::new(Vec::new()),
+            accesses: RefCell::new(FxHashMap::default()),
+            sync_point: Cell::new(false),
+        }
+    }
+
+    /// Records that the active thread performed a synchronization operation, after which other
+    /// threads may be scheduled.
+    pub fn note_sync_point(&self) {
+        self.sync_point.set(true);
+    }
+
+    /// Records that `thread` accessed `object`, with the given vector clock. Earlier accesses by
+    /// other threads that conflict with this one and are not ordered before it could also have
+    /// happened after it, so the decisions before them have to try running `thread` instead.
+    pub fn note_access(
+        &self,
+        thread: ThreadId,
+        object: SyncObject,
+        write: bool,
+        clock: Option<VClock>,
+    ) {
+        self.note_sync_point();
+        if !self.partial_order_reduction {
+            return;
+        }
+        let mut decisions = self.decisions.borrow_mut();
+        let mut accesses = self.accesses.borrow_mut();
+        let conflicting = accesses
+            .iter()
+            .filter(|&(&other, _)| {
+                object == other || object == SyncObject::Global || other == SyncObject::Global
+            })
+            .flat_map(|(_, by_thread)| by_thread.iter())
+            .filter(|&(&other_thread, access)| {
+                other_thread != thread
+                    && (write || access.write)
+                    && !access
+                        .clock
+                        .as_ref()
+                        .is_some_and(|earlier| clock.as_ref().is_some_and(|clock| earlier <= clock))
+            });
+        for (_, access) in conflicting {
+            decisions[access.decision].add_backtrack(thread);
+        }
+        // Accesses before the first decision cannot be reordered with anything.
+        if let Some(decision) = decisions.len().checked_sub(1) {
+            accesses.entry(object).or_default().insert(thread, Access { decision, write, clock });
+        }
+    }
+
+    /// Returns whether a synchronization operation happened since the last call.
+    pub fn take_sync_point(&self) -> bool {
+        self.sync_point.replace(false)
+    }
+
+    /// Picks one of `candidates` threads to run next; choice 0 is the default. If `preemptive` is
+    /// set, every other choice counts as a preemption. Returns `None` if the schedule asks for a
+    /// choice that does not exist, which means that the run diverged from the one that produced
+    /// the schedule.
+    pub fn decide(&mut self, candidates: &[ThreadId], preemptive: bool) -> Option<ThreadId> {
+        let alternatives = if preemptive && self.preemption_bound_reached() {
+            1
+        } else {
+            u32::try_from(candidates.len()).unwrap()
+        };
+        let decisions = self.decisions.get_mut();
+        let chosen = self.schedule.choice(decisions.len());
+        let thread = *candidates.get(usize::try_from(chosen).unwrap())?;
+        if preemptive && chosen != 0 {
+            self.preemptions.set(self.preemptions.get().strict_add(1));
+        }
+        let backtrack = if self.partial_order_reduction {
+            BTreeSet::from([chosen])
+        } else {
+            (0..alternatives).collect()
+        };
+        decisions.push(Decision {
+            candidates: candidates.to_vec(),
+            alternatives,
+            chosen,
+            backtrack,
+        });
+        Some(thread)
+    }
+
+    /// Whether atomic loads let exploration decide which store they read from.
+    pub fn explores_loads(&self) -> bool {
+        self.explore_loads
+    }
+
+    /// Picks which of `count` stores an atomic load reads from, ordered from the latest store in
+    /// modification order backwards; choice 0, reading the latest store, is the default. Every
+    /// other choice counts as a preemption. Returns `None` if the schedule asks for a store that
+    /// does not exist.
+    pub fn decide_load(&self, count: usize) -> Option<usize> {
+        self.decide_alternative(count)
+    }
+
+    /// Whether epoll lets exploration decide the order of ready events and woken threads.
+    pub fn explores_wakeups(&self) -> bool {
+        self.explore_wakeups
+    }
+
+    /// Picks which of `count` ready events or blocked threads an epoll instance handles next;
+    /// choice 0, the fixed order, is the default. Every other choice counts as a preemption.
+    /// Returns `None` if the schedule asks for a choice that does not exist.
+    pub fn decide_wakeup(&self, count: usize) -> Option<usize> {
+        self.decide_alternative(count)
+    }
+
+    /// Picks one of `count` alternatives that are not about which thread runs next.
+    fn decide_alternative(&self, count: usize) -> Option<usize> {
+        if count < 2 {
+            return Some(0);
+        }
+        let mut decisions = self.decisions.borrow_mut();
+        let chosen = self.schedule.choice(decisions.len());
+        let choice = usize::try_from(chosen).unwrap();
+        if choice >= count {
+            return None;
+        }
+        let alternatives =
+            if self.preemption_bound_reached() { 1 } else { u32::try_from(count).unwrap() };
+        if chosen != 0 {
+            self.preemptions.set(self.preemptions.get().strict_add(1));
+        }
+        decisions.push(Decision {
+            candidates: Vec::new(),
+            alternatives,
+            chosen,
+            backtrack: (0..alternatives).collect(),
+        });
+        Some(choice)
+    }
+
+    fn preemption_bound_reached(&self) -> bool {
+        self.preemption_bound.is_some_and(|bound| self.preemptions.get() >= bound)
+    }
+
+    /// Ends the run, and returns the decisions that were made.
+    pub fn take_decisions(&mut self) -> Vec<Decision> {
+        std::mem::take(self.decisions.get_mut())
+    }
+}
+
+/// A node on the current path through the tree of scheduling decisions.
+#[derive(Debug)]
+struct Node {
+    /// The choice taken on the current path.
+    chosen: u32,
+    /// The choices that have to be tried.
+    backtrack: BTreeSet<u32>,
+    /// The choices that have been tried.
+    done: BTreeSet<u32>,
+}
+
+/// Depth-first exploration of the tree of scheduling decisions, across runs.
+#[derive(Debug, Default)]
+pub struct Exploration {
+    path: Vec<Node>,
+}
+
+impl Exploration {
+    /// Merges the decisions of a run, which followed the schedule last returned by `next`, into
+    /// the tree.
+    pub fn record(&mut self, decisions: &[Decision]) {
+        for (index, decision) in decisions.iter().enumerate() {
+            if let Some(node) = self.path.get_mut(index) {
+                debug_assert_eq!(node.chosen, decision.chosen);
+                node.backtrack.extend(&decision.backtrack);
+            } else {
+                self.path.push(Node {
+                    chosen: decision.chosen,
+                    backtrack: decision.backtrack.clone(),
+                    done: BTreeSet::from([decision.chosen]),
+                });
+            }
+        }
+    }
+
+    /// The next schedule to explore: the last decision that still has a choice to try takes it,
+    /// and all later decisions go back to their default. Returns `None` once every schedule has
+    /// been explored.
+    pub fn next(&mut self) -> Option<Schedule> {
+        while let Some(node) = self.path.last_mut() {
+            if let Some(&choice) = node.backtrack.difference(&node.done).next() {
+                node.done.insert(choice);
+                node.chosen = choice;
+                return Some(Schedule {
+                    choices: self.path.iter().map(|node| node.chosen).collect(),
+                });
+            }
+            self.path.pop();
+        }
+        None
+    }
+}
+
+/// A scheduling event in a [`ScheduleTrace`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleEvent {
+    /// The given thread becomes the active thread.
+    Switch(ThreadId),
+    /// The timeout of the given thread fires.
+    Timeout(ThreadId),
+    /// An atomic load reads from the store with the given index, counting backwards from the
+    /// latest store in modification order. Loads that are not in the trace read the latest store.
+    Load(u32),
+    /// An epoll instance handles the ready event or blocked thread with the given index. Epoll
+    /// instances that are not in the trace handle them in the fixed order.
+    Wakeup(u32),
+}
+
+/// Every scheduling event of a run, together with the number of scheduling steps (executed
+/// statements and fired timeouts) before it. In a run that follows the trace, everything that is
+/// not in the trace does not happen: the active thread keeps running, timeouts do not fire,
+/// atomic loads read the latest store, and epoll handles events in the fixed order.
+///
+/// Traces are stored as text, with one event per line: `<step> switch <thread>`,
+/// `<step> timeout <thread>`, `<step> load <store>`, or `<step> wakeup <choice>`. Lines starting
+/// with `#` are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScheduleTrace {
+    events: Vec<(u64, ScheduleEvent)>,
+}
+
+impl ScheduleTrace {
+    /// The number of events in the trace.
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Searches for a trace with fewer events that still makes the run fail in the same way, by
+    /// delta debugging: chunks of events are removed, and whenever that keeps the failure, we
+    /// continue with the smaller trace. `reproduces` runs the program following the given trace,
+    /// and returns the trace that the run actually took if it failed in the same way.
+    pub fn minimize(
+        self,
+        mut reproduces: impl FnMut(&ScheduleTrace) -> Option<ScheduleTrace>,
+    ) -> ScheduleTrace {
+        let mut current = self;
+        let mut chunks: usize = 2;
+        while !current.events.is_empty() {
+            let len = current.events.len();
+            let chunks_now = chunks.min(len);
+            let chunk_size = len.div_ceil(chunks_now);
+            let smaller = (0..len).step_by(chunk_size).find_map(|start| {
+                let mut candidate = current.clone();
+                candidate.events.drain(start..(start + chunk_size).min(len));
+                reproduces(&candidate).filter(|trace| trace.events.len() < len)
+            });
+            if let Some(smaller) = smaller {
+                current = smaller;
+                chunks = (chunks_now - 1).max(2);
+            } else if chunks_now < len {
+                chunks = (chunks_now * 2).min(len);
+            } else {
+                break;
+            }
+        }
+        current
+    }
+}
+
+impl fmt::Display for ScheduleTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &(step, event) in &self.events {
+            match event {
+                ScheduleEvent::Switch(thread) => writeln!(f, "{step} switch {}", thread.to_u32())?,
+                ScheduleEvent::Timeout(thread) =>
+                    writeln!(f, "{step} timeout {}", thread.to_u32())?,
+                ScheduleEvent::Load(store) => writeln!(f, "{step} load {store}")?,
+                ScheduleEvent::Wakeup(choice) => writeln!(f, "{step} wakeup {choice}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ScheduleTrace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut events: Vec<(u64, ScheduleEvent)> = Vec::new();
+        for (line_number, line) in s.lines().enumerate().map(|(i, line)| (i.strict_add(1), line)) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("line {line_number}: `{line}` is not a scheduling event");
+            let [step, kind, arg] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                return Err(invalid());
+            };
+            let step: u64 = step.parse().map_err(|_| invalid())?;
+            let arg: u32 = arg.parse().map_err(|_| invalid())?;
+            let event = match kind {
+                "switch" => ScheduleEvent::Switch(ThreadId::new_unchecked(arg)),
+                "timeout" => ScheduleEvent::Timeout(ThreadId::new_unchecked(arg)),
+                "load" => ScheduleEvent::Load(arg),
+                "wakeup" => ScheduleEvent::Wakeup(arg),
+                _ => return Err(invalid()),
+            };
+            // Loads and wakeups happen while executing a step, so they can share it with the
+            // scheduling event that follows.
+            if events.last().is_some_and(|&(last, _)| last > step) {
+                return Err(format!("line {line_number}: events must be in increasing order"));
+            }
+            events.push((step, event));
+        }
+        Ok(ScheduleTrace { events })
+    }
+}
+
+/// Records the scheduling events of a run, and optionally makes the run follow a trace.
+#[derive(Debug)]
+pub struct ScheduleTracer {
+    /// The events of this run so far.
+    recorded: RefCell<ScheduleTrace>,
+    /// The trace to follow, if any.
+    replay: Option<ScheduleTrace>,
+    /// Whether to fall back to the default scheduling policy where the run diverges from
+    /// `replay`, instead of stopping. Used when minimizing schedules.
+    lenient: bool,
+    /// The number of scheduling steps so far.
+    step: u64,
+    /// The index of the next event in `replay`.
+    next_event: Cell<usize>,
+}
+
+impl ScheduleTracer {
+    pub fn new(replay: Option<ScheduleTrace>, lenient: bool) -> Self {
+        ScheduleTracer {
+            recorded: RefCell::new(ScheduleTrace::default()),
+            replay,
+            lenient,
+            step: 0,
+            next_event: Cell::new(0),
+        }
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Adds an event at the current step.
+    pub fn record_event(&self, event: ScheduleEvent) {
+        self.recorded.borrow_mut().events.push((self.step, event));
+    }
+
+    /// When replaying, returns the event at the current step, if there is one.
+    pub fn replayed_event(&self) -> Option<ScheduleEvent> {
+        let &(step, event) = self.replay.as_ref()?.events.get(self.next_event.get())?;
+        if step != self.step {
+            return None;
+        }
+        self.next_event.set(self.next_event.get().strict_add(1));
+        Some(event)
+    }
+
+    /// When replaying, returns the store that an atomic load at the current step reads from.
+    pub fn replayed_load(&self) -> u32 {
+        self.replayed_choice(|event| {
+            match event {
+                ScheduleEvent::Load(store) => Some(store),
+                _ => None,
+            }
+        })
+    }
+
+    /// When replaying, returns which ready event or blocked thread an epoll instance handles at
+    /// the current step.
+    pub fn replayed_wakeup(&self) -> u32 {
+        self.replayed_choice(|event| {
+            match event {
+                ScheduleEvent::Wakeup(choice) => Some(choice),
+                _ => None,
+            }
+        })
+    }
+
+    /// Consumes the next event if it is at the current step and `choice` accepts it; otherwise,
+    /// the default choice 0 was made.
+    fn replayed_choice(&self, choice: impl Fn(ScheduleEvent) -> Option<u32>) -> u32 {
+        let replay = self.replay.as_ref().unwrap();
+        match replay.events.get(self.next_event.get()) {
+            Some(&(step, event)) if step == self.step =>
+                match choice(event) {
+                    Some(chosen) => {
+                        self.next_event.set(self.next_event.get().strict_add(1));
+                        chosen
+                    }
+                    None => 0,
+                },
+            _ => 0,
+        }
+    }
+
+    /// Moves on to the next scheduling step.
+    pub fn finish_step(&mut self) {
+        self.step = self.step.strict_add(1);
+    }
+
+    /// The events of this run so far.
+    pub fn recorded(&self) -> ScheduleTrace {
+        self.recorded.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_round_trip() {
+        let text = "3 load 1\n3 switch 1\n7 timeout 0\n9 load 2\n9 wakeup 1\n";
+        let trace: ScheduleTrace = text.parse().unwrap();
+        assert_eq!(trace.event_count(), 5);
+        assert_eq!(trace.to_string(), text);
+        assert!("# comment\n\n5 switch 2".parse::<ScheduleTrace>().is_ok());
+        assert!("5 switch 2\n4 switch 1".parse::<ScheduleTrace>().is_err());
+        assert!("5 jump 2".parse::<ScheduleTrace>().is_err());
+    }
+}
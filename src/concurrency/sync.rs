@@ -3,14 +3,17 @@ use std::collections::VecDeque;
 use std::collections::hash_map::Entry;
 use std::default::Default;
 use std::ops::Not;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::time::Duration;
 
 use rustc_abi::Size;
-use rustc_data_structures::fx::FxHashMap;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
 use rustc_index::{Idx, IndexVec};
+use rustc_span::Span;
 
+use super::deadlock::WaitFor;
 use super::init_once::InitOnce;
+use super::schedule::SyncObject;
 use super::vector_clock::VClock;
 use crate::*;
 
@@ -47,6 +50,19 @@ macro_rules! declare_id {
 }
 pub(super) use declare_id;
 
+/// Whether the data protected by a robust mutex can be trusted, see `pthread_mutexattr_setrobust`.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MutexConsistency {
+    /// The mutex was always released by its owner.
+    #[default]
+    Consistent,
+    /// An owner terminated while holding the mutex, and no later owner marked it as consistent.
+    OwnerDied,
+    /// The mutex was released without being marked as consistent after its owner died. It can
+    /// never be acquired again.
+    NotRecoverable,
+}
+
 /// The mutex state.
 #[derive(Default, Debug)]
 struct Mutex {
@@ -54,6 +70,12 @@ struct Mutex {
     owner: Option<ThreadId>,
     /// How many times the mutex was locked by the owner.
     lock_count: usize,
+    /// Whether the mutex is released when its owner terminates, instead of staying locked forever.
+    robust: bool,
+    /// For robust mutexes, whether an owner terminated while holding the mutex.
+    consistency: MutexConsistency,
+    /// Where the owner acquired the mutex, for deadlock reports.
+    acquired_at: Option<Span>,
     /// The queue of threads waiting for this mutex.
     queue: VecDeque<ThreadId>,
     /// Mutex clock. This tracks the moment of the last unlock.
@@ -85,6 +107,8 @@ struct RwLock {
     /// The readers that currently own the lock and how many times they acquired
     /// the lock.
     readers: FxHashMap<ThreadId, usize>,
+    /// Where the writer and each of the readers acquired the lock, for deadlock reports.
+    acquired_at: FxHashMap<ThreadId, Span>,
     /// The queue of writer threads waiting for this lock.
     writer_queue: VecDeque<ThreadId>,
     /// The queue of reader threads waiting for this lock.
@@ -117,6 +141,9 @@ declare_id!(CondvarId);
 #[derive(Default, Debug)]
 struct Condvar {
     waiters: VecDeque<ThreadId>,
+    /// The thread that last signaled the condition variable while no thread was waiting, and
+    /// where it did so, for deadlock reports.
+    lost_signal: Option<(ThreadId, Span)>,
     /// Tracks the happens-before relationship
     /// between a cond-var signal and a cond-var
     /// wait during a non-spurious signal event.
@@ -129,6 +156,9 @@ struct Condvar {
 #[derive(Default, Debug)]
 struct Futex {
     waiters: VecDeque<FutexWaiter>,
+    /// The thread that last woke the futex while no thread was waiting, and where it did so, for
+    /// deadlock reports.
+    lost_wake: Option<(ThreadId, Span)>,
     /// Tracks the happens-before relationship
     /// between a futex-wake and a futex-wait
     /// during a non-spurious wake event.
@@ -153,6 +183,31 @@ struct FutexWaiter {
     thread: ThreadId,
     /// The bitset used by FUTEX_*_BITSET, or u32::MAX for other operations.
     bitset: u32,
+    /// The futex this thread is queued on. This is shared with the callback that runs when the
+    /// thread gets woken up or times out, and updated when the thread is requeued to another
+    /// futex. It is a `Weak` so that the futex does not keep itself alive.
+    queue: Weak<RefCell<FutexRef>>,
+}
+
+/// The semaphore state.
+#[derive(Default, Debug)]
+struct Semaphore {
+    /// The number of units that can be taken without blocking.
+    value: u32,
+    /// The queue of threads waiting for a unit. There are only waiters while the value is 0.
+    waiters: VecDeque<ThreadId>,
+    /// Tracks the happens-before relationship between posting a unit and taking it. Since we do
+    /// not track which unit is which, this contains the clocks of all posts so far.
+    clock: VClock,
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct SemaphoreRef(Rc<RefCell<Semaphore>>);
+
+impl VisitProvenance for SemaphoreRef {
+    fn visit_provenance(&self, _visit: &mut VisitWith<'_>) {
+        // Semaphore contains no provenance.
+    }
 }
 
 /// The state of all synchronization objects.
@@ -161,6 +216,15 @@ pub struct SynchronizationObjects {
     rwlocks: IndexVec<RwLockId, RwLock>,
     condvars: IndexVec<CondvarId, Condvar>,
     pub(super) init_onces: IndexVec<InitOnceId, InitOnce>,
+    /// The mutex each thread that is blocked on a mutex is waiting for.
+    mutex_waits: FxHashMap<ThreadId, MutexRef>,
+    /// The robust mutexes that each thread holds, which are released when it terminates.
+    robust_mutexes: FxHashMap<ThreadId, Vec<MutexRef>>,
+    /// The futex each thread that is blocked on a futex is queued on. This is the same as
+    /// `FutexWaiter::queue`.
+    futex_waits: FxHashMap<ThreadId, Weak<RefCell<FutexRef>>>,
+    /// The spans at which we already warned about process-shared primitives.
+    process_shared_warnings: FxHashSet<Span>,
 }
 
 // Private extension trait for local helper methods
@@ -184,12 +248,35 @@ pub(super) trait EvalContextExtPriv<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
         interp_ok(())
     }
+
+    /// Decide whether a wait that is about to start should end with a spurious wakeup, according
+    /// to `-Zmiri-spurious-wakeup-rate`. If so, this returns the timeout after which the waiter
+    /// wakes up without being signaled.
+    fn spurious_wakeup(&mut self) -> Option<(TimeoutClock, TimeoutAnchor, Duration)> {
+        use rand::Rng as _;
+
+        let this = self.eval_context_mut();
+        let rate = this.machine.spurious_wakeup_rate;
+        // Only draw a random number if spurious wakeups are enabled, so that the other random
+        // decisions do not change.
+        (rate > 0.0 && this.machine.rng.get_mut().gen_bool(rate)).then_some((
+            TimeoutClock::Monotonic,
+            TimeoutAnchor::Relative,
+            Duration::ZERO,
+        ))
+    }
 }
 
 impl SynchronizationObjects {
     pub fn mutex_create(&mut self) -> MutexRef {
         MutexRef::new()
     }
+    pub fn robust_mutex_create(&mut self) -> MutexRef {
+        MutexRef(Rc::new(RefCell::new(Mutex { robust: true, ..Default::default() })))
+    }
+    pub fn semaphore_create(&mut self, value: u32) -> SemaphoreRef {
+        SemaphoreRef(Rc::new(RefCell::new(Semaphore { value, ..Default::default() })))
+    }
     pub fn rwlock_create(&mut self) -> RwLockId {
         self.rwlocks.push(Default::default())
     }
@@ -328,6 +415,46 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         Some(alloc_extra.get_sync::<T>(offset).unwrap())
     }
 
+    /// Record whether the primitive at `primitive`, whose C type is `name`, was just initialized
+    /// as process-shared. We cannot actually share memory with other processes, so we warn about
+    /// this, and about freeing such a primitive without destroying it first.
+    fn note_process_shared(
+        &mut self,
+        primitive: &MPlaceTy<'tcx>,
+        name: &'static str,
+        pshared: bool,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let (alloc, offset, _) = this.ptr_get_alloc_id(primitive.ptr(), 0)?;
+        let (alloc_extra, _machine) = this.get_alloc_extra_mut(alloc)?;
+        if !pshared {
+            // This could be re-initializing a primitive that used to be process-shared.
+            alloc_extra.process_shared_sync.remove(&offset);
+            return interp_ok(());
+        }
+        alloc_extra.process_shared_sync.insert(offset, name);
+
+        // The first time this happens at a particular location, print a warning.
+        let span = this.machine.current_span();
+        let warnings = &mut this.machine.sync.process_shared_warnings;
+        let details = warnings.is_empty();
+        if warnings.insert(span) {
+            this.emit_diagnostic(NonHaltingDiagnostic::ProcessSharedSyncObject { name, details });
+        }
+        interp_ok(())
+    }
+
+    /// Record that the primitive at `primitive` was destroyed.
+    fn note_sync_object_destroyed(&mut self, primitive: &MPlaceTy<'tcx>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let (alloc, offset, _) = this.ptr_get_alloc_id(primitive.ptr(), 0)?;
+        let (alloc_extra, _machine) = this.get_alloc_extra_mut(alloc)?;
+        alloc_extra.process_shared_sync.remove(&offset);
+        interp_ok(())
+    }
+
     #[inline]
     /// Get the id of the thread that currently owns this lock.
     fn mutex_get_owner(&self, mutex_ref: &MutexRef) -> ThreadId {
@@ -353,8 +480,13 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             );
         } else {
             mutex.owner = Some(thread);
+            mutex.acquired_at = Some(this.machine.current_span());
+            if mutex.robust {
+                this.machine.sync.robust_mutexes.entry(thread).or_default().push(mutex_ref.clone());
+            }
         }
         mutex.lock_count = mutex.lock_count.strict_add(1);
+        this.note_sync_access(SyncObject::Global, /* write */ true);
         if let Some(data_race) = &this.machine.data_race {
             data_race.acquire_clock(&mutex.clock, &this.machine.threads);
         }
@@ -377,8 +509,15 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             mutex.lock_count = old_lock_count.strict_sub(1);
             if mutex.lock_count == 0 {
                 mutex.owner = None;
+                mutex.acquired_at = None;
+                if mutex.robust
+                    && let Some(held) = this.machine.sync.robust_mutexes.get_mut(&current_owner)
+                {
+                    held.retain(|held| !Rc::ptr_eq(&held.0, &mutex_ref.0));
+                }
                 // The mutex is completely unlocked. Try transferring ownership
                 // to another thread.
+                this.note_sync_access(SyncObject::Global, /* write */ true);
                 if let Some(data_race) = &this.machine.data_race {
                     data_race.release_clock(&this.machine.threads, |clock| {
                         mutex.clock.clone_from(clock)
@@ -413,6 +552,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         assert!(this.mutex_is_locked(mutex_ref), "queuing on unlocked mutex");
         let thread = this.active_thread();
         mutex_ref.0.borrow_mut().queue.push_back(thread);
+        this.machine.sync.mutex_waits.insert(thread, mutex_ref.clone());
         let mutex_ref = mutex_ref.clone();
         this.block_thread(
             BlockReason::Mutex,
@@ -424,6 +564,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 }
                 @unblock = |this| {
                     assert!(!this.mutex_is_locked(&mutex_ref));
+                    this.machine.sync.mutex_waits.remove(&this.active_thread());
                     this.mutex_lock(&mutex_ref);
 
                     if let Some((retval, dest)) = retval_dest {
@@ -436,6 +577,96 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         );
     }
 
+    /// Put the thread into the queue waiting for the robust mutex.
+    ///
+    /// Once the mutex becomes available, the thread acquires it and `retval_succ` is written to
+    /// `dest`, or `retval_owner_died` if a previous owner terminated while holding the mutex. If
+    /// the mutex became unrecoverable instead, the thread does not acquire it and
+    /// `retval_not_recoverable` is written to `dest`.
+    fn robust_mutex_enqueue_and_block(
+        &mut self,
+        mutex_ref: &MutexRef,
+        retval_succ: Scalar,
+        retval_owner_died: Scalar,
+        retval_not_recoverable: Scalar,
+        dest: MPlaceTy<'tcx>,
+    ) {
+        let this = self.eval_context_mut();
+        assert!(this.mutex_is_locked(mutex_ref), "queuing on unlocked mutex");
+        let thread = this.active_thread();
+        mutex_ref.0.borrow_mut().queue.push_back(thread);
+        this.machine.sync.mutex_waits.insert(thread, mutex_ref.clone());
+        let mutex_ref = mutex_ref.clone();
+        this.block_thread(
+            BlockReason::Mutex,
+            None,
+            callback!(
+                @capture<'tcx> {
+                    mutex_ref: MutexRef,
+                    retval_succ: Scalar,
+                    retval_owner_died: Scalar,
+                    retval_not_recoverable: Scalar,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    assert!(!this.mutex_is_locked(&mutex_ref));
+                    this.machine.sync.mutex_waits.remove(&this.active_thread());
+                    let retval = match this.mutex_consistency(&mutex_ref) {
+                        MutexConsistency::Consistent => {
+                            this.mutex_lock(&mutex_ref);
+                            retval_succ
+                        }
+                        MutexConsistency::OwnerDied => {
+                            this.mutex_lock(&mutex_ref);
+                            retval_owner_died
+                        }
+                        MutexConsistency::NotRecoverable => {
+                            // Nobody can acquire the mutex anymore, so the next waiter has to
+                            // learn about that as well.
+                            let next = mutex_ref.0.borrow_mut().queue.pop_front();
+                            if let Some(next) = next {
+                                this.unblock_thread(next, BlockReason::Mutex)?;
+                            }
+                            retval_not_recoverable
+                        }
+                    };
+                    this.write_scalar(retval, &dest)
+                }
+            ),
+        );
+    }
+
+    /// Whether the data protected by the mutex can be trusted.
+    fn mutex_consistency(&self, mutex_ref: &MutexRef) -> MutexConsistency {
+        mutex_ref.0.borrow().consistency
+    }
+
+    /// Change the consistency of a robust mutex.
+    fn mutex_set_consistency(&mut self, mutex_ref: &MutexRef, consistency: MutexConsistency) {
+        let mut mutex = mutex_ref.0.borrow_mut();
+        assert!(mutex.robust, "only robust mutexes can become inconsistent");
+        mutex.consistency = consistency;
+    }
+
+    /// Release all robust mutexes that the active thread still holds because it is terminating.
+    /// The next owner of each mutex learns that the data it protects may be inconsistent.
+    fn release_robust_mutexes(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let thread = this.active_thread();
+        let held = this.machine.sync.robust_mutexes.remove(&thread).unwrap_or_default();
+        for mutex_ref in held {
+            {
+                let mut mutex = mutex_ref.0.borrow_mut();
+                assert_eq!(mutex.owner, Some(thread));
+                // Release the mutex completely, no matter how often it was acquired.
+                mutex.lock_count = 1;
+                mutex.consistency = MutexConsistency::OwnerDied;
+            }
+            this.mutex_unlock(&mutex_ref)?;
+        }
+        interp_ok(())
+    }
+
     #[inline]
     /// Check if locked.
     fn rwlock_is_locked(&self, id: RwLockId) -> bool {
@@ -466,9 +697,12 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let thread = this.active_thread();
         assert!(!this.rwlock_is_write_locked(id), "the lock is write locked");
         trace!("rwlock_reader_lock: {:?} now also held (one more time) by {:?}", id, thread);
+        this.note_sync_access(SyncObject::Global, /* write */ true);
+        let span = this.machine.current_span();
         let rwlock = &mut this.machine.sync.rwlocks[id];
         let count = rwlock.readers.entry(thread).or_insert(0);
         *count = count.strict_add(1);
+        rwlock.acquired_at.entry(thread).or_insert(span);
         if let Some(data_race) = &this.machine.data_race {
             data_race.acquire_clock(&rwlock.clock_unlocked, &this.machine.threads);
         }
@@ -479,6 +713,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     fn rwlock_reader_unlock(&mut self, id: RwLockId) -> InterpResult<'tcx, bool> {
         let this = self.eval_context_mut();
         let thread = this.active_thread();
+        this.note_sync_access(SyncObject::Global, /* write */ true);
         let rwlock = &mut this.machine.sync.rwlocks[id];
         match rwlock.readers.entry(thread) {
             Entry::Occupied(mut entry) => {
@@ -488,6 +723,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 if *count == 0 {
                     trace!("rwlock_reader_unlock: {:?} no longer held by {:?}", id, thread);
                     entry.remove();
+                    rwlock.acquired_at.remove(&thread);
                 } else {
                     trace!("rwlock_reader_unlock: {:?} held one less time by {:?}", id, thread);
                 }
@@ -554,8 +790,11 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let thread = this.active_thread();
         assert!(!this.rwlock_is_locked(id), "the rwlock is already locked");
         trace!("rwlock_writer_lock: {:?} now held by {:?}", id, thread);
+        this.note_sync_access(SyncObject::Global, /* write */ true);
+        let span = this.machine.current_span();
         let rwlock = &mut this.machine.sync.rwlocks[id];
         rwlock.writer = Some(thread);
+        rwlock.acquired_at.insert(thread, span);
         if let Some(data_race) = &this.machine.data_race {
             data_race.acquire_clock(&rwlock.clock_unlocked, &this.machine.threads);
         }
@@ -567,6 +806,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     fn rwlock_writer_unlock(&mut self, id: RwLockId) -> InterpResult<'tcx, bool> {
         let this = self.eval_context_mut();
         let thread = this.active_thread();
+        this.note_sync_access(SyncObject::Global, /* write */ true);
         let rwlock = &mut this.machine.sync.rwlocks[id];
         interp_ok(if let Some(current_writer) = rwlock.writer {
             if current_writer != thread {
@@ -574,6 +814,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 return interp_ok(false);
             }
             rwlock.writer = None;
+            rwlock.acquired_at.remove(&thread);
             trace!("rwlock_writer_unlock: {:?} unlocked by {:?}", id, thread);
             // Record release clock for next lock holder.
             if let Some(data_race) = &this.machine.data_race {
@@ -666,19 +907,24 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let thread = this.active_thread();
         let waiters = &mut this.machine.sync.condvars[condvar].waiters;
         waiters.push_back(thread);
+        // A spurious wakeup is implemented as a timeout that reports success.
+        let spurious_timeout = this.spurious_wakeup();
+        let spurious = spurious_timeout.is_some();
         this.block_thread(
             BlockReason::Condvar(condvar),
-            timeout,
+            spurious_timeout.or(timeout),
             callback!(
                 @capture<'tcx> {
                     condvar: CondvarId,
                     mutex_ref: MutexRef,
+                    spurious: bool,
                     retval_succ: Scalar,
                     retval_timeout: Scalar,
                     dest: MPlaceTy<'tcx>,
                 }
                 @unblock = |this| {
                     // The condvar was signaled. Make sure we get the clock for that.
+                    this.note_sync_access(SyncObject::Global, /* write */ true);
                     if let Some(data_race) = &this.machine.data_race {
                         data_race.acquire_clock(
                             &this.machine.sync.condvars[condvar].clock,
@@ -694,8 +940,16 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     let thread = this.active_thread();
                     let waiters = &mut this.machine.sync.condvars[condvar].waiters;
                     waiters.retain(|waiter| *waiter != thread);
+                    let retval = if spurious {
+                        // Give the other threads a chance to run, as if the waiter had been
+                        // blocked for a while.
+                        this.yield_active_thread();
+                        retval_succ
+                    } else {
+                        retval_timeout
+                    };
                     // Now get back the lock.
-                    this.condvar_reacquire_mutex(&mutex_ref, retval_timeout, dest)
+                    this.condvar_reacquire_mutex(&mutex_ref, retval, dest)
                 }
             ),
         );
@@ -706,6 +960,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     /// variable. Returns `true` iff any thread was woken up.
     fn condvar_signal(&mut self, id: CondvarId) -> InterpResult<'tcx, bool> {
         let this = self.eval_context_mut();
+        this.note_sync_access(SyncObject::Global, /* write */ true);
         let condvar = &mut this.machine.sync.condvars[id];
         let data_race = &this.machine.data_race;
 
@@ -720,6 +975,14 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(true)
     }
 
+    /// Remember that the active thread signaled the condition variable while no thread was
+    /// waiting on it, so that deadlock reports can point out the lost wakeup.
+    fn condvar_signal_lost(&mut self, id: CondvarId) {
+        let this = self.eval_context_mut();
+        let signal = (this.active_thread(), this.machine.current_span());
+        this.machine.sync.condvars[id].lost_signal = Some(signal);
+    }
+
     /// Wait for the futex to be signaled, or a timeout.
     /// On a signal, `retval_succ` is written to `dest`.
     /// On a timeout, `retval_timeout` is written to `dest` and `errno_timeout` is set as the last error.
@@ -738,23 +1001,34 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let mut futex = futex_ref.0.borrow_mut();
         let waiters = &mut futex.waiters;
         assert!(waiters.iter().all(|waiter| waiter.thread != thread), "thread is already waiting");
-        waiters.push_back(FutexWaiter { thread, bitset });
+        let queue = Rc::new(RefCell::new(futex_ref.clone()));
+        waiters.push_back(FutexWaiter { thread, bitset, queue: Rc::downgrade(&queue) });
         drop(futex);
+        this.machine.sync.futex_waits.insert(thread, Rc::downgrade(&queue));
+        // A spurious wakeup is implemented as a timeout that reports success.
+        let spurious_timeout = this.spurious_wakeup();
+        let spurious = spurious_timeout.is_some();
 
         this.block_thread(
             BlockReason::Futex,
-            timeout,
+            spurious_timeout.or(timeout),
             callback!(
                 @capture<'tcx> {
-                    futex_ref: FutexRef,
+                    queue: Rc<RefCell<FutexRef>>,
+                    spurious: bool,
                     retval_succ: Scalar,
                     retval_timeout: Scalar,
                     dest: MPlaceTy<'tcx>,
                     errno_timeout: IoError,
                 }
                 @unblock = |this| {
+                    this.machine.sync.futex_waits.remove(&this.active_thread());
+                    // We might have been requeued, so this is not necessarily the futex we
+                    // started waiting on.
+                    let futex_ref = queue.borrow();
                     let futex = futex_ref.0.borrow();
                     // Acquire the clock of the futex.
+                    this.note_sync_access(SyncObject::Global, /* write */ true);
                     if let Some(data_race) = &this.machine.data_race {
                         data_race.acquire_clock(&futex.clock, &this.machine.threads);
                     }
@@ -763,10 +1037,18 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     interp_ok(())
                 }
                 @timeout = |this| {
-                    // Remove the waiter from the futex.
+                    // Remove the waiter from the futex it is currently queued on.
                     let thread = this.active_thread();
+                    this.machine.sync.futex_waits.remove(&thread);
+                    let futex_ref = queue.borrow();
                     let mut futex = futex_ref.0.borrow_mut();
                     futex.waiters.retain(|waiter| waiter.thread != thread);
+                    if spurious {
+                        // Like for condition variables, let the other threads run first.
+                        this.yield_active_thread();
+                        this.write_scalar(retval_succ, &dest)?;
+                        return interp_ok(());
+                    }
                     // Set errno and write return value.
                     this.set_last_error(errno_timeout)?;
                     this.write_scalar(retval_timeout, &dest)?;
@@ -784,6 +1066,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let data_race = &this.machine.data_race;
 
         // Each futex-wake happens-before the end of the futex wait
+        this.note_sync_access(SyncObject::Global, /* write */ true);
         if let Some(data_race) = data_race {
             data_race.release_clock(&this.machine.threads, |clock| futex.clock.clone_from(clock));
         }
@@ -797,4 +1080,197 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         this.unblock_thread(waiter.thread, BlockReason::Futex)?;
         interp_ok(true)
     }
+
+    /// Remember that the active thread woke the futex while no thread was waiting on it, so that
+    /// deadlock reports can point out the lost wakeup.
+    fn futex_wake_lost(&mut self, futex_ref: &FutexRef) {
+        let this = self.eval_context_mut();
+        futex_ref.0.borrow_mut().lost_wake =
+            Some((this.active_thread(), this.machine.current_span()));
+    }
+
+    /// The threads waiting on the futex, in the order in which `futex_wake` considers them.
+    fn futex_waiters(&self, futex_ref: &FutexRef) -> Vec<ThreadId> {
+        futex_ref.0.borrow().waiters.iter().map(|waiter| waiter.thread).collect()
+    }
+
+    /// Wake up to `nr_wake` threads waiting on `futex_ref`, regardless of their bitset, and then
+    /// move up to `nr_requeue` of the remaining waiters over to `target_ref` without waking them.
+    /// Returns how many threads were woken up and how many were requeued.
+    fn futex_requeue(
+        &mut self,
+        futex_ref: &FutexRef,
+        target_ref: &FutexRef,
+        nr_wake: u64,
+        nr_requeue: u64,
+    ) -> InterpResult<'tcx, (u64, u64)> {
+        let this = self.eval_context_mut();
+
+        let mut woken = 0;
+        while woken < nr_wake && this.futex_wake(futex_ref, u32::MAX)? {
+            woken = woken.strict_add(1);
+        }
+
+        let mut futex = futex_ref.0.borrow_mut();
+        let nr_requeue = nr_requeue.min(u64::try_from(futex.waiters.len()).unwrap());
+        let requeued: Vec<_> =
+            futex.waiters.drain(..usize::try_from(nr_requeue).unwrap()).collect();
+        drop(futex);
+        for waiter in &requeued {
+            // The thread is blocked, so its callback is still around.
+            *waiter.queue.upgrade().unwrap().borrow_mut() = target_ref.clone();
+        }
+        target_ref.0.borrow_mut().waiters.extend(requeued);
+
+        interp_ok((woken, nr_requeue))
+    }
+
+    #[inline]
+    /// Get the current value of the semaphore.
+    fn semaphore_value(&self, sem_ref: &SemaphoreRef) -> u32 {
+        sem_ref.0.borrow().value
+    }
+
+    #[inline]
+    /// Check if there are threads waiting on the semaphore.
+    fn semaphore_is_awaited(&self, sem_ref: &SemaphoreRef) -> bool {
+        !sem_ref.0.borrow().waiters.is_empty()
+    }
+
+    /// Take a unit from the semaphore if there is one. Returns whether that succeeded.
+    fn semaphore_try_acquire(&mut self, sem_ref: &SemaphoreRef) -> bool {
+        let this = self.eval_context_mut();
+        let mut sem = sem_ref.0.borrow_mut();
+        if sem.value == 0 {
+            return false;
+        }
+        sem.value = sem.value.strict_sub(1);
+        if let Some(data_race) = &this.machine.data_race {
+            data_race.acquire_clock(&sem.clock, &this.machine.threads);
+        }
+        true
+    }
+
+    /// Add a unit to the semaphore, handing it to the first waiting thread if there is one.
+    /// The caller is responsible for checking that the value does not overflow.
+    fn semaphore_release(&mut self, sem_ref: &SemaphoreRef) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let mut sem = sem_ref.0.borrow_mut();
+        // Each post happens-before every later wait that returns.
+        if let Some(data_race) = &this.machine.data_race {
+            data_race.release_clock(&this.machine.threads, |clock| sem.clock.join(clock));
+        }
+        if let Some(waiter) = sem.waiters.pop_front() {
+            drop(sem);
+            this.unblock_thread(waiter, BlockReason::Semaphore)?;
+        } else {
+            sem.value = sem.value.strict_add(1);
+        }
+        interp_ok(())
+    }
+
+    /// Wait until a unit is handed to this thread by `semaphore_release`, or a timeout.
+    /// The semaphore must currently have no units.
+    /// On success, `retval_succ` is written to `dest`.
+    /// On a timeout, `retval_timeout` is written to `dest` and `errno_timeout` is set as the last error.
+    fn semaphore_wait(
+        &mut self,
+        sem_ref: SemaphoreRef,
+        timeout: Option<(TimeoutClock, TimeoutAnchor, Duration)>,
+        retval_succ: Scalar,
+        retval_timeout: Scalar,
+        dest: MPlaceTy<'tcx>,
+        errno_timeout: IoError,
+    ) {
+        let this = self.eval_context_mut();
+        let thread = this.active_thread();
+        let mut sem = sem_ref.0.borrow_mut();
+        assert_eq!(sem.value, 0, "waiting on a semaphore that has units left");
+        sem.waiters.push_back(thread);
+        drop(sem);
+
+        this.block_thread(
+            BlockReason::Semaphore,
+            timeout,
+            callback!(
+                @capture<'tcx> {
+                    sem_ref: SemaphoreRef,
+                    retval_succ: Scalar,
+                    retval_timeout: Scalar,
+                    dest: MPlaceTy<'tcx>,
+                    errno_timeout: IoError,
+                }
+                @unblock = |this| {
+                    let sem = sem_ref.0.borrow();
+                    if let Some(data_race) = &this.machine.data_race {
+                        data_race.acquire_clock(&sem.clock, &this.machine.threads);
+                    }
+                    this.write_scalar(retval_succ, &dest)?;
+                    interp_ok(())
+                }
+                @timeout = |this| {
+                    // Remove the waiter from the semaphore.
+                    let thread = this.active_thread();
+                    sem_ref.0.borrow_mut().waiters.retain(|&waiter| waiter != thread);
+                    this.set_last_error(errno_timeout)?;
+                    this.write_scalar(retval_timeout, &dest)?;
+                    interp_ok(())
+                }
+            ),
+        );
+    }
+
+    /// The threads holding the lock that the given thread is blocked on, in the form of edges of
+    /// the wait-for graph. This is empty if the thread is not blocked on a lock.
+    fn lock_wait_for(&self, thread: ThreadId) -> Vec<WaitFor> {
+        let this = self.eval_context_ref();
+        match this.machine.threads.block_reason(thread) {
+            Some(BlockReason::Mutex) => {
+                let mutex = this.machine.sync.mutex_waits[&thread].0.borrow();
+                mutex
+                    .owner
+                    .map(|holder| {
+                        WaitFor {
+                            waiter: thread,
+                            holder,
+                            lock: Some(("a mutex", mutex.acquired_at.unwrap())),
+                        }
+                    })
+                    .into_iter()
+                    .collect()
+            }
+            Some(BlockReason::RwLock(id)) => {
+                let rwlock = &this.machine.sync.rwlocks[id];
+                let mut holders: Vec<_> = rwlock.acquired_at.iter().collect();
+                // Report the holders in a deterministic order.
+                holders.sort_by_key(|(holder, _)| **holder);
+                holders
+                    .into_iter()
+                    .map(|(&holder, &span)| {
+                        WaitFor { waiter: thread, holder, lock: Some(("a read-write lock", span)) }
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// If the given thread is blocked on a condition variable or futex that was woken up earlier
+    /// while no thread was waiting on it, the description of that primitive and the thread that
+    /// woke it up, and where.
+    fn lost_wakeup(&self, thread: ThreadId) -> Option<(&'static str, ThreadId, Span)> {
+        let this = self.eval_context_ref();
+        match this.machine.threads.block_reason(thread)? {
+            BlockReason::Condvar(id) => {
+                let (waker, span) = this.machine.sync.condvars[id].lost_signal?;
+                Some(("a condition variable", waker, span))
+            }
+            BlockReason::Futex => {
+                let queue = this.machine.sync.futex_waits.get(&thread)?.upgrade()?;
+                let (waker, span) = queue.borrow().0.borrow().lost_wake?;
+                Some(("a futex", waker, span))
+            }
+            _ => None,
+        }
+    }
 }
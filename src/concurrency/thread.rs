@@ -1,30 +1,39 @@
 //! Implements threads.
 
-use std::mem;
+use std::cell::Cell;
+use std::cmp::Reverse;
 use std::sync::atomic::Ordering::Relaxed;
 use std::task::Poll;
 use std::time::{Duration, SystemTime};
+use std::{iter, mem};
 
 use either::Either;
 use rustc_abi::ExternAbi;
 use rustc_const_eval::CTRL_C_RECEIVED;
 use rustc_data_structures::fx::FxHashMap;
+use rustc_hir::LangItem;
 use rustc_hir::def_id::DefId;
 use rustc_index::{Idx, IndexVec};
-use rustc_middle::mir::Mutability;
+use rustc_middle::mir::{self, Mutability};
 use rustc_middle::ty::layout::TyAndLayout;
 use rustc_span::Span;
 
-use crate::concurrency::data_race;
+use crate::concurrency::schedule::{
+    Decision, ScheduleEvent, ScheduleExplorer, ScheduleTrace, ScheduleTracer, SyncObject,
+};
+use crate::concurrency::{VClock, data_race};
 use crate::shims::tls;
 use crate::*;
 
+/// The probability of the active thread being preempted at a `miri_yield_hint`.
+const YIELD_HINT_PREEMPTION_RATE: f64 = 0.5;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum SchedulingAction {
     /// Execute step on the active thread.
     ExecuteStep,
-    /// Execute a timeout callback.
-    ExecuteTimeoutCallback,
+    /// Execute the timeout callback of the given thread.
+    ExecuteTimeoutCallback(ThreadId),
     /// Wait for a bit, until there is a timeout to be called.
     Sleep(Duration),
 }
@@ -153,20 +162,71 @@ pub enum BlockReason {
     RwLock(RwLockId),
     /// Blocked on a Futex variable.
     Futex,
+    /// Blocked on a semaphore.
+    Semaphore,
     /// Blocked on an InitOnce.
     InitOnce(InitOnceId),
     /// Blocked on epoll.
     Epoll,
     /// Blocked on eventfd.
     Eventfd,
+    /// Blocked on unnamed_socket.
+    UnnamedSocket,
+    /// Blocked on a named socket.
+    Socket,
+    /// Blocked on `poll` or `select`.
+    Poll,
+    /// Blocked on `kevent`.
+    Kqueue,
+    /// Blocked on a Windows named pipe.
+    NamedPipe,
+    /// Blocked on a Windows I/O completion port.
+    CompletionPort,
+    /// Blocked in `WaitForSingleObject`, `WaitForMultipleObjects` or `GetOverlappedResult`.
+    WaitForObjects,
+    /// The thread acts on a cancellation request, and exits as soon as the shim that noticed the
+    /// request has returned.
+    Cancelled,
+}
+
+impl BlockReason {
+    /// Describe the blocking operation, for diagnostics. Returns `None` if the thread is not
+    /// blocked by an operation of the program.
+    fn blocking_operation(self) -> Option<&'static str> {
+        Some(match self {
+            BlockReason::Join(_) => "joining a thread",
+            BlockReason::Sleep => "sleeping",
+            BlockReason::Mutex => "locking a mutex",
+            BlockReason::Condvar(_) => "waiting on a condition variable",
+            BlockReason::RwLock(_) => "locking a reader-writer lock",
+            BlockReason::Futex => "waiting on a futex",
+            BlockReason::Semaphore => "waiting on a semaphore",
+            BlockReason::InitOnce(_) => "waiting for a one-time initialization",
+            BlockReason::Epoll => "waiting on an epoll instance",
+            BlockReason::Eventfd => "blocking on an eventfd",
+            BlockReason::UnnamedSocket | BlockReason::Socket => "blocking on a socket",
+            BlockReason::Poll => "waiting in `poll` or `select`",
+            BlockReason::Kqueue => "waiting in `kevent`",
+            BlockReason::NamedPipe => "blocking on a named pipe",
+            BlockReason::CompletionPort => "waiting on an I/O completion port",
+            BlockReason::WaitForObjects => "waiting for a kernel object",
+            BlockReason::Cancelled => return None,
+        })
+    }
 }
 
 /// The state of a thread.
 enum ThreadState<'tcx> {
     /// The thread is enabled and can be executed.
     Enabled,
-    /// The thread is blocked on something.
-    Blocked { reason: BlockReason, timeout: Option<Timeout>, callback: DynUnblockCallback<'tcx> },
+    /// The thread is blocked on something. If it is `cancellable`, it is waiting at a
+    /// cancellation point and `pthread_cancel` can interrupt the wait.
+    Blocked {
+        reason: BlockReason,
+        timeout: Option<Timeout>,
+        callback: DynUnblockCallback<'tcx>,
+        cancellable: bool,
+    },
     /// The thread has terminated its execution. We do not delete terminated
     /// threads (FIXME: why?).
     Terminated,
@@ -216,6 +276,9 @@ pub struct Thread<'tcx> {
     /// Name of the thread.
     thread_name: Option<Vec<u8>>,
 
+    /// The scheduling attributes of the thread.
+    priority: ThreadPriority,
+
     /// The virtual call stack.
     stack: Vec<Frame<'tcx, Provenance, FrameExtra<'tcx>>>,
 
@@ -247,6 +310,67 @@ pub struct Thread<'tcx> {
 
     /// Last OS error location in memory. It is a 32-bit integer.
     pub(crate) last_error: Option<MPlaceTy<'tcx>>,
+
+    /// The head of the robust futex list, as registered with the `set_robust_list` syscall.
+    pub(crate) robust_list: Option<Pointer>,
+
+    /// The state of `pthread_cancel` requests for this thread.
+    pub(crate) cancel: CancelState,
+
+    /// The thread that created this thread, and its stack at that moment.
+    /// Only recorded when data races are reported in the format of ThreadSanitizer, or when
+    /// leaked threads are reported.
+    creation: Option<(ThreadId, Vec<FrameInfo<'tcx>>)>,
+}
+
+/// The cancellation state of a thread, as managed by `pthread_cancel`, `pthread_setcancelstate`
+/// and `pthread_setcanceltype`.
+#[derive(Debug)]
+pub struct CancelState {
+    /// Whether cancellation requests are acted upon (`PTHREAD_CANCEL_ENABLE`).
+    pub enabled: bool,
+    /// Whether the cancellation type is `PTHREAD_CANCEL_ASYNCHRONOUS`.
+    pub asynchronous: bool,
+    /// Whether there is a cancellation request that was not acted upon yet.
+    pub pending: bool,
+    /// Whether the thread acted upon a cancellation request and is now exiting.
+    pub cancelled: bool,
+    /// The handlers registered with `pthread_cleanup_push` that were not popped yet, as pairs of
+    /// routine and argument. The innermost handler comes last.
+    pub cleanup_handlers: Vec<(Pointer, Scalar)>,
+}
+
+impl Default for CancelState {
+    fn default() -> Self {
+        CancelState {
+            enabled: true,
+            asynchronous: false,
+            pending: false,
+            cancelled: false,
+            cleanup_handlers: Vec::new(),
+        }
+    }
+}
+
+/// The scheduling attributes of a thread, as set by `pthread_setschedparam`, `setpriority` and
+/// `nice`. They only affect scheduling under `-Zmiri-priority-scheduling`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ThreadPriority {
+    /// The scheduling policy as a libc `SCHED_*` constant, or `None` if it was never set and the
+    /// thread uses the default policy.
+    pub policy: Option<i32>,
+    /// The static priority. Only threads with a real-time policy have a priority above 0.
+    pub sched_priority: i32,
+    /// The nice value. Lower values mean that the thread is more important.
+    pub nice: i32,
+}
+
+impl ThreadPriority {
+    /// Threads that should run first have a greater rank. The static priority always takes
+    /// precedence over the nice value.
+    fn rank(&self) -> (i32, Reverse<i32>) {
+        (self.sched_priority, Reverse(self.nice))
+    }
 }
 
 pub type StackEmptyCallback<'tcx> =
@@ -321,15 +445,23 @@ impl<'tcx> std::fmt::Debug for Thread<'tcx> {
 }
 
 impl<'tcx> Thread<'tcx> {
-    fn new(name: Option<&str>, on_stack_empty: Option<StackEmptyCallback<'tcx>>) -> Self {
+    fn new(
+        name: Option<&str>,
+        priority: ThreadPriority,
+        on_stack_empty: Option<StackEmptyCallback<'tcx>>,
+    ) -> Self {
         Self {
             state: ThreadState::Enabled,
             thread_name: name.map(|name| Vec::from(name.as_bytes())),
+            priority,
             stack: Vec::new(),
             top_user_relevant_frame: None,
             join_status: ThreadJoinStatus::Joinable,
             panic_payloads: Vec::new(),
             last_error: None,
+            robust_list: None,
+            cancel: CancelState::default(),
+            creation: None,
             on_stack_empty,
         }
     }
@@ -340,11 +472,15 @@ impl VisitProvenance for Thread<'_> {
         let Thread {
             panic_payloads: panic_payload,
             last_error,
+            robust_list,
+            cancel,
             stack,
             top_user_relevant_frame: _,
             state: _,
             thread_name: _,
+            priority: _,
             join_status: _,
+            creation: _,
             on_stack_empty: _, // we assume the closure captures no GC-relevant state
         } = self;
 
@@ -352,6 +488,11 @@ impl VisitProvenance for Thread<'_> {
             payload.visit_provenance(visit);
         }
         last_error.visit_provenance(visit);
+        robust_list.visit_provenance(visit);
+        for (routine, arg) in &cancel.cleanup_handlers {
+            routine.visit_provenance(visit);
+            arg.visit_provenance(visit);
+        }
         for frame in stack {
             frame.visit_provenance(visit)
         }
@@ -401,7 +542,7 @@ impl Timeout {
         match self {
             Timeout::Monotonic(instant) => instant.duration_since(clock.now()),
             Timeout::RealTime(time) =>
-                time.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO),
+                time.duration_since(clock.system_now()).unwrap_or(Duration::ZERO),
         }
     }
 
@@ -450,7 +591,13 @@ pub struct ThreadManager<'tcx> {
     /// A mapping from a thread-local static to the thread specific allocation.
     thread_local_allocs: FxHashMap<(DefId, ThreadId), StrictPointer>,
     /// A flag that indicates that we should change the active thread.
-    yield_active_thread: bool,
+    yield_active_thread: Cell<bool>,
+    /// The state of schedule exploration, if the scheduler does not follow its default policy.
+    explorer: Option<ScheduleExplorer>,
+    /// Records the scheduling events, or makes the scheduler follow a recorded trace.
+    tracer: Option<ScheduleTracer>,
+    /// Whether the scheduler always runs one of the enabled threads with the highest priority.
+    priority_scheduling: bool,
 }
 
 impl VisitProvenance for ThreadManager<'_> {
@@ -460,6 +607,9 @@ impl VisitProvenance for ThreadManager<'_> {
             thread_local_allocs,
             active_thread: _,
             yield_active_thread: _,
+            explorer: _,
+            tracer: _,
+            priority_scheduling: _,
         } = self;
 
         for thread in threads {
@@ -471,21 +621,26 @@ impl VisitProvenance for ThreadManager<'_> {
     }
 }
 
-impl<'tcx> Default for ThreadManager<'tcx> {
-    fn default() -> Self {
+impl<'tcx> ThreadManager<'tcx> {
+    pub(crate) fn new(
+        explorer: Option<ScheduleExplorer>,
+        tracer: Option<ScheduleTracer>,
+        priority_scheduling: bool,
+    ) -> Self {
         let mut threads = IndexVec::new();
         // Create the main thread and add it to the list of threads.
-        threads.push(Thread::new(Some("main"), None));
+        threads.push(Thread::new(Some("main"), ThreadPriority::default(), None));
         Self {
             active_thread: ThreadId::MAIN_THREAD,
             threads,
             thread_local_allocs: Default::default(),
-            yield_active_thread: false,
+            yield_active_thread: Cell::new(false),
+            explorer,
+            tracer,
+            priority_scheduling,
         }
     }
-}
 
-impl<'tcx> ThreadManager<'tcx> {
     pub(crate) fn init(
         ecx: &mut MiriInterpCx<'tcx>,
         on_main_stack_empty: StackEmptyCallback<'tcx>,
@@ -544,7 +699,11 @@ impl<'tcx> ThreadManager<'tcx> {
     /// Create a new thread and returns its id.
     fn create_thread(&mut self, on_stack_empty: StackEmptyCallback<'tcx>) -> ThreadId {
         let new_thread_id = ThreadId::new(self.threads.len());
-        self.threads.push(Thread::new(None, Some(on_stack_empty)));
+        // Like with `PTHREAD_INHERIT_SCHED`, the new thread starts with the priority of its creator.
+        let priority = self.threads[self.active_thread].priority;
+        self.threads.push(Thread::new(None, priority, Some(on_stack_empty)));
+        // The new thread may run before its creator continues.
+        self.note_sync_point();
         new_thread_id
     }
 
@@ -575,11 +734,66 @@ impl<'tcx> ThreadManager<'tcx> {
         self.threads.iter().filter(|t| !t.state.is_terminated()).count()
     }
 
+    /// Get the ids of all threads that are currently live, i.e., not yet terminated.
+    pub fn live_threads(&self) -> Vec<ThreadId> {
+        self.threads
+            .iter_enumerated()
+            .filter(|(_, t)| !t.state.is_terminated())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
     /// Has the given thread terminated?
-    fn has_terminated(&self, thread_id: ThreadId) -> bool {
+    pub fn has_terminated(&self, thread_id: ThreadId) -> bool {
         self.threads[thread_id].state.is_terminated()
     }
 
+    /// Get the thread that created the given thread, and its stack at that moment, if recorded.
+    pub fn creation(&self, thread_id: ThreadId) -> Option<(ThreadId, &[FrameInfo<'tcx>])> {
+        self.threads[thread_id].creation.as_ref().map(|(creator, stack)| (*creator, &stack[..]))
+    }
+
+    /// Describe what the given thread is currently doing, for diagnostics.
+    pub fn describe_state(&self, thread_id: ThreadId) -> String {
+        match self.threads[thread_id].state {
+            ThreadState::Enabled => "still running".to_string(),
+            ThreadState::Blocked { reason, .. } =>
+                match reason.blocking_operation() {
+                    Some(operation) => format!("blocked {operation}"),
+                    None => "being cancelled".to_string(),
+                },
+            ThreadState::Terminated => "terminated".to_string(),
+        }
+    }
+
+    /// Get the location in the program where the given thread currently is.
+    pub fn thread_current_span(&self, thread_id: ThreadId) -> Span {
+        self.threads[thread_id].current_span()
+    }
+
+    /// Get the reason why the given thread is blocked, if it is blocked.
+    pub fn block_reason(&self, thread_id: ThreadId) -> Option<BlockReason> {
+        match self.threads[thread_id].state {
+            ThreadState::Blocked { reason, .. } => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// Get the robust futex list that the given thread registered with `set_robust_list`.
+    pub fn robust_list(&self, thread_id: ThreadId) -> Option<Pointer> {
+        self.threads[thread_id].robust_list
+    }
+
+    /// Get the cancellation state of the given thread.
+    pub fn cancel_state_mut(&mut self, thread_id: ThreadId) -> &mut CancelState {
+        &mut self.threads[thread_id].cancel
+    }
+
+    /// Is the given thread enabled, i.e. neither blocked nor terminated?
+    pub fn is_enabled(&self, thread_id: ThreadId) -> bool {
+        self.threads[thread_id].state.is_enabled()
+    }
+
     /// Have all threads terminated?
     fn have_all_terminated(&self) -> bool {
         self.threads.iter().all(|thread| thread.state.is_terminated())
@@ -711,6 +925,29 @@ impl<'tcx> ThreadManager<'tcx> {
         self.threads[thread].thread_display_name(thread)
     }
 
+    /// Set the scheduling attributes of the given thread.
+    pub fn set_thread_priority(&mut self, thread: ThreadId, priority: ThreadPriority) {
+        self.threads[thread].priority = priority;
+    }
+
+    /// Get the scheduling attributes of the given thread.
+    pub fn get_thread_priority(&self, thread: ThreadId) -> ThreadPriority {
+        self.threads[thread].priority
+    }
+
+    /// Under priority scheduling, restrict `candidates` to those with the highest priority among
+    /// them, keeping their order.
+    fn retain_highest_priority(&self, candidates: &mut Vec<ThreadId>) {
+        if !self.priority_scheduling {
+            return;
+        }
+        let Some(highest) = candidates.iter().map(|&id| self.threads[id].priority.rank()).max()
+        else {
+            return;
+        };
+        candidates.retain(|&id| self.threads[id].priority.rank() == highest);
+    }
+
     /// Put the thread into the blocked state.
     fn block_thread(
         &mut self,
@@ -720,29 +957,223 @@ impl<'tcx> ThreadManager<'tcx> {
     ) {
         let state = &mut self.threads[self.active_thread].state;
         assert!(state.is_enabled());
-        *state = ThreadState::Blocked { reason, timeout, callback }
+        *state = ThreadState::Blocked { reason, timeout, callback, cancellable: false }
     }
 
     /// Change the active thread to some enabled thread.
-    fn yield_active_thread(&mut self) {
+    pub(crate) fn yield_active_thread(&self) {
         // We do not yield immediately, as swapping out the current stack while executing a MIR statement
         // could lead to all sorts of confusion.
         // We should only switch stacks between steps.
-        self.yield_active_thread = true;
+        self.yield_active_thread.set(true);
+    }
+
+    /// Record that the active thread performed a synchronization operation. When exploring
+    /// schedules, this is where other threads get a chance to run.
+    pub fn note_sync_point(&self) {
+        if let Some(explorer) = &self.explorer {
+            explorer.note_sync_point();
+        }
+    }
+
+    /// Whether the schedule is chosen by the schedule explorer.
+    pub fn is_exploring_schedules(&self) -> bool {
+        self.explorer.is_some()
+    }
+
+    /// Record that the active thread accessed a synchronization object, with the given vector
+    /// clock. Like `note_sync_point`, and additionally lets the schedule explorer find operations
+    /// that conflict with this one.
+    pub fn note_sync_access(&self, object: SyncObject, write: bool, clock: Option<VClock>) {
+        if let Some(explorer) = &self.explorer {
+            explorer.note_access(self.active_thread, object, write, clock);
+        }
+    }
+
+    /// Whether the schedule trace or the schedule explorer decides which store atomic loads read
+    /// from, rather than the RNG.
+    pub fn decides_loads(&self) -> bool {
+        self.tracer.as_ref().is_some_and(|tracer| tracer.is_replaying())
+            || self.explorer.as_ref().is_some_and(|explorer| explorer.explores_loads())
+    }
+
+    /// Pick which of `count` stores an atomic load reads from, ordered from the latest store in
+    /// modification order backwards, if `decides_loads` is set. Returns `None` if the trace or
+    /// the schedule asks for a store that does not exist.
+    pub fn decide_load(&self, count: usize) -> Option<usize> {
+        self.decide_choice(count, ScheduleTracer::replayed_load, |explorer, count| {
+            explorer.decide_load(count)
+        })
+    }
+
+    /// Record that an atomic load read from the store with the given index, counting backwards
+    /// from the latest store, in the schedule trace.
+    pub fn note_load(&self, store: usize) {
+        self.note_choice(store, ScheduleEvent::Load);
+    }
+
+    /// Whether the schedule trace or the schedule explorer decides in which order epoll handles
+    /// ready events and blocked threads, rather than `-Zmiri-epoll-wakeup-order`.
+    pub fn decides_wakeups(&self) -> bool {
+        self.tracer.as_ref().is_some_and(|tracer| tracer.is_replaying())
+            || self.explorer.as_ref().is_some_and(|explorer| explorer.explores_wakeups())
+    }
+
+    /// Pick which of `count` ready events or blocked threads epoll handles next, if
+    /// `decides_wakeups` is set. Returns `None` if the trace or the schedule asks for a choice
+    /// that does not exist.
+    pub fn decide_wakeup(&self, count: usize) -> Option<usize> {
+        self.decide_choice(count, ScheduleTracer::replayed_wakeup, |explorer, count| {
+            explorer.decide_wakeup(count)
+        })
+    }
+
+    /// Record which ready event or blocked thread epoll handled next in the schedule trace.
+    pub fn note_wakeup(&self, choice: usize) {
+        self.note_choice(choice, ScheduleEvent::Wakeup);
     }
 
-    /// Get the wait time for the next timeout, or `None` if no timeout is pending.
-    fn next_callback_wait_time(&self, clock: &Clock) -> Option<Duration> {
+    /// Pick one of `count` alternatives from the trace being replayed, or else from the
+    /// schedule explorer. Choice 0 is the default.
+    fn decide_choice(
+        &self,
+        count: usize,
+        replayed: impl FnOnce(&ScheduleTracer) -> u32,
+        explored: impl FnOnce(&ScheduleExplorer, usize) -> Option<usize>,
+    ) -> Option<usize> {
+        if count < 2 {
+            return Some(0);
+        }
+        match &self.tracer {
+            Some(tracer) if tracer.is_replaying() => {
+                let choice = usize::try_from(replayed(tracer)).unwrap();
+                if choice < count {
+                    Some(choice)
+                } else if tracer.is_lenient() {
+                    // The run diverged from the trace, so make the default choice.
+                    Some(0)
+                } else {
+                    None
+                }
+            }
+            _ => explored(self.explorer.as_ref().unwrap(), count),
+        }
+    }
+
+    /// Record a choice other than the default one in the schedule trace.
+    fn note_choice(&self, choice: usize, event: impl FnOnce(u32) -> ScheduleEvent) {
+        if let Some(tracer) = &self.tracer
+            && choice != 0
+        {
+            tracer.record_event(event(u32::try_from(choice).unwrap()));
+        }
+    }
+
+    /// Take the scheduling decisions made in this run, if schedules are being explored.
+    pub fn take_schedule_decisions(&mut self) -> Vec<Decision> {
+        self.explorer.as_mut().map(|explorer| explorer.take_decisions()).unwrap_or_default()
+    }
+
+    /// Let the schedule explorer pick one of `candidates`, where the first candidate is the
+    /// default choice. Without exploration, this always picks the default.
+    fn choose_thread(
+        &mut self,
+        candidates: &[ThreadId],
+        preemptive: bool,
+    ) -> InterpResult<'tcx, ThreadId> {
+        let Some(explorer) = &mut self.explorer else {
+            return interp_ok(candidates[0]);
+        };
+        if candidates.len() < 2 {
+            return interp_ok(candidates[0]);
+        }
+        let Some(id) = explorer.decide(candidates, preemptive) else {
+            throw_unsup_format!(
+                "the schedule does not match this execution; schedules can only be replayed with the same program, inputs, and flags"
+            );
+        };
+        interp_ok(id)
+    }
+
+    /// Get the wait time for the next timeout and the thread it belongs to, or `None` if no
+    /// timeout is pending.
+    fn next_callback_wait_time(&self, clock: &Clock) -> Option<(Duration, ThreadId)> {
         self.threads
-            .iter()
-            .filter_map(|t| {
+            .iter_enumerated()
+            .filter_map(|(id, t)| {
                 match &t.state {
                     ThreadState::Blocked { timeout: Some(timeout), .. } =>
-                        Some(timeout.get_wait_time(clock)),
+                        Some((timeout.get_wait_time(clock), id)),
                     _ => None,
                 }
             })
-            .min()
+            .min_by_key(|&(wait_time, _)| wait_time)
+    }
+
+    /// The scheduling events of this run, if they are being recorded.
+    pub fn recorded_schedule_trace(&self) -> Option<ScheduleTrace> {
+        self.tracer.as_ref().map(|tracer| tracer.recorded())
+    }
+
+    /// Decide which action to take next and on which thread, and record or replay that decision
+    /// if there is a schedule trace.
+    fn schedule(&mut self, clock: &Clock) -> InterpResult<'tcx, SchedulingAction> {
+        let previous_thread = self.active_thread;
+        let action = if self.tracer.as_ref().is_some_and(|tracer| tracer.is_replaying()) {
+            self.schedule_replay(clock)?
+        } else {
+            self.schedule_default(clock)?
+        };
+        if let Some(tracer) = &mut self.tracer {
+            match action {
+                SchedulingAction::ExecuteStep if self.active_thread != previous_thread =>
+                    tracer.record_event(ScheduleEvent::Switch(self.active_thread)),
+                SchedulingAction::ExecuteTimeoutCallback(thread) =>
+                    tracer.record_event(ScheduleEvent::Timeout(thread)),
+                _ => {}
+            }
+            if !matches!(action, SchedulingAction::Sleep(_)) {
+                tracer.finish_step();
+            }
+        }
+        interp_ok(action)
+    }
+
+    /// Decide which action to take next by following the schedule trace. Threads only switch and
+    /// timeouts only fire where the trace says so, independent of the clock and of yields.
+    fn schedule_replay(&mut self, clock: &Clock) -> InterpResult<'tcx, SchedulingAction> {
+        let tracer = self.tracer.as_mut().unwrap();
+        let event = tracer.replayed_event();
+        let lenient = tracer.is_lenient();
+        self.yield_active_thread.set(false);
+        match event {
+            Some(ScheduleEvent::Switch(id))
+                if self.threads.get(id).is_some_and(|thread| thread.state.is_enabled()) =>
+            {
+                self.set_active_thread_id(id);
+            }
+            Some(ScheduleEvent::Timeout(id))
+                if self.threads.get(id).is_some_and(|thread| {
+                    matches!(thread.state, ThreadState::Blocked { timeout: Some(_), .. })
+                }) =>
+                return interp_ok(SchedulingAction::ExecuteTimeoutCallback(id)),
+            Some(_) if !lenient =>
+                throw_unsup_format!("the schedule trace does not match this execution"),
+            _ => {}
+        }
+        if self.threads[self.active_thread].state.is_enabled() {
+            return interp_ok(SchedulingAction::ExecuteStep);
+        }
+        if lenient {
+            // The run diverged from the trace, so pick the next thread as usual.
+            return self.schedule_default(clock);
+        }
+        if self.threads.iter().all(|thread| !thread.state.is_enabled())
+            && self.next_callback_wait_time(clock).is_none()
+        {
+            throw_machine_stop!(TerminationInfo::Deadlock);
+        }
+        throw_unsup_format!("the schedule trace does not match this execution")
     }
 
     /// Decide which action to take next and on which thread.
@@ -751,10 +1182,44 @@ impl<'tcx> ThreadManager<'tcx> {
     /// used in stateless model checkers such as Loom: run the active thread as
     /// long as we can and switch only when we have to (the active thread was
     /// blocked, terminated, or has explicitly asked to be preempted).
-    fn schedule(&mut self, clock: &Clock) -> InterpResult<'tcx, SchedulingAction> {
+    fn schedule_default(&mut self, clock: &Clock) -> InterpResult<'tcx, SchedulingAction> {
+        // Crucially, we consider threads starting after the current active thread ID, rather than
+        // at 0, since we want to avoid always scheduling threads 0 and 1 without ever making
+        // progress in thread 2.
+        //
+        // `skip(N)` means we start iterating at thread N, so we skip 1 more to start just *after*
+        // the active thread. Then after that we look at `take(N)`, i.e., the threads *before* the
+        // active thread.
+        let other_enabled_threads = || {
+            self.threads
+                .iter_enumerated()
+                .skip(self.active_thread.index() + 1)
+                .chain(self.threads.iter_enumerated().take(self.active_thread.index()))
+                .filter(|(_, thread)| thread.state.is_enabled())
+                .map(|(id, _)| id)
+        };
+        // Under priority scheduling, a thread that becomes enabled preempts all threads of lower
+        // priority.
+        let outranked = self.priority_scheduling && {
+            let rank = self.threads[self.active_thread].priority.rank();
+            other_enabled_threads().any(|id| self.threads[id].priority.rank() > rank)
+        };
         // This thread and the program can keep going.
-        if self.threads[self.active_thread].state.is_enabled() && !self.yield_active_thread {
-            // The currently active thread is still enabled, just continue with it.
+        if self.threads[self.active_thread].state.is_enabled()
+            && !self.yield_active_thread.get()
+            && !outranked
+        {
+            // The currently active thread is still enabled, just continue with it -- unless we are
+            // exploring schedules and it just synchronized, then other threads could run as well.
+            if self.explorer.as_ref().is_some_and(|explorer| explorer.take_sync_point()) {
+                let mut candidates: Vec<ThreadId> =
+                    iter::once(self.active_thread).chain(other_enabled_threads()).collect();
+                self.retain_highest_priority(&mut candidates);
+                let id = self.choose_thread(&candidates, /* preemptive */ true)?;
+                if id != self.active_thread {
+                    self.set_active_thread_id(id);
+                }
+            }
             return interp_ok(SchedulingAction::ExecuteStep);
         }
         // The active thread yielded or got terminated. Let's see if there are any timeouts to take
@@ -763,43 +1228,42 @@ impl<'tcx> ThreadManager<'tcx> {
         // `pthread_cond_timedwait`, "an error is returned if [...] the absolute time specified by
         // abstime has already been passed at the time of the call".
         // <https://pubs.opengroup.org/onlinepubs/9699919799/functions/pthread_cond_timedwait.html>
-        let potential_sleep_time = self.next_callback_wait_time(clock);
-        if potential_sleep_time == Some(Duration::ZERO) {
-            return interp_ok(SchedulingAction::ExecuteTimeoutCallback);
+        let next_timeout = self.next_callback_wait_time(clock);
+        if let Some((Duration::ZERO, thread)) = next_timeout {
+            return interp_ok(SchedulingAction::ExecuteTimeoutCallback(thread));
         }
         // No callbacks immediately scheduled, pick a regular thread to execute.
         // The active thread blocked or yielded. So we go search for another enabled thread.
-        // Crucially, we start searching at the current active thread ID, rather than at 0, since we
-        // want to avoid always scheduling threads 0 and 1 without ever making progress in thread 2.
-        //
-        // `skip(N)` means we start iterating at thread N, so we skip 1 more to start just *after*
-        // the active thread. Then after that we look at `take(N)`, i.e., the threads *before* the
-        // active thread.
-        let threads = self
-            .threads
-            .iter_enumerated()
-            .skip(self.active_thread.index() + 1)
-            .chain(self.threads.iter_enumerated().take(self.active_thread.index()));
-        for (id, thread) in threads {
-            debug_assert_ne!(self.active_thread, id);
-            if thread.state.is_enabled() {
-                info!(
-                    "---------- Now executing on thread `{}` (previous: `{}`) ----------------------------------------",
-                    self.get_thread_display_name(id),
-                    self.get_thread_display_name(self.active_thread)
-                );
-                self.active_thread = id;
-                break;
+        // By default we pick the next one in round-robin order. Switching away from a thread that
+        // yielded but could keep running counts as a preemption when exploring schedules.
+        let mut candidates: Vec<ThreadId> = other_enabled_threads().collect();
+        if self.priority_scheduling {
+            // A thread that yields only makes way for threads of at least its own priority, so it
+            // stays a candidate, albeit the last one.
+            if self.threads[self.active_thread].state.is_enabled() {
+                candidates.push(self.active_thread);
+            }
+            self.retain_highest_priority(&mut candidates);
+        }
+        if let Some(explorer) = &self.explorer {
+            // Whatever the old thread synchronized on, it is not running any more.
+            explorer.take_sync_point();
+        }
+        if !candidates.is_empty() {
+            let preemptive = self.threads[self.active_thread].state.is_enabled();
+            let id = self.choose_thread(&candidates, preemptive)?;
+            if id != self.active_thread {
+                self.set_active_thread_id(id);
             }
         }
-        self.yield_active_thread = false;
+        self.yield_active_thread.set(false);
         if self.threads[self.active_thread].state.is_enabled() {
             return interp_ok(SchedulingAction::ExecuteStep);
         }
         // We have not found a thread to execute.
         if self.threads.iter().all(|thread| thread.state.is_terminated()) {
             unreachable!("all threads terminated without the main thread terminating?!");
-        } else if let Some(sleep_time) = potential_sleep_time {
+        } else if let Some((sleep_time, _)) = next_timeout {
             // All threads are currently blocked, but we have unexecuted
             // timeout_callbacks, which may unblock some of the threads. Hence,
             // sleep until the first callback.
@@ -812,43 +1276,26 @@ impl<'tcx> ThreadManager<'tcx> {
 
 impl<'tcx> EvalContextPrivExt<'tcx> for MiriInterpCx<'tcx> {}
 trait EvalContextPrivExt<'tcx>: MiriInterpCxExt<'tcx> {
-    /// Execute a timeout callback on the callback's thread.
+    /// Execute the timeout callback of the given thread, which the scheduler found to have timed
+    /// out. We do not check the clock again, since it might have been shifted in the meantime (see
+    /// <https://github.com/rust-lang/miri/issues/1763>).
     #[inline]
-    fn run_timeout_callback(&mut self) -> InterpResult<'tcx> {
+    fn run_timeout_callback(&mut self, thread: ThreadId) -> InterpResult<'tcx> {
         let this = self.eval_context_mut();
-        let mut found_callback = None;
-        // Find a blocked thread that has timed out.
-        for (id, thread) in this.machine.threads.threads.iter_enumerated_mut() {
-            match &thread.state {
-                ThreadState::Blocked { timeout: Some(timeout), .. }
-                    if timeout.get_wait_time(&this.machine.clock) == Duration::ZERO =>
-                {
-                    let old_state = mem::replace(&mut thread.state, ThreadState::Enabled);
-                    let ThreadState::Blocked { callback, .. } = old_state else { unreachable!() };
-                    found_callback = Some((id, callback));
-                    // Run the fallback (after the loop because borrow-checking).
-                    break;
-                }
-                _ => {}
-            }
-        }
-        if let Some((thread, callback)) = found_callback {
-            // This back-and-forth with `set_active_thread` is here because of two
-            // design decisions:
-            // 1. Make the caller and not the callback responsible for changing
-            //    thread.
-            // 2. Make the scheduler the only place that can change the active
-            //    thread.
-            let old_thread = this.machine.threads.set_active_thread_id(thread);
-            callback.timeout(this)?;
-            this.machine.threads.set_active_thread_id(old_thread);
-        }
-        // found_callback can remain None if the computer's clock
-        // was shifted after calling the scheduler and before the call
-        // to get_ready_callback (see issue
-        // https://github.com/rust-lang/miri/issues/1763). In this case,
-        // just do nothing, which effectively just returns to the
-        // scheduler.
+        let old_state =
+            mem::replace(&mut this.machine.threads.threads[thread].state, ThreadState::Enabled);
+        let ThreadState::Blocked { callback, timeout: Some(_), .. } = old_state else {
+            panic!("the timeout callback of {thread:?} was scheduled, but it has no timeout")
+        };
+        // This back-and-forth with `set_active_thread` is here because of two
+        // design decisions:
+        // 1. Make the caller and not the callback responsible for changing
+        //    thread.
+        // 2. Make the scheduler the only place that can change the active
+        //    thread.
+        let old_thread = this.machine.threads.set_active_thread_id(thread);
+        callback.timeout(this)?;
+        this.machine.threads.set_active_thread_id(old_thread);
         interp_ok(())
     }
 
@@ -932,6 +1379,13 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         if let Some(data_race) = &mut this.machine.data_race {
             data_race.thread_created(&this.machine.threads, new_thread_id, current_span);
         }
+        if this.machine.race_report_format != RaceReportFormat::Miri
+            || this.machine.report_thread_leaks
+        {
+            let stacktrace = this.generate_stacktrace();
+            let creator = this.active_thread();
+            this.machine.threads.threads[new_thread_id].creation = Some((creator, stacktrace));
+        }
 
         // Write the current thread-id, switch to the next thread later
         // to treat this write operation as occurring on the current thread.
@@ -1027,10 +1481,52 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         for thread in joining_threads {
             this.unblock_thread(thread, unblock_reason)?;
         }
+        // Threads waiting on Windows handles may have waited for this thread, or for a mutex that
+        // it abandoned.
+        let object_waiters = std::mem::take(&mut this.machine.blocked_object_wait_tid);
+        for thread in object_waiters {
+            this.unblock_thread(thread, BlockReason::WaitForObjects)?;
+        }
 
         interp_ok(())
     }
 
+    /// With `-Zmiri-detect-blocking-in-async`, warn when the active thread performs a blocking
+    /// operation while it polls a future, which stalls all other tasks of the executor.
+    fn check_blocking_in_async(&mut self, operation: &str) {
+        let this = self.eval_context_mut();
+        if !this.machine.detect_blocking_in_async {
+            return;
+        }
+        let tcx = *this.tcx;
+        let stack = this.active_thread_stack();
+        // Find the innermost frame that polls a future: the body of an `async` fn or block, or an
+        // implementation of `Future::poll`.
+        let Some(idx) = stack.iter().rposition(|frame| {
+            let def_id = frame.instance().def_id();
+            tcx.coroutine_is_async(def_id)
+                || tcx
+                    .trait_impl_of_assoc(def_id)
+                    .map(|impl_id| tcx.impl_trait_id(impl_id))
+                    .is_some_and(|trait_id| tcx.is_lang_item(trait_id, LangItem::Future))
+        }) else {
+            return;
+        };
+        let future = stack[idx].instance().to_string();
+        // The frame below the future is where it gets polled, e.g. an `.await` or an executor.
+        let poll_span = stack[idx.checked_sub(1).unwrap_or(idx)].current_span();
+        // Report every call site only once, as blocking operations tend to happen in loops.
+        let call_site = this.machine.current_span();
+        if !this.machine.reported_blocking_in_async.insert(call_site) {
+            return;
+        }
+        this.emit_diagnostic(NonHaltingDiagnostic::BlockingInAsync {
+            operation: operation.to_string(),
+            future,
+            poll_span: poll_span.data(),
+        });
+    }
+
     /// Block the current thread, with an optional timeout.
     /// The callback will be invoked when the thread gets unblocked.
     #[inline]
@@ -1041,6 +1537,9 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         callback: DynUnblockCallback<'tcx>,
     ) {
         let this = self.eval_context_mut();
+        if let Some(operation) = reason.blocking_operation() {
+            this.check_blocking_in_async(operation);
+        }
         let timeout = timeout.map(|(clock, anchor, duration)| {
             let anchor = match clock {
                 TimeoutClock::RealTime => {
@@ -1050,7 +1549,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     );
                     Timeout::RealTime(match anchor {
                         TimeoutAnchor::Absolute => SystemTime::UNIX_EPOCH,
-                        TimeoutAnchor::Relative => SystemTime::now(),
+                        TimeoutAnchor::Relative => this.machine.clock.system_now(),
                     })
                 }
                 TimeoutClock::Monotonic =>
@@ -1087,6 +1586,90 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(())
     }
 
+    /// Declare that the active thread, which must have just been blocked, waits at a cancellation
+    /// point. `interrupt_cancellation_point` can then end the wait early by running the timeout
+    /// callback, so the callback must be able to handle that even if the thread has no timeout.
+    fn block_at_cancellation_point(&mut self) {
+        let this = self.eval_context_mut();
+        let ThreadState::Blocked { cancellable, .. } = &mut this.active_thread_mut().state else {
+            panic!("block_at_cancellation_point: thread is not blocked");
+        };
+        *cancellable = true;
+    }
+
+    /// If the given thread waits at a cancellation point, end the wait by running its timeout
+    /// callback right away. Returns whether the thread was woken up.
+    fn interrupt_cancellation_point(&mut self, thread: ThreadId) -> InterpResult<'tcx, bool> {
+        let this = self.eval_context_mut();
+        let state = &mut this.machine.threads.threads[thread].state;
+        if !matches!(state, ThreadState::Blocked { cancellable: true, .. }) {
+            return interp_ok(false);
+        }
+        let ThreadState::Blocked { callback, .. } = mem::replace(state, ThreadState::Enabled)
+        else {
+            unreachable!()
+        };
+        // The callback must be executed in the previously blocked thread.
+        let old_thread = this.machine.threads.set_active_thread_id(thread);
+        callback.timeout(this)?;
+        this.machine.threads.set_active_thread_id(old_thread);
+        interp_ok(true)
+    }
+
+    /// Remove the innermost cleanup handler of the active thread, like `pthread_cleanup_pop`. If
+    /// `execute` is set, the handler is called and then returns to `ret` in the caller of the
+    /// shim; otherwise, we return there right away.
+    fn pop_cleanup_handler(
+        &mut self,
+        execute: bool,
+        ret: Option<mir::BasicBlock>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let Some((routine, arg)) = this.active_thread_mut().cancel.cleanup_handlers.pop() else {
+            throw_ub_format!("popped a cleanup handler, but none was registered");
+        };
+        if !execute {
+            return this.return_to_block(ret);
+        }
+        let instance = this.get_ptr_fn(routine)?.as_instance()?;
+        this.call_function(
+            instance,
+            ExternAbi::C { unwind: false },
+            &[ImmTy::from_scalar(arg, this.machine.layouts.mut_raw_ptr)],
+            None,
+            // Directly return to the caller of the shim.
+            StackPopCleanup::Goto { ret, unwind: mir::UnwindAction::Unreachable },
+        )
+    }
+
+    /// Make the given thread exit without executing any more of the code on its stack: all its
+    /// frames are removed, and the thread then terminates the regular way once `on_stack_empty`
+    /// is done. Like with musl's `pthread_exit`, the stack is not unwound, so no drop glue runs.
+    ///
+    /// The thread must be enabled, and it must not be in the middle of a shim. To make the active
+    /// thread exit from within a shim, block it and call this in the callback instead.
+    fn exit_thread_without_unwinding(&mut self, thread: ThreadId) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let old_thread = this.machine.threads.set_active_thread_id(thread);
+        assert!(this.active_thread_ref().state.is_enabled(), "only enabled threads can exit");
+        while let Some(frame) = this.active_thread_stack().last() {
+            // This is what `before_stack_pop` and popping the frame would do. There is no return
+            // value to copy.
+            if this.machine.borrow_tracker.is_some() {
+                this.on_stack_pop(frame)?;
+            }
+            let frame = this.active_thread_stack_mut().pop().unwrap();
+            for local in frame.locals.iter() {
+                if let Some(Either::Left((ptr, _meta))) = local.as_mplace_or_imm() {
+                    this.deallocate_ptr(ptr, None, MemoryKind::Stack)?;
+                }
+            }
+        }
+        this.active_thread_mut().recompute_top_user_relevant_frame();
+        this.machine.threads.set_active_thread_id(old_thread);
+        interp_ok(())
+    }
+
     #[inline]
     fn detach_thread(
         &mut self,
@@ -1177,6 +1760,16 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         self.eval_context_ref().machine.threads.get_thread_name(thread)
     }
 
+    #[inline]
+    fn set_thread_priority(&mut self, thread: ThreadId, priority: ThreadPriority) {
+        self.eval_context_mut().machine.threads.set_thread_priority(thread, priority);
+    }
+
+    #[inline]
+    fn get_thread_priority(&self, thread: ThreadId) -> ThreadPriority {
+        self.eval_context_ref().machine.threads.get_thread_priority(thread)
+    }
+
     #[inline]
     fn yield_active_thread(&mut self) {
         self.eval_context_mut().machine.threads.yield_active_thread();
@@ -1192,6 +1785,19 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
     }
 
+    /// The program told us that this is a good place for a preemption (`miri_yield_hint`).
+    fn preemption_hint(&mut self) {
+        use rand::Rng as _;
+
+        let this = self.eval_context_mut();
+        if this.machine.threads.is_exploring_schedules() {
+            // Let the schedule explorer decide whether to switch threads here.
+            this.machine.threads.note_sync_point();
+        } else if this.machine.rng.get_mut().gen_bool(YIELD_HINT_PREEMPTION_RATE) {
+            this.yield_active_thread();
+        }
+    }
+
     /// Run the core interpreter loop. Returns only when an interrupt occurs (an error or program
     /// termination).
     fn run_threads(&mut self) -> InterpResult<'tcx, !> {
@@ -1212,8 +1818,8 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                         }
                     }
                 }
-                SchedulingAction::ExecuteTimeoutCallback => {
-                    this.run_timeout_callback()?;
+                SchedulingAction::ExecuteTimeoutCallback(thread) => {
+                    this.run_timeout_callback(thread)?;
                 }
                 SchedulingAction::Sleep(duration) => {
                     this.machine.clock.sleep(duration);
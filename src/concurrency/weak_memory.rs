@@ -53,10 +53,16 @@
 //! A mixed atomicity read that races with writes, or a write that races with reads or writes will still cause UBs to be thrown.
 //! Mixed size atomic accesses must not race with any other atomic access, whether read or write, or a UB will be thrown.
 //! You can refer to test cases in weak_memory/extra_cpp.rs and weak_memory/extra_cpp_unsafe.rs for examples of these operations.
+//!
+//! With `-Zmiri-weak-memory-model`, loads are further restricted to the stores a hardware memory model
+//! allows them to read from: under x86-TSO, a store is visible to all threads once it left the store buffer
+//! of its thread, and under ARMv8, once a thread other than the writer observed it. No load can then read
+//! a store earlier in modification order, which is checked together with the C++ rules in `candidates`.
 
 // Our and the author's own implementation (tsan11) of the paper have some deviations from the provided operational semantics in §5.3:
-// 1. In the operational semantics, store elements keep a copy of the atomic object's vector clock (AtomicCellClocks::sync_vector in miri),
-// but this is not used anywhere so it's omitted here.
+// 1. In the operational semantics, store elements keep a copy of the atomic object's vector clock (AtomicCellClocks::sync_vector in miri).
+// We do the same (StoreElement::sync_clock), so that a load reading an outdated store synchronizes with the release operations
+// that store actually carries rather than with those of the latest store.
 //
 // 2. In the operational semantics, each store element keeps the timestamp of a thread when it loads from the store.
 // If the same thread loads from the same store element multiple times, then the timestamps at all loads are saved in a list of load elements.
@@ -138,10 +144,19 @@ struct StoreElement {
 
     /// The timestamp of the storing thread when it performed the store
     timestamp: VTimestamp,
+
+    /// Whether this is the value the location had before the first atomic store to it.
+    /// Such an element was not written by any thread.
+    is_initial: bool,
     /// The value of this store. `None` means uninitialized.
     // FIXME: Currently, we cannot represent partial initialization.
     val: Option<Scalar>,
 
+    /// The clock that loads reading from this store synchronize with: the sync clock
+    /// of the location in the data race detector right after this store. Reading from
+    /// an older store must not synchronize with the release operations of later ones.
+    sync_clock: VClock,
+
     /// Metadata about loads from this store element,
     /// behind a RefCell to keep load op take &self
     load_info: RefCell<LoadInfo>,
@@ -235,8 +250,10 @@ impl<'tcx> StoreBuffer {
             // are never meaningfully used, so it's fine to leave them as 0
             store_index: VectorIdx::from(0),
             timestamp: VTimestamp::ZERO,
+            is_initial: true,
             val: init,
             is_seqcst: false,
+            sync_clock: VClock::default(),
             load_info: RefCell::new(LoadInfo::default()),
         };
         buffer.push_back(store_elem);
@@ -253,7 +270,7 @@ impl<'tcx> StoreBuffer {
         let store_elem = self.buffer.back();
         if let Some(store_elem) = store_elem {
             let (index, clocks) = global.active_thread_state(thread_mgr);
-            store_elem.load_impl(index, &clocks, is_seqcst);
+            store_elem.load_impl(index, &clocks, is_seqcst, global);
         }
     }
 
@@ -263,7 +280,7 @@ impl<'tcx> StoreBuffer {
         thread_mgr: &ThreadManager<'_>,
         is_seqcst: bool,
         rng: &mut (impl rand::Rng + ?Sized),
-        validate: impl FnOnce() -> InterpResult<'tcx>,
+        validate: impl FnOnce(Option<&VClock>) -> InterpResult<'tcx>,
     ) -> InterpResult<'tcx, (Option<Scalar>, LoadRecency)> {
         // Having a live borrow to store_buffer while calling validate_atomic_load is fine
         // because the race detector doesn't touch store_buffer
@@ -273,17 +290,18 @@ impl<'tcx> StoreBuffer {
             // as the race detector will update it
             let (.., clocks) = global.active_thread_state(thread_mgr);
             // Load from a valid entry in the store buffer
-            self.fetch_store(is_seqcst, &clocks, &mut *rng)
+            self.fetch_store(is_seqcst, &clocks, global, &mut *rng, thread_mgr)?
         };
 
         // Unlike in buffered_atomic_write, thread clock updates have to be done
         // after we've picked a store element from the store buffer, as presented
         // in ATOMIC LOAD rule of the paper. This is because fetch_store
-        // requires access to ThreadClockSet.clock, which is updated by the race detector
-        validate()?;
+        // requires access to ThreadClockSet.clock, which is updated by the race detector.
+        // An outdated load synchronizes with the store it actually reads from.
+        validate((recency == LoadRecency::Outdated).then_some(&store_elem.sync_clock))?;
 
         let (index, clocks) = global.active_thread_state(thread_mgr);
-        let loaded = store_elem.load_impl(index, &clocks, is_seqcst);
+        let loaded = store_elem.load_impl(index, &clocks, is_seqcst, global);
         interp_ok((loaded, recency))
     }
 
@@ -293,29 +311,67 @@ impl<'tcx> StoreBuffer {
         global: &DataRaceState,
         thread_mgr: &ThreadManager<'_>,
         is_seqcst: bool,
+        sync_clock: VClock,
     ) -> InterpResult<'tcx> {
         let (index, clocks) = global.active_thread_state(thread_mgr);
 
-        self.store_impl(val, index, &clocks.clock, is_seqcst);
+        self.store_impl(val, index, &clocks.clock, is_seqcst, sync_clock);
         interp_ok(())
     }
 
-    /// Selects a valid store element in the buffer.
-    fn fetch_store<R: rand::Rng + ?Sized>(
-        &self,
+    /// Selects a valid store element in the buffer. The store is picked at random, unless a
+    /// schedule trace or the schedule explorer decides which one.
+    fn fetch_store<'a, R: rand::Rng + ?Sized>(
+        &'a self,
         is_seqcst: bool,
         clocks: &ThreadClockSet,
+        global: &DataRaceState,
         rng: &mut R,
-    ) -> (&StoreElement, LoadRecency) {
+        thread_mgr: &ThreadManager<'_>,
+    ) -> InterpResult<'tcx, (&'a StoreElement, LoadRecency)> {
         use rand::seq::IteratorRandom;
+        let candidates = self.candidates(is_seqcst, clocks, global);
+
+        // The candidates start with the latest store, which is the default choice.
+        let (store, chosen) = if thread_mgr.decides_loads() {
+            let candidates: Vec<_> = candidates.collect();
+            let Some(store) = thread_mgr.decide_load(candidates.len()) else {
+                throw_unsup_format!(
+                    "the schedule does not match this execution; schedules can only be replayed with the same program, inputs, and flags"
+                );
+            };
+            (store, candidates[store])
+        } else {
+            candidates.enumerate().choose(rng).expect("store buffer cannot be empty")
+        };
+        thread_mgr.note_load(store);
+        interp_ok(
+            if std::ptr::eq(chosen, self.buffer.back().expect("store buffer cannot be empty")) {
+                (chosen, LoadRecency::Latest)
+            } else {
+                (chosen, LoadRecency::Outdated)
+            },
+        )
+    }
+
+    /// The store elements that a load with the given clocks may read from,
+    /// starting with the latest one.
+    fn candidates<'a, 'c>(
+        &'a self,
+        is_seqcst: bool,
+        clocks: &'c ThreadClockSet,
+        global: &'c DataRaceState,
+    ) -> impl Iterator<Item = &'a StoreElement> + 'c
+    where
+        'a: 'c,
+    {
         let mut found_sc = false;
         // FIXME: we want an inclusive take_while (stops after a false predicate, but
         // includes the element that gave the false), but such function doesn't yet
         // exist in the standard library https://github.com/rust-lang/rust/issues/62208
         // so we have to hack around it with keep_searching
         let mut keep_searching = true;
-        let candidates = self
-            .buffer
+        self.buffer
             .iter()
             .rev()
             .take_while(move |&store_elem| {
@@ -360,13 +416,17 @@ impl<'tcx> StoreBuffer {
                     // Consequences of C++20 §31.4 [atomics.order] paragraph 3.1, 3.3 (coherence-ordered before)
                     // and 4.1 (coherence-ordered before between SC makes global total order S)
                     false
+                } else if store_elem.visible_to_all(global) {
+                    // The hardware memory model we restrict ourselves to makes this store visible
+                    // to all threads at once, so no thread can read-before it any more.
+                    false
                 } else {
                     true
                 };
 
                 true
             })
-            .filter(|&store_elem| {
+            .filter(move |&store_elem| {
                 if is_seqcst && store_elem.is_seqcst {
                     // An SC load needs to ignore all but last store maked SC (stores not marked SC are not
                     // affected)
@@ -376,14 +436,47 @@ impl<'tcx> StoreBuffer {
                 } else {
                     true
                 }
-            });
+            })
+    }
 
-        let chosen = candidates.choose(rng).expect("store buffer cannot be empty");
-        if std::ptr::eq(chosen, self.buffer.back().expect("store buffer cannot be empty")) {
-            (chosen, LoadRecency::Latest)
-        } else {
-            (chosen, LoadRecency::Outdated)
+    /// Describes the stores in this buffer for diagnostics, from the oldest to the latest one in
+    /// modification order, along with the live threads that could still read each of them.
+    fn describe(&self, global: &DataRaceState, thread_mgr: &ThreadManager<'_>) -> Vec<String> {
+        let mut readers = vec![Vec::new(); self.buffer.len()];
+        for thread in thread_mgr.live_threads() {
+            let (_, clocks) = global.thread_state(thread);
+            for store_elem in self.candidates(/* is_seqcst */ false, &clocks, global) {
+                let pos = self
+                    .buffer
+                    .iter()
+                    .position(|elem| std::ptr::eq(elem, store_elem))
+                    .expect("candidates must be in the store buffer");
+                readers[pos]
+                    .push(format!("thread `{}`", thread_mgr.get_thread_display_name(thread)));
+            }
         }
+        self.buffer
+            .iter()
+            .zip(readers)
+            .map(|(store_elem, readers)| {
+                let val = match store_elem.val {
+                    Some(val) => format!("{val}"),
+                    None => "uninitialized".to_string(),
+                };
+                let origin = if store_elem.is_initial {
+                    "initial value".to_string()
+                } else {
+                    let writer = global.print_thread_metadata(thread_mgr, store_elem.store_index);
+                    format!("stored by {writer}")
+                };
+                let readers = if readers.is_empty() {
+                    "cannot be read by any live thread".to_string()
+                } else {
+                    format!("can be read by {}", readers.join(", "))
+                };
+                format!("{val} ({origin}), {readers}")
+            })
+            .collect()
     }
 
     /// ATOMIC STORE IMPL in the paper (except we don't need the location's vector clock)
@@ -393,16 +486,19 @@ impl<'tcx> StoreBuffer {
         index: VectorIdx,
         thread_clock: &VClock,
         is_seqcst: bool,
+        sync_clock: VClock,
     ) {
         let store_elem = StoreElement {
             store_index: index,
             timestamp: thread_clock[index],
+            is_initial: false,
             // In the language provided in the paper, an atomic store takes the value from a
             // non-atomic memory location.
             // But we already have the immediate value here so we don't need to do the memory
             // access.
             val: Some(val),
             is_seqcst,
+            sync_clock,
             load_info: RefCell::new(LoadInfo::default()),
         };
         if self.buffer.len() >= STORE_BUFFER_LIMIT {
@@ -423,6 +519,18 @@ impl<'tcx> StoreBuffer {
 }
 
 impl StoreElement {
+    /// Whether this store is visible to all threads under `-Zmiri-weak-memory-model`.
+    fn visible_to_all(&self, global: &DataRaceState) -> bool {
+        match global.weak_memory_model {
+            WeakMemoryModel::Cpp => false,
+            WeakMemoryModel::X86Tso => global.store_flushed(self.store_index, self.timestamp),
+            // ARMv8 is other-multi-copy-atomic: a store that a thread other than the writer
+            // observed has reached the shared memory.
+            WeakMemoryModel::Arm =>
+                self.load_info.borrow().timestamps.keys().any(|&index| index != self.store_index),
+        }
+    }
+
     /// ATOMIC LOAD IMPL in the paper
     /// Unlike the operational semantics in the paper, we don't need to keep track
     /// of the thread timestamp for every single load. Keeping track of the first (smallest)
@@ -436,16 +544,21 @@ impl StoreElement {
         index: VectorIdx,
         clocks: &ThreadClockSet,
         is_seqcst: bool,
+        global: &DataRaceState,
     ) -> Option<Scalar> {
         let mut load_info = self.load_info.borrow_mut();
         load_info.sc_loaded |= is_seqcst;
         let _ = load_info.timestamps.try_insert(index, clocks.clock[index]);
+        if index != self.store_index && !self.is_initial {
+            // On x86, a store that another thread observed has left the store buffer.
+            global.store_observed(self.store_index, self.timestamp);
+        }
         self.val
     }
 }
 
 impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
-pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     fn buffered_atomic_rmw(
         &mut self,
         new_val: Scalar,
@@ -456,7 +569,11 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let this = self.eval_context_mut();
         let (alloc_id, base_offset, ..) = this.ptr_get_alloc_id(place.ptr(), 0)?;
         if let (
-            crate::AllocExtra { weak_memory: Some(alloc_buffers), .. },
+            crate::AllocExtra {
+                weak_memory: Some(alloc_buffers),
+                data_race: Some(alloc_clocks),
+                ..
+            },
             crate::MiriMachine { data_race: Some(global), threads, .. },
         ) = this.get_alloc_extra_mut(alloc_id)?
         {
@@ -467,7 +584,15 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             let range = alloc_range(base_offset, place.layout.size);
             let buffer = alloc_buffers.get_or_create_store_buffer_mut(range, Some(init))?;
             buffer.read_from_last_store(global, threads, atomic == AtomicRwOrd::SeqCst);
-            buffer.buffered_write(new_val, global, threads, atomic == AtomicRwOrd::SeqCst)?;
+            buffer.buffered_write(
+                new_val,
+                global,
+                threads,
+                atomic == AtomicRwOrd::SeqCst,
+                alloc_clocks.sync_clock(range),
+            )?;
+            // On x86, every read-modify-write is a locked instruction.
+            global.flush_stores(threads);
         }
         interp_ok(())
     }
@@ -477,7 +602,7 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         place: &MPlaceTy<'tcx>,
         atomic: AtomicReadOrd,
         latest_in_mo: Scalar,
-        validate: impl FnOnce() -> InterpResult<'tcx>,
+        validate: impl FnOnce(Option<&VClock>) -> InterpResult<'tcx>,
     ) -> InterpResult<'tcx, Option<Scalar>> {
         let this = self.eval_context_ref();
         'fallback: {
@@ -504,6 +629,7 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     if global.track_outdated_loads && recency == LoadRecency::Outdated {
                         this.emit_diagnostic(NonHaltingDiagnostic::WeakMemoryOutdatedLoad {
                             ptr: place.ptr(),
+                            store_buffer: buffer.describe(global, &this.machine.threads),
                         });
                     }
 
@@ -513,7 +639,7 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
 
         // Race detector or weak memory disabled, simply read the latest value
-        validate()?;
+        validate(None)?;
         interp_ok(Some(latest_in_mo))
     }
 
@@ -531,7 +657,11 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let this = self.eval_context_mut();
         let (alloc_id, base_offset, ..) = this.ptr_get_alloc_id(dest.ptr(), 0)?;
         if let (
-            crate::AllocExtra { weak_memory: Some(alloc_buffers), .. },
+            crate::AllocExtra {
+                weak_memory: Some(alloc_buffers),
+                data_race: Some(alloc_clocks),
+                ..
+            },
             crate::MiriMachine { data_race: Some(global), threads, .. },
         ) = this.get_alloc_extra_mut(alloc_id)?
         {
@@ -539,9 +669,19 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 global.sc_write(threads);
             }
 
-            let buffer = alloc_buffers
-                .get_or_create_store_buffer_mut(alloc_range(base_offset, dest.layout.size), init)?;
-            buffer.buffered_write(val, global, threads, atomic == AtomicWriteOrd::SeqCst)?;
+            let range = alloc_range(base_offset, dest.layout.size);
+            let buffer = alloc_buffers.get_or_create_store_buffer_mut(range, init)?;
+            buffer.buffered_write(
+                val,
+                global,
+                threads,
+                atomic == AtomicWriteOrd::SeqCst,
+                alloc_clocks.sync_clock(range),
+            )?;
+            if atomic == AtomicWriteOrd::SeqCst {
+                // On x86, an SC store is an `xchg`, which is a locked instruction.
+                global.flush_stores(threads);
+            }
         }
 
         // Caller should've written to dest with the vanilla scalar write, we do nothing here
@@ -580,4 +720,31 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
         interp_ok(())
     }
+
+    /// Print the store buffers of all atomic locations in the allocation of `ptr`.
+    fn print_store_buffers(&self, ptr: Pointer) -> InterpResult<'tcx> {
+        let this = self.eval_context_ref();
+        let (alloc_id, _, _) = this.ptr_get_alloc_id(ptr, 0)?;
+        let (Some(global), Some(alloc_buffers)) =
+            (&this.machine.data_race, this.get_alloc_extra(alloc_id)?.weak_memory.as_ref())
+        else {
+            eprintln!("weak memory emulation is disabled");
+            return interp_ok(());
+        };
+        let store_buffers = alloc_buffers.store_buffers.borrow();
+        if store_buffers.iter().next().is_none() {
+            eprintln!("{alloc_id:?} has no store buffers");
+        }
+        for (range, buffer) in store_buffers.iter_with_range() {
+            eprintln!(
+                "store buffer of {alloc_id:?}[{:#x}..{:#x}]:",
+                range.start.bytes(),
+                range.end().bytes()
+            );
+            for store in buffer.describe(global, &this.machine.threads) {
+                eprintln!("  {store}");
+            }
+        }
+        interp_ok(())
+    }
 }
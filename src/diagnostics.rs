@@ -2,9 +2,12 @@ use std::fmt::{self, Write};
 use std::num::NonZero;
 
 use rustc_abi::{Align, Size};
-use rustc_errors::{Diag, DiagMessage, Level};
-use rustc_span::{DUMMY_SP, SpanData, Symbol};
+use rustc_data_structures::fx::FxHashSet;
+use rustc_errors::{Diag, DiagMessage, Level, pluralize};
+use rustc_span::def_id::CrateNum;
+use rustc_span::{DUMMY_SP, Span, SpanData, Symbol};
 
+use crate::borrow_tracker::protector_call_path;
 use crate::borrow_tracker::stacked_borrows::diagnostics::TagHistory;
 use crate::borrow_tracker::tree_borrows::diagnostics as tree_diagnostics;
 use crate::*;
@@ -21,13 +24,28 @@ pub enum TerminationInfo {
         msg: String,
         help: Vec<String>,
         history: Option<TagHistory>,
+        /// The allocation the error happened in.
+        alloc_id: AllocId,
+        /// On protector violations, the tag whose protector was violated.
+        protected_tag: Option<BorTag>,
     },
     TreeBorrowsUb {
         title: String,
         details: Vec<String>,
         history: tree_diagnostics::HistoryData,
+        /// Where the error happened and which tags were involved, to render the tree.
+        alloc_id: AllocId,
+        error_offset: u64,
+        accessed: BorTag,
+        conflicting: BorTag,
+        /// On protector violations, the tag whose protector was violated.
+        protected_tag: Option<BorTag>,
     },
     Int2PtrWithStrictProvenance,
+    /// A transmute from a pointer with provenance to an integer with `-Zmiri-strict-transmutes`.
+    PtrIntTransmute {
+        union_field: bool,
+    },
     Deadlock,
     MultipleSymbolDefinitions {
         link_name: Symbol,
@@ -47,6 +65,13 @@ pub enum TerminationInfo {
         op2: RacingOp,
         extra: Option<&'static str>,
         retag_explain: bool,
+        /// Whether (1) is the only write to a location that (2) reads, and the thread of (1) read
+        /// the location first, like in a lazy initialization without synchronization.
+        lazy_init: bool,
+        /// Why nothing orders (1) before (2), for `-Zmiri-race-timeline`.
+        timeline: Vec<(Option<SpanData>, String)>,
+        /// The steps of the execution from (1) to (2), for `-Zmiri-race-witness`.
+        witness: Vec<(Option<SpanData>, String)>,
     },
     UnsupportedForeignItem(String),
 }
@@ -55,6 +80,11 @@ pub struct RacingOp {
     pub action: String,
     pub thread_info: String,
     pub span: SpanData,
+    /// The kind of access as named in ThreadSanitizer reports, e.g. "atomic write".
+    pub kind: &'static str,
+    /// The size of the access, if known.
+    pub size: Option<Size>,
+    pub thread: ThreadId,
 }
 
 impl fmt::Display for TerminationInfo {
@@ -69,6 +99,14 @@ impl fmt::Display for TerminationInfo {
                     f,
                     "integer-to-pointer casts and `ptr::with_exposed_provenance` are not supported with `-Zmiri-strict-provenance`"
                 ),
+            PtrIntTransmute { union_field } => {
+                let what = if *union_field {
+                    "reading a pointer as an integer through a union field"
+                } else {
+                    "transmuting a pointer to an integer"
+                };
+                write!(f, "{what} is not supported with `-Zmiri-strict-transmutes`")
+            }
             StackedBorrowsUb { msg, .. } => write!(f, "{msg}"),
             TreeBorrowsUb { title, .. } => write!(f, "{title}"),
             Deadlock => write!(f, "the evaluated program deadlocked"),
@@ -128,8 +166,48 @@ pub enum NonHaltingDiagnostic {
     },
     WeakMemoryOutdatedLoad {
         ptr: Pointer,
+        /// The stores in the store buffer of the location, from oldest to latest.
+        store_buffer: Vec<String>,
     },
     ExternTypeReborrow,
+    BlockingInAsync {
+        /// What the program did that blocked the thread, e.g. "sleeping".
+        operation: String,
+        /// The innermost future that was being polled.
+        future: String,
+        /// Where that future was polled.
+        poll_span: SpanData,
+    },
+    ProcessSharedSyncObject {
+        name: &'static str,
+        details: bool,
+    },
+    ProcessSharedSyncObjectFreed {
+        name: &'static str,
+    },
+    DowngradedAliasingError {
+        /// The message of the aliasing violation.
+        msg: String,
+        /// The crate the violation was attributed to.
+        krate: String,
+    },
+    ConsumeOrdering {
+        /// The function that was called with `memory_order_consume`.
+        link_name: Symbol,
+        details: bool,
+    },
+    MixedAccessRace {
+        /// The message of the race, naming both accesses.
+        msg: String,
+        /// Where the earlier access happened.
+        earlier: SpanData,
+        /// Why mixed-size accesses are not allowed, if the accesses have different sizes.
+        extra: Option<&'static str>,
+    },
+    RelaxedSpinLoop {
+        /// Where the loop loaded the value it waited for.
+        spin_loop: SpanData,
+    },
 }
 
 /// Level of Miri specific diagnostics
@@ -148,6 +226,47 @@ macro_rules! note_span {
     ($span:expr, $($tt:tt)*) => { (Some($span), format!($($tt)*)) };
 }
 
+/// Name the crates involved in an aliasing violation if any of them is not a local crate, so that
+/// users with big dependency trees know where to report the bug.
+/// `invalidation` describes what `invalidated_by` did.
+fn aliasing_attribution(
+    machine: &MiriMachine<'_>,
+    created_by: Option<CrateNum>,
+    invalidated_by: Option<CrateNum>,
+    invalidation: &str,
+) -> Vec<(Option<SpanData>, String)> {
+    let accessed_by = machine.responsible_crate();
+    let mut dependencies = Vec::new();
+    // The invalidation comes first since it is the most common culprit.
+    for krate in [invalidated_by, created_by, accessed_by].into_iter().flatten() {
+        if !machine.is_local_crate(krate) && !dependencies.contains(&krate) {
+            dependencies.push(krate);
+        }
+    }
+    if dependencies.is_empty() {
+        return Vec::new();
+    }
+    let name = |krate| machine.tcx.crate_name(krate);
+    let mut helps = Vec::new();
+    if let Some(krate) = created_by {
+        helps
+            .push(note!("the pointer used for this access was created by crate `{}`", name(krate)));
+    }
+    if let Some(krate) = invalidated_by {
+        helps.push(note!("{invalidation} by crate `{}`", name(krate)));
+    }
+    if let Some(krate) = accessed_by {
+        helps.push(note!("this access was performed by crate `{}`", name(krate)));
+    }
+    let culprits = dependencies
+        .iter()
+        .map(|&krate| format!("`{}`", name(krate)))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    helps.push(note!("this is likely caused by crate {culprits}, consider reporting it there"));
+    helps
+}
+
 /// Attempts to prune a stacktrace to omit the Rust runtime, and returns a bool indicating if any
 /// frames were pruned. If the stacktrace does not have any local frames, we conclude that it must
 /// be pointing to a problem in the Rust runtime itself, and do not prune it at all.
@@ -219,14 +338,16 @@ pub fn report_error<'tcx>(
 
     let mut msg = vec![];
 
-    let (title, helps) = if let MachineStop(info) = e.kind() {
+    let (title, mut helps) = if let MachineStop(info) = e.kind() {
         let info = info.downcast_ref::<TerminationInfo>().expect("invalid MachineStop payload");
         use TerminationInfo::*;
         let title = match info {
             &Exit { code, leak_check } => return Some((code, leak_check)),
             Abort(_) => Some("abnormal termination"),
-            UnsupportedInIsolation(_) | Int2PtrWithStrictProvenance | UnsupportedForeignItem(_) =>
-                Some("unsupported operation"),
+            UnsupportedInIsolation(_)
+            | Int2PtrWithStrictProvenance
+            | PtrIntTransmute { .. }
+            | UnsupportedForeignItem(_) => Some("unsupported operation"),
             StackedBorrowsUb { .. } | TreeBorrowsUb { .. } | DataRace { .. } =>
                 Some("Undefined Behavior"),
             Deadlock => Some("deadlock"),
@@ -245,13 +366,13 @@ pub fn report_error<'tcx>(
                     note!("however, note that Miri does not aim to support every FFI function out there; for instance, we will not support APIs for things such as GUIs, scripting languages, or databases"),
                 ]
             }
-            StackedBorrowsUb { help, history, .. } => {
+            StackedBorrowsUb { help, history, protected_tag, .. } => {
                 msg.extend(help.clone());
                 let mut helps = vec![
                     note!("this indicates a potential bug in the program: it performed an invalid operation, but the Stacked Borrows rules it violated are still experimental"),
                     note!("see https://github.com/rust-lang/unsafe-code-guidelines/blob/master/wip/stacked-borrows.md for further information"),
                 ];
-                if let Some(TagHistory {created, invalidated, protected}) = history.clone() {
+                if let Some(TagHistory {created, invalidated, protected, created_by, invalidated_by}) = history.clone() {
                     helps.push((Some(created.1), created.0));
                     if let Some((msg, span)) = invalidated {
                         helps.push(note_span!(span, "{msg}"));
@@ -259,10 +380,16 @@ pub fn report_error<'tcx>(
                     if let Some((protector_msg, protector_span)) = protected {
                         helps.push(note_span!(protector_span, "{protector_msg}"));
                     }
+                    helps.extend(aliasing_attribution(&ecx.machine, created_by, invalidated_by, "the pointer was invalidated"));
+                }
+                if let Some(path) = protected_tag
+                    .and_then(|tag| protector_call_path(&ecx.machine, tag))
+                {
+                    helps.push(note!("{path}"));
                 }
                 helps
             },
-            TreeBorrowsUb { title: _, details, history } => {
+            TreeBorrowsUb { title: _, details, history, protected_tag, .. } => {
                 let mut helps = vec![
                     note!("this indicates a potential bug in the program: it performed an invalid operation, but the Tree Borrows rules it violated are still experimental")
                 ];
@@ -272,6 +399,12 @@ pub fn report_error<'tcx>(
                 for event in history.events.clone() {
                     helps.push(event);
                 }
+                helps.extend(aliasing_attribution(&ecx.machine, history.created_by, history.changed_by, "the conflicting tag was last changed"));
+                if let Some(path) = protected_tag
+                    .and_then(|tag| protector_call_path(&ecx.machine, tag))
+                {
+                    helps.push(note!("{path}"));
+                }
                 helps
             }
             MultipleSymbolDefinitions { first, first_crate, second, second_crate, .. } =>
@@ -283,8 +416,15 @@ pub fn report_error<'tcx>(
                 vec![note_span!(*span, "the `{link_name}` symbol is defined here")],
             Int2PtrWithStrictProvenance =>
                 vec![note!("use Strict Provenance APIs (https://doc.rust-lang.org/nightly/std/ptr/index.html#strict-provenance, https://crates.io/crates/sptr) instead")],
-            DataRace { op1, extra, retag_explain, .. } => {
+            PtrIntTransmute { .. } =>
+                vec![
+                    note!("the integer does not carry the provenance of the pointer, so a pointer that is later created from it cannot be used to access memory"),
+                    note!("use `ptr.addr()` if only the address is needed, or `ptr.expose_provenance()` if the integer is turned back into a pointer later"),
+                ],
+            DataRace { op1, extra, retag_explain, lazy_init, timeline, witness, .. } => {
                 let mut helps = vec![note_span!(op1.span, "and (1) occurred earlier here")];
+                helps.extend(timeline.iter().cloned());
+                helps.extend(witness.iter().cloned());
                 if let Some(extra) = extra {
                     helps.push(note!("{extra}"));
                     helps.push(note!("see https://doc.rust-lang.org/nightly/std/sync/atomic/index.html#memory-model-for-atomic-accesses for more information about the Rust memory model"));
@@ -294,6 +434,10 @@ pub fn report_error<'tcx>(
                     helps.push(note!("retags permit optimizations that insert speculative reads or writes"));
                     helps.push(note!("therefore from the perspective of data races, a retag has the same implications as a read or write"));
                 }
+                if *lazy_init {
+                    helps.push(note!("(1) is the only write to this location, and its thread read the location before writing it: this looks like a value that is lazily initialized without synchronization"));
+                    helps.push(note!("to initialize a value once and share it between threads, use `std::sync::OnceLock`, `std::sync::LazyLock`, or `std::sync::Once`; or publish it with a `Release` store to an atomic and read that atomic with `Acquire` loads"));
+                }
                 helps.push(note!("this indicates a bug in the program: it performed an invalid operation, and caused Undefined Behavior"));
                 helps.push(note!("see https://doc.rust-lang.org/nightly/reference/behavior-considered-undefined.html for further information"));
                 helps
@@ -387,6 +531,7 @@ pub fn report_error<'tcx>(
     let (stacktrace, mut any_pruned) = prune_stacktrace(stacktrace, &ecx.machine);
 
     let mut show_all_threads = false;
+    let mut notes = vec![];
 
     // We want to dump the allocation if this is `InvalidUninitBytes`.
     // Since `format_interp_error` consumes `e`, we compute the outut early.
@@ -406,6 +551,45 @@ pub fn report_error<'tcx>(
             match info {
                 TerminationInfo::Deadlock => {
                     show_all_threads = true;
+                    if let Some(cycle) = ecx.deadlock_cycle() {
+                        notes = deadlock_cycle_notes(ecx, &cycle);
+                    }
+                    let lost_wakeups = ecx.lost_wakeups();
+                    if !lost_wakeups.is_empty() {
+                        notes.extend(lost_wakeup_notes(ecx, &lost_wakeups));
+                        helps.push(note!("a wakeup is lost if it happens before the thread it is meant for starts waiting; check the condition in a loop before waiting, while holding the lock that protects it"));
+                    }
+                }
+                TerminationInfo::DataRace { ptr, op1, op2, .. }
+                    if ecx.machine.race_report_format != RaceReportFormat::Miri =>
+                {
+                    extra = tsan_race_report(ecx, *ptr, op1, op2);
+                }
+                &TerminationInfo::TreeBorrowsUb {
+                    alloc_id,
+                    error_offset,
+                    accessed,
+                    conflicting,
+                    ..
+                } => {
+                    if ecx.machine.tree_borrows_print_tree
+                        && let Some(lines) =
+                            ecx.tb_error_subtree(alloc_id, error_offset, accessed, conflicting)
+                    {
+                        for line in lines {
+                            writeln!(extra, "{line}").unwrap();
+                        }
+                    }
+                    if let Some(path) = &ecx.machine.tree_borrows_dot_on_error
+                        && let Some(dot) =
+                            ecx.tree_dot(alloc_id, Some((accessed, conflicting))).discard_err()
+                        && let Err(err) = std::fs::write(path, dot)
+                    {
+                        ecx.tcx.dcx().warn(format!(
+                            "failed to write the borrow tree to `{}`: {err}",
+                            path.display()
+                        ));
+                    }
                 }
                 _ => {}
             }
@@ -419,7 +603,7 @@ pub fn report_error<'tcx>(
         DiagLevel::Error,
         if let Some(title) = title { format!("{title}: {}", msg[0]) } else { msg[0].clone() },
         msg,
-        vec![],
+        notes,
         helps,
         &stacktrace,
         Some(ecx.active_thread()),
@@ -468,6 +652,332 @@ pub fn report_error<'tcx>(
     None
 }
 
+/// Describe a cycle of threads waiting for each other, one note per thread.
+fn deadlock_cycle_notes<'tcx>(
+    ecx: &InterpCx<'tcx, MiriMachine<'tcx>>,
+    cycle: &[WaitFor],
+) -> Vec<(Option<SpanData>, String)> {
+    let name = |thread| ecx.machine.threads.get_thread_display_name(thread);
+    let location = |span| ecx.tcx.sess.source_map().span_to_embeddable_string(span);
+    let mut notes = vec![];
+    if cycle.len() > 1 {
+        notes.push(note!("the threads are waiting for each other in a cycle:"));
+    }
+    for edge in cycle {
+        let waiter = name(edge.waiter);
+        notes.push(match edge.lock {
+            None => note!("thread `{waiter}` is waiting for thread `{}` to terminate", name(edge.holder)),
+            Some((lock, span)) if edge.holder == edge.waiter =>
+                note!(
+                    "thread `{waiter}` is waiting for {lock} that it already holds, which it acquired at {}",
+                    location(span)
+                ),
+            Some((lock, span)) =>
+                note!(
+                    "thread `{waiter}` is waiting for {lock} held by thread `{}`, which acquired it at {}",
+                    name(edge.holder),
+                    location(span)
+                ),
+        });
+    }
+    notes
+}
+
+/// Describe threads whose wakeup was lost, one note per thread.
+fn lost_wakeup_notes<'tcx>(
+    ecx: &InterpCx<'tcx, MiriMachine<'tcx>>,
+    lost_wakeups: &[LostWakeup],
+) -> Vec<(Option<SpanData>, String)> {
+    let name = |thread| ecx.machine.threads.get_thread_display_name(thread);
+    lost_wakeups
+        .iter()
+        .map(|lost| {
+            note!(
+                "thread `{}` is waiting on {} that thread `{}` woke up at {}, while no thread was waiting on it",
+                name(lost.waiter),
+                lost.primitive,
+                name(lost.waker),
+                ecx.tcx.sess.source_map().span_to_embeddable_string(lost.span)
+            )
+        })
+        .collect()
+}
+
+/// A stack frame in a ThreadSanitizer report. The function is not known for the earlier access
+/// of a race, as we only record its location.
+struct TsanFrame {
+    function: Option<String>,
+    location: String,
+}
+
+/// An access involved in a race, described as in a ThreadSanitizer report.
+struct TsanAccess {
+    kind: &'static str,
+    size: Option<u64>,
+    thread: String,
+    stack: Vec<TsanFrame>,
+}
+
+/// A thread involved in a race, described as in a ThreadSanitizer report.
+struct TsanThread {
+    id: String,
+    name: String,
+    running: bool,
+    creator: String,
+    stack: Vec<TsanFrame>,
+}
+
+/// Describe a data race in the format of a ThreadSanitizer report, or as a single line of JSON
+/// with the same contents for `-Zmiri-race-report-format=json`.
+fn tsan_race_report<'tcx>(
+    ecx: &InterpCx<'tcx, MiriMachine<'tcx>>,
+    ptr: interpret::Pointer<AllocId>,
+    op1: &RacingOp,
+    op2: &RacingOp,
+) -> String {
+    let threads = &ecx.machine.threads;
+    let thread_id = |thread: ThreadId| {
+        if thread == ThreadId::MAIN_THREAD {
+            "main thread".to_string()
+        } else {
+            format!("T{}", thread.to_u32())
+        }
+    };
+    let location = |span: Span| {
+        let lo = ecx.tcx.sess.source_map().lookup_char_pos(span.lo());
+        let filename = lo.file.name.prefer_remapped_unconditionaly();
+        format!("{filename}:{}:{}", lo.line, lo.col.0.strict_add(1))
+    };
+    let frames = |stacktrace: &[FrameInfo<'tcx>]| {
+        let (stacktrace, _was_pruned) = prune_stacktrace(stacktrace.to_vec(), &ecx.machine);
+        stacktrace
+            .iter()
+            .map(|frame| {
+                TsanFrame {
+                    function: Some(frame.instance.to_string()),
+                    location: location(frame.span),
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let access = TsanAccess {
+        kind: op2.kind,
+        size: op2.size.map(Size::bytes),
+        thread: thread_id(op2.thread),
+        stack: frames(&ecx.generate_stacktrace()),
+    };
+    let previous = TsanAccess {
+        kind: op1.kind,
+        size: op1.size.map(Size::bytes),
+        thread: thread_id(op1.thread),
+        stack: vec![TsanFrame { function: None, location: location(op1.span.span()) }],
+    };
+
+    let (alloc_id, _offset) = ptr.into_parts();
+    let alloc_size = ecx.get_alloc_info(alloc_id).size.bytes();
+    let (alloc_description, alloc_stack) = match ecx.tcx.try_get_global_alloc(alloc_id) {
+        Some(GlobalAlloc::Static(def_id)) =>
+            (format!("global `{}`", ecx.tcx.def_path_str(def_id)), vec![]),
+        _ =>
+            match ecx.get_alloc_extra(alloc_id).discard_err().and_then(|e| e.backtrace.as_ref()) {
+                Some(backtrace) => ("heap block".to_string(), frames(backtrace)),
+                None => ("memory".to_string(), vec![]),
+            },
+    };
+
+    let mut involved = vec![op2.thread, op1.thread];
+    involved.dedup();
+    let involved_threads = involved
+        .into_iter()
+        .filter_map(|thread| {
+            let (creator, stack) = threads.creation(thread)?;
+            Some(TsanThread {
+                id: thread_id(thread),
+                name: threads.get_thread_display_name(thread),
+                running: !threads.has_terminated(thread),
+                creator: thread_id(creator),
+                stack: frames(stack),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let summary = match access.stack.first() {
+        Some(TsanFrame { function: Some(function), location }) =>
+            format!("data race {location} in {function}"),
+        _ => "data race".to_string(),
+    };
+
+    let mut out = String::new();
+    if ecx.machine.race_report_format == RaceReportFormat::Json {
+        let frames_json = |stack: &[TsanFrame]| {
+            let frames = stack
+                .iter()
+                .map(|frame| {
+                    format!(
+                        "{{\"function\":{},\"location\":{}}}",
+                        frame.function.as_deref().map_or("null".to_string(), json_string),
+                        json_string(&frame.location)
+                    )
+                })
+                .collect::<Vec<_>>();
+            format!("[{}]", frames.join(","))
+        };
+        let access_json = |access: &TsanAccess| {
+            format!(
+                "{{\"kind\":{},\"size\":{},\"thread\":{},\"stack\":{}}}",
+                json_string(access.kind),
+                access.size.map_or("null".to_string(), |size| size.to_string()),
+                json_string(&access.thread),
+                frames_json(&access.stack)
+            )
+        };
+        let threads_json = involved_threads
+            .iter()
+            .map(|thread| {
+                format!(
+                    "{{\"id\":{},\"name\":{},\"running\":{},\"created_by\":{},\"stack\":{}}}",
+                    json_string(&thread.id),
+                    json_string(&thread.name),
+                    thread.running,
+                    json_string(&thread.creator),
+                    frames_json(&thread.stack)
+                )
+            })
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "{{\"type\":\"data race\",\"address\":{},\"access\":{},\"previous\":{},\"location\":{{\"description\":{},\"allocation\":{},\"size\":{alloc_size},\"stack\":{}}},\"threads\":[{}],\"summary\":{}}}",
+            json_string(&format!("{ptr:?}")),
+            access_json(&access),
+            access_json(&previous),
+            json_string(&alloc_description),
+            json_string(&format!("{alloc_id:?}")),
+            frames_json(&alloc_stack),
+            threads_json.join(","),
+            json_string(&summary),
+        )
+        .unwrap();
+        return out;
+    }
+
+    let write_frames = |out: &mut String, stack: &[TsanFrame]| {
+        for (idx, frame) in stack.iter().enumerate() {
+            match &frame.function {
+                Some(function) => writeln!(out, "    #{idx} {function} {}", frame.location),
+                None => writeln!(out, "    #{idx} {}", frame.location),
+            }
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+    };
+    let write_access = |out: &mut String, heading: &str, access: &TsanAccess| {
+        write!(out, "  {heading}").unwrap();
+        if let Some(size) = access.size {
+            write!(out, " of size {size}").unwrap();
+        }
+        let thread = if access.thread == "main thread" {
+            access.thread.clone()
+        } else {
+            format!("thread {}", access.thread)
+        };
+        writeln!(out, " at {ptr:?} by {thread}:").unwrap();
+        write_frames(out, &access.stack);
+    };
+    writeln!(out, "==================").unwrap();
+    writeln!(out, "WARNING: ThreadSanitizer: data race").unwrap();
+    let mut heading = access.kind.to_string();
+    heading[..1].make_ascii_uppercase();
+    write_access(&mut out, &heading, &access);
+    write_access(&mut out, &format!("Previous {}", previous.kind), &previous);
+    write!(out, "  Location is {alloc_description} of size {alloc_size} at {alloc_id:?}").unwrap();
+    if alloc_stack.is_empty() {
+        writeln!(out).unwrap();
+        writeln!(out).unwrap();
+    } else {
+        writeln!(out, " allocated at:").unwrap();
+        write_frames(&mut out, &alloc_stack);
+    }
+    for thread in &involved_threads {
+        writeln!(
+            out,
+            "  Thread {} '{}' ({}) created by {} at:",
+            thread.id,
+            thread.name,
+            if thread.running { "running" } else { "finished" },
+            if thread.creator == "main thread" {
+                thread.creator.clone()
+            } else {
+                format!("thread {}", thread.creator)
+            },
+        )
+        .unwrap();
+        write_frames(&mut out, &thread.stack);
+    }
+    writeln!(out, "SUMMARY: ThreadSanitizer: {summary}").unwrap();
+    writeln!(out, "==================").unwrap();
+    out
+}
+
+/// Quote and escape a string for inclusion in JSON output.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len().strict_add(2));
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => write!(out, "\\u{:04x}", u32::from(c)).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Report every thread other than the active one that has not terminated, with where it was
+/// spawned and where it currently is.
+pub fn report_leaked_threads<'tcx>(ecx: &InterpCx<'tcx, MiriMachine<'tcx>>) {
+    let threads = &ecx.machine.threads;
+    let mut any_pruned = false;
+    for thread in threads.live_threads() {
+        if thread == threads.active_thread() {
+            continue;
+        }
+        let name = threads.get_thread_display_name(thread);
+        let state = threads.describe_state(thread);
+        let current = threads.thread_current_span(thread);
+        let notes = if current != DUMMY_SP {
+            vec![note_span!(current.data(), "thread `{name}` is currently here")]
+        } else {
+            vec![]
+        };
+        let Some((_creator, stack)) = threads.creation(thread) else {
+            ecx.tcx.dcx().err(format!("thread leaked: thread `{name}` is {state}"));
+            continue;
+        };
+        let (backtrace, pruned) = prune_stacktrace(stack.to_vec(), &ecx.machine);
+        any_pruned |= pruned;
+        report_msg(
+            DiagLevel::Error,
+            format!("thread leaked: thread `{name}` is {state}, spawned here:"),
+            vec![],
+            notes,
+            vec![],
+            &backtrace,
+            None,
+            &ecx.machine,
+        );
+    }
+    ecx.tcx.dcx().note("the main thread terminated without waiting for these threads");
+    if any_pruned {
+        ecx.tcx.dcx().note(
+            "some details are omitted, run with `MIRIFLAGS=-Zmiri-backtrace=full` for a verbose backtrace",
+        );
+    }
+}
+
 pub fn report_leaks<'tcx>(
     ecx: &InterpCx<'tcx, MiriMachine<'tcx>>,
     leaks: Vec<(AllocId, MemoryKind, Allocation<Provenance, AllocExtra<'tcx>, MiriAllocBytes>)>,
@@ -505,6 +1015,117 @@ pub fn report_leaks<'tcx>(
     }
 }
 
+/// Report where the program exposed provenance and cast integers to pointers, for
+/// `-Zmiri-provenance-audit`: one note per location with the backtrace of the first occurrence,
+/// followed by the totals.
+pub fn report_provenance_audit<'tcx>(
+    ecx: &InterpCx<'tcx, MiriMachine<'tcx>>,
+    audit: &alloc_addresses::ProvenanceAudit<'tcx>,
+) {
+    let mut any_pruned = false;
+    let mut report_site = |title: String, site: &alloc_addresses::AuditSite<'tcx>| {
+        let (backtrace, pruned) = prune_stacktrace(site.backtrace.clone(), &ecx.machine);
+        any_pruned |= pruned;
+        report_msg(DiagLevel::Note, title, vec![], vec![], vec![], &backtrace, None, &ecx.machine);
+    };
+    let mut exposed = FxHashSet::default();
+    for site in audit.exposes.values() {
+        exposed.extend(site.allocs.iter().copied());
+        report_site(
+            format!(
+                "provenance audit: {} pointer{} exposed here, from {} allocation{}",
+                site.count,
+                pluralize!(site.count),
+                site.allocs.len(),
+                pluralize!(site.allocs.len()),
+            ),
+            site,
+        );
+    }
+    for site in audit.int2ptr_casts.values() {
+        report_site(
+            format!(
+                "provenance audit: {} integer-to-pointer cast{} here",
+                site.count,
+                pluralize!(site.count)
+            ),
+            site,
+        );
+    }
+    let exposes: u64 = audit.exposes.values().map(|site| site.count).sum();
+    let casts: u64 = audit.int2ptr_casts.values().map(|site| site.count).sum();
+    ecx.tcx.dcx().note(format!(
+        "provenance audit: {exposes} exposed pointer{} from {} allocation{} at {} location{}, \
+        {casts} integer-to-pointer cast{} at {} location{}",
+        pluralize!(exposes),
+        exposed.len(),
+        pluralize!(exposed.len()),
+        audit.exposes.len(),
+        pluralize!(audit.exposes.len()),
+        pluralize!(casts),
+        audit.int2ptr_casts.len(),
+        pluralize!(audit.int2ptr_casts.len()),
+    ));
+    if any_pruned {
+        ecx.tcx.dcx().note(
+            "some details are omitted, run with `MIRIFLAGS=-Zmiri-backtrace=full` for a verbose backtrace",
+        );
+    }
+}
+
+/// Report how often the aliasing violations that `-Zmiri-aliasing-warn` reported as warnings
+/// happened again after their first report.
+pub fn report_repeated_aliasing_warnings<'tcx>(ecx: &MiriInterpCx<'tcx>) {
+    let Some(borrow_tracker) = &ecx.machine.borrow_tracker else { return };
+    let borrow_tracker = borrow_tracker.borrow();
+    let mut total = 0;
+    let mut groups = 0;
+    for (span, count) in borrow_tracker.aliasing_warning_counts() {
+        total += count;
+        groups += 1;
+        if count > 1 {
+            ecx.tcx.dcx().span_note(
+                span,
+                format!(
+                    "the aliasing violation reported as a warning here happened {count} times; \
+                    later occurrences in the same place, of the same kind and affecting memory \
+                    allocated in the same place were not reported"
+                ),
+            );
+        }
+    }
+    if total > groups {
+        ecx.tcx.dcx().note(format!(
+            "{total} aliasing violations were reported as warnings, grouped into {groups} report{}",
+            pluralize!(groups),
+        ));
+    }
+}
+
+/// Report what the provenance GC did, for `-Zmiri-provenance-gc-stats`.
+pub fn report_provenance_gc_stats<'tcx>(ecx: &MiriInterpCx<'tcx>, stats: &GcStats) {
+    ecx.tcx.dcx().note(format!(
+        "provenance GC: {} collection{} ({} requested by `miri_run_provenance_gc`), \
+        {} tag{} freed, at most {} live tag{}",
+        stats.collections,
+        pluralize!(stats.collections),
+        stats.requested,
+        stats.tags_freed,
+        pluralize!(stats.tags_freed),
+        stats.peak_live_tags,
+        pluralize!(stats.peak_live_tags),
+    ));
+}
+
+pub fn report_suppressed_races<'tcx>(ecx: &MiriInterpCx<'tcx>, suppressed: &[(String, u64)]) {
+    for (rule, count) in suppressed {
+        ecx.tcx.dcx().note(format!(
+            "data race suppression `{rule}` matched {count} race{}",
+            pluralize!(*count)
+        ));
+    }
+}
+
 /// Report an error or note (depending on the `error` argument) with the given stacktrace.
 /// Also emits a full stacktrace of the interpreter stack.
 /// We want to present a multi-line span message for some errors. Diagnostics do not support this
@@ -604,6 +1225,12 @@ impl<'tcx> MiriMachine<'tcx> {
             Int2Ptr { .. } => ("integer-to-pointer cast".to_string(), DiagLevel::Warning),
             ExternTypeReborrow =>
                 ("reborrow of reference to `extern type`".to_string(), DiagLevel::Warning),
+            ProcessSharedSyncObject { .. } | ProcessSharedSyncObjectFreed { .. } =>
+                ("process-shared synchronization object".to_string(), DiagLevel::Warning),
+            BlockingInAsync { .. } =>
+                ("blocking operation in async context".to_string(), DiagLevel::Warning),
+            DowngradedAliasingError { .. } | MixedAccessRace { .. } =>
+                ("Undefined Behavior".to_string(), DiagLevel::Warning),
             CreatedPointerTag(..)
             | PoppedPointerTag(..)
             | CreatedAlloc(..)
@@ -612,6 +1239,10 @@ impl<'tcx> MiriMachine<'tcx> {
             | ProgressReport { .. }
             | WeakMemoryOutdatedLoad { .. } =>
                 ("tracking was triggered".to_string(), DiagLevel::Note),
+            ConsumeOrdering { .. } =>
+                ("`memory_order_consume` treated as acquire".to_string(), DiagLevel::Note),
+            RelaxedSpinLoop { .. } =>
+                ("non-atomic read after a relaxed spin loop".to_string(), DiagLevel::Warning),
         };
 
         let msg = match &e {
@@ -637,16 +1268,42 @@ impl<'tcx> MiriMachine<'tcx> {
             ProgressReport { .. } =>
                 format!("progress report: current operation being executed is here"),
             Int2Ptr { .. } => format!("integer-to-pointer cast"),
-            WeakMemoryOutdatedLoad { ptr } =>
+            WeakMemoryOutdatedLoad { ptr, .. } =>
                 format!("weak memory emulation: outdated value returned from load at {ptr}"),
             ExternTypeReborrow =>
                 format!("reborrow of a reference to `extern type` is not properly supported"),
+            ProcessSharedSyncObject { name, .. } =>
+                format!("`{name}` was initialized as process-shared"),
+            ProcessSharedSyncObjectFreed { name } =>
+                format!("freed memory holding a process-shared `{name}` that was not destroyed"),
+            BlockingInAsync { operation, .. } =>
+                format!("{operation} while polling a future blocks the thread of the executor"),
+            DowngradedAliasingError { msg, .. } | MixedAccessRace { msg, .. } => msg.clone(),
+            ConsumeOrdering { link_name, .. } =>
+                format!(
+                    "`{link_name}` was called with `memory_order_consume`, which Miri treats as `memory_order_acquire`"
+                ),
+            RelaxedSpinLoop { .. } =>
+                format!(
+                    "this reads data written by another thread after a loop that waited with `Relaxed` loads"
+                ),
         };
 
         let notes = match &e {
             ProgressReport { block_count } => {
                 vec![note!("so far, {block_count} basic blocks have been executed")]
             }
+            WeakMemoryOutdatedLoad { store_buffer, .. } => {
+                let mut v =
+                    vec![note!("the store buffer of this location holds, from oldest to latest:")];
+                v.extend(store_buffer.iter().map(|store| note!("{store}")));
+                v
+            }
+            BlockingInAsync { future, poll_span, .. } =>
+                vec![note_span!(
+                    *poll_span,
+                    "this happens inside the future `{future}`, polled here"
+                )],
             _ => vec![],
         };
 
@@ -689,6 +1346,78 @@ impl<'tcx> MiriMachine<'tcx> {
                     ),
                 ]
             }
+            ProcessSharedSyncObject { details: true, .. } => {
+                vec![
+                    note!(
+                        "Miri does not support memory that is shared with other processes, so this object is treated as private to this process"
+                    ),
+                    note!(
+                        "bugs that only show up when the object is used from several processes cannot be detected"
+                    ),
+                ]
+            }
+            ProcessSharedSyncObjectFreed { .. } => {
+                vec![note!(
+                    "other processes could still be using this object and would then access memory that might have been reused"
+                )]
+            }
+            BlockingInAsync { .. } => {
+                vec![
+                    note!(
+                        "while the thread is blocked, the executor cannot make progress on any other task, which can cause slowdowns or deadlocks"
+                    ),
+                    note!(
+                        "use the asynchronous equivalent of this operation, or run it on a thread meant for blocking work"
+                    ),
+                ]
+            }
+            DowngradedAliasingError { krate, .. } => {
+                vec![
+                    note!(
+                        "this aliasing violation in `{krate}` is only reported as a warning because of `-Zmiri-aliasing-warn`"
+                    ),
+                    note!(
+                        "execution continues as if the offending operation was allowed, so Miri might miss or misreport later bugs involving this memory"
+                    ),
+                ]
+            }
+            ConsumeOrdering { details: true, .. } => {
+                vec![
+                    note!(
+                        "consume ordering only orders the accesses that depend on the loaded value; Miri does not track such dependencies, so like all compilers it uses acquire ordering instead"
+                    ),
+                    note!(
+                        "acquire ordering orders more accesses, so Miri cannot detect data races that consume ordering would allow"
+                    ),
+                ]
+            }
+            MixedAccessRace { earlier, extra, .. } => {
+                let mut v = vec![note_span!(*earlier, "and (1) occurred earlier here")];
+                if let Some(extra) = extra {
+                    v.push(note!("{extra}"));
+                }
+                v.push(note!(
+                    "see https://doc.rust-lang.org/nightly/std/sync/atomic/index.html#memory-model-for-atomic-accesses for more information about the Rust memory model"
+                ));
+                v.push(note!(
+                    "this race is only reported as a warning because of `-Zmiri-mixed-access-warnings`; execution continues as if the accesses were allowed"
+                ));
+                v
+            }
+            RelaxedSpinLoop { spin_loop } => {
+                vec![
+                    note_span!(*spin_loop, "the loop waited here"),
+                    note!(
+                        "`Relaxed` loads do not synchronize, so the writes that the other thread performed before storing the awaited value might not be visible yet"
+                    ),
+                    note!(
+                        "use `Acquire` for the load that ends the loop, or put `fence(Acquire)` after the loop"
+                    ),
+                    note!(
+                        "this warning is a heuristic enabled by `-Zmiri-spin-loop-warnings`; it might also fire for correctly synchronized code"
+                    ),
+                ]
+            }
             _ => vec![],
         };
 
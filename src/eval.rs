@@ -1,6 +1,7 @@
 //! Main evaluator loop and setting up the initial stack frame.
 
 use std::ffi::{OsStr, OsString};
+use std::net::IpAddr;
 use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
 use std::task::Poll;
@@ -13,9 +14,14 @@ use rustc_hir::def_id::DefId;
 use rustc_middle::ty::layout::{LayoutCx, LayoutOf};
 use rustc_middle::ty::{self, Ty, TyCtxt};
 use rustc_session::config::EntryFnType;
+use rustc_span::Span;
 
+use crate::concurrency::schedule::{Decision, Exploration, Schedule, ScheduleTrace};
 use crate::concurrency::thread::TlsAllocAction;
-use crate::diagnostics::report_leaks;
+use crate::diagnostics::{
+    report_leaked_threads, report_leaks, report_provenance_audit, report_provenance_gc_stats,
+    report_repeated_aliasing_warnings, report_suppressed_races,
+};
 use crate::shims::tls;
 use crate::*;
 
@@ -83,6 +89,42 @@ pub enum ValidationMode {
     Deep,
 }
 
+/// The order in which an epoll instance reports ready events and wakes up blocked threads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EpollWakeupOrder {
+    /// Report events in the order of their file descriptions, and wake up the thread that blocked
+    /// last.
+    Fixed,
+    /// Pick the order at random.
+    Random,
+    /// Let schedule exploration try every order.
+    Explore,
+}
+
+/// Which weak memory behaviors the weak memory emulation may produce.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WeakMemoryModel {
+    /// Everything the emulation supports of the C++ memory model.
+    Cpp,
+    /// Only what x86-TSO allows: a store may be invisible to other threads while it sits in the
+    /// store buffer of its thread, and stores leave that buffer in program order.
+    X86Tso,
+    /// Only what the other-multi-copy-atomic ARMv8 model allows: once a thread other than the
+    /// writer observed a store, it is visible to all threads.
+    Arm,
+}
+
+/// How data races are reported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RaceReportFormat {
+    /// Only the usual Miri diagnostic.
+    Miri,
+    /// Additionally print a report in the format of ThreadSanitizer.
+    Tsan,
+    /// Additionally print the contents of a ThreadSanitizer report as a single line of JSON.
+    Json,
+}
+
 /// Configuration needed to spawn a Miri instance.
 #[derive(Clone)]
 pub struct MiriConfig {
@@ -97,6 +139,27 @@ pub struct MiriConfig {
     /// If `true` then `Unique` is reborrowed with its own new tag and permission,
     /// otherwise `Unique` is just another raw pointer.
     pub unique_is_unique: bool,
+    /// Whether mutable references to `!Unpin` types are retagged like all other mutable
+    /// references, instead of sharing the tag of their parent.
+    pub not_unpin_is_unique: bool,
+    /// Crates whose aliasing violations are only reported as warnings, optionally restricted to
+    /// the violations of one borrow tracker.
+    pub aliasing_warnings: Vec<(String, Option<BorrowTrackerMethod>)>,
+    /// If `Some`, stream the borrow tracker events of the tracked tags to this file.
+    pub borrow_log: Option<PathBuf>,
+    /// If not empty, only retag in functions of these crates.
+    pub retag_filter_crates: Vec<String>,
+    /// If not empty, only retag values whose type mentions a type whose path contains one of
+    /// these strings.
+    pub retag_filter_types: Vec<String>,
+    /// Whether Tree Borrows errors show the part of the tree involved in the error.
+    pub tree_borrows_print_tree: bool,
+    /// Whether to run the program a second time with the other borrow tracker, and report
+    /// where its verdict differs.
+    pub compare_borrow_trackers: bool,
+    /// If `Some`, Tree Borrows errors write the whole tree of the affected allocation as a
+    /// DOT graph to this file.
+    pub tree_borrows_dot_on_error: Option<PathBuf>,
     /// Controls alignment checking.
     pub check_alignment: AlignmentCheck,
     /// Action for an op requiring communication with the host.
@@ -107,12 +170,16 @@ pub struct MiriConfig {
     pub forwarded_env_vars: Vec<String>,
     /// Additional environment variables that should be set in the interpreted program.
     pub set_env_vars: FxHashMap<String, String>,
+    /// Host names that `getaddrinfo` resolves, in addition to `localhost`, with their addresses.
+    pub resolve: Vec<(String, IpAddr)>,
+    /// The commands that `posix_spawn` can run, if any.
+    pub command_script: Option<CommandScript>,
     /// Command-line arguments passed to the interpreted program.
     pub args: Vec<String>,
     /// The seed to use when non-determinism or randomness are required (e.g. ptr-to-int cast, `getrandom()`).
     pub seed: Option<u64>,
     /// The stacked borrows pointer ids to report about
-    pub tracked_pointer_tags: FxHashSet<BorTag>,
+    pub tracked_pointer_tags: TrackedTags,
     /// The allocation ids to report about.
     pub tracked_alloc_ids: FxHashSet<AllocId>,
     /// For the tracked alloc ids, also report read/write accesses.
@@ -123,9 +190,17 @@ pub struct MiriConfig {
     pub weak_memory_emulation: bool,
     /// Track when an outdated (weak memory) load happens.
     pub track_outdated_loads: bool,
+    /// Which weak memory behaviors the weak memory emulation may produce.
+    pub weak_memory_model: WeakMemoryModel,
     /// Rate of spurious failures for compare_exchange_weak atomic operations,
     /// between 0.0 and 1.0, defaulting to 0.8 (80% chance of failure).
     pub cmpxchg_weak_failure_rate: f64,
+    /// Rate of simulated failures of the `rdrand` and `rdseed` x86 instructions,
+    /// between 0.0 and 1.0, defaulting to 0.0 (they never fail).
+    pub rdrand_failure_rate: f64,
+    /// Whether float operations return the exact result and a fixed NaN, instead of
+    /// non-deterministically picking among the results that Rust allows.
+    pub deterministic_floats: bool,
     /// If `Some`, enable the `measureme` profiler, writing results to a file
     /// with the specified prefix.
     pub measureme_out: Option<String>,
@@ -133,24 +208,87 @@ pub struct MiriConfig {
     pub backtrace_style: BacktraceStyle,
     /// Which provenance to use for int2ptr casts
     pub provenance_mode: ProvenanceMode,
+    /// Report where the program exposes provenance and casts integers to pointers at exit.
+    pub provenance_audit: bool,
     /// Whether to ignore any output by the program. This is helpful when debugging miri
     /// as its messages don't get intermingled with the program messages.
     pub mute_stdout_stderr: bool,
     /// The probability of the active thread being preempted at the end of each basic block.
     pub preemption_rate: f64,
+    /// Whether to preempt the active thread after every atomic access.
+    pub preempt_on_atomics: bool,
+    /// The probability of a condition variable or futex wait ending with a spurious wakeup.
+    pub spurious_wakeup_rate: f64,
+    /// Whether the scheduler always prefers the enabled threads with the highest priority.
+    pub priority_scheduling: bool,
+    /// If `Some`, explore all schedules with at most this many preemptions instead of following a
+    /// random schedule.
+    pub exhaustive_schedules: Option<u32>,
+    /// If `Some`, follow this schedule instead of preempting randomly. This is how schedule
+    /// exploration tells each run which choices to make.
+    pub schedule: Option<Schedule>,
+    /// Whether schedule exploration skips schedules that only reorder independent operations.
+    pub partial_order_reduction: bool,
+    /// Whether schedule exploration also decides which store each atomic load reads from.
+    pub exhaustive_weak_memory: bool,
+    /// The order in which epoll reports ready events and wakes up blocked threads.
+    pub epoll_wakeup_order: EpollWakeupOrder,
+    /// How data races are reported.
+    pub race_report_format: RaceReportFormat,
+    /// Data races that are not reported.
+    pub race_suppressions: Option<RaceSuppressions>,
+    /// Whether data race reports explain which synchronization failed to order the accesses.
+    pub race_timeline: bool,
+    /// Whether data race reports show the steps of the execution from the earlier to the later
+    /// access.
+    pub race_witness: bool,
+    /// Whether to warn about non-atomic reads of data written by other threads right after spin
+    /// loops with relaxed loads.
+    pub spin_loop_warnings: bool,
+    /// If `Some((granularity, min_size))`, the data race detector tracks non-atomic accesses to
+    /// allocations of at least `min_size` bytes in chunks of `granularity` bytes.
+    pub race_granularity: Option<(u64, u64)>,
+    /// Whether races of mixed-size atomic accesses and of atomic and non-atomic accesses are only
+    /// reported as warnings.
+    pub mixed_access_warnings: bool,
+    /// Warn about blocking operations that happen while a future is being polled.
+    pub detect_blocking_in_async: bool,
+    /// Report every thread that is still running when the main thread returns, with the backtrace
+    /// of where it was spawned.
+    pub report_thread_leaks: bool,
+    /// If `Some`, write the scheduling events of the run to this file.
+    pub record_schedule: Option<PathBuf>,
+    /// If `Some`, follow this schedule trace instead of the usual scheduling policy.
+    pub replay_schedule: Option<ScheduleTrace>,
+    /// When a run fails, search for a schedule trace with fewer scheduling events that fails in
+    /// the same way.
+    pub minimize_schedule: bool,
     /// Report the current instruction being executed every N basic blocks.
     pub report_progress: Option<u32>,
     /// Whether Stacked Borrows and Tree Borrows retagging should recurse into fields of datatypes.
     pub retag_fields: RetagFields,
+    /// How many levels of mutable references and boxes retagging follows to also retag the
+    /// references stored behind them.
+    pub retag_depth: u32,
     /// The location of a shared object file to load when calling external functions
     /// FIXME! consider allowing users to specify paths to multiple files, or to a directory
     pub native_lib: Option<PathBuf>,
     /// Run a garbage collector for BorTags every N basic blocks.
     pub gc_interval: u32,
+    /// Report what the BorTag garbage collector did at exit.
+    pub provenance_gc_stats: bool,
     /// The number of CPUs to be reported by miri.
     pub num_cpus: u32,
     /// Requires Miri to emulate pages of a certain size
     pub page_size: Option<u64>,
+    /// Use the virtual clock (that advances with each basic block) even when isolation is
+    /// disabled, so that timing is deterministic.
+    pub virtual_clock: bool,
+    /// Also let the system time advance along with the virtual clock, so that sleeps and timeouts
+    /// on every clock complete without waiting. Implies `virtual_clock`.
+    pub virtual_time: bool,
+    /// The frequency of the Windows performance counter, in ticks per second.
+    pub qpc_frequency: u64,
     /// Whether to collect a backtrace when each allocation is created, just in case it leaks.
     pub collect_leak_backtraces: bool,
     /// Probability for address reuse.
@@ -166,31 +304,70 @@ impl Default for MiriConfig {
             validation: ValidationMode::Shallow,
             borrow_tracker: Some(BorrowTrackerMethod::StackedBorrows),
             unique_is_unique: false,
+            not_unpin_is_unique: false,
+            tree_borrows_print_tree: false,
+            tree_borrows_dot_on_error: None,
+            compare_borrow_trackers: false,
+            aliasing_warnings: vec![],
+            borrow_log: None,
+            retag_filter_crates: vec![],
+            retag_filter_types: vec![],
             check_alignment: AlignmentCheck::Int,
             isolated_op: IsolatedOp::Reject(RejectOpWith::Abort),
             ignore_leaks: false,
             forwarded_env_vars: vec![],
             set_env_vars: FxHashMap::default(),
+            resolve: vec![],
+            command_script: None,
             args: vec![],
             seed: None,
-            tracked_pointer_tags: FxHashSet::default(),
+            tracked_pointer_tags: TrackedTags::default(),
             tracked_alloc_ids: FxHashSet::default(),
             track_alloc_accesses: false,
             data_race_detector: true,
             weak_memory_emulation: true,
             track_outdated_loads: false,
+            weak_memory_model: WeakMemoryModel::Cpp,
             cmpxchg_weak_failure_rate: 0.8, // 80%
+            rdrand_failure_rate: 0.0,
+            deterministic_floats: false,
             measureme_out: None,
             backtrace_style: BacktraceStyle::Short,
             provenance_mode: ProvenanceMode::Default,
+            provenance_audit: false,
             mute_stdout_stderr: false,
             preemption_rate: 0.01, // 1%
+            preempt_on_atomics: false,
+            spurious_wakeup_rate: 0.0,
+            priority_scheduling: false,
+            exhaustive_schedules: None,
+            schedule: None,
+            partial_order_reduction: true,
+            exhaustive_weak_memory: false,
+            epoll_wakeup_order: EpollWakeupOrder::Fixed,
+            race_report_format: RaceReportFormat::Miri,
+            race_suppressions: None,
+            race_timeline: false,
+            race_witness: false,
+            spin_loop_warnings: false,
+            race_granularity: None,
+            mixed_access_warnings: false,
+            detect_blocking_in_async: false,
+            report_thread_leaks: false,
+            record_schedule: None,
+            replay_schedule: None,
+            minimize_schedule: false,
             report_progress: None,
             retag_fields: RetagFields::Yes,
+            retag_depth: 0,
             native_lib: None,
             gc_interval: 10_000,
+            provenance_gc_stats: false,
             num_cpus: 1,
             page_size: None,
+            virtual_clock: false,
+            virtual_time: false,
+            qpc_frequency: 1_000_000_000,
             collect_leak_backtraces: true,
             address_reuse_rate: 0.5,
             address_reuse_cross_thread_rate: 0.1,
@@ -423,17 +600,196 @@ pub fn create_ecx<'tcx>(
 /// Evaluates the entry function specified by `entry_id`.
 /// Returns `Some(return_code)` if program executed completed.
 /// Returns `None` if an evaluation error occurred.
+///
+/// When exploring schedules, the program is run once per schedule until one of the runs fails.
 #[expect(clippy::needless_lifetimes)]
 pub fn eval_entry<'tcx>(
     tcx: TyCtxt<'tcx>,
     entry_id: DefId,
     entry_type: EntryFnType,
-    config: MiriConfig,
+    mut config: MiriConfig,
 ) -> Option<i64> {
-    // Copy setting before we move `config`.
+    let run = if config.exhaustive_schedules.is_some() {
+        explore_schedules(tcx, entry_id, entry_type, &mut config)
+    } else {
+        eval_entry_once(tcx, entry_id, entry_type, &config, /* report */ true)
+    };
+    if config.compare_borrow_trackers
+        && let Some(method) = config.borrow_tracker
+    {
+        compare_borrow_trackers(tcx, entry_id, entry_type, config.clone(), method, &run);
+    }
+    if config.minimize_schedule
+        && run.return_code != Some(0)
+        && let Some(trace) = &run.trace
+    {
+        minimize_schedule(tcx, entry_id, entry_type, config, &run, trace.clone());
+    }
+    run.return_code
+}
+
+/// The result of running the program once.
+struct Run {
+    /// `Some(return_code)` if the program completed, `None` if an evaluation error occurred.
+    return_code: Option<i64>,
+    /// Where the program was when it stopped. Together with the return code, this tells whether
+    /// two runs failed in the same way.
+    stop_span: Span,
+    /// Whether the program stopped because of an aliasing violation.
+    aliasing_violation: bool,
+    /// The scheduling decisions, if schedules are being explored.
+    decisions: Vec<Decision>,
+    /// The scheduling events, if they are being recorded.
+    trace: Option<ScheduleTrace>,
+}
+
+/// Runs the program once per schedule, until all schedules are explored or a run fails. Returns
+/// the last run.
+fn explore_schedules<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    entry_id: DefId,
+    entry_type: EntryFnType,
+    config: &mut MiriConfig,
+) -> Run {
+    let mut exploration = Exploration::default();
+    let mut explored: u64 = 0;
+    loop {
+        let run = eval_entry_once(tcx, entry_id, entry_type, config, /* report */ true);
+        explored = explored.strict_add(1);
+        exploration.record(&run.decisions);
+        if run.return_code != Some(0) {
+            let replay = match &config.record_schedule {
+                Some(path) =>
+                    format!("it can be replayed with `-Zmiri-replay-schedule={}`", path.display()),
+                None =>
+                    "use `-Zmiri-record-schedule=<file>` to record it for `-Zmiri-replay-schedule`"
+                        .to_owned(),
+            };
+            tcx.dcx().note(format!(
+                "this schedule failed after exploring {explored} schedule(s); {replay}"
+            ));
+            return run;
+        }
+        let Some(schedule) = exploration.next() else {
+            return run;
+        };
+        config.schedule = Some(schedule);
+    }
+}
+
+/// Runs the program again, silently, with the other borrow tracker than `method`, which `run`
+/// used, and reports whether the two agree on the program having an aliasing violation.
+fn compare_borrow_trackers<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    entry_id: DefId,
+    entry_type: EntryFnType,
+    mut config: MiriConfig,
+    method: BorrowTrackerMethod,
+    run: &Run,
+) {
+    let other = match method {
+        BorrowTrackerMethod::StackedBorrows => BorrowTrackerMethod::TreeBorrows,
+        BorrowTrackerMethod::TreeBorrows => BorrowTrackerMethod::StackedBorrows,
+    };
+    // The seed and, after exploring schedules, the last schedule are kept, so that both runs
+    // make the same choices up to where the borrow trackers disagree.
+    config.borrow_tracker = Some(other);
+    config.exhaustive_schedules = None;
+    config.mute_stdout_stderr = true;
+    let other_run = eval_entry_once(tcx, entry_id, entry_type, &config, /* report */ false);
+    let (name, other_name) = (method.name(), other.name());
+    match (run.aliasing_violation, other_run.aliasing_violation) {
+        (false, false) =>
+            tcx.dcx().note(format!(
+                "{other_name} agrees with {name}: neither reports an aliasing violation"
+            )),
+        (true, true) if run.stop_span == other_run.stop_span =>
+            tcx.dcx().note(format!(
+                "{other_name} agrees with {name}: both report this aliasing violation"
+            )),
+        (true, true) =>
+            tcx.dcx().span_note(
+                other_run.stop_span,
+                format!("{other_name} reports a different aliasing violation than {name}, here"),
+            ),
+        (true, false) => {
+            let outcome = match other_run.return_code {
+                Some(code) => format!("exits with code {code}"),
+                None => "stops with a different error".to_string(),
+            };
+            tcx.dcx().note(format!(
+                "this aliasing violation is only reported by {name}; under {other_name}, the program {outcome}"
+            ))
+        }
+        (false, true) =>
+            tcx.dcx().span_note(
+                other_run.stop_span,
+                format!(
+                    "{other_name} reports an aliasing violation here, which {name} does not report"
+                ),
+            ),
+    }
+}
+
+/// Searches for a schedule with fewer scheduling events that fails in the same way as `failed`,
+/// which followed `trace`, and reports it.
+fn minimize_schedule<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    entry_id: DefId,
+    entry_type: EntryFnType,
+    mut config: MiriConfig,
+    failed: &Run,
+    trace: ScheduleTrace,
+) {
+    // The runs while minimizing follow the traces we give them, and must not print anything.
+    config.exhaustive_schedules = None;
+    config.schedule = None;
+    config.mute_stdout_stderr = true;
+    let original_events = trace.event_count();
+    let minimized = trace.minimize(|candidate| {
+        config.replay_schedule = Some(candidate.clone());
+        let run = eval_entry_once(tcx, entry_id, entry_type, &config, /* report */ false);
+        if run.return_code == failed.return_code && run.stop_span == failed.stop_span {
+            run.trace
+        } else {
+            None
+        }
+    });
+    let summary = format!(
+        "the failing schedule was minimized from {original_events} to {} scheduling event(s)",
+        minimized.event_count()
+    );
+    if let Some(path) = &config.record_schedule {
+        if let Err(err) = std::fs::write(path, minimized.to_string()) {
+            tcx.dcx()
+                .warn(format!("failed to write the schedule trace to `{}`: {err}", path.display()));
+        }
+        tcx.dcx().note(format!(
+            "{summary}; it can be replayed with `-Zmiri-replay-schedule={}`",
+            path.display()
+        ));
+    } else {
+        tcx.dcx().note(format!(
+            "{summary}; save this trace to a file to replay it with `-Zmiri-replay-schedule=<file>`:\n{}",
+            minimized.to_string().trim_end()
+        ));
+    }
+}
+
+/// Runs the program once. Unless `report` is set, errors are not reported, and leaks are not
+/// checked for.
+#[expect(clippy::needless_lifetimes)]
+fn eval_entry_once<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    entry_id: DefId,
+    entry_type: EntryFnType,
+    config: &MiriConfig,
+    report: bool,
+) -> Run {
     let ignore_leaks = config.ignore_leaks;
+    let report_thread_leaks = config.report_thread_leaks;
 
-    let mut ecx = match create_ecx(tcx, entry_id, entry_type, &config).report_err() {
+    let mut ecx = match create_ecx(tcx, entry_id, entry_type, config).report_err() {
         Ok(v) => v,
         Err(err) => {
             let (kind, backtrace) = err.into_parts();
@@ -451,6 +807,37 @@ pub fn eval_entry<'tcx>(
     });
     // `Ok` can never happen.
     let Err(err) = res.report_err();
+    let aliasing_violation = if let InterpErrorKind::MachineStop(info) = err.kind() {
+        matches!(
+            info.downcast_ref::<TerminationInfo>(),
+            Some(TerminationInfo::StackedBorrowsUb { .. } | TerminationInfo::TreeBorrowsUb { .. })
+        )
+    } else {
+        false
+    };
+    let mut run = Run {
+        return_code: None,
+        stop_span: ecx.machine.current_span(),
+        aliasing_violation,
+        decisions: ecx.machine.threads.take_schedule_decisions(),
+        trace: ecx.machine.threads.recorded_schedule_trace(),
+    };
+    if !report {
+        if let InterpErrorKind::MachineStop(info) = err.kind()
+            && let Some(&TerminationInfo::Exit { code, .. }) =
+                info.downcast_ref::<TerminationInfo>()
+        {
+            run.return_code = Some(code);
+        }
+        return run;
+    }
+    if let Some(path) = &config.record_schedule
+        && let Some(trace) = &run.trace
+        && let Err(err) = std::fs::write(path, trace.to_string())
+    {
+        tcx.dcx()
+            .warn(format!("failed to write the schedule trace to `{}`: {err}", path.display()));
+    }
 
     // Machine cleanup. Only do this if all threads have terminated; threads that are still running
     // might cause Stacked Borrows errors (https://github.com/rust-lang/miri/issues/2396).
@@ -463,14 +850,36 @@ pub fn eval_entry<'tcx>(
     }
 
     // Process the result.
-    let (return_code, leak_check) = report_error(&ecx, err)?;
-    if leak_check && !ignore_leaks {
-        // Check for thread leaks.
-        if !ecx.have_all_terminated() {
+    let result = report_error(&ecx, err);
+    // The audit and statistics are also useful when the program failed, so we print them in any
+    // case.
+    report_repeated_aliasing_warnings(&ecx);
+    if let Some(audit) = &ecx.machine.provenance_audit {
+        report_provenance_audit(&ecx, &audit.borrow());
+    }
+    if let Some(stats) = &ecx.machine.gc_stats {
+        report_provenance_gc_stats(&ecx, stats);
+    }
+    if let Some(data_race) = &ecx.machine.data_race {
+        report_suppressed_races(&ecx, &data_race.suppressed_races());
+    }
+    let Some((return_code, leak_check)) = result else {
+        return run;
+    };
+    // Check for thread leaks. `-Zmiri-report-thread-leaks` takes precedence over
+    // `-Zmiri-ignore-leaks` for threads.
+    if leak_check && (!ignore_leaks || report_thread_leaks) && !ecx.have_all_terminated() {
+        if report_thread_leaks {
+            report_leaked_threads(&ecx);
+        } else {
             tcx.dcx().err("the main thread terminated without waiting for all remaining threads");
+        }
+        if !ignore_leaks {
             tcx.dcx().note("set `MIRIFLAGS=-Zmiri-ignore-leaks` to disable this check");
-            return None;
         }
+        return run;
+    }
+    if leak_check && !ignore_leaks {
         // Check for memory leaks.
         info!("Additional static roots: {:?}", ecx.machine.static_roots);
         let leaks = ecx.take_leaked_allocations(|ecx| &ecx.machine.static_roots);
@@ -479,10 +888,11 @@ pub fn eval_entry<'tcx>(
             tcx.dcx().note("set `MIRIFLAGS=-Zmiri-ignore-leaks` to disable this check");
             // Ignore the provided return code - let the reported error
             // determine the return code.
-            return None;
+            return run;
         }
     }
-    Some(return_code)
+    run.return_code = Some(return_code);
+    run
 }
 
 /// Turns an array of arguments into a Windows command line string.
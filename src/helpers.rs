@@ -15,10 +15,10 @@ use rustc_index::IndexVec;
 use rustc_middle::middle::codegen_fn_attrs::CodegenFnAttrFlags;
 use rustc_middle::middle::dependency_format::Linkage;
 use rustc_middle::middle::exported_symbols::ExportedSymbol;
-use rustc_middle::ty::layout::{FnAbiOf, LayoutOf, MaybeResult, TyAndLayout};
+use rustc_middle::ty::layout::{FnAbiOf, HasTypingEnv, LayoutOf, MaybeResult, TyAndLayout};
 use rustc_middle::ty::{self, FloatTy, IntTy, Ty, TyCtxt, UintTy};
 use rustc_session::config::CrateType;
-use rustc_span::{Span, Symbol};
+use rustc_span::{Span, Symbol, sym};
 
 use crate::*;
 
@@ -468,6 +468,55 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         )
     }
 
+    /// Whether `ty` itself is marked `#[miri::interior_mutable]`, i.e., the aliasing models
+    /// should treat it like an `UnsafeCell` even though it does not contain one that Miri can see.
+    fn type_is_declared_interior_mutable(&self, ty: Ty<'tcx>) -> bool {
+        let this = self.eval_context_ref();
+        let def_id = match ty.kind() {
+            ty::Adt(adt, _) => adt.did(),
+            ty::Foreign(def_id) => *def_id,
+            _ => return false,
+        };
+        let interior_mutable = Symbol::intern("interior_mutable");
+        this.tcx.get_attrs_by_path(def_id, &[sym::miri, interior_mutable]).next().is_some()
+    }
+
+    /// Whether `ty` contains a type marked `#[miri::interior_mutable]`, not behind a pointer.
+    fn type_contains_declared_interior_mutable(&self, ty: Ty<'tcx>) -> bool {
+        let this = self.eval_context_ref();
+        if let Some(&res) = this.machine.interior_mutable_types.borrow().get(&ty) {
+            return res;
+        }
+        let tcx = *this.tcx;
+        let res = this.type_is_declared_interior_mutable(ty)
+            || match ty.kind() {
+                ty::Adt(adt, args) =>
+                    adt.all_fields().any(|field| {
+                        let field_ty = tcx.normalize_erasing_regions(
+                            this.typing_env(),
+                            field.ty(tcx, args),
+                        );
+                        this.type_contains_declared_interior_mutable(field_ty)
+                    }),
+                ty::Array(elem, _) | ty::Slice(elem) =>
+                    this.type_contains_declared_interior_mutable(*elem),
+                ty::Tuple(tys) =>
+                    tys.iter().any(|ty| this.type_contains_declared_interior_mutable(ty)),
+                _ => false,
+            };
+        this.machine.interior_mutable_types.borrow_mut().insert(ty, res);
+        res
+    }
+
+    /// Like `Ty::is_freeze`, but a type that contains a type marked `#[miri::interior_mutable]`
+    /// is not frozen either. This is what the aliasing models use to decide where interior
+    /// mutability is allowed.
+    fn type_is_freeze_for_aliasing(&self, ty: Ty<'tcx>) -> bool {
+        let this = self.eval_context_ref();
+        ty.is_freeze(*this.tcx, this.typing_env())
+            && !this.type_contains_declared_interior_mutable(ty)
+    }
+
     /// Visits the memory covered by `place`, sensitive to freezing: the 2nd parameter
     /// of `action` will be true if this is frozen, false if this is in an `UnsafeCell`.
     /// The range is relative to `place`.
@@ -578,10 +627,11 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                         Some(adt.did()) == self.ecx.tcx.lang_items().unsafe_cell_type(),
                     _ => false,
                 };
-                if is_unsafe_cell {
-                    // We do not have to recurse further, this is an `UnsafeCell`.
+                if is_unsafe_cell || self.ecx.type_is_declared_interior_mutable(v.layout.ty) {
+                    // We do not have to recurse further, this is an `UnsafeCell` (or declared to
+                    // behave like one).
                     (self.unsafe_cell_action)(v)
-                } else if self.ecx.type_is_freeze(v.layout.ty) {
+                } else if self.ecx.type_is_freeze_for_aliasing(v.layout.ty) {
                     // This is `Freeze`, there cannot be an `UnsafeCell`
                     interp_ok(())
                 } else if matches!(v.layout.fields, FieldsShape::Union(..)) {
@@ -767,6 +817,33 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         )
     }
 
+    /// Parse a `timeval` struct and return it as a `std::time::Duration`. It returns `None`
+    /// if the value in the `timeval` struct is invalid.
+    fn read_timeval(&mut self, tv: &MPlaceTy<'tcx>) -> InterpResult<'tcx, Option<Duration>> {
+        let this = self.eval_context_mut();
+        // The fields have different types on different targets.
+        let read_field = |name: &str| -> InterpResult<'tcx, i128> {
+            let field = this.project_field_named(tv, name)?;
+            this.read_scalar(&field)?.to_int(field.layout.size)
+        };
+        let seconds = read_field("tv_sec")?;
+        let microseconds = read_field("tv_usec")?;
+
+        interp_ok(
+            try {
+                // tv_sec must be non-negative.
+                let seconds: u64 = seconds.try_into().ok()?;
+                // tv_usec must be non-negative.
+                let microseconds: u32 = microseconds.try_into().ok()?;
+                if microseconds >= 1_000_000 {
+                    // tv_usec must not be greater than 999,999.
+                    None?
+                }
+                Duration::new(seconds, microseconds * 1000)
+            },
+        )
+    }
+
     /// Read bytes from a byte slice.
     fn read_byte_slice<'a>(&'a self, slice: &ImmTy<'tcx>) -> InterpResult<'tcx, &'a [u8]>
     where
@@ -5,7 +5,8 @@ mod simd;
 
 use rand::Rng;
 use rustc_abi::Size;
-use rustc_apfloat::{Float, Round};
+use rustc_apfloat::ieee::Single;
+use rustc_apfloat::{Float, FloatConvert, Round};
 use rustc_middle::mir;
 use rustc_middle::ty::{self, FloatTy};
 use rustc_span::{Symbol, sym};
@@ -263,6 +264,41 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.write_scalar(res, dest)?;
             }
 
+            #[rustfmt::skip]
+            | "sinf16"
+            | "cosf16"
+            | "sqrtf16"
+            | "expf16"
+            | "exp2f16"
+            | "logf16"
+            | "log10f16"
+            | "log2f16"
+            => {
+                let [f] = check_arg_count(args)?;
+                let f = this.read_scalar(f)?.to_f16()?;
+                // Using host floats in single precision except for sqrt (but it's fine, these
+                // operations do not have guaranteed precision).
+                let res = match intrinsic_name {
+                    "sinf16" => math::f16_via_f32(f, f32::sin),
+                    "cosf16" => math::f16_via_f32(f, f32::cos),
+                    "sqrtf16" => math::sqrt(f),
+                    "expf16" => math::f16_via_f32(f, f32::exp),
+                    "exp2f16" => math::f16_via_f32(f, f32::exp2),
+                    "logf16" => math::f16_via_f32(f, f32::ln),
+                    "log10f16" => math::f16_via_f32(f, f32::log10),
+                    "log2f16" => math::f16_via_f32(f, f32::log2),
+                    _ => bug!(),
+                };
+                let res = this.adjust_nan(res, &[f]);
+                this.write_scalar(res, dest)?;
+            }
+            "sqrtf128" => {
+                let [f] = check_arg_count(args)?;
+                let f = this.read_scalar(f)?.to_f128()?;
+                let res = this.adjust_nan(math::sqrt(f), &[f]);
+                this.write_scalar(res, dest)?;
+            }
+
             "fmaf32" => {
                 let [a, b, c] = check_arg_count(args)?;
                 let a = this.read_scalar(a)?.to_f32()?;
@@ -283,13 +319,34 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let res = this.adjust_nan(res, &[a, b, c]);
                 this.write_scalar(res, dest)?;
             }
+            "fmaf16" => {
+                let [a, b, c] = check_arg_count(args)?;
+                let a = this.read_scalar(a)?.to_f16()?;
+                let b = this.read_scalar(b)?.to_f16()?;
+                let c = this.read_scalar(c)?.to_f16()?;
+                // There are no stable host floats of this type, so we use apfloat.
+                let res = a.mul_add(b, c).value;
+                let res = this.adjust_nan(res, &[a, b, c]);
+                this.write_scalar(res, dest)?;
+            }
+            "fmaf128" => {
+                let [a, b, c] = check_arg_count(args)?;
+                let a = this.read_scalar(a)?.to_f128()?;
+                let b = this.read_scalar(b)?.to_f128()?;
+                let c = this.read_scalar(c)?.to_f128()?;
+                // There are no stable host floats of this type, so we use apfloat.
+                let res = a.mul_add(b, c).value;
+                let res = this.adjust_nan(res, &[a, b, c]);
+                this.write_scalar(res, dest)?;
+            }
 
             "fmuladdf32" => {
                 let [a, b, c] = check_arg_count(args)?;
                 let a = this.read_scalar(a)?.to_f32()?;
                 let b = this.read_scalar(b)?.to_f32()?;
                 let c = this.read_scalar(c)?.to_f32()?;
-                let fuse: bool = this.machine.rng.get_mut().gen();
+                let fuse: bool =
+                    this.machine.deterministic_floats || this.machine.rng.get_mut().gen();
                 let res = if fuse {
                     // FIXME: Using host floats, to work around https://github.com/rust-lang/rustc_apfloat/issues/11
                     a.to_host().mul_add(b.to_host(), c.to_host()).to_soft()
@@ -304,7 +361,8 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let a = this.read_scalar(a)?.to_f64()?;
                 let b = this.read_scalar(b)?.to_f64()?;
                 let c = this.read_scalar(c)?.to_f64()?;
-                let fuse: bool = this.machine.rng.get_mut().gen();
+                let fuse: bool =
+                    this.machine.deterministic_floats || this.machine.rng.get_mut().gen();
                 let res = if fuse {
                     // FIXME: Using host floats, to work around https://github.com/rust-lang/rustc_apfloat/issues/11
                     a.to_host().mul_add(b.to_host(), c.to_host()).to_soft()
@@ -314,7 +372,40 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let res = this.adjust_nan(res, &[a, b, c]);
                 this.write_scalar(res, dest)?;
             }
+            "fmuladdf16" => {
+                let [a, b, c] = check_arg_count(args)?;
+                let a = this.read_scalar(a)?.to_f16()?;
+                let b = this.read_scalar(b)?.to_f16()?;
+                let c = this.read_scalar(c)?.to_f16()?;
+                let fuse: bool =
+                    this.machine.deterministic_floats || this.machine.rng.get_mut().gen();
+                let res = if fuse { a.mul_add(b, c).value } else { ((a * b).value + c).value };
+                let res = this.adjust_nan(res, &[a, b, c]);
+                this.write_scalar(res, dest)?;
+            }
+            "fmuladdf128" => {
+                let [a, b, c] = check_arg_count(args)?;
+                let a = this.read_scalar(a)?.to_f128()?;
+                let b = this.read_scalar(b)?.to_f128()?;
+                let c = this.read_scalar(c)?.to_f128()?;
+                let fuse: bool =
+                    this.machine.deterministic_floats || this.machine.rng.get_mut().gen();
+                let res = if fuse { a.mul_add(b, c).value } else { ((a * b).value + c).value };
+                let res = this.adjust_nan(res, &[a, b, c]);
+                this.write_scalar(res, dest)?;
+            }
 
+            "powf16" => {
+                let [f1, f2] = check_arg_count(args)?;
+                let f1 = this.read_scalar(f1)?.to_f16()?;
+                let f2 = this.read_scalar(f2)?.to_f16()?;
+                let f2_single: Single = f2.convert(&mut false).value;
+                let f2_host = f2_single.to_host();
+                // Using host floats (but it's fine, this operation does not have guaranteed precision).
+                let res = math::f16_via_f32(f1, |f1| f1.powf(f2_host));
+                let res = this.adjust_nan(res, &[f1, f2]);
+                this.write_scalar(res, dest)?;
+            }
             "powf32" => {
                 let [f1, f2] = check_arg_count(args)?;
                 let f1 = this.read_scalar(f1)?.to_f32()?;
@@ -334,6 +425,15 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.write_scalar(res, dest)?;
             }
 
+            "powif16" => {
+                let [f, i] = check_arg_count(args)?;
+                let f = this.read_scalar(f)?.to_f16()?;
+                let i = this.read_scalar(i)?.to_i32()?;
+                // Using host floats (but it's fine, this operation does not have guaranteed precision).
+                let res = math::f16_via_f32(f, |f| f.powi(i));
+                let res = this.adjust_nan(res, &[f]);
+                this.write_scalar(res, dest)?;
+            }
             "powif32" => {
                 let [f, i] = check_arg_count(args)?;
                 let f = this.read_scalar(f)?.to_f32()?;
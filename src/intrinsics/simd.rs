@@ -87,14 +87,14 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                             this.unary_op(mir_op, &op)?.to_scalar()
                         }
                         Op::Abs => {
-                            // Works for f32 and f64.
+                            // Works for f16, f32 and f64.
                             let ty::Float(float_ty) = op.layout.ty.kind() else {
                                 span_bug!(this.cur_span(), "{} operand is not a float", intrinsic_name)
                             };
                             let op = op.to_scalar();
                             // "Bitwise" operation, no NaN adjustments
                             match float_ty {
-                                FloatTy::F16 => unimplemented!("f16_f128"),
+                                FloatTy::F16 => Scalar::from_f16(op.to_f16()?.abs()),
                                 FloatTy::F32 => Scalar::from_f32(op.to_f32()?.abs()),
                                 FloatTy::F64 => Scalar::from_f64(op.to_f64()?.abs()),
                                 FloatTy::F128 => unimplemented!("f16_f128"),
@@ -107,7 +107,22 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                             // Using host floats except for sqrt (but it's fine, these operations do not
                             // have guaranteed precision).
                             match float_ty {
-                                FloatTy::F16 => unimplemented!("f16_f128"),
+                                FloatTy::F16 => {
+                                    let f = op.to_scalar().to_f16()?;
+                                    let res = match host_op {
+                                        "fsqrt" => math::sqrt(f),
+                                        "fsin" => math::f16_via_f32(f, f32::sin),
+                                        "fcos" => math::f16_via_f32(f, f32::cos),
+                                        "fexp" => math::f16_via_f32(f, f32::exp),
+                                        "fexp2" => math::f16_via_f32(f, f32::exp2),
+                                        "flog" => math::f16_via_f32(f, f32::ln),
+                                        "flog2" => math::f16_via_f32(f, f32::log2),
+                                        "flog10" => math::f16_via_f32(f, f32::log10),
+                                        _ => bug!(),
+                                    };
+                                    let res = this.adjust_nan(res, &[f]);
+                                    Scalar::from(res)
+                                }
                                 FloatTy::F32 => {
                                     let f = op.to_scalar().to_f32()?;
                                     let res = match host_op {
@@ -148,7 +163,12 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                                 span_bug!(this.cur_span(), "{} operand is not a float", intrinsic_name)
                             };
                             match float_ty {
-                                FloatTy::F16 => unimplemented!("f16_f128"),
+                                FloatTy::F16 => {
+                                    let f = op.to_scalar().to_f16()?;
+                                    let res = f.round_to_integral(rounding).value;
+                                    let res = this.adjust_nan(res, &[f]);
+                                    Scalar::from_f16(res)
+                                }
                                 FloatTy::F32 => {
                                     let f = op.to_scalar().to_f32()?;
                                     let res = f.round_to_integral(rounding).value;
@@ -303,13 +323,21 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     let c = this.read_scalar(&this.project_index(&c, i)?)?;
                     let dest = this.project_index(&dest, i)?;
 
-                    // Works for f32 and f64.
+                    // Works for f16, f32 and f64.
                     // FIXME: using host floats to work around https://github.com/rust-lang/miri/issues/2468.
                     let ty::Float(float_ty) = dest.layout.ty.kind() else {
                         span_bug!(this.cur_span(), "{} operand is not a float", intrinsic_name)
                     };
                     let val = match float_ty {
-                        FloatTy::F16 => unimplemented!("f16_f128"),
+                        FloatTy::F16 => {
+                            let a = a.to_f16()?;
+                            let b = b.to_f16()?;
+                            let c = c.to_f16()?;
+                            // There are no stable host floats of this type, so we use apfloat.
+                            let res = a.mul_add(b, c).value;
+                            let res = this.adjust_nan(res, &[a, b, c]);
+                            Scalar::from(res)
+                        }
                         FloatTy::F32 => {
                             let a = a.to_f32()?;
                             let b = b.to_f32()?;
@@ -796,7 +824,16 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let left = left.to_scalar();
         let right = right.to_scalar();
         interp_ok(match float_ty {
-            FloatTy::F16 => unimplemented!("f16_f128"),
+            FloatTy::F16 => {
+                let left = left.to_f16()?;
+                let right = right.to_f16()?;
+                let res = match op {
+                    MinMax::Min => left.min(right),
+                    MinMax::Max => left.max(right),
+                };
+                let res = this.adjust_nan(res, &[left, right]);
+                Scalar::from_f16(res)
+            }
             FloatTy::F32 => {
                 let left = left.to_f32()?;
                 let right = right.to_f32()?;
@@ -4,7 +4,9 @@
 use std::any::Any;
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::collections::hash_map::Entry;
+use std::net::IpAddr;
 use std::path::Path;
 use std::{fmt, process};
 
@@ -22,12 +24,13 @@ use rustc_middle::ty::layout::{
 };
 use rustc_middle::ty::{self, Instance, Ty, TyCtxt};
 use rustc_session::config::InliningThreshold;
-use rustc_span::def_id::{CrateNum, DefId};
+use rustc_span::def_id::{CrateNum, DefId, LOCAL_CRATE};
 use rustc_span::{Span, SpanData, Symbol};
 
 use crate::concurrency::cpu_affinity::{self, CpuAffinityMask};
 use crate::concurrency::data_race::{self, NaReadType, NaWriteType};
-use crate::concurrency::weak_memory;
+use crate::concurrency::schedule::{ScheduleExplorer, ScheduleTracer};
+use crate::concurrency::{VClock, weak_memory};
 use crate::*;
 
 /// First real-time signal.
@@ -74,6 +77,11 @@ pub struct FrameExtra<'tcx> {
 
     /// Data race detector per-frame data.
     pub data_race: Option<data_race::FrameState>,
+
+    /// Whether `-Zmiri-strict-transmutes` applies to this frame, i.e., it is enabled and the
+    /// function is not part of the standard library. Computed once on creation, as it gets
+    /// checked on every read of a local.
+    pub check_transmutes: bool,
 }
 
 impl<'tcx> std::fmt::Debug for FrameExtra<'tcx> {
@@ -86,6 +94,7 @@ impl<'tcx> std::fmt::Debug for FrameExtra<'tcx> {
             is_user_relevant,
             salt,
             data_race,
+            check_transmutes,
         } = self;
         f.debug_struct("FrameData")
             .field("borrow_tracker", borrow_tracker)
@@ -93,6 +102,7 @@ impl<'tcx> std::fmt::Debug for FrameExtra<'tcx> {
             .field("is_user_relevant", is_user_relevant)
             .field("salt", salt)
             .field("data_race", data_race)
+            .field("check_transmutes", check_transmutes)
             .finish()
     }
 }
@@ -106,6 +116,7 @@ impl VisitProvenance for FrameExtra<'_> {
             is_user_relevant: _,
             salt: _,
             data_race: _,
+            check_transmutes: _,
         } = self;
 
         catch_unwind.visit_provenance(visit);
@@ -343,6 +354,9 @@ pub struct AllocExtra<'tcx> {
     /// freed.
     /// This maps offsets to synchronization-primitive-specific data.
     pub sync: FxHashMap<Size, Box<dyn Any>>,
+    /// The synchronization primitives in this allocation that were initialized as process-shared
+    /// and not destroyed yet. This maps offsets to the name of the C type of the primitive.
+    pub process_shared_sync: FxHashMap<Size, &'static str>,
 }
 
 // We need a `Clone` impl because the machine passes `Allocation` through `Cow`...
@@ -355,7 +369,14 @@ impl<'tcx> Clone for AllocExtra<'tcx> {
 
 impl VisitProvenance for AllocExtra<'_> {
     fn visit_provenance(&self, visit: &mut VisitWith<'_>) {
-        let AllocExtra { borrow_tracker, data_race, weak_memory, backtrace: _, sync: _ } = self;
+        let AllocExtra {
+            borrow_tracker,
+            data_race,
+            weak_memory,
+            backtrace: _,
+            sync: _,
+            process_shared_sync: _,
+        } = self;
 
         borrow_tracker.visit_provenance(visit);
         data_race.visit_provenance(visit);
@@ -479,6 +500,36 @@ pub struct MiriMachine<'tcx> {
 
     /// The list of all EpollEventInterest.
     pub(crate) epoll_interests: shims::EpollInterestTable,
+    /// This tracks which kqueue events watch which file descriptions.
+    pub(crate) kqueue_interests: shims::KqueueInterestTable,
+    /// The threads that are blocked in `poll` or `select`.
+    pub(crate) blocked_poll_tid: Vec<ThreadId>,
+    /// The release clock of the last readiness change of every file description. `poll` and
+    /// `select` acquire it when they report a file description as ready.
+    pub(crate) readiness_clocks: BTreeMap<shims::FdId, VClock>,
+    /// The threads that are blocked in `WaitForSingleObject`, `WaitForMultipleObjects` or
+    /// `GetOverlappedResult`.
+    pub(crate) blocked_object_wait_tid: Vec<ThreadId>,
+
+    /// The table of all bound named sockets.
+    pub(crate) sockets: shims::SocketTable,
+    /// The processes that the program created with `posix_spawn`.
+    pub(crate) processes: shims::ProcessTable,
+    /// The table of all Windows named pipes.
+    pub(crate) named_pipes: shims::NamedPipeTable,
+    /// The associations of Windows handles with I/O completion ports.
+    pub(crate) completion_ports: shims::CompletionPortTable,
+    /// The files that are open through Windows file handles.
+    pub(crate) open_files: shims::OpenFileTable,
+    /// The bytes that `ReadConsoleW` read from every console but could not return yet: the start
+    /// of a character that was split across reads, and characters that did not fit into the buffer.
+    pub(crate) console_input: BTreeMap<shims::FdId, Vec<u8>>,
+    /// The files in the virtual Windows temporary directory that is used with isolation.
+    pub(crate) temp_files: shims::VirtualTempDir,
+    /// Host names that `getaddrinfo` resolves, with their addresses.
+    pub(crate) resolve: Vec<(String, IpAddr)>,
+    /// The commands that `posix_spawn` can run, if any.
+    pub(crate) command_script: Option<shims::CommandScript>,
 
     /// This machine's monotone clock.
     pub(crate) clock: Clock,
@@ -536,6 +587,13 @@ pub struct MiriMachine<'tcx> {
     /// Failure rate of compare_exchange_weak, between 0.0 and 1.0
     pub(crate) cmpxchg_weak_failure_rate: f64,
 
+    /// Failure rate of rdrand and rdseed, between 0.0 and 1.0
+    pub(crate) rdrand_failure_rate: f64,
+
+    /// Whether float operations are deterministic: no random errors, no random fusing of
+    /// multiply-add, and always the preferred NaN.
+    pub(crate) deterministic_floats: bool,
+
     /// Corresponds to -Zmiri-mute-stdout-stderr and doesn't write the output but acts as if it succeeded.
     pub(crate) mute_stdout_stderr: bool,
 
@@ -545,6 +603,36 @@ pub struct MiriMachine<'tcx> {
     /// The probability of the active thread being preempted at the end of each basic block.
     pub(crate) preemption_rate: f64,
 
+    /// Whether the active thread is preempted after every atomic access.
+    pub(crate) preempt_on_atomics: bool,
+
+    /// The probability of a condition variable or futex wait ending with a spurious wakeup.
+    pub(crate) spurious_wakeup_rate: f64,
+
+    /// The order in which epoll reports ready events and wakes up blocked threads.
+    pub(crate) epoll_wakeup_order: EpollWakeupOrder,
+
+    /// How data races are reported.
+    pub(crate) race_report_format: RaceReportFormat,
+
+    /// Whether to warn about blocking operations that happen while a future is being polled.
+    pub(crate) detect_blocking_in_async: bool,
+    /// The call sites of blocking operations inside futures that we already warned about.
+    pub(crate) reported_blocking_in_async: FxHashSet<Span>,
+
+    /// Whether to report leaked threads individually, which requires recording where each thread
+    /// was spawned.
+    pub(crate) report_thread_leaks: bool,
+
+    /// Whether Tree Borrows errors show the part of the tree involved in the error.
+    pub(crate) tree_borrows_print_tree: bool,
+    /// If `Some`, Tree Borrows errors write the tree of the affected allocation to this file.
+    pub(crate) tree_borrows_dot_on_error: Option<std::path::PathBuf>,
+
+    /// If `Some`, where the program exposes provenance and casts integers to pointers,
+    /// for `-Zmiri-provenance-audit`.
+    pub(crate) provenance_audit: Option<RefCell<alloc_addresses::ProvenanceAudit<'tcx>>>,
+
     /// If `Some`, we will report the current stack every N basic blocks.
     pub(crate) report_progress: Option<u32>,
     // The total number of blocks that have been executed.
@@ -560,6 +648,8 @@ pub struct MiriMachine<'tcx> {
     pub(crate) gc_interval: u32,
     /// The number of blocks that passed since the last BorTag GC pass.
     pub(crate) since_gc: u32,
+    /// If `Some`, what the BorTag GC did so far, for `-Zmiri-provenance-gc-stats`.
+    pub(crate) gc_stats: Option<GcStats>,
 
     /// The number of CPUs to be reported by miri.
     pub(crate) num_cpus: u32,
@@ -569,6 +659,9 @@ pub struct MiriMachine<'tcx> {
     pub(crate) stack_addr: u64,
     pub(crate) stack_size: u64,
 
+    /// The frequency of the Windows performance counter, in ticks per second.
+    pub(crate) qpc_frequency: u64,
+
     /// Whether to collect a backtrace when each allocation is created, just in case it leaks.
     pub(crate) collect_leak_backtraces: bool,
 
@@ -591,6 +684,10 @@ pub struct MiriMachine<'tcx> {
 
     /// A cache of "data range" computations for unions (i.e., the offsets of non-padding bytes).
     union_data_ranges: FxHashMap<Ty<'tcx>, RangeSet>,
+
+    /// A cache of whether a type contains a type marked `#[miri::interior_mutable]`, not behind
+    /// a pointer.
+    pub(crate) interior_mutable_types: RefCell<FxHashMap<Ty<'tcx>, bool>>,
 }
 
 impl<'tcx> MiriMachine<'tcx> {
@@ -646,7 +743,25 @@ impl<'tcx> MiriMachine<'tcx> {
             cpu_affinity::MAX_CPUS,
             config.num_cpus
         );
-        let threads = ThreadManager::default();
+        let explore_schedules = config.exhaustive_schedules.is_some() || config.schedule.is_some();
+        let explorer = explore_schedules.then(|| {
+            ScheduleExplorer::new(
+                config.schedule.clone().unwrap_or_default(),
+                config.exhaustive_schedules,
+                config.partial_order_reduction,
+                config.exhaustive_weak_memory,
+                config.epoll_wakeup_order == EpollWakeupOrder::Explore,
+            )
+        });
+        let tracer = (config.replay_schedule.is_some()
+            || config.record_schedule.is_some()
+            || config.minimize_schedule)
+            .then(|| {
+                // When minimizing, the traces we follow are missing events, so we have to fill in
+                // the gaps.
+                ScheduleTracer::new(config.replay_schedule.clone(), config.minimize_schedule)
+            });
+        let threads = ThreadManager::new(explorer, tracer, config.priority_scheduling);
         let mut thread_cpu_affinity = FxHashMap::default();
         if matches!(&*tcx.sess.target.os, "linux" | "freebsd" | "android") {
             thread_cpu_affinity
@@ -668,6 +783,19 @@ impl<'tcx> MiriMachine<'tcx> {
             validation: config.validation,
             fds: shims::FdTable::init(config.mute_stdout_stderr),
             epoll_interests: shims::EpollInterestTable::new(),
+            kqueue_interests: shims::KqueueInterestTable::new(),
+            blocked_poll_tid: Vec::new(),
+            readiness_clocks: BTreeMap::new(),
+            blocked_object_wait_tid: Vec::new(),
+            sockets: shims::SocketTable::new(),
+            processes: Default::default(),
+            named_pipes: shims::NamedPipeTable::new(),
+            completion_ports: shims::CompletionPortTable::new(),
+            open_files: shims::OpenFileTable::new(),
+            console_input: BTreeMap::new(),
+            temp_files: shims::VirtualTempDir::new(),
+            resolve: config.resolve.clone(),
+            command_script: config.command_script.clone(),
             dirs: Default::default(),
             layouts,
             threads,
@@ -685,12 +813,28 @@ impl<'tcx> MiriMachine<'tcx> {
             track_alloc_accesses: config.track_alloc_accesses,
             check_alignment: config.check_alignment,
             cmpxchg_weak_failure_rate: config.cmpxchg_weak_failure_rate,
+            rdrand_failure_rate: config.rdrand_failure_rate,
+            deterministic_floats: config.deterministic_floats,
             mute_stdout_stderr: config.mute_stdout_stderr,
             weak_memory: config.weak_memory_emulation,
-            preemption_rate: config.preemption_rate,
+            // When the schedule is chosen by the explorer, there is no random preemption.
+            preemption_rate: if explore_schedules { 0.0 } else { config.preemption_rate },
+            preempt_on_atomics: config.preempt_on_atomics && !explore_schedules,
+            spurious_wakeup_rate: config.spurious_wakeup_rate,
+            epoll_wakeup_order: config.epoll_wakeup_order,
+            race_report_format: config.race_report_format,
+            detect_blocking_in_async: config.detect_blocking_in_async,
+            reported_blocking_in_async: FxHashSet::default(),
+            report_thread_leaks: config.report_thread_leaks,
+            tree_borrows_print_tree: config.tree_borrows_print_tree,
+            tree_borrows_dot_on_error: config.tree_borrows_dot_on_error.clone(),
+            provenance_audit: config.provenance_audit.then(RefCell::default),
             report_progress: config.report_progress,
             basic_block_count: 0,
-            clock: Clock::new(config.isolated_op == IsolatedOp::Allow),
+            clock: Clock::new(
+                config.isolated_op == IsolatedOp::Allow && !config.virtual_clock,
+                config.isolated_op == IsolatedOp::Allow && config.virtual_time,
+            ),
             #[cfg(unix)]
             native_lib: config.native_lib.as_ref().map(|lib_file_path| {
                 let target_triple = tcx.sess.opts.target_triple.tuple();
@@ -719,15 +863,18 @@ impl<'tcx> MiriMachine<'tcx> {
             }),
             gc_interval: config.gc_interval,
             since_gc: 0,
+            gc_stats: config.provenance_gc_stats.then(GcStats::default),
             num_cpus: config.num_cpus,
             page_size,
             stack_addr,
             stack_size,
+            qpc_frequency: config.qpc_frequency,
             collect_leak_backtraces: config.collect_leak_backtraces,
             allocation_spans: RefCell::new(FxHashMap::default()),
             const_cache: RefCell::new(FxHashMap::default()),
             symbolic_alignment: RefCell::new(FxHashMap::default()),
             union_data_ranges: FxHashMap::default(),
+            interior_mutable_types: RefCell::new(FxHashMap::default()),
         }
     }
 
@@ -758,6 +905,38 @@ impl<'tcx> MiriMachine<'tcx> {
         def_id.is_local() || self.local_crates.contains(&def_id.krate)
     }
 
+    /// Check whether `krate` is a local crate.
+    pub(crate) fn is_local_crate(&self, krate: CrateNum) -> bool {
+        krate == LOCAL_CRATE || self.local_crates.contains(&krate)
+    }
+
+    /// Check whether `krate` is part of the standard library: `core`, `alloc`, `std`, or one of
+    /// the crates they depend on.
+    pub(crate) fn is_std_crate(&self, mut krate: CrateNum) -> bool {
+        loop {
+            if matches!(self.tcx.crate_name(krate).as_str(), "core" | "alloc" | "std") {
+                return true;
+            }
+            match self.tcx.extern_crate(krate) {
+                Some(extern_crate) if !extern_crate.is_direct() =>
+                    krate = extern_crate.dependency_of,
+                _ => return false,
+            }
+        }
+    }
+
+    /// The crate of the innermost frame of the active thread that is not part of the standard
+    /// library. Operations are often performed by a standard library function on behalf of its
+    /// caller, so this is the crate that is responsible for what happens right now.
+    pub(crate) fn responsible_crate(&self) -> Option<CrateNum> {
+        self.threads
+            .active_thread_stack()
+            .iter()
+            .rev()
+            .map(|frame| frame.instance().def_id().krate)
+            .find(|&krate| !self.is_std_crate(krate))
+    }
+
     /// Called when the interpreter is going to shut down abnormally, such as due to a Ctrl-C.
     pub(crate) fn handle_abnormal_termination(&mut self) {
         // All strings in the profile data are stored in a single string table which is not
@@ -807,6 +986,19 @@ impl VisitProvenance for MiriMachine<'_> {
             alloc_addresses,
             fds,
             epoll_interests:_,
+            kqueue_interests: _,
+            blocked_poll_tid: _,
+            readiness_clocks: _,
+            blocked_object_wait_tid: _,
+            sockets: _,
+            processes: _,
+            named_pipes: _,
+            completion_ports: _,
+            open_files: _,
+            console_input: _,
+            temp_files: _,
+            resolve: _,
+            command_script: _,
             tcx: _,
             isolated_op: _,
             validation: _,
@@ -823,23 +1015,38 @@ impl VisitProvenance for MiriMachine<'_> {
             track_alloc_accesses: _,
             check_alignment: _,
             cmpxchg_weak_failure_rate: _,
+            rdrand_failure_rate: _,
+            deterministic_floats: _,
             mute_stdout_stderr: _,
             weak_memory: _,
             preemption_rate: _,
+            preempt_on_atomics: _,
+            spurious_wakeup_rate: _,
+            epoll_wakeup_order: _,
+            race_report_format: _,
+            detect_blocking_in_async: _,
+            reported_blocking_in_async: _,
+            report_thread_leaks: _,
+            tree_borrows_print_tree: _,
+            tree_borrows_dot_on_error: _,
+            provenance_audit: _,
             report_progress: _,
             basic_block_count: _,
             native_lib: _,
             gc_interval: _,
             since_gc: _,
+            gc_stats: _,
             num_cpus: _,
             page_size: _,
             stack_addr: _,
             stack_size: _,
+            qpc_frequency: _,
             collect_leak_backtraces: _,
             allocation_spans: _,
             const_cache: _,
             symbolic_alignment: _,
             union_data_ranges: _,
+            interior_mutable_types: _,
         } = self;
 
         threads.visit_provenance(visit);
@@ -1201,6 +1408,7 @@ impl<'tcx> Machine<'tcx> for MiriMachine<'tcx> {
             weak_memory,
             backtrace,
             sync: FxHashMap::default(),
+            process_shared_sync: FxHashMap::default(),
         })
     }
 
@@ -1365,6 +1573,15 @@ impl<'tcx> Machine<'tcx> for MiriMachine<'tcx> {
         if machine.tracked_alloc_ids.contains(&alloc_id) {
             machine.emit_diagnostic(NonHaltingDiagnostic::FreedAlloc(alloc_id));
         }
+        if !alloc_extra.process_shared_sync.is_empty() {
+            // Another process could still be using these primitives.
+            let mut process_shared: Vec<_> = alloc_extra.process_shared_sync.iter().collect();
+            process_shared.sort_by_key(|(offset, _name)| **offset);
+            for (_offset, name) in process_shared {
+                machine
+                    .emit_diagnostic(NonHaltingDiagnostic::ProcessSharedSyncObjectFreed { name });
+            }
+        }
         if let Some(data_race) = &mut alloc_extra.data_race {
             data_race.write(
                 alloc_id,
@@ -1460,6 +1677,7 @@ impl<'tcx> Machine<'tcx> for MiriMachine<'tcx> {
             is_user_relevant: ecx.machine.is_user_relevant(&frame),
             salt: ecx.machine.rng.borrow_mut().gen::<usize>() % ADDRS_PER_ANON_GLOBAL,
             data_race: ecx.machine.data_race.as_ref().map(|_| data_race::FrameState::default()),
+            check_transmutes: ecx.checks_transmutes_in(frame.instance()),
         };
 
         interp_ok(frame.with_extra(extra))
@@ -1569,6 +1787,9 @@ impl<'tcx> Machine<'tcx> for MiriMachine<'tcx> {
         if let Some(data_race) = &ecx.frame().extra.data_race {
             data_race.local_read(local, &ecx.machine);
         }
+        if ecx.frame().extra.check_transmutes {
+            ecx.check_ptr_int_transmute(local)?;
+        }
         interp_ok(())
     }
 
@@ -1,7 +1,9 @@
 use rand::Rng as _;
 use rand::distributions::Distribution as _;
-use rustc_apfloat::Float as _;
-use rustc_apfloat::ieee::IeeeFloat;
+use rustc_apfloat::ieee::{Half, IeeeFloat, Single};
+use rustc_apfloat::{Float as _, FloatConvert as _};
+
+use crate::helpers::{ToHost, ToSoft};
 
 /// Disturbes a floating-point result by a relative error on the order of (-2^scale, 2^scale).
 pub(crate) fn apply_random_float_error<F: rustc_apfloat::Float>(
@@ -9,6 +11,9 @@ pub(crate) fn apply_random_float_error<F: rustc_apfloat::Float>(
     val: F,
     err_scale: i32,
 ) -> F {
+    if ecx.machine.deterministic_floats {
+        return val;
+    }
     let rng = ecx.machine.rng.get_mut();
     // Generate a random integer in the range [0, 2^PREC).
     let dist = rand::distributions::Uniform::new(0, 1 << F::PRECISION);
@@ -21,6 +26,14 @@ pub(crate) fn apply_random_float_error<F: rustc_apfloat::Float>(
     (val * (F::from_u128(1).value + err).value).value
 }
 
+/// Computes `f` on `x` in single precision, and rounds the result back to half precision. There
+/// are no stable host floats of half precision; this is only used for operations that do not have
+/// guaranteed precision anyway.
+pub(crate) fn f16_via_f32(x: Half, f: impl FnOnce(f32) -> f32) -> Half {
+    let x: Single = x.convert(&mut false).value;
+    f(x.to_host()).to_soft().convert(&mut false).value
+}
+
 pub(crate) fn sqrt<S: rustc_apfloat::ieee::Semantics>(x: IeeeFloat<S>) -> IeeeFloat<S> {
     match x.category() {
         // preserve zero sign
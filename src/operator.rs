@@ -90,11 +90,14 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
 
         let this = self.eval_context_ref();
+        let preferred_nan = F2::qnan(Some(0));
+        if this.machine.deterministic_floats {
+            return preferred_nan;
+        }
         let mut rand = this.machine.rng.borrow_mut();
         // Assemble an iterator of possible NaNs: preferred, quieting propagation, unchanged propagation.
         // On some targets there are more possibilities; for now we just generate those options that
         // are possible everywhere.
-        let preferred_nan = F2::qnan(Some(0));
         let nans = iter::once(preferred_nan)
             .chain(inputs.iter().filter(|f| f.is_nan()).map(|&f| {
                 // Regular apfloat cast is quieting.
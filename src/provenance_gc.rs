@@ -19,7 +19,7 @@ macro_rules! no_provenance {
         )+
     }
 }
-no_provenance!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize ThreadId);
+no_provenance!(bool i8 i16 i32 i64 isize u8 u16 u32 u64 usize String ThreadId);
 
 impl<T: VisitProvenance> VisitProvenance for Option<T> {
     fn visit_provenance(&self, visit: &mut VisitWith<'_>) {
@@ -29,6 +29,14 @@ impl<T: VisitProvenance> VisitProvenance for Option<T> {
     }
 }
 
+impl<T: VisitProvenance> VisitProvenance for Vec<T> {
+    fn visit_provenance(&self, visit: &mut VisitWith<'_>) {
+        for x in self {
+            x.visit_provenance(visit);
+        }
+    }
+}
+
 impl<A, B> VisitProvenance for (A, B)
 where
     A: VisitProvenance,
@@ -46,6 +54,12 @@ impl<T: VisitProvenance> VisitProvenance for std::cell::RefCell<T> {
     }
 }
 
+impl<T: VisitProvenance> VisitProvenance for std::rc::Rc<T> {
+    fn visit_provenance(&self, visit: &mut VisitWith<'_>) {
+        (**self).visit_provenance(visit)
+    }
+}
+
 impl VisitProvenance for BorTag {
     fn visit_provenance(&self, visit: &mut VisitWith<'_>) {
         visit(None, Some(*self))
@@ -184,6 +198,19 @@ impl VisitProvenance for crate::MiriInterpCx<'_> {
     }
 }
 
+/// What the GC did so far, for `-Zmiri-provenance-gc-stats`.
+#[derive(Debug, Default)]
+pub struct GcStats {
+    /// How often the GC ran.
+    pub collections: u64,
+    /// How many of those runs were requested by `miri_run_provenance_gc`.
+    pub requested: u64,
+    /// How many tags were removed from the borrow tracker state of all allocations.
+    pub tags_freed: u64,
+    /// The largest number of tags found to be live by a single run.
+    pub peak_live_tags: usize,
+}
+
 pub struct LiveAllocs<'a, 'tcx> {
     collected: FxHashSet<AllocId>,
     ecx: &'a MiriInterpCx<'tcx>,
@@ -195,15 +222,19 @@ impl LiveAllocs<'_, '_> {
     }
 }
 
-fn remove_unreachable_tags<'tcx>(ecx: &mut MiriInterpCx<'tcx>, tags: FxHashSet<BorTag>) {
+/// Returns the number of removed tags, as counted by the aliasing model.
+fn remove_unreachable_tags<'tcx>(ecx: &mut MiriInterpCx<'tcx>, tags: FxHashSet<BorTag>) -> u64 {
+    let mut removed = 0;
     // Avoid iterating all allocations if there's no borrow tracker anyway.
     if ecx.machine.borrow_tracker.is_some() {
         ecx.memory.alloc_map().iter(|it| {
             for (_id, (_kind, alloc)) in it {
-                alloc.extra.borrow_tracker.as_ref().unwrap().remove_unreachable_tags(&tags);
+                removed +=
+                    alloc.extra.borrow_tracker.as_ref().unwrap().remove_unreachable_tags(&tags);
             }
         });
     }
+    removed
 }
 
 fn remove_unreachable_allocs<'tcx>(ecx: &mut MiriInterpCx<'tcx>, allocs: FxHashSet<AllocId>) {
@@ -236,7 +267,14 @@ pub trait EvalContextExt<'tcx>: MiriInterpCxExt<'tcx> {
         });
 
         // Based on this, clean up the interpreter state.
-        remove_unreachable_tags(this, tags);
+        let live_tags = tags.len();
+        let tags_freed = remove_unreachable_tags(this, tags);
         remove_unreachable_allocs(this, alloc_ids);
+
+        if let Some(stats) = &mut this.machine.gc_stats {
+            stats.collections += 1;
+            stats.tags_freed += tags_freed;
+            stats.peak_live_tags = stats.peak_live_tags.max(live_tags);
+        }
     }
 }
@@ -314,6 +314,27 @@ mod tests {
         assert_eq!(to_vec(&map, 10, 10), vec![23, 42, 23, 23, 23, 19, 19, 19, 19, 19]);
     }
 
+    #[test]
+    fn merge_adjacent() {
+        let mut map = RangeMap::<i32>::new(Size::from_bytes(20), -1);
+        // Fragment the map byte by byte.
+        for i in 0..20 {
+            for (_, x) in map.iter_mut(Size::from_bytes(i), Size::from_bytes(1)) {
+                *x = if i < 10 { 1 } else { 2 };
+            }
+        }
+        for (_, x) in map.iter_mut(Size::from_bytes(5), Size::from_bytes(1)) {
+            *x = 3;
+        }
+        assert!(map.v.len() > 4);
+
+        map.merge_adjacent_thorough();
+        assert_eq!(map.v.len(), 4);
+        assert_eq!(to_vec(&map, 0, 20), vec![
+            1, 1, 1, 1, 1, 3, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2
+        ]);
+    }
+
     #[test]
     #[should_panic]
     fn out_of_range_iter_mut() {
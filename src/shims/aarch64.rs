@@ -0,0 +1,317 @@
+use rustc_abi::{ExternAbi, Size};
+use rustc_middle::mir::BinOp;
+use rustc_span::Symbol;
+
+use crate::*;
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn emulate_aarch64_intrinsic(
+        &mut self,
+        link_name: Symbol,
+        abi: ExternAbi,
+        args: &[OpTy<'tcx>],
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx, EmulateItemResult> {
+        let this = self.eval_context_mut();
+        // Prefix should have already been checked.
+        let unprefixed_name = link_name.as_str().strip_prefix("llvm.aarch64.").unwrap();
+
+        // The NEON intrinsics are overloaded: their names end with the types of the result and
+        // the arguments, like `.v16i8`. We get the types from the layouts instead.
+        let name = match unprefixed_name.match_indices('.').nth(1) {
+            Some((types, _)) => &unprefixed_name[..types],
+            None => unprefixed_name,
+        };
+        match name {
+            "isb" => {
+                let [arg] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                let arg = this.read_scalar(arg)?.to_i32()?;
+                match arg {
+                    // SY ("full system scope")
+                    15 => {
+                        this.yield_active_thread();
+                    }
+                    _ => {
+                        throw_unsup_format!("unsupported llvm.aarch64.isb argument {}", arg);
+                    }
+                }
+            }
+
+            // Used to implement the vqtbl{1,2,3,4}{,q}_{s8,u8,p8} functions.
+            // Looks up each byte of `idx` in the table formed by the concatenation of the
+            // table vectors. Out-of-range indices yield 0.
+            "neon.tbl1" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [t0, idx] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                table_lookup(this, None, &[t0], idx, dest)?;
+            }
+            "neon.tbl2" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [t0, t1, idx] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                table_lookup(this, None, &[t0, t1], idx, dest)?;
+            }
+            "neon.tbl3" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [t0, t1, t2, idx] =
+                    this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                table_lookup(this, None, &[t0, t1, t2], idx, dest)?;
+            }
+            "neon.tbl4" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [t0, t1, t2, t3, idx] =
+                    this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                table_lookup(this, None, &[t0, t1, t2, t3], idx, dest)?;
+            }
+            // Used to implement the vqtbx{1,2,3,4}{,q}_{s8,u8,p8} functions.
+            // Like the `tbl` intrinsics, except that out-of-range indices keep the byte of `a`.
+            "neon.tbx1" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [a, t0, idx] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                table_lookup(this, Some(a), &[t0], idx, dest)?;
+            }
+            "neon.tbx2" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [a, t0, t1, idx] =
+                    this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                table_lookup(this, Some(a), &[t0, t1], idx, dest)?;
+            }
+            "neon.tbx3" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [a, t0, t1, t2, idx] =
+                    this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                table_lookup(this, Some(a), &[t0, t1, t2], idx, dest)?;
+            }
+            "neon.tbx4" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [a, t0, t1, t2, t3, idx] =
+                    this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+                table_lookup(this, Some(a), &[t0, t1, t2, t3], idx, dest)?;
+            }
+
+            // Used to implement the vpadd{,q}, vpmax{,q} and vpmin{,q} functions on integers.
+            // Combines adjacent pairs of elements of the concatenation of `left` and `right`.
+            "neon.addp" | "neon.umaxp" | "neon.uminp" | "neon.smaxp" | "neon.sminp" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [left, right] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let which = match name {
+                    "neon.addp" => BinOp::Add,
+                    "neon.umaxp" | "neon.smaxp" => BinOp::Gt,
+                    "neon.uminp" | "neon.sminp" => BinOp::Lt,
+                    _ => unreachable!(),
+                };
+                let signed = name.starts_with("neon.s");
+
+                let (left, left_len) = this.project_to_simd(left)?;
+                let (right, right_len) = this.project_to_simd(right)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(left_len, right_len);
+                assert_eq!(dest_len, left_len);
+
+                let half = dest_len / 2;
+                for i in 0..dest_len {
+                    let (src, j) = if i < half { (&left, i) } else { (&right, i.strict_sub(half)) };
+                    let a = this.read_immediate(&this.project_index(src, j.strict_mul(2))?)?;
+                    let b = this
+                        .read_immediate(&this.project_index(src, j.strict_mul(2).strict_add(1))?)?;
+                    let res = combine(this, which, signed, &a, &b)?;
+                    this.write_immediate(*res, &this.project_index(&dest, i)?)?;
+                }
+            }
+
+            // Used to implement the vaddv{,q}, vmaxv{,q} and vminv{,q} functions on integers.
+            // Combines all elements of `op`. The result has the width of an element, but it is
+            // returned as a 32-bit integer for elements of 8 and 16 bits.
+            "neon.uaddv" | "neon.saddv" | "neon.umaxv" | "neon.smaxv" | "neon.uminv"
+            | "neon.sminv" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [op] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let which = match name {
+                    "neon.uaddv" | "neon.saddv" => BinOp::Add,
+                    "neon.umaxv" | "neon.smaxv" => BinOp::Gt,
+                    "neon.uminv" | "neon.sminv" => BinOp::Lt,
+                    _ => unreachable!(),
+                };
+                let signed = name.starts_with("neon.s");
+
+                let (op, op_len) = this.project_to_simd(op)?;
+
+                let mut res = this.read_immediate(&this.project_index(&op, 0)?)?;
+                for i in 1..op_len {
+                    let elem = this.read_immediate(&this.project_index(&op, i)?)?;
+                    res = combine(this, which, signed, &res, &elem)?;
+                }
+                let res = this.int_to_int_or_float(&res, dest.layout)?;
+                this.write_immediate(*res, dest)?;
+            }
+            // Used to implement the vaddlv{,q} functions.
+            // Adds all elements of `op` without wrapping around, as the result is wider than
+            // the elements.
+            "neon.uaddlv" | "neon.saddlv" => {
+                this.expect_target_feature_for_intrinsic(link_name, "neon")?;
+                let [op] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (op, op_len) = this.project_to_simd(op)?;
+
+                let mut res = ImmTy::from_int(0, dest.layout);
+                for i in 0..op_len {
+                    let elem = this.read_immediate(&this.project_index(&op, i)?)?;
+                    let elem = this.int_to_int_or_float(&elem, dest.layout)?;
+                    res = this.binary_op(BinOp::Add, &res, &elem)?;
+                }
+                this.write_immediate(*res, dest)?;
+            }
+
+            // Used to implement the vaeseq_u8 function.
+            // Performs AddRoundKey, SubBytes and ShiftRows, that is, the first steps of an AES
+            // encryption round.
+            "crypto.aese" => {
+                this.expect_target_feature_for_intrinsic(link_name, "aes")?;
+                let [state, key] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                aes_op(this, state, Some(key), dest, |state| {
+                    // `aes::hazmat::cipher_round` performs SubBytes, ShiftRows, MixColumns
+                    // and the XOR with the key. Use a zeroed key to skip the XOR, and undo the
+                    // MixColumns with InvMixColumns.
+                    aes::hazmat::cipher_round(state, &aes::Block::from([0; 16]));
+                    aes::hazmat::inv_mix_columns(state);
+                })?;
+            }
+            // Used to implement the vaesdq_u8 function.
+            // Performs AddRoundKey, InvSubBytes and InvShiftRows, that is, the first steps of
+            // an AES decryption round.
+            "crypto.aesd" => {
+                this.expect_target_feature_for_intrinsic(link_name, "aes")?;
+                let [state, key] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                aes_op(this, state, Some(key), dest, |state| {
+                    // `aes::hazmat::equiv_inv_cipher_round` performs InvSubBytes, InvShiftRows,
+                    // InvMixColumns and the XOR with the key. Use a zeroed key to skip the XOR,
+                    // and undo the InvMixColumns with MixColumns.
+                    aes::hazmat::equiv_inv_cipher_round(state, &aes::Block::from([0; 16]));
+                    aes::hazmat::mix_columns(state);
+                })?;
+            }
+            // Used to implement the vaesmcq_u8 function.
+            // Performs the AES MixColumns operation.
+            "crypto.aesmc" => {
+                this.expect_target_feature_for_intrinsic(link_name, "aes")?;
+                let [state] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                aes_op(this, state, None, dest, aes::hazmat::mix_columns)?;
+            }
+            // Used to implement the vaesimcq_u8 function.
+            // Performs the AES InvMixColumns operation.
+            "crypto.aesimc" => {
+                this.expect_target_feature_for_intrinsic(link_name, "aes")?;
+                let [state] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                aes_op(this, state, None, dest, aes::hazmat::inv_mix_columns)?;
+            }
+
+            _ => return interp_ok(EmulateItemResult::NotSupported),
+        }
+        interp_ok(EmulateItemResult::NeedsReturn)
+    }
+}
+
+/// Looks up each byte of `idx` in the concatenation of `tables` and stores the result in
+/// `dest`. Out-of-range indices yield the corresponding byte of `fallback`, or 0 if there is
+/// no `fallback`.
+fn table_lookup<'tcx>(
+    ecx: &mut crate::MiriInterpCx<'tcx>,
+    fallback: Option<&OpTy<'tcx>>,
+    tables: &[&OpTy<'tcx>],
+    idx: &OpTy<'tcx>,
+    dest: &MPlaceTy<'tcx>,
+) -> InterpResult<'tcx, ()> {
+    let (idx, idx_len) = ecx.project_to_simd(idx)?;
+    let (dest, dest_len) = ecx.project_to_simd(dest)?;
+
+    assert_eq!(dest_len, idx_len);
+
+    let mut table = Vec::new();
+    for &t in tables {
+        let (t, t_len) = ecx.project_to_simd(t)?;
+        assert_eq!(t_len, 16);
+        for i in 0..t_len {
+            table.push(ecx.read_scalar(&ecx.project_index(&t, i)?)?.to_u8()?);
+        }
+    }
+
+    for i in 0..dest_len {
+        let idx = ecx.read_scalar(&ecx.project_index(&idx, i)?)?.to_u8()?;
+        let dest = ecx.project_index(&dest, i)?;
+        match table.get(usize::from(idx)) {
+            Some(&byte) => ecx.write_scalar(Scalar::from_u8(byte), &dest)?,
+            None =>
+                match fallback {
+                    Some(fallback) => {
+                        let (fallback, _) = ecx.project_to_simd(fallback)?;
+                        ecx.copy_op(&ecx.project_index(&fallback, i)?, &dest)?;
+                    }
+                    None => ecx.write_scalar(Scalar::from_u8(0), &dest)?,
+                },
+        }
+    }
+
+    interp_ok(())
+}
+
+/// Combines two integers: `Add` adds them with wrap-around, `Gt` returns the larger one and
+/// `Lt` the smaller one, comparing them as signed integers if `signed` is true.
+fn combine<'tcx>(
+    ecx: &crate::MiriInterpCx<'tcx>,
+    which: BinOp,
+    signed: bool,
+    a: &ImmTy<'tcx>,
+    b: &ImmTy<'tcx>,
+) -> InterpResult<'tcx, ImmTy<'tcx>> {
+    if which == BinOp::Add {
+        return ecx.binary_op(BinOp::Add, a, b);
+    }
+    let size = a.layout.size;
+    let ord = if signed {
+        a.to_scalar().to_int(size)?.cmp(&b.to_scalar().to_int(size)?)
+    } else {
+        a.to_scalar().to_uint(size)?.cmp(&b.to_scalar().to_uint(size)?)
+    };
+    let a_wins = match which {
+        BinOp::Gt => ord.is_gt(),
+        BinOp::Lt => ord.is_lt(),
+        _ => unreachable!(),
+    };
+    interp_ok(if a_wins { a.clone() } else { b.clone() })
+}
+
+/// Performs the AES operation `f` on the 128-bit `state`, after XOR-ing it with `key` if
+/// there is one.
+fn aes_op<'tcx>(
+    ecx: &mut crate::MiriInterpCx<'tcx>,
+    state: &OpTy<'tcx>,
+    key: Option<&OpTy<'tcx>>,
+    dest: &MPlaceTy<'tcx>,
+    f: impl Fn(&mut aes::Block),
+) -> InterpResult<'tcx, ()> {
+    assert_eq!(dest.layout.size, Size::from_bytes(16));
+
+    // Transmute to `u128`. The first element of the vector is the first byte of the state.
+    let state = state.transmute(ecx.machine.layouts.u128, ecx)?;
+    let dest = dest.transmute(ecx.machine.layouts.u128, ecx)?;
+
+    let mut state = ecx.read_scalar(&state)?.to_u128()?;
+    if let Some(key) = key {
+        let key = key.transmute(ecx.machine.layouts.u128, ecx)?;
+        state ^= ecx.read_scalar(&key)?.to_u128()?;
+    }
+
+    let mut state = aes::Block::from(state.to_le_bytes());
+    f(&mut state);
+
+    ecx.write_scalar(Scalar::from_u128(u128::from_le_bytes(state.into())), &dest)?;
+
+    interp_ok(())
+}
@@ -0,0 +1,60 @@
+//! The rounding mode of the floating-point environment, as exposed by `fegetround` and
+//! `fesetround`.
+//!
+//! Rust assumes the default floating-point environment, so the float operations of Rust code
+//! always round to nearest, and the interpreter has no way to make them round differently. The
+//! default rounding mode is therefore the only one that can be set: code that relies on directed
+//! rounding stops with an unsupported operation instead of silently getting results that are
+//! rounded to nearest.
+
+use rustc_apfloat::Round;
+
+use crate::*;
+
+/// The values of the `FE_TONEAREST`, `FE_DOWNWARD`, `FE_UPWARD` and `FE_TOWARDZERO` macros of
+/// the target, with the rounding modes they stand for.
+fn rounding_modes<'tcx>(ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, [(i32, Round); 4]> {
+    let target = &ecx.tcx.sess.target;
+    let [to_nearest, downward, upward, toward_zero] = match target.arch.as_ref() {
+        _ if target.os == "windows" => [0, 0x100, 0x200, 0x300],
+        "x86" | "x86_64" => [0, 0x400, 0x800, 0xc00],
+        "arm" | "aarch64" => [0, 0x800000, 0x400000, 0xc00000],
+        "riscv32" | "riscv64" => [0, 2, 3, 1],
+        "mips" | "mips64" | "powerpc" | "powerpc64" => [0, 3, 2, 1],
+        arch => throw_unsup_format!("the floating-point rounding mode is not supported on {arch}"),
+    };
+    interp_ok([
+        (to_nearest, Round::NearestTiesToEven),
+        (downward, Round::TowardNegative),
+        (upward, Round::TowardPositive),
+        (toward_zero, Round::TowardZero),
+    ])
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn fegetround(&self) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_ref();
+
+        let [(to_nearest, _), ..] = rounding_modes(this)?;
+        interp_ok(Scalar::from_i32(to_nearest))
+    }
+
+    fn fesetround(&mut self, mode_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let mode = this.read_scalar(mode_op)?.to_i32()?;
+        let Some((_, round)) =
+            rounding_modes(this)?.into_iter().find(|&(constant, _)| constant == mode)
+        else {
+            // Not one of the `FE_*` macros: the rounding mode is left unchanged.
+            return interp_ok(Scalar::from_i32(-1));
+        };
+        if round != Round::NearestTiesToEven {
+            throw_unsup_format!(
+                "`fesetround` only supports `FE_TONEAREST`, since Rust float operations always round to nearest"
+            );
+        }
+        interp_ok(Scalar::from_i32(0))
+    }
+}
@@ -1,6 +1,6 @@
 use std::any::Any;
-use std::collections::BTreeMap;
-use std::io::{IsTerminal, Read, SeekFrom, Write};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{IsTerminal, Read, Seek, SeekFrom, Write};
 use std::ops::Deref;
 use std::rc::{Rc, Weak};
 use std::{fs, io};
@@ -10,6 +10,10 @@ use rustc_abi::Size;
 use crate::shims::unix::UnixFileDescription;
 use crate::*;
 
+/// The maximum number of `iovec` in one call to `readv`, `writev`, `sendmsg` or `recvmsg`
+/// (`IOV_MAX`).
+pub const MAX_IOVECS: u64 = 1024;
+
 /// Represents an open file description.
 pub trait FileDescription: std::fmt::Debug + Any {
     fn name(&self) -> &'static str;
@@ -44,6 +48,40 @@ pub trait FileDescription: std::fmt::Debug + Any {
         throw_unsup_format!("cannot write to {}", self.name());
     }
 
+    /// Reads as much as possible into the buffers described by `iov`, filling them in order.
+    /// `dest` is where the return value should be stored: number of bytes read, or `-1` in case of error.
+    /// By default, this only reads into the first non-empty buffer, which is a valid short read.
+    fn readv<'tcx>(
+        &self,
+        self_ref: &FileDescriptionRef,
+        communicate_allowed: bool,
+        iov: &[(Pointer, u64)],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let Some(&(ptr, len)) = iov.iter().find(|(_, len)| *len > 0) else {
+            return ecx.return_readv_success(iov, &[], dest);
+        };
+        self.read(self_ref, communicate_allowed, ptr, usize::try_from(len).unwrap(), dest, ecx)
+    }
+
+    /// Writes as much as possible from the buffers described by `iov`, in order.
+    /// `dest` is where the return value should be stored: number of bytes written, or `-1` in case of error.
+    /// By default, this only writes from the first non-empty buffer, which is a valid short write.
+    fn writev<'tcx>(
+        &self,
+        self_ref: &FileDescriptionRef,
+        communicate_allowed: bool,
+        iov: &[(Pointer, u64)],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let Some(&(ptr, len)) = iov.iter().find(|(_, len)| *len > 0) else {
+            return ecx.return_write_success(0, dest);
+        };
+        self.write(self_ref, communicate_allowed, ptr, usize::try_from(len).unwrap(), dest, ecx)
+    }
+
     /// Seeks to the given offset (which can be relative to the beginning, end, or current position).
     /// Returns the new position from the start of the stream.
     fn seek<'tcx>(
@@ -84,6 +122,141 @@ impl dyn FileDescription {
     }
 }
 
+/// A file on the host file system.
+#[derive(Debug)]
+pub struct FileHandle {
+    pub(crate) file: fs::File,
+    pub(crate) writable: bool,
+}
+
+impl FileDescription for FileHandle {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn read<'tcx>(
+        &self,
+        _self_ref: &FileDescriptionRef,
+        communicate_allowed: bool,
+        ptr: Pointer,
+        len: usize,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        ecx.check_blocking_in_async("reading from a file");
+        let mut bytes = vec![0; len];
+        let result = (&mut &self.file).read(&mut bytes);
+        match result {
+            Ok(read_size) => ecx.return_read_success(ptr, &bytes, read_size, dest),
+            Err(e) => ecx.set_last_error_and_return(e, dest),
+        }
+    }
+
+    fn write<'tcx>(
+        &self,
+        _self_ref: &FileDescriptionRef,
+        communicate_allowed: bool,
+        ptr: Pointer,
+        len: usize,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        ecx.check_blocking_in_async("writing to a file");
+        let bytes = ecx.read_bytes_ptr_strip_provenance(ptr, Size::from_bytes(len))?;
+        let result = (&mut &self.file).write(bytes);
+        match result {
+            Ok(write_size) => ecx.return_write_success(write_size, dest),
+            Err(e) => ecx.set_last_error_and_return(e, dest),
+        }
+    }
+
+    fn readv<'tcx>(
+        &self,
+        _self_ref: &FileDescriptionRef,
+        communicate_allowed: bool,
+        iov: &[(Pointer, u64)],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        ecx.check_blocking_in_async("reading from a file");
+        let len: u64 = iov.iter().map(|(_, len)| len).sum();
+        let mut bytes = vec![0; usize::try_from(len).unwrap()];
+        let result = (&mut &self.file).read(&mut bytes);
+        match result {
+            Ok(read_size) => ecx.return_readv_success(iov, &bytes[..read_size], dest),
+            Err(e) => ecx.set_last_error_and_return(e, dest),
+        }
+    }
+
+    fn writev<'tcx>(
+        &self,
+        _self_ref: &FileDescriptionRef,
+        communicate_allowed: bool,
+        iov: &[(Pointer, u64)],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        ecx.check_blocking_in_async("writing to a file");
+        let bytes = ecx.gather_iov(iov)?;
+        let result = (&mut &self.file).write(&bytes);
+        match result {
+            Ok(write_size) => ecx.return_write_success(write_size, dest),
+            Err(e) => ecx.set_last_error_and_return(e, dest),
+        }
+    }
+
+    fn seek<'tcx>(
+        &self,
+        communicate_allowed: bool,
+        offset: SeekFrom,
+    ) -> InterpResult<'tcx, io::Result<u64>> {
+        assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        interp_ok((&mut &self.file).seek(offset))
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        communicate_allowed: bool,
+        _ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        assert!(communicate_allowed, "isolation should have prevented even opening a file");
+        // We sync the file if it was opened in a mode different than read-only.
+        if self.writable {
+            // `File::sync_all` does the checks that are done when closing a file. We do this to
+            // to handle possible errors correctly.
+            let result = self.file.sync_all();
+            // Now we actually close the file and return the result.
+            drop(*self);
+            interp_ok(result)
+        } else {
+            // We drop the file, this closes it but ignores any errors
+            // produced when closing it. This is done because
+            // `File::sync_all` cannot be done over files like
+            // `/dev/urandom` which are read-only. Check
+            // https://github.com/rust-lang/miri/issues/999#issuecomment-568920439
+            // for a deeper discussion.
+            drop(*self);
+            interp_ok(Ok(()))
+        }
+    }
+
+    fn metadata<'tcx>(&self) -> InterpResult<'tcx, io::Result<fs::Metadata>> {
+        interp_ok(self.file.metadata())
+    }
+
+    fn is_tty(&self, communicate_allowed: bool) -> bool {
+        communicate_allowed && self.file.is_terminal()
+    }
+
+    fn as_unix(&self) -> &dyn UnixFileDescription {
+        self
+    }
+}
+
 impl FileDescription for io::Stdin {
     fn name(&self) -> &'static str {
         "stdin"
@@ -201,6 +374,48 @@ impl FileDescription for NullOutput {
     }
 }
 
+/// `/dev/null` itself: reads return EOF, and writes are discarded.
+#[derive(Debug)]
+pub struct DevNull;
+
+impl FileDescription for DevNull {
+    fn name(&self) -> &'static str {
+        "/dev/null"
+    }
+
+    fn read<'tcx>(
+        &self,
+        _self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        ptr: Pointer,
+        _len: usize,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        ecx.return_read_success(ptr, &[], 0, dest)
+    }
+
+    fn write<'tcx>(
+        &self,
+        _self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        _ptr: Pointer,
+        len: usize,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        ecx.return_write_success(len, dest)
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        _ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        interp_ok(Ok(()))
+    }
+}
+
 /// Structure contains both the file description and its unique identifier.
 #[derive(Clone, Debug)]
 pub struct FileDescWithId<T: FileDescription + ?Sized> {
@@ -234,10 +449,22 @@ impl FileDescriptionRef {
         let id = self.get_id();
         match Rc::into_inner(self.0) {
             Some(fd) => {
-                // Remove entry from the global epoll_event_interest table.
+                // Remove entry from the global epoll_event_interest and kqueue interest tables,
+                // and its association with a completion port.
                 ecx.machine.epoll_interests.remove(id);
-
-                fd.file_description.close(communicate_allowed, ecx)
+                ecx.machine.kqueue_interests.remove(id);
+                ecx.machine.readiness_clocks.remove(&id);
+                ecx.machine.completion_ports.remove(id);
+                ecx.machine.console_input.remove(&id);
+                let delete_pending = ecx.machine.open_files.remove(id);
+
+                let result = fd.file_description.close(communicate_allowed, ecx)?;
+                // A file whose deletion is pending gets deleted with its last handle. Like on
+                // Windows, closing the handle does not report whether that worked.
+                if let Some(path) = delete_pending {
+                    let _ = std::fs::remove_file(path);
+                }
+                interp_ok(result)
             }
             None => interp_ok(Ok(())),
         }
@@ -286,6 +513,10 @@ pub struct FdId(usize);
 #[derive(Debug)]
 pub struct FdTable {
     pub fds: BTreeMap<i32, FileDescriptionRef>,
+    /// The file descriptors that have the close-on-exec flag (`FD_CLOEXEC`) set.
+    /// This flag belongs to the file descriptor, not to the (possibly shared) file description,
+    /// so `dup`licates do not inherit it.
+    cloexec: BTreeSet<i32>,
     /// Unique identifier for file description, used to differentiate between various file description.
     next_file_description_id: FdId,
 }
@@ -298,7 +529,11 @@ impl VisitProvenance for FdTable {
 
 impl FdTable {
     fn new() -> Self {
-        FdTable { fds: BTreeMap::new(), next_file_description_id: FdId(0) }
+        FdTable {
+            fds: BTreeMap::new(),
+            cloexec: BTreeSet::new(),
+            next_file_description_id: FdId(0),
+        }
     }
     pub(crate) fn init(mute_stdout_stderr: bool) -> FdTable {
         let mut fds = FdTable::new();
@@ -362,9 +597,35 @@ impl FdTable {
     }
 
     pub fn remove(&mut self, fd_num: i32) -> Option<FileDescriptionRef> {
+        self.cloexec.remove(&fd_num);
         self.fds.remove(&fd_num)
     }
 
+    /// Make `fd_num` refer to `file_handle`, returning the file description it previously referred
+    /// to (if any). The close-on-exec flag of `fd_num` is cleared.
+    pub fn replace(
+        &mut self,
+        fd_num: i32,
+        file_handle: FileDescriptionRef,
+    ) -> Option<FileDescriptionRef> {
+        self.cloexec.remove(&fd_num);
+        self.fds.insert(fd_num, file_handle)
+    }
+
+    /// Set or clear the close-on-exec flag of an open file descriptor.
+    pub fn set_cloexec(&mut self, fd_num: i32, cloexec: bool) {
+        assert!(self.is_fd_num(fd_num));
+        if cloexec {
+            self.cloexec.insert(fd_num);
+        } else {
+            self.cloexec.remove(&fd_num);
+        }
+    }
+
+    pub fn is_cloexec(&self, fd_num: i32) -> bool {
+        self.cloexec.contains(&fd_num)
+    }
+
     pub fn is_fd_num(&self, fd_num: i32) -> bool {
         self.fds.contains_key(&fd_num)
     }
@@ -396,6 +657,22 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(())
     }
 
+    /// Helper to implement `FileDescription::readv`:
+    /// This is only used when `readv` is successful.
+    /// `bytes` are the bytes that were actually read; they are scattered over the buffers
+    /// described by `iov` (which must be large enough), and their number is written to `dest`.
+    fn return_readv_success(
+        &mut self,
+        iov: &[(Pointer, u64)],
+        bytes: &[u8],
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        this.scatter_iov(iov, bytes)?;
+        this.write_int(u64::try_from(bytes.len()).unwrap(), dest)?;
+        interp_ok(())
+    }
+
     /// Helper to implement `FileDescription::write`:
     /// This function is only used when `write` is successful, and writes `actual_write_size` to `dest`
     fn return_write_success(
@@ -408,4 +685,57 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         this.write_int(u64::try_from(actual_write_size).unwrap(), dest)?;
         interp_ok(())
     }
+
+    /// Reads the `iovcnt` `iovec`s at `iov_ptr`, as used by `readv`, `writev`, `sendmsg` and
+    /// `recvmsg`. Returns the buffers as pointer and length, or EINVAL if their total length
+    /// does not fit into an `ssize_t`.
+    fn read_iovecs(
+        &self,
+        iov_ptr: Pointer,
+        iovcnt: u64,
+    ) -> InterpResult<'tcx, Result<Vec<(Pointer, u64)>, IoError>> {
+        let this = self.eval_context_ref();
+        let iovec_layout = this.libc_ty_layout("iovec");
+        let mut iov = Vec::new();
+        let mut total_len: u64 = 0;
+        for i in 0..iovcnt {
+            let iovec = this
+                .ptr_to_mplace(iov_ptr.wrapping_offset(iovec_layout.size * i, this), iovec_layout);
+            let base = this.read_pointer(&this.project_field_named(&iovec, "iov_base")?)?;
+            let len = this.read_target_usize(&this.project_field_named(&iovec, "iov_len")?)?;
+            // The total length must fit into an `ssize_t`.
+            total_len = total_len.saturating_add(len);
+            if total_len > u64::try_from(this.target_isize_max()).unwrap() {
+                return interp_ok(Err(LibcError("EINVAL")));
+            }
+            iov.push((base, len));
+        }
+        interp_ok(Ok(iov))
+    }
+
+    /// Gathers the data from the buffers described by `iov`.
+    fn gather_iov(&self, iov: &[(Pointer, u64)]) -> InterpResult<'tcx, Vec<u8>> {
+        let this = self.eval_context_ref();
+        let mut bytes = Vec::new();
+        for &(ptr, len) in iov {
+            bytes.extend_from_slice(
+                this.read_bytes_ptr_strip_provenance(ptr, Size::from_bytes(len))?,
+            );
+        }
+        interp_ok(bytes)
+    }
+
+    /// Scatters `bytes` into the buffers described by `iov`, as far as they fit.
+    fn scatter_iov(&mut self, iov: &[(Pointer, u64)], mut bytes: &[u8]) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        for &(ptr, len) in iov {
+            if bytes.is_empty() {
+                break;
+            }
+            let (chunk, rest) = bytes.split_at(usize::try_from(len).unwrap().min(bytes.len()));
+            this.write_bytes_ptr(ptr, chunk.iter().copied())?;
+            bytes = rest;
+        }
+        interp_ok(())
+    }
 }
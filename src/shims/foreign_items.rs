@@ -47,7 +47,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     ) -> InterpResult<'tcx, Option<(&'tcx mir::Body<'tcx>, ty::Instance<'tcx>)>> {
         let this = self.eval_context_mut();
 
-        // Some shims forward to other MIR bodies.
+        // Some shims forward to other MIR bodies, or need to know where to return to.
         match link_name.as_str() {
             "__rust_alloc_error_handler" => {
                 // Forward to the right symbol that implements this function.
@@ -63,6 +63,15 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     .expect("missing alloc error handler symbol");
                 return interp_ok(Some(handler));
             }
+            // The counterpart of `_pthread_cleanup_push`, which is what the `pthread_cleanup_pop`
+            // macro calls in musl. Running the handler needs to know where to return to.
+            "_pthread_cleanup_pop" if this.tcx.sess.target.os == "linux" => {
+                let [_buffer, execute] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let execute = this.read_scalar(execute)?.to_i32()?;
+                this.pop_cleanup_handler(execute != 0, ret)?;
+                return interp_ok(None);
+            }
             _ => {}
         }
 
@@ -281,8 +290,19 @@ trait EvalContextExtPriv<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.handle_miri_start_unwind(payload)?;
                 return interp_ok(EmulateItemResult::NeedsUnwind);
             }
+            "miri_yield_hint" => {
+                let [] = this.check_shim(abi, ExternAbi::Rust, link_name, args)?;
+                this.preemption_hint();
+            }
+            "miri_preempt_here" => {
+                let [] = this.check_shim(abi, ExternAbi::Rust, link_name, args)?;
+                this.yield_active_thread();
+            }
             "miri_run_provenance_gc" => {
                 let [] = this.check_shim(abi, ExternAbi::Rust, link_name, args)?;
+                if let Some(stats) = &mut this.machine.gc_stats {
+                    stats.requested += 1;
+                }
                 this.run_provenance_gc();
             }
             "miri_get_alloc_id" => {
@@ -307,6 +327,40 @@ trait EvalContextExtPriv<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     eprintln!("{id} is not the ID of a live data allocation");
                 }
             }
+            "miri_dump_borrow_tree" => {
+                let [ptr, path] = this.check_shim(abi, ExternAbi::Rust, link_name, args)?;
+                let ptr = this.read_pointer(ptr)?;
+                let path = this.read_pointer(path)?;
+                let (alloc_id, _, _) = this.ptr_get_alloc_id(ptr, 0).map_err_kind(|_e| {
+                    err_machine_stop!(TerminationInfo::Abort(format!(
+                        "pointer passed to `miri_dump_borrow_tree` must not be dangling, got {ptr:?}"
+                    )))
+                })?;
+                // This is a host path: the file is written by Miri, not by the program.
+                let path = this.read_os_str_from_c_str(path)?.to_owned();
+                this.dump_borrow_state_dot(alloc_id, Path::new(&path))?;
+            }
+            "miri_snapshot_borrows" => {
+                let [ptr] = this.check_shim(abi, ExternAbi::Rust, link_name, args)?;
+                let ptr = this.read_pointer(ptr)?;
+                let (alloc_id, _, _) = this.ptr_get_alloc_id(ptr, 0).map_err_kind(|_e| {
+                    err_machine_stop!(TerminationInfo::Abort(format!(
+                        "pointer passed to `miri_snapshot_borrows` must not be dangling, got {ptr:?}"
+                    )))
+                })?;
+                let id = this.snapshot_borrow_state(alloc_id)?;
+                this.write_scalar(Scalar::from_u64(id), dest)?;
+            }
+            "miri_diff_borrows" => {
+                let [snapshot] = this.check_shim(abi, ExternAbi::Rust, link_name, args)?;
+                let snapshot = this.read_scalar(snapshot)?.to_u64()?;
+                this.diff_borrow_state(snapshot)?;
+            }
+            "miri_print_store_buffers" => {
+                let [ptr] = this.check_shim(abi, ExternAbi::Rust, link_name, args)?;
+                let ptr = this.read_pointer(ptr)?;
+                this.print_store_buffers(ptr)?;
+            }
             "miri_pointer_name" => {
                 // This associates a name to a tag. Very useful for debugging, and also makes
                 // tests more strict.
@@ -899,6 +953,123 @@ trait EvalContextExtPriv<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let res = this.adjust_nan(res.to_soft(), &[x]);
                 this.write_scalar(res, dest)?;
             }
+            "sqrtf128" => {
+                let [f] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let f = this.read_scalar(f)?.to_f128()?;
+
+                let res = this.adjust_nan(math::sqrt(f), &[f]);
+                this.write_scalar(res, dest)?;
+            }
+            "fmaf128" => {
+                let [a, b, c] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let a = this.read_scalar(a)?.to_f128()?;
+                let b = this.read_scalar(b)?.to_f128()?;
+                let c = this.read_scalar(c)?.to_f128()?;
+
+                // There are no stable host floats of this type, so we use apfloat.
+                let res = a.mul_add(b, c).value;
+                let res = this.adjust_nan(res, &[a, b, c]);
+                this.write_scalar(res, dest)?;
+            }
+
+            // Soft-float routines for `f128` of compiler-builtins and libgcc, as called by C code.
+            "__addtf3" | "__subtf3" | "__multf3" | "__divtf3" => {
+                let [a, b] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let a = this.read_immediate(a)?;
+                let b = this.read_immediate(b)?;
+
+                let op = match link_name.as_str() {
+                    "__addtf3" => mir::BinOp::Add,
+                    "__subtf3" => mir::BinOp::Sub,
+                    "__multf3" => mir::BinOp::Mul,
+                    "__divtf3" => mir::BinOp::Div,
+                    _ => bug!(),
+                };
+                // `binary_op` already calls `generate_nan` if necessary.
+                let res = this.binary_op(op, &a, &b)?;
+                this.write_immediate(*res, dest)?;
+            }
+            #[rustfmt::skip]
+            | "__eqtf2"
+            | "__netf2"
+            | "__lttf2"
+            | "__letf2"
+            | "__cmptf2"
+            | "__gttf2"
+            | "__getf2"
+            | "__unordtf2"
+            => {
+                let [a, b] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let a = this.read_scalar(a)?.to_f128()?;
+                let b = this.read_scalar(b)?.to_f128()?;
+
+                // The ordered comparisons return -1, 0 or 1 depending on how `a` compares to `b`.
+                // If the operands are unordered, they return a value for which the comparison
+                // they implement is false.
+                let res = match (link_name.as_str(), a.partial_cmp(&b)) {
+                    ("__unordtf2", ordering) => i32::from(ordering.is_none()),
+                    (_, Some(ordering)) => ordering as i32,
+                    ("__gttf2" | "__getf2", None) => -1,
+                    (_, None) => 1,
+                };
+                this.write_int(res, dest)?;
+            }
+            "__extendsftf2" | "__extenddftf2" | "__trunctfsf2" | "__trunctfdf2" => {
+                let [f] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let f = this.read_immediate(f)?;
+
+                let res = this.float_to_float_or_int(&f, dest.layout)?;
+                this.write_immediate(*res, dest)?;
+            }
+
+            // These round to an integer in the current rounding mode, which is always the default
+            // one (see `shims::fenv`).
+            "rintf" | "nearbyintf" => {
+                let [f] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let f = this.read_scalar(f)?.to_f32()?;
+
+                let res = f.round_to_integral(rustc_apfloat::Round::NearestTiesToEven).value;
+                let res = this.adjust_nan(res, &[f]);
+                this.write_scalar(res, dest)?;
+            }
+            "rint" | "nearbyint" => {
+                let [f] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let f = this.read_scalar(f)?.to_f64()?;
+
+                let res = f.round_to_integral(rustc_apfloat::Round::NearestTiesToEven).value;
+                let res = this.adjust_nan(res, &[f]);
+                this.write_scalar(res, dest)?;
+            }
+            "lrintf" | "llrintf" | "lrint" | "llrint" => {
+                let [f] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let f = this.read_immediate(f)?;
+
+                let size = dest.layout.size;
+                let res = match this.float_to_int_checked(
+                    &f,
+                    dest.layout,
+                    rustc_apfloat::Round::NearestTiesToEven,
+                )? {
+                    Some(res) => res.to_scalar(),
+                    // The result is unspecified if it does not fit. Like x86, return the
+                    // smallest integer.
+                    None => Scalar::from_int(size.signed_int_min(), size),
+                };
+                this.write_scalar(res, dest)?;
+            }
+            "fegetround" => {
+                let [] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.fegetround()?;
+                this.write_scalar(res, dest)?;
+            }
+            "fesetround" => {
+                let [mode] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.fesetround(mode)?;
+                this.write_scalar(res, dest)?;
+            }
 
             // LLVM intrinsics
             "llvm.prefetch" => {
@@ -950,6 +1121,13 @@ trait EvalContextExtPriv<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 }
             }
 
+            // Out-of-line atomic operations of libatomic
+            name if name.starts_with("__atomic_") => {
+                return shims::libatomic::EvalContextExt::emulate_libatomic(
+                    this, link_name, abi, args, dest,
+                );
+            }
+
             // Target-specific shims
             name if name.starts_with("llvm.x86.")
                 && (this.tcx.sess.target.arch == "x86"
@@ -959,20 +1137,20 @@ trait EvalContextExtPriv<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     this, link_name, abi, args, dest,
                 );
             }
-            // FIXME: Move these to an `arm` submodule.
-            "llvm.aarch64.isb" if this.tcx.sess.target.arch == "aarch64" => {
-                let [arg] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
-                let arg = this.read_scalar(arg)?.to_i32()?;
-                match arg {
-                    // SY ("full system scope")
-                    15 => {
-                        this.yield_active_thread();
-                    }
-                    _ => {
-                        throw_unsup_format!("unsupported llvm.aarch64.isb argument {}", arg);
-                    }
-                }
+            name if name.starts_with("llvm.wasm.")
+                && (this.tcx.sess.target.arch == "wasm32"
+                    || this.tcx.sess.target.arch == "wasm64") =>
+            {
+                return shims::wasm::EvalContextExt::emulate_wasm_intrinsic(
+                    this, link_name, abi, args, dest,
+                );
+            }
+            name if name.starts_with("llvm.aarch64.") && this.tcx.sess.target.arch == "aarch64" => {
+                return shims::aarch64::EvalContextExt::emulate_aarch64_intrinsic(
+                    this, link_name, abi, args, dest,
+                );
             }
+            // FIXME: Move this to an `arm` submodule.
             "llvm.arm.hint" if this.tcx.sess.target.arch == "arm" => {
                 let [arg] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
                 let arg = this.read_scalar(arg)?.to_i32()?;
@@ -0,0 +1,235 @@
+//! Shims for the out-of-line atomic operations of libatomic (`__atomic_<op>_<size>`), which C code
+//! calls for atomic accesses that the compiler does not inline, and which Rust code can declare
+//! directly. Their memory order arguments are values of the C11/C++11 `memory_order` enum.
+
+use std::cell::RefCell;
+
+use rustc_abi::ExternAbi;
+use rustc_data_structures::fx::FxHashSet;
+use rustc_middle::mir;
+use rustc_span::{Span, Symbol};
+
+use crate::*;
+
+/// A C11/C++11 `memory_order`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum MemoryOrder {
+    Relaxed,
+    Consume,
+    Acquire,
+    Release,
+    AcqRel,
+    SeqCst,
+}
+
+impl MemoryOrder {
+    fn from_c(ord: i32) -> Option<Self> {
+        Some(match ord {
+            0 => MemoryOrder::Relaxed,
+            1 => MemoryOrder::Consume,
+            2 => MemoryOrder::Acquire,
+            3 => MemoryOrder::Release,
+            4 => MemoryOrder::AcqRel,
+            5 => MemoryOrder::SeqCst,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            MemoryOrder::Relaxed => "memory_order_relaxed",
+            MemoryOrder::Consume => "memory_order_consume",
+            MemoryOrder::Acquire => "memory_order_acquire",
+            MemoryOrder::Release => "memory_order_release",
+            MemoryOrder::AcqRel => "memory_order_acq_rel",
+            MemoryOrder::SeqCst => "memory_order_seq_cst",
+        }
+    }
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn emulate_libatomic(
+        &mut self,
+        link_name: Symbol,
+        abi: ExternAbi,
+        args: &[OpTy<'tcx>],
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx, EmulateItemResult> {
+        let this = self.eval_context_mut();
+        // Prefix should have already been checked.
+        let unprefixed_name = link_name.as_str().strip_prefix("__atomic_").unwrap();
+        let Some((op, size)) = unprefixed_name.rsplit_once('_') else {
+            return interp_ok(EmulateItemResult::NotSupported);
+        };
+        let layout = match size {
+            "1" => this.machine.layouts.u8,
+            "2" => this.machine.layouts.u16,
+            "4" => this.machine.layouts.u32,
+            "8" => this.machine.layouts.u64,
+            _ => return interp_ok(EmulateItemResult::NotSupported),
+        };
+
+        match op {
+            "load" => {
+                let [ptr, ord] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let place = this.deref_pointer_as(ptr, layout)?;
+                let ord = this.read_memory_order_for_load(link_name, ord)?;
+                let val = this.read_scalar_atomic(&place, ord)?;
+                this.write_scalar(val, dest)?;
+            }
+            "store" => {
+                let [ptr, val, ord] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let place = this.deref_pointer_as(ptr, layout)?;
+                let val = this.read_scalar(val)?;
+                let ord = match this.read_memory_order(link_name, ord)? {
+                    MemoryOrder::Relaxed => AtomicWriteOrd::Relaxed,
+                    MemoryOrder::Release => AtomicWriteOrd::Release,
+                    MemoryOrder::SeqCst => AtomicWriteOrd::SeqCst,
+                    ord =>
+                        throw_ub_format!("`{link_name}` cannot use `{}` for a store", ord.name()),
+                };
+                this.write_scalar_atomic(val, &place, ord)?;
+            }
+            "exchange" => {
+                let [ptr, val, ord] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let place = this.deref_pointer_as(ptr, layout)?;
+                let val = this.read_scalar(val)?;
+                let ord = this.read_memory_order_for_rmw(link_name, ord)?;
+                let old = this.atomic_exchange_scalar(&place, val, ord)?;
+                this.write_scalar(old, dest)?;
+            }
+            "compare_exchange" => {
+                let [ptr, expected, desired, success, failure] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let place = this.deref_pointer_as(ptr, layout)?;
+                let expected = this.deref_pointer_as(expected, layout)?;
+                let desired = this.read_scalar(desired)?;
+                let success = this.read_memory_order_for_rmw(link_name, success)?;
+                let failure = this.read_memory_order_for_load(link_name, failure)?;
+                let expect_old = this.read_immediate(&expected)?;
+                let (old, ok) = this
+                    .atomic_compare_exchange_scalar(
+                        &place,
+                        &expect_old,
+                        desired,
+                        success,
+                        failure,
+                        /* can_fail_spuriously */ false,
+                    )?
+                    .to_scalar_pair();
+                // On failure, the current value is stored in `expected`.
+                if !ok.to_bool()? {
+                    this.write_scalar(old, &expected)?;
+                }
+                this.write_scalar(ok, dest)?;
+            }
+            _ => {
+                // The read-modify-write operations that return the old value (`fetch_<op>`) or
+                // the new value (`<op>_fetch`).
+                let (bin_op, fetch_first) = match op.strip_prefix("fetch_") {
+                    Some(bin_op) => (bin_op, true),
+                    None =>
+                        match op.strip_suffix("_fetch") {
+                            Some(bin_op) => (bin_op, false),
+                            None => return interp_ok(EmulateItemResult::NotSupported),
+                        },
+                };
+                let (bin_op, not) = match bin_op {
+                    "add" => (mir::BinOp::Add, false),
+                    "sub" => (mir::BinOp::Sub, false),
+                    "and" => (mir::BinOp::BitAnd, false),
+                    "or" => (mir::BinOp::BitOr, false),
+                    "xor" => (mir::BinOp::BitXor, false),
+                    "nand" => (mir::BinOp::BitAnd, true),
+                    _ => return interp_ok(EmulateItemResult::NotSupported),
+                };
+                let [ptr, val, ord] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let place = this.deref_pointer_as(ptr, layout)?;
+                // Compute with the unsigned type of the place, no matter how the program declared
+                // the argument.
+                let val = ImmTy::from_scalar(this.read_scalar(val)?, layout);
+                let ord = this.read_memory_order_for_rmw(link_name, ord)?;
+                let old = this.atomic_rmw_op_immediate(&place, &val, bin_op, not, ord)?;
+                let res = if fetch_first {
+                    old
+                } else {
+                    let new = this.binary_op(bin_op, &old, &val)?;
+                    if not { this.unary_op(mir::UnOp::Not, &new)? } else { new }
+                };
+                this.write_scalar(res.to_scalar(), dest)?;
+            }
+        }
+
+        interp_ok(EmulateItemResult::NeedsReturn)
+    }
+}
+
+impl<'tcx> EvalContextPrivExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn read_memory_order(
+        &self,
+        link_name: Symbol,
+        op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, MemoryOrder> {
+        let this = self.eval_context_ref();
+        let ord = this.read_scalar(op)?.to_i32()?;
+        let Some(ord) = MemoryOrder::from_c(ord) else {
+            throw_ub_format!("invalid memory order {ord} passed to `{link_name}`");
+        };
+        if ord == MemoryOrder::Consume {
+            // The first time this happens at a particular location, print a note.
+            thread_local! {
+                // `Span` is non-`Send`, so we use a thread-local instead.
+                static PAST_NOTES: RefCell<FxHashSet<Span>> = RefCell::default();
+            }
+            PAST_NOTES.with_borrow_mut(|past_notes| {
+                let first = past_notes.is_empty();
+                if past_notes.insert(this.cur_span()) {
+                    this.emit_diagnostic(NonHaltingDiagnostic::ConsumeOrdering {
+                        link_name,
+                        details: first,
+                    });
+                }
+            });
+        }
+        interp_ok(ord)
+    }
+
+    /// Read the memory order of a load. Like all compilers, we treat `memory_order_consume` as
+    /// `memory_order_acquire`.
+    fn read_memory_order_for_load(
+        &self,
+        link_name: Symbol,
+        op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, AtomicReadOrd> {
+        let this = self.eval_context_ref();
+        interp_ok(match this.read_memory_order(link_name, op)? {
+            MemoryOrder::Relaxed => AtomicReadOrd::Relaxed,
+            MemoryOrder::Consume | MemoryOrder::Acquire => AtomicReadOrd::Acquire,
+            MemoryOrder::SeqCst => AtomicReadOrd::SeqCst,
+            ord => throw_ub_format!("`{link_name}` cannot use `{}` for a load", ord.name()),
+        })
+    }
+
+    /// Read the memory order of a read-modify-write operation. `memory_order_consume` is treated
+    /// as `memory_order_acquire`.
+    fn read_memory_order_for_rmw(
+        &self,
+        link_name: Symbol,
+        op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, AtomicRwOrd> {
+        let this = self.eval_context_ref();
+        interp_ok(match this.read_memory_order(link_name, op)? {
+            MemoryOrder::Relaxed => AtomicRwOrd::Relaxed,
+            MemoryOrder::Consume | MemoryOrder::Acquire => AtomicRwOrd::Acquire,
+            MemoryOrder::Release => AtomicRwOrd::Release,
+            MemoryOrder::AcqRel => AtomicRwOrd::AcqRel,
+            MemoryOrder::SeqCst => AtomicRwOrd::SeqCst,
+        })
+    }
+}
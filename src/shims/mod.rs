@@ -1,17 +1,21 @@
 #![warn(clippy::arithmetic_side_effects)]
 
+mod aarch64;
 mod alloc;
 mod backtrace;
 mod files;
+mod libatomic;
 #[cfg(unix)]
 mod native_lib;
 mod unix;
 mod wasi;
+mod wasm;
 mod windows;
 mod x86;
 
 pub mod env;
 pub mod extern_static;
+pub mod fenv;
 pub mod foreign_items;
 pub mod io_error;
 pub mod os_str;
@@ -19,8 +23,11 @@ pub mod panic;
 pub mod time;
 pub mod tls;
 
-pub use self::files::FdTable;
-pub use self::unix::{DirTable, EpollInterestTable};
+pub use self::files::{FdId, FdTable};
+pub use self::unix::{
+    CommandScript, DirTable, EpollInterestTable, KqueueInterestTable, ProcessTable, SocketTable,
+};
+pub use self::windows::{CompletionPortTable, NamedPipeTable, OpenFileTable, VirtualTempDir};
 
 /// What needs to be done after emulating an item (a shim or an intrinsic) is done.
 pub enum EmulateItemResult {
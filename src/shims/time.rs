@@ -77,7 +77,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
         let duration = if absolute_clocks.contains(&clk_id) {
             this.check_no_isolation("`clock_gettime` with `REALTIME` clocks")?;
-            system_time_to_duration(&SystemTime::now())?
+            system_time_to_duration(&this.machine.clock.system_now())?
         } else if relative_clocks.contains(&clk_id) {
             this.machine.clock.now().duration_since(this.machine.clock.epoch())
         } else {
@@ -110,7 +110,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             return this.set_last_error_and_return_i32(LibcError("EINVAL"));
         }
 
-        let duration = system_time_to_duration(&SystemTime::now())?;
+        let duration = system_time_to_duration(&this.machine.clock.system_now())?;
         let tv_sec = duration.as_secs();
         let tv_usec = duration.subsec_micros();
 
@@ -224,7 +224,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let NANOS_PER_INTERVAL = NANOS_PER_SEC / INTERVALS_PER_SEC;
         let SECONDS_TO_UNIX_EPOCH = INTERVALS_TO_UNIX_EPOCH / INTERVALS_PER_SEC;
 
-        let duration = system_time_to_duration(&SystemTime::now())?
+        let duration = system_time_to_duration(&this.machine.clock.system_now())?
             + Duration::from_secs(SECONDS_TO_UNIX_EPOCH);
         let duration_ticks = u64::try_from(duration.as_nanos() / u128::from(NANOS_PER_INTERVAL))
             .map_err(|_| err_unsup_format!("programs running more than 2^64 Windows ticks after the Windows epoch are not supported"))?;
@@ -246,10 +246,17 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         this.assert_target_os("windows", "QueryPerformanceCounter");
 
         // QueryPerformanceCounter uses a hardware counter as its basis.
-        // Miri will emulate a counter with a resolution of 1 nanosecond.
+        // Miri emulates a counter with the frequency given by `-Zmiri-qpc-frequency`, based on the
+        // monotone clock. With `-Zmiri-virtual-clock`, this is fully deterministic.
         let duration = this.machine.clock.now().duration_since(this.machine.clock.epoch());
-        let qpc = i64::try_from(duration.as_nanos()).map_err(|_| {
-            err_unsup_format!("programs running longer than 2^63 nanoseconds are not supported")
+        let ticks = duration
+            .as_nanos()
+            .checked_mul(this.machine.qpc_frequency.into())
+            .map(|t| t / 1_000_000_000);
+        let qpc = ticks.and_then(|t| i64::try_from(t).ok()).ok_or_else(|| {
+            err_unsup_format!(
+                "programs running longer than 2^63 performance counter ticks are not supported"
+            )
         })?;
         this.write_scalar(Scalar::from_i64(qpc), &this.deref_pointer(lpPerformanceCount_op)?)?;
         interp_ok(Scalar::from_i32(-1)) // return non-zero on success
@@ -268,9 +275,9 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         // The frequency of the performance counter is fixed at system boot and
         // is consistent across all processors.
         // Miri emulates a "hardware" performance counter with a resolution of 1ns,
-        // and thus 10^9 counts per second.
+        // and thus 10^9 counts per second, unless `-Zmiri-qpc-frequency` says otherwise.
         this.write_scalar(
-            Scalar::from_i64(1_000_000_000),
+            Scalar::from_u64(this.machine.qpc_frequency),
             &this.deref_pointer_as(lpFrequency_op, this.machine.layouts.u64)?,
         )?;
         interp_ok(Scalar::from_i32(-1)) // Return non-zero on success
@@ -332,6 +339,68 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 @timeout = |_this| { interp_ok(()) }
             ),
         );
+        // `pthread_cancel` can interrupt the sleep.
+        this.block_at_cancellation_point();
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn clock_nanosleep(
+        &mut self,
+        clock_id_op: &OpTy<'tcx>,
+        flags_op: &OpTy<'tcx>,
+        req_op: &OpTy<'tcx>,
+        _rem: &OpTy<'tcx>, // Signal handlers are not supported, so rem will never be written to.
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        this.assert_target_os_is_unix("clock_nanosleep");
+
+        let clock_id = this.read_scalar(clock_id_op)?.to_i32()?;
+        let flags = this.read_scalar(flags_op)?.to_i32()?;
+        let req = this.deref_pointer_as(req_op, this.libc_ty_layout("timespec"))?;
+
+        // Unlike `nanosleep`, this returns the error code instead of setting `errno`.
+        let timer_abstime = this.eval_libc_i32("TIMER_ABSTIME");
+        if flags & !timer_abstime != 0 {
+            return interp_ok(this.eval_libc("EINVAL"));
+        }
+        let anchor = if flags & timer_abstime != 0 {
+            TimeoutAnchor::Absolute
+        } else {
+            TimeoutAnchor::Relative
+        };
+        let Some(duration) = this.read_timespec(&req)? else {
+            return interp_ok(this.eval_libc("EINVAL"));
+        };
+
+        let clock = if clock_id == this.eval_libc_i32("CLOCK_MONOTONIC") {
+            TimeoutClock::Monotonic
+        } else if clock_id == this.eval_libc_i32("CLOCK_REALTIME") {
+            match anchor {
+                // A relative sleep takes equally long on every clock.
+                TimeoutAnchor::Relative => TimeoutClock::Monotonic,
+                TimeoutAnchor::Absolute => {
+                    this.check_no_isolation("`clock_nanosleep` with `CLOCK_REALTIME`")?;
+                    TimeoutClock::RealTime
+                }
+            }
+        } else {
+            throw_unsup_format!(
+                "`clock_nanosleep` is only supported with `CLOCK_MONOTONIC` and `CLOCK_REALTIME`"
+            );
+        };
+
+        this.block_thread(
+            BlockReason::Sleep,
+            Some((clock, anchor, duration)),
+            callback!(
+                @capture<'tcx> {}
+                @unblock = |_this| { panic!("sleeping thread unblocked before time is up") }
+                @timeout = |_this| { interp_ok(()) }
+            ),
+        );
+        // `pthread_cancel` can interrupt the sleep.
+        this.block_at_cancellation_point();
         interp_ok(Scalar::from_i32(0))
     }
 
@@ -7,6 +7,7 @@ use std::task::Poll;
 use rustc_abi::{ExternAbi, HasDataLayout, Size};
 use rustc_middle::ty;
 
+use crate::shims::unix::exit_robust_list;
 use crate::*;
 
 pub type TlsKey = u128;
@@ -32,7 +33,8 @@ pub struct TlsData<'tcx> {
     /// The Key to use for the next thread-local allocation.
     next_key: TlsKey,
 
-    /// pthreads-style thread-local storage.
+    /// pthreads-style thread-local storage. On Windows, this holds the fiber-local storage
+    /// (`FlsAlloc`) and plain `TlsAlloc` keys.
     keys: BTreeMap<TlsKey, TlsEntry<'tcx>>,
 
     /// On macOS, each thread holds a list of destructor functions with their
@@ -164,7 +166,7 @@ impl<'tcx> TlsData<'tcx> {
         // We interpret the documentation above (taken from POSIX) as saying that we need to iterate
         // over all keys and run each destructor at least once before running any destructor a 2nd
         // time. That's why we have `key` to indicate how far we got in the current iteration. If we
-        // return `None`, `schedule_next_pthread_tls_dtor` will re-try with `ket` set to `None` to
+        // return `None`, `schedule_next_keyed_tls_dtor` will re-try with `ket` set to `None` to
         // start the next round.
         // TODO: In the future, we might consider randomizing destructor order, but we still have to
         // uphold this requirement.
@@ -217,11 +219,15 @@ pub struct TlsDtorsState<'tcx>(TlsDtorsStatePriv<'tcx>);
 enum TlsDtorsStatePriv<'tcx> {
     #[default]
     Init,
+    /// A cancelled thread first runs the handlers registered with `pthread_cleanup_push`.
+    PthreadCleanupHandlers,
     MacOsDtors,
     PthreadDtors(RunningDtorState),
     /// For Windows Dtors, we store the list of functions that we still have to call.
     /// These are functions from the magic `.CRT$XLB` linker section.
     WindowsDtors(Vec<ImmTy<'tcx>>),
+    /// After those, the callbacks registered with `FlsAlloc` are run.
+    WindowsFlsDtors(RunningDtorState),
     Done,
 }
 
@@ -235,15 +241,8 @@ impl<'tcx> TlsDtorsState<'tcx> {
             match &mut self.0 {
                 Init => {
                     match this.tcx.sess.target.os.as_ref() {
-                        "macos" => {
-                            // macOS has a _tlv_atexit function that allows
-                            // registering destructors without associated keys.
-                            // These are run first.
-                            break 'new_state MacOsDtors;
-                        }
                         _ if this.target_os_is_unix() => {
-                            // All other Unixes directly jump to running the pthread dtors.
-                            break 'new_state PthreadDtors(Default::default());
+                            break 'new_state PthreadCleanupHandlers;
                         }
                         "windows" => {
                             // Determine which destructors to run.
@@ -258,6 +257,17 @@ impl<'tcx> TlsDtorsState<'tcx> {
                         }
                     }
                 }
+                PthreadCleanupHandlers => {
+                    match this.schedule_pthread_cleanup_handler()? {
+                        Poll::Pending => return interp_ok(Poll::Pending),
+                        // macOS has a _tlv_atexit function that allows registering destructors
+                        // without associated keys. These are run first.
+                        Poll::Ready(()) if this.tcx.sess.target.os == "macos" =>
+                            break 'new_state MacOsDtors,
+                        // All other Unixes directly jump to running the pthread dtors.
+                        Poll::Ready(()) => break 'new_state PthreadDtors(Default::default()),
+                    }
+                }
                 MacOsDtors => {
                     match this.schedule_macos_tls_dtor()? {
                         Poll::Pending => return interp_ok(Poll::Pending),
@@ -267,7 +277,9 @@ impl<'tcx> TlsDtorsState<'tcx> {
                     }
                 }
                 PthreadDtors(state) => {
-                    match this.schedule_next_pthread_tls_dtor(state)? {
+                    match this
+                        .schedule_next_keyed_tls_dtor(state, ExternAbi::C { unwind: false })?
+                    {
                         Poll::Pending => return interp_ok(Poll::Pending), // just keep going
                         Poll::Ready(()) => break 'new_state Done,
                     }
@@ -277,11 +289,25 @@ impl<'tcx> TlsDtorsState<'tcx> {
                         this.schedule_windows_tls_dtor(dtor)?;
                         return interp_ok(Poll::Pending); // we stay in this state (but `dtors` got shorter)
                     } else {
-                        // No more destructors to run.
-                        break 'new_state Done;
+                        // Move on to the fiber-local storage callbacks.
+                        break 'new_state WindowsFlsDtors(Default::default());
+                    }
+                }
+                WindowsFlsDtors(state) => {
+                    match this
+                        .schedule_next_keyed_tls_dtor(state, ExternAbi::System { unwind: false })?
+                    {
+                        Poll::Pending => return interp_ok(Poll::Pending), // just keep going
+                        Poll::Ready(()) => break 'new_state Done,
                     }
                 }
                 Done => {
+                    if matches!(&*this.tcx.sess.target.os, "linux" | "android") {
+                        // After the destructors, the kernel cleans up the robust futex list.
+                        exit_robust_list(this)?;
+                    }
+                    // Robust pthread mutexes are released as well.
+                    this.release_robust_mutexes()?;
                     this.machine.tls.delete_all_thread_tls(this.active_thread());
                     return interp_ok(Poll::Ready(()));
                 }
@@ -330,6 +356,32 @@ trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(())
     }
 
+    /// Schedule the next cleanup handler of a cancelled thread. Threads that exit normally do not
+    /// run the cleanup handlers that they did not pop.
+    fn schedule_pthread_cleanup_handler(&mut self) -> InterpResult<'tcx, Poll<()>> {
+        let this = self.eval_context_mut();
+        let cancel = &mut this.active_thread_mut().cancel;
+        if !cancel.cancelled {
+            return interp_ok(Poll::Ready(()));
+        }
+        if let Some((routine, arg)) = cancel.cleanup_handlers.pop() {
+            let instance = this.get_ptr_fn(routine)?.as_instance()?;
+            trace!("Running cleanup handler {:?} on {:?}", instance, arg);
+
+            this.call_function(
+                instance,
+                ExternAbi::C { unwind: false },
+                &[ImmTy::from_scalar(arg, this.machine.layouts.mut_raw_ptr)],
+                None,
+                StackPopCleanup::Root { cleanup: true },
+            )?;
+
+            return interp_ok(Poll::Pending);
+        }
+
+        interp_ok(Poll::Ready(()))
+    }
+
     /// Schedule the macOS thread local storage destructors to be executed.
     fn schedule_macos_tls_dtor(&mut self) -> InterpResult<'tcx, Poll<()>> {
         let this = self.eval_context_mut();
@@ -355,11 +407,13 @@ trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(Poll::Ready(()))
     }
 
-    /// Schedule a pthread TLS destructor. Returns `true` if found
-    /// a destructor to schedule, and `false` otherwise.
-    fn schedule_next_pthread_tls_dtor(
+    /// Schedule the destructor of a TLS key (a pthread destructor, or a Windows FLS callback),
+    /// which gets called with the given ABI. Returns `Pending` if it found a destructor to
+    /// schedule, and `Ready` otherwise.
+    fn schedule_next_keyed_tls_dtor(
         &mut self,
         state: &mut RunningDtorState,
+        abi: ExternAbi,
     ) -> InterpResult<'tcx, Poll<()>> {
         let this = self.eval_context_mut();
         let active_thread = this.active_thread();
@@ -380,7 +434,7 @@ trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
             this.call_function(
                 instance,
-                ExternAbi::C { unwind: false },
+                abi,
                 &[ImmTy::from_scalar(ptr, this.machine.layouts.mut_raw_ptr)],
                 None,
                 StackPopCleanup::Root { cleanup: true },
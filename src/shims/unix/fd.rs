@@ -7,8 +7,9 @@ use std::io::ErrorKind;
 use rustc_abi::Size;
 
 use crate::helpers::check_min_arg_count;
-use crate::shims::files::FileDescription;
+use crate::shims::files::{EvalContextExt as _, FileDescription, MAX_IOVECS};
 use crate::shims::unix::linux_like::epoll::EpollReadyEvents;
+use crate::shims::unix::unnamed_socket::AnonSocket;
 use crate::shims::unix::*;
 use crate::*;
 
@@ -64,6 +65,12 @@ pub trait UnixFileDescription: FileDescription {
     fn get_epoll_ready_events<'tcx>(&self) -> InterpResult<'tcx, EpollReadyEvents> {
         throw_unsup_format!("{}: epoll does not support this file description", self.name());
     }
+
+    /// Check the readiness of file description for `poll`. This is the same as for epoll, except
+    /// that `poll` also accepts file descriptions that are always ready, like regular files.
+    fn get_poll_ready_events<'tcx>(&self) -> InterpResult<'tcx, EpollReadyEvents> {
+        self.get_epoll_ready_events()
+    }
 }
 
 impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
@@ -86,7 +93,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         if new_fd_num != old_fd_num {
             // Close new_fd if it is previously opened.
             // If old_fd and new_fd point to the same description, then `dup_fd` ensures we keep the underlying file description alive.
-            if let Some(old_new_fd) = this.machine.fds.fds.insert(new_fd_num, fd) {
+            if let Some(old_new_fd) = this.machine.fds.replace(new_fd_num, fd) {
                 // Ignore close error (not interpreter's) according to dup2() doc.
                 old_new_fd.close(this.machine.communicate(), this)?.ok();
             }
@@ -94,6 +101,24 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(Scalar::from_i32(new_fd_num))
     }
 
+    /// Like `dup2`, but the close-on-exec flag of the new file descriptor can be set via `flags`,
+    /// and it is an error for `old_fd_num` and `new_fd_num` to be equal.
+    fn dup3(&mut self, old_fd_num: i32, new_fd_num: i32, flags: i32) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let o_cloexec = this.eval_libc_i32("O_CLOEXEC");
+        if flags & !o_cloexec != 0 || old_fd_num == new_fd_num {
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        }
+
+        if !this.machine.fds.is_fd_num(old_fd_num) || new_fd_num < 0 {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        }
+        let result = this.dup2(old_fd_num, new_fd_num)?;
+        this.machine.fds.set_cloexec(new_fd_num, flags & o_cloexec != 0);
+        interp_ok(result)
+    }
+
     fn flock(&mut self, fd_num: i32, op: i32) -> InterpResult<'tcx, Scalar> {
         let this = self.eval_context_mut();
         let Some(fd) = this.machine.fds.get(fd_num) else {
@@ -137,27 +162,37 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let cmd = this.read_scalar(cmd)?.to_i32()?;
 
         let f_getfd = this.eval_libc_i32("F_GETFD");
+        let f_setfd = this.eval_libc_i32("F_SETFD");
         let f_dupfd = this.eval_libc_i32("F_DUPFD");
         let f_dupfd_cloexec = this.eval_libc_i32("F_DUPFD_CLOEXEC");
+        let fd_cloexec = this.eval_libc_i32("FD_CLOEXEC");
 
-        // We only support getting the flags for a descriptor.
         match cmd {
             cmd if cmd == f_getfd => {
-                // Currently this is the only flag that `F_GETFD` returns. It is OK to just return the
-                // `FD_CLOEXEC` value without checking if the flag is set for the file because `std`
-                // always sets this flag when opening a file. However we still need to check that the
-                // file itself is open.
+                // `FD_CLOEXEC` is the only file descriptor flag.
+                if !this.machine.fds.is_fd_num(fd_num) {
+                    this.set_last_error_and_return_i32(LibcError("EBADF"))
+                } else if this.machine.fds.is_cloexec(fd_num) {
+                    interp_ok(Scalar::from_i32(fd_cloexec))
+                } else {
+                    interp_ok(Scalar::from_i32(0))
+                }
+            }
+            cmd if cmd == f_setfd => {
+                let [_, _, flags] = check_min_arg_count("fcntl(fd, F_SETFD, ...)", args)?;
+                let flags = this.read_scalar(flags)?.to_i32()?;
+
                 if !this.machine.fds.is_fd_num(fd_num) {
                     this.set_last_error_and_return_i32(LibcError("EBADF"))
                 } else {
-                    interp_ok(this.eval_libc("FD_CLOEXEC"))
+                    // Like Linux, we ignore all bits other than `FD_CLOEXEC`.
+                    this.machine.fds.set_cloexec(fd_num, flags & fd_cloexec != 0);
+                    interp_ok(Scalar::from_i32(0))
                 }
             }
             cmd if cmd == f_dupfd || cmd == f_dupfd_cloexec => {
-                // Note that we always assume the FD_CLOEXEC flag is set for every open file, in part
-                // because exec() isn't supported. The F_DUPFD and F_DUPFD_CLOEXEC commands only
-                // differ in whether the FD_CLOEXEC flag is pre-set on the new file descriptor,
-                // thus they can share the same implementation here.
+                // The F_DUPFD and F_DUPFD_CLOEXEC commands only differ in whether the FD_CLOEXEC
+                // flag is set on the new file descriptor.
                 let cmd_name = if cmd == f_dupfd {
                     "fcntl(fd, F_DUPFD, ...)"
                 } else {
@@ -168,7 +203,9 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let start = this.read_scalar(start)?.to_i32()?;
 
                 if let Some(fd) = this.machine.fds.get(fd_num) {
-                    interp_ok(Scalar::from_i32(this.machine.fds.insert_with_min_num(fd, start)))
+                    let new_fd_num = this.machine.fds.insert_with_min_num(fd, start);
+                    this.machine.fds.set_cloexec(new_fd_num, cmd == f_dupfd_cloexec);
+                    interp_ok(Scalar::from_i32(new_fd_num))
                 } else {
                     this.set_last_error_and_return_i32(LibcError("EBADF"))
                 }
@@ -190,6 +227,35 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
     }
 
+    fn ioctl(&mut self, args: &[OpTy<'tcx>]) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let [fd_num, request] = check_min_arg_count("ioctl", args)?;
+
+        let fd_num = this.read_scalar(fd_num)?.to_i32()?;
+        let request_size = request.layout.size;
+        let request = this.read_scalar(request)?.to_uint(request_size)?;
+
+        if request == this.eval_libc("FIONBIO").to_uint(request_size)? {
+            let [_, _, nonblock] = check_min_arg_count("ioctl(fd, FIONBIO, ...)", args)?;
+            let nonblock = this.deref_pointer_as(nonblock, this.machine.layouts.i32)?;
+            let nonblock = this.read_scalar(&nonblock)?.to_i32()? != 0;
+
+            let Some(fd) = this.machine.fds.get(fd_num) else {
+                return this.set_last_error_and_return_i32(LibcError("EBADF"));
+            };
+            let Some(stream) = fd.downcast::<AnonSocket>() else {
+                throw_unsup_format!(
+                    "ioctl(fd, FIONBIO, ...) is only supported on pipes and socketpairs"
+                );
+            };
+            stream.set_nonblock(nonblock);
+            interp_ok(Scalar::from_i32(0))
+        } else {
+            throw_unsup_format!("ioctl: unsupported request {request:#x}");
+        }
+    }
+
     fn close(&mut self, fd_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
         let this = self.eval_context_mut();
 
@@ -296,4 +362,75 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         };
         interp_ok(())
     }
+    /// Read data from `fd` into the `iovcnt` buffers described by the `iovec`s at `iov`,
+    /// filling them in order.
+    fn readv(
+        &mut self,
+        fd_num: i32,
+        iov: Pointer,
+        iovcnt: i32,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let iov = match this.read_iovec_arg(iov, iovcnt)? {
+            Ok(iov) => iov,
+            Err(e) => return this.set_last_error_and_return(e, dest),
+        };
+        // Check that the *entire* buffers are actually valid memory.
+        for &(ptr, len) in &iov {
+            this.check_ptr_access(ptr, Size::from_bytes(len), CheckInAllocMsg::MemoryAccessTest)?;
+        }
+        let communicate = this.machine.communicate();
+
+        // We temporarily dup the FD to be able to retain mutable access to `this`.
+        let Some(fd) = this.machine.fds.get(fd_num) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        fd.readv(&fd, communicate, &iov, dest, this)
+    }
+
+    /// Write the data from the `iovcnt` buffers described by the `iovec`s at `iov` to `fd`,
+    /// in order.
+    fn writev(
+        &mut self,
+        fd_num: i32,
+        iov: Pointer,
+        iovcnt: i32,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let iov = match this.read_iovec_arg(iov, iovcnt)? {
+            Ok(iov) => iov,
+            Err(e) => return this.set_last_error_and_return(e, dest),
+        };
+        // Check that the *entire* buffers are actually valid memory.
+        for &(ptr, len) in &iov {
+            this.check_ptr_access(ptr, Size::from_bytes(len), CheckInAllocMsg::MemoryAccessTest)?;
+        }
+        let communicate = this.machine.communicate();
+
+        // We temporarily dup the FD to be able to retain mutable access to `this`.
+        let Some(fd) = this.machine.fds.get(fd_num) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        fd.writev(&fd, communicate, &iov, dest, this)
+    }
+
+    /// Read the `iov` and `iovcnt` arguments of `readv` and `writev`.
+    fn read_iovec_arg(
+        &self,
+        iov: Pointer,
+        iovcnt: i32,
+    ) -> InterpResult<'tcx, Result<Vec<(Pointer, u64)>, IoError>> {
+        let this = self.eval_context_ref();
+        let Ok(iovcnt) = u64::try_from(iovcnt) else {
+            return interp_ok(Err(LibcError("EINVAL")));
+        };
+        if iovcnt > MAX_IOVECS {
+            return interp_ok(Err(LibcError("EINVAL")));
+        }
+        this.read_iovecs(iov, iovcnt)
+    }
 }
@@ -10,7 +10,6 @@ use self::shims::unix::freebsd::foreign_items as freebsd;
 use self::shims::unix::linux::foreign_items as linux;
 use self::shims::unix::macos::foreign_items as macos;
 use self::shims::unix::solarish::foreign_items as solarish;
-use crate::concurrency::cpu_affinity::CpuAffinityMask;
 use crate::shims::alloc::EvalContextExt as _;
 use crate::shims::unix::*;
 use crate::*;
@@ -136,6 +135,73 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let result = this.chdir(path)?;
                 this.write_scalar(result, dest)?;
             }
+            "posix_spawn" | "posix_spawnp" => {
+                let [pid, path, file_actions, attr, argv, envp] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.posix_spawn(pid, path, file_actions, attr, argv, envp)?;
+                this.write_scalar(result, dest)?;
+            }
+            "posix_spawn_file_actions_init" => {
+                let [file_actions] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.posix_spawn_file_actions_init(file_actions)?;
+                this.write_scalar(result, dest)?;
+            }
+            "posix_spawn_file_actions_destroy" => {
+                let [file_actions] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.posix_spawn_file_actions_destroy(file_actions)?;
+                this.write_scalar(result, dest)?;
+            }
+            "posix_spawn_file_actions_adddup2" => {
+                let [file_actions, fd, new_fd] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.posix_spawn_file_actions_adddup2(file_actions, fd, new_fd)?;
+                this.write_scalar(result, dest)?;
+            }
+            "posix_spawn_file_actions_addclose" => {
+                let [file_actions, fd] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.posix_spawn_file_actions_addclose(file_actions, fd)?;
+                this.write_scalar(result, dest)?;
+            }
+            // Scripted children do not run any code, so their attributes make no difference.
+            "posix_spawnattr_init" | "posix_spawnattr_destroy" => {
+                let [attr] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.deref_pointer_as(attr, this.libc_ty_layout("posix_spawnattr_t"))?;
+                this.write_null(dest)?;
+            }
+            | "posix_spawnattr_setflags"
+            | "posix_spawnattr_setsigdefault"
+            | "posix_spawnattr_setsigmask"
+            | "posix_spawnattr_setpgroup" => {
+                let [attr, _] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.deref_pointer_as(attr, this.libc_ty_layout("posix_spawnattr_t"))?;
+                this.write_null(dest)?;
+            }
+            "wait" => {
+                let [status] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let status = this.read_pointer(status)?;
+                let result = this.wait4(-1, status, 0, None)?;
+                this.write_scalar(result, dest)?;
+            }
+            "waitpid" => {
+                let [pid, status, options] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let pid = this.read_scalar(pid)?.to_i32()?;
+                let status = this.read_pointer(status)?;
+                let options = this.read_scalar(options)?.to_i32()?;
+                let result = this.wait4(pid, status, options, None)?;
+                this.write_scalar(result, dest)?;
+            }
+            "wait4" => {
+                let [pid, status, options, rusage] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let pid = this.read_scalar(pid)?.to_i32()?;
+                let status = this.read_pointer(status)?;
+                let options = this.read_scalar(options)?.to_i32()?;
+                let rusage = this.read_pointer(rusage)?;
+                let result = this.wait4(pid, status, options, Some(rusage))?;
+                this.write_scalar(result, dest)?;
+            }
+            "waitid" => {
+                let [idtype, id, infop, options] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.waitid(idtype, id, infop, options)?;
+                this.write_scalar(result, dest)?;
+            }
             "getpid" => {
                 let [] = this.check_shim(abi, ExternAbi::C { unwind: false}, link_name, args)?;
                 let result = this.getpid()?;
@@ -155,7 +221,9 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let fd = this.read_scalar(fd)?.to_i32()?;
                 let buf = this.read_pointer(buf)?;
                 let count = this.read_target_usize(count)?;
-                this.read(fd, buf, count, None, dest)?;
+                if !this.cancellation_point() {
+                    this.read(fd, buf, count, None, dest)?;
+                }
             }
             "write" => {
                 let [fd, buf, n] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
@@ -165,6 +233,20 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 trace!("Called write({:?}, {:?}, {:?})", fd, buf, count);
                 this.write(fd, buf, count, None, dest)?;
             }
+            "readv" => {
+                let [fd, iov, iovcnt] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let fd = this.read_scalar(fd)?.to_i32()?;
+                let iov = this.read_pointer(iov)?;
+                let iovcnt = this.read_scalar(iovcnt)?.to_i32()?;
+                this.readv(fd, iov, iovcnt, dest)?;
+            }
+            "writev" => {
+                let [fd, iov, iovcnt] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let fd = this.read_scalar(fd)?.to_i32()?;
+                let iov = this.read_pointer(iov)?;
+                let iovcnt = this.read_scalar(iovcnt)?.to_i32()?;
+                this.writev(fd, iov, iovcnt, dest)?;
+            }
             "pread" => {
                 let [fd, buf, count, offset] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 let fd = this.read_scalar(fd)?.to_i32()?;
@@ -211,6 +293,13 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let result = this.fcntl(args)?;
                 this.write_scalar(result, dest)?;
             }
+            "ioctl" => {
+                // `ioctl` is variadic. The argument count is checked based on the request in
+                // `this.ioctl()`, so we do not use `check_shim` here.
+                this.check_abi_and_shim_symbol_clash(abi, ExternAbi::C { unwind: false }, link_name)?;
+                let result = this.ioctl(args)?;
+                this.write_scalar(result, dest)?;
+            }
             "dup" => {
                 let [old_fd] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 let old_fd = this.read_scalar(old_fd)?.to_i32()?;
@@ -224,6 +313,21 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let result = this.dup2(old_fd, new_fd)?;
                 this.write_scalar(result, dest)?;
             }
+            "dup3" => {
+                // Currently this function does not exist on all Unixes, e.g. on macOS.
+                if !matches!(&*this.tcx.sess.target.os, "linux" | "android" | "freebsd") {
+                    throw_unsup_format!(
+                        "`dup3` is not supported on {}",
+                        this.tcx.sess.target.os
+                    );
+                }
+                let [old_fd, new_fd, flags] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let old_fd = this.read_scalar(old_fd)?.to_i32()?;
+                let new_fd = this.read_scalar(new_fd)?.to_i32()?;
+                let flags = this.read_scalar(flags)?.to_i32()?;
+                let result = this.dup3(old_fd, new_fd, flags)?;
+                this.write_scalar(result, dest)?;
+            }
             "flock" => {
                 let [fd, op] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 let fd = this.read_scalar(fd)?.to_i32()?;
@@ -369,6 +473,154 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.write_scalar(result, dest)?;
             }
 
+            // Polling
+            "poll" => {
+                let [fds, nfds, timeout] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.poll(fds, nfds, timeout, dest)?;
+            }
+            "ppoll" => {
+                // Currently this function does not exist on all Unixes, e.g. on macOS.
+                if !matches!(&*this.tcx.sess.target.os, "linux" | "android" | "freebsd") {
+                    throw_unsup_format!(
+                        "`ppoll` is not supported on {}",
+                        this.tcx.sess.target.os
+                    );
+                }
+                let [fds, nfds, timeout, sigmask] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.ppoll(fds, nfds, timeout, sigmask, dest)?;
+            }
+            "select" => {
+                let [nfds, readfds, writefds, exceptfds, timeout] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.select(nfds, readfds, writefds, exceptfds, timeout, dest)?;
+            }
+            "pselect" => {
+                let [nfds, readfds, writefds, exceptfds, timeout, sigmask] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.pselect(nfds, readfds, writefds, exceptfds, timeout, sigmask, dest)?;
+            }
+
+            // Sockets
+            "getaddrinfo" => {
+                let [node, service, hints, res] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.getaddrinfo(node, service, hints, res)?;
+                this.write_scalar(result, dest)?;
+            }
+            "freeaddrinfo" => {
+                let [res] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.freeaddrinfo(res)?;
+            }
+            "gai_strerror" => {
+                let [errcode] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.gai_strerror(errcode)?;
+                this.write_pointer(result, dest)?;
+            }
+            "socket" => {
+                let [domain, type_, protocol] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.socket(domain, type_, protocol)?;
+                this.write_scalar(result, dest)?;
+            }
+            "bind" => {
+                let [socket, address, address_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.bind(socket, address, address_len)?;
+                this.write_scalar(result, dest)?;
+            }
+            "listen" => {
+                let [socket, backlog] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.listen(socket, backlog)?;
+                this.write_scalar(result, dest)?;
+            }
+            "connect" => {
+                let [socket, address, address_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.connect(socket, address, address_len)?;
+                this.write_scalar(result, dest)?;
+            }
+            "accept" => {
+                let [socket, address, address_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.accept4(socket, address, address_len, /* flags */ None, dest)?;
+            }
+            "accept4" => {
+                // Currently this function does not exist on all Unixes, e.g. on macOS.
+                if !matches!(&*this.tcx.sess.target.os, "linux" | "android" | "freebsd") {
+                    throw_unsup_format!(
+                        "`accept4` is not supported on {}",
+                        this.tcx.sess.target.os
+                    );
+                }
+                let [socket, address, address_len, flags] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.accept4(socket, address, address_len, Some(flags), dest)?;
+            }
+            "getsockname" => {
+                let [socket, address, address_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.getsockname(socket, address, address_len)?;
+                this.write_scalar(result, dest)?;
+            }
+            "getpeername" => {
+                let [socket, address, address_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.getpeername(socket, address, address_len)?;
+                this.write_scalar(result, dest)?;
+            }
+            "setsockopt" => {
+                let [socket, level, option_name, option_value, option_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.setsockopt(socket, level, option_name, option_value, option_len)?;
+                this.write_scalar(result, dest)?;
+            }
+            "sendmsg" => {
+                let [socket, msg, flags] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.sendmsg(socket, msg, flags, dest)?;
+            }
+            "recvmsg" => {
+                let [socket, msg, flags] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.recvmsg(socket, msg, flags, dest)?;
+            }
+            "shutdown" => {
+                let [sockfd, how] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.shutdown(sockfd, how)?;
+                this.write_scalar(result, dest)?;
+            }
+            "getsockopt" => {
+                let [socket, level, option_name, option_value, option_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.getsockopt(socket, level, option_name, option_value, option_len)?;
+                this.write_scalar(result, dest)?;
+            }
+            "recv" => {
+                let [socket, buf, len, flags] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.recv(socket, buf, len, flags, dest)?;
+            }
+            "send" => {
+                let [socket, buf, len, flags] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.send(socket, buf, len, flags, dest)?;
+            }
+            "recvfrom" => {
+                let [socket, buf, len, flags, address, address_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.recvfrom(socket, buf, len, flags, address, address_len, dest)?;
+            }
+            "sendto" => {
+                let [socket, buf, len, flags, address, address_len] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.sendto(socket, buf, len, flags, address, address_len, dest)?;
+            }
+
             // Time
             "gettimeofday" => {
                 let [tv, tz] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
@@ -528,6 +780,42 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.pthread_mutexattr_destroy(attr)?;
                 this.write_null(dest)?;
             }
+            "pthread_mutexattr_setpshared" => {
+                let [attr, pshared] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_mutexattr_setpshared(attr, pshared)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_mutexattr_getpshared" => {
+                let [attr, pshared] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_mutexattr_getpshared(attr, pshared)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_mutexattr_setrobust" => {
+                // Robust mutexes do not exist on all Unixes, e.g. on macOS.
+                if !matches!(&*this.tcx.sess.target.os, "linux" | "freebsd") {
+                    throw_unsup_format!(
+                        "`pthread_mutexattr_setrobust` is not supported on {}",
+                        this.tcx.sess.target.os
+                    );
+                }
+
+                let [attr, robustness] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_mutexattr_setrobust(attr, robustness)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_mutexattr_getrobust" => {
+                // Robust mutexes do not exist on all Unixes, e.g. on macOS.
+                if !matches!(&*this.tcx.sess.target.os, "linux" | "freebsd") {
+                    throw_unsup_format!(
+                        "`pthread_mutexattr_getrobust` is not supported on {}",
+                        this.tcx.sess.target.os
+                    );
+                }
+
+                let [attr, robustness] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_mutexattr_getrobust(attr, robustness)?;
+                this.write_scalar(result, dest)?;
+            }
             "pthread_mutex_init" => {
                 let [mutex, attr] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 this.pthread_mutex_init(mutex, attr)?;
@@ -547,11 +835,49 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let result = this.pthread_mutex_unlock(mutex)?;
                 this.write_scalar(result, dest)?;
             }
+            "pthread_mutex_consistent" => {
+                // Robust mutexes do not exist on all Unixes, e.g. on macOS.
+                if !matches!(&*this.tcx.sess.target.os, "linux" | "freebsd") {
+                    throw_unsup_format!(
+                        "`pthread_mutex_consistent` is not supported on {}",
+                        this.tcx.sess.target.os
+                    );
+                }
+
+                let [mutex] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_mutex_consistent(mutex)?;
+                this.write_scalar(result, dest)?;
+            }
             "pthread_mutex_destroy" => {
                 let [mutex] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 this.pthread_mutex_destroy(mutex)?;
                 this.write_int(0, dest)?;
             }
+            "pthread_rwlockattr_init" => {
+                let [attr] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.pthread_rwlockattr_init(attr)?;
+                this.write_null(dest)?;
+            }
+            "pthread_rwlockattr_setpshared" => {
+                let [attr, pshared] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_rwlockattr_setpshared(attr, pshared)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_rwlockattr_getpshared" => {
+                let [attr, pshared] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_rwlockattr_getpshared(attr, pshared)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_rwlockattr_destroy" => {
+                let [attr] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.pthread_rwlockattr_destroy(attr)?;
+                this.write_null(dest)?;
+            }
+            "pthread_rwlock_init" => {
+                let [rwlock, attr] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.pthread_rwlock_init(rwlock, attr)?;
+                this.write_null(dest)?;
+            }
             "pthread_rwlock_rdlock" => {
                 let [rwlock] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 this.pthread_rwlock_rdlock(rwlock, dest)?;
@@ -580,6 +906,63 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.pthread_rwlock_destroy(rwlock)?;
                 this.write_null(dest)?;
             }
+            "pthread_spin_init" => {
+                let [lock, pshared] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_spin_init(lock, pshared)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_spin_lock" => {
+                let [lock] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.pthread_spin_lock(lock, dest)?;
+            }
+            "pthread_spin_trylock" => {
+                let [lock] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_spin_trylock(lock)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_spin_unlock" => {
+                let [lock] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_spin_unlock(lock)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_spin_destroy" => {
+                let [lock] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_spin_destroy(lock)?;
+                this.write_scalar(result, dest)?;
+            }
+            "sem_init" => {
+                let [sem, pshared, value] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.sem_init(sem, pshared, value)?;
+                this.write_scalar(result, dest)?;
+            }
+            "sem_destroy" => {
+                let [sem] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.sem_destroy(sem)?;
+                this.write_scalar(result, dest)?;
+            }
+            "sem_post" => {
+                let [sem] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.sem_post(sem)?;
+                this.write_scalar(result, dest)?;
+            }
+            "sem_wait" => {
+                let [sem] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.sem_wait(sem, dest)?;
+            }
+            "sem_trywait" => {
+                let [sem] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.sem_trywait(sem)?;
+                this.write_scalar(result, dest)?;
+            }
+            "sem_timedwait" => {
+                let [sem, abstime] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.sem_timedwait(sem, abstime, dest)?;
+            }
+            "sem_getvalue" => {
+                let [sem, sval] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.sem_getvalue(sem, sval)?;
+                this.write_scalar(result, dest)?;
+            }
             "pthread_condattr_init" => {
                 let [attr] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 this.pthread_condattr_init(attr)?;
@@ -597,6 +980,16 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.pthread_condattr_getclock(attr, clock_id)?;
                 this.write_null(dest)?;
             }
+            "pthread_condattr_setpshared" => {
+                let [attr, pshared] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_condattr_setpshared(attr, pshared)?;
+                this.write_scalar(result, dest)?;
+            }
+            "pthread_condattr_getpshared" => {
+                let [attr, pshared] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.pthread_condattr_getpshared(attr, pshared)?;
+                this.write_scalar(result, dest)?;
+            }
             "pthread_condattr_destroy" => {
                 let [attr] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 this.pthread_condattr_destroy(attr)?;
@@ -652,6 +1045,25 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let res = this.pthread_self()?;
                 this.write_scalar(res, dest)?;
             }
+            "pthread_cancel" => {
+                let [thread] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.pthread_cancel(thread)?;
+                this.write_scalar(res, dest)?;
+            }
+            "pthread_setcancelstate" => {
+                let [state, oldstate] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.pthread_setcancelstate(state, oldstate)?;
+                this.write_scalar(res, dest)?;
+            }
+            "pthread_setcanceltype" => {
+                let [ty, oldtype] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.pthread_setcanceltype(ty, oldtype)?;
+                this.write_scalar(res, dest)?;
+            }
+            "pthread_testcancel" => {
+                let [] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.pthread_testcancel()?;
+            }
             "sched_yield" => {
                 let [] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 this.sched_yield()?;
@@ -659,8 +1071,24 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             }
             "nanosleep" => {
                 let [req, rem] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
-                let result = this.nanosleep(req, rem)?;
-                this.write_scalar(result, dest)?;
+                if !this.cancellation_point() {
+                    let result = this.nanosleep(req, rem)?;
+                    this.write_scalar(result, dest)?;
+                }
+            }
+            "clock_nanosleep" => {
+                // Currently this function does not exist on all Unixes, e.g. on macOS.
+                if !matches!(&*this.tcx.sess.target.os, "linux" | "android" | "freebsd" | "solaris" | "illumos") {
+                    throw_unsup_format!(
+                        "`clock_nanosleep` is not supported on {}",
+                        this.tcx.sess.target.os
+                    );
+                }
+                let [clock_id, flags, req, rem] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                if !this.cancellation_point() {
+                    let result = this.clock_nanosleep(clock_id, flags, req, rem)?;
+                    this.write_scalar(result, dest)?;
+                }
             }
             "sched_getaffinity" => {
                 // Currently this function does not exist on all Unixes, e.g. on macOS.
@@ -673,34 +1101,8 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
                 let [pid, cpusetsize, mask] =
                     this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
-                let pid = this.read_scalar(pid)?.to_u32()?;
-                let cpusetsize = this.read_target_usize(cpusetsize)?;
-                let mask = this.read_pointer(mask)?;
-
-                // TODO: when https://github.com/rust-lang/miri/issues/3730 is fixed this should use its notion of tid/pid
-                let thread_id = match pid {
-                    0 => this.active_thread(),
-                    _ => throw_unsup_format!("`sched_getaffinity` is only supported with a pid of 0 (indicating the current thread)"),
-                };
-
-                // The mask is stored in chunks, and the size must be a whole number of chunks.
-                let chunk_size = CpuAffinityMask::chunk_size(this);
-
-                if this.ptr_is_null(mask)? {
-                    this.set_last_error_and_return(LibcError("EFAULT"), dest)?;
-                } else if cpusetsize == 0 || cpusetsize.checked_rem(chunk_size).unwrap() != 0 {
-                    // we only copy whole chunks of size_of::<c_ulong>()
-                    this.set_last_error_and_return(LibcError("EINVAL"), dest)?;
-                } else if let Some(cpuset) = this.machine.thread_cpu_affinity.get(&thread_id) {
-                    let cpuset = cpuset.clone();
-                    // we only copy whole chunks of size_of::<c_ulong>()
-                    let byte_count = Ord::min(cpuset.as_slice().len(), cpusetsize.try_into().unwrap());
-                    this.write_bytes_ptr(mask, cpuset.as_slice()[..byte_count].iter().copied())?;
-                    this.write_null(dest)?;
-                } else {
-                    // The thread whose ID is pid could not be found
-                    this.set_last_error_and_return(LibcError("ESRCH"), dest)?;
-                }
+                let result = this.sched_getaffinity(pid, cpusetsize, mask)?;
+                this.write_scalar(result, dest)?;
             }
             "sched_setaffinity" => {
                 // Currently this function does not exist on all Unixes, e.g. on macOS.
@@ -713,37 +1115,8 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
                 let [pid, cpusetsize, mask] =
                     this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
-                let pid = this.read_scalar(pid)?.to_u32()?;
-                let cpusetsize = this.read_target_usize(cpusetsize)?;
-                let mask = this.read_pointer(mask)?;
-
-                // TODO: when https://github.com/rust-lang/miri/issues/3730 is fixed this should use its notion of tid/pid
-                let thread_id = match pid {
-                    0 => this.active_thread(),
-                    _ => throw_unsup_format!("`sched_setaffinity` is only supported with a pid of 0 (indicating the current thread)"),
-                };
-
-                if this.ptr_is_null(mask)? {
-                    this.set_last_error_and_return(LibcError("EFAULT"), dest)?;
-                } else {
-                    // NOTE: cpusetsize might be smaller than `CpuAffinityMask::CPU_MASK_BYTES`.
-                    // Any unspecified bytes are treated as zero here (none of the CPUs are configured).
-                    // This is not exactly documented, so we assume that this is the behavior in practice.
-                    let bits_slice = this.read_bytes_ptr_strip_provenance(mask, Size::from_bytes(cpusetsize))?;
-                    // This ignores the bytes beyond `CpuAffinityMask::CPU_MASK_BYTES`
-                    let bits_array: [u8; CpuAffinityMask::CPU_MASK_BYTES] =
-                        std::array::from_fn(|i| bits_slice.get(i).copied().unwrap_or(0));
-                    match CpuAffinityMask::from_array(this, this.machine.num_cpus, bits_array) {
-                        Some(cpuset) => {
-                            this.machine.thread_cpu_affinity.insert(thread_id, cpuset);
-                            this.write_null(dest)?;
-                        }
-                        None => {
-                            // The intersection between the mask and the available CPUs was empty.
-                            this.set_last_error_and_return(LibcError("EINVAL"), dest)?;
-                        }
-                    }
-                }
+                let result = this.sched_setaffinity(pid, cpusetsize, mask)?;
+                this.write_scalar(result, dest)?;
             }
 
             // Miscellaneous
@@ -760,6 +1133,14 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 // We do not support forking, so there is nothing to do here.
                 this.write_null(dest)?;
             }
+            "fork" => {
+                // The child would need its own copy of the entire interpreter state, and Miri also
+                // runs host threads, so forking the host process is not an option either.
+                let [] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                throw_unsup_format!(
+                    "`fork` is not supported; `posix_spawn` can run the commands declared with `-Zmiri-command-script`"
+                );
+            }
             "getentropy" => {
                 // This function is non-standard but exists with the same signature and behavior on
                 // Linux, macOS, FreeBSD and Solaris/Illumos.
@@ -912,6 +1293,17 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let [_, _] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 this.write_null(dest)?;
             }
+            // `std` builds a signal set for `posix_spawn`, which ignores it.
+            "sigemptyset"
+            if this.frame_in_std() => {
+                let [_] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.write_null(dest)?;
+            }
+            "sigaddset"
+            if this.frame_in_std() => {
+                let [_, _] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.write_null(dest)?;
+            }
             | "sigaction"
             | "mprotect"
             if this.frame_in_std() => {
@@ -46,6 +46,18 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     /* truncate */ true,
                 )?;
             }
+            "cpuset_getaffinity" => {
+                let [level, which, id, set_size, mask] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.cpuset_getaffinity(level, which, id, set_size, mask)?;
+                this.write_scalar(result, dest)?;
+            }
+            "cpuset_setaffinity" => {
+                let [level, which, id, set_size, mask] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.cpuset_setaffinity(level, which, id, set_size, mask)?;
+                this.write_scalar(result, dest)?;
+            }
 
             // File related shims
             // For those, we both intercept `func` and `call@FBSD_1.0` symbols cases
@@ -16,7 +16,7 @@ use crate::*;
 const TASK_COMM_LEN: usize = 16;
 
 pub fn is_dyn_sym(name: &str) -> bool {
-    matches!(name, "statx")
+    matches!(name, "statx" | "gnu_get_libc_version")
 }
 
 impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
@@ -119,11 +119,76 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 };
                 this.write_scalar(res, dest)?;
             }
+            "pthread_setschedparam" => {
+                let [thread, policy, param] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.pthread_setschedparam(thread, policy, param)?;
+                this.write_scalar(res, dest)?;
+            }
+            "pthread_getschedparam" => {
+                let [thread, policy, param] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.pthread_getschedparam(thread, policy, param)?;
+                this.write_scalar(res, dest)?;
+            }
+            "sched_get_priority_max" => {
+                let [policy] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.sched_get_priority_max(policy)?;
+                this.write_scalar(res, dest)?;
+            }
+            "sched_get_priority_min" => {
+                let [policy] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.sched_get_priority_min(policy)?;
+                this.write_scalar(res, dest)?;
+            }
+            "setpriority" => {
+                let [which, who, prio] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.setpriority(which, who, prio)?;
+                this.write_scalar(res, dest)?;
+            }
+            "getpriority" => {
+                let [which, who] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.getpriority(which, who)?;
+                this.write_scalar(res, dest)?;
+            }
+            "nice" => {
+                let [inc] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let res = this.nice(inc)?;
+                this.write_scalar(res, dest)?;
+            }
+            "sem_clockwait" => {
+                let [sem, clock_id, abstime] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.sem_clockwait(sem, clock_id, abstime, dest)?;
+            }
+            "gnu_get_libc_version" => {
+                let [] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                // `std` only uses `posix_spawn` with glibc 2.24 or newer.
+                let version = this.alloc_os_str_as_c_str(
+                    std::ffi::OsStr::new("2.36"),
+                    MiriMemoryKind::Machine.into(),
+                )?;
+                this.write_pointer(version, dest)?;
+            }
             "gettid" => {
                 let [] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
                 let result = this.linux_gettid()?;
                 this.write_scalar(result, dest)?;
             }
+            // This is what the `pthread_cleanup_push` macro calls in musl. glibc exports it as
+            // well, but its own macro uses `__pthread_register_cancel` instead, which relies on
+            // `setjmp` and is not supported. `_pthread_cleanup_pop` is implemented in
+            // `shims/foreign_items.rs` since it needs to be able to call the handler.
+            "_pthread_cleanup_push" => {
+                let [_buffer, routine, arg] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.pthread_cleanup_push(routine, arg)?;
+            }
 
             // Dynamically invoked syscalls
             "syscall" => {
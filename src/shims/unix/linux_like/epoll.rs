@@ -7,6 +7,7 @@ use std::time::Duration;
 use crate::concurrency::VClock;
 use crate::shims::files::{FdId, FileDescription, FileDescriptionRef, WeakFileDescriptionRef};
 use crate::shims::unix::UnixFileDescription;
+use crate::shims::unix::macos::kqueue::EvalContextExt as _;
 use crate::*;
 
 /// An `Epoll` file descriptor connects file handles and epoll events
@@ -213,7 +214,6 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
         let epoll_cloexec = this.eval_libc_i32("EPOLL_CLOEXEC");
 
-        // Miri does not support exec, so EPOLL_CLOEXEC flag has no effect.
         if flags != epoll_cloexec && flags != 0 {
             throw_unsup_format!(
                 "epoll_create1: flag {:#x} is unsupported, only 0 or EPOLL_CLOEXEC are allowed",
@@ -222,6 +222,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
 
         let fd = this.machine.fds.insert_new(Epoll::default());
+        this.machine.fds.set_cloexec(fd, flags == epoll_cloexec);
         interp_ok(Scalar::from_i32(fd))
     }
 
@@ -532,7 +533,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     ) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
         let id = fd_ref.get_id();
-        let mut waiter = Vec::new();
+        let mut notified = Vec::new();
         // Get a list of EpollEventInterest that is associated to a specific file description.
         if let Some(epoll_interests) = this.machine.epoll_interests.get_epoll_interest(id) {
             for weak_epoll_interest in epoll_interests {
@@ -552,42 +553,91 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                         // above would fail. This guarantee holds because only the epoll instance
                         // holds a strong ref to epoll_interest.
                         let epfd = epoll_interest.borrow().weak_epfd.upgrade().unwrap();
-                        // FIXME: We can randomly pick a thread to unblock.
-
-                        let epoll = epfd.downcast::<Epoll>().unwrap();
-
-                        if let Some(thread_id) = epoll.thread_id.borrow_mut().pop() {
-                            waiter.push(thread_id);
-                        };
+                        notified.push(epfd);
                     }
                 }
             }
         }
+        let mut waiter = Vec::new();
+        for epfd in notified {
+            let epoll = epfd.downcast::<Epoll>().unwrap();
+            let blocked = epoll.thread_id.borrow().len();
+            if blocked > 0 {
+                // By default, the thread that blocked last is woken up.
+                let choice = epoll_wakeup_choice(this, blocked)?;
+                waiter.push(
+                    epoll.thread_id.borrow_mut().remove(blocked.strict_sub(1).strict_sub(choice)),
+                );
+            }
+        }
         waiter.sort();
         waiter.dedup();
         for thread_id in waiter {
             this.unblock_thread(thread_id, BlockReason::Epoll)?;
         }
+        // Threads blocked in `poll` or `select` do not register their interest, so we let them
+        // check again whether one of their file descriptions became ready. If they see it ready,
+        // they sync with this readiness change, just like epoll does with the clock of an event
+        // instance.
+        if let Some(clock) = this.release_clock(|clock| clock.clone()) {
+            this.machine.readiness_clocks.insert(id, clock);
+        }
+        let poll_waiters = std::mem::take(&mut this.machine.blocked_poll_tid);
+        for thread_id in poll_waiters {
+            this.unblock_thread(thread_id, BlockReason::Poll)?;
+        }
+        this.kqueue_notify_readiness(id)?;
         interp_ok(())
     }
 }
 
+/// Picks which of `count` ready events or blocked threads to handle next, according to
+/// `-Zmiri-epoll-wakeup-order`. Choice 0 is the fixed order.
+fn epoll_wakeup_choice<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
+    count: usize,
+) -> InterpResult<'tcx, usize> {
+    use rand::Rng as _;
+    if count < 2 {
+        return interp_ok(0);
+    }
+    let choice = if ecx.machine.threads.decides_wakeups() {
+        let Some(choice) = ecx.machine.threads.decide_wakeup(count) else {
+            throw_unsup_format!(
+                "the schedule does not match this execution; schedules can only be replayed with the same program, inputs, and flags"
+            );
+        };
+        choice
+    } else {
+        match ecx.machine.epoll_wakeup_order {
+            EpollWakeupOrder::Random => ecx.machine.rng.get_mut().gen_range(0..count),
+            EpollWakeupOrder::Fixed | EpollWakeupOrder::Explore => 0,
+        }
+    };
+    ecx.machine.threads.note_wakeup(choice);
+    interp_ok(choice)
+}
+
 /// This function takes in ready list and returns EpollEventInstance with file description
 /// that is not closed.
-fn ready_list_next(
-    ecx: &MiriInterpCx<'_>,
+fn ready_list_next<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
     ready_list: &mut BTreeMap<(FdId, i32), EpollEventInstance>,
-) -> Option<EpollEventInstance> {
-    while let Some((epoll_key, epoll_event_instance)) = ready_list.pop_first() {
-        // This ensures that we only return events that we are interested. The FD might have been closed since
-        // the event was generated, in which case we are not interested anymore.
-        // When a file description is fully closed, it gets removed from `machine.epoll_interests`,
-        // so we skip events whose FD is not in that map anymore.
-        if ecx.machine.epoll_interests.get_epoll_interest(epoll_key.0).is_some() {
-            return Some(epoll_event_instance);
-        }
+) -> InterpResult<'tcx, Option<EpollEventInstance>> {
+    // This ensures that we only return events that we are interested. The FD might have been closed since
+    // the event was generated, in which case we are not interested anymore.
+    // When a file description is fully closed, it gets removed from `machine.epoll_interests`,
+    // so we skip events whose FD is not in that map anymore.
+    ready_list.retain(|epoll_key, _| {
+        ecx.machine.epoll_interests.get_epoll_interest(epoll_key.0).is_some()
+    });
+    if ready_list.is_empty() {
+        return interp_ok(None);
     }
-    None
+    // By default, events are returned in the order of their file descriptions.
+    let choice = epoll_wakeup_choice(ecx, ready_list.len())?;
+    let epoll_key = *ready_list.keys().nth(choice).unwrap();
+    interp_ok(ready_list.remove(&epoll_key))
 }
 
 /// This helper function checks whether an epoll notification should be triggered for a specific
@@ -648,7 +698,7 @@ fn return_ready_list<'tcx>(
     let mut array_iter = ecx.project_array_fields(events)?;
 
     while let Some(des) = array_iter.next(ecx)? {
-        if let Some(epoll_event_instance) = ready_list_next(ecx, &mut ready_list) {
+        if let Some(epoll_event_instance) = ready_list_next(ecx, &mut ready_list)? {
             ecx.write_int_fields_named(
                 &[
                     ("events", epoll_event_instance.events.into()),
@@ -162,11 +162,12 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
 
         let mut is_nonblock = false;
+        let mut is_cloexec = false;
         // Unset the flag that we support.
         // After unloading, flags != 0 means other flags are used.
         if flags & efd_cloexec == efd_cloexec {
-            // cloexec is ignored because Miri does not support exec.
             flags &= !efd_cloexec;
+            is_cloexec = true;
         }
         if flags & efd_nonblock == efd_nonblock {
             flags &= !efd_nonblock;
@@ -185,6 +186,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             blocked_read_tid: RefCell::new(Vec::new()),
             blocked_write_tid: RefCell::new(Vec::new()),
         });
+        fds.set_cloexec(fd_value, is_cloexec);
 
         interp_ok(Scalar::from_i32(fd_value))
     }
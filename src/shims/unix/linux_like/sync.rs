@@ -1,11 +1,63 @@
+use rustc_abi::Size;
+
 use crate::concurrency::sync::FutexRef;
 use crate::helpers::check_min_arg_count;
+use crate::shims::unix::*;
 use crate::*;
 
 struct LinuxFutex {
     futex: FutexRef,
 }
 
+// The bits of the value of a PI futex, and of the futexes on a robust list.
+/// Set if there are threads waiting in the kernel, so unlocking has to go through the kernel.
+const FUTEX_WAITERS: u32 = 0x8000_0000;
+/// Set by the kernel if the owner of the futex exited without unlocking it.
+const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+/// The TID of the owner of the futex, or 0 if it is free.
+const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+
+/// The kernel stops walking a robust list after this many entries, so that it cannot get stuck
+/// in a cycle.
+const ROBUST_LIST_LIMIT: u32 = 2048;
+
+/// The TID of the given thread.
+fn thread_tid<'tcx>(ecx: &MiriInterpCx<'tcx>, thread: ThreadId) -> u32 {
+    // The TID of a thread is the PID plus the thread index, see `linux_gettid`.
+    ecx.get_pid().strict_add(thread.to_u32())
+}
+
+/// The thread with the given TID, if there is one.
+fn tid_thread<'tcx>(ecx: &MiriInterpCx<'tcx>, tid: u32) -> Option<ThreadId> {
+    tid.checked_sub(ecx.get_pid()).and_then(|index| ecx.thread_id_try_from(index).ok())
+}
+
+/// Make the first thread waiting on a PI futex its owner, and wake it up. The value of the futex
+/// becomes the TID of the new owner, with `FUTEX_WAITERS` set if there are more waiters, and
+/// with the given extra `flags`. If nobody is waiting, the value becomes just `flags`.
+fn futex_pi_handoff<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
+    addr: &MPlaceTy<'tcx>,
+    futex_ref: &FutexRef,
+    flags: u32,
+) -> InterpResult<'tcx> {
+    let waiters = ecx.futex_waiters(futex_ref);
+    let new_val = match waiters.as_slice() {
+        [] => flags,
+        [next, rest @ ..] => {
+            let waiters_flag = if rest.is_empty() { 0 } else { FUTEX_WAITERS };
+            thread_tid(ecx, *next) | waiters_flag | flags
+        }
+    };
+    ecx.write_scalar_atomic(Scalar::from_u32(new_val), addr, AtomicWriteOrd::Release)?;
+    if !waiters.is_empty() {
+        // This wakes up `waiters[0]`, since all threads waiting on a PI futex use the full bitset.
+        // It also makes the new owner's acquisition happen-after everything we did so far.
+        ecx.futex_wake(futex_ref, u32::MAX)?;
+    }
+    interp_ok(())
+}
+
 /// Implementation of the SYS_futex syscall.
 /// `args` is the arguments *including* the syscall number.
 pub fn futex<'tcx>(
@@ -38,6 +90,11 @@ pub fn futex<'tcx>(
     let futex_wait_bitset = ecx.eval_libc_i32("FUTEX_WAIT_BITSET");
     let futex_wake = ecx.eval_libc_i32("FUTEX_WAKE");
     let futex_wake_bitset = ecx.eval_libc_i32("FUTEX_WAKE_BITSET");
+    let futex_requeue = ecx.eval_libc_i32("FUTEX_REQUEUE");
+    let futex_cmp_requeue = ecx.eval_libc_i32("FUTEX_CMP_REQUEUE");
+    let futex_lock_pi = ecx.eval_libc_i32("FUTEX_LOCK_PI");
+    let futex_trylock_pi = ecx.eval_libc_i32("FUTEX_TRYLOCK_PI");
+    let futex_unlock_pi = ecx.eval_libc_i32("FUTEX_UNLOCK_PI");
     let futex_realtime = ecx.eval_libc_i32("FUTEX_CLOCK_REALTIME");
 
     // FUTEX_PRIVATE enables an optimization that stops it from working across processes.
@@ -52,6 +109,9 @@ pub fn futex<'tcx>(
         //  - The timeout is absolute rather than relative.
         //  - You can specify the bitset to selecting what WAKE operations to respond to.
         op if op & !futex_realtime == futex_wait || op & !futex_realtime == futex_wait_bitset => {
+            if ecx.cancellation_point() {
+                return interp_ok(());
+            }
             let wait_bitset = op & !futex_realtime == futex_wait_bitset;
 
             let (timeout, bitset) = if wait_bitset {
@@ -165,6 +225,8 @@ pub fn futex<'tcx>(
                     dest.clone(),
                     LibcError("ETIMEDOUT"), // errno_timeout
                 );
+                // `pthread_cancel` can interrupt the wait.
+                ecx.block_at_cancellation_point();
             } else {
                 // The futex value doesn't match the expected value, so we return failure
                 // right away without sleeping: -1 and errno set to EAGAIN.
@@ -214,10 +276,318 @@ pub fn futex<'tcx>(
                     break;
                 }
             }
+            if n == 0 && val > 0 {
+                ecx.futex_wake_lost(&futex_ref);
+            }
             ecx.write_scalar(Scalar::from_target_isize(n, ecx), dest)?;
         }
+        // FUTEX_REQUEUE: (int *addr, int op = FUTEX_REQUEUE, int val, uint32_t val2, int *addr2)
+        // Wakes at most `val` threads waiting on the futex at `addr`, and moves at most `val2` of
+        // the remaining waiters over to the futex at `addr2`, without waking them.
+        // Returns the amount of threads woken up.
+        // FUTEX_CMP_REQUEUE: (int *addr, int op = FUTEX_CMP_REQUEUE, int val, uint32_t val2, int *addr2, int val3)
+        // Same as FUTEX_REQUEUE, but first checks that *addr still equals val3, and fails with
+        // EAGAIN otherwise. Returns the amount of threads woken up or requeued.
+        op if op == futex_requeue || op == futex_cmp_requeue => {
+            let cmp = op == futex_cmp_requeue;
+
+            let (val2, addr2, val3) = if cmp {
+                let [_, _, _, _, val2, addr2, val3] =
+                    check_min_arg_count("`syscall(SYS_futex, FUTEX_CMP_REQUEUE, ...)`", args)?;
+                (val2, addr2, Some(ecx.read_scalar(val3)?.to_i32()?))
+            } else {
+                let [_, _, _, _, val2, addr2] =
+                    check_min_arg_count("`syscall(SYS_futex, FUTEX_REQUEUE, ...)`", args)?;
+                (val2, addr2, None)
+            };
+            // `val2` is passed in the slot that other operations use for the timeout pointer,
+            // so it may be pointer-sized. Values that do not fit an `i32` are invalid.
+            let val2 = ecx.read_scalar(val2)?;
+            let val2 = i32::try_from(val2.to_int(val2.size())?).unwrap_or(-1);
+            let addr2 = ecx.read_pointer(addr2)?;
+
+            if val < 0 || val2 < 0 {
+                return ecx.set_last_error_and_return(LibcError("EINVAL"), dest);
+            }
+
+            if let Some(val3) = val3 {
+                // Same reasoning as for the check in FUTEX_WAIT: we need to see the latest value
+                // of addr, and the fence, read and comparison must happen atomically.
+                ecx.atomic_fence(AtomicFenceOrd::SeqCst)?;
+                let futex_val = ecx.read_scalar_atomic(&addr, AtomicReadOrd::Acquire)?.to_i32()?;
+                if futex_val != val3 {
+                    return ecx.set_last_error_and_return(LibcError("EAGAIN"), dest);
+                }
+            }
+
+            let Some(futex_ref) =
+                ecx.get_sync_or_init(addr.ptr(), |_| LinuxFutex { futex: Default::default() })
+            else {
+                // See FUTEX_WAKE for why this is an error.
+                return ecx.set_last_error_and_return(LibcError("EFAULT"), dest);
+            };
+            let futex_ref = futex_ref.futex.clone();
+            let Some(target_ref) =
+                ecx.get_sync_or_init(addr2, |_| LinuxFutex { futex: Default::default() })
+            else {
+                return ecx.set_last_error_and_return(LibcError("EFAULT"), dest);
+            };
+            let target_ref = target_ref.futex.clone();
+
+            // Like FUTEX_WAKE, this makes sure that the woken threads observe the latest value
+            // of addr.
+            ecx.atomic_fence(AtomicFenceOrd::SeqCst)?;
+            let (woken, requeued) = ecx.futex_requeue(
+                &futex_ref,
+                &target_ref,
+                u64::try_from(val).unwrap(),
+                u64::try_from(val2).unwrap(),
+            )?;
+            let n = if cmp { woken.strict_add(requeued) } else { woken };
+            ecx.write_scalar(Scalar::from_target_usize(n, ecx), dest)?;
+        }
+        // FUTEX_LOCK_PI: (int *addr, int op = FUTEX_LOCK_PI, int _ignored, const timespec *timeout)
+        // Acquires the priority-inheritance futex at addr, whose value is the TID of its owner
+        // (0 if it is free) plus the FUTEX_WAITERS and FUTEX_OWNER_DIED bits. Userspace only calls
+        // this after failing to take the futex with a compare-exchange. If another thread owns
+        // the futex, sets FUTEX_WAITERS and blocks until FUTEX_UNLOCK_PI makes us the owner, or
+        // *timeout expires. The timeout is absolute and measured against CLOCK_REALTIME;
+        // `timeout == null` for an infinite timeout.
+        // FUTEX_TRYLOCK_PI: (int *addr, int op = FUTEX_TRYLOCK_PI)
+        // Same as FUTEX_LOCK_PI, but fails with EAGAIN instead of blocking.
+        //
+        // Miri does not actually do priority inheritance: waiters are woken up in FIFO order, and
+        // the owner does not get boosted.
+        op if op == futex_lock_pi || op == futex_trylock_pi => {
+            let trylock = op == futex_trylock_pi;
+
+            let timeout = if trylock {
+                None
+            } else {
+                let [_, _, _, _, timeout] =
+                    check_min_arg_count("`syscall(SYS_futex, FUTEX_LOCK_PI, ...)`", args)?;
+                let timeout = ecx.deref_pointer_as(timeout, ecx.libc_ty_layout("timespec"))?;
+                if ecx.ptr_is_null(timeout.ptr())? {
+                    None
+                } else {
+                    let Some(duration) = ecx.read_timespec(&timeout)? else {
+                        return ecx.set_last_error_and_return(LibcError("EINVAL"), dest);
+                    };
+                    ecx.check_no_isolation(
+                        "`futex` syscall with `op=FUTEX_LOCK_PI` and non-null timeout",
+                    )?;
+                    Some((TimeoutClock::RealTime, TimeoutAnchor::Absolute, duration))
+                }
+            };
+
+            let tid = thread_tid(ecx, ecx.active_thread());
+            // Same as for FUTEX_WAIT, we have to see the latest value of the futex, so that we
+            // do not go to sleep on a futex that was just unlocked.
+            ecx.atomic_fence(AtomicFenceOrd::SeqCst)?;
+            let futex_val = ecx.read_scalar_atomic(&addr, AtomicReadOrd::Acquire)?.to_u32()?;
+            // This cannot fail since we already did an atomic acquire read on that pointer.
+            let futex_ref = ecx
+                .get_sync_or_init(addr.ptr(), |_| LinuxFutex { futex: Default::default() })
+                .unwrap()
+                .futex
+                .clone();
+
+            let owner = futex_val & FUTEX_TID_MASK;
+            if owner == 0 {
+                // The futex is free, so we take it. We keep FUTEX_OWNER_DIED so that userspace can
+                // tell that the previous owner died.
+                let waiters_flag =
+                    if ecx.futex_waiters(&futex_ref).is_empty() { 0 } else { FUTEX_WAITERS };
+                let new_val = tid | waiters_flag | (futex_val & FUTEX_OWNER_DIED);
+                ecx.write_scalar_atomic(Scalar::from_u32(new_val), &addr, AtomicWriteOrd::Relaxed)?;
+                ecx.write_scalar(Scalar::from_target_isize(0, ecx), dest)?;
+                return interp_ok(());
+            }
+            if owner == tid {
+                return ecx.set_last_error_and_return(LibcError("EDEADLK"), dest);
+            }
+            if tid_thread(ecx, owner).is_none_or(|owner| ecx.machine.threads.has_terminated(owner))
+            {
+                // The owner does not exist (anymore).
+                return ecx.set_last_error_and_return(LibcError("ESRCH"), dest);
+            }
+            if trylock {
+                return ecx.set_last_error_and_return(LibcError("EAGAIN"), dest);
+            }
+
+            // Make the owner unlock through the kernel, and wait for it to hand us the futex.
+            ecx.write_scalar_atomic(
+                Scalar::from_u32(futex_val | FUTEX_WAITERS),
+                &addr,
+                AtomicWriteOrd::Relaxed,
+            )?;
+            ecx.futex_wait(
+                futex_ref,
+                u32::MAX, // bitset
+                timeout,
+                Scalar::from_target_isize(0, ecx),  // retval_succ
+                Scalar::from_target_isize(-1, ecx), // retval_timeout
+                dest.clone(),
+                LibcError("ETIMEDOUT"), // errno_timeout
+            );
+        }
+        // FUTEX_UNLOCK_PI: (int *addr, int op = FUTEX_UNLOCK_PI)
+        // Releases the priority-inheritance futex at addr, which must be owned by the current
+        // thread. If there are waiters, the first one becomes the new owner and is woken up.
+        op if op == futex_unlock_pi => {
+            let tid = thread_tid(ecx, ecx.active_thread());
+            // This pairs with the fence in FUTEX_LOCK_PI.
+            ecx.atomic_fence(AtomicFenceOrd::SeqCst)?;
+            let futex_val = ecx.read_scalar_atomic(&addr, AtomicReadOrd::Acquire)?.to_u32()?;
+            if futex_val & FUTEX_TID_MASK != tid {
+                return ecx.set_last_error_and_return(LibcError("EPERM"), dest);
+            }
+            // This cannot fail since we already did an atomic acquire read on that pointer.
+            let futex_ref = ecx
+                .get_sync_or_init(addr.ptr(), |_| LinuxFutex { futex: Default::default() })
+                .unwrap()
+                .futex
+                .clone();
+            futex_pi_handoff(ecx, &addr, &futex_ref, 0)?;
+            ecx.write_scalar(Scalar::from_target_isize(0, ecx), dest)?;
+        }
         op => throw_unsup_format!("Miri does not support `futex` syscall with op={}", op),
     }
 
     interp_ok(())
 }
+
+/// Implementation of the SYS_set_robust_list syscall.
+/// `args` is the arguments *including* the syscall number.
+pub fn set_robust_list<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
+    args: &[OpTy<'tcx>],
+    dest: &MPlaceTy<'tcx>,
+) -> InterpResult<'tcx> {
+    let [_, head, len] = check_min_arg_count("`syscall(SYS_set_robust_list, ...)`", args)?;
+    let head = ecx.read_pointer(head)?;
+    let len = ecx.read_target_usize(len)?;
+
+    // The head is a pointer to the first entry, the offset of the futex in each entry, and a
+    // pointer to an entry that is about to be added or removed.
+    if len != ecx.pointer_size().bytes().strict_mul(3) {
+        return ecx.set_last_error_and_return(LibcError("EINVAL"), dest);
+    }
+    ecx.active_thread_mut().robust_list = Some(head);
+
+    ecx.write_scalar(Scalar::from_target_isize(0, ecx), dest)?;
+    interp_ok(())
+}
+
+/// Implementation of the SYS_get_robust_list syscall.
+/// `args` is the arguments *including* the syscall number.
+pub fn get_robust_list<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
+    args: &[OpTy<'tcx>],
+    dest: &MPlaceTy<'tcx>,
+) -> InterpResult<'tcx> {
+    let [_, pid, head_ptr, len_ptr] =
+        check_min_arg_count("`syscall(SYS_get_robust_list, ...)`", args)?;
+    let pid = ecx.read_scalar(pid)?.to_i32()?;
+    let head_place = ecx.deref_pointer_as(head_ptr, ecx.machine.layouts.mut_raw_ptr)?;
+    let len_place = ecx.deref_pointer_as(len_ptr, ecx.machine.layouts.usize)?;
+
+    // `pid == 0` means the current thread.
+    let thread = if pid == 0 {
+        Some(ecx.active_thread())
+    } else {
+        u32::try_from(pid).ok().and_then(|tid| tid_thread(ecx, tid))
+    };
+    let Some(thread) = thread else {
+        return ecx.set_last_error_and_return(LibcError("ESRCH"), dest);
+    };
+
+    let head = ecx.machine.threads.robust_list(thread);
+    ecx.write_pointer(head.unwrap_or(Pointer::null()), &head_place)?;
+    let len = ecx.pointer_size().bytes().strict_mul(3);
+    ecx.write_scalar(Scalar::from_target_usize(len, ecx), &len_place)?;
+
+    ecx.write_scalar(Scalar::from_target_isize(0, ecx), dest)?;
+    interp_ok(())
+}
+
+/// Called when a thread exits, after its TLS destructors ran. Like the kernel, we mark the futexes
+/// on the thread's robust list that it still owns with `FUTEX_OWNER_DIED`, and let a waiter know.
+pub fn exit_robust_list<'tcx>(ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+    let Some(head) = ecx.active_thread_mut().robust_list.take() else {
+        return interp_ok(());
+    };
+    let ptr_size = ecx.pointer_size();
+    let ptr_layout = ecx.machine.layouts.mut_raw_ptr;
+
+    let mut entry = ecx.read_pointer(&ecx.ptr_to_mplace(head, ptr_layout))?;
+    let futex_offset = ecx.read_target_isize(
+        &ecx.ptr_to_mplace(head.wrapping_offset(ptr_size, ecx), ecx.machine.layouts.isize),
+    )?;
+    let pending = ecx.read_pointer(&ecx.ptr_to_mplace(
+        head.wrapping_offset(Size::from_bytes(ptr_size.bytes().strict_mul(2)), ecx),
+        ptr_layout,
+    ))?;
+
+    // The list is circular, and ends when we are back at the head.
+    let mut limit = ROBUST_LIST_LIMIT;
+    while entry.addr() != head.addr() && limit > 0 {
+        limit = limit.strict_sub(1);
+        // The lowest bit of each pointer says whether the next entry is a PI futex.
+        let (entry_ptr, pi) = robust_list_entry(ecx, entry);
+        let next = ecx.read_pointer(&ecx.ptr_to_mplace(entry_ptr, ptr_layout))?;
+        // The pending entry is handled below.
+        if entry_ptr.addr() != pending.addr() {
+            handle_futex_death(ecx, entry_ptr.wrapping_signed_offset(futex_offset, ecx), pi)?;
+        }
+        entry = next;
+    }
+    if !ecx.ptr_is_null(pending)? {
+        let (pending_ptr, pi) = robust_list_entry(ecx, pending);
+        handle_futex_death(ecx, pending_ptr.wrapping_signed_offset(futex_offset, ecx), pi)?;
+    }
+    interp_ok(())
+}
+
+/// Splits a robust list pointer into the actual pointer and the flag that says whether it points
+/// to a PI futex.
+fn robust_list_entry<'tcx>(ecx: &MiriInterpCx<'tcx>, ptr: Pointer) -> (Pointer, bool) {
+    if ptr.addr().bytes() & 1 == 0 {
+        (ptr, false)
+    } else {
+        (ptr.wrapping_signed_offset(-1, ecx), true)
+    }
+}
+
+/// Clean up a futex on the robust list of an exiting thread.
+fn handle_futex_death<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
+    addr: Pointer,
+    pi: bool,
+) -> InterpResult<'tcx> {
+    let tid = thread_tid(ecx, ecx.active_thread());
+    let addr = ecx.ptr_to_mplace(addr, ecx.machine.layouts.u32);
+    let futex_val = ecx.read_scalar_atomic(&addr, AtomicReadOrd::Acquire)?.to_u32()?;
+    if futex_val & FUTEX_TID_MASK != tid {
+        // We do not own this futex, so there is nothing to do.
+        return interp_ok(());
+    }
+    // This cannot fail since we already did an atomic acquire read on that pointer.
+    let futex_ref = ecx
+        .get_sync_or_init(addr.ptr(), |_| LinuxFutex { futex: Default::default() })
+        .unwrap()
+        .futex
+        .clone();
+    if pi {
+        // Waiters on a PI futex are handed the futex directly.
+        futex_pi_handoff(ecx, &addr, &futex_ref, FUTEX_OWNER_DIED)?;
+    } else {
+        // Mark the futex as free, and wake up a waiter so that it can take it.
+        let new_val = (futex_val & FUTEX_WAITERS) | FUTEX_OWNER_DIED;
+        ecx.write_scalar_atomic(Scalar::from_u32(new_val), &addr, AtomicWriteOrd::Release)?;
+        if futex_val & FUTEX_WAITERS != 0 {
+            ecx.futex_wake(&futex_ref, u32::MAX)?;
+        }
+    }
+    interp_ok(())
+}
@@ -3,7 +3,7 @@ use rustc_span::Symbol;
 
 use crate::helpers::check_min_arg_count;
 use crate::shims::unix::linux_like::eventfd::EvalContextExt as _;
-use crate::shims::unix::linux_like::sync::futex;
+use crate::shims::unix::linux_like::sync::{futex, get_robust_list, set_robust_list};
 use crate::*;
 
 pub fn syscall<'tcx>(
@@ -24,6 +24,8 @@ pub fn syscall<'tcx>(
     let sys_getrandom = ecx.eval_libc("SYS_getrandom").to_target_usize(ecx)?;
     let sys_futex = ecx.eval_libc("SYS_futex").to_target_usize(ecx)?;
     let sys_eventfd2 = ecx.eval_libc("SYS_eventfd2").to_target_usize(ecx)?;
+    let sys_set_robust_list = ecx.eval_libc("SYS_set_robust_list").to_target_usize(ecx)?;
+    let sys_get_robust_list = ecx.eval_libc("SYS_get_robust_list").to_target_usize(ecx)?;
 
     let [op] = check_min_arg_count("syscall", args)?;
     match ecx.read_target_usize(op)? {
@@ -48,6 +50,13 @@ pub fn syscall<'tcx>(
         num if num == sys_futex => {
             futex(ecx, args, dest)?;
         }
+        // Robust futex lists are used by robust mutexes, to detect when their owner died.
+        num if num == sys_set_robust_list => {
+            set_robust_list(ecx, args, dest)?;
+        }
+        num if num == sys_get_robust_list => {
+            get_robust_list(ecx, args, dest)?;
+        }
         num if num == sys_eventfd2 => {
             let [_, initval, flags] = check_min_arg_count("syscall(SYS_evetfd2, ...)", args)?;
 
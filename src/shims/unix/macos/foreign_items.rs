@@ -1,6 +1,7 @@
 use rustc_abi::ExternAbi;
 use rustc_span::Symbol;
 
+use super::kqueue::EvalContextExt as _;
 use super::sync::EvalContextExt as _;
 use crate::shims::unix::*;
 use crate::*;
@@ -74,6 +75,18 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.write_scalar(result, dest)?;
             }
 
+            // kqueue
+            "kqueue" => {
+                let [] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                let result = this.kqueue()?;
+                this.write_scalar(result, dest)?;
+            }
+            "kevent" => {
+                let [kq, changelist, nchanges, eventlist, nevents, timeout] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                this.kevent(kq, changelist, nchanges, eventlist, nevents, timeout, dest)?;
+            }
+
             // Environment related shims
             "_NSGetEnviron" => {
                 let [] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
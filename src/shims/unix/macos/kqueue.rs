@@ -0,0 +1,555 @@
+//! This implements kqueue, the event notification interface of macOS, for `EVFILT_READ` and
+//! `EVFILT_WRITE` on file descriptions, and for `EVFILT_USER`. Like epoll, a kqueue relies on
+//! `get_poll_ready_events` for the readiness of file descriptions and gets notified by
+//! `check_and_update_readiness`. Unlike epoll, events are level-triggered unless `EV_CLEAR` is
+//! set, so `kevent` checks the readiness of all registered file descriptions whenever it is called.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io;
+use std::rc::{Rc, Weak};
+
+use crate::concurrency::VClock;
+use crate::shims::files::{FdId, FileDescription, FileDescriptionRef, WeakFileDescriptionRef};
+use crate::shims::unix::UnixFileDescription;
+use crate::shims::unix::poll::{deadline_after, deadline_passed, deadline_timeout};
+use crate::*;
+
+/// A `Kqueue` file descriptor holds the events registered with `kevent`.
+#[derive(Debug, Default)]
+struct Kqueue {
+    /// The registered events. Each entry is identified by the `ident` and `filter` fields of
+    /// the `kevent` that registered it.
+    interest_list: RefCell<BTreeMap<(u64, i16), Rc<RefCell<KqueueEventInterest>>>>,
+    /// A list of thread ids blocked on this kqueue instance.
+    thread_id: RefCell<Vec<ThreadId>>,
+}
+
+/// KqueueEventInterest is created by a `kevent` call with `EV_ADD`, and stores what is needed
+/// to decide whether the event should be reported.
+///
+/// For more information see the kqueue man page:
+/// <https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kqueue.2.html>
+#[derive(Debug)]
+pub struct KqueueEventInterest {
+    /// The file description whose readiness is watched, or `None` for `EVFILT_USER` events.
+    weak_fd: Option<WeakFileDescriptionRef>,
+    /// The `EV_CLEAR`, `EV_ONESHOT` and `EV_DISPATCH` flags of this event.
+    flags: u16,
+    /// The address of the `udata` field, which is returned unchanged.
+    udata: u64,
+    /// Disabled events are never reported.
+    enabled: bool,
+    /// Whether anything happened since this event was last reported. Events with `EV_CLEAR` are
+    /// only reported if this is set, and `EVFILT_USER` events only once they were triggered.
+    triggered: bool,
+    /// The release clocks of everything that triggered this event, joined together. `kevent`
+    /// acquires it when it reports the event.
+    clock: VClock,
+    /// The kqueue file description that this event is registered under.
+    weak_kq: WeakFileDescriptionRef,
+}
+
+/// A change requested in the `changelist` of `kevent`.
+pub struct KeventChange {
+    ident: u64,
+    filter: i16,
+    flags: u16,
+    fflags: u32,
+    udata: u64,
+}
+
+impl FileDescription for Kqueue {
+    fn name(&self) -> &'static str {
+        "kqueue"
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        _ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        interp_ok(Ok(()))
+    }
+
+    fn as_unix(&self) -> &dyn UnixFileDescription {
+        self
+    }
+}
+
+impl UnixFileDescription for Kqueue {}
+
+/// The table of all KqueueEventInterest watching a file description.
+/// The BTreeMap key is the FdId of the watched file description.
+pub struct KqueueInterestTable(BTreeMap<FdId, Vec<Weak<RefCell<KqueueEventInterest>>>>);
+
+impl KqueueInterestTable {
+    pub(crate) fn new() -> Self {
+        KqueueInterestTable(BTreeMap::new())
+    }
+
+    pub fn insert_kqueue_interest(
+        &mut self,
+        id: FdId,
+        interest: Weak<RefCell<KqueueEventInterest>>,
+    ) {
+        self.0.entry(id).or_default().push(interest);
+    }
+
+    pub fn get_kqueue_interest(
+        &self,
+        id: FdId,
+    ) -> Option<&Vec<Weak<RefCell<KqueueEventInterest>>>> {
+        self.0.get(&id)
+    }
+
+    /// Remove the entries of events that are no longer registered.
+    pub fn remove_stale(&mut self, id: FdId) {
+        if let Some(interests) = self.0.get_mut(&id) {
+            interests.retain(|interest| interest.upgrade().is_some());
+        }
+    }
+
+    pub fn remove(&mut self, id: FdId) {
+        self.0.remove(&id);
+    }
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// This function returns a file descriptor referring to a new `Kqueue` instance.
+    ///
+    /// <https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kqueue.2.html>
+    fn kqueue(&mut self) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let fd = this.machine.fds.insert_new(Kqueue::default());
+        interp_ok(Scalar::from_i32(fd))
+    }
+
+    /// The `kevent()` function first applies the changes in `changelist` to the kqueue `kq`,
+    /// then waits for up to `nevents` events and stores them in `eventlist`.
+    ///
+    /// If applying a change fails, or the change has the `EV_RECEIPT` flag, an event with the
+    /// `EV_ERROR` flag and the error code (or 0) in `data` is stored in `eventlist` instead, and
+    /// `kevent()` returns without waiting. If there is no room for such an event, `kevent()`
+    /// fails with the error.
+    ///
+    /// A `timeout` of null means that `kevent()` blocks until an event is available.
+    ///
+    /// <https://developer.apple.com/library/archive/documentation/System/Conceptual/ManPages_iPhoneOS/man2/kqueue.2.html>
+    fn kevent(
+        &mut self,
+        kq: &OpTy<'tcx>,
+        changelist: &OpTy<'tcx>,
+        nchanges: &OpTy<'tcx>,
+        eventlist: &OpTy<'tcx>,
+        nevents: &OpTy<'tcx>,
+        timeout: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let kq_value = this.read_scalar(kq)?.to_i32()?;
+        let nchanges = this.read_scalar(nchanges)?.to_i32()?;
+        let nevents = this.read_scalar(nevents)?.to_i32()?;
+        let (Ok(nchanges), Ok(nevents)) = (u64::try_from(nchanges), u64::try_from(nevents)) else {
+            return this.set_last_error_and_return(LibcError("EINVAL"), dest);
+        };
+        let changelist =
+            this.deref_pointer_as(changelist, this.libc_array_ty_layout("kevent", nchanges))?;
+        let eventlist =
+            this.deref_pointer_as(eventlist, this.libc_array_ty_layout("kevent", nevents))?;
+
+        let timeout_ptr = this.read_pointer(timeout)?;
+        let timeout = if this.ptr_is_null(timeout_ptr)? {
+            None
+        } else {
+            let timeout = this.deref_pointer_as(timeout, this.libc_ty_layout("timespec"))?;
+            match this.read_timespec(&timeout)? {
+                Some(duration) => Some(duration),
+                None => return this.set_last_error_and_return(LibcError("EINVAL"), dest),
+            }
+        };
+
+        let Some(kq) = this.machine.fds.get(kq_value) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        if kq.downcast::<Kqueue>().is_none() {
+            throw_unsup_format!("non-kqueue FD passed to `kevent`");
+        }
+
+        let ev_error = this.eval_libc("EV_ERROR").to_u16()?;
+        let ev_receipt = this.eval_libc("EV_RECEIPT").to_u16()?;
+        let mut num_receipts: u64 = 0;
+        let mut array_iter = this.project_array_fields(&changelist)?;
+        while let Some((_, change)) = array_iter.next(this)? {
+            let change = read_kevent_change(&change, this)?;
+            let error = this.kevent_apply_change(&kq, &change)?;
+            if error.is_none() && change.flags & ev_receipt == 0 {
+                continue;
+            }
+            if num_receipts < nevents {
+                let data = error.map_or(0, |error| this.eval_libc_i32(error));
+                let event = this.project_index(&eventlist, num_receipts)?;
+                write_kevent(
+                    &event,
+                    change.ident,
+                    change.filter,
+                    change.flags | ev_error,
+                    data.into(),
+                    change.udata,
+                    this,
+                )?;
+                num_receipts = num_receipts.strict_add(1);
+            } else if let Some(error) = error {
+                return this.set_last_error_and_return(LibcError(error), dest);
+            }
+        }
+        // Like macOS, we do not wait for events if we reported errors or receipts.
+        if num_receipts > 0 || nevents == 0 {
+            return this.write_int(num_receipts, dest);
+        }
+
+        let deadline = deadline_after(timeout, this);
+        kevent_wait(kq.downgrade(), eventlist, deadline, dest, this)
+    }
+
+    /// Apply a change from the `changelist` of `kevent` to the kqueue `kq`. Returns the name of
+    /// the error if the change could not be applied.
+    fn kevent_apply_change(
+        &mut self,
+        kq: &FileDescriptionRef,
+        change: &KeventChange,
+    ) -> InterpResult<'tcx, Option<&'static str>> {
+        let this = self.eval_context_mut();
+        let kqueue = kq.downcast::<Kqueue>().unwrap();
+
+        let evfilt_read = this.eval_libc("EVFILT_READ").to_i16()?;
+        let evfilt_write = this.eval_libc("EVFILT_WRITE").to_i16()?;
+        let evfilt_user = this.eval_libc("EVFILT_USER").to_i16()?;
+        let ev_add = this.eval_libc("EV_ADD").to_u16()?;
+        let ev_delete = this.eval_libc("EV_DELETE").to_u16()?;
+        let ev_enable = this.eval_libc("EV_ENABLE").to_u16()?;
+        let ev_disable = this.eval_libc("EV_DISABLE").to_u16()?;
+        let ev_clear = this.eval_libc("EV_CLEAR").to_u16()?;
+        let ev_oneshot = this.eval_libc("EV_ONESHOT").to_u16()?;
+        let ev_dispatch = this.eval_libc("EV_DISPATCH").to_u16()?;
+        let ev_receipt = this.eval_libc("EV_RECEIPT").to_u16()?;
+        let note_trigger = this.eval_libc_u32("NOTE_TRIGGER");
+
+        let flags = change.flags;
+        let event_flags = ev_clear | ev_oneshot | ev_dispatch;
+        let remaining_flags =
+            flags & !(ev_add | ev_delete | ev_enable | ev_disable | ev_receipt | event_flags);
+        if remaining_flags != 0 {
+            throw_unsup_format!("kevent: flags {remaining_flags:#x} are unsupported");
+        }
+
+        // Look up the file description for filters that watch one.
+        let fd_ref = if change.filter == evfilt_user {
+            if change.fflags & !note_trigger != 0 {
+                throw_unsup_format!(
+                    "kevent: EVFILT_USER fflags {:#x} are unsupported",
+                    change.fflags & !note_trigger
+                );
+            }
+            None
+        } else if change.filter == evfilt_read || change.filter == evfilt_write {
+            if change.fflags != 0 {
+                throw_unsup_format!("kevent: fflags {:#x} are unsupported", change.fflags);
+            }
+            let fd_ref = i32::try_from(change.ident).ok().and_then(|fd| this.machine.fds.get(fd));
+            let Some(fd_ref) = fd_ref else {
+                return interp_ok(Some("EBADF"));
+            };
+            Some(fd_ref)
+        } else {
+            throw_unsup_format!("kevent: filter {} is unsupported", change.filter);
+        };
+
+        let key = (change.ident, change.filter);
+        let mut interest_list = kqueue.interest_list.borrow_mut();
+        // Events are removed when their file description is closed, even if the file descriptor
+        // has been reused since.
+        if let (Some(interest), Some(fd_ref)) = (interest_list.get(&key), &fd_ref) {
+            let is_stale = interest
+                .borrow()
+                .weak_fd
+                .as_ref()
+                .and_then(|weak_fd| weak_fd.upgrade())
+                .is_none_or(|fd| fd.get_id() != fd_ref.get_id());
+            if is_stale {
+                interest_list.remove(&key);
+            }
+        }
+
+        if flags & ev_delete != 0 {
+            if interest_list.remove(&key).is_none() {
+                return interp_ok(Some("ENOENT"));
+            }
+            if let Some(fd_ref) = fd_ref {
+                this.machine.kqueue_interests.remove_stale(fd_ref.get_id());
+            }
+            return interp_ok(None);
+        }
+
+        let interest = match interest_list.get(&key) {
+            Some(interest) => Rc::clone(interest),
+            None if flags & ev_add != 0 => {
+                // A new event reports the current readiness of its file description, so it syncs
+                // with the last readiness change.
+                let clock = fd_ref
+                    .as_ref()
+                    .and_then(|fd_ref| this.machine.readiness_clocks.get(&fd_ref.get_id()))
+                    .cloned()
+                    .unwrap_or_default();
+                let interest = Rc::new(RefCell::new(KqueueEventInterest {
+                    weak_fd: fd_ref.as_ref().map(|fd_ref| fd_ref.downgrade()),
+                    flags: 0,
+                    udata: 0,
+                    enabled: true,
+                    triggered: fd_ref.is_some(),
+                    clock,
+                    weak_kq: kq.downgrade(),
+                }));
+                if let Some(fd_ref) = &fd_ref {
+                    this.machine
+                        .kqueue_interests
+                        .insert_kqueue_interest(fd_ref.get_id(), Rc::downgrade(&interest));
+                }
+                interest_list.insert(key, Rc::clone(&interest));
+                interest
+            }
+            None => return interp_ok(Some("ENOENT")),
+        };
+        drop(interest_list);
+
+        let mut interest = interest.borrow_mut();
+        if flags & ev_add != 0 {
+            interest.flags = flags & event_flags;
+            interest.udata = change.udata;
+        }
+        if flags & ev_enable != 0 {
+            interest.enabled = true;
+            if fd_ref.is_some() {
+                // Report the current readiness again.
+                interest.triggered = true;
+            }
+        }
+        if flags & ev_disable != 0 {
+            interest.enabled = false;
+        }
+        let is_trigger = change.fflags & note_trigger != 0;
+        if is_trigger {
+            interest.triggered = true;
+            // The thread that reports the event syncs with the thread that triggered it.
+            this.release_clock(|clock| interest.clock.join(clock));
+        }
+        drop(interest);
+
+        // Threads waiting on this kqueue need to check again if an event might have become ready.
+        if is_trigger || flags & (ev_add | ev_enable) != 0 {
+            let waiters = std::mem::take(&mut *kqueue.thread_id.borrow_mut());
+            for thread_id in waiters {
+                this.unblock_thread(thread_id, BlockReason::Kqueue)?;
+            }
+        }
+        interp_ok(None)
+    }
+
+    /// Mark the events watching the file description with the given id as triggered, and let
+    /// the threads waiting on their kqueues check again whether they are ready. This is called
+    /// by `check_and_update_readiness`.
+    fn kqueue_notify_readiness(&mut self, id: FdId) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let mut waiters = Vec::new();
+        if let Some(interests) = this.machine.kqueue_interests.get_kqueue_interest(id) {
+            for weak_interest in interests {
+                let Some(interest) = weak_interest.upgrade() else { continue };
+                let mut interest = interest.borrow_mut();
+                interest.triggered = true;
+                this.release_clock(|clock| interest.clock.join(clock));
+                if let Some(kq) = interest.weak_kq.upgrade() {
+                    let kqueue = kq.downcast::<Kqueue>().unwrap();
+                    waiters.append(&mut kqueue.thread_id.borrow_mut());
+                }
+            }
+        }
+        waiters.sort();
+        waiters.dedup();
+        for thread_id in waiters {
+            this.unblock_thread(thread_id, BlockReason::Kqueue)?;
+        }
+        interp_ok(())
+    }
+}
+
+/// Read an entry of the `changelist` of `kevent`.
+fn read_kevent_change<'tcx>(
+    change: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, KeventChange> {
+    let ident = ecx.read_target_usize(&ecx.project_field_named(change, "ident")?)?;
+    let filter = ecx.read_scalar(&ecx.project_field_named(change, "filter")?)?.to_i16()?;
+    let flags = ecx.read_scalar(&ecx.project_field_named(change, "flags")?)?.to_u16()?;
+    let fflags = ecx.read_scalar(&ecx.project_field_named(change, "fflags")?)?.to_u32()?;
+    // We only remember the address of `udata`, so we expose its provenance like a
+    // pointer-to-integer cast does. The `udata` we return is then like an integer-to-pointer cast.
+    let udata = ecx.read_pointer(&ecx.project_field_named(change, "udata")?)?;
+    if let Some(Provenance::Concrete { alloc_id, tag }) = udata.provenance {
+        ecx.expose_ptr(alloc_id, tag)?;
+    }
+    interp_ok(KeventChange { ident, filter, flags, fflags, udata: udata.addr().bytes() })
+}
+
+/// Write an entry of the `eventlist` of `kevent`.
+fn write_kevent<'tcx>(
+    event: &MPlaceTy<'tcx>,
+    ident: u64,
+    filter: i16,
+    flags: u16,
+    data: i64,
+    udata: u64,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    ecx.write_int_fields_named(
+        &[
+            ("ident", ident.into()),
+            ("filter", filter.into()),
+            ("flags", flags.into()),
+            ("fflags", 0),
+            ("data", data.into()),
+        ],
+        event,
+    )?;
+    let udata = ecx.ptr_from_addr_cast(udata)?;
+    ecx.write_pointer(udata, &ecx.project_field_named(event, "udata")?)
+}
+
+/// Store the ready events of the kqueue in `eventlist`. If no event is ready, block until one
+/// is or the `deadline` passes.
+fn kevent_wait<'tcx>(
+    weak_kq: WeakFileDescriptionRef,
+    eventlist: MPlaceTy<'tcx>,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let Some(kq) = weak_kq.upgrade() else {
+        throw_unsup_format!("kqueue FD got closed while blocking.")
+    };
+    let kqueue = kq.downcast::<Kqueue>().unwrap();
+
+    let evfilt_read = ecx.eval_libc("EVFILT_READ").to_i16()?;
+    let ev_clear = ecx.eval_libc("EV_CLEAR").to_u16()?;
+    let ev_oneshot = ecx.eval_libc("EV_ONESHOT").to_u16()?;
+    let ev_dispatch = ecx.eval_libc("EV_DISPATCH").to_u16()?;
+    let ev_eof = ecx.eval_libc("EV_EOF").to_u16()?;
+
+    let max_events = eventlist.len(ecx)?;
+    let mut ready_events = Vec::new();
+    {
+        let mut interest_list = kqueue.interest_list.borrow_mut();
+        let mut removed = Vec::new();
+        for (&key, interest) in interest_list.iter() {
+            if u64::try_from(ready_events.len()).unwrap() == max_events {
+                break;
+            }
+            let (ident, filter) = key;
+            let mut interest = interest.borrow_mut();
+            if !interest.enabled {
+                continue;
+            }
+            let mut flags = interest.flags;
+            let is_ready = match &interest.weak_fd {
+                // `EVFILT_USER` events are ready once triggered.
+                None => interest.triggered,
+                Some(weak_fd) => {
+                    let Some(fd) = weak_fd.upgrade() else {
+                        // The file description was closed, which removes the event.
+                        removed.push(key);
+                        continue;
+                    };
+                    let readiness = fd.as_unix().get_poll_ready_events()?;
+                    // Like on macOS, reaching the end of the stream and errors make the file
+                    // description ready, since the next operation will not block.
+                    let (is_ready, is_eof) = if filter == evfilt_read {
+                        (
+                            readiness.epollin || readiness.epollhup || readiness.epollerr,
+                            readiness.epollrdhup || readiness.epollhup,
+                        )
+                    } else {
+                        (
+                            readiness.epollout || readiness.epollhup || readiness.epollerr,
+                            readiness.epollhup,
+                        )
+                    };
+                    if is_eof {
+                        flags |= ev_eof;
+                    }
+                    is_ready && (interest.flags & ev_clear == 0 || interest.triggered)
+                }
+            };
+            if !is_ready {
+                continue;
+            }
+            // We do not track how many bytes can be read or written, so `data` is always 0.
+            ready_events.push((ident, filter, flags, interest.udata));
+            ecx.acquire_clock(&interest.clock);
+            if interest.flags & ev_clear != 0 {
+                interest.triggered = false;
+            }
+            if interest.flags & ev_oneshot != 0 {
+                removed.push(key);
+            } else if interest.flags & ev_dispatch != 0 {
+                interest.enabled = false;
+            }
+        }
+        for key in removed {
+            interest_list.remove(&key);
+        }
+    }
+
+    if ready_events.is_empty() && !deadline_passed(deadline, ecx) {
+        kqueue.thread_id.borrow_mut().push(ecx.active_thread());
+        let dest = dest.clone();
+        ecx.block_thread(
+            BlockReason::Kqueue,
+            deadline_timeout(deadline),
+            callback!(
+                @capture<'tcx> {
+                    weak_kq: WeakFileDescriptionRef,
+                    eventlist: MPlaceTy<'tcx>,
+                    deadline: Option<u64>,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    // Something changed, so check again.
+                    kevent_wait(weak_kq, eventlist, deadline, &dest, this)
+                }
+                @timeout = |this| {
+                    if let Some(kq) = weak_kq.upgrade() {
+                        let thread_id = this.active_thread();
+                        kq.downcast::<Kqueue>()
+                            .unwrap()
+                            .thread_id
+                            .borrow_mut()
+                            .retain(|&id| id != thread_id);
+                    }
+                    // Check one last time, but do not block again.
+                    kevent_wait(weak_kq, eventlist, Some(0), &dest, this)
+                }
+            ),
+        );
+        return interp_ok(());
+    }
+
+    let num_events = u64::try_from(ready_events.len()).unwrap();
+    for (idx, (ident, filter, flags, udata)) in (0..).zip(ready_events) {
+        let event = ecx.project_index(&eventlist, idx)?;
+        write_kevent(&event, ident, filter, flags, 0, udata, ecx)?;
+    }
+    ecx.write_int(num_events, dest)
+}
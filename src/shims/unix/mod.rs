@@ -4,6 +4,10 @@ mod env;
 mod fd;
 mod fs;
 mod mem;
+mod netdb;
+mod poll;
+mod process;
+mod socket;
 mod sync;
 mod thread;
 mod unnamed_socket;
@@ -20,7 +24,14 @@ pub use self::env::{EvalContextExt as _, UnixEnvVars};
 pub use self::fd::{EvalContextExt as _, UnixFileDescription};
 pub use self::fs::{DirTable, EvalContextExt as _};
 pub use self::linux_like::epoll::EpollInterestTable;
+pub(crate) use self::linux_like::sync::exit_robust_list;
+pub use self::macos::kqueue::KqueueInterestTable;
 pub use self::mem::EvalContextExt as _;
+pub use self::netdb::EvalContextExt as _;
+pub use self::poll::EvalContextExt as _;
+pub(crate) use self::poll::{deadline_after, deadline_passed, deadline_timeout};
+pub use self::process::{CommandScript, EvalContextExt as _, ProcessTable};
+pub use self::socket::{EvalContextExt as _, SocketTable};
 pub use self::sync::EvalContextExt as _;
 pub use self::thread::{EvalContextExt as _, ThreadNameResult};
 pub use self::unnamed_socket::EvalContextExt as _;
@@ -0,0 +1,226 @@
+//! This implements name resolution with `getaddrinfo`. The host resolver is never consulted:
+//! numeric addresses and `localhost` are resolved internally, and any other name only resolves
+//! to the addresses configured with `-Zmiri-resolve`. This keeps the result deterministic.
+
+use std::ffi::OsStr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str;
+
+use crate::shims::alloc::EvalContextExt as _;
+use crate::shims::unix::socket::EvalContextExt as _;
+use crate::*;
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// For more information on the arguments see the getaddrinfo manpage:
+    /// <https://man7.org/linux/man-pages/man3/getaddrinfo.3.html>
+    fn getaddrinfo(
+        &mut self,
+        node: &OpTy<'tcx>,
+        service: &OpTy<'tcx>,
+        hints: &OpTy<'tcx>,
+        res: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let node = this.read_pointer(node)?;
+        let service = this.read_pointer(service)?;
+        let hints = this.read_pointer(hints)?;
+        let res = this.read_pointer(res)?;
+
+        let af_unspec = this.eval_libc_i32("AF_UNSPEC");
+        let (flags, family, socktype, protocol) = if this.ptr_is_null(hints)? {
+            (0, af_unspec, 0, 0)
+        } else {
+            let hints = this.ptr_to_mplace(hints, this.libc_ty_layout("addrinfo"));
+            let read_field = |name: &str| -> InterpResult<'tcx, i32> {
+                this.read_scalar(&this.project_field_named(&hints, name)?)?.to_i32()
+            };
+            (
+                read_field("ai_flags")?,
+                read_field("ai_family")?,
+                read_field("ai_socktype")?,
+                read_field("ai_protocol")?,
+            )
+        };
+
+        // Interpret the flags. Every flag we recognize is "subtracted" from `flags`, so
+        // if there is anything left at the end, that's an unsupported flag.
+        let mut remaining_flags = flags;
+        let ai_passive = this.eval_libc_i32("AI_PASSIVE");
+        let is_passive = remaining_flags & ai_passive == ai_passive;
+        remaining_flags &= !ai_passive;
+        let ai_numerichost = this.eval_libc_i32("AI_NUMERICHOST");
+        let is_numeric_host = remaining_flags & ai_numerichost == ai_numerichost;
+        remaining_flags &= !ai_numerichost;
+        let ai_numericserv = this.eval_libc_i32("AI_NUMERICSERV");
+        let is_numeric_service = remaining_flags & ai_numericserv == ai_numericserv;
+        remaining_flags &= !ai_numericserv;
+        // The loopback interface always exists, so `AI_ADDRCONFIG` does not change anything.
+        remaining_flags &= !this.eval_libc_i32("AI_ADDRCONFIG");
+        if remaining_flags != 0 {
+            throw_unsup_format!("getaddrinfo: flags {remaining_flags:#x} are not supported");
+        }
+
+        let (want_v4, want_v6) = if family == af_unspec {
+            (true, true)
+        } else if family == this.eval_libc_i32("AF_INET") {
+            (true, false)
+        } else if family == this.eval_libc_i32("AF_INET6") {
+            (false, true)
+        } else {
+            return interp_ok(Scalar::from_i32(this.eval_libc_i32("EAI_FAMILY")));
+        };
+
+        // Without a socket type, we return one entry for each of TCP and UDP.
+        let sock_stream = this.eval_libc_i32("SOCK_STREAM");
+        let sock_dgram = this.eval_libc_i32("SOCK_DGRAM");
+        let ipproto_tcp = this.eval_libc_i32("IPPROTO_TCP");
+        let ipproto_udp = this.eval_libc_i32("IPPROTO_UDP");
+        let socktypes: Vec<(i32, i32)> = [(sock_stream, ipproto_tcp), (sock_dgram, ipproto_udp)]
+            .into_iter()
+            .filter(|&(entry_socktype, entry_protocol)| {
+                (socktype == 0 || socktype == entry_socktype)
+                    && (protocol == 0 || protocol == entry_protocol)
+            })
+            .collect();
+        if socktypes.is_empty() {
+            return interp_ok(Scalar::from_i32(this.eval_libc_i32("EAI_SOCKTYPE")));
+        }
+
+        let port = if this.ptr_is_null(service)? {
+            0
+        } else {
+            let service = this.read_c_str(service)?;
+            match str::from_utf8(service).ok().and_then(|service| service.parse::<u16>().ok()) {
+                Some(port) => port,
+                None if is_numeric_service =>
+                    return interp_ok(Scalar::from_i32(this.eval_libc_i32("EAI_NONAME"))),
+                None =>
+                    throw_unsup_format!(
+                        "getaddrinfo: service names are not supported, only port numbers"
+                    ),
+            }
+        };
+
+        let ips: Vec<IpAddr> = if this.ptr_is_null(node)? {
+            if this.ptr_is_null(service)? {
+                return interp_ok(Scalar::from_i32(this.eval_libc_i32("EAI_NONAME")));
+            }
+            if is_passive {
+                vec![Ipv4Addr::UNSPECIFIED.into(), Ipv6Addr::UNSPECIFIED.into()]
+            } else {
+                vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()]
+            }
+        } else {
+            let node = String::from_utf8_lossy(this.read_c_str(node)?).into_owned();
+            if let Ok(ip) = node.parse::<IpAddr>() {
+                vec![ip]
+            } else if is_numeric_host {
+                vec![]
+            } else if node.eq_ignore_ascii_case("localhost") {
+                vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()]
+            } else {
+                this.machine
+                    .resolve
+                    .iter()
+                    .filter(|(name, _)| name.eq_ignore_ascii_case(&node))
+                    .map(|&(_, ip)| ip)
+                    .collect()
+            }
+        };
+        let ips: Vec<IpAddr> =
+            ips.into_iter().filter(|ip| if ip.is_ipv4() { want_v4 } else { want_v6 }).collect();
+        if ips.is_empty() {
+            return interp_ok(Scalar::from_i32(this.eval_libc_i32("EAI_NONAME")));
+        }
+
+        // Build the list back to front, so that every entry can point to the next one.
+        let mut list = Pointer::null();
+        for &ip in ips.iter().rev() {
+            for &(socktype, protocol) in socktypes.iter().rev() {
+                let addr = SocketAddr::new(ip, port);
+                list = this.alloc_addrinfo(&addr, flags, socktype, protocol, list)?;
+            }
+        }
+        let res = this.ptr_to_mplace(res, this.machine.layouts.mut_raw_ptr);
+        this.write_pointer(list, &res)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn freeaddrinfo(&mut self, res: &OpTy<'tcx>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let mut entry = this.read_pointer(res)?;
+        let addrinfo_layout = this.libc_ty_layout("addrinfo");
+        while !this.ptr_is_null(entry)? {
+            let addrinfo = this.ptr_to_mplace(entry, addrinfo_layout);
+            let next = this.read_pointer(&this.project_field_named(&addrinfo, "ai_next")?)?;
+            this.free(entry)?;
+            entry = next;
+        }
+
+        interp_ok(())
+    }
+
+    fn gai_strerror(&mut self, errcode: &OpTy<'tcx>) -> InterpResult<'tcx, Pointer> {
+        let this = self.eval_context_mut();
+
+        let errcode = this.read_scalar(errcode)?.to_i32()?;
+        // These are the messages of glibc. We only know about the errors that we report.
+        let msg = [
+            ("EAI_NONAME", "Name or service not known"),
+            ("EAI_FAMILY", "ai_family not supported"),
+            ("EAI_SOCKTYPE", "ai_socktype not supported"),
+        ]
+        .into_iter()
+        .find(|&(name, _)| this.eval_libc_i32(name) == errcode)
+        .map_or("Unknown error", |(_, msg)| msg);
+
+        this.alloc_os_str_as_c_str(OsStr::new(msg), MiriMemoryKind::Machine.into())
+    }
+
+    /// Allocate an `addrinfo` for `addr`, with the address stored in the same allocation. The
+    /// allocation is freed with `freeaddrinfo`.
+    fn alloc_addrinfo(
+        &mut self,
+        addr: &SocketAddr,
+        flags: i32,
+        socktype: i32,
+        protocol: i32,
+        next: Pointer,
+    ) -> InterpResult<'tcx, Pointer> {
+        let this = self.eval_context_mut();
+
+        let addrinfo_layout = this.libc_ty_layout("addrinfo");
+        let (sockaddr_layout, family) = match addr {
+            SocketAddr::V4(_) => (this.libc_ty_layout("sockaddr_in"), "AF_INET"),
+            SocketAddr::V6(_) => (this.libc_ty_layout("sockaddr_in6"), "AF_INET6"),
+        };
+        let sockaddr_offset = addrinfo_layout.size.align_to(sockaddr_layout.align.abi);
+        let ptr = this
+            .malloc((sockaddr_offset + sockaddr_layout.size).bytes(), /* zero_init */ true)?;
+
+        let sockaddr_ptr = ptr.wrapping_offset(sockaddr_offset, this);
+        this.write_sockaddr(addr, &this.ptr_to_mplace(sockaddr_ptr, sockaddr_layout))?;
+
+        let addrinfo = this.ptr_to_mplace(ptr, addrinfo_layout);
+        let family = this.eval_libc_i32(family);
+        this.write_int_fields_named(
+            &[
+                ("ai_flags", flags.into()),
+                ("ai_family", family.into()),
+                ("ai_socktype", socktype.into()),
+                ("ai_protocol", protocol.into()),
+                ("ai_addrlen", sockaddr_layout.size.bytes().into()),
+            ],
+            &addrinfo,
+        )?;
+        this.write_pointer(sockaddr_ptr, &this.project_field_named(&addrinfo, "ai_addr")?)?;
+        this.write_pointer(Pointer::null(), &this.project_field_named(&addrinfo, "ai_canonname")?)?;
+        this.write_pointer(next, &this.project_field_named(&addrinfo, "ai_next")?)?;
+
+        interp_ok(ptr)
+    }
+}
@@ -0,0 +1,428 @@
+//! This implements `poll`, `ppoll`, `select` and `pselect` on top of the readiness tracking used
+//! by epoll: a file description is ready if `get_poll_ready_events` says so. Whenever the
+//! readiness of some file description changes, all threads blocked in one of these functions
+//! wake up and check again.
+
+use std::time::Duration;
+
+use crate::shims::unix::linux_like::epoll::EpollReadyEvents;
+use crate::shims::unix::*;
+use crate::*;
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// For more information on the arguments see the poll manpage:
+    /// <https://man7.org/linux/man-pages/man2/poll.2.html>
+    fn poll(
+        &mut self,
+        fds: &OpTy<'tcx>,
+        nfds: &OpTy<'tcx>,
+        timeout: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let timeout = this.read_scalar(timeout)?.to_i32()?;
+        // A negative timeout means that there is no timeout.
+        let timeout = u64::try_from(timeout).ok().map(Duration::from_millis);
+
+        this.poll_with_timeout(fds, nfds, timeout, dest)
+    }
+
+    /// For more information on the arguments see the ppoll manpage:
+    /// <https://man7.org/linux/man-pages/man2/poll.2.html>
+    fn ppoll(
+        &mut self,
+        fds: &OpTy<'tcx>,
+        nfds: &OpTy<'tcx>,
+        timeout: &OpTy<'tcx>,
+        _sigmask: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        // Miri does not support signals, so the signal mask does not change anything.
+        let timeout_ptr = this.read_pointer(timeout)?;
+        let timeout = if this.ptr_is_null(timeout_ptr)? {
+            None
+        } else {
+            let timeout = this.deref_pointer_as(timeout, this.libc_ty_layout("timespec"))?;
+            match this.read_timespec(&timeout)? {
+                Some(duration) => Some(duration),
+                None => return this.set_last_error_and_return(LibcError("EINVAL"), dest),
+            }
+        };
+
+        this.poll_with_timeout(fds, nfds, timeout, dest)
+    }
+
+    /// Implements `poll` and `ppoll`. `timeout` is `None` if there is no timeout.
+    fn poll_with_timeout(
+        &mut self,
+        fds: &OpTy<'tcx>,
+        nfds: &OpTy<'tcx>,
+        timeout: Option<Duration>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        // `nfds_t` has a different size on different targets.
+        let nfds_size = nfds.layout.size;
+        let nfds = this.read_scalar(nfds)?.to_bits(nfds_size)?;
+        // Like Linux, we reject more entries than there can be file descriptors.
+        let Ok(nfds) = u64::try_from(nfds) else {
+            return this.set_last_error_and_return(LibcError("EINVAL"), dest);
+        };
+        if nfds > u64::try_from(i32::MAX).unwrap() {
+            return this.set_last_error_and_return(LibcError("EINVAL"), dest);
+        }
+        let fds = this.deref_pointer_as(fds, this.libc_array_ty_layout("pollfd", nfds))?;
+
+        let deadline = deadline_after(timeout, this);
+        poll_fds(fds, deadline, dest, this)
+    }
+
+    /// For more information on the arguments see the select manpage:
+    /// <https://man7.org/linux/man-pages/man2/select.2.html>
+    fn select(
+        &mut self,
+        nfds: &OpTy<'tcx>,
+        readfds: &OpTy<'tcx>,
+        writefds: &OpTy<'tcx>,
+        exceptfds: &OpTy<'tcx>,
+        timeout: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let timeout_ptr = this.read_pointer(timeout)?;
+        let timeout = if this.ptr_is_null(timeout_ptr)? {
+            None
+        } else {
+            let timeout = this.deref_pointer_as(timeout, this.libc_ty_layout("timeval"))?;
+            match this.read_timeval(&timeout)? {
+                Some(timeout) => Some(timeout),
+                None => return this.set_last_error_and_return(LibcError("EINVAL"), dest),
+            }
+        };
+
+        this.select_with_timeout(nfds, readfds, writefds, exceptfds, timeout, dest)
+    }
+
+    /// For more information on the arguments see the pselect manpage:
+    /// <https://man7.org/linux/man-pages/man2/select.2.html>
+    fn pselect(
+        &mut self,
+        nfds: &OpTy<'tcx>,
+        readfds: &OpTy<'tcx>,
+        writefds: &OpTy<'tcx>,
+        exceptfds: &OpTy<'tcx>,
+        timeout: &OpTy<'tcx>,
+        _sigmask: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        // Miri does not support signals, so the signal mask does not change anything.
+        let timeout_ptr = this.read_pointer(timeout)?;
+        let timeout = if this.ptr_is_null(timeout_ptr)? {
+            None
+        } else {
+            let timeout = this.deref_pointer_as(timeout, this.libc_ty_layout("timespec"))?;
+            match this.read_timespec(&timeout)? {
+                Some(duration) => Some(duration),
+                None => return this.set_last_error_and_return(LibcError("EINVAL"), dest),
+            }
+        };
+
+        this.select_with_timeout(nfds, readfds, writefds, exceptfds, timeout, dest)
+    }
+
+    /// Implements `select` and `pselect`. `timeout` is `None` if there is no timeout.
+    fn select_with_timeout(
+        &mut self,
+        nfds: &OpTy<'tcx>,
+        readfds: &OpTy<'tcx>,
+        writefds: &OpTy<'tcx>,
+        exceptfds: &OpTy<'tcx>,
+        timeout: Option<Duration>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let nfds = this.read_scalar(nfds)?.to_i32()?;
+        let fd_set_layout = this.libc_ty_layout("fd_set");
+        // An `fd_set` can hold `FD_SETSIZE` file descriptors, one bit each.
+        let fd_set_size = fd_set_layout.size.bits();
+        if nfds < 0 || u64::try_from(nfds).unwrap() > fd_set_size {
+            return this.set_last_error_and_return(LibcError("EINVAL"), dest);
+        }
+        let deref_fd_set = |set: &OpTy<'tcx>| -> InterpResult<'tcx, Option<MPlaceTy<'tcx>>> {
+            let ptr = this.read_pointer(set)?;
+            interp_ok(if this.ptr_is_null(ptr)? {
+                None
+            } else {
+                Some(this.ptr_to_mplace(ptr, fd_set_layout))
+            })
+        };
+        let readfds = deref_fd_set(readfds)?;
+        let writefds = deref_fd_set(writefds)?;
+        let exceptfds = deref_fd_set(exceptfds)?;
+
+        let deadline = deadline_after(timeout, this);
+        select_fds(nfds, readfds, writefds, exceptfds, deadline, dest, this)
+    }
+}
+
+/// Check all entries of the `pollfd` array `fds`. If none of them is ready, block until one of
+/// them becomes ready or the `deadline` passes.
+fn poll_fds<'tcx>(
+    fds: MPlaceTy<'tcx>,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let mut num_ready: i32 = 0;
+    let mut results = Vec::new();
+    let mut array_iter = ecx.project_array_fields(&fds)?;
+    while let Some((_, pollfd)) = array_iter.next(ecx)? {
+        let fd_num = ecx.read_scalar(&ecx.project_field_named(&pollfd, "fd")?)?.to_i32()?;
+        let events = ecx.read_scalar(&ecx.project_field_named(&pollfd, "events")?)?.to_i16()?;
+        let revents = if fd_num < 0 {
+            // Negative file descriptors are ignored.
+            0
+        } else if let Some(fd) = ecx.machine.fds.get(fd_num) {
+            let ready_events = fd.as_unix().get_poll_ready_events()?;
+            poll_revents(&ready_events, events.into(), ecx)
+        } else {
+            ecx.eval_libc_i32("POLLNVAL")
+        };
+        if revents != 0 {
+            num_ready = num_ready.strict_add(1);
+        }
+        results.push((pollfd, fd_num, revents));
+    }
+
+    if num_ready == 0 && !deadline_passed(deadline, ecx) {
+        ecx.machine.blocked_poll_tid.push(ecx.active_thread());
+        let dest = dest.clone();
+        ecx.block_thread(
+            BlockReason::Poll,
+            deadline_timeout(deadline),
+            callback!(
+                @capture<'tcx> {
+                    fds: MPlaceTy<'tcx>,
+                    deadline: Option<u64>,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    // Something changed, so check again.
+                    poll_fds(fds, deadline, &dest, this)
+                }
+                @timeout = |this| {
+                    let thread_id = this.active_thread();
+                    this.machine.blocked_poll_tid.retain(|&id| id != thread_id);
+                    // Check one last time, but do not block again.
+                    poll_fds(fds, Some(0), &dest, this)
+                }
+            ),
+        );
+        return interp_ok(());
+    }
+
+    for (pollfd, fd_num, revents) in results {
+        if revents != 0 {
+            acquire_readiness_clock(fd_num, ecx);
+        }
+        ecx.write_int(revents, &ecx.project_field_named(&pollfd, "revents")?)?;
+    }
+    ecx.write_int(num_ready, dest)
+}
+
+/// Compute `revents` for a file description with the given readiness, when `events` are
+/// requested. Hang-ups and errors are always reported.
+fn poll_revents<'tcx>(
+    ready_events: &EpollReadyEvents,
+    events: i32,
+    ecx: &MiriInterpCx<'tcx>,
+) -> i32 {
+    let flag = |is_ready: bool, name: &str| if is_ready { ecx.eval_libc_i32(name) } else { 0 };
+    let mut revents = flag(ready_events.epollin, "POLLIN")
+        | flag(ready_events.epollin, "POLLRDNORM")
+        | flag(ready_events.epollout, "POLLOUT")
+        | flag(ready_events.epollout, "POLLWRNORM");
+    if matches!(&*ecx.tcx.sess.target.os, "linux" | "android") {
+        revents |= flag(ready_events.epollrdhup, "POLLRDHUP");
+    }
+    revents &= events;
+    revents | flag(ready_events.epollhup, "POLLHUP") | flag(ready_events.epollerr, "POLLERR")
+}
+
+/// Check the file descriptors below `nfds` in the `fd_set`s. If none of them is ready, block
+/// until one of them becomes ready or the `deadline` passes.
+fn select_fds<'tcx>(
+    nfds: i32,
+    readfds: Option<MPlaceTy<'tcx>>,
+    writefds: Option<MPlaceTy<'tcx>>,
+    exceptfds: Option<MPlaceTy<'tcx>>,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let read_in = read_fd_set(readfds.as_ref(), nfds, ecx)?;
+    let write_in = read_fd_set(writefds.as_ref(), nfds, ecx)?;
+    let except_in = read_fd_set(exceptfds.as_ref(), nfds, ecx)?;
+
+    let mut read_out = Vec::new();
+    let mut write_out = Vec::new();
+    for (fds_in, fds_out, is_write) in
+        [(&read_in, &mut read_out, false), (&write_in, &mut write_out, true)]
+    {
+        for &fd_num in fds_in {
+            let Some(fd) = ecx.machine.fds.get(fd_num) else {
+                return ecx.set_last_error_and_return(LibcError("EBADF"), dest);
+            };
+            let ready_events = fd.as_unix().get_poll_ready_events()?;
+            // Like on Linux, errors and hang-ups make a file description ready for reading, and
+            // errors make it ready for writing, since the next operation will not block.
+            let is_ready = if is_write {
+                ready_events.epollout || ready_events.epollerr
+            } else {
+                ready_events.epollin || ready_events.epollhup || ready_events.epollerr
+            };
+            if is_ready {
+                fds_out.push(fd_num);
+            }
+        }
+    }
+    // We do not track exceptional conditions like out-of-band data, so those are never ready.
+    for &fd_num in &except_in {
+        if ecx.machine.fds.get(fd_num).is_none() {
+            return ecx.set_last_error_and_return(LibcError("EBADF"), dest);
+        }
+    }
+    let num_ready = i32::try_from(read_out.len().strict_add(write_out.len())).unwrap();
+
+    if num_ready == 0 && !deadline_passed(deadline, ecx) {
+        ecx.machine.blocked_poll_tid.push(ecx.active_thread());
+        let dest = dest.clone();
+        ecx.block_thread(
+            BlockReason::Poll,
+            deadline_timeout(deadline),
+            callback!(
+                @capture<'tcx> {
+                    nfds: i32,
+                    readfds: Option<MPlaceTy<'tcx>>,
+                    writefds: Option<MPlaceTy<'tcx>>,
+                    exceptfds: Option<MPlaceTy<'tcx>>,
+                    deadline: Option<u64>,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    // Something changed, so check again.
+                    select_fds(nfds, readfds, writefds, exceptfds, deadline, &dest, this)
+                }
+                @timeout = |this| {
+                    let thread_id = this.active_thread();
+                    this.machine.blocked_poll_tid.retain(|&id| id != thread_id);
+                    // Check one last time, but do not block again.
+                    select_fds(nfds, readfds, writefds, exceptfds, Some(0), &dest, this)
+                }
+            ),
+        );
+        return interp_ok(());
+    }
+
+    for &fd_num in read_out.iter().chain(&write_out) {
+        acquire_readiness_clock(fd_num, ecx);
+    }
+    write_fd_set(readfds.as_ref(), &read_out, ecx)?;
+    write_fd_set(writefds.as_ref(), &write_out, ecx)?;
+    write_fd_set(exceptfds.as_ref(), &[], ecx)?;
+    ecx.write_int(num_ready, dest)
+}
+
+/// Synchronize with the operation that made the file description behind `fd_num` ready.
+fn acquire_readiness_clock<'tcx>(fd_num: i32, ecx: &MiriInterpCx<'tcx>) {
+    if let Some(fd) = ecx.machine.fds.get(fd_num)
+        && let Some(clock) = ecx.machine.readiness_clocks.get(&fd.get_id())
+    {
+        ecx.acquire_clock(clock);
+    }
+}
+
+/// Return the file descriptors below `nfds` that are in the `fd_set`.
+fn read_fd_set<'tcx>(
+    set: Option<&MPlaceTy<'tcx>>,
+    nfds: i32,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, Vec<i32>> {
+    let mut fds = Vec::new();
+    let Some(set) = set else { return interp_ok(fds) };
+    // An `fd_set` is an array of integers, each holding one bit per file descriptor.
+    let words = ecx.project_field(set, 0)?;
+    let mut array_iter = ecx.project_array_fields(&words)?;
+    let mut fd_num: i32 = 0;
+    while let Some((_, word)) = array_iter.next(ecx)? {
+        let word_bits = word.layout.size.bits();
+        let bits = ecx.read_scalar(&word)?.to_bits(word.layout.size)?;
+        for bit in 0..word_bits {
+            if fd_num < nfds && bits & (1 << bit) != 0 {
+                fds.push(fd_num);
+            }
+            fd_num = fd_num.strict_add(1);
+        }
+    }
+    interp_ok(fds)
+}
+
+/// Overwrite the `fd_set` so that it contains exactly the file descriptors in `fds`.
+fn write_fd_set<'tcx>(
+    set: Option<&MPlaceTy<'tcx>>,
+    fds: &[i32],
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let Some(set) = set else { return interp_ok(()) };
+    let words = ecx.project_field(set, 0)?;
+    let mut array_iter = ecx.project_array_fields(&words)?;
+    while let Some((idx, word)) = array_iter.next(ecx)? {
+        let word_bits = word.layout.size.bits();
+        let first_fd = idx.strict_mul(word_bits);
+        let mut bits: u128 = 0;
+        for &fd_num in fds {
+            let fd_num = u64::try_from(fd_num).unwrap();
+            if (first_fd..first_fd.strict_add(word_bits)).contains(&fd_num) {
+                bits |= 1 << (fd_num - first_fd);
+            }
+        }
+        ecx.write_scalar(Scalar::from_uint(bits, word.layout.size), &word)?;
+    }
+    interp_ok(())
+}
+
+/// Compute the deadline for a call with the given timeout, as the number of nanoseconds since
+/// the epoch of the monotonic clock. This way, waking up early does not extend the timeout.
+pub(crate) fn deadline_after<'tcx>(
+    timeout: Option<Duration>,
+    ecx: &MiriInterpCx<'tcx>,
+) -> Option<u64> {
+    timeout.map(|timeout| {
+        let now = ecx.machine.clock.now().duration_since(ecx.machine.clock.epoch());
+        u64::try_from(now.saturating_add(timeout).as_nanos()).unwrap_or(u64::MAX)
+    })
+}
+
+/// Check whether the `deadline` computed by `deadline_after` has passed.
+pub(crate) fn deadline_passed<'tcx>(deadline: Option<u64>, ecx: &MiriInterpCx<'tcx>) -> bool {
+    let now = ecx.machine.clock.now().duration_since(ecx.machine.clock.epoch());
+    deadline.is_some_and(|deadline| u128::from(deadline) <= now.as_nanos())
+}
+
+/// The timeout for blocking until the `deadline` computed by `deadline_after`.
+pub(crate) fn deadline_timeout(
+    deadline: Option<u64>,
+) -> Option<(TimeoutClock, TimeoutAnchor, Duration)> {
+    deadline.map(|deadline| {
+        (TimeoutClock::Monotonic, TimeoutAnchor::Absolute, Duration::from_nanos(deadline))
+    })
+}
@@ -0,0 +1,504 @@
+//! This implements a limited form of creating processes with `posix_spawn`. It never runs a real
+//! program. Instead, the commands that the program may spawn are declared in a script (see
+//! `CommandScript`), together with their output and how they terminate. The spawned child writes
+//! that output and terminates right away.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+
+use rustc_abi::Size;
+use rustc_data_structures::fx::FxHashMap;
+
+use crate::shims::files::{DevNull, FdTable, FileDescriptionRef, NullOutput};
+use crate::shims::unix::UID;
+use crate::shims::unix::unnamed_socket::{AnonSocket, stream_write};
+use crate::*;
+
+/// Children get made-up PIDs that are this far apart. The TIDs of a process are its PID plus the
+/// thread index, so this leaves room for as many threads.
+const CHILD_PID_DISTANCE: u32 = 10_000;
+
+/// How a child process terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The child exited with the given exit code.
+    Exited(i32),
+    /// The child was killed by the given signal.
+    Signaled(i32),
+}
+
+impl ExitStatus {
+    /// The status as reported by `waitpid`. All Unix targets use the same encoding.
+    fn to_wait_status(self) -> i32 {
+        match self {
+            ExitStatus::Exited(code) => (code & 0xff).strict_shl(8),
+            ExitStatus::Signaled(signal) => signal & 0x7f,
+        }
+    }
+}
+
+/// An operation recorded in a `posix_spawn_file_actions_t`.
+#[derive(Debug, Clone, Copy)]
+enum SpawnFileAction {
+    /// Duplicate the first FD onto the second one.
+    Dup2(i32, i32),
+    /// Close the FD.
+    Close(i32),
+}
+
+/// The state of the processes that the interpreted program created.
+#[derive(Debug, Default)]
+pub struct ProcessTable {
+    /// How many children this process created so far.
+    spawned: u32,
+    /// The children that terminated, and how.
+    exited: BTreeMap<u32, ExitStatus>,
+    /// The operations recorded in every initialized `posix_spawn_file_actions_t`, by its address.
+    spawn_file_actions: FxHashMap<u64, Vec<SpawnFileAction>>,
+}
+
+/// A command that `posix_spawn` can run.
+#[derive(Debug, Clone)]
+struct ScriptedCommand {
+    /// The program and its arguments, separated by single spaces.
+    command_line: String,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: ExitStatus,
+}
+
+/// The commands that `posix_spawn` can run, configured with `-Zmiri-command-script`.
+///
+/// Scripts are stored as text. Every command starts with a line `command <command line>`, where
+/// the command line is the program followed by its arguments, separated by single spaces. Then
+/// follow any number of `stdout <text>` and `stderr <text>` lines, whose text gets appended to the
+/// respective output (`\n`, `\t`, and `\\` are escapes), and at most one `exit <code>` or
+/// `signal <number>` line; by default, the command exits with code 0. Lines starting with `#` are
+/// ignored.
+#[derive(Debug, Clone, Default)]
+pub struct CommandScript {
+    commands: Vec<ScriptedCommand>,
+}
+
+impl CommandScript {
+    /// The first command in the script with this command line.
+    fn find(&self, command_line: &str) -> Option<&ScriptedCommand> {
+        self.commands.iter().find(|command| command.command_line == command_line)
+    }
+}
+
+impl FromStr for CommandScript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut commands: Vec<ScriptedCommand> = Vec::new();
+        // Whether the last command already got an `exit` or `signal` line.
+        let mut terminated = false;
+        for (line_number, line) in s.lines().enumerate().map(|(i, line)| (i.strict_add(1), line)) {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("line {line_number}: `{line}` is not a command script line");
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            if keyword == "command" {
+                commands.push(ScriptedCommand {
+                    command_line: rest.trim().to_owned(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                    status: ExitStatus::Exited(0),
+                });
+                terminated = false;
+                continue;
+            }
+            let Some(command) = commands.last_mut() else {
+                return Err(format!("line {line_number}: expected a `command` line first"));
+            };
+            match keyword {
+                "stdout" => command.stdout.extend(unescape(rest).ok_or_else(invalid)?.bytes()),
+                "stderr" => command.stderr.extend(unescape(rest).ok_or_else(invalid)?.bytes()),
+                "exit" | "signal" => {
+                    if terminated {
+                        return Err(format!(
+                            "line {line_number}: `{}` already has an exit status",
+                            command.command_line
+                        ));
+                    }
+                    let value = rest.trim().parse().map_err(|_| invalid())?;
+                    command.status = if keyword == "exit" {
+                        ExitStatus::Exited(value)
+                    } else {
+                        ExitStatus::Signaled(value)
+                    };
+                    terminated = true;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(CommandScript { commands })
+    }
+}
+
+/// Resolves the escapes in the output text of a command script.
+fn unescape(text: &str) -> Option<String> {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => result.push('\n'),
+            't' => result.push('\t'),
+            '\\' => result.push('\\'),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+impl<'tcx> EvalContextPrivExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// Returns the made-up PID of the next child.
+    fn next_child_pid(&mut self) -> u32 {
+        let this = self.eval_context_mut();
+        let parent_pid = this.get_pid();
+        let processes = &mut this.machine.processes;
+        processes.spawned = processes.spawned.strict_add(1);
+        parent_pid.strict_add(CHILD_PID_DISTANCE.strict_mul(processes.spawned))
+    }
+
+    /// Looks for a terminated child with the given PID, or any terminated child if `target` is
+    /// `None`, and returns its PID and how it terminated. Unless `keep` is set, the child is
+    /// removed, so it cannot be waited for again.
+    fn reap_child(&mut self, target: Option<u32>, keep: bool) -> Option<(u32, ExitStatus)> {
+        let this = self.eval_context_mut();
+        let exited = &mut this.machine.processes.exited;
+        let pid = match target {
+            Some(pid) => pid,
+            None => *exited.keys().next()?,
+        };
+        if keep { exited.get(&pid).copied() } else { exited.remove(&pid) }
+            .map(|status| (pid, status))
+    }
+
+    /// Returns the operations recorded in the `posix_spawn_file_actions_t` at `file_actions`.
+    fn spawn_file_actions<'a>(
+        &'a mut self,
+        file_actions: Pointer,
+    ) -> InterpResult<'tcx, &'a mut Vec<SpawnFileAction>>
+    where
+        'tcx: 'a,
+    {
+        let this = self.eval_context_mut();
+        let addr = file_actions.addr().bytes();
+        match this.machine.processes.spawn_file_actions.get_mut(&addr) {
+            Some(actions) => interp_ok(actions),
+            None => throw_ub_format!("`posix_spawn_file_actions_t` was not initialized"),
+        }
+    }
+
+    /// Reads a null-terminated array of pointers to C strings, like `argv`.
+    fn read_c_str_array(&self, array: Pointer) -> InterpResult<'tcx, Vec<String>> {
+        let this = self.eval_context_ref();
+        let ptr_layout = this.machine.layouts.const_raw_ptr;
+        let mut strings = Vec::new();
+        for i in 0u64.. {
+            let elem =
+                this.ptr_to_mplace(array.wrapping_offset(ptr_layout.size * i, this), ptr_layout);
+            let elem = this.read_pointer(&elem)?;
+            if this.ptr_is_null(elem)? {
+                break;
+            }
+            strings.push(String::from_utf8_lossy(this.read_c_str(elem)?).into_owned());
+        }
+        interp_ok(strings)
+    }
+
+    /// Writes the output of a scripted child to one of the file descriptions it inherited.
+    fn write_child_output(&mut self, fd: &FileDescriptionRef, bytes: &[u8]) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        if let Some(stream) = fd.downcast::<AnonSocket>() {
+            if stream.would_block_write(bytes.len()) {
+                throw_unsup_format!("the output of a scripted command does not fit into the pipe");
+            }
+            match stream_write(fd, bytes, Vec::new(), this)? {
+                Ok(written) if written < bytes.len() =>
+                    throw_unsup_format!(
+                        "the output of a scripted command does not fit into the pipe"
+                    ),
+                // If the reading end is closed, the output gets lost.
+                _ => {}
+            }
+        } else if fd.downcast::<io::Stdout>().is_some() {
+            // Like all writes to stdout, this is not buffered.
+            let mut stdout = io::stdout();
+            stdout.write_all(bytes).unwrap();
+            stdout.flush().unwrap();
+        } else if fd.downcast::<io::Stderr>().is_some() {
+            io::stderr().write_all(bytes).unwrap();
+        } else if fd.downcast::<NullOutput>().is_none() && fd.downcast::<DevNull>().is_none() {
+            throw_unsup_format!("scripted commands cannot write to {}", fd.name());
+        }
+        interp_ok(())
+    }
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// Implements both `posix_spawn` and `posix_spawnp`: the program is never looked up anyway.
+    /// Returns an error number, or 0 on success.
+    fn posix_spawn(
+        &mut self,
+        pid: &OpTy<'tcx>,
+        path: &OpTy<'tcx>,
+        file_actions: &OpTy<'tcx>,
+        attr: &OpTy<'tcx>,
+        argv: &OpTy<'tcx>,
+        envp: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pid = this.read_pointer(pid)?;
+        let path = this.read_pointer(path)?;
+        this.read_c_str(path)?;
+        let file_actions = this.read_pointer(file_actions)?;
+        // Scripted children do not run any code, so the attributes and the environment make no
+        // difference.
+        let _attr = this.read_pointer(attr)?;
+        let _envp = this.read_pointer(envp)?;
+        let argv = this.read_c_str_array(this.read_pointer(argv)?)?;
+
+        let Some(script) = &this.machine.command_script else {
+            throw_unsup_format!(
+                "`posix_spawn` can only run commands declared with `-Zmiri-command-script`"
+            );
+        };
+        let command_line = argv.join(" ");
+        let Some(command) = script.find(&command_line).cloned() else {
+            // Only the commands in the script exist.
+            return interp_ok(this.eval_libc("ENOENT"));
+        };
+
+        // Find out where the standard output and error of the child go. Every FD that the file
+        // actions do not mention is inherited from the parent.
+        let actions = if this.ptr_is_null(file_actions)? {
+            Vec::new()
+        } else {
+            this.spawn_file_actions(file_actions)?.clone()
+        };
+        let mut child_fds: BTreeMap<i32, Option<FileDescriptionRef>> = BTreeMap::new();
+        let child_fd =
+            |fds: &FdTable, child_fds: &BTreeMap<i32, Option<FileDescriptionRef>>, fd_num: i32| {
+                child_fds.get(&fd_num).cloned().unwrap_or_else(|| fds.get(fd_num))
+            };
+        for action in actions {
+            match action {
+                SpawnFileAction::Dup2(fd_num, new_fd_num) => {
+                    let Some(fd) = child_fd(&this.machine.fds, &child_fds, fd_num) else {
+                        return interp_ok(this.eval_libc("EBADF"));
+                    };
+                    child_fds.insert(new_fd_num, Some(fd));
+                }
+                SpawnFileAction::Close(fd_num) => {
+                    child_fds.insert(fd_num, None);
+                }
+            }
+        }
+        for (fd_num, output) in [(1, &command.stdout), (2, &command.stderr)] {
+            if output.is_empty() {
+                continue;
+            }
+            // If the child has no such FD, its output gets lost.
+            if let Some(fd) = child_fd(&this.machine.fds, &child_fds, fd_num) {
+                this.write_child_output(&fd, output)?;
+            }
+        }
+
+        let child_pid = this.next_child_pid();
+        this.machine.processes.exited.insert(child_pid, command.status);
+        if !this.ptr_is_null(pid)? {
+            let pid = this.ptr_to_mplace(pid, this.libc_ty_layout("pid_t"));
+            this.write_scalar(Scalar::from_u32(child_pid), &pid)?;
+        }
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn posix_spawn_file_actions_init(
+        &mut self,
+        file_actions: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let file_actions = this.read_pointer(file_actions)?;
+        let addr = file_actions.addr().bytes();
+        this.machine.processes.spawn_file_actions.insert(addr, Vec::new());
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn posix_spawn_file_actions_destroy(
+        &mut self,
+        file_actions: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let file_actions = this.read_pointer(file_actions)?;
+        // Make sure it was initialized.
+        this.spawn_file_actions(file_actions)?;
+        this.machine.processes.spawn_file_actions.remove(&file_actions.addr().bytes());
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn posix_spawn_file_actions_adddup2(
+        &mut self,
+        file_actions: &OpTy<'tcx>,
+        fd: &OpTy<'tcx>,
+        new_fd: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let file_actions = this.read_pointer(file_actions)?;
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        let new_fd = this.read_scalar(new_fd)?.to_i32()?;
+        if fd < 0 || new_fd < 0 {
+            return interp_ok(this.eval_libc("EBADF"));
+        }
+        this.spawn_file_actions(file_actions)?.push(SpawnFileAction::Dup2(fd, new_fd));
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn posix_spawn_file_actions_addclose(
+        &mut self,
+        file_actions: &OpTy<'tcx>,
+        fd: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let file_actions = this.read_pointer(file_actions)?;
+        let fd = this.read_scalar(fd)?.to_i32()?;
+        if fd < 0 {
+            return interp_ok(this.eval_libc("EBADF"));
+        }
+        this.spawn_file_actions(file_actions)?.push(SpawnFileAction::Close(fd));
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    /// Implements `wait`, `waitpid`, and `wait4`. Only `wait4` reports the resource usage.
+    fn wait4(
+        &mut self,
+        pid: i32,
+        status: Pointer,
+        options: i32,
+        rusage: Option<Pointer>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        // Our children never stop, so `WUNTRACED` and `WCONTINUED` make no difference. Neither
+        // does `WNOHANG`, since they terminate right away.
+        let supported = this.eval_libc_i32("WNOHANG")
+            | this.eval_libc_i32("WUNTRACED")
+            | this.eval_libc_i32("WCONTINUED");
+        if options & !supported != 0 {
+            throw_unsup_format!("`waitpid`: unsupported options {:#x}", options & !supported);
+        }
+        let target = match pid {
+            // All our children are in our process group.
+            -1 | 0 => None,
+            pid if pid > 0 => Some(pid.try_into().unwrap()),
+            _ => throw_unsup_format!("`waitpid` cannot wait for another process group"),
+        };
+
+        let Some((child_pid, exit_status)) = this.reap_child(target, /* keep */ false) else {
+            return this.set_last_error_and_return_i32(LibcError("ECHILD"));
+        };
+        if !this.ptr_is_null(status)? {
+            let status = this.ptr_to_mplace(status, this.machine.layouts.i32);
+            this.write_scalar(Scalar::from_i32(exit_status.to_wait_status()), &status)?;
+        }
+        if let Some(rusage) = rusage
+            && !this.ptr_is_null(rusage)?
+        {
+            // We do not track how many resources the child used.
+            let rusage = this.ptr_to_mplace(rusage, this.libc_ty_layout("rusage"));
+            this.write_bytes_ptr(
+                rusage.ptr(),
+                std::iter::repeat_n(0u8, rusage.layout.size.bytes_usize()),
+            )?;
+        }
+        interp_ok(Scalar::from_u32(child_pid))
+    }
+
+    fn waitid(
+        &mut self,
+        idtype: &OpTy<'tcx>,
+        id: &OpTy<'tcx>,
+        infop: &OpTy<'tcx>,
+        options: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let idtype = this.read_scalar(idtype)?.to_u32()?;
+        let id = this.read_scalar(id)?.to_u32()?;
+        let info = this.deref_pointer_as(infop, this.libc_ty_layout("siginfo_t"))?;
+        let options = this.read_scalar(options)?.to_i32()?;
+
+        let wexited = this.eval_libc_i32("WEXITED");
+        let wstopped = this.eval_libc_i32("WSTOPPED");
+        let wcontinued = this.eval_libc_i32("WCONTINUED");
+        let wnohang = this.eval_libc_i32("WNOHANG");
+        let wnowait = this.eval_libc_i32("WNOWAIT");
+        if options & !(wexited | wstopped | wcontinued | wnohang | wnowait) != 0 {
+            throw_unsup_format!("`waitid`: unsupported options {options:#x}");
+        }
+        if options & (wexited | wstopped | wcontinued) == 0 {
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        }
+        if options & wexited == 0 {
+            // Our children never stop or continue, so we would wait forever.
+            throw_unsup_format!("`waitid` is only supported with `WEXITED`");
+        }
+        let target = if idtype == this.eval_libc_u32("P_ALL") {
+            None
+        } else if idtype == this.eval_libc_u32("P_PID") {
+            Some(id)
+        } else if idtype == this.eval_libc_u32("P_PGID") {
+            throw_unsup_format!("`waitid` cannot wait for a process group")
+        } else {
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        };
+
+        // As with `waitpid`, `WNOHANG` makes no difference since our children terminate right
+        // away. With `WNOWAIT`, the child can be waited for again.
+        let keep = options & wnowait != 0;
+        let Some((child_pid, exit_status)) = this.reap_child(target, keep) else {
+            return this.set_last_error_and_return_i32(LibcError("ECHILD"));
+        };
+        let (code, status) = match exit_status {
+            ExitStatus::Exited(code) => (this.eval_libc_i32("CLD_EXITED"), code),
+            ExitStatus::Signaled(signal) => (this.eval_libc_i32("CLD_KILLED"), signal),
+        };
+        this.write_bytes_ptr(info.ptr(), std::iter::repeat_n(0u8, info.layout.size.bytes_usize()))?;
+        this.write_int_fields_named(
+            &[("si_signo", this.eval_libc_i32("SIGCHLD").into()), ("si_code", code.into())],
+            &info,
+        )?;
+        let child_fields: [(&str, i128); 3] =
+            [("si_pid", child_pid.into()), ("si_uid", UID.into()), ("si_status", status.into())];
+        if matches!(&*this.tcx.sess.target.os, "linux" | "android") {
+            // The `libc` crate does not expose the fields for `SIGCHLD`. They are the first fields
+            // of a pointer-aligned union after `si_code`.
+            let mut offset =
+                Size::from_bytes(12).align_to(this.machine.layouts.mut_raw_ptr.align.abi);
+            for (_, value) in child_fields {
+                let field = info.offset(offset, this.machine.layouts.i32, this)?;
+                this.write_int(value, &field)?;
+                offset += field.layout.size;
+            }
+        } else {
+            this.write_int_fields_named(&child_fields, &info)?;
+        }
+        interp_ok(Scalar::from_i32(0))
+    }
+}
@@ -0,0 +1,2433 @@
+//! This implements named sockets on the loopback interface, and unix domain sockets in the abstract
+//! namespace. These do not correspond to anything on the host system and are entirely implemented
+//! inside Miri: a connection can only be made to a socket that is listening inside the same
+//! interpreted program.
+//! Once connected, a stream socket uses the same stream implementation as `socketpair`.
+//! Datagram sockets deliver each datagram directly into the queue of the socket bound to the
+//! destination address.
+
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::time::Duration;
+
+use rustc_abi::Size;
+
+use crate::concurrency::VClock;
+use crate::shims::files::{
+    EvalContextExt as _, FileDescription, FileDescriptionRef, MAX_IOVECS, WeakFileDescriptionRef,
+};
+use crate::shims::unix::UnixFileDescription;
+use crate::shims::unix::fd::EvalContextExt as _;
+use crate::shims::unix::linux_like::epoll::{EpollReadyEvents, EvalContextExt as _};
+use crate::shims::unix::poll::{deadline_after, deadline_passed, deadline_timeout};
+use crate::shims::unix::unnamed_socket::{
+    AnonSocket, anonsocket_read, anonsocket_write, stream_read, stream_write,
+};
+use crate::*;
+
+/// The first port that is handed out when binding to port 0. This is the start of the
+/// IANA-recommended range for ephemeral ports.
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+/// The largest accepted `listen` backlog. Larger (and negative) values are clamped to this.
+const MAX_BACKLOG: usize = 4096;
+
+/// The largest datagram that can be sent. This is the largest UDP payload that fits into an IPv4
+/// packet.
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// The number of bytes that can be queued on a datagram socket before further datagrams get
+/// dropped. This matches the default receive buffer size on Linux.
+const MAX_DATAGRAM_QUEUE_SIZE: usize = 212992;
+
+/// The maximum number of file descriptors in one `SCM_RIGHTS` control message. This matches
+/// `SCM_MAX_FD` on Linux.
+const MAX_PASSED_FDS: u64 = 253;
+
+/// The buffer size that `SO_RCVBUF` and `SO_SNDBUF` report if they were not set.
+const DEFAULT_BUFFER_SIZE: i32 = 212992;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketFamily {
+    Inet,
+    Inet6,
+    Unix,
+}
+
+impl SocketFamily {
+    fn loopback(self) -> IpAddr {
+        match self {
+            SocketFamily::Inet => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            SocketFamily::Inet6 => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            SocketFamily::Unix => unreachable!("unix domain sockets have no IP address"),
+        }
+    }
+
+    fn unspecified(self) -> IpAddr {
+        match self {
+            SocketFamily::Inet => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketFamily::Inet6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            SocketFamily::Unix => unreachable!("unix domain sockets have no IP address"),
+        }
+    }
+
+    /// The address that `getsockname` reports for an unbound socket.
+    fn unbound_addr(self) -> SocketAddress {
+        match self {
+            SocketFamily::Unix => SocketAddress::Unix(None),
+            _ => SocketAddress::Inet(SocketAddr::new(self.unspecified(), 0)),
+        }
+    }
+}
+
+/// The address of a socket.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SocketAddress {
+    Inet(SocketAddr),
+    /// The name of a unix domain socket in the abstract namespace, without the leading NUL byte,
+    /// or `None` for an unnamed socket. Names in the file system are not supported.
+    Unix(Option<Vec<u8>>),
+}
+
+impl SocketAddress {
+    /// Whether a socket that is bound to `self` prevents binding another socket to `addr`.
+    fn conflicts_with(&self, addr: &SocketAddress) -> bool {
+        match (self, addr) {
+            (SocketAddress::Inet(bound), SocketAddress::Inet(addr)) =>
+                bound.port() == addr.port()
+                    && bound.is_ipv4() == addr.is_ipv4()
+                    && (bound.ip() == addr.ip()
+                        || bound.ip().is_unspecified()
+                        || addr.ip().is_unspecified()),
+            (SocketAddress::Unix(bound), SocketAddress::Unix(name)) => bound == name,
+            _ => false,
+        }
+    }
+
+    /// Whether a socket that is bound to `self` receives what is sent to `dest`.
+    fn accepts(&self, dest: &SocketAddress) -> bool {
+        match (self, dest) {
+            (SocketAddress::Inet(bound), SocketAddress::Inet(dest)) =>
+                bound.port() == dest.port()
+                    && bound.is_ipv4() == dest.is_ipv4()
+                    && (bound.ip() == dest.ip() || bound.ip().is_unspecified()),
+            (SocketAddress::Unix(bound), SocketAddress::Unix(dest)) => bound == dest,
+            _ => false,
+        }
+    }
+
+    /// The address that a socket bound to `self` uses for talking to `peer`: an unspecified IP
+    /// address is replaced by the one of `peer`.
+    fn local_for(mut self, peer: &SocketAddress) -> SocketAddress {
+        if let (SocketAddress::Inet(local), SocketAddress::Inet(peer)) = (&mut self, peer)
+            && local.ip().is_unspecified()
+        {
+            local.set_ip(peer.ip());
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SocketType {
+    Stream,
+    Datagram,
+}
+
+/// A datagram that was sent to a socket, but not yet received.
+#[derive(Debug, Clone)]
+struct Datagram {
+    from: SocketAddress,
+    data: Vec<u8>,
+    /// The clock of the sending thread.
+    clock: VClock,
+}
+
+/// The options that can be set with `setsockopt` and read with `getsockopt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketOption {
+    ReuseAddr,
+    RcvBuf,
+    SndBuf,
+    Error,
+    Linger,
+    NoSigPipe,
+    NoDelay,
+    RcvTimeo,
+    SndTimeo,
+}
+
+/// The values of the socket options. Except for `error` and the timeouts, they do not affect how
+/// the socket behaves in Miri; they are only stored so that `getsockopt` reports what was set.
+#[derive(Debug, Clone)]
+struct SocketOptions {
+    reuseaddr: bool,
+    rcvbuf: i32,
+    sndbuf: i32,
+    /// The pending error, which is cleared by reading `SO_ERROR`.
+    error: i32,
+    /// The linger timeout in seconds, if lingering on close is enabled.
+    linger: Option<i32>,
+    nosigpipe: bool,
+    nodelay: bool,
+    /// How long receiving (including `accept`) may block before failing with EWOULDBLOCK.
+    rcvtimeo: Option<Duration>,
+    /// How long sending may block before failing with EWOULDBLOCK.
+    sndtimeo: Option<Duration>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        SocketOptions {
+            reuseaddr: false,
+            rcvbuf: DEFAULT_BUFFER_SIZE,
+            sndbuf: DEFAULT_BUFFER_SIZE,
+            error: 0,
+            linger: None,
+            nosigpipe: false,
+            nodelay: false,
+            rcvtimeo: None,
+            sndtimeo: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SocketState {
+    /// Freshly created by `socket`.
+    Unbound,
+    /// Bound to a local address by `bind`.
+    Bound(SocketAddress),
+    /// Accepting connections.
+    Listening {
+        addr: SocketAddress,
+        /// The maximum number of pending connections.
+        backlog: usize,
+        /// Connections that were established by `connect` but not yet returned by `accept`.
+        /// These are the server-side ends of the connections, together with the clock of the
+        /// connecting thread.
+        pending: VecDeque<(FileDescriptionRef, VClock)>,
+    },
+    /// Connected to a peer. For stream sockets, the stream itself is stored in `Socket::stream`.
+    /// For datagram sockets, this just sets the default destination.
+    Connected { local: SocketAddress, peer: SocketAddress },
+}
+
+/// A socket created by `socket`, or returned by `accept`.
+#[derive(Debug)]
+pub struct Socket {
+    family: SocketFamily,
+    type_: SocketType,
+    state: RefCell<SocketState>,
+    /// Once a stream socket is connected, this is the stream that data is exchanged over.
+    stream: OnceCell<AnonSocket>,
+    /// The datagrams waiting to be received. Only used by datagram sockets.
+    datagrams: RefCell<VecDeque<Datagram>>,
+    is_nonblock: Cell<bool>,
+    options: RefCell<SocketOptions>,
+    /// A list of thread ids blocked on `accept`.
+    blocked_accept_tid: RefCell<Vec<ThreadId>>,
+    /// A list of thread ids blocked on receiving a datagram.
+    blocked_recv_tid: RefCell<Vec<ThreadId>>,
+}
+
+impl Socket {
+    fn new(family: SocketFamily, type_: SocketType, is_nonblock: bool) -> Self {
+        Socket {
+            family,
+            type_,
+            state: RefCell::new(SocketState::Unbound),
+            stream: OnceCell::new(),
+            datagrams: RefCell::new(VecDeque::new()),
+            is_nonblock: Cell::new(is_nonblock),
+            options: RefCell::new(SocketOptions::default()),
+            blocked_accept_tid: RefCell::new(Vec::new()),
+            blocked_recv_tid: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The stream of a connected socket.
+    pub fn stream(&self) -> Option<&AnonSocket> {
+        self.stream.get()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddress> {
+        match &*self.state.borrow() {
+            SocketState::Unbound => None,
+            SocketState::Bound(addr) | SocketState::Listening { addr, .. } => Some(addr.clone()),
+            SocketState::Connected { local, .. } => Some(local.clone()),
+        }
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddress> {
+        match &*self.state.borrow() {
+            SocketState::Connected { peer, .. } => Some(peer.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FileDescription for Socket {
+    fn name(&self) -> &'static str {
+        "socket"
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        communicate_allowed: bool,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        // Our entry in the table of bound sockets becomes stale automatically, since it is only a
+        // weak reference.
+        if let Some(stream) = self.stream() {
+            stream.on_close(ecx)?;
+        }
+        if let SocketState::Listening { pending, .. } = self.state.into_inner() {
+            // Connections that were never accepted get reset.
+            for (conn, _clock) in pending {
+                conn.close(communicate_allowed, ecx)?.ok();
+            }
+        }
+        interp_ok(Ok(()))
+    }
+
+    fn read<'tcx>(
+        &self,
+        self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        ptr: Pointer,
+        len: usize,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        // Without flags, `read` is equivalent to `recv`.
+        socket_recv(
+            self_ref,
+            vec![(ptr, u64::try_from(len).unwrap())],
+            /* is_peek */ false,
+            /* is_dontwait */ false,
+            Pointer::null(),
+            Pointer::null(),
+            dest,
+            ecx,
+        )
+    }
+
+    fn readv<'tcx>(
+        &self,
+        self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        iov: &[(Pointer, u64)],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        socket_recv(
+            self_ref,
+            iov.to_vec(),
+            /* is_peek */ false,
+            /* is_dontwait */ false,
+            Pointer::null(),
+            Pointer::null(),
+            dest,
+            ecx,
+        )
+    }
+
+    fn write<'tcx>(
+        &self,
+        self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        ptr: Pointer,
+        len: usize,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        socket_write(self_ref, vec![(ptr, u64::try_from(len).unwrap())], dest, ecx)
+    }
+
+    fn writev<'tcx>(
+        &self,
+        self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        iov: &[(Pointer, u64)],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        socket_write(self_ref, iov.to_vec(), dest, ecx)
+    }
+
+    fn as_unix(&self) -> &dyn UnixFileDescription {
+        self
+    }
+}
+
+impl UnixFileDescription for Socket {
+    fn get_epoll_ready_events<'tcx>(&self) -> InterpResult<'tcx, EpollReadyEvents> {
+        if let Some(stream) = self.stream() {
+            return interp_ok(stream.epoll_ready_events());
+        }
+        let mut epoll_ready_events = EpollReadyEvents::new();
+        if self.type_ == SocketType::Datagram {
+            // Sending a datagram never blocks.
+            epoll_ready_events.epollin = !self.datagrams.borrow().is_empty();
+            epoll_ready_events.epollout = true;
+            return interp_ok(epoll_ready_events);
+        }
+        match &*self.state.borrow() {
+            SocketState::Listening { pending, .. } => {
+                // A listening socket is readable when `accept` would not block.
+                epoll_ready_events.epollin = !pending.is_empty();
+            }
+            _ => {
+                // Like on Linux, a socket that is not connected reports that it is hung up.
+                epoll_ready_events.epollout = true;
+                epoll_ready_events.epollhup = true;
+                // A failed non-blocking `connect` also reports its error.
+                epoll_ready_events.epollerr = self.options.borrow().error != 0;
+            }
+        }
+        interp_ok(epoll_ready_events)
+    }
+}
+
+/// The table of all named sockets that are bound to an address. This is used to find the listener
+/// for `connect` and the destination of datagrams, and to pick unused ports and names.
+/// Stream and datagram sockets have separate port spaces.
+#[derive(Debug)]
+pub struct SocketTable {
+    /// Weak references to the bound sockets. Entries become stale when the socket gets closed.
+    bound: BTreeMap<(SocketType, SocketAddress), WeakFileDescriptionRef>,
+    /// The next port to try when binding to port 0.
+    next_ephemeral_port: u16,
+    /// The next name to try when binding a unix domain socket without giving a name.
+    next_autobind_name: u32,
+}
+
+impl SocketTable {
+    pub(crate) fn new() -> Self {
+        SocketTable {
+            bound: BTreeMap::new(),
+            next_ephemeral_port: FIRST_EPHEMERAL_PORT,
+            next_autobind_name: 0,
+        }
+    }
+
+    /// Returns whether binding to `addr` would conflict with an open socket.
+    fn is_in_use(&self, type_: SocketType, addr: &SocketAddress) -> bool {
+        self.bound.iter().any(|((bound_type, bound), fd)| {
+            *bound_type == type_ && bound.conflicts_with(addr) && fd.upgrade().is_some()
+        })
+    }
+
+    /// Find a port that is not in use yet. Returns `None` if all ephemeral ports are taken.
+    fn ephemeral_addr(&mut self, type_: SocketType, ip: IpAddr) -> Option<SocketAddress> {
+        for _ in FIRST_EPHEMERAL_PORT..=u16::MAX {
+            let addr = SocketAddress::Inet(SocketAddr::new(ip, self.next_ephemeral_port));
+            self.next_ephemeral_port =
+                self.next_ephemeral_port.checked_add(1).unwrap_or(FIRST_EPHEMERAL_PORT);
+            if !self.is_in_use(type_, &addr) {
+                return Some(addr);
+            }
+        }
+        None
+    }
+
+    /// Find an abstract name for a unix domain socket that is not in use yet. Like on Linux, the
+    /// names consist of five hex digits. Returns `None` if all of them are taken.
+    fn autobind_addr(&mut self, type_: SocketType) -> Option<SocketAddress> {
+        const NAME_MASK: u32 = 0xfffff;
+        for _ in 0..=NAME_MASK {
+            let name = format!("{:05x}", self.next_autobind_name).into_bytes();
+            let addr = SocketAddress::Unix(Some(name));
+            self.next_autobind_name = self.next_autobind_name.strict_add(1) & NAME_MASK;
+            if !self.is_in_use(type_, &addr) {
+                return Some(addr);
+            }
+        }
+        None
+    }
+
+    fn insert(&mut self, type_: SocketType, addr: SocketAddress, fd: &FileDescriptionRef) {
+        // Remove stale entries so that the table does not grow without bound.
+        self.bound.retain(|_, fd| fd.upgrade().is_some());
+        self.bound.insert((type_, addr), fd.downgrade());
+    }
+
+    /// Find the open socket of the given type that is bound to `addr`, if any.
+    fn find(&self, type_: SocketType, addr: &SocketAddress) -> Option<FileDescriptionRef> {
+        self.bound.iter().find_map(|((bound_type, bound), fd)| {
+            if *bound_type != type_ || !bound.accepts(addr) {
+                return None;
+            }
+            fd.upgrade()
+        })
+    }
+
+    /// Find the socket listening on `addr`, if any.
+    fn find_listener(&self, addr: &SocketAddress) -> Option<FileDescriptionRef> {
+        let fd = self.find(SocketType::Stream, addr)?;
+        let socket = fd.downcast::<Socket>()?;
+        let is_listening = matches!(*socket.state.borrow(), SocketState::Listening { .. });
+        is_listening.then_some(fd)
+    }
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// For more information on the arguments see the socket manpage:
+    /// <https://man7.org/linux/man-pages/man2/socket.2.html>
+    fn socket(
+        &mut self,
+        domain: &OpTy<'tcx>,
+        type_: &OpTy<'tcx>,
+        protocol: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let domain = this.read_scalar(domain)?.to_i32()?;
+        let mut flags = this.read_scalar(type_)?.to_i32()?;
+        let protocol = this.read_scalar(protocol)?.to_i32()?;
+
+        let mut is_sock_nonblock = false;
+        let mut is_sock_cloexec = false;
+
+        // Interpret the flag. Every flag we recognize is "subtracted" from `flags`, so
+        // if there is anything left at the end, that's an unsupported flag.
+        if this.tcx.sess.target.os == "linux" {
+            // SOCK_NONBLOCK only exists on Linux.
+            let sock_nonblock = this.eval_libc_i32("SOCK_NONBLOCK");
+            let sock_cloexec = this.eval_libc_i32("SOCK_CLOEXEC");
+            if flags & sock_nonblock == sock_nonblock {
+                is_sock_nonblock = true;
+                flags &= !sock_nonblock;
+            }
+            if flags & sock_cloexec == sock_cloexec {
+                is_sock_cloexec = true;
+                flags &= !sock_cloexec;
+            }
+        }
+
+        let family = if domain == this.eval_libc_i32("AF_INET") {
+            SocketFamily::Inet
+        } else if domain == this.eval_libc_i32("AF_INET6") {
+            SocketFamily::Inet6
+        } else if domain == this.eval_libc_i32("AF_UNIX") {
+            SocketFamily::Unix
+        } else {
+            throw_unsup_format!(
+                "socket: domain {:#x} is unsupported, \
+                                 only AF_INET, AF_INET6 and AF_UNIX are allowed",
+                domain
+            );
+        };
+        let (type_, default_protocol) = if flags == this.eval_libc_i32("SOCK_STREAM") {
+            (SocketType::Stream, "IPPROTO_TCP")
+        } else if flags == this.eval_libc_i32("SOCK_DGRAM") {
+            (SocketType::Datagram, "IPPROTO_UDP")
+        } else {
+            throw_unsup_format!(
+                "socket: type {:#x} is unsupported, only SOCK_STREAM, SOCK_DGRAM, \
+                                 SOCK_CLOEXEC and SOCK_NONBLOCK are allowed",
+                flags
+            );
+        };
+        if family == SocketFamily::Unix {
+            if protocol != 0 {
+                throw_unsup_format!(
+                    "socket: socket protocol {protocol} is unsupported, \
+                                     only 0 is allowed for AF_UNIX",
+                );
+            }
+        } else if protocol != 0 && protocol != this.eval_libc_i32(default_protocol) {
+            throw_unsup_format!(
+                "socket: socket protocol {protocol} is unsupported, \
+                                 only 0 and {default_protocol} are allowed",
+            );
+        }
+
+        let fds = &mut this.machine.fds;
+        let fd = fds.insert_new(Socket::new(family, type_, is_sock_nonblock));
+        fds.set_cloexec(fd, is_sock_cloexec);
+        interp_ok(Scalar::from_i32(fd))
+    }
+
+    fn bind(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        address: &OpTy<'tcx>,
+        address_len: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let address = this.read_pointer(address)?;
+        let address_len = this.read_scalar(address_len)?.to_u32()?;
+
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+        };
+        let addr = match this.read_socket_address(address, address_len, sock.family)? {
+            Ok(addr) => addr,
+            Err(e) => return this.set_last_error_and_return_i32(e),
+        };
+
+        if !matches!(*sock.state.borrow(), SocketState::Unbound) {
+            // The socket is already bound.
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        }
+        if let SocketAddress::Inet(inet_addr) = &addr
+            && !inet_addr.ip().is_loopback()
+            && !inet_addr.ip().is_unspecified()
+        {
+            // We only simulate the loopback interface.
+            return this.set_last_error_and_return_i32(LibcError("EADDRNOTAVAIL"));
+        }
+
+        let sockets = &mut this.machine.sockets;
+        let addr = match addr {
+            SocketAddress::Inet(addr) if addr.port() == 0 =>
+                sockets.ephemeral_addr(sock.type_, addr.ip()),
+            // Binding a unix domain socket without a name picks an unused abstract name.
+            SocketAddress::Unix(None) => sockets.autobind_addr(sock.type_),
+            addr => (!sockets.is_in_use(sock.type_, &addr)).then_some(addr),
+        };
+        let Some(addr) = addr else {
+            return this.set_last_error_and_return_i32(LibcError("EADDRINUSE"));
+        };
+        sockets.insert(sock.type_, addr.clone(), &fd);
+        *sock.state.borrow_mut() = SocketState::Bound(addr);
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn listen(&mut self, socket: &OpTy<'tcx>, backlog: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let backlog = this.read_scalar(backlog)?.to_i32()?;
+        // Like Linux, we treat a backlog of 0 as 1, and silently clamp large values.
+        let backlog = usize::try_from(backlog).unwrap_or(MAX_BACKLOG).clamp(1, MAX_BACKLOG);
+
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+        };
+        if sock.type_ != SocketType::Stream {
+            return this.set_last_error_and_return_i32(LibcError("EOPNOTSUPP"));
+        }
+
+        let mut state = sock.state.borrow_mut();
+        let addr = match &mut *state {
+            SocketState::Unbound if sock.family == SocketFamily::Unix => {
+                // Unix domain sockets have to be bound explicitly.
+                drop(state);
+                return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+            }
+            SocketState::Unbound => {
+                // Listening on an unbound socket binds it to an ephemeral port.
+                let Some(addr) = this
+                    .machine
+                    .sockets
+                    .ephemeral_addr(SocketType::Stream, sock.family.unspecified())
+                else {
+                    drop(state);
+                    return this.set_last_error_and_return_i32(LibcError("EADDRINUSE"));
+                };
+                this.machine.sockets.insert(SocketType::Stream, addr.clone(), &fd);
+                addr
+            }
+            SocketState::Bound(addr) => addr.clone(),
+            SocketState::Listening { backlog: old_backlog, .. } => {
+                // Calling `listen` again just updates the backlog.
+                *old_backlog = backlog;
+                return interp_ok(Scalar::from_i32(0));
+            }
+            SocketState::Connected { .. } => {
+                drop(state);
+                return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+            }
+        };
+        *state = SocketState::Listening { addr, backlog, pending: VecDeque::new() };
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn connect(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        address: &OpTy<'tcx>,
+        address_len: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let address = this.read_pointer(address)?;
+        let address_len = this.read_scalar(address_len)?.to_u32()?;
+
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+        };
+        let peer = match this.read_socket_address(address, address_len, sock.family)? {
+            Ok(addr) => addr,
+            Err(e) => return this.set_last_error_and_return_i32(e),
+        };
+        let peer = match peer {
+            SocketAddress::Inet(mut peer) => {
+                // Connecting to the unspecified address means connecting to the local host.
+                if peer.ip().is_unspecified() {
+                    peer.set_ip(sock.family.loopback());
+                }
+                if !peer.ip().is_loopback() {
+                    throw_unsup_format!(
+                        "connect: only connections to the loopback interface are supported, \
+                                         not to {}",
+                        peer.ip()
+                    );
+                }
+                SocketAddress::Inet(peer)
+            }
+            SocketAddress::Unix(None) =>
+                return this.set_last_error_and_return_i32(LibcError("EINVAL")),
+            peer => peer,
+        };
+
+        if sock.type_ == SocketType::Datagram {
+            // Unlike with UDP, the peer of a unix domain socket has to exist.
+            if matches!(peer, SocketAddress::Unix(_))
+                && this.machine.sockets.find(SocketType::Datagram, &peer).is_none()
+            {
+                return this.set_last_error_and_return_i32(LibcError("ECONNREFUSED"));
+            }
+            // Connecting a datagram socket just sets the default destination, and makes it ignore
+            // datagrams from other addresses. It can be connected again at any time.
+            let Some(local) = bind_datagram_socket(&fd, this) else {
+                return this.set_last_error_and_return_i32(LibcError("EAGAIN"));
+            };
+            let local = local.local_for(&peer);
+            *sock.state.borrow_mut() = SocketState::Connected { local, peer };
+            return interp_ok(Scalar::from_i32(0));
+        }
+
+        let local = match &*sock.state.borrow() {
+            SocketState::Unbound => None,
+            SocketState::Bound(addr) => Some(addr.clone()),
+            SocketState::Listening { .. } =>
+                return this.set_last_error_and_return_i32(LibcError("EINVAL")),
+            SocketState::Connected { .. } =>
+                return this.set_last_error_and_return_i32(LibcError("EISCONN")),
+        };
+
+        // There is no network in between, so the connection attempt succeeds or fails right away.
+        // Non-blocking sockets still go through the usual sequence: `connect` fails with
+        // `EINPROGRESS`, the socket becomes writable, and the result is reported by `SO_ERROR`.
+        let Some(listener_ref) = this.machine.sockets.find_listener(&peer) else {
+            return connect_refused(&fd, this);
+        };
+        let listener = listener_ref.downcast::<Socket>().unwrap();
+        if let SocketState::Listening { backlog, pending, .. } = &*listener.state.borrow() {
+            if pending.len() >= *backlog {
+                // A real system would retry for a while, but the only way for the backlog to
+                // shrink is for another thread to run, so we cannot wait here.
+                return connect_refused(&fd, this);
+            }
+        }
+
+        // Pick the local address: either the one we are bound to (with the unspecified address
+        // replaced by the loopback address), or a fresh ephemeral port. An unbound unix domain
+        // socket stays unnamed.
+        let local = match (local, &peer) {
+            (Some(local), _) => local.local_for(&peer),
+            (None, SocketAddress::Inet(peer)) => {
+                let Some(local) =
+                    this.machine.sockets.ephemeral_addr(SocketType::Stream, peer.ip())
+                else {
+                    return this.set_last_error_and_return_i32(LibcError("EADDRNOTAVAIL"));
+                };
+                this.machine.sockets.insert(SocketType::Stream, local.clone(), &fd);
+                local
+            }
+            (None, SocketAddress::Unix(_)) => SocketAddress::Unix(None),
+        };
+
+        // Create the server-side end of the connection, which will be returned by `accept`.
+        let server = Socket::new(sock.family, SocketType::Stream, /* is_nonblock */ false);
+        // Like on Linux, the accepted socket inherits the options of the listener.
+        *server.options.borrow_mut() =
+            SocketOptions { error: 0, ..listener.options.borrow().clone() };
+        server
+            .stream
+            .set(AnonSocket::new(/* has_readbuf */ true, /* is_nonblock */ false))
+            .unwrap();
+        *server.state.borrow_mut() =
+            SocketState::Connected { local: peer.clone(), peer: local.clone() };
+        let server_ref = this.machine.fds.new_ref(server);
+
+        sock.stream.set(AnonSocket::new(/* has_readbuf */ true, sock.is_nonblock.get())).unwrap();
+        *sock.state.borrow_mut() = SocketState::Connected { local, peer };
+        AnonSocket::connect(&fd, &server_ref);
+
+        // Queue the connection, remembering our clock so that `accept` can synchronize with us.
+        let mut clock = VClock::default();
+        this.release_clock(|release_clock| clock.join(release_clock));
+        match &mut *listener.state.borrow_mut() {
+            SocketState::Listening { pending, .. } => pending.push_back((server_ref, clock)),
+            _ => unreachable!(),
+        }
+
+        // The listener became readable and our socket became writable.
+        this.check_and_update_readiness(&listener_ref)?;
+        this.check_and_update_readiness(&fd)?;
+        // Unblock all threads blocked in `accept`.
+        // We need to take out the blocked thread ids and unblock them together,
+        // because `unblock_threads` may block them again and end up re-adding the
+        // thread to the blocked list.
+        let waiting_threads = std::mem::take(&mut *listener.blocked_accept_tid.borrow_mut());
+        for thread_id in waiting_threads {
+            this.unblock_thread(thread_id, BlockReason::Socket)?;
+        }
+
+        if sock.is_nonblock.get() {
+            // The connection is already established, but the caller has to check `SO_ERROR`
+            // once the socket is writable.
+            return this.set_last_error_and_return_i32(LibcError("EINPROGRESS"));
+        }
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    /// Implements `accept` and `accept4`. `flags` is `None` for `accept`.
+    fn accept4(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        address: &OpTy<'tcx>,
+        address_len: &OpTy<'tcx>,
+        flags: Option<&OpTy<'tcx>>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let address = this.read_pointer(address)?;
+        let address_len = this.read_pointer(address_len)?;
+        let mut flags = match flags {
+            Some(flags) => this.read_scalar(flags)?.to_i32()?,
+            None => 0,
+        };
+
+        let mut is_sock_nonblock = false;
+        let mut is_sock_cloexec = false;
+        // Interpret the flag. Every flag we recognize is "subtracted" from `flags`, so
+        // if there is anything left at the end, that's an unsupported flag.
+        if flags != 0 {
+            let sock_nonblock = this.eval_libc_i32("SOCK_NONBLOCK");
+            let sock_cloexec = this.eval_libc_i32("SOCK_CLOEXEC");
+            if flags & sock_nonblock == sock_nonblock {
+                is_sock_nonblock = true;
+                flags &= !sock_nonblock;
+            }
+            if flags & sock_cloexec == sock_cloexec {
+                is_sock_cloexec = true;
+                flags &= !sock_cloexec;
+            }
+            if flags != 0 {
+                return this.set_last_error_and_return(LibcError("EINVAL"), dest);
+            }
+        }
+
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return(LibcError("ENOTSOCK"), dest);
+        };
+        if sock.type_ != SocketType::Stream {
+            return this.set_last_error_and_return(LibcError("EOPNOTSUPP"), dest);
+        }
+        let deadline = socket_deadline(&fd, /* is_recv */ true, this);
+        socket_accept(
+            fd.downgrade(),
+            address,
+            address_len,
+            is_sock_nonblock,
+            is_sock_cloexec,
+            deadline,
+            dest,
+            this,
+        )
+    }
+
+    fn getsockname(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        address: &OpTy<'tcx>,
+        address_len: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let address = this.read_pointer(address)?;
+        let address_len = this.read_pointer(address_len)?;
+
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+        };
+        // An unbound socket reports the unspecified address.
+        let addr = sock.local_addr().unwrap_or_else(|| sock.family.unbound_addr());
+        this.write_socket_address(&addr, address, address_len)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn getpeername(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        address: &OpTy<'tcx>,
+        address_len: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let address = this.read_pointer(address)?;
+        let address_len = this.read_pointer(address_len)?;
+
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+        };
+        let Some(addr) = sock.peer_addr() else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTCONN"));
+        };
+        this.write_socket_address(&addr, address, address_len)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn recv(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        buf: &OpTy<'tcx>,
+        len: &OpTy<'tcx>,
+        flags: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let buf = this.read_pointer(buf)?;
+        let len = this.read_target_usize(len)?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+
+        let (flags, is_peek, is_dontwait) = read_recv_flags(this, flags);
+        if flags != 0 {
+            throw_unsup_format!("recv: flags {flags:#x} are not supported");
+        }
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        let len = cap_recv_len(this, buf, len)?;
+        socket_recv(
+            &fd,
+            vec![(buf, len)],
+            is_peek,
+            is_dontwait,
+            Pointer::null(),
+            Pointer::null(),
+            dest,
+            this,
+        )
+    }
+
+    fn send(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        buf: &OpTy<'tcx>,
+        len: &OpTy<'tcx>,
+        flags: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let buf = this.read_pointer(buf)?;
+        let len = this.read_target_usize(len)?;
+        let mut flags = this.read_scalar(flags)?.to_i32()?;
+
+        // Miri never raises `SIGPIPE`, so `MSG_NOSIGNAL` does not change anything.
+        if matches!(&*this.tcx.sess.target.os, "linux" | "android" | "freebsd") {
+            flags &= !this.eval_libc_i32("MSG_NOSIGNAL");
+        }
+        if flags != 0 {
+            throw_unsup_format!("send: flags {flags:#x} are not supported");
+        }
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        if fd.downcast::<Socket>().is_none() {
+            return this.set_last_error_and_return(LibcError("ENOTSOCK"), dest);
+        }
+        // Without flags, `send` is equivalent to `write`.
+        this.write(socket, buf, len, None, dest)
+    }
+
+    fn recvfrom(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        buf: &OpTy<'tcx>,
+        len: &OpTy<'tcx>,
+        flags: &OpTy<'tcx>,
+        address: &OpTy<'tcx>,
+        address_len: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let buf = this.read_pointer(buf)?;
+        let len = this.read_target_usize(len)?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+        let address = this.read_pointer(address)?;
+        let address_len = this.read_pointer(address_len)?;
+
+        let (flags, is_peek, is_dontwait) = read_recv_flags(this, flags);
+        if flags != 0 {
+            throw_unsup_format!("recvfrom: flags {flags:#x} are not supported");
+        }
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        let len = cap_recv_len(this, buf, len)?;
+        socket_recv(&fd, vec![(buf, len)], is_peek, is_dontwait, address, address_len, dest, this)
+    }
+
+    fn sendto(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        buf: &OpTy<'tcx>,
+        len: &OpTy<'tcx>,
+        flags: &OpTy<'tcx>,
+        address: &OpTy<'tcx>,
+        address_len: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let buf = this.read_pointer(buf)?;
+        let len = this.read_target_usize(len)?;
+        let mut flags = this.read_scalar(flags)?.to_i32()?;
+        let address = this.read_pointer(address)?;
+        let address_len = this.read_scalar(address_len)?.to_u32()?;
+
+        // Miri never raises `SIGPIPE`, so `MSG_NOSIGNAL` does not change anything.
+        if matches!(&*this.tcx.sess.target.os, "linux" | "android" | "freebsd") {
+            flags &= !this.eval_libc_i32("MSG_NOSIGNAL");
+        }
+        if flags != 0 {
+            throw_unsup_format!("sendto: flags {flags:#x} are not supported");
+        }
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return(LibcError("ENOTSOCK"), dest);
+        };
+        if sock.type_ == SocketType::Stream {
+            // Like Linux, we ignore the address for stream sockets.
+            return this.write(socket, buf, len, None, dest);
+        }
+        let to = if this.ptr_is_null(address)? {
+            None
+        } else {
+            match this.read_socket_address(address, address_len, sock.family)? {
+                Ok(addr) => Some(addr),
+                Err(e) => return this.set_last_error_and_return(e, dest),
+            }
+        };
+        // Anything that does not fit into a `usize` is too large for a datagram anyway.
+        let len = usize::try_from(len).unwrap_or(usize::MAX);
+        if len > MAX_DATAGRAM_SIZE {
+            return this.set_last_error_and_return(LibcError("EMSGSIZE"), dest);
+        }
+        let bytes = this.read_bytes_ptr_strip_provenance(buf, Size::from_bytes(len))?.to_vec();
+        match datagram_send(&fd, &bytes, to, this)? {
+            Ok(actual_write_size) => this.return_write_success(actual_write_size, dest),
+            Err(e) => this.set_last_error_and_return(e, dest),
+        }
+    }
+
+    /// For more information on the arguments see the sendmsg manpage:
+    /// <https://man7.org/linux/man-pages/man2/sendmsg.2.html>
+    fn sendmsg(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        msg: &OpTy<'tcx>,
+        flags: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let msg = this.read_pointer(msg)?;
+        let mut flags = this.read_scalar(flags)?.to_i32()?;
+
+        // Miri never raises `SIGPIPE`, so `MSG_NOSIGNAL` does not change anything.
+        if matches!(&*this.tcx.sess.target.os, "linux" | "android" | "freebsd") {
+            flags &= !this.eval_libc_i32("MSG_NOSIGNAL");
+        }
+        if flags != 0 {
+            throw_unsup_format!("sendmsg: flags {flags:#x} are not supported");
+        }
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        let deadline = socket_deadline(&fd, /* is_recv */ false, this);
+        socket_sendmsg(fd.downgrade(), msg, deadline, dest, this)
+    }
+
+    /// For more information on the arguments see the recvmsg manpage:
+    /// <https://man7.org/linux/man-pages/man2/recvmsg.2.html>
+    fn recvmsg(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        msg: &OpTy<'tcx>,
+        flags: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let msg = this.read_pointer(msg)?;
+        let flags = this.read_scalar(flags)?.to_i32()?;
+
+        let (mut flags, is_peek, is_dontwait) = read_recv_flags(this, flags);
+        let mut is_cmsg_cloexec = false;
+        if matches!(&*this.tcx.sess.target.os, "linux" | "android" | "freebsd") {
+            let msg_cmsg_cloexec = this.eval_libc_i32("MSG_CMSG_CLOEXEC");
+            if flags & msg_cmsg_cloexec == msg_cmsg_cloexec {
+                is_cmsg_cloexec = true;
+                flags &= !msg_cmsg_cloexec;
+            }
+        }
+        if flags != 0 {
+            throw_unsup_format!("recvmsg: flags {flags:#x} are not supported");
+        }
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return(LibcError("EBADF"), dest);
+        };
+        let deadline = socket_deadline(&fd, /* is_recv */ true, this);
+        socket_recvmsg(
+            fd.downgrade(),
+            msg,
+            is_peek,
+            is_dontwait,
+            is_cmsg_cloexec,
+            deadline,
+            dest,
+            this,
+        )
+    }
+
+    fn setsockopt(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        level: &OpTy<'tcx>,
+        option_name: &OpTy<'tcx>,
+        option_value: &OpTy<'tcx>,
+        option_len: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let level = this.read_scalar(level)?.to_i32()?;
+        let option_name = this.read_scalar(option_name)?.to_i32()?;
+        let option_value = this.read_pointer(option_value)?;
+        let option_len = this.read_scalar(option_len)?.to_u32()?;
+
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+        };
+        let option = match this.socket_option(sock, level, option_name)? {
+            Ok(option) => option,
+            Err(e) => return this.set_last_error_and_return_i32(e),
+        };
+
+        if option == SocketOption::Linger {
+            let linger_layout = this.libc_ty_layout("linger");
+            if u64::from(option_len) < linger_layout.size.bytes() {
+                return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+            }
+            let linger = this.ptr_to_mplace(option_value, linger_layout);
+            let onoff =
+                this.read_scalar(&this.project_field_named(&linger, "l_onoff")?)?.to_i32()?;
+            let timeout =
+                this.read_scalar(&this.project_field_named(&linger, "l_linger")?)?.to_i32()?;
+            sock.options.borrow_mut().linger = (onoff != 0).then_some(timeout);
+            return interp_ok(Scalar::from_i32(0));
+        }
+
+        if matches!(option, SocketOption::RcvTimeo | SocketOption::SndTimeo) {
+            let timeval_layout = this.libc_ty_layout("timeval");
+            if u64::from(option_len) < timeval_layout.size.bytes() {
+                return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+            }
+            let timeval = this.ptr_to_mplace(option_value, timeval_layout);
+            let Some(timeout) = this.read_timeval(&timeval)? else {
+                return this.set_last_error_and_return_i32(LibcError("EDOM"));
+            };
+            // A zero timeout means that blocking never times out.
+            let timeout = (!timeout.is_zero()).then_some(timeout);
+            let mut options = sock.options.borrow_mut();
+            if option == SocketOption::RcvTimeo {
+                options.rcvtimeo = timeout;
+            } else {
+                options.sndtimeo = timeout;
+            }
+            return interp_ok(Scalar::from_i32(0));
+        }
+
+        // All other options are an `int`.
+        if u64::from(option_len) < this.machine.layouts.i32.size.bytes() {
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        }
+        let value = this.ptr_to_mplace(option_value, this.machine.layouts.i32);
+        let value = this.read_scalar(&value)?.to_i32()?;
+        let mut options = sock.options.borrow_mut();
+        match option {
+            SocketOption::ReuseAddr => options.reuseaddr = value != 0,
+            SocketOption::NoSigPipe => options.nosigpipe = value != 0,
+            SocketOption::NoDelay => options.nodelay = value != 0,
+            SocketOption::RcvBuf | SocketOption::SndBuf => {
+                // Linux doubles the value to make room for bookkeeping overhead, and reports the
+                // doubled value in `getsockopt`.
+                let value = if this.tcx.sess.target.os == "linux" {
+                    value.max(0).saturating_mul(2)
+                } else {
+                    value
+                };
+                if option == SocketOption::RcvBuf {
+                    options.rcvbuf = value;
+                } else {
+                    options.sndbuf = value;
+                }
+            }
+            SocketOption::Error => {
+                // `SO_ERROR` can only be read.
+                drop(options);
+                return this.set_last_error_and_return_i32(LibcError("ENOPROTOOPT"));
+            }
+            SocketOption::Linger | SocketOption::RcvTimeo | SocketOption::SndTimeo =>
+                unreachable!(),
+        }
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn getsockopt(
+        &mut self,
+        socket: &OpTy<'tcx>,
+        level: &OpTy<'tcx>,
+        option_name: &OpTy<'tcx>,
+        option_value: &OpTy<'tcx>,
+        option_len: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let socket = this.read_scalar(socket)?.to_i32()?;
+        let level = this.read_scalar(level)?.to_i32()?;
+        let option_name = this.read_scalar(option_name)?.to_i32()?;
+        let option_value = this.read_pointer(option_value)?;
+        let option_len = this.deref_pointer_as(option_len, this.libc_ty_layout("socklen_t"))?;
+        let capacity = this.read_scalar(&option_len)?.to_u32()?;
+
+        let Some(fd) = this.machine.fds.get(socket) else {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        };
+        let Some(sock) = fd.downcast::<Socket>() else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+        };
+        let option = match this.socket_option(sock, level, option_name)? {
+            Ok(option) => option,
+            Err(e) => return this.set_last_error_and_return_i32(e),
+        };
+
+        if option == SocketOption::Linger {
+            let linger_layout = this.libc_ty_layout("linger");
+            if u64::from(capacity) < linger_layout.size.bytes() {
+                return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+            }
+            let (onoff, timeout): (i32, i32) = match sock.options.borrow().linger {
+                Some(timeout) => (1, timeout),
+                None => (0, 0),
+            };
+            let linger = this.ptr_to_mplace(option_value, linger_layout);
+            this.write_int_fields_named(
+                &[("l_onoff", onoff.into()), ("l_linger", timeout.into())],
+                &linger,
+            )?;
+            this.write_int(linger_layout.size.bytes(), &option_len)?;
+            return interp_ok(Scalar::from_i32(0));
+        }
+
+        if matches!(option, SocketOption::RcvTimeo | SocketOption::SndTimeo) {
+            let timeval_layout = this.libc_ty_layout("timeval");
+            if u64::from(capacity) < timeval_layout.size.bytes() {
+                return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+            }
+            let options = sock.options.borrow();
+            let timeout =
+                if option == SocketOption::RcvTimeo { options.rcvtimeo } else { options.sndtimeo };
+            drop(options);
+            let timeout = timeout.unwrap_or(Duration::ZERO);
+            let timeval = this.ptr_to_mplace(option_value, timeval_layout);
+            this.write_int_fields_named(
+                &[
+                    ("tv_sec", timeout.as_secs().into()),
+                    ("tv_usec", timeout.subsec_micros().into()),
+                ],
+                &timeval,
+            )?;
+            this.write_int(timeval_layout.size.bytes(), &option_len)?;
+            return interp_ok(Scalar::from_i32(0));
+        }
+
+        // All other options are an `int`.
+        let int_size = this.machine.layouts.i32.size;
+        if u64::from(capacity) < int_size.bytes() {
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        }
+        let mut options = sock.options.borrow_mut();
+        let value = match option {
+            SocketOption::ReuseAddr => i32::from(options.reuseaddr),
+            SocketOption::NoSigPipe => i32::from(options.nosigpipe),
+            SocketOption::NoDelay => i32::from(options.nodelay),
+            SocketOption::RcvBuf => options.rcvbuf,
+            SocketOption::SndBuf => options.sndbuf,
+            // Reading the pending error clears it.
+            SocketOption::Error => std::mem::take(&mut options.error),
+            SocketOption::Linger | SocketOption::RcvTimeo | SocketOption::SndTimeo =>
+                unreachable!(),
+        };
+        drop(options);
+        let value_place = this.ptr_to_mplace(option_value, this.machine.layouts.i32);
+        this.write_scalar(Scalar::from_i32(value), &value_place)?;
+        this.write_int(int_size.bytes(), &option_len)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    /// Determine which option `setsockopt` and `getsockopt` refer to.
+    fn socket_option(
+        &self,
+        sock: &Socket,
+        level: i32,
+        option_name: i32,
+    ) -> InterpResult<'tcx, Result<SocketOption, IoError>> {
+        let this = self.eval_context_ref();
+
+        if level == this.eval_libc_i32("SOL_SOCKET") {
+            let option = if option_name == this.eval_libc_i32("SO_REUSEADDR") {
+                SocketOption::ReuseAddr
+            } else if option_name == this.eval_libc_i32("SO_RCVBUF") {
+                SocketOption::RcvBuf
+            } else if option_name == this.eval_libc_i32("SO_SNDBUF") {
+                SocketOption::SndBuf
+            } else if option_name == this.eval_libc_i32("SO_ERROR") {
+                SocketOption::Error
+            } else if option_name == this.eval_libc_i32("SO_LINGER") {
+                SocketOption::Linger
+            } else if option_name == this.eval_libc_i32("SO_RCVTIMEO") {
+                SocketOption::RcvTimeo
+            } else if option_name == this.eval_libc_i32("SO_SNDTIMEO") {
+                SocketOption::SndTimeo
+            } else if this.tcx.sess.target.os == "macos"
+                && option_name == this.eval_libc_i32("SO_NOSIGPIPE")
+            {
+                SocketOption::NoSigPipe
+            } else {
+                throw_unsup_format!(
+                    "socket option {option_name:#x} at level SOL_SOCKET is not supported"
+                );
+            };
+            return interp_ok(Ok(option));
+        }
+        if level == this.eval_libc_i32("IPPROTO_TCP") {
+            if option_name != this.eval_libc_i32("TCP_NODELAY") {
+                throw_unsup_format!(
+                    "socket option {option_name:#x} at level IPPROTO_TCP is not supported"
+                );
+            }
+            if sock.family == SocketFamily::Unix {
+                return interp_ok(Err(LibcError("EOPNOTSUPP")));
+            }
+            if sock.type_ != SocketType::Stream {
+                return interp_ok(Err(LibcError("ENOPROTOOPT")));
+            }
+            return interp_ok(Ok(SocketOption::NoDelay));
+        }
+
+        throw_unsup_format!("socket options at level {level:#x} are not supported");
+    }
+
+    /// Read a `sockaddr_in`, `sockaddr_in6` or `sockaddr_un` (depending on `family`) from the
+    /// given pointer.
+    fn read_socket_address(
+        &self,
+        address: Pointer,
+        address_len: u32,
+        family: SocketFamily,
+    ) -> InterpResult<'tcx, Result<SocketAddress, IoError>> {
+        let this = self.eval_context_ref();
+
+        let (sockaddr_ty, af) = match family {
+            SocketFamily::Inet => ("sockaddr_in", "AF_INET"),
+            SocketFamily::Inet6 => ("sockaddr_in6", "AF_INET6"),
+            SocketFamily::Unix => return this.read_unix_socket_address(address, address_len),
+        };
+        let layout = this.libc_ty_layout(sockaddr_ty);
+        if u64::from(address_len) < layout.size.bytes() {
+            return interp_ok(Err(LibcError("EINVAL")));
+        }
+        let sockaddr = this.ptr_to_mplace(address, layout);
+
+        let family_field = if family == SocketFamily::Inet { "sin_family" } else { "sin6_family" };
+        let addr_family = this.read_scalar(&this.project_field_named(&sockaddr, family_field)?)?;
+        if addr_family.to_int(addr_family.size())? != i128::from(this.eval_libc_i32(af)) {
+            return interp_ok(Err(LibcError("EAFNOSUPPORT")));
+        }
+
+        // The port and the address are stored in network byte order.
+        let read_bytes = |field: &str, len: u64| -> InterpResult<'tcx, Vec<u8>> {
+            let place = this.project_field_named(&sockaddr, field)?;
+            interp_ok(
+                this.read_bytes_ptr_strip_provenance(place.ptr(), Size::from_bytes(len))?.to_vec(),
+            )
+        };
+        let addr = match family {
+            SocketFamily::Inet => {
+                let port = read_bytes("sin_port", 2)?;
+                let ip = read_bytes("sin_addr", 4)?;
+                SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::from(<[u8; 4]>::try_from(ip).unwrap()),
+                    u16::from_be_bytes(port.try_into().unwrap()),
+                ))
+            }
+            SocketFamily::Inet6 => {
+                let port = read_bytes("sin6_port", 2)?;
+                let flowinfo = read_bytes("sin6_flowinfo", 4)?;
+                let ip = read_bytes("sin6_addr", 16)?;
+                let scope_id = this
+                    .read_scalar(&this.project_field_named(&sockaddr, "sin6_scope_id")?)?
+                    .to_u32()?;
+                SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(<[u8; 16]>::try_from(ip).unwrap()),
+                    u16::from_be_bytes(port.try_into().unwrap()),
+                    u32::from_be_bytes(flowinfo.try_into().unwrap()),
+                    scope_id,
+                ))
+            }
+            SocketFamily::Unix => unreachable!(),
+        };
+        interp_ok(Ok(SocketAddress::Inet(addr)))
+    }
+
+    /// Read a `sockaddr_un` of length `address_len` from the given pointer. Only unnamed
+    /// addresses and names in the abstract namespace are supported.
+    fn read_unix_socket_address(
+        &self,
+        address: Pointer,
+        address_len: u32,
+    ) -> InterpResult<'tcx, Result<SocketAddress, IoError>> {
+        let this = self.eval_context_ref();
+
+        let layout = this.libc_ty_layout("sockaddr_un");
+        let sockaddr = this.ptr_to_mplace(address, layout);
+        let sun_path = this.project_field_named(&sockaddr, "sun_path")?;
+        let path_offset = unix_path_offset(&sockaddr, &sun_path);
+        let address_len = u64::from(address_len);
+        if address_len < path_offset || address_len > layout.size.bytes() {
+            return interp_ok(Err(LibcError("EINVAL")));
+        }
+        let addr_family = this.read_scalar(&this.project_field_named(&sockaddr, "sun_family")?)?;
+        if addr_family.to_int(addr_family.size())? != i128::from(this.eval_libc_i32("AF_UNIX")) {
+            return interp_ok(Err(LibcError("EINVAL")));
+        }
+
+        let path_len = Size::from_bytes(address_len.strict_sub(path_offset));
+        let path = this.read_bytes_ptr_strip_provenance(sun_path.ptr(), path_len)?;
+        let addr = match path.split_first() {
+            // An address that is just the family refers to an unnamed socket.
+            None => SocketAddress::Unix(None),
+            // Names in the abstract namespace start with a NUL byte, and can contain anything.
+            Some((0, name)) if matches!(&*this.tcx.sess.target.os, "linux" | "android") =>
+                SocketAddress::Unix(Some(name.to_vec())),
+            Some(_) =>
+                throw_unsup_format!(
+                    "unix domain socket addresses are only supported in the abstract namespace, \
+                                     not in the file system"
+                ),
+        };
+        interp_ok(Ok(addr))
+    }
+
+    /// Write `addr` as a `sockaddr_in`, `sockaddr_in6` or `sockaddr_un` to `address`, whose size
+    /// is given by `*address_len`. Like the real system, we truncate the address if the buffer is
+    /// too small, and set `*address_len` to the full size of the address.
+    /// Does nothing if `address` is null.
+    fn write_socket_address(
+        &mut self,
+        addr: &SocketAddress,
+        address: Pointer,
+        address_len: Pointer,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        if this.ptr_is_null(address)? {
+            return interp_ok(());
+        }
+        let address_len = this.ptr_to_mplace(address_len, this.libc_ty_layout("socklen_t"));
+        let capacity = this.read_scalar(&address_len)?.to_u32()?;
+
+        let layout = this.libc_ty_layout(match addr {
+            SocketAddress::Inet(SocketAddr::V4(_)) => "sockaddr_in",
+            SocketAddress::Inet(SocketAddr::V6(_)) => "sockaddr_in6",
+            SocketAddress::Unix(_) => "sockaddr_un",
+        });
+        // Build the address in a temporary allocation, so that we can copy only a prefix of it.
+        let sockaddr = this.allocate(layout, MiriMemoryKind::Machine.into())?;
+        let len = match addr {
+            SocketAddress::Inet(addr) => {
+                this.write_sockaddr(addr, &sockaddr)?;
+                layout.size.bytes()
+            }
+            SocketAddress::Unix(name) => this.write_unix_sockaddr(name.as_deref(), &sockaddr)?,
+        };
+
+        let copy_len = u64::from(capacity).min(len);
+        this.mem_copy(
+            sockaddr.ptr(),
+            address,
+            Size::from_bytes(copy_len),
+            /*nonoverlapping*/ true,
+        )?;
+        this.deallocate_ptr(sockaddr.ptr(), None, MiriMemoryKind::Machine.into())?;
+        this.write_int(len, &address_len)?;
+
+        interp_ok(())
+    }
+
+    /// Write `addr` to `sockaddr`, which must be a `sockaddr_in` or `sockaddr_in6` matching the
+    /// family of `addr`. Padding is zeroed.
+    fn write_sockaddr(
+        &mut self,
+        addr: &SocketAddr,
+        sockaddr: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let layout = sockaddr.layout;
+        this.write_bytes_ptr(sockaddr.ptr(), std::iter::repeat_n(0u8, layout.size.bytes_usize()))?;
+        // Some targets have a length field.
+        for len_field in ["sin_len", "sin6_len"] {
+            if this.projectable_has_field(sockaddr, len_field) {
+                let field = this.project_field_named(sockaddr, len_field)?;
+                this.write_int(layout.size.bytes(), &field)?;
+            }
+        }
+        match addr {
+            SocketAddr::V4(addr) => {
+                let af_inet = this.eval_libc_i32("AF_INET");
+                this.write_int(af_inet, &this.project_field_named(sockaddr, "sin_family")?)?;
+                let port = this.project_field_named(sockaddr, "sin_port")?;
+                this.write_bytes_ptr(port.ptr(), addr.port().to_be_bytes())?;
+                let ip = this.project_field_named(sockaddr, "sin_addr")?;
+                this.write_bytes_ptr(ip.ptr(), addr.ip().octets())?;
+            }
+            SocketAddr::V6(addr) => {
+                let af_inet6 = this.eval_libc_i32("AF_INET6");
+                this.write_int(af_inet6, &this.project_field_named(sockaddr, "sin6_family")?)?;
+                let port = this.project_field_named(sockaddr, "sin6_port")?;
+                this.write_bytes_ptr(port.ptr(), addr.port().to_be_bytes())?;
+                let flowinfo = this.project_field_named(sockaddr, "sin6_flowinfo")?;
+                this.write_bytes_ptr(flowinfo.ptr(), addr.flowinfo().to_be_bytes())?;
+                let ip = this.project_field_named(sockaddr, "sin6_addr")?;
+                this.write_bytes_ptr(ip.ptr(), addr.ip().octets())?;
+                let scope_id = this.project_field_named(sockaddr, "sin6_scope_id")?;
+                this.write_int(addr.scope_id(), &scope_id)?;
+            }
+        }
+
+        interp_ok(())
+    }
+
+    /// Write the unix domain socket address with the given abstract `name` (or the address of an
+    /// unnamed socket) to `sockaddr`, which must be a `sockaddr_un`. Padding is zeroed.
+    /// Returns the length of the address.
+    fn write_unix_sockaddr(
+        &mut self,
+        name: Option<&[u8]>,
+        sockaddr: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx, u64> {
+        let this = self.eval_context_mut();
+
+        let layout = sockaddr.layout;
+        this.write_bytes_ptr(sockaddr.ptr(), std::iter::repeat_n(0u8, layout.size.bytes_usize()))?;
+        let sun_path = this.project_field_named(sockaddr, "sun_path")?;
+        let mut len = unix_path_offset(sockaddr, &sun_path);
+        if let Some(name) = name {
+            // The leading NUL byte is already there.
+            let name_start = this.project_index(&sun_path, 1)?;
+            this.write_bytes_ptr(name_start.ptr(), name.iter().copied())?;
+            len = len.strict_add(1).strict_add(u64::try_from(name.len()).unwrap());
+        }
+        // Some targets have a length field.
+        if this.projectable_has_field(sockaddr, "sun_len") {
+            this.write_int(len, &this.project_field_named(sockaddr, "sun_len")?)?;
+        }
+        let af_unix = this.eval_libc_i32("AF_UNIX");
+        this.write_int(af_unix, &this.project_field_named(sockaddr, "sun_family")?)?;
+
+        interp_ok(len)
+    }
+}
+
+/// The offset of the `sun_path` field in the `sockaddr_un` place, which is the length of the
+/// address of an unnamed socket.
+fn unix_path_offset<'tcx>(sockaddr: &MPlaceTy<'tcx>, sun_path: &MPlaceTy<'tcx>) -> u64 {
+    // `sun_path` is the last field, and there is no padding after it.
+    sockaddr.layout.size.bytes().strict_sub(sun_path.layout.size.bytes())
+}
+
+/// Fail a `connect` on the stream socket `fd` with `ECONNREFUSED`. For a non-blocking socket,
+/// `connect` fails with `EINPROGRESS` instead, and the error is reported by `SO_ERROR`.
+fn connect_refused<'tcx>(
+    fd: &FileDescriptionRef,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, Scalar> {
+    let sock = fd.downcast::<Socket>().unwrap();
+    if !sock.is_nonblock.get() {
+        return ecx.set_last_error_and_return_i32(LibcError("ECONNREFUSED"));
+    }
+    sock.options.borrow_mut().error = ecx.eval_libc_i32("ECONNREFUSED");
+    // The error makes the socket ready.
+    ecx.check_and_update_readiness(fd)?;
+    ecx.set_last_error_and_return_i32(LibcError("EINPROGRESS"))
+}
+
+/// Take a connection from the backlog of the listening socket, blocking if there is none.
+/// Blocking fails with EWOULDBLOCK once the `deadline` (computed by `deadline_after`) has passed.
+fn socket_accept<'tcx>(
+    weak_listener: WeakFileDescriptionRef,
+    address: Pointer,
+    address_len: Pointer,
+    is_sock_nonblock: bool,
+    is_sock_cloexec: bool,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let Some(listener_ref) = weak_listener.upgrade() else {
+        throw_unsup_format!("socket FD got closed while blocking in `accept`.")
+    };
+    // We checked before that this is a socket.
+    let listener = listener_ref.downcast::<Socket>().unwrap();
+
+    let conn = match &mut *listener.state.borrow_mut() {
+        SocketState::Listening { pending, .. } => pending.pop_front(),
+        _ => return ecx.set_last_error_and_return(LibcError("EINVAL"), dest),
+    };
+    let Some((conn, clock)) = conn else {
+        if listener.is_nonblock.get() || deadline_passed(deadline, ecx) {
+            return ecx.set_last_error_and_return(ErrorKind::WouldBlock, dest);
+        }
+        // Block until a connection arrives.
+        listener.blocked_accept_tid.borrow_mut().push(ecx.active_thread());
+        let dest = dest.clone();
+        // `bool` does not implement `VisitProvenance`, so we pass the flags as integers.
+        let is_sock_nonblock = u8::from(is_sock_nonblock);
+        let is_sock_cloexec = u8::from(is_sock_cloexec);
+        ecx.block_thread(
+            BlockReason::Socket,
+            deadline_timeout(deadline),
+            callback!(
+                @capture<'tcx> {
+                    weak_listener: WeakFileDescriptionRef,
+                    address: Pointer,
+                    address_len: Pointer,
+                    is_sock_nonblock: u8,
+                    is_sock_cloexec: u8,
+                    deadline: Option<u64>,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    // When we get unblocked, try again.
+                    socket_accept(
+                        weak_listener,
+                        address,
+                        address_len,
+                        is_sock_nonblock != 0,
+                        is_sock_cloexec != 0,
+                        deadline,
+                        &dest,
+                        this,
+                    )
+                }
+                @timeout = |this| {
+                    remove_timed_out_thread(&weak_listener, this);
+                    this.set_last_error_and_return(ErrorKind::WouldBlock, &dest)
+                }
+            ),
+        );
+        return interp_ok(());
+    };
+
+    // Synchronize with the thread that connected.
+    ecx.acquire_clock(&clock);
+    // The listener might not be readable any more.
+    ecx.check_and_update_readiness(&listener_ref)?;
+
+    let sock = conn.downcast::<Socket>().unwrap();
+    sock.is_nonblock.set(is_sock_nonblock);
+    sock.stream().unwrap().set_nonblock(is_sock_nonblock);
+    let peer = sock.peer_addr().unwrap();
+
+    let fd = ecx.machine.fds.insert(conn);
+    ecx.machine.fds.set_cloexec(fd, is_sock_cloexec);
+    ecx.write_socket_address(&peer, address, address_len)?;
+    ecx.write_int(fd, dest)
+}
+
+/// Make sure the datagram socket is bound, binding it to an ephemeral port if necessary.
+/// Returns the local address, or `None` if there is no free port. A unix domain socket does not
+/// get bound, and stays unnamed instead.
+fn bind_datagram_socket<'tcx>(
+    self_ref: &FileDescriptionRef,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> Option<SocketAddress> {
+    let sock = self_ref.downcast::<Socket>().unwrap();
+    if let Some(local) = sock.local_addr() {
+        return Some(local);
+    }
+    if sock.family == SocketFamily::Unix {
+        return Some(SocketAddress::Unix(None));
+    }
+    let local =
+        ecx.machine.sockets.ephemeral_addr(SocketType::Datagram, sock.family.unspecified())?;
+    ecx.machine.sockets.insert(SocketType::Datagram, local.clone(), self_ref);
+    *sock.state.borrow_mut() = SocketState::Bound(local.clone());
+    Some(local)
+}
+
+/// Send a datagram to `to`, or to the connected peer if `to` is `None`.
+/// Like with UDP, delivery is not guaranteed: a datagram gets dropped if there is no socket bound
+/// to the destination, or if the queue of the destination is full. Unix domain sockets report
+/// these cases as errors instead.
+/// Sending a datagram never blocks.
+fn datagram_send<'tcx>(
+    self_ref: &FileDescriptionRef,
+    data: &[u8],
+    to: Option<SocketAddress>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, Result<usize, IoError>> {
+    let sock = self_ref.downcast::<Socket>().unwrap();
+
+    let Some(to) = to.or_else(|| sock.peer_addr()) else {
+        return interp_ok(Err(LibcError("EDESTADDRREQ")));
+    };
+    let to = match to {
+        SocketAddress::Inet(mut to) => {
+            if to.ip().is_unspecified() {
+                to.set_ip(sock.family.loopback());
+            }
+            if !to.ip().is_loopback() {
+                throw_unsup_format!(
+                    "sendto: only sending to the loopback interface is supported, not to {}",
+                    to.ip()
+                );
+            }
+            SocketAddress::Inet(to)
+        }
+        SocketAddress::Unix(None) => return interp_ok(Err(LibcError("EINVAL"))),
+        to => to,
+    };
+    let is_unix = matches!(to, SocketAddress::Unix(_));
+    if data.len() > MAX_DATAGRAM_SIZE {
+        return interp_ok(Err(LibcError("EMSGSIZE")));
+    }
+
+    // Sending from an unbound socket binds it to an ephemeral port.
+    let Some(from) = bind_datagram_socket(self_ref, ecx) else {
+        return interp_ok(Err(LibcError("EAGAIN")));
+    };
+    let from = from.local_for(&to);
+
+    let Some(receiver_ref) = ecx.machine.sockets.find(SocketType::Datagram, &to) else {
+        if is_unix {
+            return interp_ok(Err(LibcError("ECONNREFUSED")));
+        }
+        return interp_ok(Ok(data.len()));
+    };
+    let receiver = receiver_ref.downcast::<Socket>().unwrap();
+    // A connected datagram socket only receives datagrams from its peer.
+    let is_accepted = receiver.peer_addr().is_none_or(|peer| peer == from);
+    let queued: usize = receiver.datagrams.borrow().iter().map(|d| d.data.len()).sum();
+    let is_full = queued.strict_add(data.len()) > MAX_DATAGRAM_QUEUE_SIZE;
+    if !is_accepted || is_full {
+        if is_unix {
+            return interp_ok(Err(LibcError(if is_accepted { "EAGAIN" } else { "EPERM" })));
+        }
+        return interp_ok(Ok(data.len()));
+    }
+
+    // Remember our clock so that the receiver can synchronize with us.
+    let mut clock = VClock::default();
+    ecx.release_clock(|release_clock| clock.join(release_clock));
+    receiver.datagrams.borrow_mut().push_back(Datagram { from, data: data.to_vec(), clock });
+
+    // The receiver became readable.
+    ecx.check_and_update_readiness(&receiver_ref)?;
+    let waiting_threads = std::mem::take(&mut *receiver.blocked_recv_tid.borrow_mut());
+    for thread_id in waiting_threads {
+        ecx.unblock_thread(thread_id, BlockReason::Socket)?;
+    }
+
+    interp_ok(Ok(data.len()))
+}
+
+/// Receive a datagram, blocking if there is none (unless `is_dontwait` is set). If the buffer is
+/// too small, the rest of the datagram is discarded. With `is_peek`, the datagram stays in the
+/// queue. The source address is written to `address` unless that is null. Blocking fails with
+/// EWOULDBLOCK once the `deadline` (computed by `deadline_after`) has passed.
+fn datagram_recv<'tcx>(
+    weak_self_ref: WeakFileDescriptionRef,
+    iov: Vec<(Pointer, u64)>,
+    address: Pointer,
+    address_len: Pointer,
+    is_peek: bool,
+    is_dontwait: bool,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let Some(self_ref) = weak_self_ref.upgrade() else {
+        throw_unsup_format!("socket FD got closed while blocking in `recv`.")
+    };
+    let sock = self_ref.downcast::<Socket>().unwrap();
+
+    let datagram = if is_peek {
+        sock.datagrams.borrow().front().cloned()
+    } else {
+        sock.datagrams.borrow_mut().pop_front()
+    };
+    let Some(datagram) = datagram else {
+        if sock.is_nonblock.get() || is_dontwait || deadline_passed(deadline, ecx) {
+            return ecx.set_last_error_and_return(ErrorKind::WouldBlock, dest);
+        }
+        // Block until a datagram arrives.
+        sock.blocked_recv_tid.borrow_mut().push(ecx.active_thread());
+        let dest = dest.clone();
+        let is_peek = u8::from(is_peek);
+        ecx.block_thread(
+            BlockReason::Socket,
+            deadline_timeout(deadline),
+            callback!(
+                @capture<'tcx> {
+                    weak_self_ref: WeakFileDescriptionRef,
+                    iov: Vec<(Pointer, u64)>,
+                    address: Pointer,
+                    address_len: Pointer,
+                    is_peek: u8,
+                    deadline: Option<u64>,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    // When we get unblocked, try again.
+                    datagram_recv(
+                        weak_self_ref,
+                        iov,
+                        address,
+                        address_len,
+                        is_peek != 0,
+                        false,
+                        deadline,
+                        &dest,
+                        this,
+                    )
+                }
+                @timeout = |this| {
+                    remove_timed_out_thread(&weak_self_ref, this);
+                    this.set_last_error_and_return(ErrorKind::WouldBlock, &dest)
+                }
+            ),
+        );
+        return interp_ok(());
+    };
+
+    datagram_received(&self_ref, &datagram, ecx)?;
+    ecx.write_socket_address(&datagram.from, address, address_len)?;
+    let len: u64 = iov.iter().map(|(_, len)| len).sum();
+    let read_size = usize::try_from(len).unwrap().min(datagram.data.len());
+    ecx.return_readv_success(&iov, &datagram.data[..read_size], dest)
+}
+
+/// Must be called after a datagram got taken out of (or peeked at in) the queue of `self_ref`.
+fn datagram_received<'tcx>(
+    self_ref: &FileDescriptionRef,
+    datagram: &Datagram,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    // Synchronize with the sender.
+    ecx.acquire_clock(&datagram.clock);
+    // We might not be readable any more.
+    ecx.check_and_update_readiness(self_ref)
+}
+
+/// The deadline for a call on `fd` that blocks on receiving (if `is_recv`) or on sending,
+/// according to `SO_RCVTIMEO` or `SO_SNDTIMEO`. `socketpair` sockets never time out.
+fn socket_deadline<'tcx>(
+    fd: &FileDescriptionRef,
+    is_recv: bool,
+    ecx: &MiriInterpCx<'tcx>,
+) -> Option<u64> {
+    let sock = fd.downcast::<Socket>()?;
+    let options = sock.options.borrow();
+    deadline_after(if is_recv { options.rcvtimeo } else { options.sndtimeo }, ecx)
+}
+
+/// Must be called when the active thread timed out while blocking on `weak_fd`, so that it does
+/// not get woken up later.
+fn remove_timed_out_thread<'tcx>(weak_fd: &WeakFileDescriptionRef, ecx: &MiriInterpCx<'tcx>) {
+    let thread_id = ecx.active_thread();
+    let Some(fd) = weak_fd.upgrade() else {
+        return;
+    };
+    if let Some(sock) = fd.downcast::<Socket>() {
+        sock.blocked_accept_tid.borrow_mut().retain(|&id| id != thread_id);
+        sock.blocked_recv_tid.borrow_mut().retain(|&id| id != thread_id);
+    }
+    if let Ok(Endpoint::Stream { stream, .. }) = endpoint(&fd) {
+        stream.remove_blocked_thread(thread_id);
+    }
+}
+
+/// What a socket transfers its data through.
+enum Endpoint<'a> {
+    /// A connected stream. `is_unix` is `true` for `socketpair`, which supports passing file
+    /// descriptors.
+    Stream {
+        stream: &'a AnonSocket,
+        is_unix: bool,
+    },
+    Datagram(&'a Socket),
+}
+
+/// Determine what the socket `fd` transfers its data through.
+fn endpoint(fd: &FileDescriptionRef) -> Result<Endpoint<'_>, IoError> {
+    if let Some(anonsocket) = fd.downcast::<AnonSocket>() {
+        if anonsocket.is_pipe() {
+            return Err(LibcError("ENOTSOCK"));
+        }
+        return Ok(Endpoint::Stream { stream: anonsocket, is_unix: true });
+    }
+    let Some(socket) = fd.downcast::<Socket>() else {
+        return Err(LibcError("ENOTSOCK"));
+    };
+    match socket.type_ {
+        SocketType::Datagram => Ok(Endpoint::Datagram(socket)),
+        SocketType::Stream =>
+            match socket.stream() {
+                Some(stream) =>
+                    Ok(Endpoint::Stream { stream, is_unix: socket.family == SocketFamily::Unix }),
+                None => Err(LibcError("ENOTCONN")),
+            },
+    }
+}
+
+/// The parts of a `msghdr` that we care about.
+struct MsgHdr<'tcx> {
+    place: MPlaceTy<'tcx>,
+    name: Pointer,
+    namelen: u32,
+    /// The buffers described by `msg_iov`.
+    iov: Vec<(Pointer, u64)>,
+    control: Pointer,
+    controllen: u64,
+}
+
+impl<'tcx> MsgHdr<'tcx> {
+    fn read(ecx: &MiriInterpCx<'tcx>, msg: Pointer) -> InterpResult<'tcx, Result<Self, IoError>> {
+        let place = ecx.ptr_to_mplace(msg, ecx.libc_ty_layout("msghdr"));
+        let name = ecx.read_pointer(&ecx.project_field_named(&place, "msg_name")?)?;
+        let namelen = read_uint_field(ecx, &place, "msg_namelen")?;
+        let iov_ptr = ecx.read_pointer(&ecx.project_field_named(&place, "msg_iov")?)?;
+        let iovlen = read_uint_field(ecx, &place, "msg_iovlen")?;
+        let control = ecx.read_pointer(&ecx.project_field_named(&place, "msg_control")?)?;
+        let controllen = read_uint_field(ecx, &place, "msg_controllen")?;
+
+        if iovlen > MAX_IOVECS {
+            return interp_ok(Err(LibcError("EMSGSIZE")));
+        }
+        let iov = match ecx.read_iovecs(iov_ptr, iovlen)? {
+            Ok(iov) => iov,
+            Err(e) => return interp_ok(Err(e)),
+        };
+
+        interp_ok(Ok(MsgHdr {
+            place,
+            name,
+            namelen: u32::try_from(namelen).unwrap_or(u32::MAX),
+            iov,
+            control,
+            controllen,
+        }))
+    }
+
+    fn total_len(&self) -> u64 {
+        self.iov.iter().map(|(_, len)| len).sum()
+    }
+}
+
+/// Read an integer field whose type differs between targets.
+fn read_uint_field<'tcx>(
+    ecx: &MiriInterpCx<'tcx>,
+    place: &MPlaceTy<'tcx>,
+    name: &str,
+) -> InterpResult<'tcx, u64> {
+    let field = ecx.project_field_named(place, name)?;
+    let bits = ecx.read_scalar(&field)?.to_bits(field.layout.size)?;
+    interp_ok(u64::try_from(bits).unwrap())
+}
+
+/// The alignment of control messages, see `CMSG_ALIGN`.
+fn cmsg_align(ecx: &MiriInterpCx<'_>) -> u64 {
+    if ecx.tcx.sess.target.os == "macos" { 4 } else { ecx.tcx.pointer_size().bytes() }
+}
+
+/// The size of a `cmsghdr` including the padding before the data, i.e. `CMSG_LEN(0)`.
+fn cmsg_header_len(ecx: &MiriInterpCx<'_>) -> u64 {
+    ecx.libc_ty_layout("cmsghdr").size.bytes().next_multiple_of(cmsg_align(ecx))
+}
+
+/// Parse the control messages of `sendmsg`, and return the file descriptions passed with
+/// `SCM_RIGHTS`.
+fn read_control_messages<'tcx>(
+    ecx: &MiriInterpCx<'tcx>,
+    msg: &MsgHdr<'tcx>,
+    is_unix: bool,
+) -> InterpResult<'tcx, Result<Vec<FileDescriptionRef>, IoError>> {
+    let cmsghdr_layout = ecx.libc_ty_layout("cmsghdr");
+    let header_len = cmsg_header_len(ecx);
+    let int_size = ecx.machine.layouts.i32.size;
+    let sol_socket = ecx.eval_libc_i32("SOL_SOCKET");
+    let scm_rights = ecx.eval_libc_i32("SCM_RIGHTS");
+
+    let mut fds = Vec::new();
+    let mut pos: u64 = 0;
+    while pos.strict_add(header_len) <= msg.controllen {
+        let cmsg = ecx
+            .ptr_to_mplace(msg.control.wrapping_offset(Size::from_bytes(pos), ecx), cmsghdr_layout);
+        let cmsg_len = read_uint_field(ecx, &cmsg, "cmsg_len")?;
+        let level = ecx.read_scalar(&ecx.project_field_named(&cmsg, "cmsg_level")?)?.to_i32()?;
+        let type_ = ecx.read_scalar(&ecx.project_field_named(&cmsg, "cmsg_type")?)?.to_i32()?;
+        if cmsg_len < header_len || cmsg_len > msg.controllen.strict_sub(pos) {
+            return interp_ok(Err(LibcError("EINVAL")));
+        }
+        if level != sol_socket || type_ != scm_rights {
+            throw_unsup_format!(
+                "sendmsg: control message with level {level:#x} and type {type_:#x} is not supported"
+            );
+        }
+        if !is_unix {
+            // Only unix domain sockets can pass file descriptors.
+            return interp_ok(Err(LibcError("EINVAL")));
+        }
+        let count = (cmsg_len.strict_sub(header_len)) / int_size.bytes();
+        if u64::try_from(fds.len()).unwrap().strict_add(count) > MAX_PASSED_FDS {
+            return interp_ok(Err(LibcError("EINVAL")));
+        }
+        let data = msg.control.wrapping_offset(Size::from_bytes(pos.strict_add(header_len)), ecx);
+        for i in 0..count {
+            let fd_place =
+                ecx.ptr_to_mplace(data.wrapping_offset(int_size * i, ecx), ecx.machine.layouts.i32);
+            let fd_num = ecx.read_scalar(&fd_place)?.to_i32()?;
+            let Some(fd) = ecx.machine.fds.get(fd_num) else {
+                return interp_ok(Err(LibcError("EBADF")));
+            };
+            fds.push(fd);
+        }
+        pos = pos.strict_add(cmsg_len.next_multiple_of(cmsg_align(ecx)));
+    }
+    interp_ok(Ok(fds))
+}
+
+/// Deliver file descriptions received with `SCM_RIGHTS` as a control message in `msg`.
+/// Returns the value of `msg_controllen`, and whether the control data got truncated.
+fn write_control_messages<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
+    msg: &MsgHdr<'tcx>,
+    fds: Vec<FileDescriptionRef>,
+    is_cmsg_cloexec: bool,
+) -> InterpResult<'tcx, (u64, bool)> {
+    if fds.is_empty() {
+        return interp_ok((0, false));
+    }
+    let header_len = cmsg_header_len(ecx);
+    let int_size = ecx.machine.layouts.i32.size;
+
+    // Deliver as many file descriptors as fit, and close the rest.
+    let capacity = msg.controllen.saturating_sub(header_len) / int_size.bytes();
+    let count = usize::try_from(capacity).unwrap_or(usize::MAX).min(fds.len());
+    let truncated = count < fds.len();
+    let mut fds = fds.into_iter();
+    let mut fd_nums = Vec::new();
+    for fd in fds.by_ref().take(count) {
+        let fd_num = ecx.machine.fds.insert(fd);
+        ecx.machine.fds.set_cloexec(fd_num, is_cmsg_cloexec);
+        fd_nums.push(fd_num);
+    }
+    for fd in fds {
+        fd.close(ecx.machine.communicate(), ecx)?.ok();
+    }
+    if count == 0 {
+        return interp_ok((0, truncated));
+    }
+
+    let data_len = int_size.bytes().strict_mul(u64::try_from(count).unwrap());
+    let cmsg = ecx.ptr_to_mplace(msg.control, ecx.libc_ty_layout("cmsghdr"));
+    let sol_socket = ecx.eval_libc_i32("SOL_SOCKET");
+    let scm_rights = ecx.eval_libc_i32("SCM_RIGHTS");
+    ecx.write_int_fields_named(
+        &[
+            ("cmsg_len", header_len.strict_add(data_len).into()),
+            ("cmsg_level", sol_socket.into()),
+            ("cmsg_type", scm_rights.into()),
+        ],
+        &cmsg,
+    )?;
+    let data = msg.control.wrapping_offset(Size::from_bytes(header_len), ecx);
+    for (i, fd_num) in fd_nums.into_iter().enumerate() {
+        let fd_place = ecx.ptr_to_mplace(
+            data.wrapping_offset(int_size * u64::try_from(i).unwrap(), ecx),
+            ecx.machine.layouts.i32,
+        );
+        ecx.write_scalar(Scalar::from_i32(fd_num), &fd_place)?;
+    }
+    // This is `CMSG_SPACE`, but without going past the end of the buffer.
+    let space = header_len.strict_add(data_len.next_multiple_of(cmsg_align(ecx)));
+    interp_ok((space.min(msg.controllen), truncated))
+}
+
+/// Implements `sendmsg`. Blocks if the data cannot be sent right away, until the `deadline`
+/// (computed by `deadline_after`).
+fn socket_sendmsg<'tcx>(
+    weak_fd: WeakFileDescriptionRef,
+    msg_ptr: Pointer,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let Some(fd) = weak_fd.upgrade() else {
+        throw_unsup_format!("socket FD got closed while blocking in `sendmsg`.")
+    };
+    let endpoint = match endpoint(&fd) {
+        Ok(endpoint) => endpoint,
+        Err(e) => return ecx.set_last_error_and_return(e, dest),
+    };
+    let msg = match MsgHdr::read(ecx, msg_ptr)? {
+        Ok(msg) => msg,
+        Err(e) => return ecx.set_last_error_and_return(e, dest),
+    };
+
+    match endpoint {
+        Endpoint::Stream { stream, is_unix } => {
+            if msg.total_len() == 0 && msg.controllen == 0 {
+                return ecx.return_write_success(0, dest);
+            }
+            if stream.would_block_write(usize::try_from(msg.total_len()).unwrap()) {
+                if stream.is_nonblock() || deadline_passed(deadline, ecx) {
+                    return ecx.set_last_error_and_return(ErrorKind::WouldBlock, dest);
+                }
+                if fd.downcast::<AnonSocket>().is_some() {
+                    // FIXME: blocking is currently not supported for socketpairs
+                    throw_unsup_format!("socketpair sendmsg: blocking isn't supported yet");
+                }
+                // Block until the peer reads something, and then try again.
+                stream.add_blocked_writer(ecx.active_thread());
+                let dest = dest.clone();
+                ecx.block_thread(
+                    BlockReason::UnnamedSocket,
+                    deadline_timeout(deadline),
+                    callback!(
+                        @capture<'tcx> {
+                            weak_fd: WeakFileDescriptionRef,
+                            msg_ptr: Pointer,
+                            deadline: Option<u64>,
+                            dest: MPlaceTy<'tcx>,
+                        }
+                        @unblock = |this| {
+                            // When we get unblocked, try again.
+                            socket_sendmsg(weak_fd, msg_ptr, deadline, &dest, this)
+                        }
+                        @timeout = |this| {
+                            remove_timed_out_thread(&weak_fd, this);
+                            this.set_last_error_and_return(ErrorKind::WouldBlock, &dest)
+                        }
+                    ),
+                );
+                return interp_ok(());
+            }
+            let fds = match read_control_messages(ecx, &msg, is_unix)? {
+                Ok(fds) => fds,
+                Err(e) => return ecx.set_last_error_and_return(e, dest),
+            };
+            let bytes = ecx.gather_iov(&msg.iov)?;
+            match stream_write(&fd, &bytes, fds, ecx)? {
+                Ok(actual_write_size) => ecx.return_write_success(actual_write_size, dest),
+                Err(e) => ecx.set_last_error_and_return(e, dest),
+            }
+        }
+        Endpoint::Datagram(sock) => {
+            let to = if ecx.ptr_is_null(msg.name)? {
+                None
+            } else {
+                match ecx.read_socket_address(msg.name, msg.namelen, sock.family)? {
+                    Ok(addr) => Some(addr),
+                    Err(e) => return ecx.set_last_error_and_return(e, dest),
+                }
+            };
+            if let Err(e) = read_control_messages(ecx, &msg, /* is_unix */ false)? {
+                return ecx.set_last_error_and_return(e, dest);
+            }
+            if msg.total_len() > u64::try_from(MAX_DATAGRAM_SIZE).unwrap() {
+                return ecx.set_last_error_and_return(LibcError("EMSGSIZE"), dest);
+            }
+            let bytes = ecx.gather_iov(&msg.iov)?;
+            match datagram_send(&fd, &bytes, to, ecx)? {
+                Ok(actual_write_size) => ecx.return_write_success(actual_write_size, dest),
+                Err(e) => ecx.set_last_error_and_return(e, dest),
+            }
+        }
+    }
+}
+
+/// Implements `recvmsg`. Blocks if there is nothing to receive yet, until the `deadline`
+/// (computed by `deadline_after`).
+fn socket_recvmsg<'tcx>(
+    weak_fd: WeakFileDescriptionRef,
+    msg_ptr: Pointer,
+    is_peek: bool,
+    is_dontwait: bool,
+    is_cmsg_cloexec: bool,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let Some(fd) = weak_fd.upgrade() else {
+        throw_unsup_format!("socket FD got closed while blocking in `recvmsg`.")
+    };
+    let endpoint = match endpoint(&fd) {
+        Ok(endpoint) => endpoint,
+        Err(e) => return ecx.set_last_error_and_return(e, dest),
+    };
+    let msg = match MsgHdr::read(ecx, msg_ptr)? {
+        Ok(msg) => msg,
+        Err(e) => return ecx.set_last_error_and_return(e, dest),
+    };
+
+    let (would_block, is_nonblock, reason) = match endpoint {
+        Endpoint::Stream { stream, .. } =>
+            (stream.would_block_read()?, stream.is_nonblock(), BlockReason::UnnamedSocket),
+        Endpoint::Datagram(sock) =>
+            (sock.datagrams.borrow().is_empty(), sock.is_nonblock.get(), BlockReason::Socket),
+    };
+    if would_block {
+        if is_nonblock || is_dontwait || deadline_passed(deadline, ecx) {
+            return ecx.set_last_error_and_return(ErrorKind::WouldBlock, dest);
+        }
+        if fd.downcast::<AnonSocket>().is_some() {
+            // FIXME: blocking is currently not supported for socketpairs
+            throw_unsup_format!("socketpair recvmsg: blocking isn't supported yet");
+        }
+        // Block until there is something to receive, and then try again.
+        match endpoint {
+            Endpoint::Stream { stream, .. } => stream.add_blocked_reader(ecx.active_thread()),
+            Endpoint::Datagram(sock) =>
+                sock.blocked_recv_tid.borrow_mut().push(ecx.active_thread()),
+        }
+        let dest = dest.clone();
+        // `bool` does not implement `VisitProvenance`, so we pass the flags as integers.
+        let is_peek = u8::from(is_peek);
+        let is_cmsg_cloexec = u8::from(is_cmsg_cloexec);
+        ecx.block_thread(
+            reason,
+            deadline_timeout(deadline),
+            callback!(
+                @capture<'tcx> {
+                    weak_fd: WeakFileDescriptionRef,
+                    msg_ptr: Pointer,
+                    is_peek: u8,
+                    is_cmsg_cloexec: u8,
+                    deadline: Option<u64>,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    // When we get unblocked, try again.
+                    socket_recvmsg(
+                        weak_fd,
+                        msg_ptr,
+                        is_peek != 0,
+                        false,
+                        is_cmsg_cloexec != 0,
+                        deadline,
+                        &dest,
+                        this,
+                    )
+                }
+                @timeout = |this| {
+                    remove_timed_out_thread(&weak_fd, this);
+                    this.set_last_error_and_return(ErrorKind::WouldBlock, &dest)
+                }
+            ),
+        );
+        return interp_ok(());
+    }
+
+    let msg_trunc = ecx.eval_libc_i32("MSG_TRUNC");
+    let msg_ctrunc = ecx.eval_libc_i32("MSG_CTRUNC");
+    let total_len = usize::try_from(msg.total_len()).unwrap();
+    let mut msg_flags = 0;
+    let (bytes, fds) = match endpoint {
+        Endpoint::Stream { .. } => {
+            let (bytes, fds, is_truncated) = stream_read(&fd, total_len, is_peek, ecx)?;
+            // A connected stream does not report the source address.
+            let namelen = ecx.project_field_named(&msg.place, "msg_namelen")?;
+            ecx.write_int(0, &namelen)?;
+            // The part of a `SOCK_SEQPACKET` message that does not fit is discarded.
+            if is_truncated {
+                msg_flags |= msg_trunc;
+            }
+            (bytes, fds)
+        }
+        Endpoint::Datagram(sock) => {
+            let mut datagram = if is_peek {
+                sock.datagrams.borrow().front().cloned().unwrap()
+            } else {
+                sock.datagrams.borrow_mut().pop_front().unwrap()
+            };
+            datagram_received(&fd, &datagram, ecx)?;
+            let namelen = ecx.project_field_named(&msg.place, "msg_namelen")?;
+            ecx.write_socket_address(&datagram.from, msg.name, namelen.ptr())?;
+            // The part of the datagram that does not fit into the buffers is discarded.
+            if datagram.data.len() > total_len {
+                msg_flags |= msg_trunc;
+                datagram.data.truncate(total_len);
+            }
+            (datagram.data, Vec::new())
+        }
+    };
+    ecx.scatter_iov(&msg.iov, &bytes)?;
+
+    let (controllen, control_truncated) = write_control_messages(ecx, &msg, fds, is_cmsg_cloexec)?;
+    if control_truncated {
+        msg_flags |= msg_ctrunc;
+    }
+    let controllen_place = ecx.project_field_named(&msg.place, "msg_controllen")?;
+    ecx.write_int(controllen, &controllen_place)?;
+    let flags_place = ecx.project_field_named(&msg.place, "msg_flags")?;
+    ecx.write_int(msg_flags, &flags_place)?;
+
+    ecx.write_int(u64::try_from(bytes.len()).unwrap(), dest)
+}
+
+/// Interpret the flags of `recv`, `recvfrom` and `recvmsg` that are supported on all targets.
+/// Returns the remaining flags, and whether `MSG_PEEK` and `MSG_DONTWAIT` are set.
+fn read_recv_flags<'tcx>(ecx: &MiriInterpCx<'tcx>, mut flags: i32) -> (i32, bool, bool) {
+    let msg_peek = ecx.eval_libc_i32("MSG_PEEK");
+    let msg_dontwait = ecx.eval_libc_i32("MSG_DONTWAIT");
+    let is_peek = flags & msg_peek == msg_peek;
+    let is_dontwait = flags & msg_dontwait == msg_dontwait;
+    flags &= !(msg_peek | msg_dontwait);
+    (flags, is_peek, is_dontwait)
+}
+
+/// Same as in `read`: check that the entire buffer is valid memory, and cap the length.
+fn cap_recv_len<'tcx>(ecx: &MiriInterpCx<'tcx>, buf: Pointer, len: u64) -> InterpResult<'tcx, u64> {
+    ecx.check_ptr_access(buf, Size::from_bytes(len), CheckInAllocMsg::MemoryAccessTest)?;
+    let len = len
+        .min(u64::try_from(ecx.target_isize_max()).unwrap())
+        .min(u64::try_from(isize::MAX).unwrap());
+    interp_ok(len)
+}
+
+/// Implements `recv`, `recvfrom` and `readv` on sockets, reading into the buffers described by
+/// `iov`. The source address is only reported for datagram sockets.
+fn socket_recv<'tcx>(
+    fd: &FileDescriptionRef,
+    iov: Vec<(Pointer, u64)>,
+    is_peek: bool,
+    is_dontwait: bool,
+    address: Pointer,
+    address_len: Pointer,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let deadline = socket_deadline(fd, /* is_recv */ true, ecx);
+    match endpoint(fd) {
+        Ok(Endpoint::Stream { .. }) =>
+            anonsocket_read(fd, iov, is_peek, is_dontwait, deadline, dest, ecx),
+        Ok(Endpoint::Datagram(_)) =>
+            datagram_recv(
+                fd.downgrade(),
+                iov,
+                address,
+                address_len,
+                is_peek,
+                is_dontwait,
+                deadline,
+                dest,
+                ecx,
+            ),
+        Err(e) => ecx.set_last_error_and_return(e, dest),
+    }
+}
+
+/// Implements `write` and `writev` on sockets, writing the data from the buffers described by
+/// `iov`.
+fn socket_write<'tcx>(
+    fd: &FileDescriptionRef,
+    iov: Vec<(Pointer, u64)>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    match endpoint(fd) {
+        Ok(Endpoint::Stream { .. }) => {
+            let deadline = socket_deadline(fd, /* is_recv */ false, ecx);
+            anonsocket_write(fd, iov, deadline, dest, ecx)
+        }
+        Ok(Endpoint::Datagram(_)) => {
+            let bytes = ecx.gather_iov(&iov)?;
+            match datagram_send(fd, &bytes, /* to */ None, ecx)? {
+                Ok(actual_write_size) => ecx.return_write_success(actual_write_size, dest),
+                Err(e) => ecx.set_last_error_and_return(e, dest),
+            }
+        }
+        Err(e) => ecx.set_last_error_and_return(e, dest),
+    }
+}
@@ -36,9 +36,18 @@ fn bytewise_equal_atomic_relaxed<'tcx>(
     interp_ok(true)
 }
 
+/// Set in the `i32` we store in `pthread_mutexattr_t` and `pthread_condattr_t` if the object is
+/// to be process-shared. This bit is not used by any mutex kind or clock id.
+const PTHREAD_PSHARED_FLAG: i32 = 0x4000_0000;
+
+/// Set in the `i32` we store in `pthread_mutexattr_t` if the mutex is robust. This bit is not used
+/// by any mutex kind.
+const PTHREAD_ROBUST_FLAG: i32 = 0x2000_0000;
+
 // # pthread_mutexattr_t
 // We store some data directly inside the type, ignoring the platform layout:
-// - kind: i32
+// - kind: i32, with `PTHREAD_PSHARED_FLAG` set if the mutex is process-shared, and
+//   `PTHREAD_ROBUST_FLAG` set if it is robust
 
 #[inline]
 fn mutexattr_kind_offset<'tcx>(ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, u64> {
@@ -48,28 +57,44 @@ fn mutexattr_kind_offset<'tcx>(ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, u
     })
 }
 
-fn mutexattr_get_kind<'tcx>(
+/// Returns the kind, whether the mutex is process-shared, and whether it is robust.
+fn mutexattr_get<'tcx>(
     ecx: &MiriInterpCx<'tcx>,
     attr_ptr: &OpTy<'tcx>,
-) -> InterpResult<'tcx, i32> {
-    ecx.deref_pointer_and_read(
-        attr_ptr,
-        mutexattr_kind_offset(ecx)?,
-        ecx.libc_ty_layout("pthread_mutexattr_t"),
-        ecx.machine.layouts.i32,
-    )?
-    .to_i32()
+) -> InterpResult<'tcx, (i32, bool, bool)> {
+    let word = ecx
+        .deref_pointer_and_read(
+            attr_ptr,
+            mutexattr_kind_offset(ecx)?,
+            ecx.libc_ty_layout("pthread_mutexattr_t"),
+            ecx.machine.layouts.i32,
+        )?
+        .to_i32()?;
+    interp_ok((
+        word & !(PTHREAD_PSHARED_FLAG | PTHREAD_ROBUST_FLAG),
+        word & PTHREAD_PSHARED_FLAG != 0,
+        word & PTHREAD_ROBUST_FLAG != 0,
+    ))
 }
 
-fn mutexattr_set_kind<'tcx>(
+fn mutexattr_set<'tcx>(
     ecx: &mut MiriInterpCx<'tcx>,
     attr_ptr: &OpTy<'tcx>,
     kind: i32,
+    pshared: bool,
+    robust: bool,
 ) -> InterpResult<'tcx, ()> {
+    let mut word = kind;
+    if pshared {
+        word |= PTHREAD_PSHARED_FLAG;
+    }
+    if robust {
+        word |= PTHREAD_ROBUST_FLAG;
+    }
     ecx.deref_pointer_and_write(
         attr_ptr,
         mutexattr_kind_offset(ecx)?,
-        Scalar::from_i32(kind),
+        Scalar::from_i32(word),
         ecx.libc_ty_layout("pthread_mutexattr_t"),
         ecx.machine.layouts.i32,
     )
@@ -120,6 +145,8 @@ enum MutexKind {
 struct PthreadMutex {
     mutex_ref: MutexRef,
     kind: MutexKind,
+    /// Whether the mutex is released with `EOWNERDEAD` when its owner terminates.
+    robust: bool,
 }
 
 /// To ensure an initialized mutex that was moved somewhere else can be distinguished from
@@ -172,10 +199,15 @@ fn mutex_create<'tcx>(
     ecx: &mut MiriInterpCx<'tcx>,
     mutex_ptr: &OpTy<'tcx>,
     kind: MutexKind,
+    robust: bool,
 ) -> InterpResult<'tcx, PthreadMutex> {
     let mutex = ecx.deref_pointer(mutex_ptr)?;
-    let id = ecx.machine.sync.mutex_create();
-    let data = PthreadMutex { mutex_ref: id, kind };
+    let id = if robust {
+        ecx.machine.sync.robust_mutex_create()
+    } else {
+        ecx.machine.sync.mutex_create()
+    };
+    let data = PthreadMutex { mutex_ref: id, kind, robust };
     ecx.lazy_sync_init(&mutex, mutex_init_offset(ecx)?, data.clone())?;
     interp_ok(data)
 }
@@ -196,12 +228,23 @@ where
         || throw_ub_format!("`pthread_mutex_t` can't be moved after first use"),
         |ecx| {
             let kind = mutex_kind_from_static_initializer(ecx, &mutex)?;
+            // There are no static initializers for robust mutexes.
             let id = ecx.machine.sync.mutex_create();
-            interp_ok(PthreadMutex { mutex_ref: id, kind })
+            interp_ok(PthreadMutex { mutex_ref: id, kind, robust: false })
         },
     )
 }
 
+/// The return value of a lock operation that just acquired `mutex`: `EOWNERDEAD` if a previous
+/// owner terminated while holding the mutex and nobody marked it as consistent since.
+fn mutex_acquired_retval<'tcx>(ecx: &MiriInterpCx<'tcx>, mutex: &PthreadMutex) -> i32 {
+    if ecx.mutex_consistency(&mutex.mutex_ref) == MutexConsistency::OwnerDied {
+        ecx.eval_libc_i32("EOWNERDEAD")
+    } else {
+        0
+    }
+}
+
 /// Returns the kind of a static initializer.
 fn mutex_kind_from_static_initializer<'tcx>(
     ecx: &MiriInterpCx<'tcx>,
@@ -288,41 +331,204 @@ where
     )
 }
 
-// # pthread_condattr_t
+// # pthread_rwlockattr_t
 // We store some data directly inside the type, ignoring the platform layout:
-// - clock: i32
+// - pshared: i32
 
 #[inline]
-fn condattr_clock_offset<'tcx>(ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, u64> {
+fn rwlockattr_pshared_offset<'tcx>(ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, u64> {
     interp_ok(match &*ecx.tcx.sess.target.os {
         "linux" | "illumos" | "solaris" | "freebsd" | "android" => 0,
-        // macOS does not have a clock attribute.
-        os => throw_unsup_format!("`pthread_condattr` clock field is not supported on {os}"),
+        os => throw_unsup_format!("`pthread_rwlockattr` is not supported on {os}"),
     })
 }
 
-fn condattr_get_clock_id<'tcx>(
+fn rwlockattr_get_pshared<'tcx>(
     ecx: &MiriInterpCx<'tcx>,
     attr_ptr: &OpTy<'tcx>,
 ) -> InterpResult<'tcx, i32> {
     ecx.deref_pointer_and_read(
         attr_ptr,
-        condattr_clock_offset(ecx)?,
-        ecx.libc_ty_layout("pthread_condattr_t"),
+        rwlockattr_pshared_offset(ecx)?,
+        ecx.libc_ty_layout("pthread_rwlockattr_t"),
         ecx.machine.layouts.i32,
     )?
     .to_i32()
 }
 
-fn condattr_set_clock_id<'tcx>(
+fn rwlockattr_set_pshared<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
+    attr_ptr: &OpTy<'tcx>,
+    pshared: i32,
+) -> InterpResult<'tcx, ()> {
+    ecx.deref_pointer_and_write(
+        attr_ptr,
+        rwlockattr_pshared_offset(ecx)?,
+        Scalar::from_i32(pshared),
+        ecx.libc_ty_layout("pthread_rwlockattr_t"),
+        ecx.machine.layouts.i32,
+    )
+}
+
+/// Translates the `pshared` argument of the `*_setpshared` functions and `pthread_spin_init`.
+/// Returns `None` if it is invalid.
+fn translate_pshared<'tcx>(ecx: &MiriInterpCx<'tcx>, pshared: i32) -> Option<bool> {
+    if pshared == ecx.eval_libc_i32("PTHREAD_PROCESS_PRIVATE") {
+        Some(false)
+    } else if pshared == ecx.eval_libc_i32("PTHREAD_PROCESS_SHARED") {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// The `pshared` value that the `*_getpshared` functions report.
+fn pshared_value<'tcx>(ecx: &MiriInterpCx<'tcx>, pshared: bool) -> Scalar {
+    ecx.eval_libc(if pshared { "PTHREAD_PROCESS_SHARED" } else { "PTHREAD_PROCESS_PRIVATE" })
+}
+
+// # pthread_spinlock_t
+// We store some data directly inside the type, ignoring the platform layout:
+// - init: u32
+
+#[derive(Debug, Clone)]
+struct PthreadSpinlock {
+    mutex_ref: MutexRef,
+}
+
+fn spinlock_init_offset<'tcx>(ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, Size> {
+    let offset = match &*ecx.tcx.sess.target.os {
+        "linux" | "freebsd" | "android" => 0,
+        os => throw_unsup_format!("`pthread_spin` is not supported on {os}"),
+    };
+    interp_ok(Size::from_bytes(offset))
+}
+
+fn spinlock_get_data<'tcx, 'a>(
+    ecx: &'a mut MiriInterpCx<'tcx>,
+    lock_ptr: &OpTy<'tcx>,
+) -> InterpResult<'tcx, &'a PthreadSpinlock>
+where
+    'tcx: 'a,
+{
+    let lock = ecx.deref_pointer_as(lock_ptr, ecx.libc_ty_layout("pthread_spinlock_t"))?;
+    ecx.lazy_sync_get_data(
+        &lock,
+        spinlock_init_offset(ecx)?,
+        || throw_ub_format!("`pthread_spinlock_t` can't be moved after first use"),
+        // There is no static initializer for spin locks.
+        |_ecx| throw_ub_format!("`pthread_spinlock_t` was used without `pthread_spin_init`"),
+    )
+}
+
+// # sem_t
+// We store some data directly inside the type, ignoring the platform layout:
+// - init: u32
+
+/// The largest value a semaphore can have; `SEM_VALUE_MAX` on all supported targets.
+const SEM_VALUE_MAX: u32 = 0x7fff_ffff;
+
+#[derive(Debug, Clone)]
+struct PosixSemaphore {
+    sem_ref: SemaphoreRef,
+}
+
+fn sem_init_offset<'tcx>(ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, Size> {
+    let offset = match &*ecx.tcx.sess.target.os {
+        "linux" | "freebsd" | "android" => 0,
+        os => throw_unsup_format!("POSIX semaphores are not supported on {os}"),
+    };
+    interp_ok(Size::from_bytes(offset))
+}
+
+fn sem_get_data<'tcx, 'a>(
+    ecx: &'a mut MiriInterpCx<'tcx>,
+    sem_ptr: &OpTy<'tcx>,
+) -> InterpResult<'tcx, &'a PosixSemaphore>
+where
+    'tcx: 'a,
+{
+    let sem = ecx.deref_pointer_as(sem_ptr, ecx.libc_ty_layout("sem_t"))?;
+    ecx.lazy_sync_get_data(
+        &sem,
+        sem_init_offset(ecx)?,
+        || throw_ub_format!("`sem_t` can't be moved after first use"),
+        // There is no static initializer for semaphores.
+        |_ecx| throw_ub_format!("`sem_t` was used without `sem_init`"),
+    )
+}
+
+/// Take a unit from the semaphore, blocking until `abstime` on the given clock if there is none.
+fn sem_wait_until<'tcx>(
+    ecx: &mut MiriInterpCx<'tcx>,
+    sem_op: &OpTy<'tcx>,
+    clock: TimeoutClock,
+    abstime_op: &OpTy<'tcx>,
+    dest: &MPlaceTy<'tcx>,
+) -> InterpResult<'tcx> {
+    let sem_ref = sem_get_data(ecx, sem_op)?.sem_ref.clone();
+
+    // The timeout is only validated if we have to block.
+    if ecx.semaphore_try_acquire(&sem_ref) {
+        return ecx.write_scalar(Scalar::from_i32(0), dest);
+    }
+    let Some(duration) =
+        ecx.read_timespec(&ecx.deref_pointer_as(abstime_op, ecx.libc_ty_layout("timespec"))?)?
+    else {
+        return ecx.set_last_error_and_return(LibcError("EINVAL"), dest);
+    };
+
+    ecx.semaphore_wait(
+        sem_ref,
+        Some((clock, TimeoutAnchor::Absolute, duration)),
+        Scalar::from_i32(0),  // retval_succ
+        Scalar::from_i32(-1), // retval_timeout
+        dest.clone(),
+        LibcError("ETIMEDOUT"), // errno_timeout
+    );
+    interp_ok(())
+}
+
+// # pthread_condattr_t
+// We store some data directly inside the type, ignoring the platform layout:
+// - clock: i32, with `PTHREAD_PSHARED_FLAG` set if the condvar is process-shared
+
+#[inline]
+fn condattr_clock_offset<'tcx>(ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, u64> {
+    interp_ok(match &*ecx.tcx.sess.target.os {
+        "linux" | "illumos" | "solaris" | "freebsd" | "android" => 0,
+        // macOS does not have a clock attribute.
+        os => throw_unsup_format!("`pthread_condattr` clock field is not supported on {os}"),
+    })
+}
+
+/// Returns the clock id, and whether the condvar is process-shared.
+fn condattr_get<'tcx>(
+    ecx: &MiriInterpCx<'tcx>,
+    attr_ptr: &OpTy<'tcx>,
+) -> InterpResult<'tcx, (i32, bool)> {
+    let word = ecx
+        .deref_pointer_and_read(
+            attr_ptr,
+            condattr_clock_offset(ecx)?,
+            ecx.libc_ty_layout("pthread_condattr_t"),
+            ecx.machine.layouts.i32,
+        )?
+        .to_i32()?;
+    interp_ok((word & !PTHREAD_PSHARED_FLAG, word & PTHREAD_PSHARED_FLAG != 0))
+}
+
+fn condattr_set<'tcx>(
     ecx: &mut MiriInterpCx<'tcx>,
     attr_ptr: &OpTy<'tcx>,
     clock_id: i32,
+    pshared: bool,
 ) -> InterpResult<'tcx, ()> {
+    let word = if pshared { clock_id | PTHREAD_PSHARED_FLAG } else { clock_id };
     ecx.deref_pointer_and_write(
         attr_ptr,
         condattr_clock_offset(ecx)?,
-        Scalar::from_i32(clock_id),
+        Scalar::from_i32(word),
         ecx.libc_ty_layout("pthread_condattr_t"),
         ecx.machine.layouts.i32,
     )
@@ -427,7 +633,13 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     fn pthread_mutexattr_init(&mut self, attr_op: &OpTy<'tcx>) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
 
-        mutexattr_set_kind(this, attr_op, PTHREAD_MUTEX_KIND_UNCHANGED)?;
+        mutexattr_set(
+            this,
+            attr_op,
+            PTHREAD_MUTEX_KIND_UNCHANGED,
+            /* pshared */ false,
+            /* robust */ false,
+        )?;
 
         interp_ok(())
     }
@@ -447,7 +659,8 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         {
             // Make sure we do not mix this up with the "unchanged" kind.
             assert_ne!(kind, PTHREAD_MUTEX_KIND_UNCHANGED);
-            mutexattr_set_kind(this, attr_op, kind)?;
+            let (_old_kind, pshared, robust) = mutexattr_get(this, attr_op)?;
+            mutexattr_set(this, attr_op, kind, pshared, robust)?;
         } else {
             let einval = this.eval_libc_i32("EINVAL");
             return interp_ok(Scalar::from_i32(einval));
@@ -460,7 +673,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let this = self.eval_context_mut();
 
         // Destroying an uninit pthread_mutexattr is UB, so check to make sure it's not uninit.
-        mutexattr_get_kind(this, attr_op)?;
+        mutexattr_get(this, attr_op)?;
 
         // To catch double-destroys, we de-initialize the mutexattr.
         // This is technically not right and might lead to false positives. For example, the below
@@ -481,6 +694,74 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(())
     }
 
+    fn pthread_mutexattr_setpshared(
+        &mut self,
+        attr_op: &OpTy<'tcx>,
+        pshared_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pshared = this.read_scalar(pshared_op)?.to_i32()?;
+        let Some(pshared) = translate_pshared(this, pshared) else {
+            return interp_ok(this.eval_libc("EINVAL"));
+        };
+        let (kind, _old_pshared, robust) = mutexattr_get(this, attr_op)?;
+        mutexattr_set(this, attr_op, kind, pshared, robust)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn pthread_mutexattr_getpshared(
+        &mut self,
+        attr_op: &OpTy<'tcx>,
+        pshared_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let (_kind, pshared, _robust) = mutexattr_get(this, attr_op)?;
+        let pshared_place = this.deref_pointer_as(pshared_op, this.machine.layouts.i32)?;
+        this.write_scalar(pshared_value(this, pshared), &pshared_place)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn pthread_mutexattr_setrobust(
+        &mut self,
+        attr_op: &OpTy<'tcx>,
+        robust_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let robustness = this.read_scalar(robust_op)?.to_i32()?;
+        let robust = if robustness == this.eval_libc_i32("PTHREAD_MUTEX_STALLED") {
+            false
+        } else if robustness == this.eval_libc_i32("PTHREAD_MUTEX_ROBUST") {
+            true
+        } else {
+            return interp_ok(this.eval_libc("EINVAL"));
+        };
+        let (kind, pshared, _old_robust) = mutexattr_get(this, attr_op)?;
+        mutexattr_set(this, attr_op, kind, pshared, robust)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn pthread_mutexattr_getrobust(
+        &mut self,
+        attr_op: &OpTy<'tcx>,
+        robust_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let (_kind, _pshared, robust) = mutexattr_get(this, attr_op)?;
+        let robustness =
+            this.eval_libc(if robust { "PTHREAD_MUTEX_ROBUST" } else { "PTHREAD_MUTEX_STALLED" });
+        let robust_place = this.deref_pointer_as(robust_op, this.machine.layouts.i32)?;
+        this.write_scalar(robustness, &robust_place)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
     fn pthread_mutex_init(
         &mut self,
         mutex_op: &OpTy<'tcx>,
@@ -489,13 +770,16 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let this = self.eval_context_mut();
 
         let attr = this.read_pointer(attr_op)?;
-        let kind = if this.ptr_is_null(attr)? {
-            MutexKind::Default
+        let (kind, pshared, robust) = if this.ptr_is_null(attr)? {
+            (MutexKind::Default, false, false)
         } else {
-            mutexattr_translate_kind(this, mutexattr_get_kind(this, attr_op)?)?
+            let (kind, pshared, robust) = mutexattr_get(this, attr_op)?;
+            (mutexattr_translate_kind(this, kind)?, pshared, robust)
         };
 
-        mutex_create(this, mutex_op, kind)?;
+        mutex_create(this, mutex_op, kind, robust)?;
+        let mutex = this.deref_pointer(mutex_op)?;
+        this.note_process_shared(&mutex, "pthread_mutex_t", pshared)?;
 
         interp_ok(())
     }
@@ -509,13 +793,28 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
         let mutex = mutex_get_data(this, mutex_op)?.clone();
 
+        // Nobody can acquire a mutex anymore that was released while it was inconsistent.
+        if this.mutex_consistency(&mutex.mutex_ref) == MutexConsistency::NotRecoverable {
+            return this.write_scalar(this.eval_libc("ENOTRECOVERABLE"), dest);
+        }
+
         let ret = if this.mutex_is_locked(&mutex.mutex_ref) {
             let owner_thread = this.mutex_get_owner(&mutex.mutex_ref);
             if owner_thread != this.active_thread() {
-                this.mutex_enqueue_and_block(
-                    &mutex.mutex_ref,
-                    Some((Scalar::from_i32(0), dest.clone())),
-                );
+                if mutex.robust {
+                    this.robust_mutex_enqueue_and_block(
+                        &mutex.mutex_ref,
+                        Scalar::from_i32(0),
+                        this.eval_libc("EOWNERDEAD"),
+                        this.eval_libc("ENOTRECOVERABLE"),
+                        dest.clone(),
+                    );
+                } else {
+                    this.mutex_enqueue_and_block(
+                        &mutex.mutex_ref,
+                        Some((Scalar::from_i32(0), dest.clone())),
+                    );
+                }
                 return interp_ok(());
             } else {
                 // Trying to acquire the same mutex again.
@@ -535,7 +834,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         } else {
             // The mutex is unlocked. Let's lock it.
             this.mutex_lock(&mutex.mutex_ref);
-            0
+            mutex_acquired_retval(this, &mutex)
         };
         this.write_scalar(Scalar::from_i32(ret), dest)?;
         interp_ok(())
@@ -546,6 +845,11 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
         let mutex = mutex_get_data(this, mutex_op)?.clone();
 
+        // Nobody can acquire a mutex anymore that was released while it was inconsistent.
+        if this.mutex_consistency(&mutex.mutex_ref) == MutexConsistency::NotRecoverable {
+            return interp_ok(this.eval_libc("ENOTRECOVERABLE"));
+        }
+
         interp_ok(Scalar::from_i32(if this.mutex_is_locked(&mutex.mutex_ref) {
             let owner_thread = this.mutex_get_owner(&mutex.mutex_ref);
             if owner_thread != this.active_thread() {
@@ -563,7 +867,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         } else {
             // The mutex is unlocked. Let's lock it.
             this.mutex_lock(&mutex.mutex_ref);
-            0
+            mutex_acquired_retval(this, &mutex)
         }))
     }
 
@@ -572,6 +876,15 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
         let mutex = mutex_get_data(this, mutex_op)?.clone();
 
+        // Releasing a mutex whose previous owner died without marking it as consistent first
+        // makes it unusable.
+        if this.mutex_consistency(&mutex.mutex_ref) == MutexConsistency::OwnerDied
+            && this.mutex_is_locked(&mutex.mutex_ref)
+            && this.mutex_get_owner(&mutex.mutex_ref) == this.active_thread()
+        {
+            this.mutex_set_consistency(&mutex.mutex_ref, MutexConsistency::NotRecoverable);
+        }
+
         if let Some(_old_locked_count) = this.mutex_unlock(&mutex.mutex_ref)? {
             // The mutex was locked by the current thread.
             interp_ok(Scalar::from_i32(0))
@@ -594,6 +907,24 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
     }
 
+    fn pthread_mutex_consistent(&mut self, mutex_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let mutex = mutex_get_data(this, mutex_op)?.clone();
+
+        // Only the owner of a robust mutex whose previous owner died can mark it as consistent.
+        if !mutex.robust
+            || this.mutex_consistency(&mutex.mutex_ref) != MutexConsistency::OwnerDied
+            || !this.mutex_is_locked(&mutex.mutex_ref)
+            || this.mutex_get_owner(&mutex.mutex_ref) != this.active_thread()
+        {
+            return interp_ok(this.eval_libc("EINVAL"));
+        }
+        this.mutex_set_consistency(&mutex.mutex_ref, MutexConsistency::Consistent);
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
     fn pthread_mutex_destroy(&mut self, mutex_op: &OpTy<'tcx>) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
 
@@ -605,11 +936,88 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             throw_ub_format!("destroyed a locked mutex");
         }
 
+        let mutex = this.deref_pointer_as(mutex_op, this.libc_ty_layout("pthread_mutex_t"))?;
+        this.note_sync_object_destroyed(&mutex)?;
+        // This might lead to false positives, see comment in pthread_mutexattr_destroy
+        this.write_uninit(&mutex)?;
+        // FIXME: delete interpreter state associated with this mutex.
+
+        interp_ok(())
+    }
+
+    fn pthread_rwlockattr_init(&mut self, attr_op: &OpTy<'tcx>) -> InterpResult<'tcx, ()> {
+        let this = self.eval_context_mut();
+
+        let private = this.eval_libc_i32("PTHREAD_PROCESS_PRIVATE");
+        rwlockattr_set_pshared(this, attr_op, private)?;
+
+        interp_ok(())
+    }
+
+    fn pthread_rwlockattr_setpshared(
+        &mut self,
+        attr_op: &OpTy<'tcx>,
+        pshared_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pshared = this.read_scalar(pshared_op)?.to_i32()?;
+        if translate_pshared(this, pshared).is_none() {
+            return interp_ok(this.eval_libc("EINVAL"));
+        }
+        rwlockattr_set_pshared(this, attr_op, pshared)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn pthread_rwlockattr_getpshared(
+        &mut self,
+        attr_op: &OpTy<'tcx>,
+        pshared_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pshared = rwlockattr_get_pshared(this, attr_op)?;
+        let pshared_place = this.deref_pointer_as(pshared_op, this.machine.layouts.i32)?;
+        this.write_scalar(Scalar::from_i32(pshared), &pshared_place)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn pthread_rwlockattr_destroy(&mut self, attr_op: &OpTy<'tcx>) -> InterpResult<'tcx, ()> {
+        let this = self.eval_context_mut();
+
+        // Destroying an uninit pthread_rwlockattr is UB, so check to make sure it's not uninit.
+        rwlockattr_get_pshared(this, attr_op)?;
+
         // This might lead to false positives, see comment in pthread_mutexattr_destroy
         this.write_uninit(
-            &this.deref_pointer_as(mutex_op, this.libc_ty_layout("pthread_mutex_t"))?,
+            &this.deref_pointer_as(attr_op, this.libc_ty_layout("pthread_rwlockattr_t"))?,
         )?;
-        // FIXME: delete interpreter state associated with this mutex.
+
+        interp_ok(())
+    }
+
+    fn pthread_rwlock_init(
+        &mut self,
+        rwlock_op: &OpTy<'tcx>,
+        attr_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, ()> {
+        let this = self.eval_context_mut();
+
+        let attr = this.read_pointer(attr_op)?;
+        let pshared = if this.ptr_is_null(attr)? {
+            false
+        } else {
+            let pshared = rwlockattr_get_pshared(this, attr_op)?;
+            // We only ever store valid values.
+            translate_pshared(this, pshared).unwrap()
+        };
+
+        let rwlock = this.deref_pointer(rwlock_op)?;
+        let id = this.machine.sync.rwlock_create();
+        this.lazy_sync_init(&rwlock, rwlock_init_offset(this)?, PthreadRwLock { id })?;
+        this.note_process_shared(&rwlock, "pthread_rwlock_t", pshared)?;
 
         interp_ok(())
     }
@@ -713,15 +1121,104 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             throw_ub_format!("destroyed a locked rwlock");
         }
 
+        let rwlock = this.deref_pointer_as(rwlock_op, this.libc_ty_layout("pthread_rwlock_t"))?;
+        this.note_sync_object_destroyed(&rwlock)?;
         // This might lead to false positives, see comment in pthread_mutexattr_destroy
-        this.write_uninit(
-            &this.deref_pointer_as(rwlock_op, this.libc_ty_layout("pthread_rwlock_t"))?,
-        )?;
+        this.write_uninit(&rwlock)?;
         // FIXME: delete interpreter state associated with this rwlock.
 
         interp_ok(())
     }
 
+    fn pthread_spin_init(
+        &mut self,
+        lock_op: &OpTy<'tcx>,
+        pshared_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pshared = this.read_scalar(pshared_op)?.to_i32()?;
+        let Some(pshared) = translate_pshared(this, pshared) else {
+            return interp_ok(this.eval_libc("EINVAL"));
+        };
+
+        let lock = this.deref_pointer_as(lock_op, this.libc_ty_layout("pthread_spinlock_t"))?;
+        let mutex_ref = this.machine.sync.mutex_create();
+        this.lazy_sync_init(&lock, spinlock_init_offset(this)?, PthreadSpinlock { mutex_ref })?;
+        this.note_process_shared(&lock, "pthread_spinlock_t", pshared)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn pthread_spin_lock(
+        &mut self,
+        lock_op: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let lock = spinlock_get_data(this, lock_op)?.clone();
+
+        if this.mutex_is_locked(&lock.mutex_ref) {
+            if this.mutex_get_owner(&lock.mutex_ref) == this.active_thread() {
+                // The thread would spin forever.
+                throw_machine_stop!(TerminationInfo::Deadlock);
+            }
+            // Instead of spinning, we let other threads run until the lock is released.
+            this.mutex_enqueue_and_block(
+                &lock.mutex_ref,
+                Some((Scalar::from_i32(0), dest.clone())),
+            );
+        } else {
+            this.mutex_lock(&lock.mutex_ref);
+            this.write_scalar(Scalar::from_i32(0), dest)?;
+        }
+        interp_ok(())
+    }
+
+    fn pthread_spin_trylock(&mut self, lock_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let lock = spinlock_get_data(this, lock_op)?.clone();
+
+        interp_ok(if this.mutex_is_locked(&lock.mutex_ref) {
+            this.eval_libc("EBUSY")
+        } else {
+            this.mutex_lock(&lock.mutex_ref);
+            Scalar::from_i32(0)
+        })
+    }
+
+    fn pthread_spin_unlock(&mut self, lock_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let lock = spinlock_get_data(this, lock_op)?.clone();
+
+        if this.mutex_unlock(&lock.mutex_ref)?.is_none() {
+            throw_ub_format!("unlocked a spin lock that was not locked by the current thread");
+        }
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn pthread_spin_destroy(&mut self, lock_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        // Reading the field also has the side-effect that we detect double-`destroy`
+        // since we make the field uninit below.
+        let lock = spinlock_get_data(this, lock_op)?.clone();
+
+        if this.mutex_is_locked(&lock.mutex_ref) {
+            throw_ub_format!("destroyed a locked spin lock");
+        }
+
+        let lock = this.deref_pointer_as(lock_op, this.libc_ty_layout("pthread_spinlock_t"))?;
+        this.note_sync_object_destroyed(&lock)?;
+        // This might lead to false positives, see comment in pthread_mutexattr_destroy
+        this.write_uninit(&lock)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
     fn pthread_condattr_init(&mut self, attr_op: &OpTy<'tcx>) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
 
@@ -731,7 +1228,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             // clock.
             // https://pubs.opengroup.org/onlinepubs/9699919799/functions/pthread_condattr_setclock.html
             let default_clock_id = this.eval_libc_i32("CLOCK_REALTIME");
-            condattr_set_clock_id(this, attr_op, default_clock_id)?;
+            condattr_set(this, attr_op, default_clock_id, /* pshared */ false)?;
         }
 
         interp_ok(())
@@ -748,7 +1245,8 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         if clock_id == this.eval_libc_i32("CLOCK_REALTIME")
             || clock_id == this.eval_libc_i32("CLOCK_MONOTONIC")
         {
-            condattr_set_clock_id(this, attr_op, clock_id)?;
+            let (_old_clock_id, pshared) = condattr_get(this, attr_op)?;
+            condattr_set(this, attr_op, clock_id, pshared)?;
         } else {
             let einval = this.eval_libc_i32("EINVAL");
             return interp_ok(Scalar::from_i32(einval));
@@ -764,19 +1262,50 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     ) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
 
-        let clock_id = condattr_get_clock_id(this, attr_op)?;
+        let (clock_id, _pshared) = condattr_get(this, attr_op)?;
         this.write_scalar(Scalar::from_i32(clock_id), &this.deref_pointer(clk_id_op)?)?;
 
         interp_ok(())
     }
 
+    fn pthread_condattr_setpshared(
+        &mut self,
+        attr_op: &OpTy<'tcx>,
+        pshared_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pshared = this.read_scalar(pshared_op)?.to_i32()?;
+        let Some(pshared) = translate_pshared(this, pshared) else {
+            return interp_ok(this.eval_libc("EINVAL"));
+        };
+        let (clock_id, _old_pshared) = condattr_get(this, attr_op)?;
+        condattr_set(this, attr_op, clock_id, pshared)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn pthread_condattr_getpshared(
+        &mut self,
+        attr_op: &OpTy<'tcx>,
+        pshared_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let (_clock_id, pshared) = condattr_get(this, attr_op)?;
+        let pshared_place = this.deref_pointer_as(pshared_op, this.machine.layouts.i32)?;
+        this.write_scalar(pshared_value(this, pshared), &pshared_place)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
     fn pthread_condattr_destroy(&mut self, attr_op: &OpTy<'tcx>) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
 
         // Destroying an uninit pthread_condattr is UB, so check to make sure it's not uninit.
         // There's no clock attribute on macOS.
         if this.tcx.sess.target.os != "macos" {
-            condattr_get_clock_id(this, attr_op)?;
+            condattr_get(this, attr_op)?;
         }
 
         // De-init the entire thing.
@@ -796,15 +1325,17 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let this = self.eval_context_mut();
 
         let attr = this.read_pointer(attr_op)?;
-        // Default clock if `attr` is null, and on macOS where there is no clock attribute.
-        let clock_id = if this.ptr_is_null(attr)? || this.tcx.sess.target.os == "macos" {
-            this.eval_libc_i32("CLOCK_REALTIME")
+        // Default attributes if `attr` is null, and on macOS where we do not store any.
+        let (clock_id, pshared) = if this.ptr_is_null(attr)? || this.tcx.sess.target.os == "macos" {
+            (this.eval_libc_i32("CLOCK_REALTIME"), false)
         } else {
-            condattr_get_clock_id(this, attr_op)?
+            condattr_get(this, attr_op)?
         };
         let clock_id = condattr_translate_clock_id(this, clock_id)?;
 
         cond_create(this, cond_op, clock_id)?;
+        let cond = this.deref_pointer(cond_op)?;
+        this.note_process_shared(&cond, "pthread_cond_t", pshared)?;
 
         interp_ok(())
     }
@@ -812,13 +1343,18 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     fn pthread_cond_signal(&mut self, cond_op: &OpTy<'tcx>) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
         let id = cond_get_data(this, cond_op)?.id;
-        this.condvar_signal(id)?;
+        if !this.condvar_signal(id)? {
+            this.condvar_signal_lost(id);
+        }
         interp_ok(())
     }
 
     fn pthread_cond_broadcast(&mut self, cond_op: &OpTy<'tcx>) -> InterpResult<'tcx, ()> {
         let this = self.eval_context_mut();
         let id = cond_get_data(this, cond_op)?.id;
+        if !this.condvar_is_awaited(id) {
+            this.condvar_signal_lost(id);
+        }
         while this.condvar_signal(id)? {}
         interp_ok(())
     }
@@ -899,10 +1435,146 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             throw_ub_format!("destroying an awaited conditional variable");
         }
 
+        let cond = this.deref_pointer_as(cond_op, this.libc_ty_layout("pthread_cond_t"))?;
+        this.note_sync_object_destroyed(&cond)?;
         // This might lead to false positives, see comment in pthread_mutexattr_destroy
-        this.write_uninit(&this.deref_pointer_as(cond_op, this.libc_ty_layout("pthread_cond_t"))?)?;
+        this.write_uninit(&cond)?;
         // FIXME: delete interpreter state associated with this condvar.
 
         interp_ok(())
     }
+
+    fn sem_init(
+        &mut self,
+        sem_op: &OpTy<'tcx>,
+        pshared_op: &OpTy<'tcx>,
+        value_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pshared = this.read_scalar(pshared_op)?.to_i32()?;
+        let value = this.read_scalar(value_op)?.to_u32()?;
+        if value > SEM_VALUE_MAX {
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        }
+
+        let sem = this.deref_pointer_as(sem_op, this.libc_ty_layout("sem_t"))?;
+        let sem_ref = this.machine.sync.semaphore_create(value);
+        this.lazy_sync_init(&sem, sem_init_offset(this)?, PosixSemaphore { sem_ref })?;
+        this.note_process_shared(&sem, "sem_t", pshared != 0)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn sem_destroy(&mut self, sem_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        // Reading the field also has the side-effect that we detect double-`destroy`
+        // since we make the field uninit below.
+        let sem = sem_get_data(this, sem_op)?.clone();
+
+        if this.semaphore_is_awaited(&sem.sem_ref) {
+            throw_ub_format!("destroyed a semaphore that threads are waiting on");
+        }
+
+        let sem = this.deref_pointer_as(sem_op, this.libc_ty_layout("sem_t"))?;
+        this.note_sync_object_destroyed(&sem)?;
+        // This might lead to false positives, see comment in pthread_mutexattr_destroy
+        this.write_uninit(&sem)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn sem_post(&mut self, sem_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let sem = sem_get_data(this, sem_op)?.clone();
+
+        if this.semaphore_value(&sem.sem_ref) == SEM_VALUE_MAX {
+            return this.set_last_error_and_return_i32(LibcError("EOVERFLOW"));
+        }
+        this.semaphore_release(&sem.sem_ref)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn sem_wait(&mut self, sem_op: &OpTy<'tcx>, dest: &MPlaceTy<'tcx>) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let sem = sem_get_data(this, sem_op)?.clone();
+
+        if this.semaphore_try_acquire(&sem.sem_ref) {
+            this.write_scalar(Scalar::from_i32(0), dest)?;
+        } else {
+            this.semaphore_wait(
+                sem.sem_ref,
+                None,
+                Scalar::from_i32(0),  // retval_succ
+                Scalar::from_i32(-1), // retval_timeout
+                dest.clone(),
+                LibcError("ETIMEDOUT"), // errno_timeout
+            );
+        }
+        interp_ok(())
+    }
+
+    fn sem_trywait(&mut self, sem_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let sem = sem_get_data(this, sem_op)?.clone();
+
+        if this.semaphore_try_acquire(&sem.sem_ref) {
+            interp_ok(Scalar::from_i32(0))
+        } else {
+            this.set_last_error_and_return_i32(LibcError("EAGAIN"))
+        }
+    }
+
+    fn sem_timedwait(
+        &mut self,
+        sem_op: &OpTy<'tcx>,
+        abstime_op: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        this.check_no_isolation("`sem_timedwait`")?;
+        sem_wait_until(this, sem_op, TimeoutClock::RealTime, abstime_op, dest)
+    }
+
+    fn sem_clockwait(
+        &mut self,
+        sem_op: &OpTy<'tcx>,
+        clock_id_op: &OpTy<'tcx>,
+        abstime_op: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let clock_id = this.read_scalar(clock_id_op)?.to_i32()?;
+        let clock = if clock_id == this.eval_libc_i32("CLOCK_MONOTONIC") {
+            TimeoutClock::Monotonic
+        } else if clock_id == this.eval_libc_i32("CLOCK_REALTIME") {
+            this.check_no_isolation("`sem_clockwait` with `CLOCK_REALTIME`")?;
+            TimeoutClock::RealTime
+        } else {
+            return this.set_last_error_and_return(LibcError("EINVAL"), dest);
+        };
+        sem_wait_until(this, sem_op, clock, abstime_op, dest)
+    }
+
+    fn sem_getvalue(
+        &mut self,
+        sem_op: &OpTy<'tcx>,
+        sval_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let sem = sem_get_data(this, sem_op)?.clone();
+        let value = this.semaphore_value(&sem.sem_ref);
+        let sval = this.deref_pointer_as(sval_op, this.machine.layouts.i32)?;
+        this.write_scalar(Scalar::from_u32(value), &sval)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
 }
@@ -1,5 +1,9 @@
-use rustc_abi::ExternAbi;
+use std::mem;
+use std::time::Duration;
 
+use rustc_abi::{ExternAbi, Size};
+
+use crate::concurrency::cpu_affinity::CpuAffinityMask;
 use crate::*;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -9,6 +13,171 @@ pub enum ThreadNameResult {
     ThreadNotFound,
 }
 
+/// The lowest nice value, i.e. the highest priority, a Linux thread can have.
+const NICE_MIN: i32 = -20;
+/// The highest nice value, i.e. the lowest priority, a Linux thread can have.
+const NICE_MAX: i32 = 19;
+
+impl<'tcx> EvalContextPrivExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// The range of static priorities of the given scheduling policy, or `None` if there is no
+    /// such policy.
+    fn sched_priority_range(&self, policy: i32) -> Option<(i32, i32)> {
+        let this = self.eval_context_ref();
+        let is_policy =
+            |names: &[&str]| names.iter().any(|name| this.eval_libc_i32(name) == policy);
+        if is_policy(&["SCHED_FIFO", "SCHED_RR"]) {
+            Some((1, 99))
+        } else if is_policy(&["SCHED_OTHER", "SCHED_BATCH", "SCHED_IDLE"]) {
+            Some((0, 0))
+        } else {
+            None
+        }
+    }
+
+    /// The thread addressed by the `which` and `who` arguments of `setpriority` and `getpriority`,
+    /// or `None` if there is no such thread. Like on Linux, these act on single threads, which are
+    /// identified by their TID.
+    fn priority_target(
+        &self,
+        which: &OpTy<'tcx>,
+        who: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Option<ThreadId>> {
+        let this = self.eval_context_ref();
+
+        let which = this.read_scalar(which)?.to_i32()?;
+        let who = this.read_scalar(who)?.to_u32()?;
+
+        if which != this.eval_libc_i32("PRIO_PROCESS") {
+            throw_unsup_format!("thread priorities can only be accessed with `PRIO_PROCESS`");
+        }
+        if who == 0 {
+            return interp_ok(Some(this.active_thread()));
+        }
+        // The TID of a thread is the PID plus the thread index, see `linux_gettid`.
+        interp_ok(
+            who.checked_sub(this.get_pid()).and_then(|index| this.thread_id_try_from(index).ok()),
+        )
+    }
+
+    /// The thread addressed by the `pid` argument of `sched_getaffinity` and `sched_setaffinity`,
+    /// or `None` if there is no such thread. Like on Linux, these act on single threads, which are
+    /// identified by their TID; 0 means the calling thread.
+    fn affinity_target(&self, pid: u32) -> Option<ThreadId> {
+        let this = self.eval_context_ref();
+        if pid == 0 {
+            return Some(this.active_thread());
+        }
+        // The TID of a thread is the PID plus the thread index, see `linux_gettid`.
+        pid.checked_sub(this.get_pid())
+            .and_then(|index| this.thread_id_try_from(index).ok())
+            .filter(|&thread| !this.machine.threads.has_terminated(thread))
+    }
+
+    /// The thread addressed by the `which` and `id` arguments of `cpuset_getaffinity` and
+    /// `cpuset_setaffinity`, or `None` if there is no such thread. An `id` of -1 means the calling
+    /// thread or process, and the process is represented by the calling thread.
+    fn cpuset_target(&self, which: i32, id: i64) -> InterpResult<'tcx, Option<ThreadId>> {
+        let this = self.eval_context_ref();
+        if which == this.eval_libc_i32("CPU_WHICH_TID") {
+            interp_ok((id == -1).then(|| this.active_thread()))
+        } else if which == this.eval_libc_i32("CPU_WHICH_PID") {
+            interp_ok((id == -1 || id == i64::from(this.get_pid())).then(|| this.active_thread()))
+        } else {
+            throw_unsup_format!(
+                "CPU affinity can only be accessed with `CPU_WHICH_TID` or `CPU_WHICH_PID`"
+            )
+        }
+    }
+
+    /// Copy the first `cpusetsize` bytes of `cpuset` to `mask`. If `cpusetsize` is larger than
+    /// the mask, only the bytes of the mask are written.
+    fn write_cpu_affinity(
+        &mut self,
+        cpuset: &CpuAffinityMask,
+        cpusetsize: u64,
+        mask: Pointer,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let byte_count = Ord::min(cpuset.as_slice().len(), cpusetsize.try_into().unwrap());
+        this.write_bytes_ptr(mask, cpuset.as_slice()[..byte_count].iter().copied())
+    }
+
+    /// Read a mask of `cpusetsize` bytes from `mask`, restricted to the available CPUs. Returns
+    /// `None` if none of the available CPUs is in the mask.
+    fn read_cpu_affinity(
+        &mut self,
+        cpusetsize: u64,
+        mask: Pointer,
+    ) -> InterpResult<'tcx, Option<CpuAffinityMask>> {
+        let this = self.eval_context_mut();
+        // NOTE: cpusetsize might be smaller than `CpuAffinityMask::CPU_MASK_BYTES`.
+        // Any unspecified bytes are treated as zero here (none of the CPUs are configured).
+        // This is not exactly documented, so we assume that this is the behavior in practice.
+        let bits_slice =
+            this.read_bytes_ptr_strip_provenance(mask, Size::from_bytes(cpusetsize))?;
+        // This ignores the bytes beyond `CpuAffinityMask::CPU_MASK_BYTES`
+        let bits_array: [u8; CpuAffinityMask::CPU_MASK_BYTES] =
+            std::array::from_fn(|i| bits_slice.get(i).copied().unwrap_or(0));
+        interp_ok(CpuAffinityMask::from_array(this, this.machine.num_cpus, bits_array))
+    }
+
+    /// The values of `PTHREAD_CANCEL_ENABLE`, `PTHREAD_CANCEL_DISABLE`, `PTHREAD_CANCEL_DEFERRED`
+    /// and `PTHREAD_CANCEL_ASYNCHRONOUS`, in that order. The `libc` crate does not define them for
+    /// most targets.
+    fn cancel_constants(&self, name: &str) -> InterpResult<'tcx, (i32, i32, i32, i32)> {
+        let this = self.eval_context_ref();
+        interp_ok(match this.tcx.sess.target.os.as_ref() {
+            "linux" => (0, 1, 0, 1),
+            "freebsd" | "solaris" | "illumos" => (0, 1, 0, 2),
+            "macos" => (1, 0, 2, 0),
+            os => throw_unsup_format!("`{name}` is not supported on {os}"),
+        })
+    }
+
+    /// Act upon the cancellation request of the given thread, which must not be in the middle of
+    /// a shim: it runs its cleanup handlers and TLS destructors, and then terminates.
+    fn cancel_thread(&mut self, thread: ThreadId) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        if thread == ThreadId::MAIN_THREAD {
+            throw_unsup_format!("cancelling the main thread is not supported");
+        }
+        let cancel = this.machine.threads.cancel_state_mut(thread);
+        cancel.pending = false;
+        cancel.cancelled = true;
+        // The cleanup handlers and TLS destructors cannot be cancelled again.
+        cancel.enabled = false;
+        // The cleanup handlers are run by `TlsDtorsState`.
+        this.exit_thread_without_unwinding(thread)
+    }
+
+    /// Act upon the cancellation request of the active thread once the current shim returned.
+    fn cancel_active_thread_after_shim(&mut self) {
+        let this = self.eval_context_mut();
+        // Blocking with a timeout that has already expired is the easiest way to get back control
+        // right after the shim.
+        this.block_thread(
+            BlockReason::Cancelled,
+            Some((TimeoutClock::Monotonic, TimeoutAnchor::Relative, Duration::ZERO)),
+            callback!(
+                @capture<'tcx> {}
+                @unblock = |_this| { panic!("cancelled thread unblocked before it exited") }
+                @timeout = |this| { this.cancel_thread(this.active_thread()) }
+            ),
+        );
+    }
+
+    /// Act upon a pending cancellation request of the active thread right away if it is enabled
+    /// and asynchronous. This is called whenever one of these conditions might have become true.
+    fn cancel_if_asynchronous(&mut self) {
+        let this = self.eval_context_mut();
+        let cancel = &this.active_thread_ref().cancel;
+        if cancel.enabled && cancel.asynchronous && cancel.pending {
+            this.cancel_active_thread_after_shim();
+        }
+    }
+}
+
 impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
 pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
     fn pthread_create(
@@ -71,6 +240,142 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         interp_ok(Scalar::from_u32(0))
     }
 
+    fn pthread_cancel(&mut self, thread: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        this.cancel_constants("pthread_cancel")?;
+
+        let thread = this.read_scalar(thread)?.to_int(this.libc_ty_layout("pthread_t").size)?;
+        let Ok(thread) = this.thread_id_try_from(thread) else {
+            return interp_ok(this.eval_libc("ESRCH"));
+        };
+        if this.machine.threads.has_terminated(thread) {
+            // Like glibc, we accept cancelling a thread that exited but was not joined yet.
+            return interp_ok(Scalar::from_u32(0));
+        }
+
+        let cancel = this.machine.threads.cancel_state_mut(thread);
+        cancel.pending = true;
+        if !cancel.enabled {
+            // The request stays pending until the thread enables cancellation again.
+            return interp_ok(Scalar::from_u32(0));
+        }
+        let asynchronous = cancel.asynchronous;
+        if this.interrupt_cancellation_point(thread)? {
+            // The thread was waiting at a cancellation point, so it acts upon the request now.
+            this.cancel_thread(thread)?;
+        } else if asynchronous {
+            if thread == this.active_thread() {
+                this.cancel_active_thread_after_shim();
+            } else if this.machine.threads.is_enabled(thread) {
+                this.cancel_thread(thread)?;
+            }
+            // A thread that is blocked somewhere else acts upon the request when it reaches its
+            // next cancellation point.
+        }
+
+        interp_ok(Scalar::from_u32(0))
+    }
+
+    fn pthread_setcancelstate(
+        &mut self,
+        state: &OpTy<'tcx>,
+        oldstate: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let (enable, disable, _, _) = this.cancel_constants("pthread_setcancelstate")?;
+        let state = this.read_scalar(state)?.to_i32()?;
+
+        let enabled = if state == enable {
+            true
+        } else if state == disable {
+            false
+        } else {
+            return interp_ok(this.eval_libc("EINVAL"));
+        };
+        let cancel = &mut this.active_thread_mut().cancel;
+        let old_enabled = mem::replace(&mut cancel.enabled, enabled);
+        // Like on Linux, `oldstate` may be null.
+        if !this.ptr_is_null(this.read_pointer(oldstate)?)? {
+            let old = if old_enabled { enable } else { disable };
+            let oldstate = this.deref_pointer_as(oldstate, this.machine.layouts.i32)?;
+            this.write_scalar(Scalar::from_i32(old), &oldstate)?;
+        }
+        this.cancel_if_asynchronous();
+
+        interp_ok(Scalar::from_u32(0))
+    }
+
+    fn pthread_setcanceltype(
+        &mut self,
+        ty: &OpTy<'tcx>,
+        oldtype: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let (_, _, deferred, asynchronous) = this.cancel_constants("pthread_setcanceltype")?;
+        let ty = this.read_scalar(ty)?.to_i32()?;
+
+        let new_asynchronous = if ty == asynchronous {
+            true
+        } else if ty == deferred {
+            false
+        } else {
+            return interp_ok(this.eval_libc("EINVAL"));
+        };
+        let cancel = &mut this.active_thread_mut().cancel;
+        let old_asynchronous = mem::replace(&mut cancel.asynchronous, new_asynchronous);
+        // Like on Linux, `oldtype` may be null.
+        if !this.ptr_is_null(this.read_pointer(oldtype)?)? {
+            let old = if old_asynchronous { asynchronous } else { deferred };
+            let oldtype = this.deref_pointer_as(oldtype, this.machine.layouts.i32)?;
+            this.write_scalar(Scalar::from_i32(old), &oldtype)?;
+        }
+        this.cancel_if_asynchronous();
+
+        interp_ok(Scalar::from_u32(0))
+    }
+
+    fn pthread_testcancel(&mut self) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        this.cancel_constants("pthread_testcancel")?;
+        this.cancellation_point();
+
+        interp_ok(())
+    }
+
+    /// Called by shims that are cancellation points before they do anything else. If there is a
+    /// cancellation request to act upon, this returns `true`, and the shim should return right
+    /// away; the thread then exits instead of returning to its caller.
+    fn cancellation_point(&mut self) -> bool {
+        let this = self.eval_context_mut();
+        let cancel = &this.active_thread_ref().cancel;
+        if !cancel.enabled || !cancel.pending {
+            return false;
+        }
+        this.cancel_active_thread_after_shim();
+        true
+    }
+
+    /// Register a cleanup handler that the active thread runs if it gets cancelled.
+    fn pthread_cleanup_push(
+        &mut self,
+        routine: &OpTy<'tcx>,
+        arg: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let routine = this.read_pointer(routine)?;
+        let arg = this.read_scalar(arg)?;
+        // Make sure this is a function pointer now rather than when the thread gets cancelled.
+        this.get_ptr_fn(routine)?;
+        this.active_thread_mut().cancel.cleanup_handlers.push((routine, arg));
+
+        interp_ok(())
+    }
+
     fn pthread_self(&mut self) -> InterpResult<'tcx, Scalar> {
         let this = self.eval_context_mut();
 
@@ -152,4 +457,280 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
         interp_ok(())
     }
+
+    fn pthread_setschedparam(
+        &mut self,
+        thread: &OpTy<'tcx>,
+        policy: &OpTy<'tcx>,
+        param: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let thread = this.read_scalar(thread)?.to_int(this.libc_ty_layout("pthread_t").size)?;
+        let policy = this.read_scalar(policy)?.to_i32()?;
+        let param = this.deref_pointer_as(param, this.libc_ty_layout("sched_param"))?;
+        let sched_priority =
+            this.read_scalar(&this.project_field_named(&param, "sched_priority")?)?.to_i32()?;
+
+        let Ok(thread) = this.thread_id_try_from(thread) else {
+            return interp_ok(this.eval_libc("ESRCH"));
+        };
+        let Some((min, max)) = this.sched_priority_range(policy) else {
+            return interp_ok(this.eval_libc("EINVAL"));
+        };
+        if !(min..=max).contains(&sched_priority) {
+            return interp_ok(this.eval_libc("EINVAL"));
+        }
+
+        // We do not model the privileges that are needed for real-time policies.
+        let priority = this.get_thread_priority(thread);
+        this.set_thread_priority(thread, ThreadPriority {
+            policy: Some(policy),
+            sched_priority,
+            ..priority
+        });
+
+        interp_ok(Scalar::from_u32(0))
+    }
+
+    fn pthread_getschedparam(
+        &mut self,
+        thread: &OpTy<'tcx>,
+        policy: &OpTy<'tcx>,
+        param: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let thread = this.read_scalar(thread)?.to_int(this.libc_ty_layout("pthread_t").size)?;
+        let Ok(thread) = this.thread_id_try_from(thread) else {
+            return interp_ok(this.eval_libc("ESRCH"));
+        };
+
+        let priority = this.get_thread_priority(thread);
+        let policy_place = this.deref_pointer_as(policy, this.machine.layouts.i32)?;
+        let policy = priority.policy.unwrap_or_else(|| this.eval_libc_i32("SCHED_OTHER"));
+        this.write_scalar(Scalar::from_i32(policy), &policy_place)?;
+        let param = this.deref_pointer_as(param, this.libc_ty_layout("sched_param"))?;
+        this.write_int_fields_named(&[("sched_priority", priority.sched_priority.into())], &param)?;
+
+        interp_ok(Scalar::from_u32(0))
+    }
+
+    fn sched_get_priority_max(&mut self, policy: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let policy = this.read_scalar(policy)?.to_i32()?;
+        match this.sched_priority_range(policy) {
+            Some((_min, max)) => interp_ok(Scalar::from_i32(max)),
+            None => this.set_last_error_and_return_i32(LibcError("EINVAL")),
+        }
+    }
+
+    fn sched_get_priority_min(&mut self, policy: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let policy = this.read_scalar(policy)?.to_i32()?;
+        match this.sched_priority_range(policy) {
+            Some((min, _max)) => interp_ok(Scalar::from_i32(min)),
+            None => this.set_last_error_and_return_i32(LibcError("EINVAL")),
+        }
+    }
+
+    fn setpriority(
+        &mut self,
+        which: &OpTy<'tcx>,
+        who: &OpTy<'tcx>,
+        prio: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let prio = this.read_scalar(prio)?.to_i32()?;
+        let Some(thread) = this.priority_target(which, who)? else {
+            return this.set_last_error_and_return_i32(LibcError("ESRCH"));
+        };
+
+        // Like Linux, silently clamp the nice value to the supported range. We do not model the
+        // privileges that are needed to lower it.
+        let priority = this.get_thread_priority(thread);
+        this.set_thread_priority(thread, ThreadPriority {
+            nice: prio.clamp(NICE_MIN, NICE_MAX),
+            ..priority
+        });
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn getpriority(&mut self, which: &OpTy<'tcx>, who: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let Some(thread) = this.priority_target(which, who)? else {
+            return this.set_last_error_and_return_i32(LibcError("ESRCH"));
+        };
+
+        interp_ok(Scalar::from_i32(this.get_thread_priority(thread).nice))
+    }
+
+    fn nice(&mut self, inc: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let inc = this.read_scalar(inc)?.to_i32()?;
+        let thread = this.active_thread();
+        let priority = this.get_thread_priority(thread);
+        let nice = priority.nice.saturating_add(inc).clamp(NICE_MIN, NICE_MAX);
+        this.set_thread_priority(thread, ThreadPriority { nice, ..priority });
+
+        interp_ok(Scalar::from_i32(nice))
+    }
+
+    fn sched_getaffinity(
+        &mut self,
+        pid: &OpTy<'tcx>,
+        cpusetsize: &OpTy<'tcx>,
+        mask: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pid = this.read_scalar(pid)?.to_u32()?;
+        let cpusetsize = this.read_target_usize(cpusetsize)?;
+        let mask = this.read_pointer(mask)?;
+
+        // The mask is stored in chunks, and the size must be a whole number of chunks.
+        let chunk_size = CpuAffinityMask::chunk_size(this);
+
+        if this.ptr_is_null(mask)? {
+            return this.set_last_error_and_return_i32(LibcError("EFAULT"));
+        }
+        if cpusetsize == 0 || cpusetsize.checked_rem(chunk_size).unwrap() != 0 {
+            // we only copy whole chunks of size_of::<c_ulong>()
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        }
+        let Some(cpuset) = this
+            .affinity_target(pid)
+            .and_then(|thread| this.machine.thread_cpu_affinity.get(&thread))
+            .cloned()
+        else {
+            // The thread whose ID is pid could not be found
+            return this.set_last_error_and_return_i32(LibcError("ESRCH"));
+        };
+        this.write_cpu_affinity(&cpuset, cpusetsize, mask)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    fn sched_setaffinity(
+        &mut self,
+        pid: &OpTy<'tcx>,
+        cpusetsize: &OpTy<'tcx>,
+        mask: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let pid = this.read_scalar(pid)?.to_u32()?;
+        let cpusetsize = this.read_target_usize(cpusetsize)?;
+        let mask = this.read_pointer(mask)?;
+
+        if this.ptr_is_null(mask)? {
+            return this.set_last_error_and_return_i32(LibcError("EFAULT"));
+        }
+        let Some(thread) = this.affinity_target(pid) else {
+            // The thread whose ID is pid could not be found
+            return this.set_last_error_and_return_i32(LibcError("ESRCH"));
+        };
+        let Some(cpuset) = this.read_cpu_affinity(cpusetsize, mask)? else {
+            // The intersection between the mask and the available CPUs was empty.
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        };
+        this.machine.thread_cpu_affinity.insert(thread, cpuset);
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    /// FreeBSD's `cpuset_getaffinity`. We do not model cpusets, so the root set and the set of the
+    /// process both contain all available CPUs. We track masks per thread, so the mask of the
+    /// process is that of the calling thread.
+    fn cpuset_getaffinity(
+        &mut self,
+        level: &OpTy<'tcx>,
+        which: &OpTy<'tcx>,
+        id: &OpTy<'tcx>,
+        cpusetsize: &OpTy<'tcx>,
+        mask: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let level = this.read_scalar(level)?.to_i32()?;
+        let which = this.read_scalar(which)?.to_i32()?;
+        let id = this.read_scalar(id)?.to_i64()?;
+        let cpusetsize = this.read_target_usize(cpusetsize)?;
+        let mask = this.read_pointer(mask)?;
+
+        let Some(thread) = this.cpuset_target(which, id)? else {
+            return this.set_last_error_and_return_i32(LibcError("ESRCH"));
+        };
+        if this.ptr_is_null(mask)? {
+            return this.set_last_error_and_return_i32(LibcError("EFAULT"));
+        }
+        let chunk_size = CpuAffinityMask::chunk_size(this);
+        if cpusetsize == 0 || cpusetsize.checked_rem(chunk_size).unwrap() != 0 {
+            return this.set_last_error_and_return_i32(LibcError("ERANGE"));
+        }
+
+        let cpuset = if level == this.eval_libc_i32("CPU_LEVEL_ROOT")
+            || level == this.eval_libc_i32("CPU_LEVEL_CPUSET")
+        {
+            CpuAffinityMask::new(this, this.machine.num_cpus)
+        } else if level == this.eval_libc_i32("CPU_LEVEL_WHICH") {
+            this.machine.thread_cpu_affinity[&thread].clone()
+        } else {
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        };
+        this.write_cpu_affinity(&cpuset, cpusetsize, mask)?;
+
+        interp_ok(Scalar::from_i32(0))
+    }
+
+    /// FreeBSD's `cpuset_setaffinity`. Only the masks of threads and of the process itself can be
+    /// changed; changing the mask of the process changes the masks of all its threads.
+    fn cpuset_setaffinity(
+        &mut self,
+        level: &OpTy<'tcx>,
+        which: &OpTy<'tcx>,
+        id: &OpTy<'tcx>,
+        cpusetsize: &OpTy<'tcx>,
+        mask: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let level = this.read_scalar(level)?.to_i32()?;
+        let which = this.read_scalar(which)?.to_i32()?;
+        let id = this.read_scalar(id)?.to_i64()?;
+        let cpusetsize = this.read_target_usize(cpusetsize)?;
+        let mask = this.read_pointer(mask)?;
+
+        if level != this.eval_libc_i32("CPU_LEVEL_WHICH") {
+            throw_unsup_format!(
+                "`cpuset_setaffinity` is only supported with `CPU_LEVEL_WHICH`, as cpusets are not modeled"
+            );
+        }
+        let Some(thread) = this.cpuset_target(which, id)? else {
+            return this.set_last_error_and_return_i32(LibcError("ESRCH"));
+        };
+        if this.ptr_is_null(mask)? {
+            return this.set_last_error_and_return_i32(LibcError("EFAULT"));
+        }
+        let Some(cpuset) = this.read_cpu_affinity(cpusetsize, mask)? else {
+            // The intersection between the mask and the available CPUs was empty.
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        };
+
+        if which == this.eval_libc_i32("CPU_WHICH_PID") {
+            for thread_cpuset in this.machine.thread_cpu_affinity.values_mut() {
+                *thread_cpuset = cpuset.clone();
+            }
+        } else {
+            this.machine.thread_cpu_affinity.insert(thread, cpuset);
+        }
+
+        interp_ok(Scalar::from_i32(0))
+    }
 }
@@ -7,14 +7,14 @@ use std::collections::VecDeque;
 use std::io;
 use std::io::{ErrorKind, Read};
 
-use rustc_abi::Size;
-
 use crate::concurrency::VClock;
 use crate::shims::files::{
     EvalContextExt as _, FileDescription, FileDescriptionRef, WeakFileDescriptionRef,
 };
 use crate::shims::unix::UnixFileDescription;
 use crate::shims::unix::linux_like::epoll::{EpollReadyEvents, EvalContextExt as _};
+use crate::shims::unix::poll::{deadline_passed, deadline_timeout};
+use crate::shims::unix::socket::Socket;
 use crate::*;
 
 /// The maximum capacity of the socketpair buffer in bytes.
@@ -23,8 +23,9 @@ use crate::*;
 const MAX_SOCKETPAIR_BUFFER_CAPACITY: usize = 212992;
 
 /// One end of a pair of connected unnamed sockets.
+/// This is also used as the stream state of connected named sockets, see `stream_end`.
 #[derive(Debug)]
-struct AnonSocket {
+pub struct AnonSocket {
     /// The buffer we are reading from, or `None` if this is the writing end of a pipe.
     /// (In that case, the peer FD will be the reading end of that pipe.)
     readbuf: Option<RefCell<Buffer>>,
@@ -36,135 +37,361 @@ struct AnonSocket {
     /// This flag is set to `true` if the peer's `readbuf` is non-empty at the time
     /// of closure.
     peer_lost_data: Cell<bool>,
-    is_nonblock: bool,
+    /// A list of thread ids blocked because the buffer was empty.
+    /// If a thread is blocked on reading, the buffer it reads from is `readbuf`.
+    blocked_read_tid: RefCell<Vec<ThreadId>>,
+    /// A list of thread ids blocked because the buffer was full.
+    /// If a thread is blocked on writing, the buffer it writes to is the peer's `readbuf`.
+    blocked_write_tid: RefCell<Vec<ThreadId>>,
+    is_nonblock: Cell<bool>,
+    /// Whether this is one end of a pipe rather than a socket.
+    is_pipe: bool,
+    /// Whether this is one end of a `SOCK_SEQPACKET` socketpair, which preserves the boundaries
+    /// of the messages that are sent.
+    is_seqpacket: bool,
+    /// Whether no more data can be received: either we shut down reading, or the peer shut down
+    /// writing. Reads return EOF once the buffer is empty.
+    read_shutdown: Cell<bool>,
+    /// Whether no more data can be sent: either we shut down writing, or the peer shut down
+    /// reading. Writes fail with EPIPE.
+    write_shutdown: Cell<bool>,
 }
 
 #[derive(Debug)]
 struct Buffer {
     buf: VecDeque<u8>,
     clock: VClock,
+    /// The number of bytes that were read from this buffer so far. Together with the length of
+    /// `buf`, this gives the position of every byte in the stream.
+    consumed: u64,
+    /// File descriptions passed with `SCM_RIGHTS`, together with the stream position of the
+    /// first byte that was sent with them.
+    fds: VecDeque<(u64, Vec<FileDescriptionRef>)>,
+    /// For `SOCK_SEQPACKET`, the stream position after the last byte of every message.
+    msg_ends: VecDeque<u64>,
 }
 
 impl Buffer {
     fn new() -> Self {
-        Buffer { buf: VecDeque::new(), clock: VClock::default() }
+        Buffer {
+            buf: VecDeque::new(),
+            clock: VClock::default(),
+            consumed: 0,
+            fds: VecDeque::new(),
+            msg_ends: VecDeque::new(),
+        }
     }
 }
 
 impl AnonSocket {
+    /// Create one end of a stream. Only the writing end of a pipe has no read buffer.
+    pub fn new(has_readbuf: bool, is_nonblock: bool) -> Self {
+        AnonSocket {
+            readbuf: has_readbuf.then(|| RefCell::new(Buffer::new())),
+            peer_fd: OnceCell::new(),
+            peer_lost_data: Cell::new(false),
+            blocked_read_tid: RefCell::new(Vec::new()),
+            blocked_write_tid: RefCell::new(Vec::new()),
+            is_nonblock: Cell::new(is_nonblock),
+            is_pipe: false,
+            is_seqpacket: false,
+            read_shutdown: Cell::new(false),
+            write_shutdown: Cell::new(false),
+        }
+    }
+
+    /// Create one end of a pipe.
+    fn new_pipe_end(has_readbuf: bool, is_nonblock: bool) -> Self {
+        AnonSocket { is_pipe: true, ..AnonSocket::new(has_readbuf, is_nonblock) }
+    }
+
+    /// Create one end of a `SOCK_SEQPACKET` socketpair.
+    fn new_seqpacket_end(is_nonblock: bool) -> Self {
+        AnonSocket { is_seqpacket: true, ..AnonSocket::new(/* has_readbuf */ true, is_nonblock) }
+    }
+
+    /// Make the two stream ends contained in the given file descriptions point to each other.
+    pub fn connect(fd0: &FileDescriptionRef, fd1: &FileDescriptionRef) {
+        stream_end(fd0).peer_fd.set(fd1.downgrade()).unwrap();
+        stream_end(fd1).peer_fd.set(fd0.downgrade()).unwrap();
+    }
+
     fn peer_fd(&self) -> &WeakFileDescriptionRef {
         self.peer_fd.get().unwrap()
     }
-}
 
-impl FileDescription for AnonSocket {
-    fn name(&self) -> &'static str {
-        "socketpair"
+    pub fn set_nonblock(&self, is_nonblock: bool) {
+        self.is_nonblock.set(is_nonblock);
     }
 
-    fn close<'tcx>(
-        self: Box<Self>,
-        _communicate_allowed: bool,
-        ecx: &mut MiriInterpCx<'tcx>,
-    ) -> InterpResult<'tcx, io::Result<()>> {
+    pub fn is_nonblock(&self) -> bool {
+        self.is_nonblock.get()
+    }
+
+    pub fn is_pipe(&self) -> bool {
+        self.is_pipe
+    }
+
+    /// Whether reading would block: the buffer is empty, but more data may still arrive.
+    pub fn would_block_read<'tcx>(&self) -> InterpResult<'tcx, bool> {
+        let Some(readbuf) = &self.readbuf else {
+            // FIXME: This should return EBADF, but there's no nice way to do that as there's no
+            // corresponding ErrorKind variant.
+            throw_unsup_format!("reading from the write end of a pipe")
+        };
+        interp_ok(
+            readbuf.borrow().buf.is_empty()
+                && !self.read_shutdown.get()
+                && self.peer_fd().upgrade().is_some(),
+        )
+    }
+
+    /// Whether writing `len` bytes would block: the peer's buffer is full, and the peer is still
+    /// open. A `SOCK_SEQPACKET` message is only written once the whole message fits.
+    pub fn would_block_write(&self, len: usize) -> bool {
+        if self.write_shutdown.get() {
+            return false;
+        }
+        let Some(peer_fd) = self.peer_fd().upgrade() else {
+            return false;
+        };
+        let Some(writebuf) = &stream_end(&peer_fd).readbuf else {
+            return false;
+        };
+        let available_space =
+            MAX_SOCKETPAIR_BUFFER_CAPACITY.strict_sub(writebuf.borrow().buf.len());
+        if self.is_seqpacket && len <= MAX_SOCKETPAIR_BUFFER_CAPACITY {
+            available_space < len
+        } else {
+            available_space == 0
+        }
+    }
+
+    /// Register the active thread as blocked on reading from this stream end.
+    pub fn add_blocked_reader(&self, thread_id: ThreadId) {
+        self.blocked_read_tid.borrow_mut().push(thread_id);
+    }
+
+    /// Register the active thread as blocked on writing to this stream end.
+    pub fn add_blocked_writer(&self, thread_id: ThreadId) {
+        self.blocked_write_tid.borrow_mut().push(thread_id);
+    }
+
+    /// Unregister a thread that was blocked on this stream end, but timed out.
+    pub fn remove_blocked_thread(&self, thread_id: ThreadId) {
+        self.blocked_read_tid.borrow_mut().retain(|&id| id != thread_id);
+        self.blocked_write_tid.borrow_mut().retain(|&id| id != thread_id);
+    }
+
+    /// Must be called when the file description that contains this stream end gets closed.
+    pub fn on_close<'tcx>(&self, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+        // File descriptions that were sent to us but never received are lost.
+        if let Some(readbuf) = &self.readbuf {
+            let in_flight = std::mem::take(&mut readbuf.borrow_mut().fds);
+            for fd in in_flight.into_iter().flat_map(|(_, fds)| fds) {
+                fd.close(ecx.machine.communicate(), ecx)?.ok();
+            }
+        }
         if let Some(peer_fd) = self.peer_fd().upgrade() {
+            let peer = stream_end(&peer_fd);
             // If the current readbuf is non-empty when the file description is closed,
             // notify the peer that data lost has happened in current file description.
             if let Some(readbuf) = &self.readbuf {
                 if !readbuf.borrow().buf.is_empty() {
-                    peer_fd.downcast::<AnonSocket>().unwrap().peer_lost_data.set(true);
+                    peer.peer_lost_data.set(true);
                 }
             }
             // Notify peer fd that close has happened, since that can unblock reads and writes.
             ecx.check_and_update_readiness(&peer_fd)?;
+            // The peer's blocked reads will now return EOF, and its blocked writes EPIPE.
+            // We need to take out the blocked thread ids and unblock them together,
+            // because `unblock_threads` may block them again and end up re-adding the
+            // thread to the blocked list.
+            let mut waiting_threads = std::mem::take(&mut *peer.blocked_read_tid.borrow_mut());
+            waiting_threads.append(&mut peer.blocked_write_tid.borrow_mut());
+            for thread_id in waiting_threads {
+                ecx.unblock_thread(thread_id, BlockReason::UnnamedSocket)?;
+            }
+        }
+        interp_ok(())
+    }
+
+    /// Shut down reading and/or writing on this stream end, see `shutdown(2)`.
+    fn shutdown<'tcx>(
+        &self,
+        shut_rd: bool,
+        shut_wr: bool,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let peer_fd = self.peer_fd().upgrade();
+        let peer = peer_fd.as_ref().map(stream_end);
+        if shut_rd {
+            self.read_shutdown.set(true);
+            if let Some(peer) = peer {
+                peer.write_shutdown.set(true);
+            }
+        }
+        if shut_wr {
+            self.write_shutdown.set(true);
+            if let Some(peer) = peer {
+                peer.read_shutdown.set(true);
+            }
+        }
+
+        // Blocked reads will now return EOF, and blocked writes EPIPE. This applies to threads
+        // blocked on either end.
+        let mut waiting_threads = std::mem::take(&mut *self.blocked_read_tid.borrow_mut());
+        waiting_threads.append(&mut self.blocked_write_tid.borrow_mut());
+        if let Some(peer) = peer {
+            waiting_threads.append(&mut peer.blocked_read_tid.borrow_mut());
+            waiting_threads.append(&mut peer.blocked_write_tid.borrow_mut());
+        }
+        for thread_id in waiting_threads {
+            ecx.unblock_thread(thread_id, BlockReason::UnnamedSocket)?;
+        }
+        interp_ok(())
+    }
+
+    /// Check the readiness of this stream end.
+    pub fn epoll_ready_events(&self) -> EpollReadyEvents {
+        // We only check the status of EPOLLIN, EPOLLOUT, EPOLLHUP and EPOLLRDHUP flags.
+        // If other event flags need to be supported in the future, the check should be added here.
+
+        let mut epoll_ready_events = EpollReadyEvents::new();
+
+        if self.read_shutdown.get() {
+            // Reads will not block any more, they return EOF once the buffer is empty.
+            epoll_ready_events.epollin = true;
+            epoll_ready_events.epollrdhup = true;
+        }
+        if self.write_shutdown.get() {
+            // Writes will not block any more, they return EPIPE.
+            epoll_ready_events.epollout = true;
+            if self.read_shutdown.get() {
+                epoll_ready_events.epollhup = true;
+            }
+        }
+
+        // Check if it is readable.
+        if let Some(readbuf) = &self.readbuf {
+            if !readbuf.borrow().buf.is_empty() {
+                epoll_ready_events.epollin = true;
+            }
+        } else {
+            // Without a read buffer, reading never blocks, so we are always ready.
+            epoll_ready_events.epollin = true;
+        }
+
+        // Check if is writable.
+        if let Some(peer_fd) = self.peer_fd().upgrade() {
+            if let Some(writebuf) = &stream_end(&peer_fd).readbuf {
+                let data_size = writebuf.borrow().buf.len();
+                let available_space = MAX_SOCKETPAIR_BUFFER_CAPACITY.strict_sub(data_size);
+                if available_space != 0 {
+                    epoll_ready_events.epollout = true;
+                }
+            } else {
+                // Without a write buffer, writing never blocks.
+                epoll_ready_events.epollout = true;
+            }
+        } else {
+            // Peer FD has been closed. This always sets both the RDHUP and HUP flags.
+            epoll_ready_events.epollrdhup = true;
+            epoll_ready_events.epollhup = true;
+            // Since the peer is closed, even if no data is available reads will return EOF and
+            // writes will return EPIPE. In other words, they won't block, so we mark this as ready
+            // for read and write.
+            epoll_ready_events.epollin = true;
+            epoll_ready_events.epollout = true;
+            // If there is data lost in peer_fd, set EPOLLERR.
+            if self.peer_lost_data.get() {
+                epoll_ready_events.epollerr = true;
+            }
         }
+        epoll_ready_events
+    }
+}
+
+/// Returns the stream end of a file description. This is either an `AnonSocket` itself, or a
+/// named socket that got connected.
+pub fn stream_end(fd: &FileDescriptionRef) -> &AnonSocket {
+    if let Some(anonsocket) = fd.downcast::<AnonSocket>() {
+        return anonsocket;
+    }
+    fd.downcast::<Socket>()
+        .and_then(Socket::stream)
+        .unwrap_or_else(|| panic!("{} is not a connected stream", fd.name()))
+}
+
+impl FileDescription for AnonSocket {
+    fn name(&self) -> &'static str {
+        "socketpair"
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        self.on_close(ecx)?;
         interp_ok(Ok(()))
     }
 
     fn read<'tcx>(
         &self,
-        _self_ref: &FileDescriptionRef,
+        self_ref: &FileDescriptionRef,
         _communicate_allowed: bool,
         ptr: Pointer,
         len: usize,
         dest: &MPlaceTy<'tcx>,
         ecx: &mut MiriInterpCx<'tcx>,
     ) -> InterpResult<'tcx> {
-        let mut bytes = vec![0; len];
-
-        // Always succeed on read size 0.
-        if len == 0 {
-            return ecx.return_read_success(ptr, &bytes, 0, dest);
-        }
+        let iov = vec![(ptr, u64::try_from(len).unwrap())];
+        anonsocket_read(
+            self_ref, iov, /* is_peek */ false, /* is_dontwait */ false, None, dest, ecx,
+        )
+    }
 
-        let Some(readbuf) = &self.readbuf else {
-            // FIXME: This should return EBADF, but there's no nice way to do that as there's no
-            // corresponding ErrorKind variant.
-            throw_unsup_format!("reading from the write end of a pipe");
-        };
-        if readbuf.borrow().buf.is_empty() {
-            if self.peer_fd().upgrade().is_none() {
-                // Socketpair with no peer and empty buffer.
-                // 0 bytes successfully read indicates end-of-file.
-                return ecx.return_read_success(ptr, &bytes, 0, dest);
-            } else {
-                if self.is_nonblock {
-                    // Non-blocking socketpair with writer and empty buffer.
-                    // https://linux.die.net/man/2/read
-                    // EAGAIN or EWOULDBLOCK can be returned for socket,
-                    // POSIX.1-2001 allows either error to be returned for this case.
-                    // Since there is no ErrorKind for EAGAIN, WouldBlock is used.
-                    return ecx.set_last_error_and_return(ErrorKind::WouldBlock, dest);
-                } else {
-                    // Blocking socketpair with writer and empty buffer.
-                    // FIXME: blocking is currently not supported
-                    throw_unsup_format!("socketpair/pipe/pipe2 read: blocking isn't supported yet");
-                }
-            }
-        }
-        // TODO: We might need to decide what to do if peer_fd is closed when read is blocked.
-        anonsocket_read(self, self.peer_fd().upgrade(), &mut bytes, ptr, dest, ecx)
+    fn readv<'tcx>(
+        &self,
+        self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        iov: &[(Pointer, u64)],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        anonsocket_read(
+            self_ref,
+            iov.to_vec(),
+            /* is_peek */ false,
+            /* is_dontwait */ false,
+            None,
+            dest,
+            ecx,
+        )
     }
 
     fn write<'tcx>(
         &self,
-        _self_ref: &FileDescriptionRef,
+        self_ref: &FileDescriptionRef,
         _communicate_allowed: bool,
         ptr: Pointer,
         len: usize,
         dest: &MPlaceTy<'tcx>,
         ecx: &mut MiriInterpCx<'tcx>,
     ) -> InterpResult<'tcx> {
-        // Always succeed on write size 0.
-        // ("If count is zero and fd refers to a file other than a regular file, the results are not specified.")
-        if len == 0 {
-            return ecx.return_write_success(0, dest);
-        }
-
-        // We are writing to our peer's readbuf.
-        let Some(peer_fd) = self.peer_fd().upgrade() else {
-            // If the upgrade from Weak to Rc fails, it indicates that all read ends have been
-            // closed.
-            return ecx.set_last_error_and_return(ErrorKind::BrokenPipe, dest);
-        };
+        anonsocket_write(self_ref, vec![(ptr, u64::try_from(len).unwrap())], None, dest, ecx)
+    }
 
-        let Some(writebuf) = &peer_fd.downcast::<AnonSocket>().unwrap().readbuf else {
-            // FIXME: This should return EBADF, but there's no nice way to do that as there's no
-            // corresponding ErrorKind variant.
-            throw_unsup_format!("writing to the reading end of a pipe");
-        };
-        let available_space =
-            MAX_SOCKETPAIR_BUFFER_CAPACITY.strict_sub(writebuf.borrow().buf.len());
-        if available_space == 0 {
-            if self.is_nonblock {
-                // Non-blocking socketpair with a full buffer.
-                return ecx.set_last_error_and_return(ErrorKind::WouldBlock, dest);
-            } else {
-                // Blocking socketpair with a full buffer.
-                throw_unsup_format!("socketpair/pipe/pipe2 write: blocking isn't supported yet");
-            }
-        }
-        anonsocket_write(available_space, &peer_fd, ptr, len, dest, ecx)
+    fn writev<'tcx>(
+        &self,
+        self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        iov: &[(Pointer, u64)],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        anonsocket_write(self_ref, iov.to_vec(), None, dest, ecx)
     }
 
     fn as_unix(&self) -> &dyn UnixFileDescription {
@@ -172,55 +399,245 @@ impl FileDescription for AnonSocket {
     }
 }
 
-/// Write to AnonSocket based on the space available and return the written byte size.
-fn anonsocket_write<'tcx>(
-    available_space: usize,
-    peer_fd: &FileDescriptionRef,
-    ptr: Pointer,
-    len: usize,
+/// Write the data from the buffers described by `iov` to AnonSocket based on the space available
+/// and return the written byte size. Blocks if the peer's buffer is full, unless the stream end is
+/// non-blocking. Blocking fails with EWOULDBLOCK once the `deadline` (computed by
+/// `deadline_after`) has passed. Blocking is only supported for connected named sockets, not for
+/// socketpairs and pipes.
+pub fn anonsocket_write<'tcx>(
+    self_ref: &FileDescriptionRef,
+    iov: Vec<(Pointer, u64)>,
+    deadline: Option<u64>,
     dest: &MPlaceTy<'tcx>,
     ecx: &mut MiriInterpCx<'tcx>,
 ) -> InterpResult<'tcx> {
-    let Some(writebuf) = &peer_fd.downcast::<AnonSocket>().unwrap().readbuf else {
+    let anonsocket = stream_end(self_ref);
+    let len: usize = iov.iter().map(|(_, len)| usize::try_from(*len).unwrap()).sum();
+
+    // Always succeed on write size 0.
+    // ("If count is zero and fd refers to a file other than a regular file, the results are not specified.")
+    if len == 0 {
+        return ecx.return_write_success(0, dest);
+    }
+
+    if anonsocket.would_block_write(len) {
+        if anonsocket.is_nonblock() || deadline_passed(deadline, ecx) {
+            // Non-blocking socketpair with a full buffer.
+            return ecx.set_last_error_and_return(ErrorKind::WouldBlock, dest);
+        }
+        if self_ref.downcast::<AnonSocket>().is_some() {
+            // FIXME: blocking is currently not supported for socketpairs and pipes
+            throw_unsup_format!("socketpair/pipe/pipe2 write: blocking isn't supported yet");
+        }
+        // Blocking stream with a full buffer: block until the peer reads something or gets
+        // closed.
+        anonsocket.blocked_write_tid.borrow_mut().push(ecx.active_thread());
+        let weak_self_ref = self_ref.downgrade();
+        let dest = dest.clone();
+        ecx.block_thread(
+            BlockReason::UnnamedSocket,
+            deadline_timeout(deadline),
+            callback!(
+                @capture<'tcx> {
+                    weak_self_ref: WeakFileDescriptionRef,
+                    iov: Vec<(Pointer, u64)>,
+                    deadline: Option<u64>,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    let Some(self_ref) = weak_self_ref.upgrade() else {
+                        throw_unsup_format!("socketpair/pipe FD got closed while blocking.")
+                    };
+                    // When we get unblocked, try again.
+                    anonsocket_write(&self_ref, iov, deadline, &dest, this)
+                }
+                @timeout = |this| {
+                    if let Some(self_ref) = weak_self_ref.upgrade() {
+                        stream_end(&self_ref).remove_blocked_thread(this.active_thread());
+                    }
+                    this.set_last_error_and_return(ErrorKind::WouldBlock, &dest)
+                }
+            ),
+        );
+        return interp_ok(());
+    }
+
+    let bytes = ecx.gather_iov(&iov)?;
+    match stream_write(self_ref, &bytes, Vec::new(), ecx)? {
+        Ok(actual_write_size) => ecx.return_write_success(actual_write_size, dest),
+        Err(e) => ecx.set_last_error_and_return(e, dest),
+    }
+}
+
+/// Write as much of `bytes` as fits into the peer's buffer, without blocking, and return the
+/// written byte size. `fds` are file descriptions passed along with the data (`SCM_RIGHTS`);
+/// they get attached to the first written byte. On a `SOCK_SEQPACKET` socket, `bytes` are
+/// written as one message, and the write fails with EMSGSIZE if the message can never fit.
+/// Must not be called if `would_block_write` returns `true`.
+pub fn stream_write<'tcx>(
+    self_ref: &FileDescriptionRef,
+    bytes: &[u8],
+    fds: Vec<FileDescriptionRef>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, Result<usize, IoError>> {
+    let anonsocket = stream_end(self_ref);
+
+    if anonsocket.write_shutdown.get() {
+        // Writing was shut down on our side, or reading on the peer's side.
+        return interp_ok(Err(ErrorKind::BrokenPipe.into()));
+    }
+
+    // We are writing to our peer's readbuf.
+    let Some(peer_fd) = anonsocket.peer_fd().upgrade() else {
+        // If the upgrade from Weak to Rc fails, it indicates that all read ends have been
+        // closed.
+        return interp_ok(Err(ErrorKind::BrokenPipe.into()));
+    };
+    let peer = stream_end(&peer_fd);
+
+    let Some(writebuf) = &peer.readbuf else {
         // FIXME: This should return EBADF, but there's no nice way to do that as there's no
         // corresponding ErrorKind variant.
-        throw_unsup_format!("writing to the reading end of a pipe")
+        throw_unsup_format!("writing to the reading end of a pipe");
     };
+    if anonsocket.is_seqpacket && bytes.len() > MAX_SOCKETPAIR_BUFFER_CAPACITY {
+        return interp_ok(Err(LibcError("EMSGSIZE")));
+    }
     let mut writebuf = writebuf.borrow_mut();
-
+    let available_space = MAX_SOCKETPAIR_BUFFER_CAPACITY.strict_sub(writebuf.buf.len());
+    assert!(available_space > 0, "writing to a full buffer would block");
+    assert!(
+        !anonsocket.is_seqpacket || available_space >= bytes.len(),
+        "writing a message that does not fit would block"
+    );
     // Remember this clock so `read` can synchronize with us.
     ecx.release_clock(|clock| {
         writebuf.clock.join(clock);
     });
     // Do full write / partial write based on the space available.
-    let actual_write_size = len.min(available_space);
-    let bytes = ecx.read_bytes_ptr_strip_provenance(ptr, Size::from_bytes(len))?;
+    let actual_write_size = bytes.len().min(available_space);
+    if !fds.is_empty() {
+        let pos = writebuf.consumed.strict_add(u64::try_from(writebuf.buf.len()).unwrap());
+        writebuf.fds.push_back((pos, fds));
+    }
     writebuf.buf.extend(&bytes[..actual_write_size]);
+    if anonsocket.is_seqpacket {
+        let msg_end = writebuf.consumed.strict_add(u64::try_from(writebuf.buf.len()).unwrap());
+        writebuf.msg_ends.push_back(msg_end);
+    }
 
     // Need to stop accessing peer_fd so that it can be notified.
     drop(writebuf);
 
     // Notification should be provided for peer fd as it became readable.
     // The kernel does this even if the fd was already readable before, so we follow suit.
-    ecx.check_and_update_readiness(peer_fd)?;
+    ecx.check_and_update_readiness(&peer_fd)?;
+    // Unblock all threads that are currently blocked on reading from the peer.
+    let waiting_threads = std::mem::take(&mut *peer.blocked_read_tid.borrow_mut());
+    // FIXME: We can randomize the order of unblocking.
+    for thread_id in waiting_threads {
+        ecx.unblock_thread(thread_id, BlockReason::UnnamedSocket)?;
+    }
 
-    ecx.return_write_success(actual_write_size, dest)
+    interp_ok(Ok(actual_write_size))
 }
 
-/// Read from AnonSocket and return the number of bytes read.
-fn anonsocket_read<'tcx>(
-    anonsocket: &AnonSocket,
-    peer_fd: Option<FileDescriptionRef>,
-    bytes: &mut [u8],
-    ptr: Pointer,
+/// Read from AnonSocket into the buffers described by `iov` and return the number of bytes read.
+/// Blocks if the buffer is empty and the peer is still open, unless the stream end is
+/// non-blocking or `is_dontwait` is set (`MSG_DONTWAIT`). With `is_peek` (`MSG_PEEK`), the data
+/// stays in the buffer. Blocking fails with EWOULDBLOCK once the `deadline` (computed by
+/// `deadline_after`) has passed. Blocking is only supported for connected named sockets, not for
+/// socketpairs and pipes.
+pub fn anonsocket_read<'tcx>(
+    self_ref: &FileDescriptionRef,
+    iov: Vec<(Pointer, u64)>,
+    is_peek: bool,
+    is_dontwait: bool,
+    deadline: Option<u64>,
     dest: &MPlaceTy<'tcx>,
     ecx: &mut MiriInterpCx<'tcx>,
 ) -> InterpResult<'tcx> {
-    let Some(readbuf) = &anonsocket.readbuf else {
-        // FIXME: This should return EBADF, but there's no nice way to do that as there's no
-        // corresponding ErrorKind variant.
-        throw_unsup_format!("reading from the write end of a pipe")
-    };
+    let anonsocket = stream_end(self_ref);
+    let len: usize = iov.iter().map(|(_, len)| usize::try_from(*len).unwrap()).sum();
+
+    // Always succeed on read size 0.
+    if len == 0 {
+        return ecx.return_readv_success(&iov, &[], dest);
+    }
+
+    if anonsocket.would_block_read()? {
+        if anonsocket.is_nonblock() || is_dontwait || deadline_passed(deadline, ecx) {
+            // Non-blocking socketpair with writer and empty buffer.
+            // https://linux.die.net/man/2/read
+            // EAGAIN or EWOULDBLOCK can be returned for socket,
+            // POSIX.1-2001 allows either error to be returned for this case.
+            // Since there is no ErrorKind for EAGAIN, WouldBlock is used.
+            return ecx.set_last_error_and_return(ErrorKind::WouldBlock, dest);
+        }
+        if self_ref.downcast::<AnonSocket>().is_some() {
+            // FIXME: blocking is currently not supported for socketpairs and pipes
+            throw_unsup_format!("socketpair/pipe/pipe2 read: blocking isn't supported yet");
+        }
+        // Blocking stream with writer and empty buffer: block until the peer writes something
+        // or gets closed.
+        anonsocket.blocked_read_tid.borrow_mut().push(ecx.active_thread());
+        let weak_self_ref = self_ref.downgrade();
+        let dest = dest.clone();
+        // `bool` does not implement `VisitProvenance`, so we pass the flag as an integer.
+        let is_peek = u8::from(is_peek);
+        ecx.block_thread(
+            BlockReason::UnnamedSocket,
+            deadline_timeout(deadline),
+            callback!(
+                @capture<'tcx> {
+                    weak_self_ref: WeakFileDescriptionRef,
+                    iov: Vec<(Pointer, u64)>,
+                    is_peek: u8,
+                    deadline: Option<u64>,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    let Some(self_ref) = weak_self_ref.upgrade() else {
+                        throw_unsup_format!("socketpair/pipe FD got closed while blocking.")
+                    };
+                    // When we get unblocked, try again.
+                    anonsocket_read(&self_ref, iov, is_peek != 0, false, deadline, &dest, this)
+                }
+                @timeout = |this| {
+                    if let Some(self_ref) = weak_self_ref.upgrade() {
+                        stream_end(&self_ref).remove_blocked_thread(this.active_thread());
+                    }
+                    this.set_last_error_and_return(ErrorKind::WouldBlock, &dest)
+                }
+            ),
+        );
+        return interp_ok(());
+    }
+
+    let (bytes, fds, _is_truncated) = stream_read(self_ref, len, is_peek, ecx)?;
+    // A plain read discards any file descriptions that were passed along with the data.
+    for fd in fds {
+        fd.close(ecx.machine.communicate(), ecx)?.ok();
+    }
+    ecx.return_readv_success(&iov, &bytes, dest)
+}
+
+/// Read up to `len` bytes from the buffer without blocking. Returns the data, the file
+/// descriptions that were passed along with it (`SCM_RIGHTS`), and whether part of a
+/// `SOCK_SEQPACKET` message was discarded because it did not fit into `len` bytes.
+/// A read never continues past data that has file descriptions attached, so that they are
+/// delivered together with the data they were sent with, and never past the end of a
+/// `SOCK_SEQPACKET` message.
+/// With `is_peek`, the data and file descriptions are returned but stay in the buffer.
+/// An empty result indicates end-of-file. Must not be called if `would_block_read` returns `true`.
+pub fn stream_read<'tcx>(
+    self_ref: &FileDescriptionRef,
+    len: usize,
+    is_peek: bool,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, (Vec<u8>, Vec<FileDescriptionRef>, bool)> {
+    let anonsocket = stream_end(self_ref);
+    let readbuf = anonsocket.readbuf.as_ref().unwrap();
     let mut readbuf = readbuf.borrow_mut();
 
     // Synchronize with all previous writes to this buffer.
@@ -228,9 +645,47 @@ fn anonsocket_read<'tcx>(
     // only sync with the writes whose data we will read.
     ecx.acquire_clock(&readbuf.clock);
 
+    // Take the file descriptions attached to the first byte, and stop before the next byte
+    // that has some attached.
+    let mut fds = Vec::new();
+    if readbuf.fds.front().is_some_and(|(pos, _)| *pos == readbuf.consumed) {
+        fds = if is_peek {
+            readbuf.fds.front().unwrap().1.clone()
+        } else {
+            readbuf.fds.pop_front().unwrap().1
+        };
+    }
+    let next_fds = readbuf.fds.iter().map(|(pos, _)| *pos).find(|pos| *pos > readbuf.consumed);
+    let len = match next_fds {
+        Some(pos) => len.min(usize::try_from(pos.strict_sub(readbuf.consumed)).unwrap()),
+        None => len,
+    };
+    // A read returns at most one message, and discards what does not fit.
+    let msg_len = readbuf
+        .msg_ends
+        .front()
+        .map(|msg_end| usize::try_from(msg_end.strict_sub(readbuf.consumed)).unwrap());
+    let is_truncated = msg_len.is_some_and(|msg_len| msg_len > len);
+    let len = msg_len.map_or(len, |msg_len| len.min(msg_len));
+
+    if is_peek {
+        // Leave everything in the buffer; nothing changes for the peer.
+        let bytes = readbuf.buf.iter().take(len).copied().collect();
+        return interp_ok((bytes, fds, is_truncated));
+    }
+
     // Do full read / partial read based on the space available.
     // Conveniently, `read` exists on `VecDeque` and has exactly the desired behavior.
-    let actual_read_size = readbuf.buf.read(bytes).unwrap();
+    let mut bytes = vec![0; len];
+    let actual_read_size = readbuf.buf.read(&mut bytes[..]).unwrap();
+    bytes.truncate(actual_read_size);
+    let mut consumed_size = actual_read_size;
+    if let Some(msg_len) = msg_len {
+        readbuf.buf.drain(..msg_len.strict_sub(actual_read_size));
+        readbuf.msg_ends.pop_front();
+        consumed_size = msg_len;
+    }
+    readbuf.consumed = readbuf.consumed.strict_add(u64::try_from(consumed_size).unwrap());
 
     // Need to drop before others can access the readbuf again.
     drop(readbuf);
@@ -242,58 +697,23 @@ fn anonsocket_read<'tcx>(
     // don't know what that *certain number* is, we will provide a notification every time
     // a read is successful. This might result in our epoll emulation providing more
     // notifications than the real system.
-    if let Some(peer_fd) = peer_fd {
+    if let Some(peer_fd) = anonsocket.peer_fd().upgrade() {
         ecx.check_and_update_readiness(&peer_fd)?;
+        // Unblock all threads that are currently blocked on writing to us.
+        let waiting_threads =
+            std::mem::take(&mut *stream_end(&peer_fd).blocked_write_tid.borrow_mut());
+        // FIXME: We can randomize the order of unblocking.
+        for thread_id in waiting_threads {
+            ecx.unblock_thread(thread_id, BlockReason::UnnamedSocket)?;
+        }
     }
 
-    ecx.return_read_success(ptr, bytes, actual_read_size, dest)
+    interp_ok((bytes, fds, is_truncated))
 }
 
 impl UnixFileDescription for AnonSocket {
     fn get_epoll_ready_events<'tcx>(&self) -> InterpResult<'tcx, EpollReadyEvents> {
-        // We only check the status of EPOLLIN, EPOLLOUT, EPOLLHUP and EPOLLRDHUP flags.
-        // If other event flags need to be supported in the future, the check should be added here.
-
-        let mut epoll_ready_events = EpollReadyEvents::new();
-
-        // Check if it is readable.
-        if let Some(readbuf) = &self.readbuf {
-            if !readbuf.borrow().buf.is_empty() {
-                epoll_ready_events.epollin = true;
-            }
-        } else {
-            // Without a read buffer, reading never blocks, so we are always ready.
-            epoll_ready_events.epollin = true;
-        }
-
-        // Check if is writable.
-        if let Some(peer_fd) = self.peer_fd().upgrade() {
-            if let Some(writebuf) = &peer_fd.downcast::<AnonSocket>().unwrap().readbuf {
-                let data_size = writebuf.borrow().buf.len();
-                let available_space = MAX_SOCKETPAIR_BUFFER_CAPACITY.strict_sub(data_size);
-                if available_space != 0 {
-                    epoll_ready_events.epollout = true;
-                }
-            } else {
-                // Without a write buffer, writing never blocks.
-                epoll_ready_events.epollout = true;
-            }
-        } else {
-            // Peer FD has been closed. This always sets both the RDHUP and HUP flags
-            // as we do not support `shutdown` that could be used to partially close the stream.
-            epoll_ready_events.epollrdhup = true;
-            epoll_ready_events.epollhup = true;
-            // Since the peer is closed, even if no data is available reads will return EOF and
-            // writes will return EPIPE. In other words, they won't block, so we mark this as ready
-            // for read and write.
-            epoll_ready_events.epollin = true;
-            epoll_ready_events.epollout = true;
-            // If there is data lost in peer_fd, set EPOLLERR.
-            if self.peer_lost_data.get() {
-                epoll_ready_events.epollerr = true;
-            }
-        }
-        interp_ok(epoll_ready_events)
+        interp_ok(self.epoll_ready_events())
     }
 }
 
@@ -316,6 +736,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let sv = this.deref_pointer(sv)?;
 
         let mut is_sock_nonblock = false;
+        let mut is_sock_cloexec = false;
 
         // Interpret the flag. Every flag we recognize is "subtracted" from `flags`, so
         // if there is anything left at the end, that's an unsupported flag.
@@ -328,6 +749,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 flags &= !sock_nonblock;
             }
             if flags & sock_cloexec == sock_cloexec {
+                is_sock_cloexec = true;
                 flags &= !sock_cloexec;
             }
         }
@@ -341,9 +763,11 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                                  and AF_LOCAL are allowed",
                 domain
             );
-        } else if flags != this.eval_libc_i32("SOCK_STREAM") {
+        } else if flags != this.eval_libc_i32("SOCK_STREAM")
+            && flags != this.eval_libc_i32("SOCK_SEQPACKET")
+        {
             throw_unsup_format!(
-                "socketpair: type {:#x} is unsupported, only SOCK_STREAM, \
+                "socketpair: type {:#x} is unsupported, only SOCK_STREAM, SOCK_SEQPACKET, \
                                  SOCK_CLOEXEC and SOCK_NONBLOCK are allowed",
                 flags
             );
@@ -355,27 +779,26 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
 
         // Generate file descriptions.
+        let is_seqpacket = flags == this.eval_libc_i32("SOCK_SEQPACKET");
+        let new_end = || {
+            if is_seqpacket {
+                AnonSocket::new_seqpacket_end(is_sock_nonblock)
+            } else {
+                AnonSocket::new(/* has_readbuf */ true, is_sock_nonblock)
+            }
+        };
         let fds = &mut this.machine.fds;
-        let fd0 = fds.new_ref(AnonSocket {
-            readbuf: Some(RefCell::new(Buffer::new())),
-            peer_fd: OnceCell::new(),
-            peer_lost_data: Cell::new(false),
-            is_nonblock: is_sock_nonblock,
-        });
-        let fd1 = fds.new_ref(AnonSocket {
-            readbuf: Some(RefCell::new(Buffer::new())),
-            peer_fd: OnceCell::new(),
-            peer_lost_data: Cell::new(false),
-            is_nonblock: is_sock_nonblock,
-        });
+        let fd0 = fds.new_ref(new_end());
+        let fd1 = fds.new_ref(new_end());
 
         // Make the file descriptions point to each other.
-        fd0.downcast::<AnonSocket>().unwrap().peer_fd.set(fd1.downgrade()).unwrap();
-        fd1.downcast::<AnonSocket>().unwrap().peer_fd.set(fd0.downgrade()).unwrap();
+        AnonSocket::connect(&fd0, &fd1);
 
         // Insert the file description to the fd table, generating the file descriptors.
         let sv0 = fds.insert(fd0);
         let sv1 = fds.insert(fd1);
+        fds.set_cloexec(sv0, is_sock_cloexec);
+        fds.set_cloexec(sv1, is_sock_cloexec);
 
         // Return socketpair file descriptors to the caller.
         let sv0 = Scalar::from_int(sv0, sv.layout.size);
@@ -405,12 +828,13 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         // Interpret the flag. Every flag we recognize is "subtracted" from `flags`, so
         // if there is anything left at the end, that's an unsupported flag.
         let mut is_nonblock = false;
+        let mut is_cloexec = false;
         if flags & o_nonblock == o_nonblock {
             is_nonblock = true;
             flags &= !o_nonblock;
         }
-        // As usual we ignore CLOEXEC.
         if flags & cloexec == cloexec {
+            is_cloexec = true;
             flags &= !cloexec;
         }
         if flags != 0 {
@@ -420,26 +844,17 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         // Generate file descriptions.
         // pipefd[0] refers to the read end of the pipe.
         let fds = &mut this.machine.fds;
-        let fd0 = fds.new_ref(AnonSocket {
-            readbuf: Some(RefCell::new(Buffer::new())),
-            peer_fd: OnceCell::new(),
-            peer_lost_data: Cell::new(false),
-            is_nonblock,
-        });
-        let fd1 = fds.new_ref(AnonSocket {
-            readbuf: None,
-            peer_fd: OnceCell::new(),
-            peer_lost_data: Cell::new(false),
-            is_nonblock,
-        });
+        let fd0 = fds.new_ref(AnonSocket::new_pipe_end(/* has_readbuf */ true, is_nonblock));
+        let fd1 = fds.new_ref(AnonSocket::new_pipe_end(/* has_readbuf */ false, is_nonblock));
 
         // Make the file descriptions point to each other.
-        fd0.downcast::<AnonSocket>().unwrap().peer_fd.set(fd1.downgrade()).unwrap();
-        fd1.downcast::<AnonSocket>().unwrap().peer_fd.set(fd0.downgrade()).unwrap();
+        AnonSocket::connect(&fd0, &fd1);
 
         // Insert the file description to the fd table, generating the file descriptors.
         let pipefd0 = fds.insert(fd0);
         let pipefd1 = fds.insert(fd1);
+        fds.set_cloexec(pipefd0, is_cloexec);
+        fds.set_cloexec(pipefd1, is_cloexec);
 
         // Return file descriptors to the caller.
         let pipefd0 = Scalar::from_int(pipefd0, pipefd.layout.size);
@@ -449,4 +864,49 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
         interp_ok(Scalar::from_i32(0))
     }
+
+    /// For more information on the arguments see the shutdown manpage:
+    /// <https://man7.org/linux/man-pages/man2/shutdown.2.html>
+    fn shutdown(&mut self, sockfd: &OpTy<'tcx>, how: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+
+        let sockfd = this.read_scalar(sockfd)?.to_i32()?;
+        let how = this.read_scalar(how)?.to_i32()?;
+
+        let Some(fd) = this.machine.fds.get(sockfd) else {
+            return this.set_last_error_and_return_i32(LibcError("EBADF"));
+        };
+        let stream = if let Some(anonsocket) = fd.downcast::<AnonSocket>() {
+            if anonsocket.is_pipe {
+                return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+            }
+            anonsocket
+        } else if let Some(socket) = fd.downcast::<Socket>() {
+            let Some(stream) = socket.stream() else {
+                return this.set_last_error_and_return_i32(LibcError("ENOTCONN"));
+            };
+            stream
+        } else {
+            return this.set_last_error_and_return_i32(LibcError("ENOTSOCK"));
+        };
+
+        let (shut_rd, shut_wr) = if how == this.eval_libc_i32("SHUT_RD") {
+            (true, false)
+        } else if how == this.eval_libc_i32("SHUT_WR") {
+            (false, true)
+        } else if how == this.eval_libc_i32("SHUT_RDWR") {
+            (true, true)
+        } else {
+            return this.set_last_error_and_return_i32(LibcError("EINVAL"));
+        };
+        stream.shutdown(shut_rd, shut_wr, this)?;
+
+        // Both ends might have changed their readiness.
+        this.check_and_update_readiness(&fd)?;
+        if let Some(peer_fd) = stream.peer_fd().upgrade() {
+            this.check_and_update_readiness(&peer_fd)?;
+        }
+
+        interp_ok(Scalar::from_i32(0))
+    }
 }
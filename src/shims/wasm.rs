@@ -0,0 +1,245 @@
+use rustc_abi::ExternAbi;
+use rustc_span::Symbol;
+
+use crate::*;
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn emulate_wasm_intrinsic(
+        &mut self,
+        link_name: Symbol,
+        abi: ExternAbi,
+        args: &[OpTy<'tcx>],
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx, EmulateItemResult> {
+        let this = self.eval_context_mut();
+        this.expect_target_feature_for_intrinsic(link_name, "simd128")?;
+        // Prefix should have already been checked.
+        let unprefixed_name = link_name.as_str().strip_prefix("llvm.wasm.").unwrap();
+        // Most of these intrinsics are overloaded: their names end with the vector types of the
+        // result and the arguments, like `.v16i8`. We get the types from the layouts instead.
+        let name = unprefixed_name.split_once(".v").map_or(unprefixed_name, |(name, _)| name);
+
+        match name {
+            // Used to implement the i8x16_swizzle function.
+            // Selects the bytes of `a` given by the indices in `s`; out-of-range indices
+            // select 0.
+            "swizzle" => {
+                let [a, s] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (a, a_len) = this.project_to_simd(a)?;
+                let (s, s_len) = this.project_to_simd(s)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(dest_len, a_len);
+                assert_eq!(dest_len, s_len);
+
+                for i in 0..dest_len {
+                    let idx = this.read_scalar(&this.project_index(&s, i)?)?.to_u8()?;
+                    let dest = this.project_index(&dest, i)?;
+                    if u64::from(idx) < a_len {
+                        this.copy_op(&this.project_index(&a, idx.into())?, &dest)?;
+                    } else {
+                        this.write_scalar(Scalar::from_u8(0), &dest)?;
+                    }
+                }
+            }
+            // Used to implement the v128_bitselect function.
+            // Takes the bits of `a` where `c` is set, and the bits of `b` elsewhere.
+            "bitselect" => {
+                let [a, b, c] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let a = this.read_scalar(&a.transmute(this.machine.layouts.u128, this)?)?;
+                let b = this.read_scalar(&b.transmute(this.machine.layouts.u128, this)?)?;
+                let c = this.read_scalar(&c.transmute(this.machine.layouts.u128, this)?)?;
+                let (a, b, c) = (a.to_u128()?, b.to_u128()?, c.to_u128()?);
+
+                let dest = dest.transmute(this.machine.layouts.u128, this)?;
+                this.write_scalar(Scalar::from_u128((a & c) | (b & !c)), &dest)?;
+            }
+            // Used to implement the v128_any_true function.
+            // Returns 1 if any bit of `op` is set, 0 otherwise.
+            "anytrue" => {
+                let [op] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let op = this.read_scalar(&op.transmute(this.machine.layouts.u128, this)?)?;
+                let res = op.to_u128()? != 0;
+
+                this.write_scalar(Scalar::from_i32(res.into()), dest)?;
+            }
+            // Used to implement the {i8x16,i16x8,i32x4,i64x2}_all_true functions.
+            // Returns 1 if all elements of `op` are non-zero, 0 otherwise.
+            "alltrue" => {
+                let [op] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (op, op_len) = this.project_to_simd(op)?;
+
+                let mut res = true;
+                for i in 0..op_len {
+                    let elem = this.project_index(&op, i)?;
+                    res &= this.read_scalar(&elem)?.to_uint(elem.layout.size)? != 0;
+                }
+
+                this.write_scalar(Scalar::from_i32(res.into()), dest)?;
+            }
+            // Used to implement the {i8x16,i16x8,i32x4,i64x2}_bitmask functions.
+            // Returns the sign bits of the elements of `op`, with bit `i` holding the sign
+            // bit of element `i`.
+            "bitmask" => {
+                let [op] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (op, op_len) = this.project_to_simd(op)?;
+
+                let mut res = 0u32;
+                for i in 0..op_len {
+                    let elem = this.project_index(&op, i)?;
+                    if this.read_scalar(&elem)?.to_int(elem.layout.size)? < 0 {
+                        res |= 1 << i;
+                    }
+                }
+
+                this.write_scalar(Scalar::from_u32(res), dest)?;
+            }
+            // Used to implement the {i8x16,u8x16}_narrow_i16x8 and {i16x8,u16x8}_narrow_i32x4
+            // functions.
+            // Converts the signed elements of `left` followed by those of `right` to elements
+            // of half the width, saturating to the signed or unsigned range of the result.
+            "narrow.signed" | "narrow.unsigned" => {
+                let [left, right] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (left, left_len) = this.project_to_simd(left)?;
+                let (right, right_len) = this.project_to_simd(right)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(left_len, right_len);
+                assert_eq!(dest_len, left_len.strict_mul(2));
+
+                let unsigned = name == "narrow.unsigned";
+                for i in 0..dest_len {
+                    let src = if i < left_len {
+                        this.project_index(&left, i)?
+                    } else {
+                        this.project_index(&right, i.strict_sub(left_len))?
+                    };
+                    let src = this.read_scalar(&src)?.to_int(src.layout.size)?;
+                    let dest = this.project_index(&dest, i)?;
+                    let size = dest.layout.size;
+
+                    let res = if unsigned {
+                        let res = src.clamp(0, size.unsigned_int_max().try_into().unwrap());
+                        Scalar::from_uint(u128::try_from(res).unwrap(), size)
+                    } else {
+                        Scalar::from_int(
+                            src.clamp(size.signed_int_min(), size.signed_int_max()),
+                            size,
+                        )
+                    };
+                    this.write_scalar(res, &dest)?;
+                }
+            }
+            // Used to implement the {u8x16,u16x8}_avgr functions.
+            // Computes the rounding average of the unsigned elements of `left` and `right`:
+            // `(left + right + 1) / 2`, without overflow.
+            "avgr.unsigned" => {
+                let [left, right] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (left, left_len) = this.project_to_simd(left)?;
+                let (right, right_len) = this.project_to_simd(right)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(dest_len, left_len);
+                assert_eq!(dest_len, right_len);
+
+                for i in 0..dest_len {
+                    let dest = this.project_index(&dest, i)?;
+                    let size = dest.layout.size;
+                    let left = this.read_scalar(&this.project_index(&left, i)?)?.to_uint(size)?;
+                    let right = this.read_scalar(&this.project_index(&right, i)?)?.to_uint(size)?;
+
+                    let res = left.strict_add(right).strict_add(1) >> 1;
+                    this.write_scalar(Scalar::from_uint(res, size), &dest)?;
+                }
+            }
+            // Used to implement the {i16x8,u16x8}_extadd_pairwise_{i8x16,u8x16} and
+            // {i32x4,u32x4}_extadd_pairwise_{i16x8,u16x8} functions.
+            // Adds adjacent pairs of elements of `op`, widened to twice their width.
+            "extadd.pairwise.signed" | "extadd.pairwise.unsigned" => {
+                let [op] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (op, op_len) = this.project_to_simd(op)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(op_len, dest_len.strict_mul(2));
+
+                let signed = name == "extadd.pairwise.signed";
+                for i in 0..dest_len {
+                    let dest = this.project_index(&dest, i)?;
+                    let mut res = 0i128;
+                    for j in [i.strict_mul(2), i.strict_mul(2).strict_add(1)] {
+                        let elem = this.project_index(&op, j)?;
+                        let size = elem.layout.size;
+                        let elem = this.read_scalar(&elem)?;
+                        let elem = if signed {
+                            elem.to_int(size)?
+                        } else {
+                            elem.to_uint(size)?.try_into().unwrap()
+                        };
+                        res = res.strict_add(elem);
+                    }
+                    this.write_scalar(Scalar::from_int(res, dest.layout.size), &dest)?;
+                }
+            }
+            // Used to implement the i16x8_q15mulr_sat function.
+            // Multiplies the 16-bit fixed-point numbers with 15 fractional bits in `left` and
+            // `right`, rounding to nearest and saturating the result.
+            "q15mulr.sat.signed" => {
+                let [left, right] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (left, left_len) = this.project_to_simd(left)?;
+                let (right, right_len) = this.project_to_simd(right)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(dest_len, left_len);
+                assert_eq!(dest_len, right_len);
+
+                for i in 0..dest_len {
+                    let left = this.read_scalar(&this.project_index(&left, i)?)?.to_i16()?;
+                    let right = this.read_scalar(&this.project_index(&right, i)?)?.to_i16()?;
+                    let dest = this.project_index(&dest, i)?;
+
+                    let res =
+                        (i32::from(left).strict_mul(i32::from(right)).strict_add(0x4000)) >> 15;
+                    // Only `i16::MIN * i16::MIN` overflows.
+                    let res = i16::try_from(res).unwrap_or(i16::MAX);
+                    this.write_scalar(Scalar::from_i16(res), &dest)?;
+                }
+            }
+            // Used to implement the i32x4_dot_i16x8 function.
+            // Multiplies the signed 16-bit elements of `left` and `right`, and adds adjacent
+            // pairs of the 32-bit products, with wrap-around.
+            "dot" => {
+                let [left, right] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let (left, left_len) = this.project_to_simd(left)?;
+                let (right, right_len) = this.project_to_simd(right)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(left_len, right_len);
+                assert_eq!(left_len, dest_len.strict_mul(2));
+
+                for i in 0..dest_len {
+                    let mut res = 0i32;
+                    for j in [i.strict_mul(2), i.strict_mul(2).strict_add(1)] {
+                        let left = this.read_scalar(&this.project_index(&left, j)?)?.to_i16()?;
+                        let right = this.read_scalar(&this.project_index(&right, j)?)?.to_i16()?;
+                        res = res.wrapping_add(i32::from(left).strict_mul(i32::from(right)));
+                    }
+                    this.write_scalar(Scalar::from_i32(res), &this.project_index(&dest, i)?)?;
+                }
+            }
+
+            _ => return interp_ok(EmulateItemResult::NotSupported),
+        }
+        interp_ok(EmulateItemResult::NeedsReturn)
+    }
+}
@@ -0,0 +1,297 @@
+//! The Windows console API. Only the standard streams can be consoles, and only if they are
+//! terminals on the host (which requires isolation to be disabled). The console APIs use UTF-16,
+//! which we convert to and from the UTF-8 that the host streams use.
+
+use std::io;
+
+use rustc_abi::{Align, Size};
+
+use crate::shims::files::FileDescriptionRef;
+use crate::shims::windows::fs::EvalContextExt as _;
+use crate::*;
+
+/// The console modes that we report: `ENABLE_PROCESSED_INPUT | ENABLE_LINE_INPUT |
+/// ENABLE_ECHO_INPUT` for input, and `ENABLE_PROCESSED_OUTPUT | ENABLE_WRAP_AT_EOL_OUTPUT` for
+/// output. These are the defaults of a new console.
+const CONSOLE_INPUT_MODE: u32 = 0x7;
+const CONSOLE_OUTPUT_MODE: u32 = 0x3;
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+
+#[allow(non_snake_case)]
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn GetConsoleMode(
+        &mut self,
+        console: &OpTy<'tcx>,
+        mode: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let Some(console) = this.read_console_handle(console, "GetConsoleMode")? else {
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        };
+        let mode = this.deref_pointer_as(mode, this.machine.layouts.u32)?;
+
+        let console_mode = if console.downcast::<io::Stdin>().is_some() {
+            CONSOLE_INPUT_MODE
+        } else {
+            CONSOLE_OUTPUT_MODE
+        };
+        this.write_scalar(Scalar::from_u32(console_mode), &mode)?;
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn SetConsoleMode(
+        &mut self,
+        console: &OpTy<'tcx>,
+        mode: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        if this.read_console_handle(console, "SetConsoleMode")?.is_none() {
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+        // We do not change how the host terminal behaves, so the mode is ignored.
+        this.read_scalar(mode)?.to_u32()?;
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn WriteConsoleW(
+        &mut self,
+        console: &OpTy<'tcx>,
+        buffer: &OpTy<'tcx>,
+        number_of_chars_to_write: &OpTy<'tcx>,
+        number_of_chars_written: &OpTy<'tcx>,
+        reserved: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let Some(console) = this.read_console_handle(console, "WriteConsoleW")? else {
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        };
+        let buffer = this.read_pointer(buffer)?;
+        let len = this.read_scalar(number_of_chars_to_write)?.to_u32()?;
+        let number_of_chars_written = this.read_pointer(number_of_chars_written)?;
+        let reserved = this.read_pointer(reserved)?;
+        if !this.ptr_is_null(reserved)? {
+            throw_unsup_format!("WriteConsoleW: `lpReserved` must be null");
+        }
+
+        let bytes = this.read_bytes_ptr_strip_provenance(
+            buffer,
+            Size::from_bytes(u64::from(len).strict_mul(2)),
+        )?;
+        let wide: Vec<u16> =
+            bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        // Unpaired surrogates cannot be written to a UTF-8 stream; the console shows them as
+        // replacement characters, too.
+        let text = String::from_utf16_lossy(&wide);
+
+        let written = match this.write_host_bytes(&console, text.as_bytes())? {
+            Ok(written) => written,
+            Err(e) => {
+                this.set_last_error(e)?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            }
+        };
+        // Report how many UTF-16 units made it out completely.
+        let chars_written: usize = text
+            .char_indices()
+            .take_while(|&(start, c)| start.strict_add(c.len_utf8()) <= written)
+            .map(|(_, c)| c.len_utf16())
+            .sum();
+        if !this.ptr_is_null(number_of_chars_written)? {
+            let place = this.ptr_to_mplace(number_of_chars_written, this.machine.layouts.u32);
+            this.write_scalar(Scalar::from_u32(u32::try_from(chars_written).unwrap()), &place)?;
+        }
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn ReadConsoleW(
+        &mut self,
+        console: &OpTy<'tcx>,
+        buffer: &OpTy<'tcx>,
+        number_of_chars_to_read: &OpTy<'tcx>,
+        number_of_chars_read: &OpTy<'tcx>,
+        input_control: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let Some(console) = this.read_console_handle(console, "ReadConsoleW")? else {
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        };
+        let buffer = this.read_pointer(buffer)?;
+        let len = this.read_scalar(number_of_chars_to_read)?.to_u32()?;
+        let number_of_chars_read =
+            this.deref_pointer_as(number_of_chars_read, this.machine.layouts.u32)?;
+        let input_control = this.read_pointer(input_control)?;
+        if !this.ptr_is_null(input_control)? {
+            throw_unsup_format!("ReadConsoleW: `pInputControl` is not supported");
+        }
+        if console.downcast::<io::Stdin>().is_none() {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_HANDLE"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+
+        let max_units = usize::try_from(len).unwrap();
+        if max_units == 0 {
+            this.write_scalar(Scalar::from_u32(0), &number_of_chars_read)?;
+            return interp_ok(this.eval_windows("c", "TRUE"));
+        }
+        // Start with what earlier calls read but could not return: the start of a character that
+        // was split across reads, or characters that did not fit into the buffer.
+        let id = console.get_id();
+        let mut bytes = this.machine.console_input.remove(&id).unwrap_or_default();
+        let mut at_eof = false;
+        let (wide, consumed) = loop {
+            let (wide, consumed) = decode_console_input(&bytes, max_units, at_eof);
+            if !wide.is_empty() || at_eof {
+                break (wide, consumed);
+            }
+            if decode_console_input(&bytes, 2, at_eof).1 > 0 {
+                throw_unsup_format!(
+                    "ReadConsoleW: reading a surrogate pair into a buffer of one unit is not supported"
+                );
+            }
+            // Whatever does not fit into the buffer stays pending for the next call.
+            let mut buf = vec![0; max_units];
+            match this.read_host_bytes(&console, &mut buf)? {
+                Ok(0) => at_eof = true,
+                Ok(read) => bytes.extend_from_slice(&buf[..read]),
+                Err(e) => {
+                    // Keep what we already read for the next call.
+                    this.machine.console_input.insert(id, bytes);
+                    this.set_last_error(e)?;
+                    return interp_ok(this.eval_windows("c", "FALSE"));
+                }
+            }
+        };
+        if consumed < bytes.len() {
+            this.machine.console_input.insert(id, bytes.split_off(consumed));
+        }
+        let wide_bytes: Vec<u8> = wide.iter().flat_map(|c| c.to_le_bytes()).collect();
+        this.write_bytes_ptr(buffer, wide_bytes)?;
+        this.write_scalar(
+            Scalar::from_u32(u32::try_from(wide.len()).unwrap()),
+            &number_of_chars_read,
+        )?;
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+}
+
+/// Decodes the UTF-8 `bytes` read from a console into at most `max_units` UTF-16 units. Returns
+/// these units and how many bytes they consumed. An incomplete character at the end is only
+/// consumed (as a replacement character) `at_eof`, since otherwise the rest of it may still arrive.
+fn decode_console_input(bytes: &[u8], max_units: usize, at_eof: bool) -> (Vec<u16>, usize) {
+    let mut wide = Vec::new();
+    let mut consumed = 0;
+    while let Some(chunk) = bytes[consumed..].utf8_chunks().next() {
+        let (c, len) = if let Some(c) = chunk.valid().chars().next() {
+            (c, c.len_utf8())
+        } else {
+            let rest = &bytes[consumed..];
+            let incomplete = chunk.invalid().len() == rest.len()
+                && std::str::from_utf8(rest).is_err_and(|e| e.error_len().is_none());
+            if incomplete && !at_eof {
+                break;
+            }
+            (char::REPLACEMENT_CHARACTER, chunk.invalid().len())
+        };
+        if wide.len().strict_add(c.len_utf16()) > max_units {
+            break;
+        }
+        wide.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+        consumed = consumed.strict_add(len);
+    }
+    (wide, consumed)
+}
+
+impl<'tcx> EvalContextPrivExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// Reads a handle that is passed to a console function, and returns its file description if
+    /// it is a console. Otherwise, this sets the last error and returns `None`, which is how
+    /// programs find out that their output is redirected.
+    fn read_console_handle(
+        &mut self,
+        handle: &OpTy<'tcx>,
+        function_name: &str,
+    ) -> InterpResult<'tcx, Option<FileDescriptionRef>> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(handle, function_name)?;
+        let fd = this.machine.fds.get(fd_num).unwrap();
+        if !fd.is_tty(this.machine.communicate()) {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_HANDLE"))?;
+            return interp_ok(None);
+        }
+        interp_ok(Some(fd))
+    }
+
+    /// Writes `bytes` to `fd`, and returns how many bytes were written.
+    fn write_host_bytes(
+        &mut self,
+        fd: &FileDescriptionRef,
+        bytes: &[u8],
+    ) -> InterpResult<'tcx, Result<usize, IoError>> {
+        let this = self.eval_context_mut();
+        let len = u64::try_from(bytes.len()).unwrap();
+        let buf =
+            this.allocate_ptr(Size::from_bytes(len), Align::ONE, MiriMemoryKind::Machine.into())?;
+        this.write_bytes_ptr(buf.into(), bytes.iter().copied())?;
+        let result = this.transfer_file(fd, buf.into(), u32::try_from(len).unwrap(), true)?;
+        this.deallocate_ptr(buf.into(), None, MiriMemoryKind::Machine.into())?;
+        interp_ok(result.map(|written| usize::try_from(written).unwrap()))
+    }
+
+    /// Reads into `bytes` from `fd`, and returns how many bytes were read.
+    fn read_host_bytes(
+        &mut self,
+        fd: &FileDescriptionRef,
+        bytes: &mut [u8],
+    ) -> InterpResult<'tcx, Result<usize, IoError>> {
+        let this = self.eval_context_mut();
+        let len = u64::try_from(bytes.len()).unwrap();
+        let buf =
+            this.allocate_ptr(Size::from_bytes(len), Align::ONE, MiriMemoryKind::Machine.into())?;
+        let result = this.transfer_file(fd, buf.into(), u32::try_from(len).unwrap(), false)?;
+        if let Ok(read) = result {
+            let read = this.read_bytes_ptr_strip_provenance(buf.into(), Size::from_bytes(read))?;
+            bytes[..read.len()].copy_from_slice(read);
+        }
+        this.deallocate_ptr(buf.into(), None, MiriMemoryKind::Machine.into())?;
+        interp_ok(result.map(|read| usize::try_from(read).unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_console_input;
+
+    #[test]
+    fn test_decode_console_input() {
+        let text = "aé€😀";
+        let bytes = text.as_bytes();
+        let expected: Vec<u16> = text.encode_utf16().collect();
+
+        // Reading through a buffer of two UTF-16 units returns everything, in pieces that never
+        // split a character.
+        let mut pending: Vec<u8> = Vec::new();
+        let mut wide = Vec::new();
+        for &byte in bytes {
+            // Feed the bytes one at a time, like a slow terminal.
+            pending.push(byte);
+            let (units, consumed) = decode_console_input(&pending, 2, false);
+            wide.extend(units);
+            pending.drain(..consumed);
+        }
+        assert!(pending.is_empty());
+        assert_eq!(wide, expected);
+
+        // Characters that do not fit stay pending; a surrogate pair needs room for both halves.
+        assert_eq!(decode_console_input(bytes, 1, false), (vec![u16::from(b'a')], 1));
+        let emoji = "😀".as_bytes();
+        assert_eq!(decode_console_input(emoji, 1, false), (vec![], 0));
+        assert_eq!(decode_console_input(emoji, 2, false).1, 4);
+
+        // The start of a character waits for the rest of it, unless the input ended.
+        assert_eq!(decode_console_input(&emoji[..2], 8, false), (vec![], 0));
+        assert_eq!(decode_console_input(&emoji[..2], 8, true), (vec![0xFFFD], 2));
+        // Invalid bytes become replacement characters right away.
+        assert_eq!(decode_console_input(b"\xFFa", 8, false), (vec![0xFFFD, u16::from(b'a')], 2));
+    }
+}
@@ -0,0 +1,339 @@
+//! Directory enumeration on Windows with `FindFirstFileW` and `FindNextFileW`. A search handle
+//! reads the host directory lazily, like `readdir` on unix, and is stored in the `FdTable`.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, Metadata, ReadDir};
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use crate::shims::files::FileDescription;
+use crate::shims::windows::fs::{file_attributes, filetime, invalid_handle_value, write_filetime};
+use crate::shims::windows::handle::{EvalContextExt as _, Handle};
+use crate::*;
+
+/// `FindExInfoStandard` and `FindExInfoBasic`, the supported `FINDEX_INFO_LEVELS`. We never fill
+/// in the short name, so both behave the same.
+const FIND_EX_INFO_STANDARD: i32 = 0;
+const FIND_EX_INFO_BASIC: i32 = 1;
+/// `FindExSearchNameMatch` and `FindExSearchLimitToDirectories`, the supported
+/// `FINDEX_SEARCH_OPS`. Limiting the search to directories is only advisory, so we ignore it.
+const FIND_EX_SEARCH_NAME_MATCH: i32 = 0;
+const FIND_EX_SEARCH_LIMIT_TO_DIRECTORIES: i32 = 1;
+/// `FIND_FIRST_EX_CASE_SENSITIVE`, which we do not support.
+const FIND_FIRST_EX_CASE_SENSITIVE: u32 = 1;
+/// `IO_REPARSE_TAG_SYMLINK`, which is reported in `dwReserved0` for symbolic links.
+const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000000C;
+/// The length of `WIN32_FIND_DATAW::cFileName`, in `u16`s.
+const MAX_PATH: u64 = 260;
+
+/// Returns whether `name` matches the wildcard `pattern`, where `*` matches any sequence of
+/// characters and `?` matches any single character. Like on Windows, this ignores case.
+fn matches_pattern(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches_pattern(rest, &name[skip..])),
+        Some((&p, rest)) =>
+            match name.split_first() {
+                Some((&n, name_rest)) =>
+                    (p == '?' || p.to_lowercase().eq(n.to_lowercase()))
+                        && matches_pattern(rest, name_rest),
+                None => false,
+            },
+    }
+}
+
+/// A directory entry that matched the pattern of a search.
+#[derive(Debug)]
+struct FoundEntry {
+    name: OsString,
+    /// The metadata of the entry itself, without following symbolic links.
+    metadata: Metadata,
+    /// For symbolic links, whether they point to a directory.
+    link_target_is_dir: bool,
+}
+
+impl FoundEntry {
+    fn new(name: OsString, path: &Path, metadata: Metadata) -> Self {
+        let link_target_is_dir = metadata.file_type().is_symlink() && path.is_dir();
+        FoundEntry { name, metadata, link_target_is_dir }
+    }
+}
+
+/// A search handle, created by `FindFirstFileW`.
+#[derive(Debug)]
+struct FindHandle {
+    /// The pattern that the names of the entries must match, see `matches_pattern`.
+    pattern: Vec<char>,
+    /// The `.` and `..` entries, which the host does not report, if they match the pattern.
+    dot_entries: RefCell<VecDeque<FoundEntry>>,
+    read_dir: RefCell<ReadDir>,
+}
+
+impl FindHandle {
+    /// Returns the next entry that matches the pattern, if any.
+    fn next_entry(&self) -> Option<io::Result<FoundEntry>> {
+        if let Some(entry) = self.dot_entries.borrow_mut().pop_front() {
+            return Some(Ok(entry));
+        }
+        let mut read_dir = self.read_dir.borrow_mut();
+        loop {
+            let entry = match read_dir.next()? {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+            let name = entry.file_name();
+            if !matches_pattern(&self.pattern, &name.to_string_lossy().chars().collect::<Vec<_>>())
+            {
+                continue;
+            }
+            return Some(
+                entry.metadata().map(|metadata| FoundEntry::new(name, &entry.path(), metadata)),
+            );
+        }
+    }
+}
+
+impl FileDescription for FindHandle {
+    fn name(&self) -> &'static str {
+        "directory search"
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        _ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        interp_ok(Ok(()))
+    }
+}
+
+/// Writes `entry` into the `WIN32_FIND_DATAW` that `ptr` points to.
+fn write_find_data<'tcx>(
+    entry: &FoundEntry,
+    ptr: Pointer,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let data = ecx.ptr_to_mplace(ptr, ecx.windows_ty_layout("WIN32_FIND_DATAW"));
+    let metadata = &entry.metadata;
+    let attributes = file_attributes(metadata, entry.link_target_is_dir, ecx);
+    // Windows reports a size of 0 for directories.
+    let size = if metadata.is_dir() { 0 } else { metadata.len() };
+    let reparse_tag = if metadata.file_type().is_symlink() { IO_REPARSE_TAG_SYMLINK } else { 0 };
+    ecx.write_int_fields_named(
+        &[
+            ("dwFileAttributes", attributes.into()),
+            ("nFileSizeHigh", (size >> 32).into()),
+            ("nFileSizeLow", (size & 0xFFFF_FFFF).into()),
+            ("dwReserved0", reparse_tag.into()),
+            ("dwReserved1", 0),
+        ],
+        &data,
+    )?;
+    for (field, time) in [
+        ("ftCreationTime", metadata.created()),
+        ("ftLastAccessTime", metadata.accessed()),
+        ("ftLastWriteTime", metadata.modified()),
+    ] {
+        let time = filetime(time, ecx);
+        write_filetime(time, &ecx.project_field_named(&data, field)?, ecx)?;
+    }
+    let file_name = ecx.project_field_named(&data, "cFileName")?;
+    let (written, _) = ecx.write_os_str_to_wide_str(&entry.name, file_name.ptr(), MAX_PATH)?;
+    if !written {
+        throw_unsup_format!(
+            "directory entry names with more than {} characters are not supported",
+            MAX_PATH - 1
+        );
+    }
+    // We do not have short names.
+    let alternate_file_name = ecx.project_field_named(&data, "cAlternateFileName")?;
+    ecx.write_os_str_to_wide_str(OsStr::new(""), alternate_file_name.ptr(), 1)?;
+    interp_ok(())
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+
+#[allow(non_snake_case)]
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn FindFirstFileW(
+        &mut self,
+        file_name: &OpTy<'tcx>,
+        find_file_data: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        this.assert_target_os("windows", "FindFirstFileW");
+
+        let file_name = this.read_pointer(file_name)?;
+        let find_file_data = this.read_pointer(find_file_data)?;
+        this.find_first_file(file_name, find_file_data, "FindFirstFileW")
+    }
+
+    fn FindFirstFileExW(
+        &mut self,
+        file_name: &OpTy<'tcx>,
+        info_level: &OpTy<'tcx>,
+        find_file_data: &OpTy<'tcx>,
+        search_op: &OpTy<'tcx>,
+        search_filter: &OpTy<'tcx>,
+        additional_flags: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        this.assert_target_os("windows", "FindFirstFileExW");
+
+        let file_name = this.read_pointer(file_name)?;
+        let info_level = this.read_scalar(info_level)?.to_i32()?;
+        let find_file_data = this.read_pointer(find_file_data)?;
+        let search_op = this.read_scalar(search_op)?.to_i32()?;
+        let search_filter = this.read_pointer(search_filter)?;
+        let additional_flags = this.read_scalar(additional_flags)?.to_u32()?;
+
+        if info_level != FIND_EX_INFO_STANDARD && info_level != FIND_EX_INFO_BASIC {
+            throw_unsup_format!("FindFirstFileExW: unsupported info level: {info_level}");
+        }
+        if search_op != FIND_EX_SEARCH_NAME_MATCH
+            && search_op != FIND_EX_SEARCH_LIMIT_TO_DIRECTORIES
+        {
+            throw_unsup_format!("FindFirstFileExW: unsupported search operation: {search_op}");
+        }
+        if !this.ptr_is_null(search_filter)? {
+            throw_unsup_format!("FindFirstFileExW: search filters are not supported");
+        }
+        // The other flags only affect performance.
+        if additional_flags & FIND_FIRST_EX_CASE_SENSITIVE != 0 {
+            throw_unsup_format!("FindFirstFileExW: case-sensitive searches are not supported");
+        }
+        this.find_first_file(file_name, find_file_data, "FindFirstFileExW")
+    }
+
+    fn FindNextFileW(
+        &mut self,
+        find_file: &OpTy<'tcx>,
+        find_file_data: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_find_handle(find_file, "FindNextFileW")?;
+        let find_file_data = this.read_pointer(find_file_data)?;
+
+        let fd = this.machine.fds.get(fd_num).unwrap();
+        let find_handle = fd.downcast::<FindHandle>().unwrap();
+        match find_handle.next_entry() {
+            Some(Ok(entry)) => {
+                write_find_data(&entry, find_file_data, this)?;
+                interp_ok(this.eval_windows("c", "TRUE"))
+            }
+            Some(Err(e)) => {
+                this.set_last_error(e)?;
+                interp_ok(this.eval_windows("c", "FALSE"))
+            }
+            None => {
+                this.set_last_error(IoError::WindowsError("ERROR_NO_MORE_FILES"))?;
+                interp_ok(this.eval_windows("c", "FALSE"))
+            }
+        }
+    }
+
+    fn FindClose(&mut self, find_file: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_find_handle(find_file, "FindClose")?;
+        let fd = this.machine.fds.remove(fd_num).unwrap();
+        fd.close(this.machine.communicate(), this)?.unwrap();
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    /// Starts a search for the entries matching `file_name`, whose last component may contain
+    /// wildcards, and writes the first one to `find_file_data`.
+    fn find_first_file(
+        &mut self,
+        file_name: Pointer,
+        find_file_data: Pointer,
+        function_name: &str,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let path = this.read_path_from_wide_str(file_name)?;
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            this.reject_in_isolation(&format!("`{function_name}`"), reject_with)?;
+            this.set_last_error(ErrorKind::PermissionDenied)?;
+            return interp_ok(invalid_handle_value(this));
+        }
+
+        let Some(pattern) = path.file_name() else {
+            this.set_last_error(IoError::WindowsError("ERROR_FILE_NOT_FOUND"))?;
+            return interp_ok(invalid_handle_value(this));
+        };
+        let mut pattern: Vec<char> = pattern.to_string_lossy().chars().collect();
+        // `*.*` matches names without an extension as well.
+        if pattern == ['*', '.', '*'] {
+            pattern = vec!['*'];
+        }
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let read_dir = match fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                this.set_last_error(IoError::WindowsError("ERROR_PATH_NOT_FOUND"))?;
+                return interp_ok(invalid_handle_value(this));
+            }
+            Err(e) => {
+                this.set_last_error(e)?;
+                return interp_ok(invalid_handle_value(this));
+            }
+        };
+        // Every directory except the root contains `.` and `..`.
+        let mut dot_entries = VecDeque::new();
+        let parent = dir.join("..");
+        if fs::canonicalize(dir).is_ok_and(|dir| dir.parent().is_some()) {
+            for (name, path) in [(".", dir), ("..", parent.as_path())] {
+                let name_chars: Vec<char> = name.chars().collect();
+                if matches_pattern(&pattern, &name_chars)
+                    && let Ok(metadata) = fs::symlink_metadata(path)
+                {
+                    dot_entries.push_back(FoundEntry::new(name.into(), path, metadata));
+                }
+            }
+        }
+        let find_handle = FindHandle {
+            pattern,
+            dot_entries: RefCell::new(dot_entries),
+            read_dir: RefCell::new(read_dir),
+        };
+
+        let entry = match find_handle.next_entry() {
+            Some(Ok(entry)) => entry,
+            Some(Err(e)) => {
+                this.set_last_error(e)?;
+                return interp_ok(invalid_handle_value(this));
+            }
+            None => {
+                this.set_last_error(IoError::WindowsError("ERROR_FILE_NOT_FOUND"))?;
+                return interp_ok(invalid_handle_value(this));
+            }
+        };
+        write_find_data(&entry, find_file_data, this)?;
+        let fd_num = this.machine.fds.insert_new(find_handle);
+        interp_ok(Handle::File(fd_num).to_scalar(this))
+    }
+
+    /// Reads a search handle created by `FindFirstFileW`, and returns its file descriptor number.
+    /// Aborts the program if the handle is invalid.
+    fn read_find_handle(
+        &mut self,
+        handle: &OpTy<'tcx>,
+        function_name: &str,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+        let handle = this.read_scalar(handle)?;
+        if let Ok(Handle::File(fd_num)) = Handle::try_from_scalar(handle, this)?
+            && let Some(fd) = this.machine.fds.get(fd_num)
+            && fd.downcast::<FindHandle>().is_some()
+        {
+            return interp_ok(fd_num);
+        }
+        this.invalid_handle(function_name)?
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
 use std::env;
 use std::ffi::{OsStr, OsString};
 use std::io::ErrorKind;
@@ -9,8 +11,10 @@ use crate::*;
 
 #[derive(Default)]
 pub struct WindowsEnvVars {
-    /// Stores the environment variables.
-    map: FxHashMap<OsString, OsString>,
+    /// Stores the environment variables as `(name, value)`, indexed by the upper-cased name:
+    /// variable names are case-insensitive on Windows. Using an ordered map keeps the block
+    /// returned by `GetEnvironmentStringsW` sorted, like Windows does.
+    map: BTreeMap<OsString, (OsString, OsString)>,
 }
 
 impl VisitProvenance for WindowsEnvVars {
@@ -24,12 +28,35 @@ impl WindowsEnvVars {
         _ecx: &mut InterpCx<'tcx, MiriMachine<'tcx>>,
         env_vars: FxHashMap<OsString, OsString>,
     ) -> InterpResult<'tcx, Self> {
-        interp_ok(Self { map: env_vars })
+        let mut vars = Self::default();
+        for (name, value) in env_vars {
+            vars.set(name, value);
+        }
+        interp_ok(vars)
     }
 
     /// Implementation detail for [`InterpCx::get_env_var`].
     pub(crate) fn get<'tcx>(&self, name: &OsStr) -> InterpResult<'tcx, Option<OsString>> {
-        interp_ok(self.map.get(name).cloned())
+        interp_ok(self.lookup(name).cloned())
+    }
+
+    fn lookup(&self, name: &OsStr) -> Option<&OsString> {
+        self.map.get(&name.to_ascii_uppercase()).map(|(_name, value)| value)
+    }
+
+    /// Sets the variable `name`. If a variable with that name exists already, it keeps the case
+    /// of its name.
+    fn set(&mut self, name: OsString, value: OsString) {
+        match self.map.entry(name.to_ascii_uppercase()) {
+            Entry::Occupied(mut entry) => entry.get_mut().1 = value,
+            Entry::Vacant(entry) => {
+                entry.insert((name, value));
+            }
+        }
+    }
+
+    fn remove(&mut self, name: &OsStr) {
+        self.map.remove(&name.to_ascii_uppercase());
     }
 }
 
@@ -52,7 +79,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let buf_size = this.read_scalar(size_op)?.to_u32()?; // in characters
 
         let name = this.read_os_str_from_wide_str(name_ptr)?;
-        interp_ok(match this.machine.env_vars.windows().map.get(&name).cloned() {
+        interp_ok(match this.machine.env_vars.windows().lookup(&name).cloned() {
             Some(val) => {
                 Scalar::from_u32(windows_check_buffer_size(this.write_os_str_to_wide_str(
                     &val,
@@ -78,7 +105,7 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         // Info on layout of environment blocks in Windows:
         // https://docs.microsoft.com/en-us/windows/win32/procthread/environment-variables
         let mut env_vars = std::ffi::OsString::new();
-        for (name, value) in this.machine.env_vars.windows().map.iter() {
+        for (name, value) in this.machine.env_vars.windows().map.values() {
             env_vars.push(name);
             env_vars.push("=");
             env_vars.push(value);
@@ -123,15 +150,16 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         let name = this.read_os_str_from_wide_str(name_ptr)?;
         if name.is_empty() {
             throw_unsup_format!("environment variable name is an empty string");
-        } else if name.to_string_lossy().contains('=') {
+        } else if name.to_string_lossy().chars().skip(1).any(|c| c == '=') {
+            // Names may start with `=`; Windows uses those for per-drive current directories.
             throw_unsup_format!("environment variable name contains '='");
         } else if this.ptr_is_null(value_ptr)? {
             // Delete environment variable `{name}` if it exists.
-            this.machine.env_vars.windows_mut().map.remove(&name);
+            this.machine.env_vars.windows_mut().remove(&name);
             interp_ok(this.eval_windows("c", "TRUE"))
         } else {
             let value = this.read_os_str_from_wide_str(value_ptr)?;
-            this.machine.env_vars.windows_mut().map.insert(name, value);
+            this.machine.env_vars.windows_mut().set(name, value);
             interp_ok(this.eval_windows("c", "TRUE"))
         }
     }
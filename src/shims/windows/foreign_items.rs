@@ -7,14 +7,9 @@ use rustc_span::Symbol;
 
 use self::shims::windows::handle::{Handle, PseudoHandle};
 use crate::shims::os_str::bytes_to_os_str;
-use crate::shims::windows::handle::HandleError;
 use crate::shims::windows::*;
 use crate::*;
 
-// The NTSTATUS STATUS_INVALID_HANDLE (0xC0000008) encoded as a HRESULT by setting the N bit.
-// (https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-erref/0642cb2f-2075-4469-918c-4441e69c548a)
-const STATUS_INVALID_HANDLE: u32 = 0xD0000008;
-
 pub fn is_dyn_sym(name: &str) -> bool {
     // std does dynamic detection for these symbols
     matches!(
@@ -135,6 +130,19 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let result = this.SetCurrentDirectoryW(path)?;
                 this.write_scalar(result, dest)?;
             }
+            "GetTempPathW" | "GetTempPath2W" => {
+                let [buffer_length, buffer] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let result = this.GetTempPathW(buffer_length, buffer)?;
+                this.write_scalar(result, dest)?;
+            }
+            "GetTempFileNameW" => {
+                let [path_name, prefix_string, unique, temp_file_name] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let result =
+                    this.GetTempFileNameW(path_name, prefix_string, unique, temp_file_name)?;
+                this.write_scalar(result, dest)?;
+            }
             "GetUserProfileDirectoryW" => {
                 let [token, buf, size] =
                     this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
@@ -150,72 +158,261 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
             // File related shims
             "NtWriteFile" => {
-                if !this.frame_in_std() {
-                    throw_unsup_format!(
-                        "`NtWriteFile` support is crude and just enough for stdout to work"
-                    );
-                }
-
+                let args =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.NtReadWriteFile(args, /* is_write */ true, dest)?;
+            }
+            "NtReadFile" => {
+                let args =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.NtReadWriteFile(args, /* is_write */ false, dest)?;
+            }
+            "CreateFileW" => {
                 let [
-                    handle,
-                    _event,
-                    _apc_routine,
-                    _apc_context,
-                    io_status_block,
-                    buf,
-                    n,
-                    byte_offset,
-                    _key,
+                    file_name,
+                    desired_access,
+                    share_mode,
+                    security_attributes,
+                    creation_disposition,
+                    flags_and_attributes,
+                    template_file,
                 ] = this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
-                let handle = this.read_target_isize(handle)?;
-                let buf = this.read_pointer(buf)?;
-                let n = this.read_scalar(n)?.to_u32()?;
-                let byte_offset = this.read_target_usize(byte_offset)?; // is actually a pointer
-                let io_status_block = this
-                    .deref_pointer_as(io_status_block, this.windows_ty_layout("IO_STATUS_BLOCK"))?;
-
-                if byte_offset != 0 {
-                    throw_unsup_format!(
-                        "`NtWriteFile` `ByteOffset` parameter is non-null, which is unsupported"
-                    );
-                }
+                let handle = this.CreateFileW(
+                    file_name,
+                    desired_access,
+                    share_mode,
+                    security_attributes,
+                    creation_disposition,
+                    flags_and_attributes,
+                    template_file,
+                )?;
+                this.write_scalar(handle, dest)?;
+            }
+            "ReadFile" => {
+                let [file, buffer, len, number_of_bytes_read, overlapped] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.ReadFile(file, buffer, len, number_of_bytes_read, overlapped, dest)?;
+            }
+            "WriteFile" => {
+                let [file, buffer, len, number_of_bytes_written, overlapped] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.WriteFile(file, buffer, len, number_of_bytes_written, overlapped, dest)?;
+            }
+            "SetFilePointerEx" => {
+                let [file, distance_to_move, new_file_pointer, move_method] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res =
+                    this.SetFilePointerEx(file, distance_to_move, new_file_pointer, move_method)?;
+                this.write_scalar(res, dest)?;
+            }
+            "SetFileInformationByHandle" => {
+                let [file, class, info, size] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.SetFileInformationByHandle(file, class, info, size)?;
+                this.write_scalar(res, dest)?;
+            }
+            "DeleteFileW" => {
+                let [file_name] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.DeleteFileW(file_name)?;
+                this.write_scalar(res, dest)?;
+            }
+            "MoveFileExW" => {
+                let [existing_file_name, new_file_name, flags] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.MoveFileExW(existing_file_name, new_file_name, flags)?;
+                this.write_scalar(res, dest)?;
+            }
+            "ReplaceFileW" => {
+                let [replaced, replacement, backup, flags, exclude, reserved] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res =
+                    this.ReplaceFileW(replaced, replacement, backup, flags, exclude, reserved)?;
+                this.write_scalar(res, dest)?;
+            }
+            "GetFileInformationByHandle" => {
+                let [file, info] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.GetFileInformationByHandle(file, info)?;
+                this.write_scalar(res, dest)?;
+            }
+            "GetFileInformationByHandleEx" => {
+                let [file, class, info, size] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.GetFileInformationByHandleEx(file, class, info, size)?;
+                this.write_scalar(res, dest)?;
+            }
+            "RtlNtStatusToDosError" => {
+                let [status] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.RtlNtStatusToDosError(status)?;
+                this.write_scalar(res, dest)?;
+            }
 
-                let written = if handle == -11 || handle == -12 {
-                    // stdout/stderr
-                    use io::Write;
-
-                    let buf_cont =
-                        this.read_bytes_ptr_strip_provenance(buf, Size::from_bytes(u64::from(n)))?;
-                    let res = if this.machine.mute_stdout_stderr {
-                        Ok(buf_cont.len())
-                    } else if handle == -11 {
-                        io::stdout().write(buf_cont)
-                    } else {
-                        io::stderr().write(buf_cont)
-                    };
-                    // We write at most `n` bytes, which is a `u32`, so we cannot have written more than that.
-                    res.ok().map(|n| u32::try_from(n).unwrap())
-                } else {
-                    throw_unsup_format!(
-                        "on Windows, writing to anything except stdout/stderr is not supported"
-                    )
-                };
-                // We have to put the result into io_status_block.
-                if let Some(n) = written {
-                    let io_status_information =
-                        this.project_field_named(&io_status_block, "Information")?;
-                    this.write_scalar(
-                        Scalar::from_target_usize(n.into(), this),
-                        &io_status_information,
-                    )?;
-                }
-                // Return whether this was a success. >= 0 is success.
-                // For the error code we arbitrarily pick 0xC0000185, STATUS_IO_DEVICE_ERROR.
-                this.write_scalar(
-                    Scalar::from_u32(if written.is_some() { 0 } else { 0xC0000185u32 }),
+            // Named pipes
+            "CreateNamedPipeW" => {
+                let [
+                    name,
+                    open_mode,
+                    pipe_mode,
+                    max_instances,
+                    out_buffer_size,
+                    in_buffer_size,
+                    default_timeout,
+                    security_attributes,
+                ] = this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let handle = this.CreateNamedPipeW(
+                    name,
+                    open_mode,
+                    pipe_mode,
+                    max_instances,
+                    out_buffer_size,
+                    in_buffer_size,
+                    default_timeout,
+                    security_attributes,
+                )?;
+                this.write_scalar(handle, dest)?;
+            }
+            "ConnectNamedPipe" => {
+                let [pipe, overlapped] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.ConnectNamedPipe(pipe, overlapped, dest)?;
+            }
+            "DisconnectNamedPipe" => {
+                let [pipe] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.DisconnectNamedPipe(pipe)?;
+                this.write_scalar(res, dest)?;
+            }
+            "WaitNamedPipeW" => {
+                let [name, timeout] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.WaitNamedPipeW(name, timeout, dest)?;
+            }
+            "SetNamedPipeHandleState" => {
+                let [pipe, mode, max_collection_count, collect_data_timeout] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.SetNamedPipeHandleState(
+                    pipe,
+                    mode,
+                    max_collection_count,
+                    collect_data_timeout,
+                )?;
+                this.write_scalar(res, dest)?;
+            }
+            "CreateIoCompletionPort" => {
+                let [file, existing_completion_port, completion_key, number_of_concurrent_threads] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.CreateIoCompletionPort(
+                    file,
+                    existing_completion_port,
+                    completion_key,
+                    number_of_concurrent_threads,
+                )?;
+                this.write_scalar(res, dest)?;
+            }
+            "GetQueuedCompletionStatus" => {
+                let [completion_port, number_of_bytes, completion_key, overlapped, milliseconds] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.GetQueuedCompletionStatus(
+                    completion_port,
+                    number_of_bytes,
+                    completion_key,
+                    overlapped,
+                    milliseconds,
                     dest,
                 )?;
             }
+            "GetQueuedCompletionStatusEx" => {
+                let [completion_port, entries, count, num_entries_removed, milliseconds, alertable] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.GetQueuedCompletionStatusEx(
+                    completion_port,
+                    entries,
+                    count,
+                    num_entries_removed,
+                    milliseconds,
+                    alertable,
+                    dest,
+                )?;
+            }
+            "PostQueuedCompletionStatus" => {
+                let [completion_port, number_of_bytes, completion_key, overlapped] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.PostQueuedCompletionStatus(
+                    completion_port,
+                    number_of_bytes,
+                    completion_key,
+                    overlapped,
+                )?;
+                this.write_scalar(res, dest)?;
+            }
+            "GetOverlappedResult" => {
+                let [file, overlapped, number_of_bytes, wait] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.GetOverlappedResult(file, overlapped, number_of_bytes, wait, dest)?;
+            }
+            "CancelIoEx" => {
+                let [file, overlapped] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.CancelIoEx(file, overlapped)?;
+                this.write_scalar(res, dest)?;
+            }
+            "GetConsoleMode" => {
+                let [console, mode] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.GetConsoleMode(console, mode)?;
+                this.write_scalar(res, dest)?;
+            }
+            "SetConsoleMode" => {
+                let [console, mode] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.SetConsoleMode(console, mode)?;
+                this.write_scalar(res, dest)?;
+            }
+            "WriteConsoleW" => {
+                let [console, buffer, len, written, reserved] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.WriteConsoleW(console, buffer, len, written, reserved)?;
+                this.write_scalar(res, dest)?;
+            }
+            "ReadConsoleW" => {
+                let [console, buffer, len, read, input_control] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.ReadConsoleW(console, buffer, len, read, input_control)?;
+                this.write_scalar(res, dest)?;
+            }
+            "FindFirstFileW" => {
+                let [file_name, find_file_data] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.FindFirstFileW(file_name, find_file_data)?;
+                this.write_scalar(res, dest)?;
+            }
+            "FindFirstFileExW" => {
+                let [file_name, info_level, find_file_data, search_op, search_filter, flags] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.FindFirstFileExW(
+                    file_name,
+                    info_level,
+                    find_file_data,
+                    search_op,
+                    search_filter,
+                    flags,
+                )?;
+                this.write_scalar(res, dest)?;
+            }
+            "FindNextFileW" => {
+                let [find_file, find_file_data] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.FindNextFileW(find_file, find_file_data)?;
+                this.write_scalar(res, dest)?;
+            }
+            "FindClose" => {
+                let [find_file] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.FindClose(find_file)?;
+                this.write_scalar(res, dest)?;
+            }
             "GetFullPathNameW" => {
                 let [filename, size, buffer, filepart] =
                     this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
@@ -392,6 +589,50 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.write_int(1, dest)?;
             }
 
+            // Fiber-local storage. We do not support fibers, so this behaves like TLS, except that
+            // keys can have a callback that gets run when the thread exits.
+            "FlsAlloc" => {
+                let [callback] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let callback = this.read_pointer(callback)?;
+                let dtor = if !this.ptr_is_null(callback)? {
+                    Some(this.get_ptr_fn(callback)?.as_instance()?)
+                } else {
+                    None
+                };
+                let key = this.machine.tls.create_tls_key(dtor, dest.layout.size)?;
+                this.write_scalar(Scalar::from_uint(key, dest.layout.size), dest)?;
+            }
+            "FlsGetValue" => {
+                let [key] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let key = u128::from(this.read_scalar(key)?.to_u32()?);
+                let active_thread = this.active_thread();
+                let ptr = this.machine.tls.load_tls(key, active_thread, this)?;
+                this.write_scalar(ptr, dest)?;
+            }
+            "FlsSetValue" => {
+                let [key, new_ptr] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let key = u128::from(this.read_scalar(key)?.to_u32()?);
+                let active_thread = this.active_thread();
+                let new_data = this.read_scalar(new_ptr)?;
+                this.machine.tls.store_tls(key, active_thread, new_data, &*this.tcx)?;
+
+                // Return success (`1`).
+                this.write_int(1, dest)?;
+            }
+            "FlsFree" => {
+                let [key] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let key = u128::from(this.read_scalar(key)?.to_u32()?);
+                // FIXME: Windows runs the callback for the values that are still set.
+                this.machine.tls.delete_tls_key(key)?;
+
+                // Return success (`1`).
+                this.write_int(1, dest)?;
+            }
+
             // Access to command-line arguments
             "GetCommandLineW" => {
                 let [] =
@@ -504,8 +745,43 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 let [handle, timeout] =
                     this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
 
-                let ret = this.WaitForSingleObject(handle, timeout)?;
-                this.write_scalar(ret, dest)?;
+                this.WaitForSingleObject(handle, timeout, dest)?;
+            }
+            "WaitForMultipleObjects" => {
+                let [count, handles, wait_all, timeout] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                this.WaitForMultipleObjects(count, handles, wait_all, timeout, dest)?;
+            }
+            "CreateEventW" => {
+                let [security_attributes, manual_reset, initial_state, name] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res =
+                    this.CreateEventW(security_attributes, manual_reset, initial_state, name)?;
+                this.write_scalar(res, dest)?;
+            }
+            "SetEvent" => {
+                let [event] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.SetEvent(event)?;
+                this.write_scalar(res, dest)?;
+            }
+            "ResetEvent" => {
+                let [event] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.ResetEvent(event)?;
+                this.write_scalar(res, dest)?;
+            }
+            "CreateMutexW" => {
+                let [security_attributes, initial_owner, name] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.CreateMutexW(security_attributes, initial_owner, name)?;
+                this.write_scalar(res, dest)?;
+            }
+            "ReleaseMutex" => {
+                let [mutex] =
+                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
+                let res = this.ReleaseMutex(mutex)?;
+                this.write_scalar(res, dest)?;
             }
             "GetCurrentThread" => {
                 let [] =
@@ -519,56 +795,13 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
             "SetThreadDescription" => {
                 let [handle, name] =
                     this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
-
-                let handle = this.read_scalar(handle)?;
-                let name = this.read_wide_str(this.read_pointer(name)?)?;
-
-                let thread = match Handle::try_from_scalar(handle, this)? {
-                    Ok(Handle::Thread(thread)) => Ok(thread),
-                    Ok(Handle::Pseudo(PseudoHandle::CurrentThread)) => Ok(this.active_thread()),
-                    Ok(_) | Err(HandleError::InvalidHandle) =>
-                        this.invalid_handle("SetThreadDescription")?,
-                    Err(HandleError::ThreadNotFound(e)) => Err(e),
-                };
-                let res = match thread {
-                    Ok(thread) => {
-                        // FIXME: use non-lossy conversion
-                        this.set_thread_name(thread, String::from_utf16_lossy(&name).into_bytes());
-                        Scalar::from_u32(0)
-                    }
-                    Err(_) => Scalar::from_u32(STATUS_INVALID_HANDLE),
-                };
-
+                let res = this.SetThreadDescription(handle, name)?;
                 this.write_scalar(res, dest)?;
             }
             "GetThreadDescription" => {
                 let [handle, name_ptr] =
                     this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
-
-                let handle = this.read_scalar(handle)?;
-                let name_ptr = this.deref_pointer(name_ptr)?; // the pointer where we should store the ptr to the name
-
-                let thread = match Handle::try_from_scalar(handle, this)? {
-                    Ok(Handle::Thread(thread)) => Ok(thread),
-                    Ok(Handle::Pseudo(PseudoHandle::CurrentThread)) => Ok(this.active_thread()),
-                    Ok(_) | Err(HandleError::InvalidHandle) =>
-                        this.invalid_handle("GetThreadDescription")?,
-                    Err(HandleError::ThreadNotFound(e)) => Err(e),
-                };
-                let (name, res) = match thread {
-                    Ok(thread) => {
-                        // Looks like the default thread name is empty.
-                        let name = this.get_thread_name(thread).unwrap_or(b"").to_owned();
-                        let name = this.alloc_os_str_as_wide_str(
-                            bytes_to_os_str(&name)?,
-                            MiriMemoryKind::WinLocal.into(),
-                        )?;
-                        (Scalar::from_maybe_pointer(name, this), Scalar::from_u32(0))
-                    }
-                    Err(_) => (Scalar::null_ptr(this), Scalar::from_u32(STATUS_INVALID_HANDLE)),
-                };
-
-                this.write_scalar(name, &name_ptr)?;
+                let res = this.GetThreadDescription(handle, name_ptr)?;
                 this.write_scalar(res, dest)?;
             }
 
@@ -753,14 +986,6 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 // Pretend these does not exist / nothing happened, by returning zero.
                 this.write_null(dest)?;
             }
-            "GetConsoleMode" if this.frame_in_std() => {
-                let [console, mode] =
-                    this.check_shim(abi, ExternAbi::System { unwind: false }, link_name, args)?;
-                this.read_target_isize(console)?;
-                this.deref_pointer(mode)?;
-                // Indicate an error.
-                this.write_null(dest)?;
-            }
             "GetFileType" if this.frame_in_std() => {
                 #[allow(non_snake_case)]
                 let [_hFile] =
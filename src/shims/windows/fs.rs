@@ -0,0 +1,1270 @@
+//! File access on Windows. Files are stored in the same `FdTable` as on unix, and a file `HANDLE`
+//! refers to its entry there (see `Handle::File`).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{Metadata, OpenOptions};
+use std::io::{self, ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::shims::files::{
+    FdId, FileDescription, FileDescriptionRef, FileHandle, WeakFileDescriptionRef,
+};
+use crate::shims::windows::handle::{EvalContextExt as _, Handle};
+use crate::shims::windows::iocp::{
+    CompletionPacket, PendingCompletion, exposed_addr, post_completion,
+};
+use crate::shims::windows::pipe::{
+    EvalContextExt as _, NamedPipe, pipe_name, pipe_read, pipe_write,
+};
+use crate::shims::windows::temp::{VirtualFile, open_virtual_temp_file, virtual_temp_file_name};
+use crate::shims::windows::wait::{notify_object_waiters, set_overlapped_event};
+use crate::*;
+
+/// The `HANDLE` that `GetStdHandle` returns for `STD_INPUT_HANDLE`, `STD_OUTPUT_HANDLE` and
+/// `STD_ERROR_HANDLE`, and the file descriptor that they refer to.
+const STD_HANDLES: &[(i64, i32)] = &[(-10, 0), (-11, 1), (-12, 2)];
+
+/// The `NTSTATUS` values that `NtReadFile` and `NtWriteFile` can return, and the corresponding
+/// Windows error codes that `RtlNtStatusToDosError` converts them to.
+const NTSTATUS_TABLE: &[(u32, &str)] = &[
+    // STATUS_BUFFER_OVERFLOW
+    (0x80000005, "ERROR_MORE_DATA"),
+    // STATUS_END_OF_FILE
+    (0xC0000011, "ERROR_HANDLE_EOF"),
+    // STATUS_ACCESS_DENIED
+    (0xC0000022, "ERROR_ACCESS_DENIED"),
+    // STATUS_PIPE_DISCONNECTED
+    (0xC00000B0, "ERROR_PIPE_NOT_CONNECTED"),
+    // STATUS_PIPE_CLOSING
+    (0xC00000B1, "ERROR_NO_DATA"),
+    // STATUS_PIPE_LISTENING
+    (0xC00000B3, "ERROR_PIPE_LISTENING"),
+    // STATUS_CANCELLED
+    (0xC0000120, "ERROR_OPERATION_ABORTED"),
+    // STATUS_PIPE_BROKEN
+    (0xC000014B, "ERROR_BROKEN_PIPE"),
+    // STATUS_IO_DEVICE_ERROR
+    (0xC0000185, "ERROR_IO_DEVICE"),
+];
+
+/// STATUS_IO_DEVICE_ERROR, which we use for all errors that have no better `NTSTATUS`.
+const STATUS_IO_DEVICE_ERROR: u32 = 0xC0000185;
+/// The flags of `MoveFileExW` that we support.
+const MOVEFILE_REPLACE_EXISTING: u32 = 0x1;
+const MOVEFILE_COPY_ALLOWED: u32 = 0x2;
+const MOVEFILE_WRITE_THROUGH: u32 = 0x8;
+/// The flags of `ReplaceFileW` that we support: `REPLACEFILE_WRITE_THROUGH`,
+/// `REPLACEFILE_IGNORE_MERGE_ERRORS` and `REPLACEFILE_IGNORE_ACL_ERRORS`. All of them only affect
+/// things we do not emulate.
+const REPLACEFILE_SUPPORTED_FLAGS: u32 = 0x1 | 0x2 | 0x4;
+/// STATUS_PENDING, which the `Internal` field of an `OVERLAPPED` structure holds while its
+/// operation is pending.
+pub const STATUS_PENDING: u32 = 0x103;
+
+/// The files that are open through Windows file handles. We cannot lock files on the host, so we
+/// use this to emulate `FILE_SHARE_DELETE` and files whose deletion is pending.
+#[derive(Debug)]
+pub struct OpenFileTable {
+    /// The canonical path of every open file, and whether it was opened with `FILE_SHARE_DELETE`.
+    files: BTreeMap<FdId, (PathBuf, bool)>,
+    /// The files that `DeleteFileW` deletes once their last handle is closed.
+    delete_pending: BTreeSet<PathBuf>,
+}
+
+impl OpenFileTable {
+    pub(crate) fn new() -> Self {
+        OpenFileTable { files: BTreeMap::new(), delete_pending: BTreeSet::new() }
+    }
+
+    /// Forgets the file description `id`. Returns the file that has to be deleted now, if this was
+    /// the last handle of a file whose deletion is pending.
+    pub fn remove(&mut self, id: FdId) -> Option<PathBuf> {
+        let (path, _) = self.files.remove(&id)?;
+        if self.files.values().any(|(other, _)| *other == path) {
+            return None;
+        }
+        self.delete_pending.take(&path)
+    }
+}
+
+/// Reads the event of an `OVERLAPPED` structure. If the low bit of `hEvent` is set, the event is
+/// the handle without that bit, and the operation does not queue a packet on the completion port
+/// of its handle. Returns the event and whether the completion port gets skipped.
+fn overlapped_event<'tcx>(
+    overlapped: &MPlaceTy<'tcx>,
+    ecx: &MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, (Scalar, bool)> {
+    let event = ecx.read_target_usize(&ecx.project_field_named(overlapped, "hEvent")?)?;
+    interp_ok((Scalar::from_target_usize(event & !1, ecx), event & 1 != 0))
+}
+
+/// How a read or write reports its result: either through the return value and the
+/// `lpNumberOfBytes*` parameter of `ReadFile` and `WriteFile`, through the `NTSTATUS` and the
+/// `IO_STATUS_BLOCK` of `NtReadFile` and `NtWriteFile`, or through the `OVERLAPPED` structure (and
+/// its event, and the I/O completion port of the handle). Reads from pipes can block, so this gets
+/// captured until the operation completes.
+#[derive(Debug)]
+pub enum IoCompletion<'tcx> {
+    Win32 {
+        number_of_bytes: Pointer,
+        dest: MPlaceTy<'tcx>,
+    },
+    Nt {
+        io_status_block: MPlaceTy<'tcx>,
+        dest: MPlaceTy<'tcx>,
+    },
+    /// An overlapped operation. While the call that started the operation is still running,
+    /// `sync` holds the `lpNumberOfBytes*` parameter and the return place of that call; once the
+    /// operation is pending, it is `None`.
+    Overlapped {
+        /// The address of the `OVERLAPPED` structure, whose provenance got exposed.
+        overlapped: u64,
+        /// The completion port and key of the handle, if it is associated with a port.
+        port: Option<(WeakFileDescriptionRef, u64)>,
+        sync: Option<(Pointer, MPlaceTy<'tcx>)>,
+    },
+}
+
+impl VisitProvenance for IoCompletion<'_> {
+    fn visit_provenance(&self, visit: &mut VisitWith<'_>) {
+        match self {
+            IoCompletion::Win32 { number_of_bytes, dest } => {
+                number_of_bytes.visit_provenance(visit);
+                dest.visit_provenance(visit);
+            }
+            IoCompletion::Nt { io_status_block, dest } => {
+                io_status_block.visit_provenance(visit);
+                dest.visit_provenance(visit);
+            }
+            IoCompletion::Overlapped { overlapped: _, port, sync } => {
+                if let Some((port, _key)) = port {
+                    port.visit_provenance(visit);
+                }
+                sync.visit_provenance(visit);
+            }
+        }
+    }
+}
+
+impl<'tcx> IoCompletion<'tcx> {
+    /// Reports that `transferred` bytes were read or written. If `error` is set, the operation
+    /// still failed; this is used for `ERROR_MORE_DATA`, which reports a partial message read.
+    pub fn complete(
+        self,
+        transferred: u32,
+        error: Option<IoError>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        match self {
+            IoCompletion::Win32 { number_of_bytes, dest } => {
+                if !ecx.ptr_is_null(number_of_bytes)? {
+                    let number_of_bytes =
+                        ecx.ptr_to_mplace(number_of_bytes, ecx.machine.layouts.u32);
+                    ecx.write_scalar(Scalar::from_u32(transferred), &number_of_bytes)?;
+                }
+                let res = if let Some(error) = error {
+                    ecx.set_last_error(error)?;
+                    "FALSE"
+                } else {
+                    "TRUE"
+                };
+                ecx.write_scalar(ecx.eval_windows("c", res), &dest)
+            }
+            IoCompletion::Nt { io_status_block, dest } => {
+                let status = io_error_to_ntstatus(error, ecx)?;
+                let information = ecx.project_field_named(&io_status_block, "Information")?;
+                ecx.write_scalar(Scalar::from_target_usize(transferred.into(), ecx), &information)?;
+                ecx.write_scalar(Scalar::from_u32(status), &dest)
+            }
+            IoCompletion::Overlapped { overlapped, port, sync } => {
+                let failed = error.is_some();
+                // Operations that fail right away do not queue a completion packet. Only partial
+                // message reads are reported both ways.
+                let is_warning = matches!(error, Some(IoError::WindowsError("ERROR_MORE_DATA")));
+                if let Some((_, dest)) = &sync
+                    && failed
+                    && !is_warning
+                {
+                    ecx.set_last_error(error.unwrap())?;
+                    return ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest);
+                }
+                let status = io_error_to_ntstatus(error, ecx)?;
+                let overlapped_place = ecx.ptr_to_mplace(
+                    ecx.ptr_from_addr_cast(overlapped)?,
+                    ecx.windows_ty_layout("OVERLAPPED"),
+                );
+                ecx.write_int_fields_named(
+                    &[("Internal", status.into()), ("InternalHigh", transferred.into())],
+                    &overlapped_place,
+                )?;
+                // Remember this clock so `GetOverlappedResult` for this operation can synchronize
+                // with us.
+                if let Some(clock) = ecx.release_clock(|clock| clock.clone()) {
+                    ecx.machine.completion_ports.overlapped_clocks.insert(overlapped, clock);
+                }
+                let (event, _skip_port) = overlapped_event(&overlapped_place, ecx)?;
+                set_overlapped_event(event, /* signaled */ true, ecx)?;
+                // Threads in `GetOverlappedResult` check their operation again.
+                notify_object_waiters(ecx)?;
+                if let Some((port, key)) = port {
+                    post_completion(
+                        &port,
+                        CompletionPacket { bytes: transferred, key, overlapped, status },
+                        ecx,
+                    )?;
+                }
+                if let Some((number_of_bytes, dest)) = sync {
+                    IoCompletion::Win32 { number_of_bytes, dest }.complete(
+                        transferred,
+                        failed.then_some(IoError::WindowsError("ERROR_MORE_DATA")),
+                        ecx,
+                    )?;
+                }
+                interp_ok(())
+            }
+        }
+    }
+
+    /// Reports that a read hit the end of the file.
+    fn complete_eof(self, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+        match self {
+            // `ReadFile` reports the end of the file as a successful read of 0 bytes.
+            IoCompletion::Win32 { .. } => self.complete(0, None, ecx),
+            IoCompletion::Nt { .. } | IoCompletion::Overlapped { .. } =>
+                self.complete(0, Some(IoError::WindowsError("ERROR_HANDLE_EOF")), ecx),
+        }
+    }
+
+    /// Reports that the operation could not complete yet, if the call that started it is still
+    /// running. The operation must be completed later with the result of `into_pending`.
+    pub fn pending(&self, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+        let IoCompletion::Overlapped { overlapped, sync, .. } = self else {
+            panic!("only overlapped operations can be pending");
+        };
+        let overlapped = ecx.ptr_to_mplace(
+            ecx.ptr_from_addr_cast(*overlapped)?,
+            ecx.windows_ty_layout("OVERLAPPED"),
+        );
+        ecx.write_int_fields_named(&[("Internal", STATUS_PENDING.into())], &overlapped)?;
+        if let Some((_, dest)) = sync {
+            ecx.set_last_error(IoError::WindowsError("ERROR_IO_PENDING"))?;
+            ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest)?;
+        }
+        interp_ok(())
+    }
+
+    /// If this is an overlapped operation, returns what is needed to complete it once it is
+    /// pending.
+    pub fn into_pending(self) -> Option<PendingCompletion> {
+        match self {
+            IoCompletion::Overlapped { overlapped, port, sync: _ } =>
+                Some(PendingCompletion { overlapped, port }),
+            IoCompletion::Win32 { .. } | IoCompletion::Nt { .. } => None,
+        }
+    }
+}
+
+/// Converts the result of a read or write into an `NTSTATUS`. Errors that do not have a
+/// corresponding status are reported as `STATUS_IO_DEVICE_ERROR`, and set the last error.
+fn io_error_to_ntstatus<'tcx>(
+    error: Option<IoError>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, u32> {
+    let Some(error) = error else {
+        return interp_ok(0); // STATUS_SUCCESS
+    };
+    if let IoError::WindowsError(name) = &error
+        && let Some(&(status, _)) = NTSTATUS_TABLE.iter().find(|(_, error)| error == name)
+    {
+        return interp_ok(status);
+    }
+    // Keep the error around, even though we can only report a generic status.
+    ecx.set_last_error(error)?;
+    interp_ok(STATUS_IO_DEVICE_ERROR)
+}
+
+/// Converts an `NTSTATUS` into the corresponding Windows error code, like `RtlNtStatusToDosError`.
+pub fn ntstatus_to_error<'tcx>(status: u32, ecx: &MiriInterpCx<'tcx>) -> Scalar {
+    if status == 0 {
+        // STATUS_SUCCESS
+        return ecx.eval_windows("c", "ERROR_SUCCESS");
+    }
+    let error = NTSTATUS_TABLE
+        .iter()
+        .find(|&&(known, _)| known == status)
+        .map_or("ERROR_MR_MID_NOT_FOUND", |&(_, error)| error);
+    ecx.eval_windows("c", error)
+}
+
+/// The metadata of a handle, as `GetFileInformationByHandle` reports it. Times are `FILETIME`s.
+struct HandleInfo {
+    attributes: u32,
+    creation_time: u64,
+    last_access_time: u64,
+    last_write_time: u64,
+    size: u64,
+    volume_serial_number: u32,
+    number_of_links: u32,
+    file_index: u64,
+    is_dir: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) enum CreationDisposition {
+    CreateAlways,
+    CreateNew,
+    OpenAlways,
+    OpenExisting,
+    TruncateExisting,
+}
+
+impl CreationDisposition {
+    fn new<'tcx>(
+        value: u32,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, CreationDisposition> {
+        let create_always = ecx.eval_windows_u32("c", "CREATE_ALWAYS");
+        let create_new = ecx.eval_windows_u32("c", "CREATE_NEW");
+        let open_always = ecx.eval_windows_u32("c", "OPEN_ALWAYS");
+        let open_existing = ecx.eval_windows_u32("c", "OPEN_EXISTING");
+        let truncate_existing = ecx.eval_windows_u32("c", "TRUNCATE_EXISTING");
+
+        let out = if value == create_always {
+            CreationDisposition::CreateAlways
+        } else if value == create_new {
+            CreationDisposition::CreateNew
+        } else if value == open_always {
+            CreationDisposition::OpenAlways
+        } else if value == open_existing {
+            CreationDisposition::OpenExisting
+        } else if value == truncate_existing {
+            CreationDisposition::TruncateExisting
+        } else {
+            throw_unsup_format!("CreateFileW: unsupported creation disposition: {value}");
+        };
+        interp_ok(out)
+    }
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+
+#[allow(non_snake_case)]
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn CreateFileW(
+        &mut self,
+        file_name: &OpTy<'tcx>,
+        desired_access: &OpTy<'tcx>,
+        share_mode: &OpTy<'tcx>,
+        security_attributes: &OpTy<'tcx>,
+        creation_disposition: &OpTy<'tcx>,
+        flags_and_attributes: &OpTy<'tcx>,
+        template_file: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        this.assert_target_os("windows", "CreateFileW");
+
+        let file_name = this.read_pointer(file_name)?;
+        let wide_file_name = this.read_wide_str(file_name)?;
+        let pipe_name = pipe_name(&wide_file_name);
+        let file_name = this.read_path_from_wide_str(file_name)?;
+        let mut desired_access = this.read_scalar(desired_access)?.to_u32()?;
+        let share_mode = this.read_scalar(share_mode)?.to_u32()?;
+        let security_attributes = this.read_pointer(security_attributes)?;
+        let creation_disposition = this.read_scalar(creation_disposition)?.to_u32()?;
+        let flags_and_attributes = this.read_scalar(flags_and_attributes)?.to_u32()?;
+        let template_file = this.read_target_usize(template_file)?;
+
+        let generic_read = this.eval_windows_u32("c", "GENERIC_READ");
+        let generic_write = this.eval_windows_u32("c", "GENERIC_WRITE");
+        let file_share_delete = this.eval_windows_u32("c", "FILE_SHARE_DELETE");
+        let file_share_read = this.eval_windows_u32("c", "FILE_SHARE_READ");
+        let file_share_write = this.eval_windows_u32("c", "FILE_SHARE_WRITE");
+        let file_attribute_normal = this.eval_windows_u32("c", "FILE_ATTRIBUTE_NORMAL");
+        let file_flag_backup_semantics = this.eval_windows_u32("c", "FILE_FLAG_BACKUP_SEMANTICS");
+        let file_flag_open_reparse_point =
+            this.eval_windows_u32("c", "FILE_FLAG_OPEN_REPARSE_POINT");
+        let file_flag_overlapped = this.eval_windows_u32("c", "FILE_FLAG_OVERLAPPED");
+
+        let creation_disposition = CreationDisposition::new(creation_disposition, this)?;
+
+        // We cannot lock files on the host, so we only support sharing reading and writing (`std`
+        // shares everything by default). Whether deleting is shared only matters for `DeleteFileW`,
+        // which checks it itself.
+        if share_mode & !file_share_delete != (file_share_read | file_share_write) {
+            throw_unsup_format!("CreateFileW: unsupported share mode: {share_mode:#x}");
+        }
+        let share_delete = share_mode & file_share_delete != 0;
+        if !this.ptr_is_null(security_attributes)? {
+            throw_unsup_format!("CreateFileW: security attributes are not supported");
+        }
+        if template_file != 0 {
+            throw_unsup_format!("CreateFileW: template files are not supported");
+        }
+        // Overlapped I/O only needs the handle to be associated with a completion port later.
+        let supported_flags = file_attribute_normal
+            | file_flag_backup_semantics
+            | file_flag_open_reparse_point
+            | file_flag_overlapped;
+        if flags_and_attributes & !supported_flags != 0 {
+            throw_unsup_format!(
+                "CreateFileW: unsupported flags and attributes: {flags_and_attributes:#x}"
+            );
+        }
+
+        let mut options = OpenOptions::new();
+        let readable = desired_access & generic_read != 0;
+        let writable = desired_access & generic_write != 0;
+        desired_access &= !(generic_read | generic_write);
+        // A handle without any access rights can still be used to query metadata, so we open the
+        // host file for reading unless it is only opened for writing.
+        options.read(readable || !writable);
+        options.write(writable);
+        if desired_access != 0 {
+            throw_unsup_format!("CreateFileW: unsupported access mode: {desired_access:#x}");
+        }
+
+        // Named pipes only exist inside Miri, so clients can connect to them even with isolation.
+        if let Some(pipe_name) = pipe_name {
+            if creation_disposition != CreationDisposition::OpenExisting {
+                throw_unsup_format!("CreateFileW: pipes can only be opened with `OPEN_EXISTING`");
+            }
+            return this.open_named_pipe(&pipe_name, readable, writable);
+        }
+
+        // Reject if isolation is enabled, unless this is a file in the virtual temporary directory.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            if let Some(name) = virtual_temp_file_name(&wide_file_name) {
+                return open_virtual_temp_file(
+                    &name,
+                    creation_disposition,
+                    readable,
+                    writable,
+                    this,
+                );
+            }
+            this.reject_in_isolation("`CreateFileW`", reject_with)?;
+            this.set_last_error(ErrorKind::PermissionDenied)?;
+            return interp_ok(invalid_handle_value(this));
+        }
+
+        // Opening a directory requires `FILE_FLAG_BACKUP_SEMANTICS`.
+        if file_name.is_dir() {
+            if flags_and_attributes & file_flag_backup_semantics == 0 {
+                this.set_last_error(IoError::WindowsError("ERROR_ACCESS_DENIED"))?;
+                return interp_ok(invalid_handle_value(this));
+            }
+            throw_unsup_format!("CreateFileW: opening directories is not supported");
+        }
+        // Files whose deletion is pending cannot be opened anymore.
+        if let Ok(path) = file_name.canonicalize()
+            && this.machine.open_files.delete_pending.contains(&path)
+        {
+            this.set_last_error(IoError::WindowsError("ERROR_ACCESS_DENIED"))?;
+            return interp_ok(invalid_handle_value(this));
+        }
+
+        // `CREATE_ALWAYS` and `OPEN_ALWAYS` report whether the file already existed through the
+        // last error. This check is racy, but the host does not tell us whether `open` created the
+        // file.
+        let existed = matches!(
+            creation_disposition,
+            CreationDisposition::CreateAlways | CreationDisposition::OpenAlways
+        ) && file_name.exists();
+
+        match creation_disposition {
+            CreationDisposition::CreateAlways => {
+                options.create(true).truncate(true);
+            }
+            CreationDisposition::CreateNew => {
+                options.create_new(true);
+            }
+            CreationDisposition::OpenAlways => {
+                options.create(true);
+            }
+            CreationDisposition::OpenExisting => {}
+            CreationDisposition::TruncateExisting => {
+                options.truncate(true);
+            }
+        }
+        if !writable && creation_disposition != CreationDisposition::OpenExisting {
+            // The host only creates and truncates files that are opened for writing. So we
+            // create the file with a separate handle, and then open it without write access.
+            if creation_disposition != CreationDisposition::CreateNew
+                && creation_disposition != CreationDisposition::OpenAlways
+            {
+                throw_unsup_format!(
+                    "CreateFileW: truncating a file requires `GENERIC_WRITE` access"
+                );
+            }
+            if let Err(e) = options.clone().write(true).open(&file_name) {
+                this.set_last_error(e)?;
+                return interp_ok(invalid_handle_value(this));
+            }
+            options = OpenOptions::new();
+            options.read(true);
+        }
+
+        match options.open(&file_name) {
+            Ok(file) => {
+                if existed {
+                    this.set_last_error(IoError::WindowsError("ERROR_ALREADY_EXISTS"))?;
+                } else {
+                    this.set_last_error(Scalar::from_u32(0))?;
+                }
+                let fd_num = this.machine.fds.insert_new(FileHandle { file, writable });
+                if let Ok(path) = file_name.canonicalize() {
+                    let id = this.machine.fds.get(fd_num).unwrap().get_id();
+                    this.machine.open_files.files.insert(id, (path, share_delete));
+                }
+                interp_ok(Handle::File(fd_num).to_scalar(this))
+            }
+            Err(e) => {
+                this.set_last_error(e)?;
+                interp_ok(invalid_handle_value(this))
+            }
+        }
+    }
+
+    fn ReadFile(
+        &mut self,
+        file: &OpTy<'tcx>,
+        buffer: &OpTy<'tcx>,
+        number_of_bytes_to_read: &OpTy<'tcx>,
+        number_of_bytes_read: &OpTy<'tcx>,
+        overlapped: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(file, "ReadFile")?;
+        let buffer = this.read_pointer(buffer)?;
+        let len = this.read_scalar(number_of_bytes_to_read)?.to_u32()?;
+        let number_of_bytes = this.read_pointer(number_of_bytes_read)?;
+        let overlapped = this.read_pointer(overlapped)?;
+
+        let completion =
+            this.io_completion(fd_num, overlapped, number_of_bytes, dest, "ReadFile")?;
+        this.read_write_handle(fd_num, buffer, len, /* is_write */ false, completion)
+    }
+
+    fn WriteFile(
+        &mut self,
+        file: &OpTy<'tcx>,
+        buffer: &OpTy<'tcx>,
+        number_of_bytes_to_write: &OpTy<'tcx>,
+        number_of_bytes_written: &OpTy<'tcx>,
+        overlapped: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(file, "WriteFile")?;
+        let buffer = this.read_pointer(buffer)?;
+        let len = this.read_scalar(number_of_bytes_to_write)?.to_u32()?;
+        let number_of_bytes = this.read_pointer(number_of_bytes_written)?;
+        let overlapped = this.read_pointer(overlapped)?;
+
+        let completion =
+            this.io_completion(fd_num, overlapped, number_of_bytes, dest, "WriteFile")?;
+        this.read_write_handle(fd_num, buffer, len, /* is_write */ true, completion)
+    }
+
+    fn SetFilePointerEx(
+        &mut self,
+        file: &OpTy<'tcx>,
+        distance_to_move: &OpTy<'tcx>,
+        new_file_pointer: &OpTy<'tcx>,
+        move_method: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(file, "SetFilePointerEx")?;
+        let distance_to_move = this.read_scalar(distance_to_move)?.to_i64()?;
+        let new_file_pointer = this.read_pointer(new_file_pointer)?;
+        let move_method = this.read_scalar(move_method)?.to_u32()?;
+
+        let seek_from = if move_method == this.eval_windows_u32("c", "FILE_BEGIN") {
+            let Ok(offset) = u64::try_from(distance_to_move) else {
+                this.set_last_error(IoError::WindowsError("ERROR_NEGATIVE_SEEK"))?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            };
+            SeekFrom::Start(offset)
+        } else if move_method == this.eval_windows_u32("c", "FILE_CURRENT") {
+            SeekFrom::Current(distance_to_move)
+        } else if move_method == this.eval_windows_u32("c", "FILE_END") {
+            SeekFrom::End(distance_to_move)
+        } else {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        };
+
+        let fd = this.machine.fds.get(fd_num).unwrap();
+        let result = fd.seek(this.machine.communicate(), seek_from)?;
+        match result {
+            Ok(pos) => {
+                if !this.ptr_is_null(new_file_pointer)? {
+                    let new_file_pointer =
+                        this.ptr_to_mplace(new_file_pointer, this.machine.layouts.i64);
+                    // The position comes from a seek on the host, so it fits into an `i64`.
+                    this.write_int(pos, &new_file_pointer)?;
+                }
+                interp_ok(this.eval_windows("c", "TRUE"))
+            }
+            Err(e) => {
+                this.set_last_error(e)?;
+                interp_ok(this.eval_windows("c", "FALSE"))
+            }
+        }
+    }
+
+    fn SetFileInformationByHandle(
+        &mut self,
+        file: &OpTy<'tcx>,
+        file_information_class: &OpTy<'tcx>,
+        file_information: &OpTy<'tcx>,
+        buffer_size: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(file, "SetFileInformationByHandle")?;
+        let class = this.read_scalar(file_information_class)?.to_i32()?;
+        let file_information = this.read_pointer(file_information)?;
+        let buffer_size = this.read_scalar(buffer_size)?.to_u32()?;
+
+        // `std` uses these to truncate files (see `File::set_len` and `File::create`).
+        let (layout, field) = if class == this.eval_windows("c", "FileEndOfFileInfo").to_i32()? {
+            (this.windows_ty_layout("FILE_END_OF_FILE_INFO"), "EndOfFile")
+        } else if class == this.eval_windows("c", "FileAllocationInfo").to_i32()? {
+            (this.windows_ty_layout("FILE_ALLOCATION_INFO"), "AllocationSize")
+        } else {
+            throw_unsup_format!(
+                "SetFileInformationByHandle: unsupported information class {class}"
+            );
+        };
+        if u64::from(buffer_size) < layout.size.bytes() {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+        let info = this.ptr_to_mplace(file_information, layout);
+        let size = this.read_scalar(&this.project_field_named(&info, field)?)?.to_i64()?;
+        let Ok(size) = u64::try_from(size) else {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        };
+
+        let fd = this.machine.fds.get(fd_num).unwrap();
+        if let Some(file) = fd.downcast::<VirtualFile>() {
+            if !file.is_writable() {
+                this.set_last_error(IoError::WindowsError("ERROR_ACCESS_DENIED"))?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            }
+            // Shrinking the allocation truncates the file; growing it does not change its size.
+            if (field == "EndOfFile" || size < file.size())
+                && let Err(e) = file.set_len(size)
+            {
+                this.set_last_error(e)?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            }
+            return interp_ok(this.eval_windows("c", "TRUE"));
+        }
+        let Some(FileHandle { file, writable }) = fd.downcast::<FileHandle>() else {
+            throw_unsup_format!(
+                "SetFileInformationByHandle is only supported on file-backed handles"
+            );
+        };
+        if !writable {
+            this.set_last_error(IoError::WindowsError("ERROR_ACCESS_DENIED"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+        let result = if field == "AllocationSize" {
+            // Shrinking the allocation truncates the file; growing it does not change its size.
+            file.metadata().and_then(|m| if size < m.len() { file.set_len(size) } else { Ok(()) })
+        } else {
+            file.set_len(size)
+        };
+        match result {
+            Ok(()) => interp_ok(this.eval_windows("c", "TRUE")),
+            Err(e) => {
+                this.set_last_error(e)?;
+                interp_ok(this.eval_windows("c", "FALSE"))
+            }
+        }
+    }
+
+    fn GetFileInformationByHandle(
+        &mut self,
+        file: &OpTy<'tcx>,
+        file_information: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(file, "GetFileInformationByHandle")?;
+        let file_information = this.read_pointer(file_information)?;
+
+        let info = match handle_info(fd_num, this)? {
+            Ok(info) => info,
+            Err(e) => {
+                this.set_last_error(e)?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            }
+        };
+        let dest = this
+            .ptr_to_mplace(file_information, this.windows_ty_layout("BY_HANDLE_FILE_INFORMATION"));
+        this.write_int_fields_named(
+            &[
+                ("dwFileAttributes", info.attributes.into()),
+                ("dwVolumeSerialNumber", info.volume_serial_number.into()),
+                ("nFileSizeHigh", (info.size >> 32).into()),
+                ("nFileSizeLow", (info.size & 0xFFFF_FFFF).into()),
+                ("nNumberOfLinks", info.number_of_links.into()),
+                ("nFileIndexHigh", (info.file_index >> 32).into()),
+                ("nFileIndexLow", (info.file_index & 0xFFFF_FFFF).into()),
+            ],
+            &dest,
+        )?;
+        for (field, time) in [
+            ("ftCreationTime", info.creation_time),
+            ("ftLastAccessTime", info.last_access_time),
+            ("ftLastWriteTime", info.last_write_time),
+        ] {
+            write_filetime(time, &this.project_field_named(&dest, field)?, this)?;
+        }
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn GetFileInformationByHandleEx(
+        &mut self,
+        file: &OpTy<'tcx>,
+        file_information_class: &OpTy<'tcx>,
+        file_information: &OpTy<'tcx>,
+        buffer_size: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(file, "GetFileInformationByHandleEx")?;
+        let class = this.read_scalar(file_information_class)?.to_i32()?;
+        let file_information = this.read_pointer(file_information)?;
+        let buffer_size = this.read_scalar(buffer_size)?.to_u32()?;
+
+        let ty = if class == this.eval_windows("c", "FileBasicInfo").to_i32()? {
+            "FILE_BASIC_INFO"
+        } else if class == this.eval_windows("c", "FileStandardInfo").to_i32()? {
+            "FILE_STANDARD_INFO"
+        } else if class == this.eval_windows("c", "FileAttributeTagInfo").to_i32()? {
+            "FILE_ATTRIBUTE_TAG_INFO"
+        } else {
+            throw_unsup_format!(
+                "GetFileInformationByHandleEx: unsupported information class {class}"
+            );
+        };
+        let layout = this.windows_ty_layout(ty);
+        if u64::from(buffer_size) < layout.size.bytes() {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+        let info = match handle_info(fd_num, this)? {
+            Ok(info) => info,
+            Err(e) => {
+                this.set_last_error(e)?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            }
+        };
+        let dest = this.ptr_to_mplace(file_information, layout);
+        let fields: &[(&str, i128)] = match ty {
+            "FILE_BASIC_INFO" =>
+                &[
+                    ("CreationTime", info.creation_time.into()),
+                    ("LastAccessTime", info.last_access_time.into()),
+                    ("LastWriteTime", info.last_write_time.into()),
+                    // We do not know when the metadata changed, so we use the last write.
+                    ("ChangeTime", info.last_write_time.into()),
+                    ("FileAttributes", info.attributes.into()),
+                ],
+            "FILE_STANDARD_INFO" =>
+                &[
+                    ("AllocationSize", info.size.into()),
+                    ("EndOfFile", info.size.into()),
+                    ("NumberOfLinks", info.number_of_links.into()),
+                    ("DeletePending", 0),
+                    ("Directory", info.is_dir.into()),
+                ],
+            // The handles we open never refer to reparse points themselves, so there is no tag.
+            _ => &[("FileAttributes", info.attributes.into()), ("ReparseTag", 0)],
+        };
+        this.write_int_fields_named(fields, &dest)?;
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn DeleteFileW(&mut self, file_name: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let file_name = this.read_pointer(file_name)?;
+        let virtual_name = virtual_temp_file_name(&this.read_wide_str(file_name)?);
+        let file_name = this.read_path_from_wide_str(file_name)?;
+
+        // Directories have to be removed with `RemoveDirectoryW`.
+        let result = if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            // Reject if isolation is enabled, unless this is a file in the virtual temporary
+            // directory.
+            let Some(name) = virtual_name else {
+                this.reject_in_isolation("`DeleteFileW`", reject_with)?;
+                this.set_last_error(ErrorKind::PermissionDenied)?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            };
+            this.machine.temp_files.remove(&name)
+        } else if file_name.is_dir() {
+            Err(IoError::WindowsError("ERROR_ACCESS_DENIED"))
+        } else {
+            match file_name.canonicalize() {
+                Ok(path) => this.delete_file(path),
+                Err(e) => Err(e.into()),
+            }
+        };
+        match result {
+            Ok(()) => interp_ok(this.eval_windows("c", "TRUE")),
+            Err(e) => {
+                this.set_last_error(e)?;
+                interp_ok(this.eval_windows("c", "FALSE"))
+            }
+        }
+    }
+
+    /// Deletes the file at the canonical `path`. Files that are still open can only be deleted if
+    /// all their handles share deleting, and are then only deleted once their last handle is
+    /// closed. Until then, their deletion is pending, and they cannot be opened again.
+    fn delete_file(&mut self, path: PathBuf) -> Result<(), IoError> {
+        let this = self.eval_context_mut();
+        let open_files = &mut this.machine.open_files;
+        if open_files.delete_pending.contains(&path) {
+            return Err(IoError::WindowsError("ERROR_ACCESS_DENIED"));
+        }
+        let mut handles = open_files.files.values().filter(|(other, _)| *other == path).peekable();
+        if handles.peek().is_none() {
+            return std::fs::remove_file(&path).map_err(IoError::from);
+        }
+        if !handles.all(|&(_, share_delete)| share_delete) {
+            return Err(IoError::WindowsError("ERROR_SHARING_VIOLATION"));
+        }
+        open_files.delete_pending.insert(path);
+        Ok(())
+    }
+
+    fn MoveFileExW(
+        &mut self,
+        existing_file_name: &OpTy<'tcx>,
+        new_file_name: &OpTy<'tcx>,
+        flags: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let existing_file_name = this.read_pointer(existing_file_name)?;
+        let existing_virtual_name =
+            virtual_temp_file_name(&this.read_wide_str(existing_file_name)?);
+        let existing_file_name = this.read_path_from_wide_str(existing_file_name)?;
+        let new_file_name = this.read_pointer(new_file_name)?;
+        let flags = this.read_scalar(flags)?.to_u32()?;
+
+        let supported_flags =
+            MOVEFILE_REPLACE_EXISTING | MOVEFILE_COPY_ALLOWED | MOVEFILE_WRITE_THROUGH;
+        if flags & !supported_flags != 0 {
+            throw_unsup_format!("MoveFileExW: unsupported flags: {flags:#x}");
+        }
+        // Only `MOVEFILE_DELAY_UNTIL_REBOOT` allows deleting a file by moving it nowhere.
+        if this.ptr_is_null(new_file_name)? {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+        let new_virtual_name = virtual_temp_file_name(&this.read_wide_str(new_file_name)?);
+        let new_file_name = this.read_path_from_wide_str(new_file_name)?;
+
+        let result = if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            // Reject if isolation is enabled, unless both files are in the virtual temporary
+            // directory.
+            let (Some(from), Some(to)) = (existing_virtual_name, new_virtual_name) else {
+                this.reject_in_isolation("`MoveFileExW`", reject_with)?;
+                this.set_last_error(ErrorKind::PermissionDenied)?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            };
+            let replace_existing = flags & MOVEFILE_REPLACE_EXISTING != 0;
+            this.machine.temp_files.rename(&from, &to, replace_existing)
+        } else if let Ok(metadata) = std::fs::symlink_metadata(&new_file_name)
+            && existing_file_name != new_file_name
+        {
+            // Unlike `rename` on unix, this never replaces directories, and only replaces files
+            // if asked to.
+            if flags & MOVEFILE_REPLACE_EXISTING == 0 {
+                Err(IoError::WindowsError("ERROR_ALREADY_EXISTS"))
+            } else if metadata.is_dir() {
+                Err(IoError::WindowsError("ERROR_ACCESS_DENIED"))
+            } else {
+                move_file(&existing_file_name, &new_file_name, flags)
+            }
+        } else {
+            move_file(&existing_file_name, &new_file_name, flags)
+        };
+        match result {
+            Ok(()) => interp_ok(this.eval_windows("c", "TRUE")),
+            Err(e) => {
+                this.set_last_error(e)?;
+                interp_ok(this.eval_windows("c", "FALSE"))
+            }
+        }
+    }
+
+    fn ReplaceFileW(
+        &mut self,
+        replaced_file_name: &OpTy<'tcx>,
+        replacement_file_name: &OpTy<'tcx>,
+        backup_file_name: &OpTy<'tcx>,
+        replace_flags: &OpTy<'tcx>,
+        exclude: &OpTy<'tcx>,
+        reserved: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let replaced = this.read_path_from_wide_str(this.read_pointer(replaced_file_name)?)?;
+        let replacement =
+            this.read_path_from_wide_str(this.read_pointer(replacement_file_name)?)?;
+        let backup_file_name = this.read_pointer(backup_file_name)?;
+        let replace_flags = this.read_scalar(replace_flags)?.to_u32()?;
+        let exclude = this.read_pointer(exclude)?;
+        let reserved = this.read_pointer(reserved)?;
+
+        if replace_flags & !REPLACEFILE_SUPPORTED_FLAGS != 0 {
+            throw_unsup_format!("ReplaceFileW: unsupported flags: {replace_flags:#x}");
+        }
+        if !this.ptr_is_null(exclude)? || !this.ptr_is_null(reserved)? {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+        let backup = if this.ptr_is_null(backup_file_name)? {
+            None
+        } else {
+            Some(this.read_path_from_wide_str(backup_file_name)?)
+        };
+
+        // Reject if isolation is enabled.
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            this.reject_in_isolation("`ReplaceFileW`", reject_with)?;
+            this.set_last_error(ErrorKind::PermissionDenied)?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+
+        let result = if !replaced.is_file() || !replacement.is_file() {
+            Err(IoError::WindowsError("ERROR_FILE_NOT_FOUND"))
+        } else if let Some(backup) = backup {
+            // Move the replaced file out of the way, and move it back if the replacement fails.
+            std::fs::rename(&replaced, &backup)
+                .and_then(|()| {
+                    std::fs::rename(&replacement, &replaced).inspect_err(|_| {
+                        let _ = std::fs::rename(&backup, &replaced);
+                    })
+                })
+                .map_err(IoError::from)
+        } else {
+            // Renaming replaces the file atomically.
+            std::fs::rename(&replacement, &replaced).map_err(IoError::from)
+        };
+        match result {
+            Ok(()) => interp_ok(this.eval_windows("c", "TRUE")),
+            Err(e) => {
+                this.set_last_error(e)?;
+                interp_ok(this.eval_windows("c", "FALSE"))
+            }
+        }
+    }
+
+    /// Implements `NtReadFile` (if `is_write` is false) and `NtWriteFile`. Writes the `NTSTATUS`
+    /// to `dest`.
+    fn NtReadWriteFile(
+        &mut self,
+        args: &[OpTy<'tcx>; 9],
+        is_write: bool,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let function_name = if is_write { "NtWriteFile" } else { "NtReadFile" };
+        let [handle, event, apc_routine, _apc_context, io_status_block, buf, n, byte_offset, key] =
+            args;
+        let fd_num = this.read_file_handle(handle, function_name)?;
+        let event = this.read_target_usize(event)?;
+        let apc_routine = this.read_pointer(apc_routine)?;
+        let io_status_block =
+            this.deref_pointer_as(io_status_block, this.windows_ty_layout("IO_STATUS_BLOCK"))?;
+        let buf = this.read_pointer(buf)?;
+        let n = this.read_scalar(n)?.to_u32()?;
+        let byte_offset = this.read_pointer(byte_offset)?;
+        let key = this.read_pointer(key)?;
+
+        if event != 0 || !this.ptr_is_null(apc_routine)? {
+            throw_unsup_format!("`{function_name}`: asynchronous I/O is not supported");
+        }
+        if !this.ptr_is_null(byte_offset)? {
+            throw_unsup_format!(
+                "`{function_name}` `ByteOffset` parameter is non-null, which is unsupported"
+            );
+        }
+        if !this.ptr_is_null(key)? {
+            throw_unsup_format!(
+                "`{function_name}` `Key` parameter is non-null, which is unsupported"
+            );
+        }
+
+        let completion = IoCompletion::Nt { io_status_block, dest: dest.clone() };
+        this.read_write_handle(fd_num, buf, n, is_write, completion)
+    }
+
+    fn RtlNtStatusToDosError(&mut self, status: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let status = this.read_scalar(status)?.to_u32()?;
+        interp_ok(ntstatus_to_error(status, this))
+    }
+
+    /// Reads a `HANDLE` that must refer to a file (or to one of the standard streams), and returns
+    /// its file descriptor number. Aborts the program if the handle is invalid.
+    fn read_file_handle(
+        &mut self,
+        handle: &OpTy<'tcx>,
+        function_name: &str,
+    ) -> InterpResult<'tcx, i32> {
+        let this = self.eval_context_mut();
+        let handle = this.read_scalar(handle)?;
+        let raw = handle.to_target_isize(this)?;
+        if let Some(&(_, fd_num)) = STD_HANDLES.iter().find(|(std_handle, _)| *std_handle == raw) {
+            return interp_ok(fd_num);
+        }
+        match Handle::try_from_scalar(handle, this)? {
+            Ok(Handle::File(fd_num)) => interp_ok(fd_num),
+            _ => this.invalid_handle(function_name)?,
+        }
+    }
+
+    /// Returns how a `ReadFile` or `WriteFile` call on `fd_num` reports its result. This resets the
+    /// event of an overlapped operation, which gets signaled once the operation completes.
+    fn io_completion(
+        &mut self,
+        fd_num: i32,
+        overlapped: Pointer,
+        number_of_bytes: Pointer,
+        dest: &MPlaceTy<'tcx>,
+        function_name: &str,
+    ) -> InterpResult<'tcx, IoCompletion<'tcx>> {
+        let this = self.eval_context_mut();
+        if this.ptr_is_null(overlapped)? {
+            return interp_ok(IoCompletion::Win32 { number_of_bytes, dest: dest.clone() });
+        }
+        let port = this
+            .machine
+            .fds
+            .get(fd_num)
+            .and_then(|fd| this.machine.completion_ports.association(fd.get_id()));
+        let overlapped_place = this.ptr_to_mplace(overlapped, this.windows_ty_layout("OVERLAPPED"));
+        let (event, skip_port) = overlapped_event(&overlapped_place, this)?;
+        if !set_overlapped_event(event, /* signaled */ false, this)? {
+            this.invalid_handle(function_name)?;
+        }
+        let port = if skip_port { None } else { port };
+        let overlapped = exposed_addr(overlapped, this)?;
+        // The `OVERLAPPED` structure now belongs to this operation.
+        this.machine.completion_ports.overlapped_clocks.remove(&overlapped);
+        interp_ok(IoCompletion::Overlapped {
+            overlapped,
+            port,
+            sync: Some((number_of_bytes, dest.clone())),
+        })
+    }
+
+    /// Reads `len` bytes from the file `fd_num` into `buf`, or writes them from `buf` to the file,
+    /// and reports the result to `completion`. Pipes may block until the operation can complete.
+    fn read_write_handle(
+        &mut self,
+        fd_num: i32,
+        buf: Pointer,
+        len: u32,
+        is_write: bool,
+        completion: IoCompletion<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let Some(fd) = this.machine.fds.get(fd_num) else {
+            return completion.complete(
+                0,
+                Some(IoError::WindowsError("ERROR_INVALID_HANDLE")),
+                this,
+            );
+        };
+        if fd.downcast::<NamedPipe>().is_some() {
+            return if is_write {
+                pipe_write(&fd, buf, len, completion, this)
+            } else {
+                pipe_read(&fd, buf, len, completion, this)
+            };
+        }
+        if let IoCompletion::Overlapped { overlapped, .. } = &completion
+            && (fd.downcast::<FileHandle>().is_some() || fd.downcast::<VirtualFile>().is_some())
+        {
+            // Overlapped operations on files do not use the file pointer, but the offset in the
+            // `OVERLAPPED` structure.
+            let overlapped = this.ptr_to_mplace(
+                this.ptr_from_addr_cast(*overlapped)?,
+                this.windows_ty_layout("OVERLAPPED"),
+            );
+            let offset = this.project_field_named(&overlapped, "Anonymous")?;
+            let offset = this.project_field_named(&offset, "Anonymous")?;
+            let low = this.read_scalar(&this.project_field_named(&offset, "Offset")?)?.to_u32()?;
+            let high =
+                this.read_scalar(&this.project_field_named(&offset, "OffsetHigh")?)?.to_u32()?;
+            let offset = u64::from(high) << 32 | u64::from(low);
+            if let Err(e) = fd.seek(this.machine.communicate(), SeekFrom::Start(offset))? {
+                return completion.complete(0, Some(e.into()), this);
+            }
+        }
+        match this.transfer_file(&fd, buf, len, is_write)? {
+            // Reading nothing into a non-empty buffer means we are at the end of the file.
+            Ok(0) if !is_write && len > 0 => completion.complete_eof(this),
+            Ok(transferred) => completion.complete(transferred, None, this),
+            Err(e) => completion.complete(0, Some(e), this),
+        }
+    }
+
+    /// Reads `len` bytes from `fd` into `buf`, or writes them from `buf` to `fd`, without blocking.
+    /// Returns the number of bytes that were transferred.
+    fn transfer_file(
+        &mut self,
+        fd: &FileDescriptionRef,
+        buf: Pointer,
+        len: u32,
+        is_write: bool,
+    ) -> InterpResult<'tcx, Result<u32, IoError>> {
+        let this = self.eval_context_mut();
+        let communicate = this.machine.communicate();
+        let len = usize::try_from(len).unwrap();
+        // File descriptions report their result like `read` and `write` on unix: the number of
+        // bytes transferred, or -1. We let them write that into a temporary place.
+        let result = this.allocate(this.machine.layouts.isize, MiriMemoryKind::Machine.into())?;
+        if is_write {
+            fd.write(fd, communicate, buf, len, &result, this)?;
+        } else {
+            fd.read(fd, communicate, buf, len, &result, this)?;
+        }
+        let transferred = this.read_scalar(&result)?.to_target_isize(this)?;
+        this.deallocate_ptr(result.ptr(), None, MiriMemoryKind::Machine.into())?;
+        // At most `len` bytes were transferred, so this fits into a `u32` unless it is -1.
+        match u32::try_from(transferred) {
+            Ok(transferred) => interp_ok(Ok(transferred)),
+            Err(_) => interp_ok(Err(IoError::Raw(this.get_last_error()?))),
+        }
+    }
+}
+
+/// The `HANDLE` returned by `CreateFileW` on failure.
+pub(super) fn invalid_handle_value(cx: &impl rustc_abi::HasDataLayout) -> Scalar {
+    Scalar::from_target_isize(-1, cx)
+}
+
+/// Returns the `FILE_ATTRIBUTE_*` flags of a file with the given host metadata. The metadata must
+/// not follow symbolic links; `link_target_is_dir` tells whether a link points to a directory.
+pub(super) fn file_attributes<'tcx>(
+    metadata: &Metadata,
+    link_target_is_dir: bool,
+    ecx: &MiriInterpCx<'tcx>,
+) -> u32 {
+    let mut attributes = 0;
+    let file_type = metadata.file_type();
+    if file_type.is_dir() || (file_type.is_symlink() && link_target_is_dir) {
+        attributes |= ecx.eval_windows_u32("c", "FILE_ATTRIBUTE_DIRECTORY");
+    }
+    if file_type.is_symlink() {
+        attributes |= ecx.eval_windows_u32("c", "FILE_ATTRIBUTE_REPARSE_POINT");
+    }
+    if metadata.permissions().readonly() {
+        attributes |= ecx.eval_windows_u32("c", "FILE_ATTRIBUTE_READONLY");
+    }
+    // `FILE_ATTRIBUTE_NORMAL` is only valid on its own.
+    if attributes == 0 { ecx.eval_windows_u32("c", "FILE_ATTRIBUTE_NORMAL") } else { attributes }
+}
+
+/// Converts a host time into a `FILETIME`, the number of 100ns intervals since 1601. Times that
+/// the host does not provide are reported as 0.
+pub(super) fn filetime<'tcx>(time: io::Result<SystemTime>, ecx: &MiriInterpCx<'tcx>) -> u64 {
+    let Ok(time) = time else {
+        return 0;
+    };
+    let nanos_per_interval = ecx.eval_windows_u64("time", "NANOS_PER_SEC")
+        / ecx.eval_windows_u64("time", "INTERVALS_PER_SEC");
+    let intervals_to_unix_epoch = ecx.eval_windows_u64("time", "INTERVALS_TO_UNIX_EPOCH");
+    let intervals = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(after) => i128::try_from(after.as_nanos() / u128::from(nanos_per_interval)).unwrap(),
+        Err(before) =>
+            -i128::try_from(before.duration().as_nanos() / u128::from(nanos_per_interval)).unwrap(),
+    };
+    u64::try_from(intervals.saturating_add(intervals_to_unix_epoch.into())).unwrap_or(0)
+}
+
+/// Writes a `FILETIME` (see `filetime`) into the place, which must have the `FILETIME` layout.
+pub(super) fn write_filetime<'tcx>(
+    filetime: u64,
+    place: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    ecx.write_int_fields_named(
+        &[
+            ("dwLowDateTime", (filetime & 0xFFFF_FFFF).into()),
+            ("dwHighDateTime", (filetime >> 32).into()),
+        ],
+        place,
+    )
+}
+
+/// Returns the metadata of the handle `fd_num`. Handles that do not refer to host files, like
+/// pipes and virtual temporary files, report synthetic metadata.
+fn handle_info<'tcx>(
+    fd_num: i32,
+    ecx: &MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, Result<HandleInfo, IoError>> {
+    let fd = ecx.machine.fds.get(fd_num).unwrap();
+    let Some(FileHandle { file, .. }) = fd.downcast::<FileHandle>() else {
+        return interp_ok(Ok(HandleInfo {
+            attributes: ecx.eval_windows_u32("c", "FILE_ATTRIBUTE_NORMAL"),
+            creation_time: 0,
+            last_access_time: 0,
+            last_write_time: 0,
+            size: fd.downcast::<VirtualFile>().map_or(0, VirtualFile::size),
+            volume_serial_number: 0,
+            number_of_links: 1,
+            file_index: 0,
+            is_dir: false,
+        }));
+    };
+    let metadata = match file.metadata() {
+        Ok(metadata) => metadata,
+        Err(e) => return interp_ok(Err(e.into())),
+    };
+    // On unix hosts, the device and inode numbers identify the file, like the volume serial
+    // number and the file index do on Windows.
+    #[cfg(unix)]
+    let (volume_serial_number, file_index, number_of_links) = {
+        use std::os::unix::fs::MetadataExt;
+        #[expect(clippy::cast_possible_truncation)] // only used to compare files
+        let volume_serial_number = metadata.dev() as u32;
+        (volume_serial_number, metadata.ino(), u32::try_from(metadata.nlink()).unwrap_or(u32::MAX))
+    };
+    #[cfg(not(unix))]
+    let (volume_serial_number, file_index, number_of_links) = (0, 0, 1);
+    interp_ok(Ok(HandleInfo {
+        attributes: file_attributes(&metadata, /* link_target_is_dir */ false, ecx),
+        creation_time: filetime(metadata.created(), ecx),
+        last_access_time: filetime(metadata.accessed(), ecx),
+        last_write_time: filetime(metadata.modified(), ecx),
+        // Windows reports a size of 0 for directories.
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        volume_serial_number,
+        number_of_links,
+        file_index,
+        is_dir: metadata.is_dir(),
+    }))
+}
+
+/// Moves the file `from` to `to`, replacing it if it exists. With `MOVEFILE_COPY_ALLOWED`, this
+/// falls back to copying and deleting the file if it cannot be renamed to another volume.
+fn move_file(from: &Path, to: &Path, flags: u32) -> Result<(), IoError> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices && flags & MOVEFILE_COPY_ALLOWED != 0 => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)?;
+            Ok(())
+        }
+        result => Ok(result?),
+    }
+}
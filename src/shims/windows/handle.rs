@@ -16,6 +16,8 @@ pub enum Handle {
     Null,
     Pseudo(PseudoHandle),
     Thread(ThreadId),
+    /// A file, stored as its number in the `FdTable`.
+    File(i32),
 }
 
 impl PseudoHandle {
@@ -47,12 +49,14 @@ impl Handle {
     const NULL_DISCRIMINANT: u32 = 0;
     const PSEUDO_DISCRIMINANT: u32 = 1;
     const THREAD_DISCRIMINANT: u32 = 2;
+    const FILE_DISCRIMINANT: u32 = 3;
 
     fn discriminant(self) -> u32 {
         match self {
             Self::Null => Self::NULL_DISCRIMINANT,
             Self::Pseudo(_) => Self::PSEUDO_DISCRIMINANT,
             Self::Thread(_) => Self::THREAD_DISCRIMINANT,
+            Self::File(_) => Self::FILE_DISCRIMINANT,
         }
     }
 
@@ -61,6 +65,8 @@ impl Handle {
             Self::Null => 0,
             Self::Pseudo(pseudo_handle) => pseudo_handle.value(),
             Self::Thread(thread) => thread.to_u32(),
+            // File descriptor numbers are never negative.
+            Self::File(fd_num) => u32::try_from(fd_num).unwrap(),
         }
     }
 
@@ -105,6 +111,7 @@ impl Handle {
             Self::NULL_DISCRIMINANT if data == 0 => Some(Self::Null),
             Self::PSEUDO_DISCRIMINANT => Some(Self::Pseudo(PseudoHandle::from_value(data)?)),
             Self::THREAD_DISCRIMINANT => Some(Self::Thread(ThreadId::new_unchecked(data))),
+            Self::FILE_DISCRIMINANT => Some(Self::File(i32::try_from(data).ok()?)),
             _ => None,
         }
     }
@@ -138,7 +145,8 @@ impl Handle {
     /// Convert a scalar into a structured `Handle`.
     /// Structurally invalid handles return [`HandleError::InvalidHandle`].
     /// If the handle is structurally valid but semantically invalid, e.g. a for non-existent thread
-    /// ID, returns [`HandleError::ThreadNotFound`].
+    /// ID, returns [`HandleError::ThreadNotFound`]. Handles of files that are not open are
+    /// [`HandleError::InvalidHandle`].
     pub fn try_from_scalar<'tcx>(
         handle: Scalar,
         cx: &MiriInterpCx<'tcx>,
@@ -161,6 +169,8 @@ impl Handle {
                     Err(e) => interp_ok(Err(HandleError::ThreadNotFound(e))),
                 }
             }
+            Some(Self::File(fd_num)) if !cx.machine.fds.is_fd_num(fd_num) =>
+                interp_ok(Err(HandleError::InvalidHandle)),
             Some(handle) => interp_ok(Ok(handle)),
             None => interp_ok(Err(HandleError::InvalidHandle)),
         }
@@ -186,6 +196,17 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.detach_thread(thread, /*allow_terminated_joined*/ true)?;
                 this.eval_windows("c", "TRUE")
             }
+            Ok(Handle::File(fd_num)) => {
+                let fd = this.machine.fds.remove(fd_num).unwrap();
+                let result = fd.close(this.machine.communicate(), this)?;
+                match result {
+                    Ok(()) => this.eval_windows("c", "TRUE"),
+                    Err(e) => {
+                        this.set_last_error(e)?;
+                        this.eval_windows("c", "FALSE")
+                    }
+                }
+            }
             _ => this.invalid_handle("CloseHandle")?,
         };
 
@@ -0,0 +1,534 @@
+//! Overlapped I/O and I/O completion ports on Windows. Overlapped reads and writes report their
+//! result in their `OVERLAPPED` structure, where `GetOverlappedResult` picks it up, and signal its
+//! event. On a handle that is associated with a port, they also queue a completion packet there
+//! when they finish, and `GetQueuedCompletionStatus` waits for these packets. Operations on files
+//! always finish right away; only reads from named pipes and `ConnectNamedPipe` can stay pending.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+
+use crate::concurrency::VClock;
+use crate::shims::files::{FdId, FileDescription, FileDescriptionRef, WeakFileDescriptionRef};
+use crate::shims::unix::{deadline_passed, deadline_timeout};
+use crate::shims::windows::fs::{
+    EvalContextExt as _, IoCompletion, STATUS_PENDING, ntstatus_to_error,
+};
+use crate::shims::windows::handle::{EvalContextExt as _, Handle};
+use crate::shims::windows::pipe::cancel_pending_io;
+use crate::shims::windows::wait::read_wait_deadline;
+use crate::*;
+
+/// The associations between file descriptions and completion ports.
+#[derive(Debug)]
+pub struct CompletionPortTable {
+    /// The port and the completion key of every file description that is associated with a port.
+    associations: BTreeMap<FdId, (WeakFileDescriptionRef, u64)>,
+    /// The release clock of the last completed operation of every `OVERLAPPED` structure, by its
+    /// address. `GetOverlappedResult` acquires it, so it synchronizes with the thread that
+    /// completed that operation.
+    pub overlapped_clocks: BTreeMap<u64, VClock>,
+}
+
+impl CompletionPortTable {
+    pub(crate) fn new() -> Self {
+        CompletionPortTable { associations: BTreeMap::new(), overlapped_clocks: BTreeMap::new() }
+    }
+
+    /// Returns the port and the completion key that the file description `id` is associated with.
+    pub fn association(&self, id: FdId) -> Option<(WeakFileDescriptionRef, u64)> {
+        self.associations.get(&id).cloned()
+    }
+
+    pub fn remove(&mut self, id: FdId) {
+        self.associations.remove(&id);
+    }
+}
+
+/// An I/O completion port, created by `CreateIoCompletionPort`.
+#[derive(Debug, Default)]
+pub struct CompletionPort {
+    packets: RefCell<VecDeque<CompletionPacket>>,
+    /// Synchronizes the threads that queue packets with the threads that dequeue them.
+    clock: RefCell<VClock>,
+    /// Threads blocked in `GetQueuedCompletionStatus(Ex)`.
+    blocked_tid: RefCell<Vec<ThreadId>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CompletionPacket {
+    pub bytes: u32,
+    pub key: u64,
+    /// The address of the `OVERLAPPED` structure of the operation, or 0.
+    pub overlapped: u64,
+    /// The `NTSTATUS` of the operation.
+    pub status: u32,
+}
+
+/// An overlapped operation that could not complete right away. This only remembers addresses, so
+/// it does not hold any provenance.
+#[derive(Debug)]
+pub struct PendingCompletion {
+    /// The address of the `OVERLAPPED` structure, whose provenance got exposed.
+    pub overlapped: u64,
+    /// The completion port and key of the handle, if it is associated with a port.
+    pub port: Option<(WeakFileDescriptionRef, u64)>,
+}
+
+impl PendingCompletion {
+    pub fn into_completion<'tcx>(self) -> IoCompletion<'tcx> {
+        let PendingCompletion { overlapped, port } = self;
+        IoCompletion::Overlapped { overlapped, port, sync: None }
+    }
+}
+
+impl FileDescription for CompletionPort {
+    fn name(&self) -> &'static str {
+        "I/O completion port"
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        // Waiting threads notice that the port is gone.
+        for thread_id in self.blocked_tid.take() {
+            ecx.unblock_thread(thread_id, BlockReason::CompletionPort)?;
+        }
+        interp_ok(Ok(()))
+    }
+}
+
+/// Queues a completion packet on `port`. Packets for ports that got closed are lost.
+pub fn post_completion<'tcx>(
+    port: &WeakFileDescriptionRef,
+    packet: CompletionPacket,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let Some(port) = port.upgrade() else {
+        return interp_ok(());
+    };
+    let port = port.downcast::<CompletionPort>().unwrap();
+    // Remember this clock so the thread that dequeues the packet can synchronize with us.
+    ecx.release_clock(|clock| {
+        port.clock.borrow_mut().join(clock);
+    });
+    port.packets.borrow_mut().push_back(packet);
+    // We need to take out the blocked thread ids first, because threads that do not get a packet
+    // block again and end up re-adding themselves to the list.
+    let waiting_threads = port.blocked_tid.take();
+    for thread_id in waiting_threads {
+        ecx.unblock_thread(thread_id, BlockReason::CompletionPort)?;
+    }
+    interp_ok(())
+}
+
+/// Returns the address of `ptr`. We only remember the address, so we expose its provenance like a
+/// pointer-to-integer cast does. Turning the address back into a pointer is then like an
+/// integer-to-pointer cast.
+pub fn exposed_addr<'tcx>(ptr: Pointer, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx, u64> {
+    if let Some(Provenance::Concrete { alloc_id, tag }) = ptr.provenance {
+        ecx.expose_ptr(alloc_id, tag)?;
+    }
+    interp_ok(ptr.addr().bytes())
+}
+
+/// Where `GetQueuedCompletionStatus` and `GetQueuedCompletionStatusEx` store the packets they
+/// dequeue.
+#[derive(Debug)]
+enum DequeueTarget {
+    Single {
+        number_of_bytes: Pointer,
+        key: Pointer,
+        overlapped: Pointer,
+    },
+    /// An array of `count` `OVERLAPPED_ENTRY`s, and where to store how many were filled.
+    Ex {
+        entries: Pointer,
+        count: u32,
+        num_removed: Pointer,
+    },
+}
+
+impl VisitProvenance for DequeueTarget {
+    fn visit_provenance(&self, visit: &mut VisitWith<'_>) {
+        match self {
+            DequeueTarget::Single { number_of_bytes, key, overlapped } => {
+                number_of_bytes.visit_provenance(visit);
+                key.visit_provenance(visit);
+                overlapped.visit_provenance(visit);
+            }
+            DequeueTarget::Ex { entries, count: _, num_removed } => {
+                entries.visit_provenance(visit);
+                num_removed.visit_provenance(visit);
+            }
+        }
+    }
+}
+
+impl DequeueTarget {
+    /// Reports that no packet was dequeued because of `error`.
+    fn fail<'tcx>(
+        &self,
+        error: &'static str,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        if let DequeueTarget::Single { overlapped, .. } = self {
+            let overlapped = ecx.ptr_to_mplace(*overlapped, ecx.machine.layouts.mut_raw_ptr);
+            ecx.write_pointer(Pointer::null(), &overlapped)?;
+        }
+        ecx.set_last_error(IoError::WindowsError(error))?;
+        ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest)
+    }
+
+    /// Stores the dequeued packets and returns the result of the call.
+    fn store<'tcx>(
+        &self,
+        packets: &[CompletionPacket],
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        match *self {
+            DequeueTarget::Single { number_of_bytes, key, overlapped } => {
+                let [packet] = packets else { unreachable!() };
+                let number_of_bytes = ecx.ptr_to_mplace(number_of_bytes, ecx.machine.layouts.u32);
+                ecx.write_scalar(Scalar::from_u32(packet.bytes), &number_of_bytes)?;
+                let key = ecx.ptr_to_mplace(key, ecx.machine.layouts.usize);
+                ecx.write_scalar(Scalar::from_target_usize(packet.key, ecx), &key)?;
+                let overlapped = ecx.ptr_to_mplace(overlapped, ecx.machine.layouts.mut_raw_ptr);
+                ecx.write_pointer(ecx.ptr_from_addr_cast(packet.overlapped)?, &overlapped)?;
+                // Failed operations are reported through the last error, but the packet still
+                // gets dequeued.
+                if i32::from_ne_bytes(packet.status.to_ne_bytes()) < 0 {
+                    ecx.set_last_error(ntstatus_to_error(packet.status, ecx))?;
+                    return ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest);
+                }
+            }
+            DequeueTarget::Ex { entries, count: _, num_removed } => {
+                // `OVERLAPPED_ENTRY` is not used by the standard library, so we compute its layout
+                // ourselves: the completion key, the `OVERLAPPED` pointer, the status and the
+                // number of transferred bytes, each in a pointer-sized slot.
+                let ptr_size = ecx.pointer_size();
+                for (i, packet) in (0u64..).zip(packets) {
+                    let entry = entries.wrapping_offset(ptr_size * i.strict_mul(4), ecx);
+                    let [key, overlapped, status, bytes] =
+                        [0, 1, 2, 3].map(|n: u64| entry.wrapping_offset(ptr_size * n, ecx));
+                    let key = ecx.ptr_to_mplace(key, ecx.machine.layouts.usize);
+                    ecx.write_scalar(Scalar::from_target_usize(packet.key, ecx), &key)?;
+                    let overlapped = ecx.ptr_to_mplace(overlapped, ecx.machine.layouts.mut_raw_ptr);
+                    ecx.write_pointer(ecx.ptr_from_addr_cast(packet.overlapped)?, &overlapped)?;
+                    let status = ecx.ptr_to_mplace(status, ecx.machine.layouts.usize);
+                    ecx.write_scalar(
+                        Scalar::from_target_usize(packet.status.into(), ecx),
+                        &status,
+                    )?;
+                    let bytes = ecx.ptr_to_mplace(bytes, ecx.machine.layouts.u32);
+                    ecx.write_scalar(Scalar::from_u32(packet.bytes), &bytes)?;
+                }
+                let num_removed = ecx.ptr_to_mplace(num_removed, ecx.machine.layouts.u32);
+                ecx.write_scalar(
+                    Scalar::from_u32(u32::try_from(packets.len()).unwrap()),
+                    &num_removed,
+                )?;
+            }
+        }
+        ecx.write_scalar(ecx.eval_windows("c", "TRUE"), dest)
+    }
+}
+
+/// Dequeues packets from `port` into `target`. Blocks until a packet arrives or the `deadline`
+/// (computed by `deadline_after`) has passed.
+fn dequeue_completion<'tcx>(
+    port: WeakFileDescriptionRef,
+    target: DequeueTarget,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let Some(port_ref) = port.upgrade() else {
+        // The port got closed while we were waiting.
+        return target.fail("ERROR_ABANDONED_WAIT_0", dest, ecx);
+    };
+    let completion_port = port_ref.downcast::<CompletionPort>().unwrap();
+    let max_packets = match target {
+        DequeueTarget::Single { .. } => 1,
+        DequeueTarget::Ex { count, .. } => usize::try_from(count).unwrap(),
+    };
+    let packets: Vec<_> = {
+        let mut queue = completion_port.packets.borrow_mut();
+        let len = queue.len().min(max_packets);
+        queue.drain(..len).collect()
+    };
+    if !packets.is_empty() {
+        ecx.acquire_clock(&completion_port.clock.borrow());
+        return target.store(&packets, dest, ecx);
+    }
+    if deadline_passed(deadline, ecx) {
+        return target.fail("WAIT_TIMEOUT", dest, ecx);
+    }
+
+    completion_port.blocked_tid.borrow_mut().push(ecx.active_thread());
+    let dest = dest.clone();
+    ecx.block_thread(
+        BlockReason::CompletionPort,
+        deadline_timeout(deadline),
+        callback!(
+            @capture<'tcx> {
+                port: WeakFileDescriptionRef,
+                target: DequeueTarget,
+                deadline: Option<u64>,
+                dest: MPlaceTy<'tcx>,
+            }
+            @unblock = |this| {
+                // When we get unblocked, try again: another thread may have taken the packet.
+                dequeue_completion(port, target, deadline, &dest, this)
+            }
+            @timeout = |this| {
+                if let Some(port) = port.upgrade() {
+                    let active_thread = this.active_thread();
+                    port.downcast::<CompletionPort>()
+                        .unwrap()
+                        .blocked_tid
+                        .borrow_mut()
+                        .retain(|&id| id != active_thread);
+                }
+                target.fail("WAIT_TIMEOUT", &dest, this)
+            }
+        ),
+    );
+    interp_ok(())
+}
+
+/// Reports the result of the overlapped operation with the given `OVERLAPPED` structure. If it is
+/// still pending and `wait` is set, this blocks until it completes.
+fn get_overlapped_result<'tcx>(
+    overlapped: Pointer,
+    number_of_bytes: Pointer,
+    wait: bool,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let overlapped_place = ecx.ptr_to_mplace(overlapped, ecx.windows_ty_layout("OVERLAPPED"));
+    let status = ecx.read_target_usize(&ecx.project_field_named(&overlapped_place, "Internal")?)?;
+    if status == u64::from(STATUS_PENDING) {
+        if !wait {
+            ecx.set_last_error(IoError::WindowsError("ERROR_IO_INCOMPLETE"))?;
+            return ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest);
+        }
+        // Operations that complete notify all threads that wait on handles.
+        ecx.machine.blocked_object_wait_tid.push(ecx.active_thread());
+        let dest = dest.clone();
+        ecx.block_thread(
+            BlockReason::WaitForObjects,
+            None,
+            callback!(
+                @capture<'tcx> {
+                    overlapped: Pointer,
+                    number_of_bytes: Pointer,
+                    dest: MPlaceTy<'tcx>,
+                }
+                @unblock = |this| {
+                    get_overlapped_result(overlapped, number_of_bytes, /* wait */ true, &dest, this)
+                }
+            ),
+        );
+        return interp_ok(());
+    }
+
+    if let Some(clock) =
+        ecx.machine.completion_ports.overlapped_clocks.get(&overlapped.addr().bytes())
+    {
+        ecx.acquire_clock(clock);
+    }
+    let transferred =
+        ecx.read_target_usize(&ecx.project_field_named(&overlapped_place, "InternalHigh")?)?;
+    let number_of_bytes = ecx.ptr_to_mplace(number_of_bytes, ecx.machine.layouts.u32);
+    ecx.write_scalar(Scalar::from_u32(u32::try_from(transferred).unwrap()), &number_of_bytes)?;
+    if status != 0 {
+        let status = u32::try_from(status).unwrap();
+        ecx.set_last_error(ntstatus_to_error(status, ecx))?;
+        return ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest);
+    }
+    ecx.write_scalar(ecx.eval_windows("c", "TRUE"), dest)
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+
+#[allow(non_snake_case)]
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn CreateIoCompletionPort(
+        &mut self,
+        file_handle: &OpTy<'tcx>,
+        existing_completion_port: &OpTy<'tcx>,
+        completion_key: &OpTy<'tcx>,
+        _number_of_concurrent_threads: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let raw_file_handle = this.read_scalar(file_handle)?.to_target_isize(this)?;
+        let existing_completion_port = this.read_scalar(existing_completion_port)?;
+        let completion_key = this.read_target_usize(completion_key)?;
+        // We do not limit how many threads process packets concurrently.
+
+        let port_handle = if existing_completion_port.to_target_usize(this)? == 0 {
+            let fd_num = this.machine.fds.insert_new(CompletionPort::default());
+            Handle::File(fd_num).to_scalar(this)
+        } else {
+            this.read_port_handle(existing_completion_port, "CreateIoCompletionPort")?;
+            existing_completion_port
+        };
+        // `INVALID_HANDLE_VALUE` only creates a port.
+        if raw_file_handle == -1 {
+            return interp_ok(port_handle);
+        }
+
+        let port = this.read_port_handle(port_handle, "CreateIoCompletionPort")?;
+        let fd_num = this.read_file_handle(file_handle, "CreateIoCompletionPort")?;
+        let Some(fd) = this.machine.fds.get(fd_num) else {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_HANDLE"))?;
+            return interp_ok(Scalar::from_target_usize(0, this));
+        };
+        // A handle can only be associated with one port.
+        let associations = &mut this.machine.completion_ports.associations;
+        if associations.contains_key(&fd.get_id()) {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return interp_ok(Scalar::from_target_usize(0, this));
+        }
+        associations.insert(fd.get_id(), (port.downgrade(), completion_key));
+        interp_ok(port_handle)
+    }
+
+    fn GetQueuedCompletionStatus(
+        &mut self,
+        completion_port: &OpTy<'tcx>,
+        number_of_bytes: &OpTy<'tcx>,
+        completion_key: &OpTy<'tcx>,
+        overlapped: &OpTy<'tcx>,
+        milliseconds: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let port = this.read_scalar(completion_port)?;
+        let port = this.read_port_handle(port, "GetQueuedCompletionStatus")?;
+        let target = DequeueTarget::Single {
+            number_of_bytes: this.read_pointer(number_of_bytes)?,
+            key: this.read_pointer(completion_key)?,
+            overlapped: this.read_pointer(overlapped)?,
+        };
+        let deadline = read_wait_deadline(milliseconds, this)?;
+        dequeue_completion(port.downgrade(), target, deadline, dest, this)
+    }
+
+    fn GetQueuedCompletionStatusEx(
+        &mut self,
+        completion_port: &OpTy<'tcx>,
+        completion_port_entries: &OpTy<'tcx>,
+        count: &OpTy<'tcx>,
+        num_entries_removed: &OpTy<'tcx>,
+        milliseconds: &OpTy<'tcx>,
+        alertable: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let port = this.read_scalar(completion_port)?;
+        let port = this.read_port_handle(port, "GetQueuedCompletionStatusEx")?;
+        let entries = this.read_pointer(completion_port_entries)?;
+        let count = this.read_scalar(count)?.to_u32()?;
+        let num_removed = this.read_pointer(num_entries_removed)?;
+        let deadline = read_wait_deadline(milliseconds, this)?;
+        if this.read_scalar(alertable)?.to_i32()? != 0 {
+            throw_unsup_format!("GetQueuedCompletionStatusEx: alertable waits are not supported");
+        }
+        if count == 0 {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return this.write_scalar(this.eval_windows("c", "FALSE"), dest);
+        }
+        // Make sure the whole array is valid, even if we do not fill all of it.
+        let size = this.pointer_size() * u64::from(count).strict_mul(4);
+        this.check_ptr_access(entries, size, CheckInAllocMsg::InboundsTest)?;
+
+        let target = DequeueTarget::Ex { entries, count, num_removed };
+        dequeue_completion(port.downgrade(), target, deadline, dest, this)
+    }
+
+    fn PostQueuedCompletionStatus(
+        &mut self,
+        completion_port: &OpTy<'tcx>,
+        number_of_bytes: &OpTy<'tcx>,
+        completion_key: &OpTy<'tcx>,
+        overlapped: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let port = this.read_scalar(completion_port)?;
+        let port = this.read_port_handle(port, "PostQueuedCompletionStatus")?;
+        let bytes = this.read_scalar(number_of_bytes)?.to_u32()?;
+        let key = this.read_target_usize(completion_key)?;
+        let overlapped = this.read_pointer(overlapped)?;
+
+        let overlapped = exposed_addr(overlapped, this)?;
+        let packet = CompletionPacket { bytes, key, overlapped, status: 0 };
+        post_completion(&port.downgrade(), packet, this)?;
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn GetOverlappedResult(
+        &mut self,
+        file: &OpTy<'tcx>,
+        overlapped: &OpTy<'tcx>,
+        number_of_bytes_transferred: &OpTy<'tcx>,
+        wait: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        // The result is stored in the `OVERLAPPED` structure, so we do not need the file.
+        this.read_file_handle(file, "GetOverlappedResult")?;
+        let overlapped = this.read_pointer(overlapped)?;
+        let number_of_bytes = this.read_pointer(number_of_bytes_transferred)?;
+        let wait = this.read_scalar(wait)?.to_i32()? != 0;
+
+        get_overlapped_result(overlapped, number_of_bytes, wait, dest, this)
+    }
+
+    fn CancelIoEx(
+        &mut self,
+        file: &OpTy<'tcx>,
+        overlapped: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(file, "CancelIoEx")?;
+        let overlapped = this.read_pointer(overlapped)?;
+
+        let overlapped =
+            if this.ptr_is_null(overlapped)? { None } else { Some(overlapped.addr().bytes()) };
+        let cancelled = match this.machine.fds.get(fd_num) {
+            Some(fd) => cancel_pending_io(&fd, overlapped),
+            None => Vec::new(),
+        };
+        if cancelled.is_empty() {
+            this.set_last_error(IoError::WindowsError("ERROR_NOT_FOUND"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+        for pending in cancelled {
+            let error = IoError::WindowsError("ERROR_OPERATION_ABORTED");
+            pending.into_completion().complete(0, Some(error), this)?;
+        }
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    /// Reads a `HANDLE` that must refer to a completion port. Aborts the program if it does not.
+    fn read_port_handle(
+        &mut self,
+        handle: Scalar,
+        function_name: &str,
+    ) -> InterpResult<'tcx, FileDescriptionRef> {
+        let this = self.eval_context_mut();
+        if let Ok(Handle::File(fd_num)) = Handle::try_from_scalar(handle, this)?
+            && let Some(fd) = this.machine.fds.get(fd_num)
+            && fd.downcast::<CompletionPort>().is_some()
+        {
+            return interp_ok(fd);
+        }
+        this.invalid_handle(function_name)?
+    }
+}
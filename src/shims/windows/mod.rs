@@ -1,12 +1,26 @@
 pub mod foreign_items;
 
+mod console;
+mod dir;
 mod env;
+mod fs;
 mod handle;
+mod iocp;
+mod pipe;
 mod sync;
+mod temp;
 mod thread;
+mod wait;
 
 // All the Windows-specific extension traits
+pub use self::console::EvalContextExt as _;
+pub use self::dir::EvalContextExt as _;
 pub use self::env::{EvalContextExt as _, WindowsEnvVars};
+pub use self::fs::{EvalContextExt as _, OpenFileTable};
 pub use self::handle::EvalContextExt as _;
+pub use self::iocp::{CompletionPortTable, EvalContextExt as _};
+pub use self::pipe::{EvalContextExt as _, NamedPipeTable};
 pub use self::sync::EvalContextExt as _;
+pub use self::temp::{EvalContextExt as _, VirtualTempDir};
 pub use self::thread::EvalContextExt as _;
+pub use self::wait::EvalContextExt as _;
@@ -0,0 +1,817 @@
+//! Named pipes on Windows. These do not correspond to anything on the host system and are entirely
+//! implemented inside Miri, so clients can only connect to pipes that were created by the
+//! interpreted program itself. Both ends of a pipe are stored in the `FdTable`, like files.
+//! Overlapped reads and `ConnectNamedPipe` calls that cannot complete right away stay pending on
+//! the pipe end until they can, and then report their result to a completion port.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+
+use rustc_abi::Size;
+use rustc_data_structures::fx::FxHashMap;
+
+use crate::concurrency::VClock;
+use crate::shims::files::{FileDescription, FileDescriptionRef, WeakFileDescriptionRef};
+use crate::shims::unix::{deadline_after, deadline_passed, deadline_timeout};
+use crate::shims::windows::fs::{EvalContextExt as _, IoCompletion, invalid_handle_value};
+use crate::shims::windows::handle::Handle;
+use crate::shims::windows::iocp::{PendingCompletion, exposed_addr};
+use crate::*;
+
+/// The prefix of all pipe names, in lowercase.
+const PIPE_PREFIX: &str = r"\\.\pipe\";
+/// `PIPE_UNLIMITED_INSTANCES`, the largest number of instances a pipe can have.
+const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+/// `NMPWAIT_USE_DEFAULT_WAIT`, which makes `WaitNamedPipeW` use the timeout of the pipe.
+const NMPWAIT_USE_DEFAULT_WAIT: u32 = 0;
+/// `NMPWAIT_WAIT_FOREVER`, which makes `WaitNamedPipeW` wait without a timeout.
+const NMPWAIT_WAIT_FOREVER: u32 = u32::MAX;
+/// The timeout of a pipe that is created with a default timeout of 0.
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Returns the name of the pipe that `name` refers to, if it is a pipe name. Pipe names are case
+/// insensitive, so we use the lowercase name.
+pub fn pipe_name(name: &[u16]) -> Option<String> {
+    let name = String::from_utf16_lossy(name).to_lowercase();
+    (name.len() > PIPE_PREFIX.len() && name.starts_with(PIPE_PREFIX)).then_some(name)
+}
+
+/// The table of all named pipes that have at least one server end.
+#[derive(Debug)]
+pub struct NamedPipeTable {
+    pipes: FxHashMap<String, PipeInfo>,
+}
+
+#[derive(Debug)]
+struct PipeInfo {
+    /// Whether data is written as messages (`PIPE_TYPE_MESSAGE`). All instances share this.
+    message_mode: bool,
+    max_instances: u32,
+    /// The timeout for `WaitNamedPipeW` with `NMPWAIT_USE_DEFAULT_WAIT`.
+    default_timeout: Duration,
+    /// The server ends of this pipe, connected or not. Entries become stale when the server end
+    /// gets closed.
+    instances: Vec<WeakFileDescriptionRef>,
+    /// Threads blocked in `WaitNamedPipeW` until an instance is listening.
+    waiting_clients: Vec<ThreadId>,
+}
+
+impl NamedPipeTable {
+    pub(crate) fn new() -> Self {
+        NamedPipeTable { pipes: FxHashMap::default() }
+    }
+
+    /// Returns the pipe with the given name, if it has any server ends left.
+    fn get(&mut self, name: &str) -> Option<&mut PipeInfo> {
+        let info = self.pipes.get_mut(name)?;
+        info.instances.retain(|instance| instance.upgrade().is_some());
+        if info.instances.is_empty() {
+            self.pipes.remove(name);
+            return None;
+        }
+        self.pipes.get_mut(name)
+    }
+
+    /// Takes the threads that wait for an instance of the pipe to start listening.
+    fn take_waiting_clients(&mut self, name: &str) -> Vec<ThreadId> {
+        self.pipes
+            .get_mut(name)
+            .map(|info| std::mem::take(&mut info.waiting_clients))
+            .unwrap_or_default()
+    }
+}
+
+impl PipeInfo {
+    /// Returns the first server end that waits for a client to connect.
+    fn listening_instance(&self) -> Option<FileDescriptionRef> {
+        self.instances
+            .iter()
+            .filter_map(WeakFileDescriptionRef::upgrade)
+            .find(|fd| pipe_end(fd).state.get() == PipeState::Listening)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum PipeState {
+    /// A server end that waits for a client to connect.
+    Listening,
+    Connected,
+    /// The other end got closed or disconnected.
+    Broken,
+}
+
+/// One end of a named pipe: either a server end created by `CreateNamedPipeW`, or a client end
+/// created by `CreateFileW`.
+#[derive(Debug)]
+pub struct NamedPipe {
+    /// The name of the pipe, see `pipe_name`.
+    name: String,
+    is_server: bool,
+    readable: bool,
+    writable: bool,
+    /// Whether data is written as messages (`PIPE_TYPE_MESSAGE`). Both ends share this.
+    message_mode: bool,
+    /// Whether reads stop at the end of a message (`PIPE_READMODE_MESSAGE`).
+    read_message_mode: Cell<bool>,
+    state: Cell<PipeState>,
+    /// The other end of the connection. This is a weak reference because the other end may be
+    /// closed before us.
+    peer: RefCell<WeakFileDescriptionRef>,
+    /// The data that the other end wrote to us.
+    buf: RefCell<Buffer>,
+    /// Threads blocked on reading from this end, or on `ConnectNamedPipe`.
+    blocked_tid: RefCell<Vec<ThreadId>>,
+    /// Overlapped reads from this end that wait for data, in the order they were started.
+    pending_reads: RefCell<VecDeque<PendingRead>>,
+    /// An overlapped `ConnectNamedPipe` that waits for a client.
+    pending_connect: RefCell<Option<PendingCompletion>>,
+}
+
+#[derive(Debug)]
+struct PendingRead {
+    /// The address of the buffer to read into, whose provenance got exposed.
+    buf: u64,
+    len: u32,
+    completion: PendingCompletion,
+}
+
+#[derive(Debug, Default)]
+struct Buffer {
+    bytes: VecDeque<u8>,
+    /// In message mode, the length of every message in `bytes`.
+    messages: VecDeque<usize>,
+    clock: VClock,
+}
+
+impl Buffer {
+    fn is_empty(&self) -> bool {
+        // Messages can be empty, so they can be readable even if there are no bytes.
+        self.bytes.is_empty() && self.messages.is_empty()
+    }
+
+    /// Discards all data in the buffer.
+    fn clear(&mut self) {
+        self.bytes.clear();
+        self.messages.clear();
+    }
+
+    /// Takes up to `len` bytes out of the buffer. If `read_message_mode` is set, this stops at the
+    /// end of the first message. Returns the bytes, and whether the rest of that message is still
+    /// in the buffer.
+    fn take(&mut self, len: usize, read_message_mode: bool) -> (Vec<u8>, bool) {
+        let len = match self.messages.front() {
+            Some(&msg_len) if read_message_mode => len.min(msg_len),
+            _ => len.min(self.bytes.len()),
+        };
+        let bytes = self.bytes.drain(..len).collect();
+        // Drop the messages that were read completely, and shorten the one that was read partially.
+        let mut remaining = len;
+        while let Some(msg_len) = self.messages.front_mut() {
+            if *msg_len > remaining {
+                *msg_len = msg_len.strict_sub(remaining);
+                return (bytes, read_message_mode);
+            }
+            remaining = remaining.strict_sub(*msg_len);
+            self.messages.pop_front();
+            if read_message_mode {
+                break;
+            }
+        }
+        (bytes, false)
+    }
+}
+
+impl NamedPipe {
+    fn new(
+        name: String,
+        is_server: bool,
+        readable: bool,
+        writable: bool,
+        message_mode: bool,
+        read_message_mode: bool,
+        state: PipeState,
+    ) -> Self {
+        NamedPipe {
+            name,
+            is_server,
+            readable,
+            writable,
+            message_mode,
+            read_message_mode: Cell::new(read_message_mode),
+            state: Cell::new(state),
+            peer: RefCell::new(WeakFileDescriptionRef::default()),
+            buf: RefCell::new(Buffer::default()),
+            blocked_tid: RefCell::new(Vec::new()),
+            pending_reads: RefCell::new(VecDeque::new()),
+            pending_connect: RefCell::new(None),
+        }
+    }
+
+    /// Removes the pending overlapped operations that use the `OVERLAPPED` structure at the given
+    /// address, or all of them if there is no address.
+    fn take_pending(&self, overlapped: Option<u64>) -> Vec<PendingCompletion> {
+        let matches = |completion: &PendingCompletion| {
+            overlapped.is_none_or(|overlapped| completion.overlapped == overlapped)
+        };
+        let mut cancelled = Vec::new();
+        if self.pending_connect.borrow().as_ref().is_some_and(matches) {
+            cancelled.extend(self.pending_connect.take());
+        }
+        let mut pending_reads = self.pending_reads.borrow_mut();
+        let (taken, kept) =
+            pending_reads.drain(..).partition(|read: &PendingRead| matches(&read.completion));
+        *pending_reads = kept;
+        cancelled.extend(taken.into_iter().map(|read: PendingRead| read.completion));
+        cancelled
+    }
+
+    /// Unblocks all threads that are blocked on this end, so that they check its state again.
+    fn unblock_threads<'tcx>(&self, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+        // We need to take out the blocked thread ids first, because unblocked threads may block
+        // again and end up re-adding themselves to the list.
+        let waiting_threads = std::mem::take(&mut *self.blocked_tid.borrow_mut());
+        for thread_id in waiting_threads {
+            ecx.unblock_thread(thread_id, BlockReason::NamedPipe)?;
+        }
+        interp_ok(())
+    }
+
+    /// Breaks the connection to the other end, if there is one. Its blocked reads then fail.
+    fn break_connection<'tcx>(&self, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+        let Some(peer_fd) = self.peer.take().upgrade() else {
+            return interp_ok(());
+        };
+        let peer = pipe_end(&peer_fd);
+        peer.state.set(PipeState::Broken);
+        peer.peer.take();
+        notify(&peer_fd, ecx)
+    }
+}
+
+/// Lets the operations that wait on the pipe end `self_ref` check its state again: this unblocks
+/// the blocked threads, and completes the pending overlapped operations that can complete now.
+fn notify<'tcx>(self_ref: &FileDescriptionRef, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+    let pipe = pipe_end(self_ref);
+    pipe.unblock_threads(ecx)?;
+    if pipe.state.get() != PipeState::Listening
+        && let Some(completion) = pipe.pending_connect.take()
+    {
+        // Like a blocking `ConnectNamedPipe`, this succeeds even if the client already went away.
+        completion.into_completion().complete(0, None, ecx)?;
+    }
+    // Reads that find the buffer empty on a working connection would just stay pending.
+    while !(pipe.buf.borrow().is_empty() && pipe.state.get() == PipeState::Connected) {
+        let Some(read) = pipe.pending_reads.borrow_mut().pop_front() else {
+            break;
+        };
+        let buf = ecx.ptr_from_addr_cast(read.buf)?;
+        pipe_read(self_ref, buf, read.len, read.completion.into_completion(), ecx)?;
+    }
+    interp_ok(())
+}
+
+/// Removes the pending overlapped operations on `fd` that use the `OVERLAPPED` structure at the
+/// given address, or all of them if there is no address. This is used by `CancelIoEx`.
+pub fn cancel_pending_io(
+    fd: &FileDescriptionRef,
+    overlapped: Option<u64>,
+) -> Vec<PendingCompletion> {
+    match fd.downcast::<NamedPipe>() {
+        Some(pipe) => pipe.take_pending(overlapped),
+        None => Vec::new(),
+    }
+}
+
+/// Returns the pipe end of a file description that is known to be a named pipe.
+fn pipe_end(fd: &FileDescriptionRef) -> &NamedPipe {
+    fd.downcast::<NamedPipe>().unwrap()
+}
+
+/// Unblocks the threads in `WaitNamedPipeW` for the given pipe, so that they check it again.
+fn unblock_waiting_clients<'tcx>(name: &str, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+    for thread_id in ecx.machine.named_pipes.take_waiting_clients(name) {
+        ecx.unblock_thread(thread_id, BlockReason::NamedPipe)?;
+    }
+    interp_ok(())
+}
+
+impl FileDescription for NamedPipe {
+    fn name(&self) -> &'static str {
+        "named pipe"
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        self.break_connection(ecx)?;
+        for completion in self.take_pending(None) {
+            let error = IoError::WindowsError("ERROR_OPERATION_ABORTED");
+            completion.into_completion().complete(0, Some(error), ecx)?;
+        }
+        if self.is_server {
+            // If this was the last instance, the pipe is gone. Clients waiting for it need to
+            // check again.
+            unblock_waiting_clients(&self.name, ecx)?;
+        }
+        interp_ok(Ok(()))
+    }
+}
+
+/// Reads up to `len` bytes from a pipe end into `buf`. Blocks until the other end writes something
+/// or the connection breaks; overlapped reads stay pending instead.
+pub fn pipe_read<'tcx>(
+    self_ref: &FileDescriptionRef,
+    buf: Pointer,
+    len: u32,
+    completion: IoCompletion<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let pipe = pipe_end(self_ref);
+    if !pipe.readable {
+        return completion.complete(0, Some(IoError::WindowsError("ERROR_ACCESS_DENIED")), ecx);
+    }
+    if pipe.buf.borrow().is_empty() {
+        let error = match pipe.state.get() {
+            PipeState::Listening => "ERROR_PIPE_LISTENING",
+            PipeState::Broken => "ERROR_BROKEN_PIPE",
+            PipeState::Connected if matches!(completion, IoCompletion::Overlapped { .. }) => {
+                completion.pending(ecx)?;
+                let buf = exposed_addr(buf, ecx)?;
+                let completion = completion.into_pending().unwrap();
+                pipe.pending_reads.borrow_mut().push_back(PendingRead { buf, len, completion });
+                return interp_ok(());
+            }
+            PipeState::Connected => {
+                // Block until the other end writes something, or goes away.
+                pipe.blocked_tid.borrow_mut().push(ecx.active_thread());
+                let weak_self_ref = self_ref.downgrade();
+                ecx.block_thread(
+                    BlockReason::NamedPipe,
+                    None,
+                    callback!(
+                        @capture<'tcx> {
+                            weak_self_ref: WeakFileDescriptionRef,
+                            buf: Pointer,
+                            len: u32,
+                            completion: IoCompletion<'tcx>,
+                        }
+                        @unblock = |this| {
+                            let Some(self_ref) = weak_self_ref.upgrade() else {
+                                throw_unsup_format!("named pipe handle got closed while blocking.")
+                            };
+                            // When we get unblocked, try again.
+                            pipe_read(&self_ref, buf, len, completion, this)
+                        }
+                    ),
+                );
+                return interp_ok(());
+            }
+        };
+        return completion.complete(0, Some(IoError::WindowsError(error)), ecx);
+    }
+
+    let mut readbuf = pipe.buf.borrow_mut();
+    // Synchronize with all previous writes to this buffer.
+    ecx.acquire_clock(&readbuf.clock);
+    let (bytes, more_data) =
+        readbuf.take(usize::try_from(len).unwrap(), pipe.read_message_mode.get());
+    drop(readbuf);
+    ecx.write_bytes_ptr(buf, bytes.iter().copied())?;
+    // A message that did not fit into the buffer fails with `ERROR_MORE_DATA`; the rest of it can
+    // be read by the next call.
+    let error = more_data.then_some(IoError::WindowsError("ERROR_MORE_DATA"));
+    completion.complete(u32::try_from(bytes.len()).unwrap(), error, ecx)
+}
+
+/// Writes `len` bytes from `buf` to a pipe end. This never blocks: the data is buffered until the
+/// other end reads it.
+pub fn pipe_write<'tcx>(
+    self_ref: &FileDescriptionRef,
+    buf: Pointer,
+    len: u32,
+    completion: IoCompletion<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let pipe = pipe_end(self_ref);
+    if !pipe.writable {
+        return completion.complete(0, Some(IoError::WindowsError("ERROR_ACCESS_DENIED")), ecx);
+    }
+    let error = match pipe.state.get() {
+        PipeState::Listening => "ERROR_PIPE_LISTENING",
+        PipeState::Broken => "ERROR_NO_DATA",
+        PipeState::Connected => {
+            // A connected end always has a peer: closing either end breaks the connection.
+            let peer_fd = pipe.peer.borrow().upgrade().unwrap();
+            let peer = pipe_end(&peer_fd);
+            let bytes = ecx.read_bytes_ptr_strip_provenance(buf, Size::from_bytes(len))?.to_vec();
+            let mut writebuf = peer.buf.borrow_mut();
+            // Remember this clock so `read` can synchronize with us.
+            ecx.release_clock(|clock| {
+                writebuf.clock.join(clock);
+            });
+            writebuf.bytes.extend(bytes);
+            if pipe.message_mode {
+                writebuf.messages.push_back(usize::try_from(len).unwrap());
+            }
+            drop(writebuf);
+            // Report our own result first, so that it is queued before the peer's reads complete.
+            completion.complete(len, None, ecx)?;
+            return notify(&peer_fd, ecx);
+        }
+    };
+    completion.complete(0, Some(IoError::WindowsError(error)), ecx)
+}
+
+/// Waits for a client to connect to the server end `self_ref`.
+fn connect_named_pipe<'tcx>(
+    self_ref: &FileDescriptionRef,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let pipe = pipe_end(self_ref);
+    let error = match pipe.state.get() {
+        PipeState::Listening => {
+            pipe.blocked_tid.borrow_mut().push(ecx.active_thread());
+            let weak_self_ref = self_ref.downgrade();
+            let dest = dest.clone();
+            ecx.block_thread(
+                BlockReason::NamedPipe,
+                None,
+                callback!(
+                    @capture<'tcx> {
+                        weak_self_ref: WeakFileDescriptionRef,
+                        dest: MPlaceTy<'tcx>,
+                    }
+                    @unblock = |this| {
+                        let Some(self_ref) = weak_self_ref.upgrade() else {
+                            throw_unsup_format!("named pipe handle got closed while blocking.")
+                        };
+                        if pipe_end(&self_ref).state.get() == PipeState::Listening {
+                            return connect_named_pipe(&self_ref, &dest, this);
+                        }
+                        // A client connected while we were waiting. Even if it already went away
+                        // again, this succeeds; the next read reports the broken connection.
+                        this.write_scalar(this.eval_windows("c", "TRUE"), &dest)
+                    }
+                ),
+            );
+            return interp_ok(());
+        }
+        // The client connected before we called `ConnectNamedPipe`. Callers treat this as
+        // success, even though it is reported as an error.
+        PipeState::Connected => "ERROR_PIPE_CONNECTED",
+        PipeState::Broken => "ERROR_NO_DATA",
+    };
+    ecx.set_last_error(IoError::WindowsError(error))?;
+    ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest)
+}
+
+/// Waits until the pipe `name` has an instance that a client can connect to, or until the
+/// `deadline` (computed by `deadline_after`) has passed.
+fn wait_named_pipe<'tcx>(
+    name: String,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let active_thread = ecx.active_thread();
+    let timed_out = deadline_passed(deadline, ecx);
+    let Some(info) = ecx.machine.named_pipes.get(&name) else {
+        ecx.set_last_error(IoError::WindowsError("ERROR_FILE_NOT_FOUND"))?;
+        return ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest);
+    };
+    if info.listening_instance().is_some() {
+        return ecx.write_scalar(ecx.eval_windows("c", "TRUE"), dest);
+    }
+    if timed_out {
+        ecx.set_last_error(IoError::WindowsError("ERROR_SEM_TIMEOUT"))?;
+        return ecx.write_scalar(ecx.eval_windows("c", "FALSE"), dest);
+    }
+    info.waiting_clients.push(active_thread);
+    let dest = dest.clone();
+    ecx.block_thread(
+        BlockReason::NamedPipe,
+        deadline_timeout(deadline),
+        callback!(
+            @capture<'tcx> {
+                name: String,
+                deadline: Option<u64>,
+                dest: MPlaceTy<'tcx>,
+            }
+            @unblock = |this| {
+                // When we get unblocked, check again: another client may have connected first.
+                wait_named_pipe(name, deadline, &dest, this)
+            }
+            @timeout = |this| {
+                let active_thread = this.active_thread();
+                if let Some(info) = this.machine.named_pipes.get(&name) {
+                    info.waiting_clients.retain(|&id| id != active_thread);
+                }
+                this.set_last_error(IoError::WindowsError("ERROR_SEM_TIMEOUT"))?;
+                this.write_scalar(this.eval_windows("c", "FALSE"), &dest)
+            }
+        ),
+    );
+    interp_ok(())
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+
+#[allow(non_snake_case)]
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn CreateNamedPipeW(
+        &mut self,
+        name: &OpTy<'tcx>,
+        open_mode: &OpTy<'tcx>,
+        pipe_mode: &OpTy<'tcx>,
+        max_instances: &OpTy<'tcx>,
+        _out_buffer_size: &OpTy<'tcx>,
+        _in_buffer_size: &OpTy<'tcx>,
+        default_timeout: &OpTy<'tcx>,
+        security_attributes: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        this.assert_target_os("windows", "CreateNamedPipeW");
+
+        let name = this.read_wide_str(this.read_pointer(name)?)?;
+        let mut open_mode = this.read_scalar(open_mode)?.to_u32()?;
+        let mut pipe_mode = this.read_scalar(pipe_mode)?.to_u32()?;
+        let max_instances = this.read_scalar(max_instances)?.to_u32()?;
+        // The buffer sizes are only advisory, and our writes never block anyway.
+        let default_timeout = this.read_scalar(default_timeout)?.to_u32()?;
+        let security_attributes = this.read_pointer(security_attributes)?;
+
+        let pipe_access_inbound = this.eval_windows_u32("c", "PIPE_ACCESS_INBOUND");
+        let pipe_access_outbound = this.eval_windows_u32("c", "PIPE_ACCESS_OUTBOUND");
+        let pipe_access_duplex = this.eval_windows_u32("c", "PIPE_ACCESS_DUPLEX");
+        let file_flag_first_pipe_instance =
+            this.eval_windows_u32("c", "FILE_FLAG_FIRST_PIPE_INSTANCE");
+        let file_flag_overlapped = this.eval_windows_u32("c", "FILE_FLAG_OVERLAPPED");
+        let pipe_type_message = this.eval_windows_u32("c", "PIPE_TYPE_MESSAGE");
+        let pipe_readmode_message = this.eval_windows_u32("c", "PIPE_READMODE_MESSAGE");
+        let pipe_nowait = this.eval_windows_u32("c", "PIPE_NOWAIT");
+        let pipe_reject_remote_clients = this.eval_windows_u32("c", "PIPE_REJECT_REMOTE_CLIENTS");
+
+        if !this.ptr_is_null(security_attributes)? {
+            throw_unsup_format!("CreateNamedPipeW: security attributes are not supported");
+        }
+        // Overlapped operations are allowed on all handles, see `io_completion`.
+        open_mode &= !file_flag_overlapped;
+        let first_instance = open_mode & file_flag_first_pipe_instance != 0;
+        open_mode &= !file_flag_first_pipe_instance;
+        let (readable, writable) = if open_mode == pipe_access_inbound {
+            (true, false)
+        } else if open_mode == pipe_access_outbound {
+            (false, true)
+        } else if open_mode == pipe_access_duplex {
+            (true, true)
+        } else {
+            throw_unsup_format!("CreateNamedPipeW: unsupported open mode: {open_mode:#x}");
+        };
+        if pipe_mode & pipe_nowait != 0 {
+            throw_unsup_format!("CreateNamedPipeW: non-blocking pipes are not supported");
+        }
+        // There are no remote clients.
+        pipe_mode &= !pipe_reject_remote_clients;
+        if pipe_mode & !(pipe_type_message | pipe_readmode_message) != 0 {
+            throw_unsup_format!("CreateNamedPipeW: unsupported pipe mode: {pipe_mode:#x}");
+        }
+        let message_mode = pipe_mode & pipe_type_message != 0;
+        let read_message_mode = pipe_mode & pipe_readmode_message != 0;
+
+        let Some(name) = pipe_name(&name) else {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_NAME"))?;
+            return interp_ok(invalid_handle_value(this));
+        };
+        if (read_message_mode && !message_mode)
+            || max_instances == 0
+            || max_instances > PIPE_UNLIMITED_INSTANCES
+        {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return interp_ok(invalid_handle_value(this));
+        }
+        if let Some(info) = this.machine.named_pipes.get(&name) {
+            // All instances of a pipe have the same type.
+            if first_instance || info.message_mode != message_mode {
+                this.set_last_error(IoError::WindowsError("ERROR_ACCESS_DENIED"))?;
+                return interp_ok(invalid_handle_value(this));
+            }
+            if info.instances.len() >= usize::try_from(info.max_instances).unwrap() {
+                this.set_last_error(IoError::WindowsError("ERROR_PIPE_BUSY"))?;
+                return interp_ok(invalid_handle_value(this));
+            }
+        }
+
+        let fd_num = this.machine.fds.insert_new(NamedPipe::new(
+            name.clone(),
+            /* is_server */ true,
+            readable,
+            writable,
+            message_mode,
+            read_message_mode,
+            PipeState::Listening,
+        ));
+        let fd = this.machine.fds.get(fd_num).unwrap();
+        let default_timeout = if default_timeout == 0 {
+            DEFAULT_TIMEOUT
+        } else {
+            Duration::from_millis(default_timeout.into())
+        };
+        let info = this.machine.named_pipes.pipes.entry(name.clone()).or_insert_with(|| {
+            PipeInfo {
+                message_mode,
+                max_instances,
+                default_timeout,
+                instances: Vec::new(),
+                waiting_clients: Vec::new(),
+            }
+        });
+        info.instances.push(fd.downgrade());
+        // Clients in `WaitNamedPipeW` can connect to the new instance.
+        unblock_waiting_clients(&name, this)?;
+        interp_ok(Handle::File(fd_num).to_scalar(this))
+    }
+
+    fn ConnectNamedPipe(
+        &mut self,
+        pipe_op: &OpTy<'tcx>,
+        overlapped: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let overlapped = this.read_pointer(overlapped)?;
+        let Some(fd) = this.read_pipe_server_handle(pipe_op, "ConnectNamedPipe")? else {
+            return this.write_scalar(this.eval_windows("c", "FALSE"), dest);
+        };
+        let pipe = pipe_end(&fd);
+        if !this.ptr_is_null(overlapped)? && pipe.state.get() == PipeState::Listening {
+            // Wait for a client without blocking. `lpNumberOfBytesTransferred` does not exist here.
+            let fd_num = this.read_file_handle(pipe_op, "ConnectNamedPipe")?;
+            let completion =
+                this.io_completion(fd_num, overlapped, Pointer::null(), dest, "ConnectNamedPipe")?;
+            completion.pending(this)?;
+            *pipe.pending_connect.borrow_mut() = completion.into_pending();
+            return interp_ok(());
+        }
+        connect_named_pipe(&fd, dest, this)
+    }
+
+    fn DisconnectNamedPipe(&mut self, pipe: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let Some(fd) = this.read_pipe_server_handle(pipe, "DisconnectNamedPipe")? else {
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        };
+        let pipe = pipe_end(&fd);
+        // The client can no longer read or write. Data that has not been read yet is discarded.
+        if let Some(peer_fd) = pipe.peer.borrow().upgrade() {
+            pipe_end(&peer_fd).buf.borrow_mut().clear();
+        }
+        pipe.break_connection(this)?;
+        pipe.buf.borrow_mut().clear();
+        pipe.state.set(PipeState::Listening);
+        // Our own pending reads can never complete now.
+        let pending_reads: Vec<_> = pipe.pending_reads.borrow_mut().drain(..).collect();
+        for read in pending_reads {
+            let error = IoError::WindowsError("ERROR_BROKEN_PIPE");
+            read.completion.into_completion().complete(0, Some(error), this)?;
+        }
+        // Clients in `WaitNamedPipeW` can connect to this instance again.
+        unblock_waiting_clients(&pipe.name, this)?;
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn WaitNamedPipeW(
+        &mut self,
+        name: &OpTy<'tcx>,
+        timeout: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+        let name = this.read_wide_str(this.read_pointer(name)?)?;
+        let timeout = this.read_scalar(timeout)?.to_u32()?;
+
+        let Some(name) = pipe_name(&name) else {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_NAME"))?;
+            return this.write_scalar(this.eval_windows("c", "FALSE"), dest);
+        };
+        let timeout = match timeout {
+            NMPWAIT_WAIT_FOREVER => None,
+            NMPWAIT_USE_DEFAULT_WAIT =>
+                this.machine.named_pipes.get(&name).map(|info| info.default_timeout),
+            timeout => Some(Duration::from_millis(timeout.into())),
+        };
+        let deadline = deadline_after(timeout, this);
+        wait_named_pipe(name, deadline, dest, this)
+    }
+
+    fn SetNamedPipeHandleState(
+        &mut self,
+        pipe: &OpTy<'tcx>,
+        mode: &OpTy<'tcx>,
+        max_collection_count: &OpTy<'tcx>,
+        collect_data_timeout: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(pipe, "SetNamedPipeHandleState")?;
+        let mode = this.read_pointer(mode)?;
+        let max_collection_count = this.read_pointer(max_collection_count)?;
+        let collect_data_timeout = this.read_pointer(collect_data_timeout)?;
+
+        let pipe_readmode_message = this.eval_windows_u32("c", "PIPE_READMODE_MESSAGE");
+        let pipe_nowait = this.eval_windows_u32("c", "PIPE_NOWAIT");
+
+        if !this.ptr_is_null(max_collection_count)? || !this.ptr_is_null(collect_data_timeout)? {
+            throw_unsup_format!(
+                "SetNamedPipeHandleState: collection settings are only supported for remote pipes"
+            );
+        }
+        let fd = this.machine.fds.get(fd_num).unwrap();
+        let Some(pipe) = fd.downcast::<NamedPipe>() else {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_FUNCTION"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        };
+        if !this.ptr_is_null(mode)? {
+            let mode = this.read_scalar(&this.ptr_to_mplace(mode, this.machine.layouts.u32))?;
+            let mode = mode.to_u32()?;
+            if mode & pipe_nowait != 0 {
+                throw_unsup_format!(
+                    "SetNamedPipeHandleState: non-blocking pipes are not supported"
+                );
+            }
+            let read_message_mode = mode & pipe_readmode_message != 0;
+            // Only pipes of message type can be read in message mode.
+            if mode & !pipe_readmode_message != 0 || (read_message_mode && !pipe.message_mode) {
+                this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+                return interp_ok(this.eval_windows("c", "FALSE"));
+            }
+            pipe.read_message_mode.set(read_message_mode);
+        }
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    /// Opens the client end of the pipe `name` and connects it to a listening server end. This is
+    /// called by `CreateFileW`.
+    fn open_named_pipe(
+        &mut self,
+        name: &str,
+        readable: bool,
+        writable: bool,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let Some(info) = this.machine.named_pipes.get(name) else {
+            this.set_last_error(IoError::WindowsError("ERROR_FILE_NOT_FOUND"))?;
+            return interp_ok(invalid_handle_value(this));
+        };
+        let message_mode = info.message_mode;
+        let Some(server_fd) = info.listening_instance() else {
+            this.set_last_error(IoError::WindowsError("ERROR_PIPE_BUSY"))?;
+            return interp_ok(invalid_handle_value(this));
+        };
+        let server = pipe_end(&server_fd);
+        // The client can only use the directions that the server allows.
+        if (readable && !server.writable) || (writable && !server.readable) {
+            this.set_last_error(IoError::WindowsError("ERROR_ACCESS_DENIED"))?;
+            return interp_ok(invalid_handle_value(this));
+        }
+
+        let client = NamedPipe::new(
+            name.to_owned(),
+            /* is_server */ false,
+            readable,
+            writable,
+            message_mode,
+            /* read_message_mode */ false,
+            PipeState::Connected,
+        );
+        *client.peer.borrow_mut() = server_fd.downgrade();
+        let fd_num = this.machine.fds.insert_new(client);
+        let client_fd = this.machine.fds.get(fd_num).unwrap();
+        *server.peer.borrow_mut() = client_fd.downgrade();
+        server.state.set(PipeState::Connected);
+        // Wake up the server if it is waiting in `ConnectNamedPipe`.
+        notify(&server_fd, this)?;
+        interp_ok(Handle::File(fd_num).to_scalar(this))
+    }
+
+    /// Reads a `HANDLE` that must refer to the server end of a named pipe. If it refers to another
+    /// file, this sets the last error and returns `None`.
+    fn read_pipe_server_handle(
+        &mut self,
+        handle: &OpTy<'tcx>,
+        function_name: &str,
+    ) -> InterpResult<'tcx, Option<FileDescriptionRef>> {
+        let this = self.eval_context_mut();
+        let fd_num = this.read_file_handle(handle, function_name)?;
+        if let Some(fd) = this.machine.fds.get(fd_num)
+            && fd.downcast::<NamedPipe>().is_some_and(|pipe| pipe.is_server)
+        {
+            return interp_ok(Some(fd));
+        }
+        this.set_last_error(IoError::WindowsError("ERROR_INVALID_FUNCTION"))?;
+        interp_ok(None)
+    }
+}
@@ -241,7 +241,9 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         };
         let futex_ref = futex_ref.futex.clone();
 
-        this.futex_wake(&futex_ref, u32::MAX)?;
+        if !this.futex_wake(&futex_ref, u32::MAX)? {
+            this.futex_wake_lost(&futex_ref);
+        }
 
         interp_ok(())
     }
@@ -261,6 +263,9 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         };
         let futex_ref = futex_ref.futex.clone();
 
+        if this.futex_waiters(&futex_ref).is_empty() {
+            this.futex_wake_lost(&futex_ref);
+        }
         while this.futex_wake(&futex_ref, u32::MAX)? {}
 
         interp_ok(())
@@ -0,0 +1,390 @@
+//! Temporary files on Windows. Like on Windows, `GetTempPathW` reports the directory named by the
+//! `TMP`, `TEMP` or `USERPROFILE` environment variables. If none of them is set, it reports the
+//! temporary directory of the host, or with isolation, a virtual directory. Files in the virtual
+//! directory do not correspond to anything on the host and are entirely implemented inside Miri:
+//! `CreateFileW`, `DeleteFileW` and `MoveFileExW` work on them like on host files, but their
+//! contents are kept in memory. This lets programs use temporary files without disabling isolation,
+//! and makes them behave the same on every run.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::OpenOptions;
+use std::io::{self, ErrorKind, SeekFrom};
+use std::path::Path;
+use std::rc::Rc;
+use std::{env, iter};
+
+use rand::Rng;
+use rustc_abi::Size;
+
+use crate::helpers::windows_check_buffer_size;
+use crate::shims::files::{EvalContextExt as _, FileDescription, FileDescriptionRef};
+use crate::shims::os_str::PathConversion;
+use crate::shims::windows::fs::{CreationDisposition, invalid_handle_value};
+use crate::shims::windows::handle::Handle;
+use crate::*;
+
+/// The temporary directory that `GetTempPathW` reports with isolation, in lowercase.
+const VIRTUAL_TEMP_DIR: &str = r"c:\windows\temp\";
+/// `MAX_PATH`, the size of the buffer that `GetTempFileNameW` writes the file name to.
+const MAX_PATH: u64 = 260;
+/// `ERROR_BUFFER_OVERFLOW`, which `GetTempFileNameW` reports if the file name is too long.
+const ERROR_BUFFER_OVERFLOW: u32 = 111;
+/// The largest size of a virtual temporary file. Their contents are kept in memory, so we report
+/// a full disk instead of letting the program make Miri allocate arbitrary amounts of memory.
+const MAX_VIRTUAL_FILE_SIZE: u64 = 1 << 30;
+
+/// Returns the name of the virtual temporary file that `name` refers to, if it is in the virtual
+/// temporary directory. File names are case insensitive, so we use the lowercase name. The virtual
+/// directory has no subdirectories.
+pub fn virtual_temp_file_name(name: &[u16]) -> Option<String> {
+    let name = String::from_utf16_lossy(name).to_lowercase().replace('/', r"\");
+    let file_name = name.strip_prefix(VIRTUAL_TEMP_DIR)?;
+    (!file_name.is_empty() && !file_name.contains('\\')).then_some(name)
+}
+
+/// The files in the virtual temporary directory.
+#[derive(Debug)]
+pub struct VirtualTempDir {
+    files: BTreeMap<String, Rc<RefCell<Vec<u8>>>>,
+}
+
+impl VirtualTempDir {
+    pub(crate) fn new() -> Self {
+        VirtualTempDir { files: BTreeMap::new() }
+    }
+
+    /// Removes the file with the given name. Handles that are still open keep the contents alive,
+    /// like on unix.
+    pub fn remove(&mut self, name: &str) -> Result<(), IoError> {
+        match self.files.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(IoError::WindowsError("ERROR_FILE_NOT_FOUND")),
+        }
+    }
+
+    /// Renames the file `from` to `to`, replacing `to` if `replace_existing` is set.
+    pub fn rename(&mut self, from: &str, to: &str, replace_existing: bool) -> Result<(), IoError> {
+        if !self.files.contains_key(from) {
+            return Err(IoError::WindowsError("ERROR_FILE_NOT_FOUND"));
+        }
+        if from == to {
+            return Ok(());
+        }
+        if !replace_existing && self.files.contains_key(to) {
+            return Err(IoError::WindowsError("ERROR_ALREADY_EXISTS"));
+        }
+        let contents = self.files.remove(from).unwrap();
+        self.files.insert(to.to_owned(), contents);
+        Ok(())
+    }
+}
+
+/// An open handle to a file in the virtual temporary directory.
+#[derive(Debug)]
+pub struct VirtualFile {
+    contents: Rc<RefCell<Vec<u8>>>,
+    /// The file pointer.
+    pos: Cell<u64>,
+    readable: bool,
+    writable: bool,
+}
+
+impl VirtualFile {
+    pub fn size(&self) -> u64 {
+        u64::try_from(self.contents.borrow().len()).unwrap()
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Truncates or extends the file with zeros. This does not move the file pointer.
+    pub fn set_len(&self, len: u64) -> Result<(), IoError> {
+        if len > MAX_VIRTUAL_FILE_SIZE {
+            return Err(IoError::WindowsError("ERROR_DISK_FULL"));
+        }
+        self.contents.borrow_mut().resize(usize::try_from(len).unwrap(), 0);
+        Ok(())
+    }
+}
+
+impl FileDescription for VirtualFile {
+    fn name(&self) -> &'static str {
+        "virtual file"
+    }
+
+    fn read<'tcx>(
+        &self,
+        _self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        ptr: Pointer,
+        len: usize,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        if !self.readable {
+            return ecx
+                .set_last_error_and_return(IoError::WindowsError("ERROR_ACCESS_DENIED"), dest);
+        }
+        let contents = self.contents.borrow();
+        // Reading past the end of the file reads nothing.
+        let start = usize::try_from(self.pos.get()).unwrap_or(usize::MAX).min(contents.len());
+        let end = start.saturating_add(len).min(contents.len());
+        let bytes = contents[start..end].to_vec();
+        drop(contents);
+        self.pos.set(self.pos.get().strict_add(u64::try_from(bytes.len()).unwrap()));
+        ecx.return_read_success(ptr, &bytes, bytes.len(), dest)
+    }
+
+    fn write<'tcx>(
+        &self,
+        _self_ref: &FileDescriptionRef,
+        _communicate_allowed: bool,
+        ptr: Pointer,
+        len: usize,
+        dest: &MPlaceTy<'tcx>,
+        ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx> {
+        if !self.writable {
+            return ecx
+                .set_last_error_and_return(IoError::WindowsError("ERROR_ACCESS_DENIED"), dest);
+        }
+        // The file pointer can be anywhere, so the write may not fit into a virtual file.
+        let end = self.pos.get().saturating_add(u64::try_from(len).unwrap());
+        if end > MAX_VIRTUAL_FILE_SIZE {
+            return ecx.set_last_error_and_return(IoError::WindowsError("ERROR_DISK_FULL"), dest);
+        }
+        let bytes = ecx.read_bytes_ptr_strip_provenance(ptr, Size::from_bytes(len))?;
+        let mut contents = self.contents.borrow_mut();
+        // Writing past the end of the file fills the gap with zeros.
+        let start = usize::try_from(self.pos.get()).unwrap();
+        let end = usize::try_from(end).unwrap();
+        if contents.len() < end {
+            contents.resize(end, 0);
+        }
+        contents[start..end].copy_from_slice(bytes);
+        self.pos.set(u64::try_from(end).unwrap());
+        ecx.return_write_success(len, dest)
+    }
+
+    fn seek<'tcx>(
+        &self,
+        _communicate_allowed: bool,
+        offset: SeekFrom,
+    ) -> InterpResult<'tcx, io::Result<u64>> {
+        let pos = match offset {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.get().checked_add_signed(offset),
+            SeekFrom::End(offset) => self.size().checked_add_signed(offset),
+        };
+        // Like on Windows, the file pointer can be moved past the end of the file, but not beyond
+        // what fits into the `LARGE_INTEGER` that `SetFilePointerEx` returns it in.
+        let Some(pos) = pos.filter(|&pos| i64::try_from(pos).is_ok()) else {
+            return interp_ok(Err(ErrorKind::InvalidInput.into()));
+        };
+        self.pos.set(pos);
+        interp_ok(Ok(pos))
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        _ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        interp_ok(Ok(()))
+    }
+}
+
+/// Opens the virtual temporary file `name` (see `virtual_temp_file_name`) for `CreateFileW`, and
+/// returns the new handle.
+pub fn open_virtual_temp_file<'tcx>(
+    name: &str,
+    creation_disposition: CreationDisposition,
+    readable: bool,
+    writable: bool,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, Scalar> {
+    let existing = ecx.machine.temp_files.files.get(name).cloned();
+    let contents = match (creation_disposition, existing) {
+        (CreationDisposition::CreateNew, Some(_)) => {
+            ecx.set_last_error(IoError::WindowsError("ERROR_FILE_EXISTS"))?;
+            return interp_ok(invalid_handle_value(ecx));
+        }
+        (CreationDisposition::OpenExisting | CreationDisposition::TruncateExisting, None) => {
+            ecx.set_last_error(IoError::WindowsError("ERROR_FILE_NOT_FOUND"))?;
+            return interp_ok(invalid_handle_value(ecx));
+        }
+        (CreationDisposition::TruncateExisting, Some(_)) if !writable => {
+            ecx.set_last_error(IoError::WindowsError("ERROR_ACCESS_DENIED"))?;
+            return interp_ok(invalid_handle_value(ecx));
+        }
+        (_, Some(contents)) => {
+            if matches!(
+                creation_disposition,
+                CreationDisposition::CreateAlways | CreationDisposition::TruncateExisting
+            ) {
+                contents.borrow_mut().clear();
+            }
+            // `CREATE_ALWAYS` and `OPEN_ALWAYS` report that the file already existed.
+            if matches!(
+                creation_disposition,
+                CreationDisposition::CreateAlways | CreationDisposition::OpenAlways
+            ) {
+                ecx.set_last_error(IoError::WindowsError("ERROR_ALREADY_EXISTS"))?;
+            } else {
+                ecx.set_last_error(Scalar::from_u32(0))?;
+            }
+            contents
+        }
+        (_, None) => {
+            let contents = Rc::new(RefCell::new(Vec::new()));
+            ecx.machine.temp_files.files.insert(name.to_owned(), contents.clone());
+            ecx.set_last_error(Scalar::from_u32(0))?;
+            contents
+        }
+    };
+    let fd_num =
+        ecx.machine.fds.insert_new(VirtualFile { contents, pos: Cell::new(0), readable, writable });
+    interp_ok(Handle::File(fd_num).to_scalar(ecx))
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+
+#[allow(non_snake_case)]
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// Implements `GetTempPathW` and `GetTempPath2W`. These only differ for processes running as
+    /// `SYSTEM`, which Miri never does.
+    fn GetTempPathW(
+        &mut self,
+        buffer_length: &OpTy<'tcx>,
+        buffer: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let size = u64::from(this.read_scalar(buffer_length)?.to_u32()?);
+        let buf = this.read_pointer(buffer)?;
+
+        let mut path = this.temp_path()?;
+        // The path always ends with a backslash.
+        if !path.as_encoded_bytes().ends_with(b"\\") {
+            path.push(r"\");
+        }
+        interp_ok(Scalar::from_u32(windows_check_buffer_size(
+            this.write_os_str_to_wide_str(&path, buf, size)?,
+        )))
+    }
+
+    fn GetTempFileNameW(
+        &mut self,
+        path_name: &OpTy<'tcx>,
+        prefix_string: &OpTy<'tcx>,
+        unique: &OpTy<'tcx>,
+        temp_file_name: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let path_name = this.read_wide_str(this.read_pointer(path_name)?)?;
+        let prefix = this.read_wide_str(this.read_pointer(prefix_string)?)?;
+        let unique = this.read_scalar(unique)?.to_u32()?;
+        let temp_file_name = this.read_pointer(temp_file_name)?;
+
+        // The name is `<path>\<prefix><unique>.TMP`, where only the first three characters of the
+        // prefix are used and `<unique>` is the unique number in hexadecimal.
+        let mut dir = path_name;
+        if !dir.is_empty()
+            && !dir.ends_with(&[u16::from(b'\\')])
+            && !dir.ends_with(&[u16::from(b'/')])
+        {
+            dir.push(u16::from(b'\\'));
+        }
+        dir.extend(prefix.iter().take(3));
+        let file_name = |unique: u32| -> Vec<u16> {
+            dir.iter().copied().chain(format!("{unique:X}.TMP").encode_utf16()).collect()
+        };
+        let fits = |name: &[u16]| u64::try_from(name.len()).unwrap() < MAX_PATH;
+
+        // With a nonzero unique number, the file is not created.
+        if unique != 0 {
+            let name = file_name(unique);
+            if !fits(&name) {
+                this.set_last_error(IoError::Raw(Scalar::from_u32(ERROR_BUFFER_OVERFLOW)))?;
+                return interp_ok(Scalar::from_u32(0));
+            }
+            this.write_wide_str(&name, temp_file_name, MAX_PATH)?;
+            return interp_ok(Scalar::from_u32(unique));
+        }
+
+        // Otherwise, we try all numbers until we find one that does not exist yet. Windows starts
+        // at the current time, we pick a random number (which is deterministic with isolation).
+        let start = this.machine.rng.get_mut().gen_range(0..=u16::MAX);
+        for unique in iter::successors(Some(start), |u| Some(u.wrapping_add(1))).take(0x10000) {
+            if unique == 0 {
+                continue;
+            }
+            let unique = u32::from(unique);
+            let name = file_name(unique);
+            if !fits(&name) {
+                this.set_last_error(IoError::Raw(Scalar::from_u32(ERROR_BUFFER_OVERFLOW)))?;
+                return interp_ok(Scalar::from_u32(0));
+            }
+            match this.create_new_temp_file(&name)? {
+                Ok(true) => {
+                    this.write_wide_str(&name, temp_file_name, MAX_PATH)?;
+                    return interp_ok(Scalar::from_u32(unique));
+                }
+                Ok(false) => continue,
+                Err(e) => {
+                    this.set_last_error(e)?;
+                    return interp_ok(Scalar::from_u32(0));
+                }
+            }
+        }
+        this.set_last_error(IoError::WindowsError("ERROR_FILE_EXISTS"))?;
+        interp_ok(Scalar::from_u32(0))
+    }
+}
+
+impl<'tcx> EvalContextPrivExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// Returns the temporary directory, as a Windows path.
+    fn temp_path(&mut self) -> InterpResult<'tcx, OsString> {
+        let this = self.eval_context_mut();
+        for var in ["TMP", "TEMP", "USERPROFILE"] {
+            if let Some(value) = this.machine.env_vars.windows().get(OsStr::new(var))?
+                && !value.is_empty()
+            {
+                return interp_ok(value);
+            }
+        }
+        if this.machine.communicate() {
+            let path = env::temp_dir().into_os_string();
+            interp_ok(this.convert_path(path.into(), PathConversion::HostToTarget).into_owned())
+        } else {
+            // The virtual directory, capitalized like on Windows.
+            interp_ok(r"C:\Windows\Temp\".into())
+        }
+    }
+
+    /// Creates the file `name` for `GetTempFileNameW`. Returns `false` if it already exists.
+    fn create_new_temp_file(&mut self, name: &[u16]) -> InterpResult<'tcx, Result<bool, IoError>> {
+        let this = self.eval_context_mut();
+        if let IsolatedOp::Reject(reject_with) = this.machine.isolated_op {
+            let Some(name) = virtual_temp_file_name(name) else {
+                this.reject_in_isolation("`GetTempFileNameW`", reject_with)?;
+                return interp_ok(Err(ErrorKind::PermissionDenied.into()));
+            };
+            if this.machine.temp_files.files.contains_key(&name) {
+                return interp_ok(Ok(false));
+            }
+            this.machine.temp_files.files.insert(name, Rc::new(RefCell::new(Vec::new())));
+            return interp_ok(Ok(true));
+        }
+        let name = OsString::from(String::from_utf16_lossy(name));
+        let path = this.convert_path(name.into(), PathConversion::TargetToHost);
+        match OpenOptions::new().write(true).create_new(true).open(Path::new(&path)) {
+            Ok(_) => interp_ok(Ok(true)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => interp_ok(Ok(false)),
+            Err(e) => interp_ok(Err(e.into())),
+        }
+    }
+}
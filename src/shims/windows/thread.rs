@@ -1,9 +1,57 @@
 use rustc_abi::ExternAbi;
+use rustc_middle::ty::Ty;
 use rustc_middle::ty::layout::LayoutOf;
 
-use self::shims::windows::handle::{EvalContextExt as _, Handle, PseudoHandle};
+use crate::shims::windows::handle::{EvalContextExt as _, Handle, HandleError, PseudoHandle};
 use crate::*;
 
+// The NTSTATUS STATUS_INVALID_HANDLE (0xC0000008) encoded as a HRESULT by setting the N bit.
+// (https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-erref/0642cb2f-2075-4469-918c-4441e69c548a)
+const STATUS_INVALID_HANDLE: u32 = 0xD0000008;
+
+/// Encodes a thread description as WTF-8, the generalization of UTF-8 that can represent unpaired
+/// surrogates. This way, valid descriptions show up as they are in diagnostics, and
+/// `GetThreadDescription` returns exactly what was passed to `SetThreadDescription`.
+fn wide_to_wtf8(wide: &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for c in char::decode_utf16(wide.iter().copied()) {
+        match c {
+            Ok(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            Err(e) => {
+                let [hi, lo] = e.unpaired_surrogate().to_be_bytes();
+                bytes.extend_from_slice(&[
+                    0xE0 | (hi >> 4),
+                    0x80 | ((hi & 0x0F) << 2) | (lo >> 6),
+                    0x80 | (lo & 0x3F),
+                ]);
+            }
+        }
+    }
+    bytes
+}
+
+/// The inverse of `wide_to_wtf8`. Thread names that were not set by `SetThreadDescription` are not
+/// necessarily WTF-8; invalid sequences in them are replaced by U+FFFD.
+fn wtf8_to_wide(bytes: &[u8]) -> Vec<u16> {
+    let mut wide = Vec::new();
+    let mut rest = bytes;
+    // An encoded surrogate starts with 0xED followed by a byte in 0xA0..=0xBF. This is never valid
+    // UTF-8, so it cannot appear inside the valid parts of the name.
+    while let Some(pos) = rest.windows(3).position(|w| {
+        w[0] == 0xED && (0xA0..=0xBF).contains(&w[1]) && (0x80..=0xBF).contains(&w[2])
+    }) {
+        let (valid, [_, b1, b2, tail @ ..]) = rest.split_at(pos) else { unreachable!() };
+        wide.extend(String::from_utf8_lossy(valid).encode_utf16());
+        wide.push(u16::from_be_bytes([
+            0xD0 | ((b1 & 0x3F) >> 2),
+            ((b1 & 0x03) << 6) | (b2 & 0x3F),
+        ]));
+        rest = tail;
+    }
+    wide.extend(String::from_utf8_lossy(rest).encode_utf16());
+    wide
+}
+
 impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
 
 #[allow(non_snake_case)]
@@ -55,30 +103,68 @@ pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         )
     }
 
-    fn WaitForSingleObject(
+    fn SetThreadDescription(
         &mut self,
         handle_op: &OpTy<'tcx>,
-        timeout_op: &OpTy<'tcx>,
+        name_op: &OpTy<'tcx>,
     ) -> InterpResult<'tcx, Scalar> {
         let this = self.eval_context_mut();
 
         let handle = this.read_scalar(handle_op)?;
-        let timeout = this.read_scalar(timeout_op)?.to_u32()?;
-
-        let thread = match Handle::try_from_scalar(handle, this)? {
-            Ok(Handle::Thread(thread)) => thread,
-            // Unlike on posix, the outcome of joining the current thread is not documented.
-            // On current Windows, it just deadlocks.
-            Ok(Handle::Pseudo(PseudoHandle::CurrentThread)) => this.active_thread(),
-            _ => this.invalid_handle("WaitForSingleObject")?,
+        let name = this.read_wide_str(this.read_pointer(name_op)?)?;
+
+        let Some(thread) = this.read_thread_description_handle(handle, "SetThreadDescription")?
+        else {
+            return interp_ok(Scalar::from_u32(STATUS_INVALID_HANDLE));
         };
+        this.set_thread_name(thread, wide_to_wtf8(&name));
+        interp_ok(Scalar::from_u32(0))
+    }
 
-        if timeout != this.eval_windows_u32("c", "INFINITE") {
-            throw_unsup_format!("`WaitForSingleObject` with non-infinite timeout");
-        }
+    fn GetThreadDescription(
+        &mut self,
+        handle_op: &OpTy<'tcx>,
+        name_op: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
 
-        this.join_thread(thread)?;
+        let handle = this.read_scalar(handle_op)?;
+        let name_place = this.deref_pointer(name_op)?; // the pointer where we should store the ptr to the name
 
-        interp_ok(this.eval_windows("c", "WAIT_OBJECT_0"))
+        let Some(thread) = this.read_thread_description_handle(handle, "GetThreadDescription")?
+        else {
+            this.write_scalar(Scalar::null_ptr(this), &name_place)?;
+            return interp_ok(Scalar::from_u32(STATUS_INVALID_HANDLE));
+        };
+        // Looks like the default thread name is empty.
+        let name = wtf8_to_wide(this.get_thread_name(thread).unwrap_or(b""));
+        let size = u64::try_from(name.len()).unwrap().strict_add(1); // Make space for `0x0000` terminator.
+        let name_type = Ty::new_array(this.tcx.tcx, this.tcx.types.u16, size);
+        let name_alloc =
+            this.allocate(this.layout_of(name_type)?, MiriMemoryKind::WinLocal.into())?;
+        let (written, _) = this.write_wide_str(&name, name_alloc.ptr(), size)?;
+        assert!(written);
+        this.write_pointer(name_alloc.ptr(), &name_place)?;
+        interp_ok(Scalar::from_u32(0))
+    }
+}
+
+impl<'tcx> EvalContextPrivExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+trait EvalContextPrivExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    /// Resolves the thread handle passed to `SetThreadDescription` or `GetThreadDescription`.
+    /// Returns `None` if the handle refers to a thread that no longer exists.
+    fn read_thread_description_handle(
+        &mut self,
+        handle: Scalar,
+        function_name: &str,
+    ) -> InterpResult<'tcx, Option<ThreadId>> {
+        let this = self.eval_context_mut();
+        match Handle::try_from_scalar(handle, this)? {
+            Ok(Handle::Thread(thread)) => interp_ok(Some(thread)),
+            Ok(Handle::Pseudo(PseudoHandle::CurrentThread)) =>
+                interp_ok(Some(this.active_thread())),
+            Ok(_) | Err(HandleError::InvalidHandle) => this.invalid_handle(function_name)?,
+            Err(HandleError::ThreadNotFound(_)) => interp_ok(None),
+        }
     }
 }
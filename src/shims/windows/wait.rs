@@ -0,0 +1,472 @@
+//! Waiting on handles with `WaitForSingleObject` and `WaitForMultipleObjects`, and the event and
+//! mutex objects that are used that way. Events and mutexes are stored in the `FdTable`, like
+//! files. Waiting threads do not register with the objects they wait on: whenever an object may have
+//! become signaled, all of them get unblocked and check their objects again.
+
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::time::Duration;
+
+use crate::concurrency::VClock;
+use crate::shims::files::{FileDescription, FileDescriptionRef, WeakFileDescriptionRef};
+use crate::shims::unix::{deadline_after, deadline_passed, deadline_timeout};
+use crate::shims::windows::handle::{EvalContextExt as _, Handle, PseudoHandle};
+use crate::*;
+
+/// `MAXIMUM_WAIT_OBJECTS`, the largest number of handles `WaitForMultipleObjects` accepts.
+const MAXIMUM_WAIT_OBJECTS: u32 = 64;
+/// `WAIT_ABANDONED_0`, which is returned for mutexes whose owner terminated.
+const WAIT_ABANDONED_0: u32 = 0x80;
+/// `WAIT_FAILED`, which is returned if the arguments are invalid.
+const WAIT_FAILED: u32 = u32::MAX;
+
+/// An event object, created by `CreateEventW`.
+#[derive(Debug)]
+struct Event {
+    /// Whether the event stays signaled until `ResetEvent` is called. Otherwise, a wait that
+    /// succeeds resets it.
+    manual_reset: bool,
+    signaled: Cell<bool>,
+    /// Synchronizes the threads that set the event with the threads that wait for it.
+    clock: RefCell<VClock>,
+}
+
+impl FileDescription for Event {
+    fn name(&self) -> &'static str {
+        "event"
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        _ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        interp_ok(Ok(()))
+    }
+}
+
+/// A mutex object, created by `CreateMutexW`. Unlike `SRWLOCK`s, these are recursive.
+#[derive(Debug, Default)]
+struct Mutex {
+    owner: Cell<Option<ThreadId>>,
+    /// How often the owner acquired the mutex.
+    count: Cell<u32>,
+    /// Synchronizes the threads that release the mutex with the threads that acquire it.
+    clock: RefCell<VClock>,
+}
+
+impl FileDescription for Mutex {
+    fn name(&self) -> &'static str {
+        "mutex"
+    }
+
+    fn close<'tcx>(
+        self: Box<Self>,
+        _communicate_allowed: bool,
+        _ecx: &mut MiriInterpCx<'tcx>,
+    ) -> InterpResult<'tcx, io::Result<()>> {
+        interp_ok(Ok(()))
+    }
+}
+
+/// An object that a thread waits on.
+#[derive(Debug)]
+enum WaitObject {
+    /// A thread is signaled once it terminated.
+    Thread(ThreadId),
+    /// An event or a mutex.
+    Object(WeakFileDescriptionRef),
+}
+
+impl VisitProvenance for WaitObject {
+    fn visit_provenance(&self, _visit: &mut VisitWith<'_>) {
+        // File descriptions do not contain provenance.
+    }
+}
+
+/// Whether an object can be acquired by the active thread.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Signal {
+    NotSignaled,
+    Signaled,
+    /// A mutex whose owner terminated without releasing it.
+    Abandoned,
+}
+
+/// Returns whether `object` can be acquired by the active thread.
+fn signal_state<'tcx>(object: &WaitObject, ecx: &MiriInterpCx<'tcx>) -> InterpResult<'tcx, Signal> {
+    let fd = match object {
+        WaitObject::Thread(thread) =>
+            return interp_ok(if ecx.machine.threads.has_terminated(*thread) {
+                Signal::Signaled
+            } else {
+                Signal::NotSignaled
+            }),
+        WaitObject::Object(fd) => fd,
+    };
+    let Some(fd) = fd.upgrade() else {
+        throw_unsup_format!("handle got closed while a thread was waiting on it");
+    };
+    if let Some(event) = fd.downcast::<Event>() {
+        return interp_ok(if event.signaled.get() {
+            Signal::Signaled
+        } else {
+            Signal::NotSignaled
+        });
+    }
+    let mutex = fd.downcast::<Mutex>().unwrap();
+    interp_ok(match mutex.owner.get() {
+        None => Signal::Signaled,
+        Some(owner) if owner == ecx.active_thread() => Signal::Signaled,
+        Some(owner) if ecx.machine.threads.has_terminated(owner) => Signal::Abandoned,
+        Some(_) => Signal::NotSignaled,
+    })
+}
+
+/// Acquires `object`, which must be signaled, for the active thread.
+fn acquire_object<'tcx>(object: &WaitObject, ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+    let fd = match object {
+        // Waiting for a thread synchronizes with everything it did, like joining it.
+        WaitObject::Thread(thread) => return ecx.join_thread(*thread),
+        WaitObject::Object(fd) => fd.upgrade().unwrap(),
+    };
+    if let Some(event) = fd.downcast::<Event>() {
+        ecx.acquire_clock(&event.clock.borrow());
+        if !event.manual_reset {
+            event.signaled.set(false);
+        }
+        return interp_ok(());
+    }
+    let mutex = fd.downcast::<Mutex>().unwrap();
+    ecx.acquire_clock(&mutex.clock.borrow());
+    if mutex.owner.get() != Some(ecx.active_thread()) {
+        // The mutex was free or abandoned.
+        mutex.owner.set(Some(ecx.active_thread()));
+        mutex.count.set(0);
+    }
+    mutex.count.set(mutex.count.get().strict_add(1));
+    interp_ok(())
+}
+
+/// Unblocks all threads that wait on handles, so that they check their objects again.
+pub fn notify_object_waiters<'tcx>(ecx: &mut MiriInterpCx<'tcx>) -> InterpResult<'tcx> {
+    // We need to take out the blocked thread ids first, because threads that still cannot acquire
+    // their objects block again and end up re-adding themselves to the list.
+    let waiting_threads = std::mem::take(&mut ecx.machine.blocked_object_wait_tid);
+    for thread_id in waiting_threads {
+        ecx.unblock_thread(thread_id, BlockReason::WaitForObjects)?;
+    }
+    interp_ok(())
+}
+
+/// Sets or resets an event, and notifies the waiting threads if it got set.
+fn set_event<'tcx>(
+    event: &Event,
+    signaled: bool,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    if signaled {
+        // Remember this clock so the threads that wait for the event can synchronize with us.
+        ecx.release_clock(|clock| {
+            event.clock.borrow_mut().join(clock);
+        });
+        event.signaled.set(true);
+        notify_object_waiters(ecx)?;
+    } else {
+        event.signaled.set(false);
+    }
+    interp_ok(())
+}
+
+/// Sets or resets the event in the `hEvent` field of an `OVERLAPPED` structure, which may be null.
+/// Returns `false` if the handle does not refer to an event.
+pub fn set_overlapped_event<'tcx>(
+    handle: Scalar,
+    signaled: bool,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, bool> {
+    if handle.to_target_usize(ecx)? == 0 {
+        return interp_ok(true);
+    }
+    if let Ok(Handle::File(fd_num)) = Handle::try_from_scalar(handle, ecx)?
+        && let Some(fd) = ecx.machine.fds.get(fd_num)
+        && let Some(event) = fd.downcast::<Event>()
+    {
+        set_event(event, signaled, ecx)?;
+        return interp_ok(true);
+    }
+    interp_ok(false)
+}
+
+/// Waits until one of the `objects` is signaled, or all of them if `wait_all` is set, or until the
+/// `deadline` (computed by `deadline_after`) has passed.
+fn wait_for_objects<'tcx>(
+    objects: Vec<WaitObject>,
+    wait_all: bool,
+    deadline: Option<u64>,
+    dest: &MPlaceTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx> {
+    let signals = objects
+        .iter()
+        .map(|object| signal_state(object, ecx))
+        .collect::<InterpResult<'_, Vec<_>>>()?;
+    let acquired: Vec<_> = if wait_all {
+        if signals.contains(&Signal::NotSignaled) {
+            Vec::new()
+        } else {
+            (0..objects.len()).collect()
+        }
+    } else {
+        signals.iter().position(|&signal| signal != Signal::NotSignaled).into_iter().collect()
+    };
+    if let Some(&first) = acquired.first() {
+        for &i in &acquired {
+            acquire_object(&objects[i], ecx)?;
+        }
+        // When waiting for all objects, an abandoned mutex is reported with the lowest index.
+        let (base, index) = match acquired.iter().find(|&&i| signals[i] == Signal::Abandoned) {
+            Some(&abandoned) => (WAIT_ABANDONED_0, abandoned),
+            None => (ecx.eval_windows_u32("c", "WAIT_OBJECT_0"), first),
+        };
+        let index = u32::try_from(index).unwrap();
+        return ecx.write_scalar(Scalar::from_u32(base.strict_add(index)), dest);
+    }
+    if deadline_passed(deadline, ecx) {
+        return ecx.write_scalar(ecx.eval_windows("c", "WAIT_TIMEOUT"), dest);
+    }
+
+    ecx.machine.blocked_object_wait_tid.push(ecx.active_thread());
+    let dest = dest.clone();
+    ecx.block_thread(
+        BlockReason::WaitForObjects,
+        deadline_timeout(deadline),
+        callback!(
+            @capture<'tcx> {
+                objects: Vec<WaitObject>,
+                wait_all: bool,
+                deadline: Option<u64>,
+                dest: MPlaceTy<'tcx>,
+            }
+            @unblock = |this| {
+                wait_for_objects(objects, wait_all, deadline, &dest, this)
+            }
+            @timeout = |this| {
+                let active_thread = this.active_thread();
+                this.machine.blocked_object_wait_tid.retain(|&id| id != active_thread);
+                this.write_scalar(this.eval_windows("c", "WAIT_TIMEOUT"), &dest)
+            }
+        ),
+    );
+    interp_ok(())
+}
+
+/// Reads a handle that `WaitForSingleObject` and `WaitForMultipleObjects` can wait on. Aborts the
+/// program if it is invalid.
+fn read_wait_object<'tcx>(
+    handle: Scalar,
+    function_name: &str,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, WaitObject> {
+    interp_ok(match Handle::try_from_scalar(handle, ecx)? {
+        Ok(Handle::Thread(thread)) => WaitObject::Thread(thread),
+        // Unlike on posix, the outcome of joining the current thread is not documented.
+        // On current Windows, it just deadlocks.
+        Ok(Handle::Pseudo(PseudoHandle::CurrentThread)) => WaitObject::Thread(ecx.active_thread()),
+        Ok(Handle::File(fd_num)) => {
+            let fd = ecx.machine.fds.get(fd_num).unwrap();
+            if fd.downcast::<Event>().is_none() && fd.downcast::<Mutex>().is_none() {
+                throw_unsup_format!("`{function_name}` on a {} is not supported", fd.name());
+            }
+            WaitObject::Object(fd.downgrade())
+        }
+        _ => ecx.invalid_handle(function_name)?,
+    })
+}
+
+/// Reads a handle that must refer to an event or a mutex, depending on `T`. Aborts the program if
+/// it does not.
+fn read_object_handle<'tcx, T: FileDescription>(
+    handle_op: &OpTy<'tcx>,
+    function_name: &str,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, FileDescriptionRef> {
+    let handle = ecx.read_scalar(handle_op)?;
+    if let Ok(Handle::File(fd_num)) = Handle::try_from_scalar(handle, ecx)?
+        && let Some(fd) = ecx.machine.fds.get(fd_num)
+        && fd.downcast::<T>().is_some()
+    {
+        return interp_ok(fd);
+    }
+    ecx.invalid_handle(function_name)?
+}
+
+/// Reads the timeout of a wait in milliseconds, where `INFINITE` means no timeout, and returns
+/// the deadline for it.
+pub fn read_wait_deadline<'tcx>(
+    timeout_op: &OpTy<'tcx>,
+    ecx: &mut MiriInterpCx<'tcx>,
+) -> InterpResult<'tcx, Option<u64>> {
+    let timeout = ecx.read_scalar(timeout_op)?.to_u32()?;
+    let timeout = if timeout == ecx.eval_windows_u32("c", "INFINITE") {
+        None
+    } else {
+        Some(Duration::from_millis(timeout.into()))
+    };
+    interp_ok(deadline_after(timeout, ecx))
+}
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+
+#[allow(non_snake_case)]
+pub trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn WaitForSingleObject(
+        &mut self,
+        handle_op: &OpTy<'tcx>,
+        timeout_op: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let handle = this.read_scalar(handle_op)?;
+        let object = read_wait_object(handle, "WaitForSingleObject", this)?;
+        let deadline = read_wait_deadline(timeout_op, this)?;
+
+        wait_for_objects(vec![object], /* wait_all */ false, deadline, dest, this)
+    }
+
+    fn WaitForMultipleObjects(
+        &mut self,
+        count_op: &OpTy<'tcx>,
+        handles_op: &OpTy<'tcx>,
+        wait_all_op: &OpTy<'tcx>,
+        timeout_op: &OpTy<'tcx>,
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx> {
+        let this = self.eval_context_mut();
+
+        let count = this.read_scalar(count_op)?.to_u32()?;
+        let handles = this.read_pointer(handles_op)?;
+        let wait_all = this.read_scalar(wait_all_op)?.to_i32()? != 0;
+        let deadline = read_wait_deadline(timeout_op, this)?;
+
+        if count == 0 || count > MAXIMUM_WAIT_OBJECTS {
+            this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+            return this.write_scalar(Scalar::from_u32(WAIT_FAILED), dest);
+        }
+        let handle_layout = this.machine.layouts.isize;
+        let mut objects = Vec::new();
+        for i in 0..u64::from(count) {
+            let handle = handles.wrapping_offset(handle_layout.size * i, this);
+            let handle = this.read_scalar(&this.ptr_to_mplace(handle, handle_layout))?;
+            objects.push(read_wait_object(handle, "WaitForMultipleObjects", this)?);
+        }
+        // Waiting for the same mutex twice would be ambiguous.
+        if wait_all {
+            let fds: Vec<_> = objects
+                .iter()
+                .filter_map(|object| {
+                    match object {
+                        WaitObject::Object(fd) => fd.upgrade().map(|fd| fd.get_id()),
+                        WaitObject::Thread(_) => None,
+                    }
+                })
+                .collect();
+            if (1..fds.len()).any(|i| fds[..i].contains(&fds[i])) {
+                this.set_last_error(IoError::WindowsError("ERROR_INVALID_PARAMETER"))?;
+                return this.write_scalar(Scalar::from_u32(WAIT_FAILED), dest);
+            }
+        }
+
+        wait_for_objects(objects, wait_all, deadline, dest, this)
+    }
+
+    fn CreateEventW(
+        &mut self,
+        security_attributes: &OpTy<'tcx>,
+        manual_reset: &OpTy<'tcx>,
+        initial_state: &OpTy<'tcx>,
+        name: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let security_attributes = this.read_pointer(security_attributes)?;
+        let manual_reset = this.read_scalar(manual_reset)?.to_i32()? != 0;
+        let initial_state = this.read_scalar(initial_state)?.to_i32()? != 0;
+        let name = this.read_pointer(name)?;
+
+        if !this.ptr_is_null(security_attributes)? {
+            throw_unsup_format!("CreateEventW: security attributes are not supported");
+        }
+        if !this.ptr_is_null(name)? {
+            throw_unsup_format!("CreateEventW: named events are not supported");
+        }
+        let fd_num = this.machine.fds.insert_new(Event {
+            manual_reset,
+            signaled: Cell::new(initial_state),
+            clock: RefCell::new(VClock::default()),
+        });
+        interp_ok(Handle::File(fd_num).to_scalar(this))
+    }
+
+    fn SetEvent(&mut self, event_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let event = read_object_handle::<Event>(event_op, "SetEvent", this)?;
+        set_event(event.downcast::<Event>().unwrap(), /* signaled */ true, this)?;
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn ResetEvent(&mut self, event_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let event = read_object_handle::<Event>(event_op, "ResetEvent", this)?;
+        set_event(event.downcast::<Event>().unwrap(), /* signaled */ false, this)?;
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+
+    fn CreateMutexW(
+        &mut self,
+        security_attributes: &OpTy<'tcx>,
+        initial_owner: &OpTy<'tcx>,
+        name: &OpTy<'tcx>,
+    ) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let security_attributes = this.read_pointer(security_attributes)?;
+        let initial_owner = this.read_scalar(initial_owner)?.to_i32()? != 0;
+        let name = this.read_pointer(name)?;
+
+        if !this.ptr_is_null(security_attributes)? {
+            throw_unsup_format!("CreateMutexW: security attributes are not supported");
+        }
+        if !this.ptr_is_null(name)? {
+            throw_unsup_format!("CreateMutexW: named mutexes are not supported");
+        }
+        let mutex = Mutex::default();
+        if initial_owner {
+            mutex.owner.set(Some(this.active_thread()));
+            mutex.count.set(1);
+        }
+        let fd_num = this.machine.fds.insert_new(mutex);
+        interp_ok(Handle::File(fd_num).to_scalar(this))
+    }
+
+    fn ReleaseMutex(&mut self, mutex_op: &OpTy<'tcx>) -> InterpResult<'tcx, Scalar> {
+        let this = self.eval_context_mut();
+        let mutex = read_object_handle::<Mutex>(mutex_op, "ReleaseMutex", this)?;
+        let mutex = mutex.downcast::<Mutex>().unwrap();
+
+        if mutex.owner.get() != Some(this.active_thread()) {
+            this.set_last_error(IoError::WindowsError("ERROR_NOT_OWNER"))?;
+            return interp_ok(this.eval_windows("c", "FALSE"));
+        }
+        let count = mutex.count.get().strict_sub(1);
+        mutex.count.set(count);
+        if count == 0 {
+            mutex.owner.set(None);
+            // Remember this clock so the next owner can synchronize with us.
+            this.release_clock(|clock| {
+                mutex.clock.borrow_mut().join(clock);
+            });
+            notify_object_waiters(this)?;
+        }
+        interp_ok(this.eval_windows("c", "TRUE"))
+    }
+}
@@ -121,8 +121,31 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
 
                 this.write_scalar(Scalar::from_u128(u128::from_le_bytes(state.into())), &dest)?;
             }
-            // TODO: Implement the `llvm.x86.aesni.aeskeygenassist` when possible
-            // with an external crate.
+            // Used to implement the _mm_aeskeygenassist_si128 function.
+            // Assists in expanding the AES key: with X1 and X3 the second and fourth
+            // 32-bit words of `op`, the result is
+            // [SubWord(X1), RotWord(SubWord(X1)) ^ imm, SubWord(X3), RotWord(SubWord(X3)) ^ imm].
+            // https://www.intel.com/content/www/us/en/docs/intrinsics-guide/index.html#text=_mm_aeskeygenassist_si128
+            "aeskeygenassist" => {
+                let [op, imm] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                // Transmute to `u128`
+                let op = op.transmute(this.machine.layouts.u128, this)?;
+                let dest = dest.transmute(this.machine.layouts.u128, this)?;
+
+                let op = this.read_scalar(&op)?.to_u128()?;
+                let rcon = u32::from(this.read_scalar(imm)?.to_u8()?);
+
+                let x1 = sub_word((op >> 32) as u32);
+                let x3 = sub_word((op >> 96) as u32);
+                let res = u128::from(x1)
+                    | u128::from(x1.rotate_right(8) ^ rcon) << 32
+                    | u128::from(x3) << 64
+                    | u128::from(x3.rotate_right(8) ^ rcon) << 96;
+
+                this.write_scalar(Scalar::from_u128(res), &dest)?;
+            }
             _ => return interp_ok(EmulateItemResult::NotSupported),
         }
         interp_ok(EmulateItemResult::NeedsReturn)
@@ -163,3 +186,16 @@ fn aes_round<'tcx>(
 
     interp_ok(())
 }
+
+// Applies the AES S-box to each byte of `word`.
+fn sub_word(word: u32) -> u32 {
+    // Fill every column of the state with `word`, so that ShiftRows leaves it unchanged.
+    let word = u128::from(word);
+    let state = word | word << 32 | word << 64 | word << 96;
+    let mut state = aes::Block::from(state.to_le_bytes());
+    // `aes::hazmat::cipher_round` does ShiftRows, SubBytes, MixColumns and the XOR with the
+    // key. Use a zeroed key to skip the XOR, and undo the MixColumns with InvMixColumns.
+    aes::hazmat::cipher_round(&mut state, &aes::Block::from([0; 16]));
+    aes::hazmat::inv_mix_columns(&mut state);
+    u32::from_le_bytes(state[..4].try_into().unwrap())
+}
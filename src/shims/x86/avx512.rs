@@ -0,0 +1,364 @@
+use rustc_abi::ExternAbi;
+use rustc_apfloat::ieee::{Double, IeeeFloat, Semantics, Single};
+use rustc_middle::mir;
+use rustc_span::Symbol;
+
+use super::{
+    FloatBinOp, ShiftOp, bin_op_simd_float_all, rounding_from_imm, shift_simd_by_scalar,
+    shift_simd_by_simd,
+};
+use crate::*;
+
+impl<'tcx> EvalContextExt<'tcx> for crate::MiriInterpCx<'tcx> {}
+pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
+    fn emulate_x86_avx512_intrinsic(
+        &mut self,
+        link_name: Symbol,
+        abi: ExternAbi,
+        args: &[OpTy<'tcx>],
+        dest: &MPlaceTy<'tcx>,
+    ) -> InterpResult<'tcx, EmulateItemResult> {
+        let this = self.eval_context_mut();
+        this.expect_target_feature_for_intrinsic(link_name, "avx512f")?;
+        // Prefix should have already been checked.
+        let unprefixed_name = link_name.as_str().strip_prefix("llvm.x86.avx512.").unwrap();
+        // The 128-bit and 256-bit variants of AVX-512 instructions additionally require the
+        // "vector length" extension.
+        if unprefixed_name.ends_with(".128") || unprefixed_name.ends_with(".256") {
+            this.expect_target_feature_for_intrinsic(link_name, "avx512vl")?;
+        }
+
+        match unprefixed_name {
+            // Used to implement the _mm{,256,512}_{,mask_}cmp_{ps,pd}_mask functions.
+            // Performs a comparison operation on each component of `left` and `right`
+            // and returns a bitmask, where bit `i` is set if the comparison is true for
+            // component `i` and bit `i` of `mask` is set.
+            "mask.cmp.ps.128" | "mask.cmp.ps.256" | "mask.cmp.ps.512" | "mask.cmp.pd.128"
+            | "mask.cmp.pd.256" | "mask.cmp.pd.512" => {
+                let (left, right, imm, mask) = if unprefixed_name.ends_with(".512") {
+                    // The last argument can only suppress floating-point exceptions, which
+                    // Miri does not emulate.
+                    let [left, right, imm, mask, _sae] =
+                        this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                    (left, right, imm, mask)
+                } else {
+                    let [left, right, imm, mask] =
+                        this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+                    (left, right, imm, mask)
+                };
+
+                let imm = i8::try_from(this.read_scalar(imm)?.to_i32()?).unwrap();
+                let which = FloatBinOp::cmp_from_imm(this, imm, link_name)?;
+
+                if unprefixed_name.starts_with("mask.cmp.ps") {
+                    cmp_mask::<Single>(this, which, left, right, mask, dest)?;
+                } else {
+                    cmp_mask::<Double>(this, which, left, right, mask, dest)?;
+                }
+            }
+            // Used to implement the _mm512_{min,max}_{ps,pd} and _mm512_{min,max}_round_{ps,pd}
+            // functions. Like in SSE and AVX, NaN and zero operands are handled differently
+            // than by the Rust simd_min and simd_max intrinsics.
+            "min.ps.512" | "max.ps.512" | "min.pd.512" | "max.pd.512" => {
+                // The last argument can only suppress floating-point exceptions, which Miri
+                // does not emulate.
+                let [left, right, _sae] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let which = if unprefixed_name.starts_with("min") {
+                    FloatBinOp::Min
+                } else {
+                    FloatBinOp::Max
+                };
+
+                if unprefixed_name.ends_with(".ps.512") {
+                    bin_op_simd_float_all::<Single>(this, which, left, right, dest)?;
+                } else {
+                    bin_op_simd_float_all::<Double>(this, which, left, right, dest)?;
+                }
+            }
+            // Used to implement the _mm512_{add,sub,mul,div}_round_{ps,pd} functions.
+            // Performs the operation on each component of `left` and `right`, rounding
+            // the results as specified by `rounding`.
+            "add.ps.512" | "sub.ps.512" | "mul.ps.512" | "div.ps.512" | "add.pd.512"
+            | "sub.pd.512" | "mul.pd.512" | "div.pd.512" => {
+                let [left, right, rounding] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let which = match &unprefixed_name[..3] {
+                    "add" => mir::BinOp::Add,
+                    "sub" => mir::BinOp::Sub,
+                    "mul" => mir::BinOp::Mul,
+                    "div" => mir::BinOp::Div,
+                    _ => unreachable!(),
+                };
+
+                if unprefixed_name.ends_with(".ps.512") {
+                    bin_op_simd_float_round::<Single>(this, which, left, right, rounding, dest)?;
+                } else {
+                    bin_op_simd_float_round::<Double>(this, which, left, right, rounding, dest)?;
+                }
+            }
+            // Used to implement the _mm512_sqrt_round_{ps,pd} functions.
+            "sqrt.ps.512" | "sqrt.pd.512" => {
+                let [op, rounding] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                if unprefixed_name == "sqrt.ps.512" {
+                    sqrt_simd::<rustc_apfloat::ieee::SingleS>(this, op, rounding, dest)?;
+                } else {
+                    sqrt_simd::<rustc_apfloat::ieee::DoubleS>(this, op, rounding, dest)?;
+                }
+            }
+            // Used to implement the _mm{,256,512}_ternarylogic_epi{32,64} functions.
+            // Computes an arbitrary bitwise function of `a`, `b` and `c`: for every bit
+            // position, the bits of `a`, `b` and `c` select a bit of the truth table `imm`.
+            // https://www.felixcloutier.com/x86/vpternlogd:vpternlogq
+            "pternlog.d.128" | "pternlog.d.256" | "pternlog.d.512" | "pternlog.q.128"
+            | "pternlog.q.256" | "pternlog.q.512" => {
+                let [a, b, c, imm] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (a, a_len) = this.project_to_simd(a)?;
+                let (b, b_len) = this.project_to_simd(b)?;
+                let (c, c_len) = this.project_to_simd(c)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(dest_len, a_len);
+                assert_eq!(dest_len, b_len);
+                assert_eq!(dest_len, c_len);
+
+                let imm = this.read_scalar(imm)?.to_u32()?;
+
+                for i in 0..dest_len {
+                    let dest = this.project_index(&dest, i)?;
+                    let size = dest.layout.size;
+                    let a = this.read_scalar(&this.project_index(&a, i)?)?.to_uint(size)?;
+                    let b = this.read_scalar(&this.project_index(&b, i)?)?.to_uint(size)?;
+                    let c = this.read_scalar(&this.project_index(&c, i)?)?.to_uint(size)?;
+
+                    // Bit `k` of the truth table is used where `a`, `b` and `c` have the
+                    // bits of `k`, from most to least significant.
+                    let mut res = 0;
+                    for k in 0..8 {
+                        if imm & (1 << k) != 0 {
+                            let a = if k & 0b100 != 0 { a } else { !a };
+                            let b = if k & 0b010 != 0 { b } else { !b };
+                            let c = if k & 0b001 != 0 { c } else { !c };
+                            res |= a & b & c;
+                        }
+                    }
+                    this.write_scalar(Scalar::from_uint(size.truncate(res), size), &dest)?;
+                }
+            }
+            // Used to implement the _mm512_{sll,srl,sra}_epi{32,64} and the
+            // _mm{,256}_sra_epi64 functions.
+            // Shifts each element by the amount in the lowest 64 bits of `right`.
+            "psll.d.512" | "psrl.d.512" | "psra.d.512" | "psll.q.512" | "psrl.q.512"
+            | "psra.q.128" | "psra.q.256" | "psra.q.512" => {
+                let [left, right] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let which = match &unprefixed_name[..4] {
+                    "psll" => ShiftOp::Left,
+                    "psrl" => ShiftOp::RightLogic,
+                    "psra" => ShiftOp::RightArith,
+                    _ => unreachable!(),
+                };
+
+                shift_simd_by_scalar(this, left, right, which, dest)?;
+            }
+            // Used to implement the _mm512_{sllv,srlv,srav}_epi{32,64} and the
+            // _mm{,256}_srav_epi64 functions.
+            // Shifts each element of `left` by the corresponding element of `right`.
+            "psllv.d.512" | "psrlv.d.512" | "psrav.d.512" | "psllv.q.512" | "psrlv.q.512"
+            | "psrav.q.128" | "psrav.q.256" | "psrav.q.512" => {
+                let [left, right] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let which = match &unprefixed_name[..5] {
+                    "psllv" => ShiftOp::Left,
+                    "psrlv" => ShiftOp::RightLogic,
+                    "psrav" => ShiftOp::RightArith,
+                    _ => unreachable!(),
+                };
+
+                shift_simd_by_simd(this, left, right, which, dest)?;
+            }
+            // Used to implement the _mm{256,512}_permutexvar_{epi32,epi64,ps,pd} functions.
+            // Shuffles the elements of `left` across the whole vector, using the low bits of
+            // the corresponding element of `idx` as index.
+            "permvar.si.512" | "permvar.sf.512" | "permvar.di.256" | "permvar.df.256"
+            | "permvar.di.512" | "permvar.df.512" => {
+                let [left, idx] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (left, left_len) = this.project_to_simd(left)?;
+                let (idx, idx_len) = this.project_to_simd(idx)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(dest_len, left_len);
+                assert_eq!(dest_len, idx_len);
+
+                for i in 0..dest_len {
+                    let idx = this.project_index(&idx, i)?;
+                    let idx = this.read_scalar(&idx)?.to_uint(idx.layout.size)?;
+                    let src = this
+                        .project_index(&left, u64::try_from(idx).unwrap().strict_rem(left_len))?;
+                    this.copy_op(&src, &this.project_index(&dest, i)?)?;
+                }
+            }
+            // Used to implement the _mm512_permutex2var_{epi32,epi64,ps,pd} functions.
+            // Shuffles the elements of `a` and `b` across the whole vector, using the
+            // corresponding element of `idx` as index: its low bits select the element, and
+            // the bit above them selects the vector.
+            "vpermi2var.d.512" | "vpermi2var.q.512" | "vpermi2var.ps.512" | "vpermi2var.pd.512" => {
+                let [a, idx, b] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (a, a_len) = this.project_to_simd(a)?;
+                let (idx, idx_len) = this.project_to_simd(idx)?;
+                let (b, b_len) = this.project_to_simd(b)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(dest_len, a_len);
+                assert_eq!(dest_len, idx_len);
+                assert_eq!(dest_len, b_len);
+
+                for i in 0..dest_len {
+                    let idx = this.project_index(&idx, i)?;
+                    let idx = u64::try_from(this.read_scalar(&idx)?.to_uint(idx.layout.size)?)
+                        .unwrap()
+                        .strict_rem(a_len.strict_mul(2));
+                    let src = if idx < a_len {
+                        this.project_index(&a, idx)?
+                    } else {
+                        this.project_index(&b, idx.strict_sub(a_len))?
+                    };
+                    this.copy_op(&src, &this.project_index(&dest, i)?)?;
+                }
+            }
+            _ => return interp_ok(EmulateItemResult::NotSupported),
+        }
+        interp_ok(EmulateItemResult::NeedsReturn)
+    }
+}
+
+/// Compares each component of `left` and `right` with `which` (which must be a
+/// `FloatBinOp::Cmp`) and writes the results as a bitmask to `dest`. Components
+/// whose bit in `mask` is not set yield 0.
+fn cmp_mask<'tcx, F: rustc_apfloat::Float>(
+    ecx: &mut crate::MiriInterpCx<'tcx>,
+    which: FloatBinOp,
+    left: &OpTy<'tcx>,
+    right: &OpTy<'tcx>,
+    mask: &OpTy<'tcx>,
+    dest: &MPlaceTy<'tcx>,
+) -> InterpResult<'tcx, ()> {
+    let FloatBinOp::Cmp { gt, lt, eq, unord } = which else {
+        unreachable!();
+    };
+
+    let (left, left_len) = ecx.project_to_simd(left)?;
+    let (right, right_len) = ecx.project_to_simd(right)?;
+
+    assert_eq!(left_len, right_len);
+    assert!(left_len <= dest.layout.size.bits());
+
+    let mask = ecx.read_scalar(mask)?.to_uint(mask.layout.size)?;
+
+    let mut res = 0u128;
+    for i in 0..left_len {
+        let left = ecx.read_scalar(&ecx.project_index(&left, i)?)?.to_float::<F>()?;
+        let right = ecx.read_scalar(&ecx.project_index(&right, i)?)?.to_float::<F>()?;
+
+        let cmp = match left.partial_cmp(&right) {
+            None => unord,
+            Some(std::cmp::Ordering::Less) => lt,
+            Some(std::cmp::Ordering::Equal) => eq,
+            Some(std::cmp::Ordering::Greater) => gt,
+        };
+        if cmp && mask & (1 << i) != 0 {
+            res |= 1 << i;
+        }
+    }
+
+    ecx.write_scalar(Scalar::from_uint(res, dest.layout.size), dest)?;
+
+    interp_ok(())
+}
+
+/// Performs `which` operation (one of `Add`, `Sub`, `Mul` or `Div`) on each
+/// component of `left` and `right`, rounding as specified by `rounding`, and
+/// stores the results in `dest`.
+fn bin_op_simd_float_round<'tcx, F>(
+    ecx: &mut crate::MiriInterpCx<'tcx>,
+    which: mir::BinOp,
+    left: &OpTy<'tcx>,
+    right: &OpTy<'tcx>,
+    rounding: &OpTy<'tcx>,
+    dest: &MPlaceTy<'tcx>,
+) -> InterpResult<'tcx, ()>
+where
+    F: rustc_apfloat::Float + rustc_apfloat::FloatConvert<F> + Into<Scalar>,
+{
+    let (left, left_len) = ecx.project_to_simd(left)?;
+    let (right, right_len) = ecx.project_to_simd(right)?;
+    let (dest, dest_len) = ecx.project_to_simd(dest)?;
+
+    assert_eq!(dest_len, left_len);
+    assert_eq!(dest_len, right_len);
+
+    let rounding = rounding_from_imm(ecx.read_scalar(rounding)?.to_i32()?)?;
+
+    for i in 0..dest_len {
+        let left = ecx.read_scalar(&ecx.project_index(&left, i)?)?.to_float::<F>()?;
+        let right = ecx.read_scalar(&ecx.project_index(&right, i)?)?.to_float::<F>()?;
+        let dest = ecx.project_index(&dest, i)?;
+
+        let res = match which {
+            mir::BinOp::Add => left.add_r(right, rounding),
+            mir::BinOp::Sub => left.sub_r(right, rounding),
+            mir::BinOp::Mul => left.mul_r(right, rounding),
+            mir::BinOp::Div => left.div_r(right, rounding),
+            _ => unreachable!(),
+        }
+        .value;
+        let res = ecx.adjust_nan(res, &[left, right]);
+        ecx.write_scalar(res, &dest)?;
+    }
+
+    interp_ok(())
+}
+
+/// Computes the square root of each component of `op` and stores the results
+/// in `dest`. Only rounding to nearest is supported.
+fn sqrt_simd<'tcx, S: Semantics>(
+    ecx: &mut crate::MiriInterpCx<'tcx>,
+    op: &OpTy<'tcx>,
+    rounding: &OpTy<'tcx>,
+    dest: &MPlaceTy<'tcx>,
+) -> InterpResult<'tcx, ()>
+where
+    IeeeFloat<S>: Into<Scalar>,
+{
+    let (op, op_len) = ecx.project_to_simd(op)?;
+    let (dest, dest_len) = ecx.project_to_simd(dest)?;
+
+    assert_eq!(dest_len, op_len);
+
+    let rounding = rounding_from_imm(ecx.read_scalar(rounding)?.to_i32()?)?;
+    if rounding != rustc_apfloat::Round::NearestTiesToEven {
+        throw_unsup_format!("square root with rounding mode {rounding:?} is not supported");
+    }
+
+    for i in 0..dest_len {
+        let op = ecx.read_scalar(&ecx.project_index(&op, i)?)?.to_float::<IeeeFloat<S>>()?;
+        let dest = ecx.project_index(&dest, i)?;
+
+        let res = ecx.adjust_nan(math::sqrt(op), &[op]);
+        ecx.write_scalar(res, &dest)?;
+    }
+
+    interp_ok(())
+}
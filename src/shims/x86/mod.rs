@@ -1,6 +1,7 @@
+use rand::Rng as _;
 use rustc_abi::{ExternAbi, Size};
-use rustc_apfloat::Float;
-use rustc_apfloat::ieee::Single;
+use rustc_apfloat::ieee::{Half, Single};
+use rustc_apfloat::{Float, FloatConvert as _};
 use rustc_middle::ty::Ty;
 use rustc_middle::ty::layout::LayoutOf as _;
 use rustc_middle::{mir, ty};
@@ -12,6 +13,7 @@ use crate::*;
 mod aesni;
 mod avx;
 mod avx2;
+mod avx512;
 mod bmi;
 mod gfni;
 mod sha;
@@ -81,6 +83,35 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 this.write_immediate(*sum, &out)?;
             }
 
+            // Used to implement the `_rdrand{16, 32, 64}_step` and `_rdseed{16, 32, 64}_step`
+            // functions. Returns random bits together with 1, or 0 together with 0 when the
+            // hardware random number generator has no entropy available. The bits come from
+            // Miri's RNG. Failures are only simulated with `-Zmiri-rdrand-failure-rate`, which
+            // exercises the retry loops that callers need.
+            // https://www.intel.com/content/www/us/en/docs/cpp-compiler/developer-guide-reference/2021-8/rdrand16-step-rdrand32-step-rdrand64-step.html
+            // https://www.intel.com/content/www/us/en/docs/cpp-compiler/developer-guide-reference/2021-8/rdseed16-step-rdseed32-step-rdseed64-step.html
+            "rdrand.16" | "rdrand.32" | "rdrand.64" | "rdseed.16" | "rdseed.32" | "rdseed.64" => {
+                let (feature, bits) = unprefixed_name.split_once('.').unwrap();
+                if bits == "64" && this.tcx.sess.target.arch != "x86_64" {
+                    return interp_ok(EmulateItemResult::NotSupported);
+                }
+                this.expect_target_feature_for_intrinsic(link_name, feature)?;
+
+                let [] = this.check_shim(abi, ExternAbi::Unadjusted, link_name, args)?;
+
+                let failure_rate = this.machine.rdrand_failure_rate;
+                let value_place = this.project_field(dest, 0)?;
+                let size = value_place.layout.size;
+                let rng = this.machine.rng.get_mut();
+                let (value, success) = if rng.gen_bool(failure_rate) {
+                    (0, 0)
+                } else {
+                    (size.truncate(rng.gen::<u64>().into()), 1)
+                };
+                this.write_scalar(Scalar::from_uint(value, size), &value_place)?;
+                this.write_scalar(Scalar::from_i32(success), &this.project_field(dest, 1)?)?;
+            }
+
             // Used to implement the `_mm_pause` function.
             // The intrinsic is used to hint the processor that the code is in a spin-loop.
             // It is compiled down to a `pause` instruction. When SSE2 is not available,
@@ -112,6 +143,56 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                 pclmulqdq(this, left, right, imm, dest, len)?;
             }
 
+            // Used to implement the `_mm_cvtph_ps` and `_mm256_cvtph_ps` functions.
+            // Converts the half-precision floats in the low elements of `op` (given as
+            // 16-bit integers) to single precision.
+            "vcvtph2ps.128" | "vcvtph2ps.256" => {
+                this.expect_target_feature_for_intrinsic(link_name, "f16c")?;
+
+                let [op] = this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (op, op_len) = this.project_to_simd(op)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert!(dest_len <= op_len);
+
+                for i in 0..dest_len {
+                    let op = this.read_scalar(&this.project_index(&op, i)?)?.to_u16()?;
+                    // The conversion is exact. NaNs are quieted and keep their payload.
+                    let res: Single = Half::from_bits(op.into()).convert(&mut false).value;
+                    this.write_scalar(Scalar::from_f32(res), &this.project_index(&dest, i)?)?;
+                }
+            }
+            // Used to implement the `_mm_cvtps_ph` and `_mm256_cvtps_ph` functions.
+            // Converts the single-precision floats of `op` to half precision (given as 16-bit
+            // integers), rounding as specified by `rounding`. The remaining elements of `dest`
+            // are set to zero.
+            "vcvtps2ph.128" | "vcvtps2ph.256" => {
+                this.expect_target_feature_for_intrinsic(link_name, "f16c")?;
+
+                let [op, rounding] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (op, op_len) = this.project_to_simd(op)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert!(op_len <= dest_len);
+
+                // Only the three lowest bits are used.
+                let rounding = rounding_from_imm(this.read_scalar(rounding)?.to_i32()? & 0b111)?;
+
+                for i in 0..dest_len {
+                    let res = if i < op_len {
+                        let op = this.read_scalar(&this.project_index(&op, i)?)?.to_f32()?;
+                        let res: Half = op.convert_r(rounding, &mut false).value;
+                        u16::try_from(res.to_bits()).unwrap()
+                    } else {
+                        0
+                    };
+                    this.write_scalar(Scalar::from_u16(res), &this.project_index(&dest, i)?)?;
+                }
+            }
+
             name if name.starts_with("bmi.") => {
                 return bmi::EvalContextExt::emulate_x86_bmi_intrinsic(
                     this, link_name, abi, args, dest,
@@ -174,6 +255,11 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
                     this, link_name, abi, args, dest,
                 );
             }
+            name if name.starts_with("avx512.") => {
+                return avx512::EvalContextExt::emulate_x86_avx512_intrinsic(
+                    this, link_name, abi, args, dest,
+                );
+            }
 
             _ => return interp_ok(EmulateItemResult::NotSupported),
         }
@@ -1,6 +1,7 @@
-//! Implements sha256 SIMD instructions of x86 targets
+//! Implements sha1 and sha256 SIMD instructions of x86 targets
 //!
 //! The functions that actually compute SHA256 were copied from [RustCrypto's sha256 module].
+//! The SHA1 functions follow the pseudocode in Intel's documentation of the instructions.
 //!
 //! [RustCrypto's sha256 module]: https://github.com/RustCrypto/hashes/blob/6be8466247e936c415d8aafb848697f39894a386/sha2/src/sha256/soft.rs
 
@@ -49,6 +50,86 @@ pub(super) trait EvalContextExt<'tcx>: crate::MiriInterpCxExt<'tcx> {
         }
 
         match unprefixed_name {
+            // Used to implement the _mm_sha1rnds4_epu32 function.
+            // Performs four rounds of SHA1 on the state `a` with the message words (plus E)
+            // `b`, using the round function and constant selected by `func`.
+            "1rnds4" => {
+                let [a, b, func] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (a_reg, a_len) = this.project_to_simd(a)?;
+                let (b_reg, b_len) = this.project_to_simd(b)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(a_len, 4);
+                assert_eq!(b_len, 4);
+                assert_eq!(dest_len, 4);
+
+                let a = read(this, &a_reg)?;
+                let b = read(this, &b_reg)?;
+                // Only the two lowest bits are used.
+                let func = this.read_scalar(func)?.to_u8()? & 0b11;
+
+                let result = sha1_digest_round_x4(a, b, func);
+                write(this, &dest, result)?;
+            }
+            // Used to implement the _mm_sha1nexte_epu32 function.
+            "1nexte" => {
+                let [a, b] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (a_reg, a_len) = this.project_to_simd(a)?;
+                let (b_reg, b_len) = this.project_to_simd(b)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(a_len, 4);
+                assert_eq!(b_len, 4);
+                assert_eq!(dest_len, 4);
+
+                let a = read(this, &a_reg)?;
+                let b = read(this, &b_reg)?;
+
+                let result = sha1nexte(a, b);
+                write(this, &dest, result)?;
+            }
+            // Used to implement the _mm_sha1msg1_epu32 function.
+            "1msg1" => {
+                let [a, b] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (a_reg, a_len) = this.project_to_simd(a)?;
+                let (b_reg, b_len) = this.project_to_simd(b)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(a_len, 4);
+                assert_eq!(b_len, 4);
+                assert_eq!(dest_len, 4);
+
+                let a = read(this, &a_reg)?;
+                let b = read(this, &b_reg)?;
+
+                let result = sha1msg1(a, b);
+                write(this, &dest, result)?;
+            }
+            // Used to implement the _mm_sha1msg2_epu32 function.
+            "1msg2" => {
+                let [a, b] =
+                    this.check_shim(abi, ExternAbi::C { unwind: false }, link_name, args)?;
+
+                let (a_reg, a_len) = this.project_to_simd(a)?;
+                let (b_reg, b_len) = this.project_to_simd(b)?;
+                let (dest, dest_len) = this.project_to_simd(dest)?;
+
+                assert_eq!(a_len, 4);
+                assert_eq!(b_len, 4);
+                assert_eq!(dest_len, 4);
+
+                let a = read(this, &a_reg)?;
+                let b = read(this, &b_reg)?;
+
+                let result = sha1msg2(a, b);
+                write(this, &dest, result)?;
+            }
             // Used to implement the _mm_sha256rnds2_epu32 function.
             "256rnds2" => {
                 let [a, b, k] =
@@ -145,6 +226,59 @@ fn add(a: [u32; 4], b: [u32; 4]) -> [u32; 4] {
     ]
 }
 
+fn sha1_digest_round_x4(abcd: [u32; 4], msg: [u32; 4], func: u8) -> [u32; 4] {
+    fn ch(b: u32, c: u32, d: u32) -> u32 {
+        (b & c) ^ (!b & d)
+    }
+    fn parity(b: u32, c: u32, d: u32) -> u32 {
+        b ^ c ^ d
+    }
+    fn maj(b: u32, c: u32, d: u32) -> u32 {
+        (b & c) ^ (b & d) ^ (c & d)
+    }
+
+    let (f, k): (fn(u32, u32, u32) -> u32, u32) = match func {
+        0 => (ch, 0x5A827999),
+        1 => (parity, 0x6ED9EBA1),
+        2 => (maj, 0x8F1BBCDC),
+        3 => (parity, 0xCA62C1D6),
+        _ => unreachable!(),
+    };
+
+    // E has already been added to the first message word.
+    let [mut a, mut b, mut c, mut d] = abcd;
+    let mut e = 0;
+    for w in msg {
+        let t = f(b, c, d).wrapping_add(a.rotate_left(5)).wrapping_add(w).wrapping_add(e);
+        (a, b, c, d, e) = (t.wrapping_add(k), a, b.rotate_left(30), c, d);
+    }
+
+    [a, b, c, d]
+}
+
+fn sha1nexte(a: [u32; 4], b: [u32; 4]) -> [u32; 4] {
+    [b[0].wrapping_add(a[0].rotate_left(30)), b[1], b[2], b[3]]
+}
+
+fn sha1msg1(a: [u32; 4], b: [u32; 4]) -> [u32; 4] {
+    let [w0, w1, w2, w3] = a;
+    let [w4, w5, _, _] = b;
+
+    [w0 ^ w2, w1 ^ w3, w2 ^ w4, w3 ^ w5]
+}
+
+fn sha1msg2(a: [u32; 4], b: [u32; 4]) -> [u32; 4] {
+    let [x0, x1, x2, x3] = a;
+    let [_, w13, w14, w15] = b;
+
+    let w16 = (x0 ^ w13).rotate_left(1);
+    let w17 = (x1 ^ w14).rotate_left(1);
+    let w18 = (x2 ^ w15).rotate_left(1);
+    let w19 = (x3 ^ w16).rotate_left(1);
+
+    [w16, w17, w18, w19]
+}
+
 fn sha256load(v2: [u32; 4], v3: [u32; 4]) -> [u32; 4] {
     [v3[3], v2[0], v2[1], v2[2]]
 }
@@ -0,0 +1,13 @@
+//@ignore-target: windows # No pthreads on Windows
+// The deadlock report points out the signal that happened before anybody was waiting.
+
+fn main() {
+    let mut mutex = libc::PTHREAD_MUTEX_INITIALIZER;
+    let mut cond = libc::PTHREAD_COND_INITIALIZER;
+    unsafe {
+        // Nobody is waiting yet, so this wakeup is lost.
+        assert_eq!(libc::pthread_cond_signal(&mut cond), 0);
+        assert_eq!(libc::pthread_mutex_lock(&mut mutex), 0);
+        libc::pthread_cond_wait(&mut cond, &mut mutex); //~ ERROR: deadlock
+    }
+}
@@ -0,0 +1,42 @@
+//@ignore-target: windows # No pthreads on Windows
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::cell::UnsafeCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+struct Mutex(UnsafeCell<libc::pthread_mutex_t>);
+
+unsafe impl Send for Mutex {}
+unsafe impl Sync for Mutex {}
+
+fn new_lock() -> Arc<Mutex> {
+    Arc::new(Mutex(UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER)))
+}
+
+fn main() {
+    let first = new_lock();
+    let second = new_lock();
+    let second_locked = Arc::new(AtomicBool::new(false));
+    unsafe {
+        libc::pthread_mutex_lock(first.0.get());
+    }
+
+    // Acquire the locks in the opposite order in another thread.
+    let first_copy = first.clone();
+    let second_copy = second.clone();
+    let second_locked_copy = second_locked.clone();
+    thread::spawn(move || unsafe {
+        libc::pthread_mutex_lock(second_copy.0.get());
+        second_locked_copy.store(true, Ordering::Release);
+        libc::pthread_mutex_lock(first_copy.0.get()); //~ ERROR: deadlock
+    });
+
+    while !second_locked.load(Ordering::Acquire) {
+        thread::yield_now();
+    }
+    unsafe {
+        libc::pthread_mutex_lock(second.0.get()); //~ ERROR: deadlock
+    }
+}
@@ -0,0 +1,36 @@
+//@ignore-target: windows # No pthreads on Windows
+//@revisions: tsan json
+//@compile-flags: -Zmiri-preemption-rate=0
+//@[tsan]compile-flags: -Zmiri-race-report-format=tsan
+//@[json]compile-flags: -Zmiri-race-report-format=json
+
+use std::ffi::c_void;
+use std::ptr;
+
+static mut DATA: u32 = 0;
+
+extern "C" fn thread_write(_: *mut c_void) -> *mut c_void {
+    unsafe { DATA = 1 };
+    ptr::null_mut()
+}
+
+extern "C" fn thread_read(_: *mut c_void) -> *mut c_void {
+    let _val = unsafe { DATA }; //~ ERROR: Data race detected
+    ptr::null_mut()
+}
+
+fn main() {
+    unsafe {
+        let mut writer: libc::pthread_t = 0;
+        let mut reader: libc::pthread_t = 0;
+        assert_eq!(
+            libc::pthread_create(&mut writer, ptr::null(), thread_write, ptr::null_mut()),
+            0
+        );
+        assert_eq!(libc::pthread_create(&mut reader, ptr::null(), thread_read, ptr::null_mut()), 0);
+        // Joining the writer lets it run, and then the reader runs without having synchronized
+        // with the writer.
+        assert_eq!(libc::pthread_join(writer, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_join(reader, ptr::null_mut()), 0);
+    }
+}
@@ -0,0 +1,10 @@
+//@only-target: linux freebsd android # pthread spin locks are not available everywhere
+
+fn main() {
+    unsafe {
+        let mut lock: libc::pthread_spinlock_t = std::mem::zeroed();
+        assert_eq!(libc::pthread_spin_init(&mut lock as *mut _, libc::PTHREAD_PROCESS_PRIVATE), 0);
+        assert_eq!(libc::pthread_spin_lock(&mut lock as *mut _), 0);
+        libc::pthread_spin_destroy(&mut lock as *mut _); //~ ERROR: destroyed a locked spin lock
+    }
+}
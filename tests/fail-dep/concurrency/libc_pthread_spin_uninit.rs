@@ -0,0 +1,8 @@
+//@only-target: linux freebsd android # pthread spin locks are not available everywhere
+
+fn main() {
+    unsafe {
+        let mut lock: libc::pthread_spinlock_t = std::mem::zeroed();
+        libc::pthread_spin_lock(&mut lock as *mut _); //~ ERROR: `pthread_spinlock_t` was used without `pthread_spin_init`
+    }
+}
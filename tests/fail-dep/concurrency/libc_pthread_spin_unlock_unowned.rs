@@ -0,0 +1,9 @@
+//@only-target: linux freebsd android # pthread spin locks are not available everywhere
+
+fn main() {
+    unsafe {
+        let mut lock: libc::pthread_spinlock_t = std::mem::zeroed();
+        assert_eq!(libc::pthread_spin_init(&mut lock as *mut _, libc::PTHREAD_PROCESS_PRIVATE), 0);
+        libc::pthread_spin_unlock(&mut lock as *mut _); //~ ERROR: unlocked a spin lock that was not locked by the current thread
+    }
+}
@@ -0,0 +1,31 @@
+//@ignore-target: windows # No pthreads on Windows
+//@compile-flags: -Zmiri-report-thread-leaks -Zmiri-preemption-rate=0
+//@error-in-other-file: thread leaked
+
+// Check that leaked threads are reported with where they were spawned and what they are doing.
+
+use std::{mem, ptr, thread};
+
+static mut MUTEX: libc::pthread_mutex_t = libc::PTHREAD_MUTEX_INITIALIZER;
+
+extern "C" fn lock_mutex(_null: *mut libc::c_void) -> *mut libc::c_void {
+    unsafe { libc::pthread_mutex_lock(&raw mut MUTEX) };
+    ptr::null_mut()
+}
+
+extern "C" fn sleep(_null: *mut libc::c_void) -> *mut libc::c_void {
+    let req = libc::timespec { tv_sec: 3600, tv_nsec: 0 };
+    unsafe { libc::nanosleep(&req, ptr::null_mut()) };
+    ptr::null_mut()
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(libc::pthread_mutex_lock(&raw mut MUTEX), 0);
+        let mut native: libc::pthread_t = mem::zeroed();
+        assert_eq!(libc::pthread_create(&mut native, ptr::null(), lock_mutex, ptr::null_mut()), 0);
+        assert_eq!(libc::pthread_create(&mut native, ptr::null(), sleep, ptr::null_mut()), 0);
+    }
+    // Let both threads run until they block.
+    thread::yield_now();
+}
@@ -0,0 +1,20 @@
+//@only-target: linux freebsd android # POSIX semaphores are not available everywhere
+
+use std::cell::UnsafeCell;
+use std::thread;
+use std::time::Duration;
+
+struct Sem(UnsafeCell<libc::sem_t>);
+unsafe impl Sync for Sem {}
+
+static SEM: Sem = Sem(UnsafeCell::new(unsafe { std::mem::zeroed() }));
+
+fn main() {
+    unsafe {
+        assert_eq!(libc::sem_init(SEM.0.get(), 0, 0), 0);
+        thread::spawn(|| libc::sem_wait(SEM.0.get()));
+        // Give the other thread time to block on the semaphore.
+        thread::sleep(Duration::from_millis(100));
+        libc::sem_destroy(SEM.0.get()); //~ ERROR: destroyed a semaphore that threads are waiting on
+    }
+}
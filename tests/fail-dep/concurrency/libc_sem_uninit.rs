@@ -0,0 +1,8 @@
+//@only-target: linux freebsd android # POSIX semaphores are not available everywhere
+
+fn main() {
+    unsafe {
+        let mut sem: libc::sem_t = std::mem::zeroed();
+        libc::sem_post(&mut sem as *mut _); //~ ERROR: `sem_t` was used without `sem_init`
+    }
+}
@@ -0,0 +1,32 @@
+//@only-target: windows # Uses win32 api functions
+// We are making scheduler assumptions here.
+//@compile-flags: -Zmiri-preemption-rate=0
+//@error-in-other-file: deadlock
+
+// The description given with `SetThreadDescription` shows up in the deadlock report.
+
+use std::thread;
+
+use windows_sys::Win32::Foundation::{HANDLE, WAIT_OBJECT_0};
+use windows_sys::Win32::System::Threading::{
+    GetCurrentThread, INFINITE, SetThreadDescription, WaitForSingleObject,
+};
+
+fn name_current_thread() -> HANDLE {
+    let name: Vec<u16> = "worker\0".encode_utf16().collect();
+    unsafe {
+        assert_eq!(SetThreadDescription(GetCurrentThread(), name.as_ptr()), 0);
+        GetCurrentThread()
+    }
+}
+
+fn main() {
+    thread::spawn(|| {
+        unsafe {
+            let native = name_current_thread();
+            assert_eq!(WaitForSingleObject(native, INFINITE), WAIT_OBJECT_0); //~ ERROR: deadlock: the evaluated program deadlocked
+        }
+    })
+    .join()
+    .unwrap();
+}
@@ -0,0 +1,5 @@
+//@ignore-target: windows # No fork on Windows
+
+fn main() {
+    let _pid = unsafe { libc::fork() }; //~ERROR: `fork` is not supported
+}
@@ -0,0 +1,34 @@
+//@only-target: linux android
+// The program assumes that ready events are reported in the order the file descriptions were
+// created. Exploring the epoll wakeup order finds a run where that is not the case.
+//@compile-flags: -Zmiri-epoll-wakeup-order=explore
+//@normalize-stderr-test: "exploring \d+ schedule" -> "exploring N schedule"
+
+fn readable_eventfd() -> i32 {
+    let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+    assert_ne!(fd, -1);
+    let data: [u8; 8] = 1_u64.to_ne_bytes();
+    let res = unsafe { libc::write(fd, data.as_ptr() as *const libc::c_void, 8) };
+    assert_eq!(res, 8);
+    fd
+}
+
+fn main() {
+    let epfd = unsafe { libc::epoll_create1(0) };
+    assert_ne!(epfd, -1);
+    let fd1 = readable_eventfd();
+    let fd2 = readable_eventfd();
+    for fd in [fd1, fd2] {
+        let mut ev =
+            libc::epoll_event { events: libc::EPOLLIN as u32, u64: u64::try_from(fd).unwrap() };
+        let res = unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, fd, &mut ev) };
+        assert_eq!(res, 0);
+    }
+
+    let mut array = [libc::epoll_event { events: 0, u64: 0 }; 1];
+    let res = unsafe { libc::epoll_wait(epfd, array.as_mut_ptr(), 1, 0) };
+    assert_eq!(res, 1);
+    if array[0].u64 != u64::try_from(fd1).unwrap() {
+        unsafe { std::hint::unreachable_unchecked() } //~ERROR: entering unreachable code
+    }
+}
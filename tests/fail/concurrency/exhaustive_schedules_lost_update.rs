@@ -0,0 +1,26 @@
+// The update is only lost if the spawned thread runs between the load and the store of the main
+// thread, which needs a preemption.
+//@revisions: dpor no_dpor
+//@compile-flags: -Zmiri-exhaustive-schedules=1
+//@[no_dpor]compile-flags: -Zmiri-disable-partial-order-reduction
+//@normalize-stderr-test: "exploring \d+ schedule" -> "exploring N schedule"
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::thread;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn increment() {
+    let value = COUNTER.load(SeqCst);
+    COUNTER.store(value + 1, SeqCst);
+}
+
+fn main() {
+    let handle = thread::spawn(increment);
+    increment();
+    handle.join().unwrap();
+    if COUNTER.load(SeqCst) != 2 {
+        unsafe { std::hint::unreachable_unchecked() } //~ERROR: entering unreachable code
+    }
+}
@@ -0,0 +1,25 @@
+// The failing schedule found by exploration is shrunk to the switches needed to lose the update.
+//@compile-flags: -Zmiri-exhaustive-schedules=1 -Zmiri-minimize-schedule
+//@normalize-stderr-test: "exploring \d+ schedule" -> "exploring N schedule"
+//@normalize-stderr-test: "from \d+ to \d+ scheduling event" -> "from N to M scheduling event"
+//@normalize-stderr-test: "\n\d+ (switch|timeout) \d+" -> ""
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::thread;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn increment() {
+    let value = COUNTER.load(SeqCst);
+    COUNTER.store(value + 1, SeqCst);
+}
+
+fn main() {
+    let handle = thread::spawn(increment);
+    increment();
+    handle.join().unwrap();
+    if COUNTER.load(SeqCst) != 2 {
+        unsafe { std::hint::unreachable_unchecked() } //~ERROR: entering unreachable code
+    }
+}
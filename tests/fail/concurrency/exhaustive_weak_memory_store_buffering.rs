@@ -0,0 +1,27 @@
+// Both threads can read the initial value of the location that the other thread writes, since
+// nothing orders the relaxed accesses. This only happens if a load reads an outdated store.
+//@compile-flags: -Zmiri-exhaustive-weak-memory
+//@normalize-stderr-test: "exploring \d+ schedule" -> "exploring N schedule"
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+
+static X: AtomicUsize = AtomicUsize::new(0);
+static Y: AtomicUsize = AtomicUsize::new(0);
+
+fn main() {
+    // Make sure both locations have a store buffer.
+    X.store(0, Relaxed);
+    Y.store(0, Relaxed);
+    let handle = thread::spawn(|| {
+        Y.store(1, Relaxed);
+        X.load(Relaxed)
+    });
+    X.store(1, Relaxed);
+    let a = Y.load(Relaxed);
+    let b = handle.join().unwrap();
+    if a == 0 && b == 0 {
+        unsafe { std::hint::unreachable_unchecked() } //~ERROR: entering unreachable code
+    }
+}
@@ -0,0 +1,28 @@
+// We want to control preemption here.
+//@compile-flags: -Zmiri-preemption-rate=0
+// Avoid accidental synchronization via address reuse inside `thread::spawn`.
+//@compile-flags: -Zmiri-address-reuse-cross-thread-rate=0
+
+use std::thread::spawn;
+
+static mut CONFIG: u32 = 0;
+
+// A lazy initialization without any synchronization.
+fn get_or_init() -> u32 {
+    unsafe {
+        let value = CONFIG; //~ ERROR: Data race detected between (1) non-atomic write on thread `unnamed-1` and (2) non-atomic read on thread `unnamed-2`
+        if value == 0 {
+            CONFIG = 42;
+            42
+        } else {
+            value
+        }
+    }
+}
+
+pub fn main() {
+    let j1 = spawn(|| get_or_init());
+    let j2 = spawn(|| get_or_init());
+    j1.join().unwrap();
+    j2.join().unwrap();
+}
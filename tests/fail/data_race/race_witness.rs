@@ -0,0 +1,41 @@
+// We want to control preemption here. Stacked borrows interferes by having its own accesses.
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-disable-stacked-borrows -Zmiri-race-witness
+// Avoid accidental synchronization via address reuse, and make the loads see the latest stores.
+//@compile-flags: -Zmiri-address-reuse-cross-thread-rate=0 -Zmiri-disable-weak-memory-emulation
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{spawn, yield_now};
+
+#[derive(Copy, Clone)]
+struct EvilSend<T>(pub T);
+
+unsafe impl<T> Send for EvilSend<T> {}
+unsafe impl<T> Sync for EvilSend<T> {}
+
+static DONE: AtomicBool = AtomicBool::new(false);
+static EXIT: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    let mut a = 0u32;
+    let b = &mut a as *mut u32;
+    let c = EvilSend(b);
+
+    let _join = spawn(move || {
+        let c = c; // avoid field capturing
+        unsafe { *c.0 = 32 };
+        yield_now();
+        DONE.store(true, Ordering::Relaxed);
+        while !EXIT.load(Ordering::Relaxed) {
+            yield_now();
+        }
+    });
+
+    // This does not synchronize at all.
+    while !DONE.load(Ordering::Relaxed) {
+        yield_now();
+    }
+
+    unsafe {
+        *c.0 = 64; //~ ERROR: Data race detected between (1) non-atomic write on thread `unnamed-1` and (2) non-atomic write on thread `main`
+    }
+}
@@ -0,0 +1,36 @@
+// We want to control preemption here. Stacked borrows interferes by having its own accesses.
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-disable-stacked-borrows -Zmiri-spin-loop-warnings
+// Avoid accidental synchronization via address reuse, and make the loads see the latest stores.
+//@compile-flags: -Zmiri-address-reuse-cross-thread-rate=0 -Zmiri-disable-weak-memory-emulation
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{spawn, yield_now};
+
+#[derive(Copy, Clone)]
+struct EvilSend<T>(pub T);
+
+unsafe impl<T> Send for EvilSend<T> {}
+unsafe impl<T> Sync for EvilSend<T> {}
+
+static READY: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    let mut a = 0u32;
+    let b = &mut a as *mut u32;
+    let c = EvilSend(b);
+
+    let _join = spawn(move || {
+        let c = c; // avoid field capturing
+        unsafe { *c.0 = 32 };
+        READY.store(true, Ordering::Release);
+    });
+
+    // The loop should use `Acquire` to synchronize with the store.
+    while !READY.load(Ordering::Relaxed) {
+        yield_now();
+    }
+
+    unsafe {
+        let _val = *c.0; //~ ERROR: Data race detected between (1) non-atomic write on thread `unnamed-1` and (2) non-atomic read on thread `main`
+    }
+}
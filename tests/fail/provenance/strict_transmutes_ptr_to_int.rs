@@ -0,0 +1,9 @@
+//@compile-flags: -Zmiri-strict-transmutes
+
+fn main() {
+    let x = 0i32;
+    let ptr = &x as *const i32;
+    // Casting is fine, the provenance gets exposed.
+    let _addr = ptr as usize;
+    let _addr: usize = unsafe { std::mem::transmute(ptr) }; //~ ERROR: transmuting a pointer to an integer is not supported
+}
@@ -0,0 +1,7 @@
+//@compile-flags: -Zmiri-strict-transmutes
+
+fn main() {
+    let x = 0i32;
+    let ptr = &x as *const i32;
+    let _addr: usize = unsafe { std::mem::transmute_copy(&ptr) }; //~ ERROR: transmuting a pointer to an integer is not supported
+}
@@ -0,0 +1,15 @@
+//@compile-flags: -Zmiri-strict-transmutes
+
+union PtrOrInt {
+    ptr: *const i32,
+    int: usize,
+}
+
+fn main() {
+    let x = 0i32;
+    // Going through `with_exposed_provenance` is fine.
+    let addr = (&x as *const i32).expose_provenance();
+    let u = PtrOrInt { ptr: std::ptr::with_exposed_provenance(addr) };
+    let _ptr = unsafe { u.ptr };
+    let _int = unsafe { u.int }; //~ ERROR: reading a pointer as an integer through a union field is not supported
+}
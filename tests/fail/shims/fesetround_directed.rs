@@ -0,0 +1,19 @@
+//@only-target: x86_64 i686 aarch64
+
+// The value of `FE_UPWARD`.
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), not(windows)))]
+const FE_UPWARD: i32 = 0x800;
+#[cfg(all(target_arch = "aarch64", not(windows)))]
+const FE_UPWARD: i32 = 0x400000;
+#[cfg(windows)]
+const FE_UPWARD: i32 = 0x200;
+
+extern "C" {
+    fn fesetround(mode: i32) -> i32;
+}
+
+fn main() {
+    unsafe {
+        fesetround(FE_UPWARD); //~ ERROR: unsupported operation
+    }
+}
@@ -0,0 +1,19 @@
+//@compile-flags: -Zmiri-compare-borrow-trackers
+
+// A mutable reborrow invalidates `z` for reading under Stacked Borrows, but not under Tree Borrows.
+fn main() {
+    let mut x = 0i32;
+    let z = &mut x as *mut i32;
+    x.do_bad();
+    unsafe {
+        let _oof = *z; //~ ERROR: /read access .* tag does not exist in the borrow stack/
+    }
+}
+
+trait Bad {
+    fn do_bad(&mut self) {
+        // who knows
+    }
+}
+
+impl Bad for i32 {}
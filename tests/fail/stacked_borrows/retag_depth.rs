@@ -0,0 +1,21 @@
+//@compile-flags: -Zmiri-retag-depth=1
+// With `-Zmiri-retag-depth`, retagging `&mut s` also retags the mutable reference stored in `s`.
+// This catches that `t` holds a second copy of that reference.
+
+use std::ptr;
+
+struct S<'a> {
+    r: &'a mut i32,
+}
+
+fn bump(s: &mut S<'_>) {
+    *s.r += 1;
+}
+
+fn main() {
+    let mut x = 0;
+    let mut s = S { r: &mut x };
+    let t = unsafe { ptr::read(&s) }; // duplicate the mutable reference
+    bump(&mut s);
+    *t.r = 42; //~ ERROR: /write access .* tag does not exist in the borrow stack/
+}
@@ -0,0 +1,29 @@
+//@revisions: default uniq
+//@compile-flags: -Zmiri-tree-borrows
+//@[uniq]compile-flags: -Zmiri-not-unpin-is-unique
+
+// A pattern that detects if `&mut` to a `!Unpin` type is treated as unique or not:
+// activate the reference then do a read that is foreign iff the reference
+// got its own tag.
+
+use std::marker::PhantomPinned;
+
+struct NotUnpin(u8, PhantomPinned);
+
+fn main() {
+    let mut data = NotUnpin(0, PhantomPinned);
+    // `&mut u8` is `Unpin`, so this gets a new tag in both revisions.
+    let refmut = unsafe { &mut *(&raw mut data as *mut u8) };
+    let rawptr = refmut as *mut u8 as *mut NotUnpin;
+
+    unsafe {
+        let pinned = &mut *rawptr;
+        pinned.0 = 1; // activation
+        let _maybe_foreign = *(rawptr as *const u8); // maybe becomes Frozen
+        pinned.0 = 2;
+        //~[uniq]^ ERROR: /write access through .* is forbidden/
+        let _definitely_foreign = data.0; // definitely Frozen by now
+        pinned.0 = 3;
+        //~[default]^ ERROR: /write access through .* is forbidden/
+    }
+}
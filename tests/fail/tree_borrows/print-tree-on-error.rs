@@ -0,0 +1,27 @@
+// We disable the GC for this test because it would change what is printed.
+//@compile-flags: -Zmiri-tree-borrows -Zmiri-tree-borrows-print-tree -Zmiri-provenance-gc=0
+
+#[path = "../../utils/mod.rs"]
+#[macro_use]
+mod utils;
+
+// Check that the error shows the part of the tree between the accessed
+// and the conflicting tag.
+fn main() {
+    unsafe {
+        let n = &mut 0u8;
+        name!(n);
+        let x = &mut *(n as *mut _);
+        name!(x);
+        let y = (&mut *n) as *mut _;
+        name!(y);
+        write_second(x, y);
+        unsafe fn write_second(x: &mut u8, y: *mut u8) {
+            name!(x, "callee:x");
+            name!(x=>1, "caller:x");
+            name!(y, "callee:y");
+            name!(y, "caller:y");
+            *y = 0; //~ ERROR: /write access through .* is forbidden/
+        }
+    }
+}
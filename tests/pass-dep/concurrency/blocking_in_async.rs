@@ -0,0 +1,28 @@
+//@ignore-target: windows # uses libc::nanosleep
+//@compile-flags: -Zmiri-detect-blocking-in-async
+
+use std::future::Future;
+use std::pin::pin;
+use std::ptr;
+use std::task::{Context, Poll, Waker};
+
+fn sleep() {
+    let req = libc::timespec { tv_sec: 0, tv_nsec: 1_000_000 };
+    assert_eq!(unsafe { libc::nanosleep(&req, ptr::null_mut()) }, 0);
+}
+
+async fn wait_a_bit() {
+    for _ in 0..2 {
+        // Only the first iteration is reported.
+        let req = libc::timespec { tv_sec: 0, tv_nsec: 1_000_000 };
+        unsafe { libc::nanosleep(&req, ptr::null_mut()) };
+    }
+}
+
+fn main() {
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut fut = pin!(async { wait_a_bit().await });
+    assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(()));
+    // Blocking outside of a future is fine.
+    sleep();
+}
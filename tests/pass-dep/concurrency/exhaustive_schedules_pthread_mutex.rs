@@ -0,0 +1,37 @@
+// Locking and unlocking a pthread mutex are scheduling decisions, so every interleaving of the
+// critical sections is explored. They all have to pass, and none of them may race.
+//@ignore-target: windows # No pthreads on Windows
+//@compile-flags: -Zmiri-exhaustive-schedules=2
+
+use std::cell::UnsafeCell;
+use std::thread;
+
+struct Shared {
+    mutex: UnsafeCell<libc::pthread_mutex_t>,
+    counter: UnsafeCell<u32>,
+}
+
+unsafe impl Sync for Shared {}
+
+static SHARED: Shared =
+    Shared { mutex: UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER), counter: UnsafeCell::new(0) };
+
+fn increment() {
+    unsafe {
+        assert_eq!(libc::pthread_mutex_lock(SHARED.mutex.get()), 0);
+        let value = *SHARED.counter.get();
+        *SHARED.counter.get() = value + 1;
+        assert_eq!(libc::pthread_mutex_unlock(SHARED.mutex.get()), 0);
+    }
+}
+
+fn main() {
+    let handle = thread::spawn(increment);
+    increment();
+    handle.join().unwrap();
+    unsafe {
+        assert_eq!(libc::pthread_mutex_lock(SHARED.mutex.get()), 0);
+        assert_eq!(*SHARED.counter.get(), 2);
+        assert_eq!(libc::pthread_mutex_unlock(SHARED.mutex.get()), 0);
+    }
+}
@@ -0,0 +1,219 @@
+//@only-target: linux # gettid is only available on Linux
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::mem::offset_of;
+use std::ptr::{self, addr_of};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const FUTEX_WAITERS: u32 = 0x8000_0000;
+const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+
+fn tid() -> u32 {
+    unsafe { libc::gettid() as u32 }
+}
+
+fn lock_pi(futex: &AtomicU32) -> libc::c_long {
+    unsafe {
+        libc::syscall(libc::SYS_futex, futex, libc::FUTEX_LOCK_PI, 0, ptr::null::<libc::timespec>())
+    }
+}
+
+fn trylock_pi(futex: &AtomicU32) -> libc::c_long {
+    unsafe { libc::syscall(libc::SYS_futex, futex, libc::FUTEX_TRYLOCK_PI) }
+}
+
+fn unlock_pi(futex: &AtomicU32) -> libc::c_long {
+    unsafe { libc::syscall(libc::SYS_futex, futex, libc::FUTEX_UNLOCK_PI) }
+}
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn lock_unlock() {
+    let futex = AtomicU32::new(0);
+    assert_eq!(lock_pi(&futex), 0);
+    assert_eq!(futex.load(Ordering::Relaxed), tid());
+
+    // We already own it.
+    assert_eq!(lock_pi(&futex), -1);
+    assert_eq!(errno(), libc::EDEADLK);
+    assert_eq!(trylock_pi(&futex), -1);
+    assert_eq!(errno(), libc::EDEADLK);
+
+    assert_eq!(unlock_pi(&futex), 0);
+    assert_eq!(futex.load(Ordering::Relaxed), 0);
+    // Now we do not own it anymore.
+    assert_eq!(unlock_pi(&futex), -1);
+    assert_eq!(errno(), libc::EPERM);
+
+    // A TID that belongs to no thread.
+    futex.store(FUTEX_TID_MASK, Ordering::Relaxed);
+    assert_eq!(lock_pi(&futex), -1);
+    assert_eq!(errno(), libc::ESRCH);
+}
+
+fn contended() {
+    static FUTEX: AtomicU32 = AtomicU32::new(0);
+    static mut DATA: i32 = 0;
+
+    assert_eq!(lock_pi(&FUTEX), 0);
+
+    let waiters: Vec<_> = (0..2)
+        .map(|_| {
+            thread::spawn(|| {
+                assert_eq!(trylock_pi(&FUTEX), -1);
+                assert_eq!(errno(), libc::EAGAIN);
+                // Once we are woken up, we own the futex.
+                assert_eq!(lock_pi(&FUTEX), 0);
+                assert_eq!(FUTEX.load(Ordering::Relaxed) & FUTEX_TID_MASK, tid());
+                // Unlocking happens-before the next owner is woken up.
+                unsafe { DATA += 1 };
+                assert_eq!(unlock_pi(&FUTEX), 0);
+            })
+        })
+        .collect();
+    // Make sure both threads are waiting.
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(FUTEX.load(Ordering::Relaxed), tid() | FUTEX_WAITERS);
+
+    unsafe { DATA += 1 };
+    assert_eq!(unlock_pi(&FUTEX), 0);
+    for t in waiters {
+        t.join().unwrap();
+    }
+    assert_eq!(FUTEX.load(Ordering::Relaxed), 0);
+    assert_eq!(unsafe { DATA }, 3);
+}
+
+fn lock_timeout() {
+    static FUTEX: AtomicU32 = AtomicU32::new(0);
+
+    assert_eq!(lock_pi(&FUTEX), 0);
+    let t = thread::spawn(|| {
+        // The timeout is absolute, measured against the realtime clock.
+        let deadline =
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + Duration::from_millis(200);
+        let timeout = libc::timespec {
+            tv_sec: deadline.as_secs().try_into().unwrap(),
+            tv_nsec: deadline.subsec_nanos().into(),
+        };
+        unsafe {
+            assert_eq!(
+                libc::syscall(libc::SYS_futex, addr_of!(FUTEX), libc::FUTEX_LOCK_PI, 0, &timeout),
+                -1,
+            );
+        }
+        assert_eq!(errno(), libc::ETIMEDOUT);
+    });
+    t.join().unwrap();
+    // Nobody is waiting anymore, so unlocking frees the futex.
+    assert_eq!(unlock_pi(&FUTEX), 0);
+    assert_eq!(FUTEX.load(Ordering::Relaxed), 0);
+}
+
+#[repr(C)]
+struct RobustList {
+    next: *mut RobustList,
+}
+
+#[repr(C)]
+struct RobustListHead {
+    list: RobustList,
+    futex_offset: isize,
+    list_op_pending: *mut RobustList,
+}
+
+#[repr(C)]
+struct RobustMutex {
+    list: RobustList,
+    futex: AtomicU32,
+}
+
+impl RobustMutex {
+    const fn new() -> Self {
+        RobustMutex { list: RobustList { next: ptr::null_mut() }, futex: AtomicU32::new(0) }
+    }
+}
+
+impl RobustListHead {
+    const fn new() -> Self {
+        RobustListHead {
+            list: RobustList { next: ptr::null_mut() },
+            futex_offset: (offset_of!(RobustMutex, futex) - offset_of!(RobustMutex, list)) as isize,
+            list_op_pending: ptr::null_mut(),
+        }
+    }
+}
+
+/// Registers a robust list for the current thread that contains just `mutex`.
+/// Returns the futex of the mutex.
+fn register_robust_list(
+    head: *mut RobustListHead,
+    mutex: *mut RobustMutex,
+    pi: bool,
+) -> &'static AtomicU32 {
+    unsafe {
+        // The list is circular. The lowest bit marks PI futexes.
+        let entry = mutex.cast::<RobustList>();
+        (*head).list.next = if pi { entry.map_addr(|addr| addr | 1) } else { entry };
+        (*mutex).list.next = head.cast::<RobustList>();
+
+        assert_eq!(libc::syscall(libc::SYS_set_robust_list, head, size_of::<RobustListHead>()), 0);
+        let mut registered = ptr::null_mut::<RobustListHead>();
+        let mut len = 0usize;
+        assert_eq!(libc::syscall(libc::SYS_get_robust_list, 0, &mut registered, &mut len), 0);
+        assert_eq!(registered, head);
+        assert_eq!(len, size_of::<RobustListHead>());
+
+        &(*mutex).futex
+    }
+}
+
+fn robust_owner_died() {
+    static mut HEAD: RobustListHead = RobustListHead::new();
+    static mut MUTEX: RobustMutex = RobustMutex::new();
+
+    let futex = thread::spawn(|| {
+        let futex = register_robust_list(&raw mut HEAD, &raw mut MUTEX, false);
+        // Take the lock like userspace would, and exit without unlocking it.
+        futex.store(tid(), Ordering::Relaxed);
+        futex
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(futex.load(Ordering::Relaxed), FUTEX_OWNER_DIED);
+}
+
+fn robust_pi_owner_died() {
+    static mut HEAD: RobustListHead = RobustListHead::new();
+    static mut MUTEX: RobustMutex = RobustMutex::new();
+
+    let (send, recv) = mpsc::channel();
+    let t = thread::spawn(move || {
+        let futex = register_robust_list(&raw mut HEAD, &raw mut MUTEX, true);
+        assert_eq!(lock_pi(futex), 0);
+        send.send(futex).unwrap();
+        // Give the main thread time to start waiting, then exit without unlocking.
+        thread::sleep(Duration::from_millis(200));
+    });
+    let futex = recv.recv().unwrap();
+
+    // The dying owner hands us the futex.
+    assert_eq!(lock_pi(futex), 0);
+    assert_eq!(futex.load(Ordering::Relaxed), tid() | FUTEX_OWNER_DIED);
+    t.join().unwrap();
+}
+
+fn main() {
+    lock_unlock();
+    contended();
+    lock_timeout();
+    robust_owner_died();
+    robust_pi_owner_died();
+}
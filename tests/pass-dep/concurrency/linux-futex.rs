@@ -287,6 +287,120 @@ fn concurrent_wait_wake() {
     assert!(woken > 0 && woken < rounds);
 }
 
+fn requeue() {
+    static FUTEX: AtomicI32 = AtomicI32::new(0);
+    static TARGET: AtomicI32 = AtomicI32::new(0);
+
+    let waiters: Vec<_> = (0..3)
+        .map(|_| {
+            thread::spawn(|| unsafe {
+                assert_eq!(
+                    libc::syscall(
+                        libc::SYS_futex,
+                        addr_of!(FUTEX),
+                        libc::FUTEX_WAIT,
+                        0,
+                        ptr::null::<libc::timespec>(),
+                    ),
+                    0,
+                );
+            })
+        })
+        .collect();
+    // Make sure all threads are waiting.
+    thread::sleep(Duration::from_millis(200));
+
+    unsafe {
+        // Wake one thread, and move one of the others to `TARGET`.
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                addr_of!(FUTEX),
+                libc::FUTEX_REQUEUE,
+                1,      // Wake up at most 1 thread.
+                1usize, // Requeue at most 1 thread.
+                addr_of!(TARGET),
+            ),
+            1, // Woken up one thread.
+        );
+        // The futex value does not match, so nothing happens.
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                addr_of!(FUTEX),
+                libc::FUTEX_CMP_REQUEUE,
+                0,
+                i32::MAX as usize,
+                addr_of!(TARGET),
+                1,
+            ),
+            -1,
+        );
+        assert_eq!(io::Error::last_os_error().raw_os_error().unwrap(), libc::EAGAIN);
+        // Move the last thread over.
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                addr_of!(FUTEX),
+                libc::FUTEX_CMP_REQUEUE,
+                0,
+                i32::MAX as usize,
+                addr_of!(TARGET),
+                0,
+            ),
+            1, // Requeued one thread.
+        );
+        // Nobody is left waiting on `FUTEX`...
+        assert_eq!(libc::syscall(libc::SYS_futex, addr_of!(FUTEX), libc::FUTEX_WAKE, 10), 0);
+        // ... because they are all on `TARGET` now.
+        assert_eq!(libc::syscall(libc::SYS_futex, addr_of!(TARGET), libc::FUTEX_WAKE, 10), 2);
+    }
+
+    for t in waiters {
+        t.join().unwrap();
+    }
+}
+
+fn requeue_timeout() {
+    static FUTEX: AtomicI32 = AtomicI32::new(0);
+    static TARGET: AtomicI32 = AtomicI32::new(0);
+
+    let t = thread::spawn(|| unsafe {
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                addr_of!(FUTEX),
+                libc::FUTEX_WAIT,
+                0,
+                &libc::timespec { tv_sec: 0, tv_nsec: 400_000_000 },
+            ),
+            -1,
+        );
+        assert_eq!(io::Error::last_os_error().raw_os_error().unwrap(), libc::ETIMEDOUT);
+    });
+    thread::sleep(Duration::from_millis(200));
+
+    unsafe {
+        assert_eq!(
+            libc::syscall(
+                libc::SYS_futex,
+                addr_of!(FUTEX),
+                libc::FUTEX_REQUEUE,
+                0,
+                1usize,
+                addr_of!(TARGET),
+            ),
+            0,
+        );
+    }
+    t.join().unwrap();
+
+    // The timeout removed the thread from the futex it was requeued to.
+    unsafe {
+        assert_eq!(libc::syscall(libc::SYS_futex, addr_of!(TARGET), libc::FUTEX_WAKE, 10), 0);
+    }
+}
+
 fn main() {
     wake_nobody();
     wake_dangling();
@@ -296,4 +410,6 @@ fn main() {
     wait_wake();
     wait_wake_bitset();
     concurrent_wait_wake();
+    requeue();
+    requeue_timeout();
 }
@@ -0,0 +1,45 @@
+//@only-target: windows # Uses win32 api functions
+// We are making scheduler assumptions here.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::ffi::c_void;
+use std::ptr::null;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use windows_sys::Win32::Foundation::TRUE;
+use windows_sys::Win32::System::Threading::{
+    FLS_OUT_OF_INDEXES, FlsAlloc, FlsFree, FlsGetValue, FlsSetValue,
+};
+
+static DTOR_SUM: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "system" fn dtor(data: *const c_void) {
+    DTOR_SUM.fetch_add(data.addr(), Ordering::Relaxed);
+}
+
+fn main() {
+    let key = unsafe { FlsAlloc(Some(dtor)) };
+    assert_ne!(key, FLS_OUT_OF_INDEXES);
+    assert_eq!(unsafe { FlsGetValue(key) }, null());
+
+    // Each thread has its own value, and the callback runs when a thread exits with a value set.
+    assert_eq!(unsafe { FlsSetValue(key, null::<c_void>().wrapping_byte_add(1)) }, TRUE);
+    thread::spawn(move || {
+        assert_eq!(unsafe { FlsGetValue(key) }, null());
+        assert_eq!(unsafe { FlsSetValue(key, null::<c_void>().wrapping_byte_add(10)) }, TRUE);
+    })
+    .join()
+    .unwrap();
+    assert_eq!(DTOR_SUM.load(Ordering::Relaxed), 10);
+    // Threads that do not set a value do not run the callback.
+    thread::spawn(|| {}).join().unwrap();
+    assert_eq!(DTOR_SUM.load(Ordering::Relaxed), 10);
+    assert_eq!(unsafe { FlsGetValue(key) }, null::<c_void>().wrapping_byte_add(1));
+
+    // Keys without a callback work like plain TLS.
+    let other = unsafe { FlsAlloc(None) };
+    assert_ne!(other, key);
+    assert_eq!(unsafe { FlsSetValue(other, null::<c_void>().wrapping_byte_add(5)) }, TRUE);
+    assert_eq!(unsafe { FlsFree(other) }, TRUE);
+}
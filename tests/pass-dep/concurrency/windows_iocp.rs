@@ -0,0 +1,223 @@
+//@only-target: windows # Uses win32 api functions
+// We are making scheduler assumptions here.
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-preemption-rate=0
+
+use std::fs::{File, OpenOptions};
+use std::os::windows::fs::OpenOptionsExt;
+use std::os::windows::io::AsRawHandle;
+use std::ptr::{null, null_mut};
+use std::{mem, thread};
+
+use windows_sys::Win32::Foundation::{
+    CloseHandle, ERROR_IO_PENDING, ERROR_NOT_FOUND, ERROR_OPERATION_ABORTED, FALSE, GetLastError,
+    HANDLE, INVALID_HANDLE_VALUE, TRUE, WAIT_TIMEOUT,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX, ReadFile, WriteFile,
+};
+use windows_sys::Win32::System::IO::{
+    CancelIoEx, CreateIoCompletionPort, GetQueuedCompletionStatus, GetQueuedCompletionStatusEx,
+    OVERLAPPED, OVERLAPPED_ENTRY, PostQueuedCompletionStatus,
+};
+use windows_sys::Win32::System::Pipes::{ConnectNamedPipe, CreateNamedPipeW, PIPE_TYPE_BYTE};
+use windows_sys::Win32::System::Threading::INFINITE;
+
+#[path = "../../utils/mod.rs"]
+mod utils;
+
+fn main() {
+    test_post_and_get();
+    test_blocking_get();
+    test_get_ex();
+    test_overlapped_file();
+    test_overlapped_pipe();
+}
+
+fn create_port() -> HANDLE {
+    let port = unsafe { CreateIoCompletionPort(INVALID_HANDLE_VALUE, 0, 0, 0) };
+    assert_ne!(port, 0);
+    port
+}
+
+fn associate(handle: HANDLE, port: HANDLE, key: usize) {
+    assert_eq!(unsafe { CreateIoCompletionPort(handle, port, key, 0) }, port);
+}
+
+/// Dequeues a packet from `port`, and returns whether that succeeded, the number of bytes, the key
+/// and the `OVERLAPPED` pointer.
+fn get(port: HANDLE, timeout: u32) -> (bool, u32, usize, *mut OVERLAPPED) {
+    let mut bytes = 0;
+    let mut key = 0;
+    let mut overlapped = null_mut();
+    let res =
+        unsafe { GetQueuedCompletionStatus(port, &mut bytes, &mut key, &mut overlapped, timeout) };
+    (res == TRUE, bytes, key, overlapped)
+}
+
+fn test_post_and_get() {
+    let port = create_port();
+    // There is nothing to dequeue yet.
+    assert_eq!(get(port, 10), (false, 0, 0, null_mut()));
+    assert_eq!(unsafe { GetLastError() }, WAIT_TIMEOUT);
+
+    // Packets are dequeued in the order they were posted.
+    let mut overlapped: OVERLAPPED = unsafe { mem::zeroed() };
+    assert_eq!(unsafe { PostQueuedCompletionStatus(port, 1, 10, &mut overlapped) }, TRUE);
+    assert_eq!(unsafe { PostQueuedCompletionStatus(port, 2, 20, null()) }, TRUE);
+    assert_eq!(get(port, 0), (true, 1, 10, &raw mut overlapped));
+    assert_eq!(get(port, INFINITE), (true, 2, 20, null_mut()));
+    assert_eq!(unsafe { CloseHandle(port) }, TRUE);
+}
+
+fn test_blocking_get() {
+    let port = create_port();
+    static mut DATA: u32 = 0;
+    let port_value = port as usize;
+    let poster = thread::spawn(move || {
+        unsafe { DATA = 42 };
+        let port = port_value as HANDLE;
+        assert_eq!(unsafe { PostQueuedCompletionStatus(port, 7, 1, null()) }, TRUE);
+    });
+    // This blocks until the other thread posts, and synchronizes with it.
+    assert_eq!(get(port, INFINITE), (true, 7, 1, null_mut()));
+    assert_eq!(unsafe { DATA }, 42);
+    poster.join().unwrap();
+    assert_eq!(unsafe { CloseHandle(port) }, TRUE);
+}
+
+fn test_get_ex() {
+    let port = create_port();
+    for i in 0..3 {
+        assert_eq!(unsafe { PostQueuedCompletionStatus(port, i, i as usize, null()) }, TRUE);
+    }
+    let mut entries: [OVERLAPPED_ENTRY; 2] = unsafe { mem::zeroed() };
+    let mut removed = 0;
+    let res = unsafe {
+        GetQueuedCompletionStatusEx(port, entries.as_mut_ptr(), 2, &mut removed, 0, FALSE)
+    };
+    assert_eq!(res, TRUE);
+    assert_eq!(removed, 2);
+    assert_eq!((entries[0].lpCompletionKey, entries[0].dwNumberOfBytesTransferred), (0, 0));
+    assert_eq!((entries[1].lpCompletionKey, entries[1].dwNumberOfBytesTransferred), (1, 1));
+    let res = unsafe {
+        GetQueuedCompletionStatusEx(port, entries.as_mut_ptr(), 2, &mut removed, 0, FALSE)
+    };
+    assert_eq!(res, TRUE);
+    assert_eq!(removed, 1);
+    assert_eq!(entries[0].lpCompletionKey, 2);
+    let res = unsafe {
+        GetQueuedCompletionStatusEx(port, entries.as_mut_ptr(), 2, &mut removed, 0, FALSE)
+    };
+    assert_eq!(res, FALSE);
+    assert_eq!(unsafe { GetLastError() }, WAIT_TIMEOUT);
+    assert_eq!(unsafe { CloseHandle(port) }, TRUE);
+}
+
+fn overlapped_at(offset: u32) -> OVERLAPPED {
+    let mut overlapped: OVERLAPPED = unsafe { mem::zeroed() };
+    overlapped.Anonymous.Anonymous.Offset = offset;
+    overlapped
+}
+
+fn test_overlapped_file() {
+    let path = utils::tmp().join("miri_test_windows_iocp.txt");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(FILE_FLAG_OVERLAPPED)
+        .open(&path)
+        .unwrap();
+    let handle = file.as_raw_handle() as HANDLE;
+    let port = create_port();
+    associate(handle, port, 5);
+
+    // File operations complete right away, but still queue a packet.
+    let mut overlapped = overlapped_at(0);
+    let data = b"hello world";
+    let mut written = 0;
+    let res = unsafe {
+        WriteFile(handle, data.as_ptr(), data.len() as u32, &mut written, &mut overlapped)
+    };
+    assert_eq!(res, TRUE);
+    assert_eq!(written, 11);
+    assert_eq!(get(port, 0), (true, 11, 5, &raw mut overlapped));
+    assert_eq!(overlapped.InternalHigh, 11);
+
+    // Reads use the offset in the `OVERLAPPED` structure.
+    let mut overlapped = overlapped_at(6);
+    let mut buf = [0u8; 16];
+    let mut read = 0;
+    let res = unsafe { ReadFile(handle, buf.as_mut_ptr(), 16, &mut read, &mut overlapped) };
+    assert_eq!(res, TRUE);
+    assert_eq!(&buf[..read as usize], b"world");
+    assert_eq!(get(port, 0), (true, 5, 5, &raw mut overlapped));
+    drop(file);
+    assert_eq!(unsafe { CloseHandle(port) }, TRUE);
+}
+
+fn test_overlapped_pipe() {
+    let name = r"\\.\pipe\miri-test-iocp";
+    let wide_name: Vec<u16> = name.encode_utf16().chain([0]).collect();
+    let server = unsafe {
+        CreateNamedPipeW(
+            wide_name.as_ptr(),
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED,
+            PIPE_TYPE_BYTE,
+            1,
+            512,
+            512,
+            0,
+            null(),
+        )
+    };
+    assert_ne!(server, INVALID_HANDLE_VALUE);
+    let port = create_port();
+    associate(server, port, 1);
+
+    // Waiting for a client does not block.
+    let mut connect = overlapped_at(0);
+    assert_eq!(unsafe { ConnectNamedPipe(server, &mut connect) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_IO_PENDING);
+    let client = OpenOptions::new().read(true).write(true).open(name).unwrap();
+    assert_eq!(get(port, 0), (true, 0, 1, &raw mut connect));
+
+    // Reads stay pending until the client writes.
+    let mut overlapped = overlapped_at(0);
+    let mut buf = [0u8; 16];
+    let res = unsafe { ReadFile(server, buf.as_mut_ptr(), 16, null_mut(), &mut overlapped) };
+    assert_eq!(res, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_IO_PENDING);
+    assert_eq!(get(port, 10), (false, 0, 0, null_mut()));
+    write_client(&client, b"ping");
+    assert_eq!(get(port, 0), (true, 4, 1, &raw mut overlapped));
+    assert_eq!(&buf[..4], b"ping");
+
+    // Cancelled reads complete with an error.
+    let res = unsafe { ReadFile(server, buf.as_mut_ptr(), 16, null_mut(), &mut overlapped) };
+    assert_eq!(res, FALSE);
+    assert_eq!(unsafe { CancelIoEx(server, &overlapped) }, TRUE);
+    assert_eq!(get(port, 0), (false, 0, 1, &raw mut overlapped));
+    assert_eq!(unsafe { GetLastError() }, ERROR_OPERATION_ABORTED);
+    assert_eq!(unsafe { CancelIoEx(server, null()) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_NOT_FOUND);
+
+    drop(client);
+    assert_eq!(unsafe { CloseHandle(server) }, TRUE);
+    assert_eq!(unsafe { CloseHandle(port) }, TRUE);
+}
+
+fn write_client(client: &File, data: &[u8]) {
+    let mut written = 0;
+    let res = unsafe {
+        WriteFile(
+            client.as_raw_handle() as HANDLE,
+            data.as_ptr(),
+            data.len() as u32,
+            &mut written,
+            null_mut(),
+        )
+    };
+    assert_eq!(res, TRUE);
+}
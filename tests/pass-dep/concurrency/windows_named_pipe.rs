@@ -0,0 +1,206 @@
+//@only-target: windows # Uses win32 api functions
+// We are making scheduler assumptions here.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::windows::io::AsRawHandle;
+use std::ptr::{null, null_mut};
+use std::thread;
+
+use windows_sys::Win32::Foundation::{
+    CloseHandle, ERROR_ACCESS_DENIED, ERROR_BROKEN_PIPE, ERROR_INVALID_NAME, ERROR_MORE_DATA,
+    ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, ERROR_PIPE_LISTENING, ERROR_SEM_TIMEOUT, FALSE,
+    GetLastError, HANDLE, INVALID_HANDLE_VALUE, TRUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX, ReadFile, WriteFile,
+};
+use windows_sys::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, NMPWAIT_WAIT_FOREVER,
+    PIPE_READMODE_MESSAGE, PIPE_TYPE_BYTE, PIPE_TYPE_MESSAGE, PIPE_UNLIMITED_INSTANCES,
+    SetNamedPipeHandleState, WaitNamedPipeW,
+};
+
+fn main() {
+    test_byte_mode();
+    test_message_mode();
+    test_wait_and_disconnect();
+    test_errors();
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain([0]).collect()
+}
+
+fn create_pipe(name: &str, pipe_mode: u32, max_instances: u32) -> HANDLE {
+    let name = wide(name);
+    unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            PIPE_ACCESS_DUPLEX,
+            pipe_mode,
+            max_instances,
+            512,
+            512,
+            0,
+            null(),
+        )
+    }
+}
+
+fn open_client(name: &str) -> std::io::Result<File> {
+    OpenOptions::new().read(true).write(true).open(name)
+}
+
+/// Reads from `pipe` with `ReadFile`, and returns the bytes and the error, if any.
+fn read_pipe(pipe: HANDLE, len: usize) -> (Vec<u8>, Option<u32>) {
+    let mut buf = vec![0u8; len];
+    let mut read = 0;
+    let res =
+        unsafe { ReadFile(pipe, buf.as_mut_ptr(), len.try_into().unwrap(), &mut read, null_mut()) };
+    buf.truncate(read.try_into().unwrap());
+    let error = if res == FALSE { Some(unsafe { GetLastError() }) } else { None };
+    (buf, error)
+}
+
+fn write_pipe(pipe: HANDLE, data: &[u8]) {
+    let mut written = 0;
+    let res = unsafe {
+        WriteFile(pipe, data.as_ptr(), data.len().try_into().unwrap(), &mut written, null_mut())
+    };
+    assert_eq!(res, TRUE);
+    assert_eq!(written as usize, data.len());
+}
+
+fn test_byte_mode() {
+    let name = r"\\.\pipe\miri-test-byte-mode";
+    let server = create_pipe(name, PIPE_TYPE_BYTE, 1);
+    assert_ne!(server, INVALID_HANDLE_VALUE);
+
+    let client = thread::spawn(move || {
+        let mut client = open_client(name).unwrap();
+        client.write_all(b"hello").unwrap();
+        let mut buf = [0u8; 5];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"world");
+    });
+    // This blocks until the client connects.
+    assert_eq!(unsafe { ConnectNamedPipe(server, null_mut()) }, TRUE);
+    // This blocks until the client writes.
+    assert_eq!(read_pipe(server, 16), (b"hello".to_vec(), None));
+    write_pipe(server, b"world");
+    client.join().unwrap();
+    // The client is gone.
+    assert_eq!(read_pipe(server, 16), (vec![], Some(ERROR_BROKEN_PIPE)));
+    assert_eq!(unsafe { CloseHandle(server) }, TRUE);
+}
+
+fn test_message_mode() {
+    let name = r"\\.\pipe\miri-test-message-mode";
+    let server = create_pipe(name, PIPE_TYPE_MESSAGE | PIPE_READMODE_MESSAGE, 1);
+    assert_ne!(server, INVALID_HANDLE_VALUE);
+    let mut client = open_client(name).unwrap();
+    // The client connected before we waited for it.
+    assert_eq!(unsafe { ConnectNamedPipe(server, null_mut()) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_PIPE_CONNECTED);
+
+    // Reads stop at the end of a message, and report messages that do not fit.
+    client.write_all(b"abc").unwrap();
+    client.write_all(b"defgh").unwrap();
+    assert_eq!(read_pipe(server, 2), (b"ab".to_vec(), Some(ERROR_MORE_DATA)));
+    assert_eq!(read_pipe(server, 16), (b"c".to_vec(), None));
+    assert_eq!(read_pipe(server, 16), (b"defgh".to_vec(), None));
+
+    // The client reads in byte mode by default.
+    write_pipe(server, b"one");
+    write_pipe(server, b"two");
+    let mut buf = [0u8; 16];
+    assert_eq!(client.read(&mut buf).unwrap(), 6);
+    assert_eq!(&buf[..6], b"onetwo");
+    let mode = PIPE_READMODE_MESSAGE;
+    let res =
+        unsafe { SetNamedPipeHandleState(client.as_raw_handle() as HANDLE, &mode, null(), null()) };
+    assert_eq!(res, TRUE);
+    write_pipe(server, b"xyz");
+    write_pipe(server, b"w");
+    assert_eq!(client.read(&mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], b"xyz");
+    assert_eq!(client.read(&mut buf).unwrap(), 1);
+
+    // Closing the server breaks the pipe, so the client reads EOF.
+    assert_eq!(unsafe { CloseHandle(server) }, TRUE);
+    assert_eq!(client.read(&mut buf).unwrap(), 0);
+    assert!(client.write(b"x").is_err());
+}
+
+fn test_wait_and_disconnect() {
+    let name = r"\\.\pipe\miri-test-wait";
+    let wide_name = wide(name);
+    let server = create_pipe(name, PIPE_TYPE_BYTE, 1);
+    let mut first = open_client(name).unwrap();
+    // The only instance is connected, so waiting for it times out.
+    assert_eq!(unsafe { WaitNamedPipeW(wide_name.as_ptr(), 10) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_SEM_TIMEOUT);
+
+    let second = thread::spawn(move || {
+        // This blocks until the server disconnects the first client.
+        let wide_name = wide(name);
+        assert_eq!(unsafe { WaitNamedPipeW(wide_name.as_ptr(), NMPWAIT_WAIT_FOREVER) }, TRUE);
+        let mut second = open_client(name).unwrap();
+        second.write_all(b"second").unwrap();
+    });
+    // Give the other thread time to block.
+    thread::yield_now();
+    write_pipe(server, b"discarded");
+    assert_eq!(unsafe { DisconnectNamedPipe(server) }, TRUE);
+    // The first client lost its connection, and the data that it did not read yet.
+    let mut buf = [0u8; 16];
+    assert_eq!(first.read(&mut buf).unwrap(), 0);
+    assert!(first.write(b"x").is_err());
+
+    assert_eq!(unsafe { ConnectNamedPipe(server, null_mut()) }, TRUE);
+    assert_eq!(read_pipe(server, 16), (b"second".to_vec(), None));
+    second.join().unwrap();
+    assert_eq!(unsafe { CloseHandle(server) }, TRUE);
+}
+
+fn test_errors() {
+    let name = r"\\.\pipe\miri-test-errors";
+    assert_eq!(open_client(name).unwrap_err().kind(), ErrorKind::NotFound);
+
+    let server = create_pipe(name, PIPE_TYPE_BYTE, 1);
+    // Nobody is connected yet.
+    assert_eq!(read_pipe(server, 16), (vec![], Some(ERROR_PIPE_LISTENING)));
+    // There can only be one instance.
+    assert_eq!(create_pipe(name, PIPE_TYPE_BYTE, 1), INVALID_HANDLE_VALUE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_PIPE_BUSY);
+    let _client = open_client(name).unwrap();
+    // The only instance is taken.
+    assert_eq!(open_client(name).unwrap_err().raw_os_error(), Some(ERROR_PIPE_BUSY as i32));
+
+    let name = r"\\.\pipe\miri-test-errors-2";
+    let server2 = create_pipe(name, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES);
+    assert_ne!(server2, INVALID_HANDLE_VALUE);
+    let name = wide(name);
+    let res = unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+            PIPE_TYPE_BYTE,
+            1,
+            512,
+            512,
+            0,
+            null(),
+        )
+    };
+    assert_eq!(res, INVALID_HANDLE_VALUE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_ACCESS_DENIED);
+
+    assert_eq!(create_pipe(r"\\.\notapipe\x", PIPE_TYPE_BYTE, 1), INVALID_HANDLE_VALUE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_INVALID_NAME);
+
+    assert_eq!(unsafe { CloseHandle(server) }, TRUE);
+    assert_eq!(unsafe { CloseHandle(server2) }, TRUE);
+}
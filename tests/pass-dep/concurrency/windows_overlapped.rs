@@ -0,0 +1,166 @@
+//@only-target: windows # Uses win32 api functions
+// We are making scheduler assumptions here.
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-preemption-rate=0
+
+use std::fs::OpenOptions;
+use std::os::windows::fs::OpenOptionsExt;
+use std::os::windows::io::AsRawHandle;
+use std::ptr::{null, null_mut};
+use std::{mem, thread};
+
+use windows_sys::Win32::Foundation::{
+    CloseHandle, ERROR_IO_INCOMPLETE, ERROR_IO_PENDING, FALSE, GetLastError, HANDLE,
+    INVALID_HANDLE_VALUE, TRUE, WAIT_OBJECT_0, WAIT_TIMEOUT,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX, ReadFile, WriteFile,
+};
+use windows_sys::Win32::System::IO::{
+    CreateIoCompletionPort, GetOverlappedResult, GetQueuedCompletionStatus, OVERLAPPED,
+};
+use windows_sys::Win32::System::Pipes::{CreateNamedPipeW, PIPE_TYPE_BYTE};
+use windows_sys::Win32::System::Threading::{CreateEventW, WaitForSingleObject};
+
+#[path = "../../utils/mod.rs"]
+mod utils;
+
+fn main() {
+    test_overlapped_file();
+    test_overlapped_pipe();
+    test_skip_completion_port();
+}
+
+fn overlapped_with_event(offset: u32) -> OVERLAPPED {
+    let mut overlapped: OVERLAPPED = unsafe { mem::zeroed() };
+    overlapped.Anonymous.Anonymous.Offset = offset;
+    overlapped.hEvent = unsafe { CreateEventW(null(), TRUE, TRUE, null()) };
+    assert_ne!(overlapped.hEvent, 0);
+    overlapped
+}
+
+fn test_overlapped_file() {
+    let path = utils::tmp().join("miri_test_windows_overlapped.txt");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(FILE_FLAG_OVERLAPPED)
+        .open(&path)
+        .unwrap();
+    let handle = file.as_raw_handle() as HANDLE;
+
+    // Without a completion port, operations on files complete right away and signal the event.
+    let mut overlapped = overlapped_with_event(0);
+    let data = b"hello world";
+    let res =
+        unsafe { WriteFile(handle, data.as_ptr(), data.len() as u32, null_mut(), &mut overlapped) };
+    assert_eq!(res, TRUE);
+    assert_eq!(unsafe { WaitForSingleObject(overlapped.hEvent, 0) }, WAIT_OBJECT_0);
+    let mut transferred = 0;
+    assert_eq!(unsafe { GetOverlappedResult(handle, &overlapped, &mut transferred, FALSE) }, TRUE);
+    assert_eq!(transferred, 11);
+    assert_eq!(unsafe { CloseHandle(overlapped.hEvent) }, TRUE);
+
+    let mut overlapped = overlapped_with_event(6);
+    let mut buf = [0u8; 16];
+    let res = unsafe { ReadFile(handle, buf.as_mut_ptr(), 16, null_mut(), &mut overlapped) };
+    assert_eq!(res, TRUE);
+    assert_eq!(unsafe { GetOverlappedResult(handle, &overlapped, &mut transferred, TRUE) }, TRUE);
+    assert_eq!(&buf[..transferred as usize], b"world");
+    assert_eq!(unsafe { CloseHandle(overlapped.hEvent) }, TRUE);
+}
+
+fn test_overlapped_pipe() {
+    let name = r"\\.\pipe\miri-test-overlapped";
+    let wide_name: Vec<u16> = name.encode_utf16().chain([0]).collect();
+    let server = unsafe {
+        CreateNamedPipeW(
+            wide_name.as_ptr(),
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED,
+            PIPE_TYPE_BYTE,
+            1,
+            512,
+            512,
+            0,
+            null(),
+        )
+    };
+    assert_ne!(server, INVALID_HANDLE_VALUE);
+    let client = OpenOptions::new().read(true).write(true).open(name).unwrap();
+
+    // Reads stay pending until the client writes, and reset the event until then.
+    let mut overlapped = overlapped_with_event(0);
+    let mut buf = [0u8; 16];
+    let res = unsafe { ReadFile(server, buf.as_mut_ptr(), 16, null_mut(), &mut overlapped) };
+    assert_eq!(res, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_IO_PENDING);
+    assert_eq!(unsafe { WaitForSingleObject(overlapped.hEvent, 0) }, WAIT_TIMEOUT);
+    let mut transferred = 0;
+    assert_eq!(unsafe { GetOverlappedResult(server, &overlapped, &mut transferred, FALSE) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_IO_INCOMPLETE);
+
+    static mut DATA: u32 = 0;
+    let writer = thread::spawn(move || {
+        unsafe { DATA = 42 };
+        let mut written = 0;
+        let data = b"ping";
+        let res = unsafe {
+            WriteFile(client.as_raw_handle() as HANDLE, data.as_ptr(), 4, &mut written, null_mut())
+        };
+        assert_eq!(res, TRUE);
+        client
+    });
+    // This blocks until the other thread writes.
+    assert_eq!(unsafe { GetOverlappedResult(server, &overlapped, &mut transferred, TRUE) }, TRUE);
+    assert_eq!(&buf[..transferred as usize], b"ping");
+    // Getting the result synchronizes with the thread that completed the operation.
+    assert_eq!(unsafe { DATA }, 42);
+    assert_eq!(unsafe { WaitForSingleObject(overlapped.hEvent, 0) }, WAIT_OBJECT_0);
+
+    drop(writer.join().unwrap());
+    assert_eq!(unsafe { CloseHandle(overlapped.hEvent) }, TRUE);
+    assert_eq!(unsafe { CloseHandle(server) }, TRUE);
+}
+
+fn test_skip_completion_port() {
+    let path = utils::tmp().join("miri_test_windows_overlapped_skip_port.txt");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(FILE_FLAG_OVERLAPPED)
+        .open(&path)
+        .unwrap();
+    let handle = file.as_raw_handle() as HANDLE;
+    let port = unsafe { CreateIoCompletionPort(INVALID_HANDLE_VALUE, 0, 0, 0) };
+    assert_ne!(port, 0);
+    assert_eq!(unsafe { CreateIoCompletionPort(handle, port, 1, 0) }, port);
+
+    // With the low bit of `hEvent` set, the operation still signals the event, but does not queue
+    // a completion packet.
+    let mut overlapped = overlapped_with_event(0);
+    let event = overlapped.hEvent;
+    overlapped.hEvent = event | 1;
+    let res = unsafe { WriteFile(handle, b"abc".as_ptr(), 3, null_mut(), &mut overlapped) };
+    assert_eq!(res, TRUE);
+    assert_eq!(unsafe { WaitForSingleObject(event, 0) }, WAIT_OBJECT_0);
+    let mut bytes = 0;
+    let mut key = 0;
+    let mut packet = null_mut();
+    let res = unsafe { GetQueuedCompletionStatus(port, &mut bytes, &mut key, &mut packet, 0) };
+    assert_eq!(res, FALSE);
+    assert_eq!(unsafe { GetLastError() }, WAIT_TIMEOUT);
+
+    // Without it, the packet gets queued as usual.
+    overlapped.hEvent = event;
+    let res = unsafe { WriteFile(handle, b"def".as_ptr(), 3, null_mut(), &mut overlapped) };
+    assert_eq!(res, TRUE);
+    let res = unsafe { GetQueuedCompletionStatus(port, &mut bytes, &mut key, &mut packet, 0) };
+    assert_eq!(res, TRUE);
+    assert_eq!((bytes, key, packet), (3, 1, &raw mut overlapped));
+
+    assert_eq!(unsafe { CloseHandle(event) }, TRUE);
+    assert_eq!(unsafe { CloseHandle(port) }, TRUE);
+}
@@ -0,0 +1,129 @@
+//@only-target: windows # Uses win32 api functions
+// We are making scheduler assumptions here.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::os::windows::io::AsRawHandle;
+use std::ptr::null;
+use std::thread;
+
+use windows_sys::Win32::Foundation::{
+    CloseHandle, ERROR_NOT_OWNER, FALSE, GetLastError, HANDLE, TRUE, WAIT_ABANDONED_0,
+    WAIT_OBJECT_0, WAIT_TIMEOUT,
+};
+use windows_sys::Win32::System::Threading::{
+    CreateEventW, CreateMutexW, INFINITE, ReleaseMutex, ResetEvent, SetEvent,
+    WaitForMultipleObjects, WaitForSingleObject,
+};
+
+fn main() {
+    test_manual_reset_event();
+    test_auto_reset_event();
+    test_event_across_threads();
+    test_wait_for_multiple();
+    test_mutex();
+    test_abandoned_mutex();
+}
+
+fn create_event(manual_reset: bool, initial_state: bool) -> HANDLE {
+    let event = unsafe { CreateEventW(null(), manual_reset.into(), initial_state.into(), null()) };
+    assert_ne!(event, 0);
+    event
+}
+
+fn test_manual_reset_event() {
+    let event = create_event(true, false);
+    assert_eq!(unsafe { WaitForSingleObject(event, 10) }, WAIT_TIMEOUT);
+    assert_eq!(unsafe { SetEvent(event) }, TRUE);
+    // The event stays signaled.
+    assert_eq!(unsafe { WaitForSingleObject(event, 0) }, WAIT_OBJECT_0);
+    assert_eq!(unsafe { WaitForSingleObject(event, INFINITE) }, WAIT_OBJECT_0);
+    assert_eq!(unsafe { ResetEvent(event) }, TRUE);
+    assert_eq!(unsafe { WaitForSingleObject(event, 0) }, WAIT_TIMEOUT);
+    assert_eq!(unsafe { CloseHandle(event) }, TRUE);
+}
+
+fn test_auto_reset_event() {
+    let event = create_event(false, true);
+    // Only one wait succeeds.
+    assert_eq!(unsafe { WaitForSingleObject(event, 0) }, WAIT_OBJECT_0);
+    assert_eq!(unsafe { WaitForSingleObject(event, 0) }, WAIT_TIMEOUT);
+    assert_eq!(unsafe { CloseHandle(event) }, TRUE);
+}
+
+fn test_event_across_threads() {
+    static mut DATA: u32 = 0;
+    let event = create_event(false, false);
+    let setter = thread::spawn(move || {
+        unsafe { DATA = 42 };
+        assert_eq!(unsafe { SetEvent(event) }, TRUE);
+    });
+    // This blocks until the other thread sets the event, and synchronizes with it.
+    assert_eq!(unsafe { WaitForSingleObject(event, INFINITE) }, WAIT_OBJECT_0);
+    assert_eq!(unsafe { DATA }, 42);
+    setter.join().unwrap();
+    assert_eq!(unsafe { CloseHandle(event) }, TRUE);
+}
+
+fn test_wait_for_multiple() {
+    let first = create_event(true, false);
+    let second = create_event(true, false);
+    let thread = thread::spawn(move || {
+        assert_eq!(unsafe { SetEvent(second) }, TRUE);
+        // Give the main thread time to wait for all objects.
+        thread::yield_now();
+    });
+    let handles = [first, second, thread.as_raw_handle() as HANDLE];
+
+    // The second event gets set first.
+    assert_eq!(
+        unsafe { WaitForMultipleObjects(3, handles.as_ptr(), FALSE, INFINITE) },
+        WAIT_OBJECT_0 + 1
+    );
+    // Not all objects are signaled.
+    assert_eq!(unsafe { WaitForMultipleObjects(3, handles.as_ptr(), TRUE, 10) }, WAIT_TIMEOUT);
+    assert_eq!(unsafe { SetEvent(first) }, TRUE);
+    // This blocks until the thread terminates.
+    assert_eq!(
+        unsafe { WaitForMultipleObjects(3, handles.as_ptr(), TRUE, INFINITE) },
+        WAIT_OBJECT_0
+    );
+    thread.join().unwrap();
+    assert_eq!(unsafe { CloseHandle(first) }, TRUE);
+    assert_eq!(unsafe { CloseHandle(second) }, TRUE);
+}
+
+fn test_mutex() {
+    let mutex = unsafe { CreateMutexW(null(), TRUE, null()) };
+    assert_ne!(mutex, 0);
+    // The mutex is recursive.
+    assert_eq!(unsafe { WaitForSingleObject(mutex, 0) }, WAIT_OBJECT_0);
+    let other = thread::spawn(move || {
+        // Other threads cannot acquire the mutex until it is released as often as it was acquired.
+        assert_eq!(unsafe { ReleaseMutex(mutex) }, FALSE);
+        assert_eq!(unsafe { GetLastError() }, ERROR_NOT_OWNER);
+        assert_eq!(unsafe { WaitForSingleObject(mutex, 0) }, WAIT_TIMEOUT);
+        assert_eq!(unsafe { WaitForSingleObject(mutex, INFINITE) }, WAIT_OBJECT_0);
+        assert_eq!(unsafe { ReleaseMutex(mutex) }, TRUE);
+    });
+    thread::yield_now();
+    assert_eq!(unsafe { ReleaseMutex(mutex) }, TRUE);
+    thread::yield_now();
+    assert_eq!(unsafe { ReleaseMutex(mutex) }, TRUE);
+    other.join().unwrap();
+    assert_eq!(unsafe { WaitForSingleObject(mutex, 0) }, WAIT_OBJECT_0);
+    assert_eq!(unsafe { ReleaseMutex(mutex) }, TRUE);
+    assert_eq!(unsafe { CloseHandle(mutex) }, TRUE);
+}
+
+fn test_abandoned_mutex() {
+    let mutex = unsafe { CreateMutexW(null(), FALSE, null()) };
+    thread::spawn(move || {
+        assert_eq!(unsafe { WaitForSingleObject(mutex, INFINITE) }, WAIT_OBJECT_0);
+    })
+    .join()
+    .unwrap();
+    // The thread terminated while owning the mutex.
+    assert_eq!(unsafe { WaitForSingleObject(mutex, INFINITE) }, WAIT_ABANDONED_0);
+    assert_eq!(unsafe { ReleaseMutex(mutex) }, TRUE);
+    assert_eq!(unsafe { CloseHandle(mutex) }, TRUE);
+}
@@ -206,6 +206,81 @@ fn parent_child() {
     assert_eq!(err, 0);
 }
 
+fn available_parallelism() {
+    let cpu_count = std::thread::available_parallelism().unwrap().get();
+
+    assert!(cpu_count > 1, "this test cannot do anything interesting with just one thread");
+
+    // narrowing the mask of the thread is reflected in the available parallelism
+    let mut cpuset: cpu_set_t = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+    unsafe { libc::CPU_SET(0, &mut cpuset) };
+    unsafe { libc::CPU_SET(1, &mut cpuset) };
+    let err = unsafe { sched_setaffinity(PID, size_of::<cpu_set_t>(), &cpuset) };
+    assert_eq!(err, 0);
+    assert_eq!(std::thread::available_parallelism().unwrap().get(), 2);
+
+    // it is important that we reset the cpu mask now for future tests
+    for i in 0..cpu_count {
+        unsafe { libc::CPU_SET(i, &mut cpuset) };
+    }
+
+    let err = unsafe { sched_setaffinity(PID, size_of::<cpu_set_t>(), &cpuset) };
+    assert_eq!(err, 0);
+    assert_eq!(std::thread::available_parallelism().unwrap().get(), cpu_count);
+}
+
+/// Threads other than the calling one are identified by their TID.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn other_thread() {
+    use std::sync::{Barrier, mpsc};
+
+    let cpu_count = std::thread::available_parallelism().unwrap().get();
+
+    assert!(cpu_count > 1, "this test cannot do anything interesting with just one thread");
+
+    // the main thread can be addressed with its TID, which is the PID
+    let mut cpuset: cpu_set_t = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+    let err = unsafe { sched_getaffinity(libc::getpid(), size_of::<cpu_set_t>(), &mut cpuset) };
+    assert_eq!(err, 0);
+    assert!(unsafe { libc::CPU_ISSET(1, &cpuset) });
+
+    let (tid_sender, tid_receiver) = mpsc::channel();
+    let barrier = Barrier::new(2);
+    std::thread::scope(|spawner| {
+        spawner.spawn(|| {
+            tid_sender.send(unsafe { libc::gettid() }).unwrap();
+            barrier.wait();
+
+            // the main thread has configured only CPU 1 for this thread
+            let mut cpuset: cpu_set_t = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+            let err = unsafe { sched_getaffinity(PID, size_of::<cpu_set_t>(), &mut cpuset) };
+            assert_eq!(err, 0);
+            assert!(unsafe { !libc::CPU_ISSET(0, &cpuset) });
+            assert!(unsafe { libc::CPU_ISSET(1, &cpuset) });
+        });
+
+        let tid = tid_receiver.recv().unwrap();
+        let mut cpuset: cpu_set_t = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+        unsafe { libc::CPU_SET(1, &mut cpuset) };
+        let err = unsafe { sched_setaffinity(tid, size_of::<cpu_set_t>(), &cpuset) };
+        assert_eq!(err, 0);
+        barrier.wait();
+    });
+
+    // the main thread's set is unaffected
+    let err = unsafe { sched_getaffinity(PID, size_of::<cpu_set_t>(), &mut cpuset) };
+    assert_eq!(err, 0);
+    assert!(unsafe { libc::CPU_ISSET(0, &cpuset) });
+
+    // unknown threads cannot be addressed
+    let err = unsafe { sched_getaffinity(-1, size_of::<cpu_set_t>(), &mut cpuset) };
+    assert_eq!(err, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ESRCH));
+    let err = unsafe { sched_setaffinity(i32::MAX, size_of::<cpu_set_t>(), &cpuset) };
+    assert_eq!(err, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ESRCH));
+}
+
 fn main() {
     null_pointers();
     configure_no_cpus();
@@ -215,4 +290,7 @@ fn main() {
     set_small_cpu_mask();
     set_custom_cpu_mask();
     parent_child();
+    available_parallelism();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    other_thread();
 }
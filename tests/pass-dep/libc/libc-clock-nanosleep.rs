@@ -0,0 +1,64 @@
+//@only-target: linux android freebsd solaris illumos
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+fn main() {
+    test_relative();
+    test_absolute_monotonic();
+    test_absolute_in_the_past();
+    test_invalid();
+}
+
+fn timespec(duration: Duration) -> libc::timespec {
+    libc::timespec {
+        tv_sec: duration.as_secs().try_into().unwrap(),
+        tv_nsec: duration.subsec_nanos().try_into().unwrap(),
+    }
+}
+
+fn monotonic_now() -> Duration {
+    let mut tp = MaybeUninit::<libc::timespec>::uninit();
+    assert_eq!(unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, tp.as_mut_ptr()) }, 0);
+    let tp = unsafe { tp.assume_init() };
+    Duration::new(tp.tv_sec.try_into().unwrap(), tp.tv_nsec.try_into().unwrap())
+}
+
+fn test_relative() {
+    for clock in [libc::CLOCK_MONOTONIC, libc::CLOCK_REALTIME] {
+        let before = Instant::now();
+        let req = timespec(Duration::from_millis(100));
+        assert_eq!(unsafe { libc::clock_nanosleep(clock, 0, &req, ptr::null_mut()) }, 0);
+        assert!(before.elapsed() >= Duration::from_millis(100));
+    }
+}
+
+fn test_absolute_monotonic() {
+    let deadline = monotonic_now() + Duration::from_millis(100);
+    let req = timespec(deadline);
+    let res = unsafe {
+        libc::clock_nanosleep(libc::CLOCK_MONOTONIC, libc::TIMER_ABSTIME, &req, ptr::null_mut())
+    };
+    assert_eq!(res, 0);
+    assert!(monotonic_now() >= deadline);
+}
+
+fn test_absolute_in_the_past() {
+    let req = timespec(Duration::ZERO);
+    let before = Instant::now();
+    let res = unsafe {
+        libc::clock_nanosleep(libc::CLOCK_MONOTONIC, libc::TIMER_ABSTIME, &req, ptr::null_mut())
+    };
+    assert_eq!(res, 0);
+    assert!(before.elapsed() < Duration::from_millis(100));
+}
+
+fn test_invalid() {
+    // Errors are returned, not stored in `errno`.
+    let req = libc::timespec { tv_sec: 0, tv_nsec: 1_000_000_000 };
+    let res = unsafe { libc::clock_nanosleep(libc::CLOCK_MONOTONIC, 0, &req, ptr::null_mut()) };
+    assert_eq!(res, libc::EINVAL);
+    let req = timespec(Duration::ZERO);
+    let res = unsafe { libc::clock_nanosleep(libc::CLOCK_MONOTONIC, 0x100, &req, ptr::null_mut()) };
+    assert_eq!(res, libc::EINVAL);
+}
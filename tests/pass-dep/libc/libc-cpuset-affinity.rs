@@ -0,0 +1,57 @@
+//@only-target: freebsd # `cpuset_{g, s}etaffinity` are FreeBSD-specific
+//@compile-flags: -Zmiri-num-cpus=4
+
+use std::mem::size_of;
+
+use libc::{
+    CPU_LEVEL_ROOT, CPU_LEVEL_WHICH, CPU_WHICH_PID, CPU_WHICH_TID, cpuset_getaffinity,
+    cpuset_setaffinity, cpuset_t,
+};
+
+fn get(level: i32, which: i32) -> cpuset_t {
+    let mut cpuset: cpuset_t = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+    let err = unsafe { cpuset_getaffinity(level, which, -1, size_of::<cpuset_t>(), &mut cpuset) };
+    assert_eq!(err, 0);
+    cpuset
+}
+
+fn main() {
+    // by default, all configured CPUs are available
+    let cpuset = get(CPU_LEVEL_WHICH, CPU_WHICH_PID);
+    assert_eq!(unsafe { libc::CPU_COUNT(&cpuset) }, 4);
+    assert_eq!(std::thread::available_parallelism().unwrap().get(), 4);
+
+    // narrow the mask of the calling thread
+    let mut cpuset: cpuset_t = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+    unsafe { libc::CPU_SET(2, &mut cpuset) };
+    let err = unsafe {
+        cpuset_setaffinity(CPU_LEVEL_WHICH, CPU_WHICH_TID, -1, size_of::<cpuset_t>(), &cpuset)
+    };
+    assert_eq!(err, 0);
+
+    let cpuset = get(CPU_LEVEL_WHICH, CPU_WHICH_TID);
+    assert_eq!(unsafe { libc::CPU_COUNT(&cpuset) }, 1);
+    assert!(unsafe { libc::CPU_ISSET(2, &cpuset) });
+    assert_eq!(std::thread::available_parallelism().unwrap().get(), 1);
+
+    // the root set still contains all CPUs
+    let cpuset = get(CPU_LEVEL_ROOT, CPU_WHICH_PID);
+    assert_eq!(unsafe { libc::CPU_COUNT(&cpuset) }, 4);
+
+    // a mask without any available CPU is rejected
+    let mut cpuset: cpuset_t = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+    unsafe { libc::CPU_SET(5, &mut cpuset) };
+    let err = unsafe {
+        cpuset_setaffinity(CPU_LEVEL_WHICH, CPU_WHICH_PID, -1, size_of::<cpuset_t>(), &cpuset)
+    };
+    assert_eq!(err, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EINVAL));
+
+    // other processes cannot be addressed
+    let mut cpuset: cpuset_t = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+    let err = unsafe {
+        cpuset_getaffinity(CPU_LEVEL_WHICH, CPU_WHICH_PID, 1, size_of::<cpuset_t>(), &mut cpuset)
+    };
+    assert_eq!(err, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ESRCH));
+}
@@ -23,6 +23,8 @@ fn main() {
     test_epoll_ctl_epfd_equal_fd();
     test_epoll_ctl_notification();
     test_issue_3858();
+    test_socketpair_shutdown();
+    test_nonblocking_connect();
 }
 
 // Using `as` cast since `EPOLLET` wraps around
@@ -720,3 +722,111 @@ fn test_issue_3858() {
     let res = unsafe { libc::write(fd, sized_8_data.as_ptr() as *const libc::c_void, 8) };
     assert_eq!(res, 8);
 }
+
+// Shutting down writing on one end should notify the other end with EPOLLRDHUP.
+fn test_socketpair_shutdown() {
+    // Create an epoll instance.
+    let epfd = unsafe { libc::epoll_create1(0) };
+    assert_ne!(epfd, -1);
+
+    // Create a socketpair instance.
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+
+    // Register fd[1] with EPOLLIN|EPOLLOUT|EPOLLET|EPOLLRDHUP
+    let mut ev = libc::epoll_event {
+        events: (libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLET | libc::EPOLLRDHUP) as _,
+        u64: u64::try_from(fds[1]).unwrap(),
+    };
+    let res = unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, fds[1], &mut ev) };
+    assert_eq!(res, 0);
+    let expected_event = u32::try_from(libc::EPOLLOUT).unwrap();
+    let expected_value = u64::try_from(fds[1]).unwrap();
+    check_epoll_wait::<8>(epfd, &[(expected_event, expected_value)]);
+
+    // Shut down writing on fd[0].
+    let res = unsafe { libc::shutdown(fds[0], libc::SHUT_WR) };
+    assert_eq!(res, 0);
+    let expected_event = u32::try_from(libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLRDHUP).unwrap();
+    check_epoll_wait::<8>(epfd, &[(expected_event, expected_value)]);
+
+    // Shut down reading on fd[1] too, so it is fully hung up.
+    let res = unsafe { libc::shutdown(fds[1], libc::SHUT_RD) };
+    assert_eq!(res, 0);
+    check_epoll_wait::<8>(epfd, &[(expected_event, expected_value)]);
+    let res = unsafe { libc::shutdown(fds[1], libc::SHUT_WR) };
+    assert_eq!(res, 0);
+    let expected_event =
+        u32::try_from(libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLRDHUP | libc::EPOLLHUP).unwrap();
+    check_epoll_wait::<8>(epfd, &[(expected_event, expected_value)]);
+}
+
+fn loopback(port: u16) -> libc::sockaddr_in {
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    addr.sin_port = port.to_be();
+    addr.sin_addr.s_addr = u32::from_be_bytes([127, 0, 0, 1]).to_be();
+    addr
+}
+
+fn so_error(fd: i32) -> i32 {
+    let mut error = 0;
+    let mut len = size_of::<i32>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_ERROR, (&raw mut error).cast(), &mut len)
+    };
+    assert_eq!(res, 0);
+    error
+}
+
+fn test_nonblocking_connect() {
+    let addr_len = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+
+    // Set up a listener on an ephemeral port.
+    let listener = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    assert!(listener >= 0);
+    let mut addr = loopback(0);
+    let res = unsafe { libc::bind(listener, (&raw const addr).cast(), addr_len) };
+    assert_eq!(res, 0);
+    assert_eq!(unsafe { libc::listen(listener, 8) }, 0);
+    let mut len = addr_len;
+    let res = unsafe { libc::getsockname(listener, (&raw mut addr).cast(), &mut len) };
+    assert_eq!(res, 0);
+
+    // A non-blocking connect reports `EINPROGRESS`, and then the socket becomes writable.
+    let epfd = unsafe { libc::epoll_create1(0) };
+    assert_ne!(epfd, -1);
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_NONBLOCK, 0) };
+    assert!(fd >= 0);
+    let res = unsafe { libc::connect(fd, (&raw const addr).cast(), addr_len) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EINPROGRESS));
+    let mut ev = libc::epoll_event { events: EPOLL_IN_OUT_ET, u64: fd as u64 };
+    let res = unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, fd, &mut ev) };
+    assert_eq!(res, 0);
+    check_epoll_wait::<8>(epfd, &[(libc::EPOLLOUT as u32, fd as u64)]);
+    assert_eq!(so_error(fd), 0);
+    // Connecting again reports that we are connected.
+    let res = unsafe { libc::connect(fd, (&raw const addr).cast(), addr_len) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EISCONN));
+    let conn = unsafe { libc::accept(listener, std::ptr::null_mut(), std::ptr::null_mut()) };
+    assert!(conn >= 0);
+
+    // A refused connection is also reported through `SO_ERROR`.
+    assert_eq!(unsafe { libc::close(listener) }, 0);
+    let fd2 = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_NONBLOCK, 0) };
+    assert!(fd2 >= 0);
+    let res = unsafe { libc::connect(fd2, (&raw const addr).cast(), addr_len) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EINPROGRESS));
+    let mut ev = libc::epoll_event { events: EPOLL_IN_OUT_ET, u64: fd2 as u64 };
+    let res = unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, fd2, &mut ev) };
+    assert_eq!(res, 0);
+    let expected_event = u32::try_from(libc::EPOLLOUT | libc::EPOLLHUP | libc::EPOLLERR).unwrap();
+    check_epoll_wait::<8>(epfd, &[(expected_event, fd2 as u64)]);
+    assert_eq!(so_error(fd2), libc::ECONNREFUSED);
+    // Reading the error clears it.
+    assert_eq!(so_error(fd2), 0);
+}
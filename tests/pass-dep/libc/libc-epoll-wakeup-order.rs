@@ -0,0 +1,84 @@
+//@only-target: linux android
+//@revisions: random explore
+//@[random]compile-flags: -Zmiri-epoll-wakeup-order=random -Zmiri-preemption-rate=0
+//@[explore]compile-flags: -Zmiri-epoll-wakeup-order=explore
+
+use std::convert::TryInto;
+use std::thread;
+
+fn main() {
+    test_ready_events();
+    test_blocked_threads();
+}
+
+/// Creates an eventfd that is readable.
+fn readable_eventfd() -> i32 {
+    let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+    assert_ne!(fd, -1);
+    write_eventfd(fd);
+    fd
+}
+
+fn write_eventfd(fd: i32) {
+    let data: [u8; 8] = 1_u64.to_ne_bytes();
+    let res = unsafe { libc::write(fd, data.as_ptr() as *const libc::c_void, 8) };
+    assert_eq!(res, 8);
+}
+
+fn register(epfd: i32, fd: i32) {
+    let mut ev =
+        libc::epoll_event { events: libc::EPOLLIN as u32, u64: u64::try_from(fd).unwrap() };
+    let res = unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, fd, &mut ev) };
+    assert_eq!(res, 0);
+}
+
+/// Waits for a single event and returns its data.
+fn wait_one(epfd: i32, timeout: i32) -> u64 {
+    let mut array = [libc::epoll_event { events: 0, u64: 0 }; 1];
+    let res = unsafe { libc::epoll_wait(epfd, array.as_mut_ptr(), 1, timeout) };
+    assert_eq!(res, 1);
+    array[0].u64
+}
+
+// However the events are ordered, each of them is reported exactly once.
+fn test_ready_events() {
+    let epfd = unsafe { libc::epoll_create1(0) };
+    assert_ne!(epfd, -1);
+    let fds = [readable_eventfd(), readable_eventfd(), readable_eventfd()];
+    for fd in fds {
+        register(epfd, fd);
+    }
+
+    let first = wait_one(epfd, 0);
+    let mut array = [libc::epoll_event { events: 0, u64: 0 }; 4];
+    let res = unsafe { libc::epoll_wait(epfd, array.as_mut_ptr(), 4, 0) };
+    assert_eq!(res, 2);
+
+    let mut seen: Vec<u64> = vec![first, array[0].u64, array[1].u64];
+    seen.sort();
+    let mut expected: Vec<u64> = fds.iter().map(|&fd| fd.try_into().unwrap()).collect();
+    expected.sort();
+    assert_eq!(seen, expected);
+}
+
+// However the blocked threads are woken up, each of them gets one of the events.
+fn test_blocked_threads() {
+    let epfd = unsafe { libc::epoll_create1(0) };
+    assert_ne!(epfd, -1);
+    let fd1 = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+    let fd2 = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+    register(epfd, fd1);
+    register(epfd, fd2);
+
+    let waiters: Vec<_> = (0..2).map(|_| thread::spawn(move || wait_one(epfd, -1))).collect();
+    // Give the waiters a chance to block.
+    thread::yield_now();
+    write_eventfd(fd1);
+    write_eventfd(fd2);
+
+    let mut seen: Vec<u64> = waiters.into_iter().map(|t| t.join().unwrap()).collect();
+    seen.sort();
+    let mut expected: Vec<u64> = vec![fd1.try_into().unwrap(), fd2.try_into().unwrap()];
+    expected.sort();
+    assert_eq!(seen, expected);
+}
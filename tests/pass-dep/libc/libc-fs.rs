@@ -17,6 +17,7 @@ mod utils;
 fn main() {
     test_dup();
     test_dup_stdout_stderr();
+    test_fd_cloexec();
     test_canonicalize_too_long();
     test_rename();
     test_ftruncate::<libc::off_t>(libc::ftruncate);
@@ -102,6 +103,46 @@ fn test_dup() {
     }
 }
 
+fn test_fd_cloexec() {
+    let path = utils::prepare_with_content("miri_test_libc_fd_cloexec.txt", b"cloexec");
+
+    let mut name = path.into_os_string();
+    name.push("\0");
+    let name_ptr = name.as_bytes().as_ptr().cast::<libc::c_char>();
+    unsafe {
+        let fd = libc::open(name_ptr, libc::O_RDONLY);
+        assert_eq!(libc::fcntl(fd, libc::F_GETFD), 0);
+        assert_eq!(libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC), 0);
+        assert_eq!(libc::fcntl(fd, libc::F_GETFD), libc::FD_CLOEXEC);
+
+        // The flag belongs to the descriptor, so duplicates do not inherit it.
+        let dup_fd = libc::dup(fd);
+        assert_eq!(libc::fcntl(dup_fd, libc::F_GETFD), 0);
+        let dup_fd = libc::fcntl(fd, libc::F_DUPFD, 0);
+        assert_eq!(libc::fcntl(dup_fd, libc::F_GETFD), 0);
+        let dup_fd = libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0);
+        assert_eq!(libc::fcntl(dup_fd, libc::F_GETFD), libc::FD_CLOEXEC);
+        assert_eq!(libc::fcntl(fd, libc::F_SETFD, 0), 0);
+        assert_eq!(libc::fcntl(fd, libc::F_GETFD), 0);
+        assert_eq!(libc::fcntl(dup_fd, libc::F_GETFD), libc::FD_CLOEXEC);
+
+        // `dup2` clears the flag of the target descriptor.
+        assert_eq!(libc::dup2(fd, dup_fd), dup_fd);
+        assert_eq!(libc::fcntl(dup_fd, libc::F_GETFD), 0);
+
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(libc::dup3(fd, dup_fd, libc::O_CLOEXEC), dup_fd);
+            assert_eq!(libc::fcntl(dup_fd, libc::F_GETFD), libc::FD_CLOEXEC);
+            assert_eq!(libc::dup3(fd, fd, libc::O_CLOEXEC), -1);
+            assert_eq!(Error::last_os_error().raw_os_error(), Some(libc::EINVAL));
+        }
+
+        let fd = libc::open(name_ptr, libc::O_RDONLY | libc::O_CLOEXEC);
+        assert_eq!(libc::fcntl(fd, libc::F_GETFD), libc::FD_CLOEXEC);
+    }
+}
+
 fn test_canonicalize_too_long() {
     // Make sure we get an error for long paths.
     let too_long = "x/".repeat(libc::PATH_MAX.try_into().unwrap());
@@ -0,0 +1,146 @@
+//@ignore-target: windows # No libc getaddrinfo on Windows
+//@compile-flags: -Zmiri-resolve=example.test=192.0.2.1 -Zmiri-resolve=example.test=2001:db8::1
+
+use std::ffi::{CStr, CString};
+use std::mem::zeroed;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ptr;
+
+fn main() {
+    test_localhost();
+    test_numeric();
+    test_configured();
+    test_passive();
+    test_errors();
+}
+
+/// Calls `getaddrinfo` and returns the addresses and socket types of all results.
+fn lookup(
+    node: Option<&str>,
+    service: Option<&str>,
+    hints: &libc::addrinfo,
+) -> Result<Vec<(SocketAddr, i32)>, i32> {
+    let node = node.map(|node| CString::new(node).unwrap());
+    let service = service.map(|service| CString::new(service).unwrap());
+    let mut res = ptr::null_mut();
+    let ret = unsafe {
+        libc::getaddrinfo(
+            node.as_ref().map_or(ptr::null(), |node| node.as_ptr()),
+            service.as_ref().map_or(ptr::null(), |service| service.as_ptr()),
+            hints,
+            &mut res,
+        )
+    };
+    if ret != 0 {
+        return Err(ret);
+    }
+    let mut addrs = Vec::new();
+    let mut entry = res;
+    while !entry.is_null() {
+        let info = unsafe { &*entry };
+        let addr = match info.ai_family {
+            libc::AF_INET => {
+                assert_eq!(info.ai_addrlen as usize, size_of::<libc::sockaddr_in>());
+                let addr = unsafe { &*info.ai_addr.cast::<libc::sockaddr_in>() };
+                SocketAddr::new(
+                    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).into(),
+                    u16::from_be(addr.sin_port),
+                )
+            }
+            libc::AF_INET6 => {
+                assert_eq!(info.ai_addrlen as usize, size_of::<libc::sockaddr_in6>());
+                let addr = unsafe { &*info.ai_addr.cast::<libc::sockaddr_in6>() };
+                SocketAddr::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr).into(),
+                    u16::from_be(addr.sin6_port),
+                )
+            }
+            family => panic!("unexpected address family {family}"),
+        };
+        addrs.push((addr, info.ai_socktype));
+        entry = info.ai_next;
+    }
+    unsafe { libc::freeaddrinfo(res) };
+    Ok(addrs)
+}
+
+fn stream_hints(family: i32) -> libc::addrinfo {
+    let mut hints: libc::addrinfo = unsafe { zeroed() };
+    hints.ai_family = family;
+    hints.ai_socktype = libc::SOCK_STREAM;
+    hints
+}
+
+fn test_localhost() {
+    let addrs = lookup(Some("localhost"), Some("8080"), &stream_hints(libc::AF_UNSPEC)).unwrap();
+    assert_eq!(
+        addrs,
+        [
+            (SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8080), libc::SOCK_STREAM),
+            (SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 8080), libc::SOCK_STREAM),
+        ]
+    );
+    let addrs = lookup(Some("localhost"), None, &stream_hints(libc::AF_INET6)).unwrap();
+    assert_eq!(addrs, [(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0), libc::SOCK_STREAM)]);
+
+    // Without a socket type, there is one result per socket type.
+    let hints: libc::addrinfo = unsafe { zeroed() };
+    let addrs = lookup(Some("localhost"), Some("53"), &hints).unwrap();
+    let v4 = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 53);
+    let v6 = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 53);
+    assert_eq!(
+        addrs,
+        [
+            (v4, libc::SOCK_STREAM),
+            (v4, libc::SOCK_DGRAM),
+            (v6, libc::SOCK_STREAM),
+            (v6, libc::SOCK_DGRAM)
+        ]
+    );
+}
+
+fn test_numeric() {
+    let addrs = lookup(Some("10.1.2.3"), Some("80"), &stream_hints(libc::AF_UNSPEC)).unwrap();
+    assert_eq!(addrs, [(SocketAddr::new([10, 1, 2, 3].into(), 80), libc::SOCK_STREAM)]);
+    let addrs = lookup(Some("::1"), Some("80"), &stream_hints(libc::AF_UNSPEC)).unwrap();
+    assert_eq!(addrs, [(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 80), libc::SOCK_STREAM)]);
+    // An IPv4 address does not match an IPv6 query.
+    let res = lookup(Some("10.1.2.3"), Some("80"), &stream_hints(libc::AF_INET6));
+    assert_eq!(res, Err(libc::EAI_NONAME));
+}
+
+fn test_configured() {
+    let addrs = lookup(Some("example.test"), Some("443"), &stream_hints(libc::AF_UNSPEC)).unwrap();
+    let expected: [IpAddr; 2] = ["192.0.2.1".parse().unwrap(), "2001:db8::1".parse().unwrap()];
+    assert_eq!(addrs, expected.map(|ip| (SocketAddr::new(ip, 443), libc::SOCK_STREAM)));
+
+    // Names are only resolved when allowed.
+    let mut hints = stream_hints(libc::AF_UNSPEC);
+    hints.ai_flags = libc::AI_NUMERICHOST;
+    let res = lookup(Some("example.test"), Some("443"), &hints);
+    assert_eq!(res, Err(libc::EAI_NONAME));
+    // Other names are not known.
+    let res = lookup(Some("unknown.test"), Some("443"), &stream_hints(libc::AF_UNSPEC));
+    assert_eq!(res, Err(libc::EAI_NONAME));
+}
+
+fn test_passive() {
+    let mut hints = stream_hints(libc::AF_INET);
+    hints.ai_flags = libc::AI_PASSIVE;
+    let addrs = lookup(None, Some("8080"), &hints).unwrap();
+    assert_eq!(addrs, [(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 8080), libc::SOCK_STREAM)]);
+}
+
+fn test_errors() {
+    let res = lookup(None, None, &stream_hints(libc::AF_UNSPEC));
+    assert_eq!(res, Err(libc::EAI_NONAME));
+    let res = lookup(Some("localhost"), None, &stream_hints(libc::AF_UNIX));
+    assert_eq!(res, Err(libc::EAI_FAMILY));
+    let mut hints = stream_hints(libc::AF_UNSPEC);
+    hints.ai_flags = libc::AI_NUMERICSERV;
+    let res = lookup(Some("localhost"), Some("http"), &hints);
+    assert_eq!(res, Err(libc::EAI_NONAME));
+
+    let msg = unsafe { CStr::from_ptr(libc::gai_strerror(libc::EAI_NONAME)) };
+    assert!(!msg.to_bytes().is_empty());
+}
@@ -0,0 +1,240 @@
+//@only-target: apple # kqueue is only supported on apple systems
+// test_user_event_wakes_waiter depends on a deterministic schedule.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::{ptr, thread};
+
+fn main() {
+    test_read_write_level_triggered();
+    test_clear();
+    test_oneshot_and_dispatch();
+    test_receipt_and_errors();
+    test_eof();
+    test_timeout();
+    test_user_event_wakes_waiter();
+    test_user_event_syncs();
+    test_read_wakes_waiter();
+}
+
+fn kevent(ident: usize, filter: i16, flags: u16, fflags: u32, udata: usize) -> libc::kevent {
+    libc::kevent { ident, filter, flags, fflags, data: 0, udata: udata as *mut libc::c_void }
+}
+
+fn socketpair() -> [i32; 2] {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    fds
+}
+
+fn write_all(fd: i32, data: &[u8]) {
+    let res = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+    assert_eq!(res, data.len() as isize);
+}
+
+fn read_all(fd: i32, len: usize) {
+    let mut buf = vec![0u8; len];
+    let res = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), len) };
+    assert_eq!(res, len as isize);
+}
+
+fn register(kq: i32, changes: &[libc::kevent]) {
+    let res = unsafe {
+        libc::kevent(kq, changes.as_ptr(), changes.len() as i32, ptr::null_mut(), 0, ptr::null())
+    };
+    assert_eq!(res, 0);
+}
+
+/// Returns the `(ident, filter, udata)` of the events that are ready now.
+fn ready_events(kq: i32, timeout: Option<libc::timespec>) -> Vec<(usize, i16, usize)> {
+    let mut events = [kevent(0, 0, 0, 0, 0); 8];
+    let timeout = timeout.unwrap_or(libc::timespec { tv_sec: 0, tv_nsec: 0 });
+    let res = unsafe {
+        libc::kevent(kq, ptr::null(), 0, events.as_mut_ptr(), events.len() as i32, &timeout)
+    };
+    assert!(res >= 0, "kevent failed: {}", std::io::Error::last_os_error());
+    events[..res as usize].iter().map(|ev| (ev.ident, ev.filter, ev.udata as usize)).collect()
+}
+
+fn test_read_write_level_triggered() {
+    let kq = unsafe { libc::kqueue() };
+    assert!(kq >= 0);
+    let fds = socketpair();
+    let fd = fds[0] as usize;
+    register(
+        kq,
+        &[
+            kevent(fd, libc::EVFILT_READ, libc::EV_ADD, 0, 1),
+            kevent(fd, libc::EVFILT_WRITE, libc::EV_ADD, 0, 2),
+        ],
+    );
+    // The socket is writable, but there is nothing to read.
+    assert_eq!(ready_events(kq, None), [(fd, libc::EVFILT_WRITE, 2)]);
+
+    write_all(fds[1], b"abc");
+    let both = [(fd, libc::EVFILT_WRITE, 2), (fd, libc::EVFILT_READ, 1)];
+    let mut events = ready_events(kq, None);
+    events.sort();
+    let mut expected = both.to_vec();
+    expected.sort();
+    assert_eq!(events, expected);
+    // Events are level-triggered, so they are reported again.
+    assert_eq!(ready_events(kq, None).len(), 2);
+
+    // Deleted events are no longer reported.
+    register(kq, &[kevent(fd, libc::EVFILT_WRITE, libc::EV_DELETE, 0, 0)]);
+    assert_eq!(ready_events(kq, None), [(fd, libc::EVFILT_READ, 1)]);
+    read_all(fds[0], 3);
+    assert_eq!(ready_events(kq, None), []);
+}
+
+fn test_clear() {
+    let kq = unsafe { libc::kqueue() };
+    let fds = socketpair();
+    let fd = fds[0] as usize;
+    register(kq, &[kevent(fd, libc::EVFILT_READ, libc::EV_ADD | libc::EV_CLEAR, 0, 0)]);
+    assert_eq!(ready_events(kq, None), []);
+
+    write_all(fds[1], b"abc");
+    assert_eq!(ready_events(kq, None), [(fd, libc::EVFILT_READ, 0)]);
+    // The event is only reported again when something happens.
+    assert_eq!(ready_events(kq, None), []);
+    write_all(fds[1], b"def");
+    assert_eq!(ready_events(kq, None), [(fd, libc::EVFILT_READ, 0)]);
+}
+
+fn test_oneshot_and_dispatch() {
+    let kq = unsafe { libc::kqueue() };
+    let fds = socketpair();
+    let fd = fds[0] as usize;
+    register(
+        kq,
+        &[
+            kevent(fd, libc::EVFILT_READ, libc::EV_ADD | libc::EV_ONESHOT, 0, 0),
+            kevent(fd, libc::EVFILT_WRITE, libc::EV_ADD | libc::EV_DISPATCH, 0, 0),
+        ],
+    );
+    write_all(fds[1], b"abc");
+    assert_eq!(ready_events(kq, None).len(), 2);
+    assert_eq!(ready_events(kq, None), []);
+
+    // A dispatched event can be enabled again, but a oneshot event is gone.
+    register(kq, &[kevent(fd, libc::EVFILT_WRITE, libc::EV_ENABLE, 0, 0)]);
+    assert_eq!(ready_events(kq, None), [(fd, libc::EVFILT_WRITE, 0)]);
+    let change = kevent(fd, libc::EVFILT_READ, libc::EV_ENABLE, 0, 0);
+    let res = unsafe { libc::kevent(kq, &change, 1, ptr::null_mut(), 0, ptr::null()) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ENOENT));
+}
+
+fn test_receipt_and_errors() {
+    let kq = unsafe { libc::kqueue() };
+    let fds = socketpair();
+    let changes = [
+        kevent(fds[0] as usize, libc::EVFILT_READ, libc::EV_ADD | libc::EV_RECEIPT, 0, 7),
+        kevent(fds[0] as usize, libc::EVFILT_WRITE, libc::EV_DELETE, 0, 8),
+        kevent(1000, libc::EVFILT_READ, libc::EV_ADD, 0, 9),
+    ];
+    let mut events = [kevent(0, 0, 0, 0, 0); 4];
+    let res = unsafe { libc::kevent(kq, changes.as_ptr(), 3, events.as_mut_ptr(), 4, ptr::null()) };
+    // Receipts and errors are returned without waiting for events.
+    assert_eq!(res, 3);
+    let results: Vec<_> = events[..3]
+        .iter()
+        .map(|ev| {
+            assert_eq!(ev.flags & libc::EV_ERROR, libc::EV_ERROR);
+            (ev.ident, ev.data, ev.udata as usize)
+        })
+        .collect();
+    assert_eq!(
+        results,
+        [
+            (fds[0] as usize, 0, 7),
+            (fds[0] as usize, libc::ENOENT as isize, 8),
+            (1000, libc::EBADF as isize, 9)
+        ]
+    );
+}
+
+fn test_eof() {
+    let kq = unsafe { libc::kqueue() };
+    let fds = socketpair();
+    let fd = fds[0] as usize;
+    register(kq, &[kevent(fd, libc::EVFILT_READ, libc::EV_ADD, 0, 0)]);
+    assert_eq!(unsafe { libc::close(fds[1]) }, 0);
+    let mut event = kevent(0, 0, 0, 0, 0);
+    let res = unsafe { libc::kevent(kq, ptr::null(), 0, &mut event, 1, ptr::null()) };
+    assert_eq!(res, 1);
+    assert_eq!(event.ident, fd);
+    assert_eq!(event.flags & libc::EV_EOF, libc::EV_EOF);
+}
+
+fn test_timeout() {
+    let kq = unsafe { libc::kqueue() };
+    let fds = socketpair();
+    register(kq, &[kevent(fds[0] as usize, libc::EVFILT_READ, libc::EV_ADD, 0, 0)]);
+    let start = std::time::Instant::now();
+    let events = ready_events(kq, Some(libc::timespec { tv_sec: 0, tv_nsec: 10_000_000 }));
+    assert_eq!(events, []);
+    assert!(start.elapsed() >= std::time::Duration::from_millis(10));
+}
+
+fn test_user_event_wakes_waiter() {
+    let kq = unsafe { libc::kqueue() };
+    register(kq, &[kevent(42, libc::EVFILT_USER, libc::EV_ADD | libc::EV_CLEAR, 0, 5)]);
+    assert_eq!(ready_events(kq, None), []);
+
+    let thread = thread::spawn(move || {
+        // Give the main thread time to block.
+        thread::yield_now();
+        register(kq, &[kevent(42, libc::EVFILT_USER, 0, libc::NOTE_TRIGGER, 0)]);
+    });
+    // This blocks until the other thread triggers the event.
+    let mut event = kevent(0, 0, 0, 0, 0);
+    let res = unsafe { libc::kevent(kq, ptr::null(), 0, &mut event, 1, ptr::null()) };
+    assert_eq!(res, 1);
+    assert_eq!((event.ident, event.filter, event.udata as usize), (42, libc::EVFILT_USER, 5));
+    thread.join().unwrap();
+    // The event was cleared when it was reported.
+    assert_eq!(ready_events(kq, None), []);
+}
+
+/// Reporting an event synchronizes with the thread that triggered it.
+fn test_user_event_syncs() {
+    let kq = unsafe { libc::kqueue() };
+    register(kq, &[kevent(7, libc::EVFILT_USER, libc::EV_ADD | libc::EV_CLEAR, 0, 0)]);
+
+    static mut VAL: u8 = 0;
+    let thread = thread::spawn(move || {
+        unsafe { VAL = 1 };
+        register(kq, &[kevent(7, libc::EVFILT_USER, 0, libc::NOTE_TRIGGER, 0)]);
+    });
+    let mut event = kevent(0, 0, 0, 0, 0);
+    let res = unsafe { libc::kevent(kq, ptr::null(), 0, &mut event, 1, ptr::null()) };
+    assert_eq!(res, 1);
+    // The write of the other thread happened before the event was triggered, so this does not
+    // race with it.
+    #[allow(static_mut_refs)]
+    unsafe {
+        assert_eq!(VAL, 1)
+    };
+    thread.join().unwrap();
+}
+
+fn test_read_wakes_waiter() {
+    let kq = unsafe { libc::kqueue() };
+    let fds = socketpair();
+    register(kq, &[kevent(fds[0] as usize, libc::EVFILT_READ, libc::EV_ADD, 0, 0)]);
+
+    let thread = thread::spawn(move || {
+        // Give the main thread time to block.
+        thread::yield_now();
+        write_all(fds[1], b"abc");
+    });
+    // This blocks until the other thread writes.
+    let mut event = kevent(0, 0, 0, 0, 0);
+    let res = unsafe { libc::kevent(kq, ptr::null(), 0, &mut event, 1, ptr::null()) };
+    assert_eq!(res, 1);
+    assert_eq!((event.ident, event.filter), (fds[0] as usize, libc::EVFILT_READ));
+    thread.join().unwrap();
+}
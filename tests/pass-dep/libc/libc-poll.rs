@@ -0,0 +1,148 @@
+//@ignore-target: windows # No libc poll on Windows
+// test_poll_block_then_unblock and test_poll_race depend on a deterministic schedule.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn main() {
+    test_socketpair();
+    test_pipe();
+    test_hangup();
+    test_ignored_and_invalid();
+    test_poll_timeout();
+    test_poll_block_then_unblock();
+    test_poll_race();
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    test_ppoll();
+}
+
+fn pollfd(fd: i32, events: i16) -> libc::pollfd {
+    libc::pollfd { fd, events, revents: 0 }
+}
+
+#[track_caller]
+fn check_poll(fds: &mut [libc::pollfd], timeout: i32, expected: &[i16]) {
+    let res = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+    if res < 0 {
+        panic!("poll failed: {}", std::io::Error::last_os_error());
+    }
+    let revents: Vec<i16> = fds.iter().map(|fd| fd.revents).collect();
+    assert_eq!(revents, expected);
+    assert_eq!(res as usize, expected.iter().filter(|&&revents| revents != 0).count());
+}
+
+fn socketpair() -> [i32; 2] {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    fds
+}
+
+fn write_all(fd: i32, data: &[u8]) {
+    let res = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+    assert_eq!(res, data.len() as isize);
+}
+
+fn test_socketpair() {
+    let fds = socketpair();
+    let events = libc::POLLIN | libc::POLLOUT;
+    // Both ends are writable, but there is nothing to read yet.
+    let mut pollfds = [pollfd(fds[0], events), pollfd(fds[1], events)];
+    check_poll(&mut pollfds, 0, &[libc::POLLOUT, libc::POLLOUT]);
+
+    write_all(fds[0], b"abc");
+    let mut pollfds = [pollfd(fds[0], events), pollfd(fds[1], events)];
+    check_poll(&mut pollfds, 0, &[libc::POLLOUT, libc::POLLIN | libc::POLLOUT]);
+
+    // Only the requested events are reported.
+    let mut pollfds = [pollfd(fds[0], libc::POLLIN), pollfd(fds[1], libc::POLLIN)];
+    check_poll(&mut pollfds, 0, &[0, libc::POLLIN]);
+}
+
+fn test_pipe() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::pipe(fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    let mut pollfds = [pollfd(fds[0], libc::POLLIN), pollfd(fds[1], libc::POLLOUT)];
+    check_poll(&mut pollfds, 0, &[0, libc::POLLOUT]);
+
+    write_all(fds[1], b"abc");
+    let mut pollfds = [pollfd(fds[0], libc::POLLIN), pollfd(fds[1], libc::POLLOUT)];
+    check_poll(&mut pollfds, 0, &[libc::POLLIN, libc::POLLOUT]);
+}
+
+fn test_hangup() {
+    let fds = socketpair();
+    let res = unsafe { libc::close(fds[1]) };
+    assert_eq!(res, 0);
+    // Hang-ups are reported even if they were not requested.
+    let mut pollfds = [pollfd(fds[0], 0)];
+    let res = unsafe { libc::poll(pollfds.as_mut_ptr(), 1, 0) };
+    assert_eq!(res, 1);
+    assert_eq!(pollfds[0].revents & libc::POLLHUP, libc::POLLHUP);
+}
+
+fn test_ignored_and_invalid() {
+    let fds = socketpair();
+    // Negative file descriptors are ignored, unknown ones are reported as invalid.
+    let mut pollfds = [pollfd(-1, libc::POLLOUT), pollfd(1000, libc::POLLIN), pollfd(fds[0], 0)];
+    check_poll(&mut pollfds, 0, &[0, libc::POLLNVAL, 0]);
+}
+
+fn test_poll_timeout() {
+    let fds = socketpair();
+    let mut pollfds = [pollfd(fds[0], libc::POLLIN)];
+    let start = Instant::now();
+    check_poll(&mut pollfds, 10, &[0]);
+    assert!(start.elapsed() >= Duration::from_millis(10));
+}
+
+fn test_poll_block_then_unblock() {
+    let fds = socketpair();
+    let thread = thread::spawn(move || {
+        // Give the main thread time to block.
+        thread::yield_now();
+        write_all(fds[1], b"abc");
+    });
+    // This blocks until the other thread writes.
+    let mut pollfds = [pollfd(fds[0], libc::POLLIN)];
+    check_poll(&mut pollfds, -1, &[libc::POLLIN]);
+    thread.join().unwrap();
+}
+
+// Seeing a file description ready through `poll` synchronizes with the operation that made it
+// ready, even if the data is never read.
+fn test_poll_race() {
+    let fds = socketpair();
+    static mut VAL: u8 = 0;
+    let thread = thread::spawn(move || {
+        unsafe { VAL = 1 };
+        write_all(fds[1], b"abc");
+    });
+    thread::yield_now();
+    let mut pollfds = [pollfd(fds[0], libc::POLLIN)];
+    check_poll(&mut pollfds, -1, &[libc::POLLIN]);
+    #[allow(static_mut_refs)]
+    unsafe {
+        assert_eq!(VAL, 1)
+    };
+    thread.join().unwrap();
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn test_ppoll() {
+    let fds = socketpair();
+    write_all(fds[0], b"abc");
+    let timeout = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    let mut pollfds = [pollfd(fds[1], libc::POLLIN)];
+    let res = unsafe { libc::ppoll(pollfds.as_mut_ptr(), 1, &timeout, std::ptr::null()) };
+    assert_eq!(res, 1);
+    assert_eq!(pollfds[0].revents, libc::POLLIN);
+
+    // An invalid timeout is rejected.
+    let timeout = libc::timespec { tv_sec: 0, tv_nsec: 1_000_000_000 };
+    let res = unsafe { libc::ppoll(pollfds.as_mut_ptr(), 1, &timeout, std::ptr::null()) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EINVAL));
+}
@@ -0,0 +1,132 @@
+//@ignore-target: windows # No libc readv/writev on Windows
+// test_readv_blocks depends on a deterministic schedule.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::io::{IoSlice, IoSliceMut, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::AsRawFd;
+use std::thread;
+
+fn main() {
+    test_pipe();
+    test_partial_read();
+    test_readv_blocks();
+    test_invalid();
+    test_tcp_vectored();
+}
+
+fn iovec(buf: &[u8]) -> libc::iovec {
+    libc::iovec { iov_base: buf.as_ptr() as *mut libc::c_void, iov_len: buf.len() }
+}
+
+fn iovec_mut(buf: &mut [u8]) -> libc::iovec {
+    libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: buf.len() }
+}
+
+fn test_pipe() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::pipe(fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+
+    // Empty buffers are skipped.
+    let iov = [iovec(b"abc"), iovec(b""), iovec(b"defg")];
+    let res = unsafe { libc::writev(fds[1], iov.as_ptr(), 3) };
+    assert_eq!(res, 7);
+
+    let mut buf1 = [0u8; 2];
+    let mut buf2 = [0u8; 5];
+    let iov = [iovec_mut(&mut buf1), iovec_mut(&mut buf2)];
+    let res = unsafe { libc::readv(fds[0], iov.as_ptr(), 2) };
+    assert_eq!(res, 7);
+    assert_eq!(&buf1, b"ab");
+    assert_eq!(&buf2, b"cdefg");
+
+    // Zero buffers transfer nothing.
+    let res = unsafe { libc::writev(fds[1], iov.as_ptr(), 0) };
+    assert_eq!(res, 0);
+}
+
+fn test_partial_read() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+
+    let res = unsafe { libc::write(fds[0], b"hello".as_ptr().cast(), 5) };
+    assert_eq!(res, 5);
+    // Only as much as is available gets read, and later buffers stay untouched.
+    let mut buf1 = [0u8; 3];
+    let mut buf2 = [0u8; 3];
+    let mut buf3 = [0u8; 3];
+    let iov = [iovec_mut(&mut buf1), iovec_mut(&mut buf2), iovec_mut(&mut buf3)];
+    let res = unsafe { libc::readv(fds[1], iov.as_ptr(), 3) };
+    assert_eq!(res, 5);
+    assert_eq!(&buf1, b"hel");
+    assert_eq!(&buf2, b"lo\0");
+    assert_eq!(&buf3, b"\0\0\0");
+}
+
+fn test_readv_blocks() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    let (client_fd, server_fd) = (client.as_raw_fd(), server.as_raw_fd());
+
+    let thread = thread::spawn(move || {
+        // Give the main thread time to block.
+        thread::yield_now();
+        let iov = [iovec(b"12"), iovec(b"34")];
+        let res = unsafe { libc::writev(client_fd, iov.as_ptr(), 2) };
+        assert_eq!(res, 4);
+    });
+    // This blocks until the other thread writes.
+    let mut buf1 = [0u8; 1];
+    let mut buf2 = [0u8; 3];
+    let iov = [iovec_mut(&mut buf1), iovec_mut(&mut buf2)];
+    let res = unsafe { libc::readv(server_fd, iov.as_ptr(), 2) };
+    assert_eq!(res, 4);
+    assert_eq!(&buf1, b"1");
+    assert_eq!(&buf2, b"234");
+    thread.join().unwrap();
+}
+
+fn test_invalid() {
+    let errno = || std::io::Error::last_os_error().raw_os_error().unwrap();
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::pipe(fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+
+    let iov = [iovec(b"abc")];
+    let res = unsafe { libc::writev(fds[1], iov.as_ptr(), -1) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EINVAL);
+    let res = unsafe { libc::writev(fds[1], iov.as_ptr(), 1025) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EINVAL);
+    let res = unsafe { libc::writev(100, iov.as_ptr(), 1) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EBADF);
+}
+
+/// `BufWriter` and friends use vectored writes on sockets.
+fn test_tcp_vectored() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+
+    let written =
+        client.write_vectored(&[IoSlice::new(b"hello "), IoSlice::new(b"world")]).unwrap();
+    assert_eq!(written, 11);
+    drop(client);
+
+    let mut buf1 = [0u8; 6];
+    let mut buf2 = [0u8; 16];
+    let read = server
+        .read_vectored(&mut [IoSliceMut::new(&mut buf1), IoSliceMut::new(&mut buf2)])
+        .unwrap();
+    assert_eq!(read, 11);
+    assert_eq!(&buf1, b"hello ");
+    assert_eq!(&buf2[..5], b"world");
+    let mut rest = Vec::new();
+    server.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+}
@@ -0,0 +1,181 @@
+//@ignore-target: windows # No libc select on Windows
+// test_select_block_then_unblock and test_select_race depend on a deterministic schedule.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::mem::MaybeUninit;
+use std::time::{Duration, Instant};
+use std::{ptr, thread};
+
+fn main() {
+    test_select_ready();
+    test_select_hangup();
+    test_select_invalid();
+    test_select_timeout();
+    test_select_block_then_unblock();
+    test_select_race();
+    test_pselect();
+}
+
+fn socketpair() -> [i32; 2] {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    fds
+}
+
+fn write_all(fd: i32, data: &[u8]) {
+    let res = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+    assert_eq!(res, data.len() as isize);
+}
+
+fn fd_set(fds: &[i32]) -> libc::fd_set {
+    unsafe {
+        let mut set = MaybeUninit::<libc::fd_set>::uninit();
+        libc::FD_ZERO(set.as_mut_ptr());
+        let mut set = set.assume_init();
+        for &fd in fds {
+            libc::FD_SET(fd, &mut set);
+        }
+        set
+    }
+}
+
+fn fds_in(set: &libc::fd_set, fds: &[i32]) -> Vec<i32> {
+    fds.iter().copied().filter(|&fd| unsafe { libc::FD_ISSET(fd, set) }).collect()
+}
+
+fn test_select_ready() {
+    let fds = socketpair();
+    let nfds = fds[0].max(fds[1]) + 1;
+
+    // Both ends are writable, but there is nothing to read yet.
+    let mut readfds = fd_set(&fds);
+    let mut writefds = fd_set(&fds);
+    let mut timeout = libc::timeval { tv_sec: 0, tv_usec: 0 };
+    let res =
+        unsafe { libc::select(nfds, &mut readfds, &mut writefds, ptr::null_mut(), &mut timeout) };
+    assert_eq!(res, 2);
+    assert_eq!(fds_in(&readfds, &fds), []);
+    assert_eq!(fds_in(&writefds, &fds), fds);
+
+    write_all(fds[0], b"abc");
+    let mut readfds = fd_set(&fds);
+    let mut exceptfds = fd_set(&fds);
+    let mut timeout = libc::timeval { tv_sec: 0, tv_usec: 0 };
+    let res =
+        unsafe { libc::select(nfds, &mut readfds, ptr::null_mut(), &mut exceptfds, &mut timeout) };
+    assert_eq!(res, 1);
+    assert_eq!(fds_in(&readfds, &fds), [fds[1]]);
+    assert_eq!(fds_in(&exceptfds, &fds), []);
+}
+
+fn test_select_hangup() {
+    let fds = socketpair();
+    let res = unsafe { libc::close(fds[1]) };
+    assert_eq!(res, 0);
+    // Reading from a socket whose peer is closed does not block.
+    let mut readfds = fd_set(&[fds[0]]);
+    let res = unsafe {
+        libc::select(fds[0] + 1, &mut readfds, ptr::null_mut(), ptr::null_mut(), ptr::null_mut())
+    };
+    assert_eq!(res, 1);
+    assert_eq!(fds_in(&readfds, &[fds[0]]), [fds[0]]);
+}
+
+fn test_select_invalid() {
+    let errno = || std::io::Error::last_os_error().raw_os_error().unwrap();
+
+    // Unknown file descriptors are rejected.
+    let mut readfds = fd_set(&[100]);
+    let mut timeout = libc::timeval { tv_sec: 0, tv_usec: 0 };
+    let res =
+        unsafe { libc::select(101, &mut readfds, ptr::null_mut(), ptr::null_mut(), &mut timeout) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EBADF);
+
+    // So are invalid timeouts.
+    let mut timeout = libc::timeval { tv_sec: 0, tv_usec: 1_000_000 };
+    let res =
+        unsafe { libc::select(0, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), &mut timeout) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EINVAL);
+
+    // And negative `nfds`.
+    let mut timeout = libc::timeval { tv_sec: 0, tv_usec: 0 };
+    let res = unsafe {
+        libc::select(-1, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), &mut timeout)
+    };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EINVAL);
+}
+
+fn test_select_timeout() {
+    let fds = socketpair();
+    let mut readfds = fd_set(&[fds[0]]);
+    let mut timeout = libc::timeval { tv_sec: 0, tv_usec: 10_000 };
+    let start = Instant::now();
+    let res = unsafe {
+        libc::select(fds[0] + 1, &mut readfds, ptr::null_mut(), ptr::null_mut(), &mut timeout)
+    };
+    assert_eq!(res, 0);
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert_eq!(fds_in(&readfds, &[fds[0]]), []);
+}
+
+fn test_select_block_then_unblock() {
+    let fds = socketpair();
+    let thread = thread::spawn(move || {
+        // Give the main thread time to block.
+        thread::yield_now();
+        write_all(fds[1], b"abc");
+    });
+    // This blocks until the other thread writes.
+    let mut readfds = fd_set(&[fds[0]]);
+    let res = unsafe {
+        libc::select(fds[0] + 1, &mut readfds, ptr::null_mut(), ptr::null_mut(), ptr::null_mut())
+    };
+    assert_eq!(res, 1);
+    assert_eq!(fds_in(&readfds, &[fds[0]]), [fds[0]]);
+    thread.join().unwrap();
+}
+
+// Seeing a file description ready through `select` synchronizes with the operation that made it
+// ready, even if the data is never read.
+fn test_select_race() {
+    let fds = socketpair();
+    static mut VAL: u8 = 0;
+    let thread = thread::spawn(move || {
+        unsafe { VAL = 1 };
+        write_all(fds[1], b"abc");
+    });
+    thread::yield_now();
+    let mut readfds = fd_set(&[fds[0]]);
+    let res = unsafe {
+        libc::select(fds[0] + 1, &mut readfds, ptr::null_mut(), ptr::null_mut(), ptr::null_mut())
+    };
+    assert_eq!(res, 1);
+    #[allow(static_mut_refs)]
+    unsafe {
+        assert_eq!(VAL, 1)
+    };
+    thread.join().unwrap();
+}
+
+fn test_pselect() {
+    let fds = socketpair();
+    write_all(fds[0], b"abc");
+    let mut readfds = fd_set(&[fds[1]]);
+    let timeout = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    let res = unsafe {
+        libc::pselect(
+            fds[1] + 1,
+            &mut readfds,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &timeout,
+            ptr::null(),
+        )
+    };
+    assert_eq!(res, 1);
+    assert_eq!(fds_in(&readfds, &[fds[1]]), [fds[1]]);
+}
@@ -0,0 +1,150 @@
+//@only-target: linux freebsd android # POSIX semaphores are not available everywhere
+// `sem_timedwait` uses the real-time clock.
+//@compile-flags: -Zmiri-disable-isolation
+#![feature(sync_unsafe_cell)]
+
+use std::cell::SyncUnsafeCell;
+use std::mem::MaybeUninit;
+use std::thread;
+use std::time::Instant;
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn get_value(sem: *mut libc::sem_t) -> i32 {
+    let mut value = 0;
+    assert_eq!(unsafe { libc::sem_getvalue(sem, &mut value) }, 0);
+    value
+}
+
+fn test_counting() {
+    let mut sem = MaybeUninit::<libc::sem_t>::uninit();
+    let sem = sem.as_mut_ptr();
+    unsafe {
+        assert_eq!(libc::sem_init(sem, 0, 2), 0);
+        assert_eq!(get_value(sem), 2);
+        assert_eq!(libc::sem_wait(sem), 0);
+        assert_eq!(libc::sem_trywait(sem), 0);
+        assert_eq!(libc::sem_trywait(sem), -1);
+        assert_eq!(errno(), libc::EAGAIN);
+        assert_eq!(libc::sem_post(sem), 0);
+        assert_eq!(get_value(sem), 1);
+        assert_eq!(libc::sem_destroy(sem), 0);
+    }
+}
+
+fn test_overflow() {
+    const SEM_VALUE_MAX: u32 = 0x7fff_ffff;
+    let mut sem = MaybeUninit::<libc::sem_t>::uninit();
+    let sem = sem.as_mut_ptr();
+    unsafe {
+        assert_eq!(libc::sem_init(sem, 0, SEM_VALUE_MAX + 1), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        assert_eq!(libc::sem_init(sem, 0, SEM_VALUE_MAX), 0);
+        assert_eq!(libc::sem_post(sem), -1);
+        assert_eq!(errno(), libc::EOVERFLOW);
+        assert_eq!(libc::sem_destroy(sem), 0);
+    }
+}
+
+/// Data handed over with semaphores is synchronized.
+fn test_blocking() {
+    struct Channel {
+        full: MaybeUninit<libc::sem_t>,
+        empty: MaybeUninit<libc::sem_t>,
+        value: usize,
+    }
+    struct SendPtr(*mut Channel);
+    unsafe impl Send for SendPtr {}
+
+    let channel = SyncUnsafeCell::new(Channel {
+        full: MaybeUninit::uninit(),
+        empty: MaybeUninit::uninit(),
+        value: 0,
+    });
+    let ptr = SendPtr(channel.get());
+    unsafe {
+        let channel = channel.get();
+        assert_eq!(libc::sem_init((*channel).full.as_mut_ptr(), 0, 0), 0);
+        assert_eq!(libc::sem_init((*channel).empty.as_mut_ptr(), 0, 1), 0);
+        let consumer = thread::spawn(move || {
+            let channel = { ptr }.0;
+            for expected in 1..=3 {
+                assert_eq!(libc::sem_wait((*channel).full.as_mut_ptr()), 0);
+                assert_eq!((*channel).value, expected);
+                assert_eq!(libc::sem_post((*channel).empty.as_mut_ptr()), 0);
+            }
+        });
+        for value in 1..=3 {
+            assert_eq!(libc::sem_wait((*channel).empty.as_mut_ptr()), 0);
+            (*channel).value = value;
+            assert_eq!(libc::sem_post((*channel).full.as_mut_ptr()), 0);
+        }
+        consumer.join().unwrap();
+        assert_eq!(libc::sem_destroy((*channel).full.as_mut_ptr()), 0);
+        assert_eq!(libc::sem_destroy((*channel).empty.as_mut_ptr()), 0);
+    }
+}
+
+fn test_timedwait() {
+    let mut sem = MaybeUninit::<libc::sem_t>::uninit();
+    let sem = sem.as_mut_ptr();
+    unsafe {
+        assert_eq!(libc::sem_init(sem, 0, 1), 0);
+        // The timeout is not checked if the semaphore can be taken right away.
+        let invalid = libc::timespec { tv_sec: 0, tv_nsec: 1_000_000_000 };
+        assert_eq!(libc::sem_timedwait(sem, &invalid), 0);
+        assert_eq!(libc::sem_timedwait(sem, &invalid), -1);
+        assert_eq!(errno(), libc::EINVAL);
+
+        let mut now = MaybeUninit::<libc::timespec>::uninit();
+        assert_eq!(libc::clock_gettime(libc::CLOCK_REALTIME, now.as_mut_ptr()), 0);
+        let now = now.assume_init();
+        let timeout = libc::timespec { tv_sec: now.tv_sec + 1, tv_nsec: now.tv_nsec };
+        let start = Instant::now();
+        assert_eq!(libc::sem_timedwait(sem, &timeout), -1);
+        assert_eq!(errno(), libc::ETIMEDOUT);
+        let elapsed = start.elapsed().as_millis();
+        assert!(900 <= elapsed && elapsed <= 1300);
+        assert_eq!(libc::sem_destroy(sem), 0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn test_clockwait() {
+    unsafe extern "C" {
+        fn sem_clockwait(
+            sem: *mut libc::sem_t,
+            clock_id: libc::clockid_t,
+            abstime: *const libc::timespec,
+        ) -> i32;
+    }
+
+    let mut sem = MaybeUninit::<libc::sem_t>::uninit();
+    let sem = sem.as_mut_ptr();
+    unsafe {
+        assert_eq!(libc::sem_init(sem, 0, 0), 0);
+        let mut now = MaybeUninit::<libc::timespec>::uninit();
+        assert_eq!(libc::clock_gettime(libc::CLOCK_MONOTONIC, now.as_mut_ptr()), 0);
+        let now = now.assume_init();
+        let timeout = libc::timespec { tv_sec: now.tv_sec + 1, tv_nsec: now.tv_nsec };
+        assert_eq!(sem_clockwait(sem, -1, &timeout), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        let start = Instant::now();
+        assert_eq!(sem_clockwait(sem, libc::CLOCK_MONOTONIC, &timeout), -1);
+        assert_eq!(errno(), libc::ETIMEDOUT);
+        let elapsed = start.elapsed().as_millis();
+        assert!(900 <= elapsed && elapsed <= 1300);
+        assert_eq!(libc::sem_destroy(sem), 0);
+    }
+}
+
+fn main() {
+    test_counting();
+    test_overflow();
+    test_blocking();
+    test_timedwait();
+    #[cfg(target_os = "linux")]
+    test_clockwait();
+}
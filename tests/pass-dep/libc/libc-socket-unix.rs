@@ -0,0 +1,197 @@
+//@only-target: linux android # abstract unix domain socket addresses are Linux-specific
+
+use std::mem::{size_of, zeroed};
+
+fn main() {
+    test_stream();
+    test_bind_errors();
+    test_autobind();
+    test_datagram();
+}
+
+fn new_socket(type_: i32) -> i32 {
+    let fd = unsafe { libc::socket(libc::AF_UNIX, type_, 0) };
+    assert!(fd >= 0);
+    fd
+}
+
+/// Returns the `sockaddr_un` for the abstract `name`, together with its length.
+fn abstract_addr(name: &[u8]) -> (libc::sockaddr_un, libc::socklen_t) {
+    let mut addr: libc::sockaddr_un = unsafe { zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    for (dst, src) in addr.sun_path[1..].iter_mut().zip(name) {
+        *dst = *src as libc::c_char;
+    }
+    let len = size_of::<libc::sa_family_t>() + 1 + name.len();
+    (addr, len as libc::socklen_t)
+}
+
+/// Returns the abstract name in `addr`, or `None` for an unnamed address.
+fn abstract_name(addr: &libc::sockaddr_un, len: libc::socklen_t) -> Option<Vec<u8>> {
+    let path_len = len as usize - size_of::<libc::sa_family_t>();
+    if path_len == 0 {
+        return None;
+    }
+    assert_eq!(addr.sun_path[0], 0);
+    Some(addr.sun_path[1..path_len].iter().map(|&c| c as u8).collect())
+}
+
+fn bind(fd: i32, name: &[u8]) -> i32 {
+    let (addr, len) = abstract_addr(name);
+    unsafe { libc::bind(fd, (&raw const addr).cast(), len) }
+}
+
+fn connect(fd: i32, name: &[u8]) -> i32 {
+    let (addr, len) = abstract_addr(name);
+    unsafe { libc::connect(fd, (&raw const addr).cast(), len) }
+}
+
+fn local_name(fd: i32) -> Option<Vec<u8>> {
+    let mut addr: libc::sockaddr_un = unsafe { zeroed() };
+    let mut len = size_of::<libc::sockaddr_un>() as libc::socklen_t;
+    let res = unsafe { libc::getsockname(fd, (&raw mut addr).cast(), &mut len) };
+    assert_eq!(res, 0);
+    abstract_name(&addr, len)
+}
+
+fn test_stream() {
+    let listener = new_socket(libc::SOCK_STREAM);
+    assert_eq!(bind(listener, b"miri-stream"), 0);
+    assert_eq!(unsafe { libc::listen(listener, 16) }, 0);
+    assert_eq!(local_name(listener).as_deref(), Some(&b"miri-stream"[..]));
+
+    let client = new_socket(libc::SOCK_STREAM);
+    assert_eq!(connect(client, b"miri-stream"), 0);
+    let mut peer: libc::sockaddr_un = unsafe { zeroed() };
+    let mut len = size_of::<libc::sockaddr_un>() as libc::socklen_t;
+    let server = unsafe { libc::accept(listener, (&raw mut peer).cast(), &mut len) };
+    assert!(server >= 0);
+    // The client did not bind, so it has no name.
+    assert_eq!(abstract_name(&peer, len), None);
+    assert_eq!(local_name(client), None);
+    assert_eq!(local_name(server).as_deref(), Some(&b"miri-stream"[..]));
+
+    // Data flows in both directions.
+    let res = unsafe { libc::write(client, b"abc".as_ptr().cast(), 3) };
+    assert_eq!(res, 3);
+    let mut buf = [0u8; 3];
+    let res = unsafe { libc::read(server, buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, 3);
+    assert_eq!(&buf, b"abc");
+    let res = unsafe { libc::write(server, b"def".as_ptr().cast(), 3) };
+    assert_eq!(res, 3);
+    let res = unsafe { libc::read(client, buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, 3);
+    assert_eq!(&buf, b"def");
+
+    assert_eq!(unsafe { libc::close(client) }, 0);
+    assert_eq!(unsafe { libc::close(server) }, 0);
+    assert_eq!(unsafe { libc::close(listener) }, 0);
+
+    // Once the listener is closed, its name is free again.
+    let listener = new_socket(libc::SOCK_STREAM);
+    assert_eq!(bind(listener, b"miri-stream"), 0);
+    assert_eq!(unsafe { libc::close(listener) }, 0);
+}
+
+fn test_bind_errors() {
+    let errno = || std::io::Error::last_os_error().raw_os_error().unwrap();
+
+    let fd = new_socket(libc::SOCK_STREAM);
+    assert_eq!(bind(fd, b"miri-in-use"), 0);
+    let other = new_socket(libc::SOCK_STREAM);
+    assert_eq!(bind(other, b"miri-in-use"), -1);
+    assert_eq!(errno(), libc::EADDRINUSE);
+
+    // Nobody is listening.
+    assert_eq!(connect(other, b"miri-in-use"), -1);
+    assert_eq!(errno(), libc::ECONNREFUSED);
+    assert_eq!(connect(other, b"miri-nobody"), -1);
+    assert_eq!(errno(), libc::ECONNREFUSED);
+
+    // An unbound socket cannot listen.
+    assert_eq!(unsafe { libc::listen(other, 16) }, -1);
+    assert_eq!(errno(), libc::EINVAL);
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+    assert_eq!(unsafe { libc::close(other) }, 0);
+}
+
+fn test_autobind() {
+    // Binding to an address without a name picks an unused name.
+    let fd = new_socket(libc::SOCK_STREAM);
+    let addr =
+        libc::sockaddr_un { sun_family: libc::AF_UNIX as libc::sa_family_t, sun_path: [0; 108] };
+    let len = size_of::<libc::sa_family_t>() as libc::socklen_t;
+    assert_eq!(unsafe { libc::bind(fd, (&raw const addr).cast(), len) }, 0);
+    let name = local_name(fd).unwrap();
+    assert_eq!(name.len(), 5);
+    assert!(name.iter().all(u8::is_ascii_hexdigit));
+
+    // Others can connect to that name.
+    assert_eq!(unsafe { libc::listen(fd, 16) }, 0);
+    let client = new_socket(libc::SOCK_STREAM);
+    assert_eq!(connect(client, &name), 0);
+
+    assert_eq!(unsafe { libc::close(client) }, 0);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+fn test_datagram() {
+    let receiver = new_socket(libc::SOCK_DGRAM);
+    assert_eq!(bind(receiver, b"miri-dgram"), 0);
+
+    // An unbound sender stays unnamed.
+    let sender = new_socket(libc::SOCK_DGRAM);
+    let (addr, len) = abstract_addr(b"miri-dgram");
+    let res = unsafe {
+        libc::sendto(sender, b"ping".as_ptr().cast(), 4, 0, (&raw const addr).cast(), len)
+    };
+    assert_eq!(res, 4);
+    let mut buf = [0u8; 8];
+    let mut from: libc::sockaddr_un = unsafe { zeroed() };
+    let mut from_len = size_of::<libc::sockaddr_un>() as libc::socklen_t;
+    let res = unsafe {
+        libc::recvfrom(
+            receiver,
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+            0,
+            (&raw mut from).cast(),
+            &mut from_len,
+        )
+    };
+    assert_eq!(res, 4);
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(abstract_name(&from, from_len), None);
+
+    // A named sender is reported, so the receiver can reply.
+    assert_eq!(bind(sender, b"miri-dgram-sender"), 0);
+    assert_eq!(connect(sender, b"miri-dgram"), 0);
+    let res = unsafe { libc::send(sender, b"pong".as_ptr().cast(), 4, 0) };
+    assert_eq!(res, 4);
+    let mut from_len = size_of::<libc::sockaddr_un>() as libc::socklen_t;
+    let res = unsafe {
+        libc::recvfrom(
+            receiver,
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+            0,
+            (&raw mut from).cast(),
+            &mut from_len,
+        )
+    };
+    assert_eq!(res, 4);
+    assert_eq!(abstract_name(&from, from_len).as_deref(), Some(&b"miri-dgram-sender"[..]));
+
+    // Unlike with UDP, sending to a name that nobody is bound to fails.
+    let (addr, len) = abstract_addr(b"miri-nobody");
+    let res = unsafe {
+        libc::sendto(receiver, b"ping".as_ptr().cast(), 4, 0, (&raw const addr).cast(), len)
+    };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ECONNREFUSED));
+
+    assert_eq!(unsafe { libc::close(sender) }, 0);
+    assert_eq!(unsafe { libc::close(receiver) }, 0);
+}
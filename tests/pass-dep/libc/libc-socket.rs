@@ -0,0 +1,594 @@
+//@ignore-target: windows # No libc sockets on Windows
+// The blocking tests depend on a deterministic schedule.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::mem::{size_of, zeroed};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn main() {
+    test_bind_ephemeral();
+    test_bind_in_use();
+    test_connect_refused();
+    test_connect_accept();
+    test_accept_blocking();
+    test_not_connected();
+    test_udp_sendto_recvfrom();
+    test_udp_connect();
+    test_udp_recv_blocking();
+    test_sockopt();
+    test_udp_sendmsg_recvmsg();
+    test_udp_recv_flags();
+    test_timeouts();
+}
+
+fn new_udp_socket() -> i32 {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+    assert!(fd >= 0);
+    fd
+}
+
+fn sendto(fd: i32, data: &[u8], addr: &libc::sockaddr_in) -> isize {
+    unsafe {
+        libc::sendto(
+            fd,
+            data.as_ptr().cast(),
+            data.len(),
+            0,
+            (addr as *const libc::sockaddr_in).cast(),
+            size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    }
+}
+
+fn new_socket() -> i32 {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    assert!(fd >= 0);
+    fd
+}
+
+fn loopback(port: u16) -> libc::sockaddr_in {
+    let mut addr: libc::sockaddr_in = unsafe { zeroed() };
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    addr.sin_port = port.to_be();
+    addr.sin_addr.s_addr = u32::from_be_bytes([127, 0, 0, 1]).to_be();
+    addr
+}
+
+fn bind(fd: i32, addr: &libc::sockaddr_in) -> i32 {
+    unsafe {
+        libc::bind(
+            fd,
+            (addr as *const libc::sockaddr_in).cast(),
+            size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    }
+}
+
+fn connect(fd: i32, addr: &libc::sockaddr_in) -> i32 {
+    unsafe {
+        libc::connect(
+            fd,
+            (addr as *const libc::sockaddr_in).cast(),
+            size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    }
+}
+
+fn local_addr(fd: i32) -> libc::sockaddr_in {
+    let mut addr: libc::sockaddr_in = unsafe { zeroed() };
+    let mut len = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    let res = unsafe { libc::getsockname(fd, (&raw mut addr).cast(), &mut len) };
+    assert_eq!(res, 0);
+    assert_eq!(len as usize, size_of::<libc::sockaddr_in>());
+    addr
+}
+
+/// Creates a socket listening on an ephemeral port, and returns it together with its address.
+fn listener() -> (i32, libc::sockaddr_in) {
+    let fd = new_socket();
+    assert_eq!(bind(fd, &loopback(0)), 0);
+    assert_eq!(unsafe { libc::listen(fd, 8) }, 0);
+    (fd, local_addr(fd))
+}
+
+fn test_bind_ephemeral() {
+    let fd = new_socket();
+    assert_eq!(bind(fd, &loopback(0)), 0);
+    let addr = local_addr(fd);
+    assert_eq!(addr.sin_family, libc::AF_INET as libc::sa_family_t);
+    assert_ne!(u16::from_be(addr.sin_port), 0);
+    assert_eq!(addr.sin_addr.s_addr, loopback(0).sin_addr.s_addr);
+
+    // Binding twice is an error.
+    assert_eq!(bind(fd, &loopback(0)), -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EINVAL));
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+fn test_bind_in_use() {
+    let fd1 = new_socket();
+    assert_eq!(bind(fd1, &loopback(0)), 0);
+    let addr = local_addr(fd1);
+
+    let fd2 = new_socket();
+    assert_eq!(bind(fd2, &addr), -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EADDRINUSE));
+
+    // Once the first socket is closed, the address can be used again.
+    assert_eq!(unsafe { libc::close(fd1) }, 0);
+    assert_eq!(bind(fd2, &addr), 0);
+    assert_eq!(unsafe { libc::close(fd2) }, 0);
+}
+
+fn test_connect_refused() {
+    // Bound, but not listening.
+    let fd1 = new_socket();
+    assert_eq!(bind(fd1, &loopback(0)), 0);
+    let addr = local_addr(fd1);
+
+    let fd2 = new_socket();
+    assert_eq!(connect(fd2, &addr), -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ECONNREFUSED));
+
+    assert_eq!(unsafe { libc::close(fd1) }, 0);
+    assert_eq!(unsafe { libc::close(fd2) }, 0);
+}
+
+fn test_connect_accept() {
+    let (listener, addr) = listener();
+
+    let client = new_socket();
+    assert_eq!(connect(client, &addr), 0);
+    // Connecting again is an error.
+    assert_eq!(connect(client, &addr), -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EISCONN));
+
+    let mut peer: libc::sockaddr_in = unsafe { zeroed() };
+    let mut len = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    let server = unsafe { libc::accept(listener, (&raw mut peer).cast(), &mut len) };
+    assert!(server >= 0);
+    assert_eq!(len as usize, size_of::<libc::sockaddr_in>());
+
+    // Both ends agree on the addresses.
+    let client_addr = local_addr(client);
+    assert_eq!(peer.sin_port, client_addr.sin_port);
+    assert_eq!(local_addr(server).sin_port, addr.sin_port);
+    let mut client_peer: libc::sockaddr_in = unsafe { zeroed() };
+    let mut len = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    let res = unsafe { libc::getpeername(client, (&raw mut client_peer).cast(), &mut len) };
+    assert_eq!(res, 0);
+    assert_eq!(client_peer.sin_port, addr.sin_port);
+
+    // Data flows in both directions.
+    let data = "abcde".as_bytes();
+    let res = unsafe { libc::send(client, data.as_ptr().cast(), data.len(), 0) };
+    assert_eq!(res, 5);
+    let mut buf = [0u8; 5];
+    let res = unsafe { libc::recv(server, buf.as_mut_ptr().cast(), buf.len(), 0) };
+    assert_eq!(res, 5);
+    assert_eq!(&buf, data);
+
+    let res = unsafe { libc::write(server, data.as_ptr().cast(), data.len()) };
+    assert_eq!(res, 5);
+    let mut buf = [0u8; 5];
+    let res = unsafe { libc::read(client, buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, 5);
+    assert_eq!(&buf, data);
+
+    // Closing one end means EOF for the other.
+    assert_eq!(unsafe { libc::close(server) }, 0);
+    let res = unsafe { libc::read(client, buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, 0);
+
+    assert_eq!(unsafe { libc::close(client) }, 0);
+    assert_eq!(unsafe { libc::close(listener) }, 0);
+}
+
+fn test_accept_blocking() {
+    let (listener, addr) = listener();
+
+    let thread = thread::spawn(move || {
+        let server = unsafe { libc::accept(listener, std::ptr::null_mut(), std::ptr::null_mut()) };
+        assert!(server >= 0);
+        let mut buf = [0u8; 3];
+        // This blocks until the main thread has written.
+        let res = unsafe { libc::read(server, buf.as_mut_ptr().cast(), buf.len()) };
+        assert_eq!(res, 3);
+        assert_eq!(&buf, b"abc");
+        assert_eq!(unsafe { libc::close(server) }, 0);
+    });
+    // Let the other thread block in `accept`.
+    thread::yield_now();
+
+    let client = new_socket();
+    assert_eq!(connect(client, &addr), 0);
+    thread::yield_now();
+    let res = unsafe { libc::write(client, b"abc".as_ptr().cast(), 3) };
+    assert_eq!(res, 3);
+    thread.join().unwrap();
+
+    assert_eq!(unsafe { libc::close(client) }, 0);
+    assert_eq!(unsafe { libc::close(listener) }, 0);
+}
+
+fn test_not_connected() {
+    let fd = new_socket();
+    let mut buf = [0u8; 1];
+    let res = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ENOTCONN));
+
+    let mut addr: libc::sockaddr_in = unsafe { zeroed() };
+    let mut len = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    let res = unsafe { libc::getpeername(fd, (&raw mut addr).cast(), &mut len) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ENOTCONN));
+
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+fn test_udp_sendto_recvfrom() {
+    let receiver = new_udp_socket();
+    assert_eq!(bind(receiver, &loopback(0)), 0);
+    let receiver_addr = local_addr(receiver);
+
+    // Sending from an unbound socket binds it.
+    let sender = new_udp_socket();
+    assert_eq!(sendto(sender, b"hello", &receiver_addr), 5);
+    assert_eq!(sendto(sender, b"", &receiver_addr), 0);
+    assert_eq!(sendto(sender, b"world!", &receiver_addr), 6);
+    let sender_addr = local_addr(sender);
+    assert_ne!(u16::from_be(sender_addr.sin_port), 0);
+
+    // Message boundaries are preserved.
+    let mut buf = [0u8; 16];
+    let mut from: libc::sockaddr_in = unsafe { zeroed() };
+    let mut len = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    let res = unsafe {
+        libc::recvfrom(
+            receiver,
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+            0,
+            (&raw mut from).cast(),
+            &mut len,
+        )
+    };
+    assert_eq!(res, 5);
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(len as usize, size_of::<libc::sockaddr_in>());
+    assert_eq!(from.sin_port, sender_addr.sin_port);
+    assert_eq!(from.sin_addr.s_addr, loopback(0).sin_addr.s_addr);
+
+    // Empty datagrams are datagrams, too.
+    let res = unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), buf.len(), 0) };
+    assert_eq!(res, 0);
+
+    // The rest of a datagram that does not fit into the buffer is discarded.
+    let res = unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), 3, 0) };
+    assert_eq!(res, 3);
+    assert_eq!(&buf[..3], b"wor");
+
+    // Sending to an address nobody listens on silently drops the datagram.
+    let unbound = new_udp_socket();
+    assert_eq!(bind(unbound, &loopback(0)), 0);
+    let unbound_addr = local_addr(unbound);
+    assert_eq!(unsafe { libc::close(unbound) }, 0);
+    assert_eq!(sendto(sender, b"lost", &unbound_addr), 4);
+
+    assert_eq!(unsafe { libc::close(sender) }, 0);
+    assert_eq!(unsafe { libc::close(receiver) }, 0);
+}
+
+fn test_udp_connect() {
+    let a = new_udp_socket();
+    assert_eq!(bind(a, &loopback(0)), 0);
+    let a_addr = local_addr(a);
+    let b = new_udp_socket();
+    assert_eq!(bind(b, &loopback(0)), 0);
+    let b_addr = local_addr(b);
+    let other = new_udp_socket();
+
+    // Without a destination, there is nowhere to send to.
+    let res = unsafe { libc::write(a, b"x".as_ptr().cast(), 1) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EDESTADDRREQ));
+
+    assert_eq!(connect(a, &b_addr), 0);
+    assert_eq!(unsafe { libc::write(a, b"abc".as_ptr().cast(), 3) }, 3);
+    let mut buf = [0u8; 3];
+    assert_eq!(unsafe { libc::read(b, buf.as_mut_ptr().cast(), buf.len()) }, 3);
+    assert_eq!(&buf, b"abc");
+
+    // A connected socket ignores datagrams from other addresses.
+    assert_eq!(sendto(other, b"no", &a_addr), 2);
+    assert_eq!(sendto(b, b"yes", &a_addr), 3);
+    assert_eq!(unsafe { libc::read(a, buf.as_mut_ptr().cast(), buf.len()) }, 3);
+    assert_eq!(&buf, b"yes");
+
+    assert_eq!(unsafe { libc::close(a) }, 0);
+    assert_eq!(unsafe { libc::close(b) }, 0);
+    assert_eq!(unsafe { libc::close(other) }, 0);
+}
+
+fn test_udp_recv_blocking() {
+    let receiver = new_udp_socket();
+    assert_eq!(bind(receiver, &loopback(0)), 0);
+    let receiver_addr = local_addr(receiver);
+
+    let thread = thread::spawn(move || {
+        let mut buf = [0u8; 4];
+        // This blocks until the main thread has sent something.
+        let res = unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), buf.len(), 0) };
+        assert_eq!(res, 4);
+        assert_eq!(&buf, b"ping");
+    });
+    // Let the other thread block.
+    thread::yield_now();
+
+    let sender = new_udp_socket();
+    assert_eq!(sendto(sender, b"ping", &receiver_addr), 4);
+    thread.join().unwrap();
+
+    assert_eq!(unsafe { libc::close(sender) }, 0);
+    assert_eq!(unsafe { libc::close(receiver) }, 0);
+}
+
+fn getsockopt_int(fd: i32, level: i32, name: i32) -> i32 {
+    let mut value: libc::c_int = -1;
+    let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+    let res = unsafe { libc::getsockopt(fd, level, name, (&raw mut value).cast(), &mut len) };
+    assert_eq!(res, 0);
+    assert_eq!(len as usize, size_of::<libc::c_int>());
+    value
+}
+
+fn setsockopt_int(fd: i32, level: i32, name: i32, value: i32) -> i32 {
+    unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            (&raw const value).cast(),
+            size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    }
+}
+
+fn test_sockopt() {
+    let fd = new_socket();
+
+    assert_eq!(getsockopt_int(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR), 0);
+    assert_eq!(setsockopt_int(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1), 0);
+    assert_ne!(getsockopt_int(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR), 0);
+
+    assert_eq!(getsockopt_int(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY), 0);
+    assert_eq!(setsockopt_int(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY, 1), 0);
+    assert_ne!(getsockopt_int(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY), 0);
+
+    assert!(getsockopt_int(fd, libc::SOL_SOCKET, libc::SO_RCVBUF) > 0);
+    assert_eq!(setsockopt_int(fd, libc::SOL_SOCKET, libc::SO_RCVBUF, 4096), 0);
+    // Linux doubles the requested size.
+    assert!(getsockopt_int(fd, libc::SOL_SOCKET, libc::SO_RCVBUF) >= 4096);
+    assert_eq!(setsockopt_int(fd, libc::SOL_SOCKET, libc::SO_SNDBUF, 8192), 0);
+    assert!(getsockopt_int(fd, libc::SOL_SOCKET, libc::SO_SNDBUF) >= 8192);
+
+    assert_eq!(getsockopt_int(fd, libc::SOL_SOCKET, libc::SO_ERROR), 0);
+
+    let linger = libc::linger { l_onoff: 1, l_linger: 5 };
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_LINGER,
+            (&raw const linger).cast(),
+            size_of::<libc::linger>() as libc::socklen_t,
+        )
+    };
+    assert_eq!(res, 0);
+    let mut linger = libc::linger { l_onoff: 0, l_linger: 0 };
+    let mut len = size_of::<libc::linger>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_LINGER, (&raw mut linger).cast(), &mut len)
+    };
+    assert_eq!(res, 0);
+    assert_eq!(len as usize, size_of::<libc::linger>());
+    assert_ne!(linger.l_onoff, 0);
+    assert_eq!(linger.l_linger, 5);
+
+    // TCP options do not apply to UDP sockets.
+    let udp = new_udp_socket();
+    assert_eq!(setsockopt_int(udp, libc::IPPROTO_TCP, libc::TCP_NODELAY, 1), -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ENOPROTOOPT));
+
+    assert_eq!(unsafe { libc::close(udp) }, 0);
+    assert_eq!(unsafe { libc::close(fd) }, 0);
+}
+
+fn test_udp_sendmsg_recvmsg() {
+    let receiver = new_udp_socket();
+    assert_eq!(bind(receiver, &loopback(0)), 0);
+    let mut receiver_addr = local_addr(receiver);
+    let sender = new_udp_socket();
+
+    // The buffers of one `sendmsg` form a single datagram.
+    let (a, b) = (*b"hello ", *b"world");
+    let mut iov = [
+        libc::iovec { iov_base: a.as_ptr() as *mut libc::c_void, iov_len: a.len() },
+        libc::iovec { iov_base: b.as_ptr() as *mut libc::c_void, iov_len: b.len() },
+    ];
+    let mut msg: libc::msghdr = unsafe { zeroed() };
+    msg.msg_name = (&raw mut receiver_addr).cast();
+    msg.msg_namelen = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    msg.msg_iov = iov.as_mut_ptr();
+    msg.msg_iovlen = iov.len() as _;
+    assert_eq!(unsafe { libc::sendmsg(sender, &msg, 0) }, 11);
+    assert_eq!(unsafe { libc::sendmsg(sender, &msg, 0) }, 11);
+    let sender_addr = local_addr(sender);
+
+    let mut x = [0u8; 4];
+    let mut y = [0u8; 16];
+    let mut iov = [
+        libc::iovec { iov_base: x.as_mut_ptr().cast(), iov_len: x.len() },
+        libc::iovec { iov_base: y.as_mut_ptr().cast(), iov_len: y.len() },
+    ];
+    let mut from: libc::sockaddr_in = unsafe { zeroed() };
+    let mut msg: libc::msghdr = unsafe { zeroed() };
+    msg.msg_name = (&raw mut from).cast();
+    msg.msg_namelen = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    msg.msg_iov = iov.as_mut_ptr();
+    msg.msg_iovlen = iov.len() as _;
+    assert_eq!(unsafe { libc::recvmsg(receiver, &mut msg, 0) }, 11);
+    assert_eq!(&x, b"hell");
+    assert_eq!(&y[..7], b"o world");
+    assert_eq!(msg.msg_flags, 0);
+    assert_eq!(msg.msg_namelen as usize, size_of::<libc::sockaddr_in>());
+    assert_eq!(from.sin_port, sender_addr.sin_port);
+
+    // A datagram that does not fit is truncated, and `MSG_TRUNC` is set.
+    msg.msg_iovlen = 1;
+    assert_eq!(unsafe { libc::recvmsg(receiver, &mut msg, 0) }, 4);
+    assert_eq!(&x, b"hell");
+    assert_eq!(msg.msg_flags, libc::MSG_TRUNC);
+
+    assert_eq!(unsafe { libc::close(sender) }, 0);
+    assert_eq!(unsafe { libc::close(receiver) }, 0);
+}
+
+fn test_udp_recv_flags() {
+    let receiver = new_udp_socket();
+    assert_eq!(bind(receiver, &loopback(0)), 0);
+    let receiver_addr = local_addr(receiver);
+    let sender = new_udp_socket();
+    let mut buf = [0u8; 16];
+
+    // `MSG_DONTWAIT` makes a blocking socket fail instead of blocking.
+    let res =
+        unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), buf.len(), libc::MSG_DONTWAIT) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EAGAIN));
+
+    // `MSG_PEEK` leaves the datagram in the queue.
+    assert_eq!(sendto(sender, b"first", &receiver_addr), 5);
+    assert_eq!(sendto(sender, b"second", &receiver_addr), 6);
+    let res = unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), 2, libc::MSG_PEEK) };
+    assert_eq!(res, 2);
+    assert_eq!(&buf[..2], b"fi");
+    let res = unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), buf.len(), 0) };
+    assert_eq!(res, 5);
+    assert_eq!(&buf[..5], b"first");
+    let res = unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), buf.len(), 0) };
+    assert_eq!(res, 6);
+    assert_eq!(&buf[..6], b"second");
+
+    assert_eq!(unsafe { libc::close(sender) }, 0);
+    assert_eq!(unsafe { libc::close(receiver) }, 0);
+}
+
+fn set_timeout(fd: i32, name: i32, tv_sec: libc::time_t, tv_usec: libc::suseconds_t) -> i32 {
+    let timeout = libc::timeval { tv_sec, tv_usec };
+    unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            name,
+            (&raw const timeout).cast(),
+            size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    }
+}
+
+fn test_timeouts() {
+    let errno = || std::io::Error::last_os_error().raw_os_error().unwrap();
+
+    // The timeouts are reported by `getsockopt`, and invalid ones are rejected.
+    let receiver = new_udp_socket();
+    assert_eq!(bind(receiver, &loopback(0)), 0);
+    assert_eq!(set_timeout(receiver, libc::SO_RCVTIMEO, 0, 10_000), 0);
+    let mut timeout = libc::timeval { tv_sec: -1, tv_usec: -1 };
+    let mut len = size_of::<libc::timeval>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            receiver,
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            (&raw mut timeout).cast(),
+            &mut len,
+        )
+    };
+    assert_eq!(res, 0);
+    assert_eq!((timeout.tv_sec, timeout.tv_usec), (0, 10_000));
+    assert_eq!(set_timeout(receiver, libc::SO_SNDTIMEO, 0, 1_000_000), -1);
+    assert_eq!(errno(), libc::EDOM);
+
+    // Receiving a datagram times out.
+    let mut buf = [0u8; 4];
+    let start = Instant::now();
+    let res = unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), buf.len(), 0) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EAGAIN);
+    assert!(start.elapsed() >= Duration::from_millis(10));
+
+    // But not if something arrives in time.
+    let receiver_addr = local_addr(receiver);
+    assert_eq!(set_timeout(receiver, libc::SO_RCVTIMEO, 10, 0), 0);
+    let thread = thread::spawn(move || {
+        let sender = new_udp_socket();
+        assert_eq!(sendto(sender, b"ping", &receiver_addr), 4);
+        assert_eq!(unsafe { libc::close(sender) }, 0);
+    });
+    let res = unsafe { libc::recv(receiver, buf.as_mut_ptr().cast(), buf.len(), 0) };
+    assert_eq!(res, 4);
+    thread.join().unwrap();
+    assert_eq!(unsafe { libc::close(receiver) }, 0);
+
+    // `accept` honors the receive timeout.
+    let (listener, addr) = listener();
+    assert_eq!(set_timeout(listener, libc::SO_RCVTIMEO, 0, 10_000), 0);
+    let res = unsafe { libc::accept(listener, std::ptr::null_mut(), std::ptr::null_mut()) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EAGAIN);
+
+    // Reading from a connected stream socket times out.
+    let client = new_socket();
+    assert_eq!(connect(client, &addr), 0);
+    let server = unsafe { libc::accept(listener, std::ptr::null_mut(), std::ptr::null_mut()) };
+    assert!(server >= 0);
+    assert_eq!(set_timeout(client, libc::SO_RCVTIMEO, 0, 10_000), 0);
+    let res = unsafe { libc::read(client, buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EAGAIN);
+
+    // Writing times out once the peer's buffer is full.
+    assert_eq!(set_timeout(client, libc::SO_SNDTIMEO, 0, 10_000), 0);
+    let data = vec![0u8; 1 << 16];
+    loop {
+        let res = unsafe { libc::write(client, data.as_ptr().cast(), data.len()) };
+        if res == -1 {
+            assert_eq!(errno(), libc::EAGAIN);
+            break;
+        }
+        assert!(res > 0);
+    }
+    // So does `sendmsg`.
+    let mut iov = libc::iovec { iov_base: data.as_ptr() as *mut libc::c_void, iov_len: data.len() };
+    let mut msg: libc::msghdr = unsafe { zeroed() };
+    msg.msg_iov = &raw mut iov;
+    msg.msg_iovlen = 1;
+    let start = Instant::now();
+    let res = unsafe { libc::sendmsg(client, &msg, 0) };
+    assert_eq!(res, -1);
+    assert_eq!(errno(), libc::EAGAIN);
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    // The timed out threads are not woken up when the peer becomes ready.
+    let res = unsafe { libc::write(server, b"abc".as_ptr().cast(), 3) };
+    assert_eq!(res, 3);
+    let res = unsafe { libc::read(server, buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, 4);
+
+    assert_eq!(unsafe { libc::close(client) }, 0);
+    assert_eq!(unsafe { libc::close(server) }, 0);
+    assert_eq!(unsafe { libc::close(listener) }, 0);
+}
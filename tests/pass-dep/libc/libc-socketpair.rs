@@ -5,11 +5,17 @@
 // FIXME(static_mut_refs): Do not allow `static_mut_refs` lint
 #![allow(static_mut_refs)]
 
+use std::mem::size_of;
 use std::thread;
 fn main() {
     test_socketpair();
     test_socketpair_threaded();
     test_race();
+    test_shutdown();
+    test_sendmsg_recvmsg();
+    test_scm_rights();
+    test_recv_flags();
+    test_seqpacket();
 }
 
 fn test_socketpair() {
@@ -136,3 +142,234 @@ fn test_race() {
     thread::yield_now();
     thread1.join().unwrap();
 }
+
+fn test_shutdown() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+
+    // Data written before the shutdown can still be read, then there is EOF.
+    let data = "abc".as_bytes().as_ptr();
+    let res = unsafe { libc::write(fds[0], data as *const libc::c_void, 3) };
+    assert_eq!(res, 3);
+    let res = unsafe { libc::shutdown(fds[0], libc::SHUT_WR) };
+    assert_eq!(res, 0);
+    let res = unsafe { libc::write(fds[0], data as *const libc::c_void, 3) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EPIPE));
+    let mut buf: [u8; 5] = [0; 5];
+    let res = unsafe { libc::read(fds[1], buf.as_mut_ptr().cast(), buf.len() as libc::size_t) };
+    assert_eq!(res, 3);
+    assert_eq!(&buf[0..3], "abc".as_bytes());
+    let res = unsafe { libc::read(fds[1], buf.as_mut_ptr().cast(), buf.len() as libc::size_t) };
+    assert_eq!(res, 0);
+
+    // The other direction still works.
+    let res = unsafe { libc::write(fds[1], data as *const libc::c_void, 3) };
+    assert_eq!(res, 3);
+    let res = unsafe { libc::read(fds[0], buf.as_mut_ptr().cast(), buf.len() as libc::size_t) };
+    assert_eq!(res, 3);
+
+    // After shutting down reading, reads return EOF and the peer cannot write.
+    let res = unsafe { libc::shutdown(fds[0], libc::SHUT_RD) };
+    assert_eq!(res, 0);
+    let res = unsafe { libc::read(fds[0], buf.as_mut_ptr().cast(), buf.len() as libc::size_t) };
+    assert_eq!(res, 0);
+    let res = unsafe { libc::write(fds[1], data as *const libc::c_void, 3) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EPIPE));
+
+    // Invalid arguments.
+    let res = unsafe { libc::shutdown(fds[0], 42) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EINVAL));
+    let mut pipefd = [-1, -1];
+    let res = unsafe { libc::pipe(pipefd.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    let res = unsafe { libc::shutdown(pipefd[0], libc::SHUT_RDWR) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ENOTSOCK));
+}
+
+fn test_sendmsg_recvmsg() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+
+    // The data of all buffers is sent in order.
+    let (a, b, c) = (*b"abc", *b"", *b"defg");
+    let mut iov = [
+        libc::iovec { iov_base: a.as_ptr() as *mut libc::c_void, iov_len: a.len() },
+        libc::iovec { iov_base: b.as_ptr() as *mut libc::c_void, iov_len: b.len() },
+        libc::iovec { iov_base: c.as_ptr() as *mut libc::c_void, iov_len: c.len() },
+    ];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = iov.as_mut_ptr();
+    msg.msg_iovlen = iov.len() as _;
+    let res = unsafe { libc::sendmsg(fds[0], &msg, 0) };
+    assert_eq!(res, 7);
+
+    // The data is scattered into the buffers in order.
+    let mut x = [0u8; 2];
+    let mut y = [0u8; 8];
+    let mut iov = [
+        libc::iovec { iov_base: x.as_mut_ptr().cast(), iov_len: x.len() },
+        libc::iovec { iov_base: y.as_mut_ptr().cast(), iov_len: y.len() },
+    ];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = iov.as_mut_ptr();
+    msg.msg_iovlen = iov.len() as _;
+    let res = unsafe { libc::recvmsg(fds[1], &mut msg, 0) };
+    assert_eq!(res, 7);
+    assert_eq!(&x, b"ab");
+    assert_eq!(&y[..5], b"cdefg");
+    assert_eq!(msg.msg_flags, 0);
+    assert_eq!(msg.msg_controllen, 0);
+
+    // `recvmsg` only works on sockets.
+    let mut pipefd = [-1, -1];
+    let res = unsafe { libc::pipe(pipefd.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    let res = unsafe { libc::recvmsg(pipefd[0], &mut msg, 0) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::ENOTSOCK));
+}
+
+fn test_scm_rights() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    let mut pipefd = [-1, -1];
+    let res = unsafe { libc::pipe(pipefd.as_mut_ptr()) };
+    assert_eq!(res, 0);
+
+    // Send the read end of the pipe along with one byte of data.
+    let data = *b"x";
+    let mut iov = [libc::iovec { iov_base: data.as_ptr() as *mut libc::c_void, iov_len: 1 }];
+    let space = unsafe { libc::CMSG_SPACE(size_of::<i32>() as u32) } as usize;
+    let mut control = vec![0u64; space.div_ceil(8)];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = iov.as_mut_ptr();
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = space as _;
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<i32>() as u32) as _;
+        libc::CMSG_DATA(cmsg).cast::<i32>().write_unaligned(pipefd[0]);
+    }
+    let res = unsafe { libc::sendmsg(fds[0], &msg, 0) };
+    assert_eq!(res, 1);
+    // Closing the original fd does not affect the one in flight.
+    let res = unsafe { libc::close(pipefd[0]) };
+    assert_eq!(res, 0);
+
+    let mut buf = [0u8; 4];
+    let mut iov = [libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: buf.len() }];
+    let mut control = vec![0u64; space.div_ceil(8)];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = iov.as_mut_ptr();
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = space as _;
+    let res = unsafe { libc::recvmsg(fds[1], &mut msg, 0) };
+    assert_eq!(res, 1);
+    assert_eq!(buf[0], b'x');
+    assert_eq!(msg.msg_flags, 0);
+    let received = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        assert!(!cmsg.is_null());
+        assert_eq!((*cmsg).cmsg_level, libc::SOL_SOCKET);
+        assert_eq!((*cmsg).cmsg_type, libc::SCM_RIGHTS);
+        assert_eq!((*cmsg).cmsg_len as usize, libc::CMSG_LEN(size_of::<i32>() as u32) as usize);
+        libc::CMSG_DATA(cmsg).cast::<i32>().read_unaligned()
+    };
+
+    // The received fd refers to the read end of the pipe.
+    let res = unsafe { libc::write(pipefd[1], data.as_ptr().cast(), 1) };
+    assert_eq!(res, 1);
+    let res = unsafe { libc::read(received, buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, 1);
+    assert_eq!(buf[0], b'x');
+
+    // Passing an invalid fd fails.
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        libc::CMSG_DATA(cmsg).cast::<i32>().write_unaligned(1000);
+    }
+    msg.msg_controllen = space as _;
+    let res = unsafe { libc::sendmsg(fds[0], &msg, 0) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EBADF));
+}
+
+fn test_recv_flags() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    let mut buf = [0u8; 8];
+
+    // `MSG_DONTWAIT` makes a blocking socket fail instead of blocking.
+    let res = unsafe { libc::recv(fds[1], buf.as_mut_ptr().cast(), buf.len(), libc::MSG_DONTWAIT) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EAGAIN));
+
+    // `MSG_PEEK` leaves the data in the buffer.
+    let data = *b"\x03abc";
+    let res = unsafe { libc::send(fds[0], data.as_ptr().cast(), data.len(), 0) };
+    assert_eq!(res, 4);
+    let res = unsafe { libc::recv(fds[1], buf.as_mut_ptr().cast(), 1, libc::MSG_PEEK) };
+    assert_eq!(res, 1);
+    assert_eq!(buf[0], 3);
+    let res = unsafe {
+        libc::recv(fds[1], buf.as_mut_ptr().cast(), buf.len(), libc::MSG_PEEK | libc::MSG_DONTWAIT)
+    };
+    assert_eq!(res, 4);
+    assert_eq!(&buf[..4], &data);
+    let res = unsafe { libc::recv(fds[1], buf.as_mut_ptr().cast(), buf.len(), 0) };
+    assert_eq!(res, 4);
+    assert_eq!(&buf[..4], &data);
+}
+
+fn test_seqpacket() {
+    let mut fds = [-1, -1];
+    let res = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_SEQPACKET, 0, fds.as_mut_ptr()) };
+    assert_eq!(res, 0);
+    let mut buf = [0u8; 8];
+
+    // Every read returns exactly one message, in order.
+    for msg in [&b"abc"[..], b"defgh", b"ij"] {
+        let res = unsafe { libc::write(fds[0], msg.as_ptr().cast(), msg.len()) };
+        assert_eq!(res, msg.len() as isize);
+    }
+    let res = unsafe { libc::read(fds[1], buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, 3);
+    assert_eq!(&buf[..3], b"abc");
+
+    // Peeking does not consume the message.
+    let res = unsafe { libc::recv(fds[1], buf.as_mut_ptr().cast(), 2, libc::MSG_PEEK) };
+    assert_eq!(res, 2);
+    assert_eq!(&buf[..2], b"de");
+
+    // The part of a message that does not fit is discarded, which `recvmsg` reports.
+    let mut iov = libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: 2 };
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    let res = unsafe { libc::recvmsg(fds[1], &mut msg, 0) };
+    assert_eq!(res, 2);
+    assert_eq!(&buf[..2], b"de");
+    assert_eq!(msg.msg_flags & libc::MSG_TRUNC, libc::MSG_TRUNC);
+
+    let res = unsafe { libc::read(fds[1], buf.as_mut_ptr().cast(), buf.len()) };
+    assert_eq!(res, 2);
+    assert_eq!(&buf[..2], b"ij");
+
+    // Messages that can never fit into the buffer are rejected.
+    let big = vec![0u8; 1 << 20];
+    let res = unsafe { libc::write(fds[0], big.as_ptr().cast(), big.len()) };
+    assert_eq!(res, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EMSGSIZE));
+}
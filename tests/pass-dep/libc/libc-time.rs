@@ -6,6 +6,14 @@ fn main() {
     test_clocks();
     test_posix_gettimeofday();
     test_localtime_r();
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "solaris",
+        target_os = "illumos"
+    ))]
+    test_clock_nanosleep_realtime();
 }
 
 /// Tests whether clock support exists at all
@@ -30,6 +38,31 @@ fn test_clocks() {
     }
 }
 
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "solaris",
+    target_os = "illumos"
+))]
+fn test_clock_nanosleep_realtime() {
+    let mut tp = mem::MaybeUninit::<libc::timespec>::uninit();
+    assert_eq!(unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, tp.as_mut_ptr()) }, 0);
+    let mut deadline = unsafe { tp.assume_init() };
+    deadline.tv_nsec += 50_000_000;
+    if deadline.tv_nsec >= 1_000_000_000 {
+        deadline.tv_sec += 1;
+        deadline.tv_nsec -= 1_000_000_000;
+    }
+    let res = unsafe {
+        libc::clock_nanosleep(libc::CLOCK_REALTIME, libc::TIMER_ABSTIME, &deadline, ptr::null_mut())
+    };
+    assert_eq!(res, 0);
+    assert_eq!(unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, tp.as_mut_ptr()) }, 0);
+    let now = unsafe { tp.assume_init() };
+    assert!((now.tv_sec, now.tv_nsec) >= (deadline.tv_sec, deadline.tv_nsec));
+}
+
 fn test_posix_gettimeofday() {
     let mut tp = mem::MaybeUninit::<libc::timeval>::uninit();
     let tz = ptr::null_mut::<libc::timezone>();
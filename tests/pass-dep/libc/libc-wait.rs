@@ -0,0 +1,131 @@
+//@only-target: linux # the layout of `siginfo_t` differs between targets
+//@compile-flags: -Zmiri-command-script=tests/pass-dep/libc/libc-wait.script
+
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+fn spawn(command: &str) -> libc::pid_t {
+    let args: Vec<CString> = command.split(' ').map(|arg| CString::new(arg).unwrap()).collect();
+    let mut argv: Vec<*mut libc::c_char> = args.iter().map(|arg| arg.as_ptr().cast_mut()).collect();
+    argv.push(ptr::null_mut());
+    let envp = [ptr::null_mut()];
+    let mut pid = 0;
+    let res = unsafe {
+        libc::posix_spawnp(
+            &mut pid,
+            argv[0],
+            ptr::null(),
+            ptr::null(),
+            argv.as_ptr(),
+            envp.as_ptr(),
+        )
+    };
+    assert_eq!(res, 0);
+    pid
+}
+
+fn errno() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap()
+}
+
+fn main() {
+    test_waitpid();
+    test_wait_any();
+    test_wait4();
+    test_waitid();
+}
+
+fn test_waitpid() {
+    unsafe {
+        let pid = spawn("false");
+        let mut status = 0;
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 1);
+        // The child can only be waited for once.
+        assert_eq!(libc::waitpid(pid, &mut status, 0), -1);
+        assert_eq!(errno(), libc::ECHILD);
+
+        let pid = spawn("sleep infinity");
+        // The child already terminated, so `WNOHANG` returns it right away.
+        assert_eq!(libc::waitpid(pid, &mut status, libc::WNOHANG), pid);
+        assert!(libc::WIFSIGNALED(status));
+        assert_eq!(libc::WTERMSIG(status), libc::SIGKILL);
+
+        // The status is optional.
+        let pid = spawn("true");
+        assert_eq!(libc::waitpid(pid, ptr::null_mut(), 0), pid);
+    }
+}
+
+fn test_wait_any() {
+    unsafe {
+        let first = spawn("true");
+        let second = spawn("false");
+        let mut status = 0;
+        assert_eq!(libc::waitpid(-1, &mut status, 0), first);
+        assert_eq!(status, 0);
+        assert_eq!(libc::wait(&mut status), second);
+        assert_eq!(libc::WEXITSTATUS(status), 1);
+        assert_eq!(libc::wait(&mut status), -1);
+        assert_eq!(errno(), libc::ECHILD);
+    }
+}
+
+fn test_wait4() {
+    unsafe {
+        let pid = spawn("false");
+        let mut status = 0;
+        let mut rusage = MaybeUninit::<libc::rusage>::uninit();
+        assert_eq!(libc::wait4(pid, &mut status, 0, rusage.as_mut_ptr()), pid);
+        assert_eq!(libc::WEXITSTATUS(status), 1);
+        let rusage = rusage.assume_init();
+        assert_eq!(rusage.ru_utime.tv_sec, 0);
+    }
+}
+
+fn test_waitid() {
+    unsafe {
+        let pid = spawn("false");
+        let mut info = MaybeUninit::<libc::siginfo_t>::uninit();
+        // With `WNOWAIT`, the child can be waited for again.
+        assert_eq!(
+            libc::waitid(
+                libc::P_PID,
+                pid.try_into().unwrap(),
+                info.as_mut_ptr(),
+                libc::WEXITED | libc::WNOWAIT,
+            ),
+            0
+        );
+        let info = info.assume_init();
+        assert_eq!(info.si_signo, libc::SIGCHLD);
+        assert_eq!(info.si_code, libc::CLD_EXITED);
+        assert_eq!(info.si_pid(), pid);
+        assert_eq!(info.si_uid(), libc::getuid());
+        assert_eq!(info.si_status(), 1);
+
+        let mut info = MaybeUninit::<libc::siginfo_t>::uninit();
+        assert_eq!(libc::waitid(libc::P_ALL, 0, info.as_mut_ptr(), libc::WEXITED), 0);
+        assert_eq!(info.assume_init().si_pid(), pid);
+
+        let pid = spawn("sleep infinity");
+        let mut info = MaybeUninit::<libc::siginfo_t>::uninit();
+        assert_eq!(
+            libc::waitid(libc::P_ALL, 0, info.as_mut_ptr(), libc::WEXITED | libc::WNOHANG),
+            0
+        );
+        let info = info.assume_init();
+        assert_eq!(info.si_code, libc::CLD_KILLED);
+        assert_eq!(info.si_pid(), pid);
+        assert_eq!(info.si_status(), libc::SIGKILL);
+
+        let mut info = MaybeUninit::<libc::siginfo_t>::uninit();
+        // Without anything to wait for, the call is invalid.
+        assert_eq!(libc::waitid(libc::P_ALL, 0, info.as_mut_ptr(), 0), -1);
+        assert_eq!(errno(), libc::EINVAL);
+        assert_eq!(libc::waitid(libc::P_ALL, 0, info.as_mut_ptr(), libc::WEXITED), -1);
+        assert_eq!(errno(), libc::ECHILD);
+    }
+}
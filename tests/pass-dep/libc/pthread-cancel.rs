@@ -0,0 +1,252 @@
+//@only-target: linux # `_pthread_cleanup_push` and the cancellation constants are Linux-specific
+
+use std::ffi::c_void;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+// The `libc` crate does not declare these.
+const PTHREAD_CANCEL_ENABLE: i32 = 0;
+const PTHREAD_CANCEL_DISABLE: i32 = 1;
+const PTHREAD_CANCEL_DEFERRED: i32 = 0;
+const PTHREAD_CANCEL_ASYNCHRONOUS: i32 = 1;
+
+extern "C" {
+    fn pthread_setcancelstate(state: i32, oldstate: *mut i32) -> i32;
+    fn pthread_setcanceltype(ty: i32, oldtype: *mut i32) -> i32;
+    fn pthread_testcancel();
+    fn _pthread_cleanup_push(
+        buffer: *mut c_void,
+        routine: extern "C" fn(*mut c_void),
+        arg: *mut c_void,
+    );
+    fn _pthread_cleanup_pop(buffer: *mut c_void, execute: i32);
+}
+
+/// Records the order in which cleanup handlers ran, one decimal digit per handler.
+static CLEANUP: AtomicU32 = AtomicU32::new(0);
+/// Set by threads right before they reach the cancellation point under test.
+static READY: AtomicBool = AtomicBool::new(false);
+/// Set by threads if they get past the point where they should have been cancelled.
+static NOT_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+fn sleep(secs: libc::time_t) {
+    let req = libc::timespec { tv_sec: secs, tv_nsec: 0 };
+    unsafe { libc::nanosleep(&req, ptr::null_mut()) };
+}
+
+extern "C" fn cleanup(arg: *mut c_void) {
+    let digit = u32::try_from(arg.addr()).unwrap();
+    CLEANUP.store(CLEANUP.load(Ordering::Relaxed) * 10 + digit, Ordering::Relaxed);
+}
+
+/// Runs `start` on a new thread, cancels it once it is `READY`, and joins it.
+fn cancel_thread(start: extern "C" fn(*mut c_void) -> *mut c_void) {
+    CLEANUP.store(0, Ordering::Relaxed);
+    READY.store(false, Ordering::Relaxed);
+    unsafe {
+        let mut thread = MaybeUninit::<libc::pthread_t>::uninit();
+        assert_eq!(
+            libc::pthread_create(thread.as_mut_ptr(), ptr::null(), start, ptr::null_mut()),
+            0
+        );
+        let thread = thread.assume_init();
+        while !READY.load(Ordering::Acquire) {
+            libc::sched_yield();
+        }
+        assert_eq!(libc::pthread_cancel(thread), 0);
+        assert_eq!(libc::pthread_join(thread, ptr::null_mut()), 0);
+    }
+    assert!(!NOT_CANCELLED.load(Ordering::Relaxed));
+}
+
+fn cancel_state() {
+    unsafe {
+        let mut old = -1;
+        assert_eq!(pthread_setcancelstate(PTHREAD_CANCEL_DISABLE, &mut old), 0);
+        assert_eq!(old, PTHREAD_CANCEL_ENABLE);
+        assert_eq!(pthread_setcancelstate(42, &mut old), libc::EINVAL);
+        assert_eq!(pthread_setcancelstate(PTHREAD_CANCEL_ENABLE, ptr::null_mut()), 0);
+        assert_eq!(pthread_setcancelstate(PTHREAD_CANCEL_ENABLE, &mut old), 0);
+        assert_eq!(old, PTHREAD_CANCEL_ENABLE);
+
+        assert_eq!(pthread_setcanceltype(PTHREAD_CANCEL_ASYNCHRONOUS, &mut old), 0);
+        assert_eq!(old, PTHREAD_CANCEL_DEFERRED);
+        assert_eq!(pthread_setcanceltype(42, &mut old), libc::EINVAL);
+        assert_eq!(pthread_setcanceltype(PTHREAD_CANCEL_DEFERRED, &mut old), 0);
+        assert_eq!(old, PTHREAD_CANCEL_ASYNCHRONOUS);
+
+        // Without a cancellation request, this does nothing.
+        pthread_testcancel();
+    }
+}
+
+fn cancel_sleep() {
+    extern "C" fn start(_arg: *mut c_void) -> *mut c_void {
+        let mut buffer = MaybeUninit::<[usize; 4]>::uninit();
+        let mut inner = MaybeUninit::<[usize; 4]>::uninit();
+        unsafe {
+            _pthread_cleanup_push(
+                buffer.as_mut_ptr().cast(),
+                cleanup,
+                ptr::without_provenance_mut(1),
+            );
+            _pthread_cleanup_push(
+                inner.as_mut_ptr().cast(),
+                cleanup,
+                ptr::without_provenance_mut(2),
+            );
+            READY.store(true, Ordering::Release);
+            sleep(3600);
+            NOT_CANCELLED.store(true, Ordering::Relaxed);
+            _pthread_cleanup_pop(inner.as_mut_ptr().cast(), 0);
+            _pthread_cleanup_pop(buffer.as_mut_ptr().cast(), 0);
+        }
+        ptr::null_mut()
+    }
+
+    cancel_thread(start);
+    // The innermost handler runs first.
+    assert_eq!(CLEANUP.load(Ordering::Relaxed), 21);
+}
+
+fn cancel_futex_wait() {
+    extern "C" fn start(_arg: *mut c_void) -> *mut c_void {
+        static FUTEX: AtomicU32 = AtomicU32::new(0);
+        let mut buffer = MaybeUninit::<[usize; 4]>::uninit();
+        unsafe {
+            _pthread_cleanup_push(
+                buffer.as_mut_ptr().cast(),
+                cleanup,
+                ptr::without_provenance_mut(3),
+            );
+            READY.store(true, Ordering::Release);
+            // Nobody ever wakes us up.
+            libc::syscall(
+                libc::SYS_futex,
+                &FUTEX,
+                libc::FUTEX_WAIT,
+                0,
+                ptr::null::<libc::timespec>(),
+            );
+            NOT_CANCELLED.store(true, Ordering::Relaxed);
+            _pthread_cleanup_pop(buffer.as_mut_ptr().cast(), 0);
+        }
+        ptr::null_mut()
+    }
+
+    cancel_thread(start);
+    assert_eq!(CLEANUP.load(Ordering::Relaxed), 3);
+}
+
+fn cancel_disabled_then_read() {
+    extern "C" fn start(_arg: *mut c_void) -> *mut c_void {
+        let mut buffer = MaybeUninit::<[usize; 4]>::uninit();
+        let mut fds = [-1; 2];
+        unsafe {
+            assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+            assert_eq!(libc::write(fds[1], [1u8].as_ptr().cast(), 1), 1);
+            _pthread_cleanup_push(
+                buffer.as_mut_ptr().cast(),
+                cleanup,
+                ptr::without_provenance_mut(4),
+            );
+            // This handler is popped before the cancellation, so it never runs.
+            let mut popped = MaybeUninit::<[usize; 4]>::uninit();
+            _pthread_cleanup_push(
+                popped.as_mut_ptr().cast(),
+                cleanup,
+                ptr::without_provenance_mut(5),
+            );
+            _pthread_cleanup_pop(popped.as_mut_ptr().cast(), 0);
+
+            assert_eq!(pthread_setcancelstate(PTHREAD_CANCEL_DISABLE, ptr::null_mut()), 0);
+            READY.store(true, Ordering::Release);
+            // Wait until the main thread cancelled us. Sleeping is not a cancellation point while
+            // cancellation is disabled.
+            sleep(1);
+            assert_eq!(pthread_setcancelstate(PTHREAD_CANCEL_ENABLE, ptr::null_mut()), 0);
+            // The request was kept pending, and `read` acts upon it even though there is data.
+            let mut byte = 0u8;
+            libc::read(fds[0], (&raw mut byte).cast(), 1);
+            NOT_CANCELLED.store(true, Ordering::Relaxed);
+            _pthread_cleanup_pop(buffer.as_mut_ptr().cast(), 0);
+        }
+        ptr::null_mut()
+    }
+
+    cancel_thread(start);
+    assert_eq!(CLEANUP.load(Ordering::Relaxed), 4);
+}
+
+fn cancel_testcancel() {
+    extern "C" fn start(_arg: *mut c_void) -> *mut c_void {
+        unsafe {
+            assert_eq!(pthread_setcancelstate(PTHREAD_CANCEL_DISABLE, ptr::null_mut()), 0);
+            READY.store(true, Ordering::Release);
+            sleep(1);
+            assert_eq!(pthread_setcancelstate(PTHREAD_CANCEL_ENABLE, ptr::null_mut()), 0);
+            pthread_testcancel();
+            NOT_CANCELLED.store(true, Ordering::Relaxed);
+        }
+        ptr::null_mut()
+    }
+
+    cancel_thread(start);
+}
+
+fn cancel_self_asynchronous() {
+    extern "C" fn start(_arg: *mut c_void) -> *mut c_void {
+        let mut buffer = MaybeUninit::<[usize; 4]>::uninit();
+        unsafe {
+            _pthread_cleanup_push(
+                buffer.as_mut_ptr().cast(),
+                cleanup,
+                ptr::without_provenance_mut(6),
+            );
+            assert_eq!(pthread_setcanceltype(PTHREAD_CANCEL_ASYNCHRONOUS, ptr::null_mut()), 0);
+            // Asynchronous cancellation takes effect right away.
+            libc::pthread_cancel(libc::pthread_self());
+            NOT_CANCELLED.store(true, Ordering::Relaxed);
+            _pthread_cleanup_pop(buffer.as_mut_ptr().cast(), 0);
+        }
+        ptr::null_mut()
+    }
+
+    CLEANUP.store(0, Ordering::Relaxed);
+    unsafe {
+        let mut thread = MaybeUninit::<libc::pthread_t>::uninit();
+        assert_eq!(
+            libc::pthread_create(thread.as_mut_ptr(), ptr::null(), start, ptr::null_mut()),
+            0
+        );
+        assert_eq!(libc::pthread_join(thread.assume_init(), ptr::null_mut()), 0);
+    }
+    assert!(!NOT_CANCELLED.load(Ordering::Relaxed));
+    assert_eq!(CLEANUP.load(Ordering::Relaxed), 6);
+}
+
+fn cleanup_pop_execute() {
+    let mut outer = MaybeUninit::<[usize; 4]>::uninit();
+    let mut inner = MaybeUninit::<[usize; 4]>::uninit();
+    CLEANUP.store(0, Ordering::Relaxed);
+    unsafe {
+        _pthread_cleanup_push(outer.as_mut_ptr().cast(), cleanup, ptr::without_provenance_mut(7));
+        _pthread_cleanup_push(inner.as_mut_ptr().cast(), cleanup, ptr::without_provenance_mut(8));
+        // Popping with a non-zero `execute` runs the handler, and then returns normally.
+        _pthread_cleanup_pop(inner.as_mut_ptr().cast(), 1);
+        assert_eq!(CLEANUP.load(Ordering::Relaxed), 8);
+        _pthread_cleanup_pop(outer.as_mut_ptr().cast(), 0);
+    }
+    assert_eq!(CLEANUP.load(Ordering::Relaxed), 8);
+}
+
+fn main() {
+    cancel_state();
+    cleanup_pop_execute();
+    cancel_sleep();
+    cancel_futex_wait();
+    cancel_disabled_then_read();
+    cancel_testcancel();
+    cancel_self_asynchronous();
+}
@@ -0,0 +1,36 @@
+//@only-target: linux # thread priorities are only supported on Linux
+//@compile-flags: -Zmiri-priority-scheduling -Zmiri-preemption-rate=0
+use std::os::unix::thread::JoinHandleExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+static DONE: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    let handle = thread::spawn(|| DONE.store(true, Ordering::Relaxed));
+    // Once it has a higher priority than us, the new thread runs to completion before we can
+    // continue, even though neither of us blocks.
+    let param = libc::sched_param { sched_priority: 1 };
+    assert_eq!(
+        unsafe { libc::pthread_setschedparam(handle.as_pthread_t(), libc::SCHED_FIFO, &param) },
+        0
+    );
+    assert!(DONE.load(Ordering::Relaxed));
+    handle.join().unwrap();
+
+    // Nice values order threads of the same static priority. A thread that yields only makes way
+    // for threads of at least its own priority, so the main thread does not run until the
+    // spawned thread is done spinning.
+    unsafe { libc::nice(1) };
+    DONE.store(false, Ordering::Relaxed);
+    let handle = thread::spawn(|| {
+        unsafe { libc::nice(-1) };
+        for _ in 0..10 {
+            thread::yield_now();
+        }
+        DONE.store(true, Ordering::Relaxed);
+    });
+    thread::yield_now();
+    assert!(DONE.load(Ordering::Relaxed));
+    handle.join().unwrap();
+}
@@ -0,0 +1,84 @@
+//@only-target: linux # thread priorities are only supported on Linux
+use std::mem::MaybeUninit;
+use std::os::unix::thread::JoinHandleExt;
+use std::thread;
+
+fn get_sched_param(thread: libc::pthread_t) -> (i32, i32) {
+    let mut policy = 0;
+    let mut param = MaybeUninit::<libc::sched_param>::uninit();
+    assert_eq!(unsafe { libc::pthread_getschedparam(thread, &mut policy, param.as_mut_ptr()) }, 0);
+    (policy, unsafe { param.assume_init() }.sched_priority)
+}
+
+fn set_sched_param(thread: libc::pthread_t, policy: i32, sched_priority: i32) -> i32 {
+    let param = libc::sched_param { sched_priority };
+    unsafe { libc::pthread_setschedparam(thread, policy, &param) }
+}
+
+fn test_sched_param() {
+    let this = unsafe { libc::pthread_self() };
+    assert_eq!(get_sched_param(this), (libc::SCHED_OTHER, 0));
+
+    unsafe {
+        assert_eq!(libc::sched_get_priority_min(libc::SCHED_OTHER), 0);
+        assert_eq!(libc::sched_get_priority_max(libc::SCHED_OTHER), 0);
+        assert_eq!(libc::sched_get_priority_min(libc::SCHED_FIFO), 1);
+        assert_eq!(libc::sched_get_priority_max(libc::SCHED_FIFO), 99);
+        assert_eq!(libc::sched_get_priority_max(-1), -1);
+        assert_eq!(*libc::__errno_location(), libc::EINVAL);
+    }
+
+    assert_eq!(set_sched_param(this, libc::SCHED_RR, 10), 0);
+    assert_eq!(get_sched_param(this), (libc::SCHED_RR, 10));
+    // Out-of-range priorities and unknown policies are rejected, and leave the thread unchanged.
+    assert_eq!(set_sched_param(this, libc::SCHED_FIFO, 100), libc::EINVAL);
+    assert_eq!(set_sched_param(this, libc::SCHED_OTHER, 1), libc::EINVAL);
+    assert_eq!(set_sched_param(this, -1, 0), libc::EINVAL);
+    assert_eq!(get_sched_param(this), (libc::SCHED_RR, 10));
+
+    // New threads inherit the scheduling attributes of their creator.
+    let handle = thread::spawn(|| get_sched_param(unsafe { libc::pthread_self() }));
+    assert_eq!(handle.join().unwrap(), (libc::SCHED_RR, 10));
+
+    assert_eq!(set_sched_param(this, libc::SCHED_OTHER, 0), 0);
+}
+
+fn test_nice() {
+    unsafe {
+        assert_eq!(libc::getpriority(libc::PRIO_PROCESS, 0), 0);
+        assert_eq!(libc::setpriority(libc::PRIO_PROCESS, 0, 5), 0);
+        assert_eq!(libc::getpriority(libc::PRIO_PROCESS, 0), 5);
+        // The nice value is clamped to the range from -20 to 19.
+        assert_eq!(libc::nice(100), 19);
+        assert_eq!(libc::setpriority(libc::PRIO_PROCESS, 0, -100), 0);
+        assert_eq!(libc::getpriority(libc::PRIO_PROCESS, 0), -20);
+        assert_eq!(libc::nice(20), 0);
+    }
+
+    // Other threads are addressed by their TID.
+    let handle = thread::spawn(|| unsafe {
+        assert_eq!(libc::nice(3), 3);
+        libc::gettid()
+    });
+    let tid = handle.join().unwrap();
+    unsafe {
+        assert_eq!(libc::getpriority(libc::PRIO_PROCESS, tid as libc::id_t), 3);
+        assert_eq!(libc::getpriority(libc::PRIO_PROCESS, 0), 0);
+        assert_eq!(libc::getpriority(libc::PRIO_PROCESS, u32::MAX), -1);
+        assert_eq!(*libc::__errno_location(), libc::ESRCH);
+    }
+}
+
+fn test_priority_of_other_thread() {
+    let handle = thread::spawn(|| {});
+    let thread = handle.as_pthread_t();
+    assert_eq!(set_sched_param(thread, libc::SCHED_FIFO, 1), 0);
+    assert_eq!(get_sched_param(thread), (libc::SCHED_FIFO, 1));
+    handle.join().unwrap();
+}
+
+fn main() {
+    test_sched_param();
+    test_nice();
+    test_priority_of_other_thread();
+}
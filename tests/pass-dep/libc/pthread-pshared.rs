@@ -0,0 +1,136 @@
+//@only-target: linux freebsd android # not all of these attributes exist on other targets
+//! Process-shared synchronization objects work like private ones (there is only one process),
+//! but Miri warns about them.
+
+use std::mem::MaybeUninit;
+
+fn main() {
+    check_mutexattr();
+    check_condattr();
+    check_rwlockattr();
+    check_spinlock();
+    check_freed_without_destroy();
+}
+
+fn check_mutexattr() {
+    unsafe {
+        let mut attr = MaybeUninit::<libc::pthread_mutexattr_t>::uninit();
+        assert_eq!(libc::pthread_mutexattr_init(attr.as_mut_ptr()), 0);
+        let mut pshared = -1;
+        assert_eq!(libc::pthread_mutexattr_getpshared(attr.as_ptr(), &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_PRIVATE);
+
+        assert_eq!(libc::pthread_mutexattr_setpshared(attr.as_mut_ptr(), 42), libc::EINVAL);
+        assert_eq!(
+            libc::pthread_mutexattr_setpshared(attr.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED),
+            0,
+        );
+        // Setting the kind must not reset the pshared attribute, and vice versa.
+        assert_eq!(
+            libc::pthread_mutexattr_settype(attr.as_mut_ptr(), libc::PTHREAD_MUTEX_RECURSIVE),
+            0,
+        );
+        assert_eq!(libc::pthread_mutexattr_getpshared(attr.as_ptr(), &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_SHARED);
+
+        let mut mutex = MaybeUninit::<libc::pthread_mutex_t>::uninit();
+        assert_eq!(libc::pthread_mutex_init(mutex.as_mut_ptr(), attr.as_ptr()), 0);
+        assert_eq!(libc::pthread_mutex_lock(mutex.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_mutex_lock(mutex.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_mutex_unlock(mutex.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_mutex_unlock(mutex.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_mutex_destroy(mutex.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_mutexattr_destroy(attr.as_mut_ptr()), 0);
+    }
+}
+
+fn check_condattr() {
+    unsafe {
+        let mut attr = MaybeUninit::<libc::pthread_condattr_t>::uninit();
+        assert_eq!(libc::pthread_condattr_init(attr.as_mut_ptr()), 0);
+        let mut pshared = -1;
+        assert_eq!(libc::pthread_condattr_getpshared(attr.as_ptr(), &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_PRIVATE);
+
+        assert_eq!(libc::pthread_condattr_setpshared(attr.as_mut_ptr(), 42), libc::EINVAL);
+        assert_eq!(
+            libc::pthread_condattr_setpshared(attr.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED),
+            0,
+        );
+        // Setting the clock must not reset the pshared attribute, and vice versa.
+        assert_eq!(libc::pthread_condattr_setclock(attr.as_mut_ptr(), libc::CLOCK_MONOTONIC), 0);
+        assert_eq!(libc::pthread_condattr_getpshared(attr.as_ptr(), &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_SHARED);
+        let mut clock_id = MaybeUninit::<libc::clockid_t>::uninit();
+        assert_eq!(libc::pthread_condattr_getclock(attr.as_ptr(), clock_id.as_mut_ptr()), 0);
+        assert_eq!(clock_id.assume_init(), libc::CLOCK_MONOTONIC);
+
+        let mut cond = MaybeUninit::<libc::pthread_cond_t>::uninit();
+        assert_eq!(libc::pthread_cond_init(cond.as_mut_ptr(), attr.as_ptr()), 0);
+        assert_eq!(libc::pthread_cond_signal(cond.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_cond_destroy(cond.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_condattr_destroy(attr.as_mut_ptr()), 0);
+    }
+}
+
+fn check_rwlockattr() {
+    unsafe {
+        let mut attr = MaybeUninit::<libc::pthread_rwlockattr_t>::uninit();
+        assert_eq!(libc::pthread_rwlockattr_init(attr.as_mut_ptr()), 0);
+        let mut pshared = -1;
+        assert_eq!(libc::pthread_rwlockattr_getpshared(attr.as_ptr(), &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_PRIVATE);
+
+        assert_eq!(libc::pthread_rwlockattr_setpshared(attr.as_mut_ptr(), 42), libc::EINVAL);
+        assert_eq!(
+            libc::pthread_rwlockattr_setpshared(attr.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED),
+            0,
+        );
+        assert_eq!(libc::pthread_rwlockattr_getpshared(attr.as_ptr(), &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_SHARED);
+
+        let mut rwlock = MaybeUninit::<libc::pthread_rwlock_t>::uninit();
+        assert_eq!(libc::pthread_rwlock_init(rwlock.as_mut_ptr(), attr.as_ptr()), 0);
+        assert_eq!(libc::pthread_rwlock_rdlock(rwlock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_rwlock_rdlock(rwlock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_rwlock_trywrlock(rwlock.as_mut_ptr()), libc::EBUSY);
+        assert_eq!(libc::pthread_rwlock_unlock(rwlock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_rwlock_unlock(rwlock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_rwlock_destroy(rwlock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_rwlockattr_destroy(attr.as_mut_ptr()), 0);
+
+        // A null attribute gives a private rwlock.
+        let mut rwlock = MaybeUninit::<libc::pthread_rwlock_t>::uninit();
+        assert_eq!(libc::pthread_rwlock_init(rwlock.as_mut_ptr(), std::ptr::null()), 0);
+        assert_eq!(libc::pthread_rwlock_wrlock(rwlock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_rwlock_unlock(rwlock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_rwlock_destroy(rwlock.as_mut_ptr()), 0);
+    }
+}
+
+fn check_spinlock() {
+    unsafe {
+        let mut lock = MaybeUninit::<libc::pthread_spinlock_t>::uninit();
+        assert_eq!(libc::pthread_spin_init(lock.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED), 0);
+        assert_eq!(libc::pthread_spin_lock(lock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_spin_unlock(lock.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_spin_destroy(lock.as_mut_ptr()), 0);
+    }
+}
+
+fn check_freed_without_destroy() {
+    unsafe {
+        let mut attr = MaybeUninit::<libc::pthread_mutexattr_t>::uninit();
+        assert_eq!(libc::pthread_mutexattr_init(attr.as_mut_ptr()), 0);
+        assert_eq!(
+            libc::pthread_mutexattr_setpshared(attr.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED),
+            0,
+        );
+        let mutex =
+            libc::malloc(size_of::<libc::pthread_mutex_t>()).cast::<libc::pthread_mutex_t>();
+        assert_eq!(libc::pthread_mutex_init(mutex, attr.as_ptr()), 0);
+        assert_eq!(libc::pthread_mutexattr_destroy(attr.as_mut_ptr()), 0);
+        // Another process could still be using the mutex, so Miri warns about this.
+        libc::free(mutex.cast());
+    }
+}
@@ -0,0 +1,182 @@
+//@only-target: linux freebsd # robust mutexes are not available everywhere
+// We use `yield` to test specific interleavings, so disable automatic preemption.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+fn main() {
+    test_attr();
+    test_not_robust();
+    test_owner_died();
+    test_owner_died_trylock();
+    test_not_recoverable();
+    test_owner_died_while_waiting();
+}
+
+/// A mutex that is shared between threads.
+#[derive(Copy, Clone)]
+struct Mutex(*mut libc::pthread_mutex_t);
+
+unsafe impl Send for Mutex {}
+
+impl Mutex {
+    fn new(robust: bool) -> Self {
+        unsafe {
+            let mut attr = MaybeUninit::<libc::pthread_mutexattr_t>::uninit();
+            assert_eq!(libc::pthread_mutexattr_init(attr.as_mut_ptr()), 0);
+            if robust {
+                assert_eq!(
+                    libc::pthread_mutexattr_setrobust(
+                        attr.as_mut_ptr(),
+                        libc::PTHREAD_MUTEX_ROBUST
+                    ),
+                    0
+                );
+            }
+            let mutex = Box::leak(Box::new(MaybeUninit::<libc::pthread_mutex_t>::uninit()));
+            assert_eq!(libc::pthread_mutex_init(mutex.as_mut_ptr(), attr.as_ptr()), 0);
+            assert_eq!(libc::pthread_mutexattr_destroy(attr.as_mut_ptr()), 0);
+            Mutex(mutex.as_mut_ptr())
+        }
+    }
+
+    fn ptr(self) -> *mut libc::pthread_mutex_t {
+        self.0
+    }
+
+    fn lock(self) -> i32 {
+        unsafe { libc::pthread_mutex_lock(self.ptr()) }
+    }
+
+    fn trylock(self) -> i32 {
+        unsafe { libc::pthread_mutex_trylock(self.ptr()) }
+    }
+
+    fn unlock(self) -> i32 {
+        unsafe { libc::pthread_mutex_unlock(self.ptr()) }
+    }
+
+    fn consistent(self) -> i32 {
+        unsafe { libc::pthread_mutex_consistent(self.ptr()) }
+    }
+
+    /// Lock the mutex on a thread that terminates without unlocking it.
+    fn abandon(self) {
+        thread::spawn(move || assert_eq!(self.lock(), 0)).join().unwrap();
+    }
+}
+
+fn test_attr() {
+    unsafe {
+        let mut attr = MaybeUninit::<libc::pthread_mutexattr_t>::uninit();
+        let mut robustness = 0;
+        assert_eq!(libc::pthread_mutexattr_init(attr.as_mut_ptr()), 0);
+        assert_eq!(libc::pthread_mutexattr_getrobust(attr.as_ptr(), &mut robustness), 0);
+        assert_eq!(robustness, libc::PTHREAD_MUTEX_STALLED);
+        assert_eq!(
+            libc::pthread_mutexattr_setrobust(attr.as_mut_ptr(), libc::PTHREAD_MUTEX_ROBUST),
+            0
+        );
+        assert_eq!(libc::pthread_mutexattr_getrobust(attr.as_ptr(), &mut robustness), 0);
+        assert_eq!(robustness, libc::PTHREAD_MUTEX_ROBUST);
+        assert_eq!(libc::pthread_mutexattr_setrobust(attr.as_mut_ptr(), 1234), libc::EINVAL);
+        assert_eq!(libc::pthread_mutexattr_getrobust(attr.as_ptr(), &mut robustness), 0);
+        assert_eq!(robustness, libc::PTHREAD_MUTEX_ROBUST);
+        // The other attributes are not affected.
+        let mut pshared = 0;
+        assert_eq!(libc::pthread_mutexattr_getpshared(attr.as_ptr(), &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_PRIVATE);
+        assert_eq!(
+            libc::pthread_mutexattr_settype(attr.as_mut_ptr(), libc::PTHREAD_MUTEX_ERRORCHECK),
+            0
+        );
+        assert_eq!(libc::pthread_mutexattr_getrobust(attr.as_ptr(), &mut robustness), 0);
+        assert_eq!(robustness, libc::PTHREAD_MUTEX_ROBUST);
+        assert_eq!(libc::pthread_mutexattr_destroy(attr.as_mut_ptr()), 0);
+    }
+}
+
+fn test_not_robust() {
+    let mutex = Mutex::new(false);
+    assert_eq!(mutex.lock(), 0);
+    assert_eq!(mutex.consistent(), libc::EINVAL);
+    assert_eq!(mutex.unlock(), 0);
+}
+
+fn test_owner_died() {
+    let mutex = Mutex::new(true);
+    // A consistent mutex cannot be marked as consistent.
+    assert_eq!(mutex.lock(), 0);
+    assert_eq!(mutex.consistent(), libc::EINVAL);
+    assert_eq!(mutex.unlock(), 0);
+
+    mutex.abandon();
+    assert_eq!(mutex.lock(), libc::EOWNERDEAD);
+    assert_eq!(mutex.consistent(), 0);
+    assert_eq!(mutex.unlock(), 0);
+    // Once marked as consistent, the mutex works as usual again.
+    assert_eq!(mutex.lock(), 0);
+    assert_eq!(mutex.unlock(), 0);
+}
+
+fn test_owner_died_trylock() {
+    let mutex = Mutex::new(true);
+    mutex.abandon();
+    assert_eq!(mutex.trylock(), libc::EOWNERDEAD);
+    // Only the owner can mark the mutex as consistent.
+    thread::spawn(move || assert_eq!(mutex.consistent(), libc::EINVAL)).join().unwrap();
+    assert_eq!(mutex.consistent(), 0);
+    assert_eq!(mutex.unlock(), 0);
+}
+
+fn test_not_recoverable() {
+    let mutex = Mutex::new(true);
+    mutex.abandon();
+    assert_eq!(mutex.lock(), libc::EOWNERDEAD);
+    // Unlocking without marking the mutex as consistent makes it unusable.
+    assert_eq!(mutex.unlock(), 0);
+    assert_eq!(mutex.lock(), libc::ENOTRECOVERABLE);
+    assert_eq!(mutex.trylock(), libc::ENOTRECOVERABLE);
+}
+
+fn test_owner_died_while_waiting() {
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+    static EXIT: AtomicBool = AtomicBool::new(false);
+
+    let mutex = Mutex::new(true);
+    let owner = thread::spawn(move || {
+        assert_eq!(mutex.lock(), 0);
+        LOCKED.store(true, Ordering::Relaxed);
+        while !EXIT.load(Ordering::Relaxed) {
+            thread::yield_now();
+        }
+        // Terminate while holding the mutex.
+    });
+    while !LOCKED.load(Ordering::Relaxed) {
+        thread::yield_now();
+    }
+    let waiters: Vec<_> = (0..2)
+        .map(|_| {
+            thread::spawn(move || {
+                let result = mutex.lock();
+                if result == libc::EOWNERDEAD {
+                    // Do not repair the mutex, so that the other waiter learns that it is broken.
+                    assert_eq!(mutex.unlock(), 0);
+                }
+                result
+            })
+        })
+        .collect();
+    // Let the waiters block on the mutex.
+    thread::yield_now();
+    thread::yield_now();
+    EXIT.store(true, Ordering::Relaxed);
+    owner.join().unwrap();
+    let mut results: Vec<_> = waiters.into_iter().map(|t| t.join().unwrap()).collect();
+    results.sort();
+    let mut expected = vec![libc::EOWNERDEAD, libc::ENOTRECOVERABLE];
+    expected.sort();
+    assert_eq!(results, expected);
+}
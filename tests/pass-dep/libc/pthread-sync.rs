@@ -16,6 +16,8 @@ fn main() {
     test_mutex_libc_static_initializer_recursive();
 
     check_mutex();
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "android"))]
+    check_spinlock();
     check_rwlock_write();
     check_rwlock_read_no_deadlock();
     check_cond();
@@ -168,6 +170,42 @@ fn check_mutex() {
     bomb.defuse();
 }
 
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "android"))]
+fn check_spinlock() {
+    let bomb = AbortOnDrop;
+    unsafe {
+        let data = SyncUnsafeCell::new((MaybeUninit::<libc::pthread_spinlock_t>::uninit(), 0));
+        let ptr = SendPtr { ptr: data.get() };
+        let lockptr = (*ptr.ptr).0.as_mut_ptr();
+        assert_eq!(libc::pthread_spin_init(lockptr, -1), libc::EINVAL);
+        assert_eq!(libc::pthread_spin_init(lockptr, libc::PTHREAD_PROCESS_PRIVATE), 0);
+        let mut threads = Vec::new();
+
+        for _ in 0..3 {
+            let thread = thread::spawn(move || {
+                let ptr = ptr; // circumvent per-field closure capture
+                let lockptr = (*ptr.ptr).0.as_mut_ptr();
+                assert_eq!(libc::pthread_spin_lock(lockptr), 0);
+                thread::yield_now();
+                (*ptr.ptr).1 += 1;
+                assert_eq!(libc::pthread_spin_unlock(lockptr), 0);
+            });
+            threads.push(thread);
+        }
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(libc::pthread_spin_trylock(lockptr), 0);
+        assert_eq!((*ptr.ptr).1, 3);
+        assert_eq!(libc::pthread_spin_trylock(lockptr), libc::EBUSY);
+        assert_eq!(libc::pthread_spin_unlock(lockptr), 0);
+        assert_eq!(libc::pthread_spin_destroy(lockptr), 0);
+    }
+    bomb.defuse();
+}
+
 fn check_rwlock_write() {
     let bomb = AbortOnDrop;
     unsafe {
@@ -0,0 +1,26 @@
+//@only-target: windows # Uses win32 api functions
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-virtual-clock -Zmiri-qpc-frequency=10000000
+
+use std::thread;
+use std::time::Duration;
+
+use windows_sys::Win32::System::Performance::{QueryPerformanceCounter, QueryPerformanceFrequency};
+
+fn qpc() -> i64 {
+    let mut count = 0;
+    assert_ne!(unsafe { QueryPerformanceCounter(&mut count) }, 0);
+    count
+}
+
+fn main() {
+    let mut frequency = 0;
+    assert_ne!(unsafe { QueryPerformanceFrequency(&mut frequency) }, 0);
+    assert_eq!(frequency, 10_000_000);
+
+    // The counter advances as the program runs, and when it sleeps.
+    let start = qpc();
+    let after_work = qpc();
+    assert!(after_work > start);
+    thread::sleep(Duration::from_millis(10));
+    assert!(qpc() - after_work >= 100_000);
+}
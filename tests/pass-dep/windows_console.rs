@@ -0,0 +1,27 @@
+//@only-target: windows # Uses win32 api functions
+
+use std::ptr::null;
+
+use windows_sys::Win32::Foundation::{ERROR_INVALID_HANDLE, FALSE, GetLastError};
+use windows_sys::Win32::System::Console::{
+    GetConsoleMode, GetStdHandle, STD_ERROR_HANDLE, STD_OUTPUT_HANDLE, WriteConsoleW,
+};
+
+fn main() {
+    // With isolation, the standard streams are never consoles, so programs treat their output as
+    // redirected.
+    for which in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+        let handle = unsafe { GetStdHandle(which) };
+        let mut mode = 0;
+        assert_eq!(unsafe { GetConsoleMode(handle, &mut mode) }, FALSE);
+        assert_eq!(unsafe { GetLastError() }, ERROR_INVALID_HANDLE);
+
+        let text: Vec<u16> = "hi".encode_utf16().collect();
+        let mut written = 0;
+        let res = unsafe { WriteConsoleW(handle, text.as_ptr().cast(), 2, &mut written, null()) };
+        assert_eq!(res, FALSE);
+        assert_eq!(unsafe { GetLastError() }, ERROR_INVALID_HANDLE);
+    }
+    // Which is what `std` does then, too.
+    println!("hello");
+}
@@ -0,0 +1,136 @@
+//@only-target: windows # Uses win32 api functions
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::ptr::null;
+
+use windows_sys::Win32::Foundation::{
+    ERROR_ACCESS_DENIED, ERROR_ALREADY_EXISTS, ERROR_FILE_NOT_FOUND, ERROR_SHARING_VIOLATION,
+    FALSE, GetLastError, TRUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    DeleteFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, MOVEFILE_REPLACE_EXISTING, MoveFileExW,
+    ReplaceFileW,
+};
+
+#[path = "../utils/mod.rs"]
+mod utils;
+
+fn main() {
+    test_delete_file();
+    test_delete_file_sharing_violation();
+    test_move_file();
+    test_replace_file();
+}
+
+fn wide(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain([0]).collect()
+}
+
+fn create(name: &str, contents: &str) -> PathBuf {
+    let path = utils::tmp().join(name);
+    File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+    path
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+fn test_delete_file() {
+    let path = create("miri_test_windows_delete_file.txt", "data");
+    // Files can be deleted while they are open, but their deletion is pending until their last
+    // handle is closed. Until then, they cannot be opened again.
+    let mut file = File::open(&path).unwrap();
+    assert_eq!(unsafe { DeleteFileW(wide(&path).as_ptr()) }, TRUE);
+    assert_eq!(File::open(&path).unwrap_err().raw_os_error(), Some(ERROR_ACCESS_DENIED as i32));
+    assert_eq!(unsafe { DeleteFileW(wide(&path).as_ptr()) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_ACCESS_DENIED);
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "data");
+    drop(file);
+    assert_eq!(File::open(&path).unwrap_err().kind(), ErrorKind::NotFound);
+
+    assert_eq!(unsafe { DeleteFileW(wide(&path).as_ptr()) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_FILE_NOT_FOUND);
+    // Directories cannot be deleted this way.
+    assert_eq!(unsafe { DeleteFileW(wide(&utils::tmp()).as_ptr()) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_ACCESS_DENIED);
+}
+
+fn test_delete_file_sharing_violation() {
+    let path = create("miri_test_windows_delete_file_shared.txt", "data");
+    // Files that are open without `FILE_SHARE_DELETE` cannot be deleted.
+    let file = OpenOptions::new()
+        .read(true)
+        .share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE)
+        .open(&path)
+        .unwrap();
+    assert_eq!(unsafe { DeleteFileW(wide(&path).as_ptr()) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_SHARING_VIOLATION);
+    drop(file);
+    assert_eq!(read(&path), "data");
+    assert_eq!(unsafe { DeleteFileW(wide(&path).as_ptr()) }, TRUE);
+    assert!(!path.exists());
+}
+
+fn test_move_file() {
+    let from = create("miri_test_windows_move_from.txt", "from");
+    let to = create("miri_test_windows_move_to.txt", "to");
+    // Existing files are only replaced if that is requested.
+    assert_eq!(unsafe { MoveFileExW(wide(&from).as_ptr(), wide(&to).as_ptr(), 0) }, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_ALREADY_EXISTS);
+    assert_eq!(read(&to), "to");
+    let res =
+        unsafe { MoveFileExW(wide(&from).as_ptr(), wide(&to).as_ptr(), MOVEFILE_REPLACE_EXISTING) };
+    assert_eq!(res, TRUE);
+    assert_eq!(read(&to), "from");
+    assert!(!from.exists());
+
+    // Moving to a new name does not need any flags.
+    assert_eq!(unsafe { MoveFileExW(wide(&to).as_ptr(), wide(&from).as_ptr(), 0) }, TRUE);
+    assert_eq!(read(&from), "from");
+    fs::remove_file(&from).unwrap();
+}
+
+fn test_replace_file() {
+    let replaced = create("miri_test_windows_replaced.txt", "old");
+    let replacement = create("miri_test_windows_replacement.txt", "new");
+    let backup = utils::tmp().join("miri_test_windows_replace_backup.txt");
+    let res = unsafe {
+        ReplaceFileW(
+            wide(&replaced).as_ptr(),
+            wide(&replacement).as_ptr(),
+            wide(&backup).as_ptr(),
+            0,
+            null(),
+            null(),
+        )
+    };
+    assert_eq!(res, TRUE);
+    assert_eq!(read(&replaced), "new");
+    assert_eq!(read(&backup), "old");
+    assert!(!replacement.exists());
+
+    // The replacement has to exist.
+    let res = unsafe {
+        ReplaceFileW(
+            wide(&replaced).as_ptr(),
+            wide(&replacement).as_ptr(),
+            null(),
+            0,
+            null(),
+            null(),
+        )
+    };
+    assert_eq!(res, FALSE);
+    assert_eq!(unsafe { GetLastError() }, ERROR_FILE_NOT_FOUND);
+    assert_eq!(read(&replaced), "new");
+    fs::remove_file(&replaced).unwrap();
+    fs::remove_file(&backup).unwrap();
+}
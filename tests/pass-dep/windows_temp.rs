@@ -0,0 +1,148 @@
+//@only-target: windows # Uses win32 api functions
+// This test runs with isolation, so all files live in the virtual temporary directory.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+use windows_sys::Win32::Foundation::{
+    ERROR_BUFFER_OVERFLOW, ERROR_DISK_FULL, GetLastError, MAX_PATH,
+};
+use windows_sys::Win32::Storage::FileSystem::{GetTempFileNameW, GetTempPathW};
+
+fn main() {
+    test_temp_path();
+    test_create_write_read();
+    test_rename_and_remove();
+    test_temp_file_name();
+    test_huge_file();
+}
+
+fn wide(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain([0]).collect()
+}
+
+fn test_temp_path() {
+    let mut buf = [0u16; MAX_PATH as usize];
+    let len = unsafe { GetTempPathW(buf.len() as u32, buf.as_mut_ptr()) };
+    let path = OsString::from_wide(&buf[..len as usize]);
+    assert_eq!(path, r"C:\Windows\Temp\");
+    assert_eq!(env::temp_dir(), Path::new(r"C:\Windows\Temp\"));
+
+    // A buffer that is too small gets the required size, including the null terminator.
+    assert_eq!(unsafe { GetTempPathW(4, buf.as_mut_ptr()) }, len + 1);
+}
+
+fn test_create_write_read() {
+    let path = env::temp_dir().join("miri_test_create.txt");
+    let mut file = File::create(&path).unwrap();
+    file.write_all(b"hello world").unwrap();
+    assert_eq!(file.metadata().unwrap().len(), 11);
+    drop(file);
+
+    // The file can be reopened, and file names are case insensitive.
+    let mut file = File::open(env::temp_dir().join("MIRI_TEST_CREATE.TXT")).unwrap();
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "hello world");
+    assert_eq!(file.seek(SeekFrom::Start(6)).unwrap(), 6);
+    contents.clear();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "world");
+    // The file was only opened for reading.
+    assert!(file.write(b"x").is_err());
+
+    // Writing past the end fills the gap with zeros, and files can be truncated.
+    let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    file.seek(SeekFrom::End(2)).unwrap();
+    file.write_all(b"!").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"hello world\0\0!");
+    file.set_len(5).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"hello");
+
+    // Creating an existing file truncates it, unless it must be new.
+    File::create(&path).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"");
+    let err = OpenOptions::new().write(true).create_new(true).open(&path).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+}
+
+fn test_rename_and_remove() {
+    let from = env::temp_dir().join("miri_test_rename_from.txt");
+    let to = env::temp_dir().join("miri_test_rename_to.txt");
+    fs::write(&from, "contents").unwrap();
+    fs::write(&to, "replaced").unwrap();
+    fs::rename(&from, &to).unwrap();
+    assert_eq!(File::open(&from).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(fs::read_to_string(&to).unwrap(), "contents");
+
+    // Open handles keep working after the file got removed.
+    let mut file = File::open(&to).unwrap();
+    fs::remove_file(&to).unwrap();
+    assert_eq!(File::open(&to).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(fs::remove_file(&to).unwrap_err().kind(), ErrorKind::NotFound);
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "contents");
+}
+
+fn test_temp_file_name() {
+    let dir = wide(&env::temp_dir());
+    let prefix = wide(Path::new("miri"));
+    let mut buf = [0u16; MAX_PATH as usize];
+
+    // With a unique number, the name is only generated.
+    let unique = unsafe { GetTempFileNameW(dir.as_ptr(), prefix.as_ptr(), 0xab, buf.as_mut_ptr()) };
+    assert_eq!(unique, 0xab);
+    let path =
+        PathBuf::from(OsString::from_wide(&buf[..buf.iter().position(|&c| c == 0).unwrap()]));
+    assert_eq!(path, env::temp_dir().join("mirAB.TMP"));
+    assert_eq!(File::open(&path).unwrap_err().kind(), ErrorKind::NotFound);
+
+    // Otherwise, a new, empty file is created.
+    let mut names = Vec::new();
+    for _ in 0..2 {
+        let unique =
+            unsafe { GetTempFileNameW(dir.as_ptr(), prefix.as_ptr(), 0, buf.as_mut_ptr()) };
+        assert_ne!(unique, 0);
+        let path =
+            PathBuf::from(OsString::from_wide(&buf[..buf.iter().position(|&c| c == 0).unwrap()]));
+        assert_eq!(path, env::temp_dir().join(format!("mir{unique:X}.TMP")));
+        assert_eq!(fs::read(&path).unwrap(), b"");
+        names.push(path);
+    }
+    assert_ne!(names[0], names[1]);
+
+    // The name has to fit into `MAX_PATH` characters.
+    let long_dir = wide(&env::temp_dir().join("a".repeat(MAX_PATH as usize)));
+    let unique =
+        unsafe { GetTempFileNameW(long_dir.as_ptr(), prefix.as_ptr(), 1, buf.as_mut_ptr()) };
+    assert_eq!(unique, 0);
+    assert_eq!(unsafe { GetLastError() }, ERROR_BUFFER_OVERFLOW);
+}
+
+fn test_huge_file() {
+    let path = env::temp_dir().join("miri_test_huge.txt");
+    let mut file = OpenOptions::new().read(true).write(true).create(true).open(&path).unwrap();
+    file.write_all(b"data").unwrap();
+
+    // The file pointer can be moved far past the end, but the file cannot grow that large.
+    assert_eq!(file.seek(SeekFrom::Start(1 << 40)).unwrap(), 1 << 40);
+    let err = file.write(b"x").unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(ERROR_DISK_FULL as i32));
+    let err = file.set_len(1 << 40).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(ERROR_DISK_FULL as i32));
+    // Reading there reads nothing.
+    let mut buf = [0u8; 4];
+    assert_eq!(file.read(&mut buf).unwrap(), 0);
+
+    // The file is unchanged and still usable.
+    assert_eq!(file.metadata().unwrap().len(), 4);
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"data");
+    fs::remove_file(&path).unwrap();
+}
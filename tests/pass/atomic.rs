@@ -3,6 +3,7 @@
 //@compile-flags: -Zmiri-strict-provenance
 
 #![feature(strict_provenance_atomic_ptr)]
+#![feature(integer_atomics)]
 // FIXME(static_mut_refs): Do not allow `static_mut_refs` lint
 #![allow(static_mut_refs)]
 
@@ -13,6 +14,8 @@ fn main() {
     atomic_bool();
     atomic_all_ops();
     atomic_u64();
+    #[cfg(target_has_atomic = "128")]
+    atomic_u128();
     atomic_fences();
     atomic_ptr();
     weak_sometimes_fails();
@@ -122,6 +125,27 @@ fn atomic_u64() {
     assert_eq!(ATOMIC.fetch_min(0x10, SeqCst), 0x100);
 }
 
+#[cfg(target_has_atomic = "128")]
+fn atomic_u128() {
+    use std::sync::atomic::AtomicU128;
+
+    static ATOMIC: AtomicU128 = AtomicU128::new(0);
+    const HIGH: u128 = 1 << 64;
+
+    ATOMIC.store(HIGH | 1, SeqCst);
+    assert_eq!(ATOMIC.load(Acquire), HIGH | 1);
+    assert_eq!(ATOMIC.compare_exchange(1, 2, AcqRel, Acquire), Err(HIGH | 1));
+    assert_eq!(ATOMIC.compare_exchange(HIGH, 2, AcqRel, Acquire), Err(HIGH | 1));
+    assert_eq!(ATOMIC.compare_exchange(HIGH | 1, HIGH, Release, Relaxed), Ok(HIGH | 1));
+    compare_exchange_weak_loop!(ATOMIC, HIGH, u128::MAX, AcqRel, Acquire);
+    assert_eq!(ATOMIC.fetch_add(1, SeqCst), u128::MAX);
+    assert_eq!(ATOMIC.swap(HIGH, Relaxed), 0);
+    assert_eq!(ATOMIC.fetch_max(HIGH - 1, SeqCst), HIGH);
+    assert_eq!(ATOMIC.fetch_min(HIGH - 1, SeqCst), HIGH);
+    assert_eq!(ATOMIC.fetch_or(1, SeqCst), HIGH - 1);
+    assert_eq!(ATOMIC.load(Relaxed), HIGH - 1);
+}
+
 fn atomic_fences() {
     fence(SeqCst);
     fence(Release);
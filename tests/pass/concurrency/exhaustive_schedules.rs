@@ -0,0 +1,29 @@
+// Every schedule with at most one preemption is explored, and they all have to pass.
+//@compile-flags: -Zmiri-exhaustive-schedules=1
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+fn main() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let handle = {
+        let counter = Arc::clone(&counter);
+        let log = Arc::clone(&log);
+        thread::spawn(move || {
+            counter.fetch_add(1, SeqCst);
+            log.lock().unwrap().push(1);
+        })
+    };
+    counter.fetch_add(1, SeqCst);
+    log.lock().unwrap().push(0);
+    handle.join().unwrap();
+
+    assert_eq!(counter.load(SeqCst), 2);
+    let mut log = log.lock().unwrap();
+    log.sort();
+    assert_eq!(*log, [0, 1]);
+}
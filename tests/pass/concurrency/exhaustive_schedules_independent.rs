@@ -0,0 +1,25 @@
+// The threads only touch their own atomics, so partial-order reduction only has to explore the
+// schedules that reorder the shared synchronization of spawning and joining them. Without it, the
+// number of schedules would be far too large for a test.
+//@compile-flags: -Zmiri-exhaustive-schedules=3
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+
+static COUNTERS: [AtomicUsize; 3] = [AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)];
+
+fn main() {
+    thread::scope(|s| {
+        for counter in &COUNTERS {
+            s.spawn(move || {
+                for _ in 0..5 {
+                    counter.fetch_add(1, Relaxed);
+                }
+            });
+        }
+    });
+    for counter in &COUNTERS {
+        assert_eq!(counter.load(Relaxed), 5);
+    }
+}
@@ -0,0 +1,23 @@
+// Every schedule and every store that the loads may read from is explored, and they all have to
+// pass: with release/acquire synchronization, the data is always visible once the flag is.
+//@compile-flags: -Zmiri-exhaustive-weak-memory
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use std::thread;
+
+static DATA: AtomicUsize = AtomicUsize::new(0);
+static FLAG: AtomicUsize = AtomicUsize::new(0);
+
+fn main() {
+    let handle = thread::spawn(|| {
+        DATA.store(1, Relaxed);
+        DATA.store(2, Relaxed);
+        FLAG.store(1, Release);
+    });
+    while FLAG.load(Acquire) == 0 {
+        thread::yield_now();
+    }
+    assert_eq!(DATA.load(Relaxed), 2);
+    handle.join().unwrap();
+}
@@ -0,0 +1,32 @@
+//@compile-flags: -Zmiri-mixed-access-warnings
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-disable-weak-memory-emulation
+// Avoid accidental synchronization via address reuse inside `thread::spawn`.
+//@compile-flags: -Zmiri-address-reuse-cross-thread-rate=0
+
+// Make sure that with `-Zmiri-mixed-access-warnings`, mixed-size atomic accesses and mixes of
+// atomic and non-atomic accesses are reported as warnings, and that the program keeps running.
+
+use std::sync::atomic::{AtomicU8, AtomicU16, AtomicU32, Ordering};
+use std::thread;
+
+fn convert(a: &AtomicU16) -> &[AtomicU8; 2] {
+    unsafe { std::mem::transmute(a) }
+}
+
+fn main() {
+    let a = AtomicU16::new(0);
+    let a16 = &a;
+    let a8 = convert(a16);
+    thread::scope(|s| {
+        s.spawn(|| a16.store(1, Ordering::SeqCst));
+        s.spawn(|| a8[0].store(2, Ordering::SeqCst));
+    });
+    assert_eq!(a.load(Ordering::SeqCst), 2);
+
+    let b = AtomicU32::new(0);
+    thread::scope(|s| {
+        s.spawn(|| b.store(1, Ordering::Relaxed));
+        s.spawn(|| unsafe { b.as_ptr().read() });
+    });
+    assert_eq!(b.load(Ordering::Relaxed), 1);
+}
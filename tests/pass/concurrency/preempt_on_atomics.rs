@@ -0,0 +1,15 @@
+// Random preemption is disabled, so the spin loop below only terminates because the main thread
+// gets preempted at its atomic loads.
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-preempt-on-atomics
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::{Acquire, Release};
+use std::thread;
+
+static FLAG: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    let handle = thread::spawn(|| FLAG.store(true, Release));
+    while !FLAG.load(Acquire) {}
+    handle.join().unwrap();
+}
@@ -0,0 +1,49 @@
+// Random preemption is disabled, so threads only switch where the program asks for it.
+//@compile-flags: -Zmiri-preemption-rate=0
+
+#[path = "../../utils/mod.rs"]
+mod utils;
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+
+fn preempt_here() {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let handle = thread::spawn(|| {
+        COUNTER.fetch_add(1, Relaxed);
+    });
+    // Without preemption, the spawned thread has not run yet.
+    assert_eq!(COUNTER.load(Relaxed), 0);
+    // The spawned thread runs to completion before we continue.
+    unsafe { utils::miri_preempt_here() };
+    assert_eq!(COUNTER.load(Relaxed), 1);
+    handle.join().unwrap();
+}
+
+fn yield_hint() {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            thread::spawn(|| {
+                for _ in 0..10 {
+                    let value = COUNTER.load(Relaxed);
+                    unsafe { utils::miri_yield_hint() };
+                    COUNTER.store(value + 1, Relaxed);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    // Some updates may have been lost, depending on where we got preempted.
+    assert!((10..=40).contains(&COUNTER.load(Relaxed)));
+}
+
+fn main() {
+    preempt_here();
+    yield_hint();
+}
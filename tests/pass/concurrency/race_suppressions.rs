@@ -0,0 +1,21 @@
+//@compile-flags: -Zmiri-race-suppressions=tests/pass/concurrency/race_suppressions.supp -Zmiri-preemption-rate=0
+
+use std::thread::spawn;
+
+static mut FLAG: u32 = 0;
+
+// Stands in for third-party code with a known data race.
+mod vendored {
+    pub fn set_flag(val: u32) {
+        unsafe { *&raw mut super::FLAG = val };
+    }
+}
+
+fn main() {
+    let t1 = spawn(|| vendored::set_flag(1));
+    let t2 = spawn(|| vendored::set_flag(2));
+    t1.join().unwrap();
+    t2.join().unwrap();
+    // The program keeps running after the suppressed race.
+    assert_eq!(unsafe { *&raw const FLAG }, 2);
+}
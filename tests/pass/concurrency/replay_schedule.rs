@@ -0,0 +1,18 @@
+// The trace pins the schedule, independent of the preemption rate.
+//@compile-flags: -Zmiri-preemption-rate=1 -Zmiri-ignore-leaks
+//@compile-flags: -Zmiri-replay-schedule=tests/pass/concurrency/replay_schedule.trace
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+
+static RAN: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    thread::spawn(|| RAN.store(true, Relaxed));
+    for _ in 0..100 {
+        thread::yield_now();
+    }
+    // The trace never switched to the spawned thread.
+    assert!(!RAN.load(Relaxed));
+}
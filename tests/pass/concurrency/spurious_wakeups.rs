@@ -0,0 +1,39 @@
+//@compile-flags: -Zmiri-spurious-wakeup-rate=1
+// With this rate, every wait on a condition variable ends with a spurious wakeup.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Without a notification, the wait still returns.
+fn wait_without_notify() {
+    let mutex = Mutex::new(());
+    let cvar = Condvar::new();
+    let guard = mutex.lock().unwrap();
+    let _guard = cvar.wait(guard).unwrap();
+}
+
+/// Code that re-checks its condition in a loop keeps working, and the other threads still make
+/// progress.
+fn wait_in_loop() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let pair2 = Arc::clone(&pair);
+
+    let handle = thread::spawn(move || {
+        let (lock, cvar) = &*pair2;
+        *lock.lock().unwrap() = true;
+        cvar.notify_one();
+    });
+
+    let (lock, cvar) = &*pair;
+    let mut started = lock.lock().unwrap();
+    while !*started {
+        started = cvar.wait(started).unwrap();
+    }
+    drop(started);
+    handle.join().unwrap();
+}
+
+fn main() {
+    wait_without_notify();
+    wait_in_loop();
+}
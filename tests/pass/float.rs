@@ -934,11 +934,13 @@ fn rounding() {
 }
 
 fn mul_add() {
-    // FIXME(f16_f128): add when supported
-
+    assert_eq!(3.0f16.mul_add(2.0f16, 5.0f16), 11.0);
     assert_eq!(3.0f32.mul_add(2.0f32, 5.0f32), 11.0);
     assert_eq!(0.0f32.mul_add(-2.0, f32::consts::E), f32::consts::E);
     assert_eq!(3.0f64.mul_add(2.0, 5.0), 11.0);
+    assert_eq!(3.0f128.mul_add(2.0, 5.0), 11.0);
+    // This is only exact if the multiplication is not rounded.
+    assert_eq!(0.1f128.mul_add(10.0, -1.0).to_bits(), 0x3f8d_0000_0000_0000_0000_0000_0000_0000);
     assert_eq!(0.0f64.mul_add(-2.0f64, f64::consts::E), f64::consts::E);
     assert_eq!((-3.2f32).mul_add(2.4, f32::NEG_INFINITY), f32::NEG_INFINITY);
     assert_eq!((-3.2f64).mul_add(2.4, f64::NEG_INFINITY), f64::NEG_INFINITY);
@@ -959,8 +961,11 @@ pub fn libm() {
         unsafe { ldexp(a, b) }
     }
 
+    assert_eq!(64_f16.sqrt(), 8_f16);
     assert_eq!(64_f32.sqrt(), 8_f32);
     assert_eq!(64_f64.sqrt(), 8_f64);
+    assert_eq!(64_f128.sqrt(), 8_f128);
+    assert_eq!(2_f128.sqrt().to_bits(), 0x3fff_6a09_e667_f3bc_c908_b2fb_1366_ea95);
     assert_eq!(f32::INFINITY.sqrt(), f32::INFINITY);
     assert_eq!(f64::INFINITY.sqrt(), f64::INFINITY);
     assert_eq!(0.0_f32.sqrt().total_cmp(&0.0), std::cmp::Ordering::Equal);
@@ -973,6 +978,7 @@ pub fn libm() {
     assert!(f64::NEG_INFINITY.sqrt().is_nan());
     assert!(f32::NAN.sqrt().is_nan());
     assert!(f64::NAN.sqrt().is_nan());
+    assert!((-5.0_f128).sqrt().is_nan());
 
     assert_approx_eq!(25f32.powi(-2), 0.0016f32);
     assert_approx_eq!(23.2f64.powi(2), 538.24f64);
@@ -1075,6 +1081,39 @@ pub fn libm() {
     let (val, sign) = (-0.5f64).ln_gamma();
     assert_approx_eq!(val, (2.0 * f64::consts::PI.sqrt()).ln());
     assert_eq!(sign, -1);
+
+    // The `f128` functions of libm and the soft-float routines of compiler-builtins, as used by C
+    // code.
+    extern "C" {
+        fn sqrtf128(x: f128) -> f128;
+        fn fmaf128(x: f128, y: f128, z: f128) -> f128;
+        fn __addtf3(a: f128, b: f128) -> f128;
+        fn __divtf3(a: f128, b: f128) -> f128;
+        fn __lttf2(a: f128, b: f128) -> i32;
+        fn __gttf2(a: f128, b: f128) -> i32;
+        fn __eqtf2(a: f128, b: f128) -> i32;
+        fn __unordtf2(a: f128, b: f128) -> i32;
+        fn __extenddftf2(a: f64) -> f128;
+        fn __trunctfdf2(a: f128) -> f64;
+    }
+    unsafe {
+        assert_eq!(sqrtf128(64.0), 8.0);
+        assert!(sqrtf128(-1.0).is_nan());
+        assert_eq!(fmaf128(3.0, 2.0, 5.0), 11.0);
+        assert_eq!(__addtf3(1.5, 2.25), 3.75);
+        assert_eq!(__divtf3(1.0, 4.0), 0.25);
+        assert!(__lttf2(1.0, 2.0) < 0);
+        assert!(__lttf2(2.0, 1.0) > 0);
+        assert!(__lttf2(f128::NAN, 1.0) >= 0);
+        assert!(__gttf2(2.0, 1.0) > 0);
+        assert!(__gttf2(f128::NAN, 1.0) <= 0);
+        assert_eq!(__eqtf2(-0.0, 0.0), 0);
+        assert_ne!(__eqtf2(f128::NAN, f128::NAN), 0);
+        assert_eq!(__unordtf2(1.0, 2.0), 0);
+        assert_ne!(__unordtf2(1.0, f128::NAN), 0);
+        assert_eq!(__extenddftf2(0.1), 0.1f64 as f128);
+        assert_eq!(__trunctfdf2(0.1), 0.1f64);
+    }
 }
 
 fn test_fast() {
@@ -1194,9 +1233,12 @@ fn test_algebraic() {
 }
 
 fn test_fmuladd() {
-    use std::intrinsics::{fmuladdf32, fmuladdf64};
+    use std::intrinsics::{fmuladdf16, fmuladdf32, fmuladdf64, fmuladdf128};
 
-    // FIXME(f16_f128): add when supported
+    #[inline(never)]
+    pub fn test_operations_f16(a: f16, b: f16, c: f16) {
+        assert_eq!(unsafe { fmuladdf16(a, b, c) }, a * b + c);
+    }
 
     #[inline(never)]
     pub fn test_operations_f32(a: f32, b: f32, c: f32) {
@@ -1208,6 +1250,13 @@ fn test_fmuladd() {
         assert_approx_eq!(unsafe { fmuladdf64(a, b, c) }, a * b + c);
     }
 
+    #[inline(never)]
+    pub fn test_operations_f128(a: f128, b: f128, c: f128) {
+        assert_eq!(unsafe { fmuladdf128(a, b, c) }, a * b + c);
+    }
+
+    test_operations_f16(1.5, 2.0, 0.25);
     test_operations_f32(0.1, 0.2, 0.3);
     test_operations_f64(1.1, 1.2, 1.3);
+    test_operations_f128(1.5, 2.0, 0.25);
 }
@@ -0,0 +1,26 @@
+//@compile-flags: -Zmiri-deterministic-floats
+#![feature(core_intrinsics)]
+use std::hint::black_box;
+use std::intrinsics::{fmuladdf32, fmuladdf64};
+
+fn main() {
+    for _ in 0..50 {
+        // NaN results are always the positive quiet NaN with an empty payload.
+        let nan = black_box(0.0_f32) / black_box(0.0);
+        assert_eq!(nan.to_bits(), 0x7fc0_0000);
+        let nan = black_box(-f64::from_bits(0x7ff0_0000_0000_0001)) + black_box(1.0);
+        assert_eq!(nan.to_bits(), 0x7ff8_0000_0000_0000);
+        let nan = black_box(f32::NAN) as f64;
+        assert_eq!(nan.to_bits(), 0x7ff8_0000_0000_0000);
+
+        // `fmuladd` is always fused.
+        let a = black_box(0.1_f64);
+        let b = black_box(0.2);
+        let c = black_box(-a * b);
+        assert_ne!(unsafe { fmuladdf64(a, b, c) }, 0.0);
+        let a = black_box(0.1_f32);
+        let b = black_box(0.2);
+        let c = black_box(-a * b);
+        assert_ne!(unsafe { fmuladdf32(a, b, c) }, 0.0);
+    }
+}
@@ -0,0 +1,37 @@
+#![feature(repr_simd, core_intrinsics, f16)]
+#![allow(internal_features)]
+
+use std::intrinsics::simd::*;
+
+#[repr(simd)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
+struct f16x4([f16; 4]);
+
+#[repr(simd)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
+struct f32x4([f32; 4]);
+
+fn main() {
+    let a = f16x4([4.0, -2.5, 0.5, 9.0]);
+    let b = f16x4([1.0, 3.0, -0.5, 16.0]);
+
+    unsafe {
+        assert_eq!(simd_add(a, b), f16x4([5.0, 0.5, 0.0, 25.0]));
+        assert_eq!(simd_mul(a, b), f16x4([4.0, -7.5, -0.25, 144.0]));
+        assert_eq!(simd_fabs(a), f16x4([4.0, 2.5, 0.5, 9.0]));
+        assert_eq!(simd_fsqrt(b).0[3], 4.0);
+        assert_eq!(simd_floor(a), f16x4([4.0, -3.0, 0.0, 9.0]));
+        assert_eq!(simd_round(a), f16x4([4.0, -3.0, 1.0, 9.0]));
+        assert_eq!(simd_fma(a, b, a), f16x4([8.0, -10.0, 0.25, 153.0]));
+        assert_eq!(simd_fmin(a, b), f16x4([1.0, -2.5, -0.5, 9.0]));
+        assert_eq!(simd_fmax(a, b), f16x4([4.0, 3.0, 0.5, 16.0]));
+        assert_eq!(simd_fexp2(f16x4([0.0, 1.0, 2.0, -1.0])), f16x4([1.0, 2.0, 4.0, 0.5]));
+
+        let r: f32x4 = simd_cast(a);
+        assert_eq!(r, f32x4([4.0, -2.5, 0.5, 9.0]));
+        let r: f16x4 = simd_cast(f32x4([1.0, 65504.0, 70000.0, -0.25]));
+        assert_eq!(r, f16x4([1.0, 65504.0, f16::INFINITY, -0.25]));
+    }
+}
@@ -0,0 +1,11 @@
+//@compile-flags: -Zmiri-provenance-audit -Zmiri-permissive-provenance
+
+fn main() {
+    let x = [0u8; 2];
+    for i in 0..2 {
+        let addr = &x[i] as *const u8 as usize;
+        let _ptr = addr as *const u8;
+    }
+    let y = 0u8;
+    let _addr = &y as *const u8 as usize;
+}
@@ -0,0 +1,17 @@
+//@compile-flags: -Zmiri-provenance-gc=0 -Zmiri-provenance-gc-stats
+// How many tags the standard library creates and keeps alive is not stable.
+//@normalize-stderr-test: "[0-9]+ tags? freed, at most [0-9]+ live tags?" -> "N tags freed, at most N live tags"
+#[path = "../utils/mod.rs"]
+mod utils;
+
+fn main() {
+    let mut x = 0u8;
+    for _ in 0..10 {
+        let r = &mut x;
+        *r += 1;
+    }
+    utils::run_provenance_gc();
+    let y = &x;
+    assert_eq!(*y, 10);
+    utils::run_provenance_gc();
+}
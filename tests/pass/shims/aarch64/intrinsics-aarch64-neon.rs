@@ -0,0 +1,107 @@
+// We're testing aarch64 target specific features
+//@only-target: aarch64
+//@compile-flags: -C target-feature=+neon,+aes
+
+use std::arch::aarch64::*;
+use std::arch::is_aarch64_feature_detected;
+use std::mem::transmute;
+
+fn main() {
+    assert!(is_aarch64_feature_detected!("neon"));
+    assert!(is_aarch64_feature_detected!("aes"));
+
+    unsafe {
+        test_neon();
+        test_aes();
+    }
+}
+
+#[target_feature(enable = "neon")]
+unsafe fn test_neon() {
+    let a: uint8x16_t = transmute(std::array::from_fn::<u8, 16, _>(|i| i as u8));
+    let b: uint8x16_t = transmute(std::array::from_fn::<u8, 16, _>(|i| 16 + i as u8));
+
+    // Table lookups
+    let idx: uint8x16_t = transmute([15u8, 0, 16, 255, 1, 14, 2, 13, 3, 12, 4, 11, 5, 10, 6, 31]);
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vqtbl1q_u8(a, idx)),
+        [15, 0, 0, 0, 1, 14, 2, 13, 3, 12, 4, 11, 5, 10, 6, 0]
+    );
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vqtbx1q_u8(b, a, idx)),
+        [15, 0, 18, 19, 1, 14, 2, 13, 3, 12, 4, 11, 5, 10, 6, 31]
+    );
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vqtbl2q_u8(uint8x16x2_t(a, b), idx)),
+        [15, 0, 16, 0, 1, 14, 2, 13, 3, 12, 4, 11, 5, 10, 6, 31]
+    );
+    let idx: uint8x8_t = transmute([7u8, 6, 5, 4, 3, 2, 1, 64]);
+    assert_eq!(transmute::<_, [u8; 8]>(vqtbl1_u8(a, idx)), [7, 6, 5, 4, 3, 2, 1, 0]);
+
+    // Zip, unzip and transpose
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vzip1q_u8(a, b)),
+        [0, 16, 1, 17, 2, 18, 3, 19, 4, 20, 5, 21, 6, 22, 7, 23]
+    );
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vuzp1q_u8(a, b)),
+        [0, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 22, 24, 26, 28, 30]
+    );
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vtrn2q_u8(a, b)),
+        [1, 17, 3, 19, 5, 21, 7, 23, 9, 25, 11, 27, 13, 29, 15, 31]
+    );
+
+    // Pairwise operations
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vpaddq_u8(a, b)),
+        [1, 5, 9, 13, 17, 21, 25, 29, 33, 37, 41, 45, 49, 53, 57, 61]
+    );
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vpmaxq_u8(a, b)),
+        [1, 3, 5, 7, 9, 11, 13, 15, 17, 19, 21, 23, 25, 27, 29, 31]
+    );
+    let c: int8x16_t = transmute([-1i8, 1, -128, 127, 0, -5, 3, 2, 1, 1, -2, -3, 4, 5, -6, 6]);
+    assert_eq!(
+        transmute::<_, [i8; 16]>(vpminq_s8(c, c)),
+        [-1, -128, -5, 2, 1, -3, 4, -6, -1, -128, -5, 2, 1, -3, 4, -6]
+    );
+    assert_eq!(
+        transmute::<_, [i8; 16]>(vpmaxq_s8(c, c)),
+        [1, 127, 0, 3, 1, -2, 5, 6, 1, 127, 0, 3, 1, -2, 5, 6]
+    );
+
+    // Reductions across all lanes
+    assert_eq!(vmaxvq_u8(a), 15);
+    assert_eq!(vminvq_u8(b), 16);
+    assert_eq!(vmaxvq_s8(c), 127);
+    assert_eq!(vminvq_s8(c), -128);
+    assert_eq!(vaddvq_u8(a), 120);
+    // The sum wraps around, unless it is widened.
+    let d = vdupq_n_u8(255);
+    assert_eq!(vaddvq_u8(d), 240);
+    assert_eq!(vaddlvq_u8(d), 4080);
+    assert_eq!(vaddlvq_s8(c), 5);
+}
+
+#[target_feature(enable = "neon,aes")]
+unsafe fn test_aes() {
+    let zero = vdupq_n_u8(0);
+    // SubBytes maps 0 to 0x63; ShiftRows does not change a uniform state.
+    assert_eq!(transmute::<_, [u8; 16]>(vaeseq_u8(zero, zero)), [0x63; 16]);
+
+    // The MixColumns example from FIPS-197, applied to every column.
+    let column = [0xdbu8, 0x13, 0x53, 0x45];
+    let mixed = [0x8eu8, 0x4d, 0xa1, 0xbc];
+    let state: uint8x16_t = transmute([column; 4]);
+    assert_eq!(transmute::<_, [[u8; 4]; 4]>(vaesmcq_u8(state)), [mixed; 4]);
+    assert_eq!(transmute::<_, [[u8; 4]; 4]>(vaesimcq_u8(transmute([mixed; 4]))), [column; 4]);
+
+    // Decryption undoes encryption, except for the round key.
+    let state: uint8x16_t = transmute(std::array::from_fn::<u8, 16, _>(|i| i as u8 * 17));
+    let key: uint8x16_t = transmute(std::array::from_fn::<u8, 16, _>(|i| 0xa5 ^ i as u8));
+    assert_eq!(
+        transmute::<_, [u8; 16]>(vaesdq_u8(vaeseq_u8(state, key), zero)),
+        transmute::<_, [u8; 16]>(veorq_u8(state, key))
+    );
+}
@@ -0,0 +1,57 @@
+//@only-target: linux # `posix_spawn` is only used with glibc
+//@compile-flags: -Zmiri-command-script=tests/pass/shims/command.script
+
+use std::io::ErrorKind;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, Stdio};
+
+fn main() {
+    test_output();
+    test_exit_code();
+    test_signal();
+    test_not_found();
+    test_inherited_stdout();
+    test_null_stdout();
+}
+
+fn test_output() {
+    let output = Command::new("echo").args(["hello", "world"]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hello world\n");
+    assert!(output.stderr.is_empty());
+
+    // Outputs can span several lines of the script, and use escapes.
+    let output = Command::new("cat").arg("notes.txt").output().unwrap();
+    assert_eq!(output.stdout, b"first line\n\tindented\n");
+}
+
+fn test_exit_code() {
+    let output = Command::new("git").args(["rev-parse", "HEAD"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(128));
+    assert!(output.stdout.is_empty());
+    assert_eq!(output.stderr, b"fatal: not a git repository\n");
+}
+
+fn test_signal() {
+    let status = Command::new("sleep").arg("infinity").status().unwrap();
+    assert_eq!(status.code(), None);
+    assert_eq!(status.signal(), Some(9));
+}
+
+fn test_not_found() {
+    // Only the commands in the script exist.
+    let err = Command::new("echo").arg("goodbye").output().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+fn test_inherited_stdout() {
+    // The child writes to our stdout.
+    let status = Command::new("echo").arg("inherited").status().unwrap();
+    assert!(status.success());
+}
+
+fn test_null_stdout() {
+    let mut child =
+        Command::new("echo").args(["hello", "world"]).stdout(Stdio::null()).spawn().unwrap();
+    assert!(child.wait().unwrap().success());
+}
@@ -0,0 +1,22 @@
+//@only-target: windows # Windows environment variables are case-insensitive
+use std::env;
+
+fn main() {
+    // Names are case-insensitive, but keep the case they were first set with.
+    env::set_var("Miri_Test_B", "1");
+    assert_eq!(env::var("MIRI_TEST_B"), Ok("1".to_owned()));
+    env::set_var("MIRI_TEST_B", "2");
+    assert_eq!(env::var("miri_test_b"), Ok("2".to_owned()));
+    env::set_var("miri_test_a", "3");
+
+    // The environment block is sorted by name.
+    let vars: Vec<_> =
+        env::vars().filter(|(name, _)| name.to_uppercase().starts_with("MIRI_TEST_")).collect();
+    assert_eq!(
+        vars,
+        [("miri_test_a".to_owned(), "3".to_owned()), ("Miri_Test_B".to_owned(), "2".to_owned())]
+    );
+
+    env::remove_var("MIRI_TEST_A");
+    assert_eq!(env::var("miri_test_a"), Err(env::VarError::NotPresent));
+}
@@ -0,0 +1,33 @@
+//@only-target: x86_64 i686 aarch64
+// Test the rounding mode of the floating-point environment, as used by C code.
+
+// The value of `FE_TONEAREST`, which is the same on all supported targets.
+const FE_TONEAREST: i32 = 0;
+
+extern "C" {
+    fn fegetround() -> i32;
+    fn fesetround(mode: i32) -> i32;
+    fn rint(x: f64) -> f64;
+    fn nearbyintf(x: f32) -> f32;
+    fn llrint(x: f64) -> i64;
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(fegetround(), FE_TONEAREST);
+        assert_eq!(rint(2.5), 2.0);
+        assert_eq!(rint(-1.5), -2.0);
+        assert_eq!(rint(2.6), 3.0);
+        assert_eq!(nearbyintf(0.5), 0.0);
+        assert_eq!(nearbyintf(-0.7), -1.0);
+        assert_eq!(llrint(3.5), 4);
+        assert_eq!(llrint(-2.5), -2);
+
+        // Setting the default rounding mode is fine.
+        assert_eq!(fesetround(FE_TONEAREST), 0);
+        assert_eq!(fegetround(), FE_TONEAREST);
+        // Invalid modes leave the rounding mode unchanged.
+        assert_ne!(fesetround(12345), 0);
+        assert_eq!(fegetround(), FE_TONEAREST);
+    }
+}
@@ -0,0 +1,81 @@
+// Test the out-of-line atomic operations of libatomic, as called by C code.
+
+use std::sync::atomic::AtomicU32;
+use std::thread;
+
+// The values of the C11 `memory_order` enum.
+const RELAXED: i32 = 0;
+const CONSUME: i32 = 1;
+const ACQUIRE: i32 = 2;
+const RELEASE: i32 = 3;
+const ACQ_REL: i32 = 4;
+const SEQ_CST: i32 = 5;
+
+extern "C" {
+    fn __atomic_load_4(ptr: *const u32, order: i32) -> u32;
+    fn __atomic_store_4(ptr: *mut u32, val: u32, order: i32);
+    fn __atomic_exchange_4(ptr: *mut u32, val: u32, order: i32) -> u32;
+    fn __atomic_compare_exchange_4(
+        ptr: *mut u32,
+        expected: *mut u32,
+        desired: u32,
+        success: i32,
+        failure: i32,
+    ) -> bool;
+    fn __atomic_fetch_add_4(ptr: *mut u32, val: u32, order: i32) -> u32;
+    fn __atomic_sub_fetch_8(ptr: *mut u64, val: u64, order: i32) -> u64;
+    fn __atomic_fetch_nand_1(ptr: *mut u8, val: u8, order: i32) -> u8;
+    fn __atomic_or_fetch_2(ptr: *mut u16, val: u16, order: i32) -> u16;
+}
+
+fn operations() {
+    unsafe {
+        let mut x = 5u32;
+        assert_eq!(__atomic_load_4(&x, SEQ_CST), 5);
+        __atomic_store_4(&mut x, 6, RELEASE);
+        assert_eq!(__atomic_exchange_4(&mut x, 7, ACQ_REL), 6);
+
+        let mut expected = 0;
+        assert!(!__atomic_compare_exchange_4(&mut x, &mut expected, 8, SEQ_CST, RELAXED));
+        assert_eq!(expected, 7);
+        assert!(__atomic_compare_exchange_4(&mut x, &mut expected, 8, ACQUIRE, ACQUIRE));
+        assert_eq!(x, 8);
+
+        assert_eq!(__atomic_fetch_add_4(&mut x, u32::MAX, RELAXED), 8);
+        assert_eq!(x, 7);
+
+        let mut y = 1u64;
+        assert_eq!(__atomic_sub_fetch_8(&mut y, 2, SEQ_CST), u64::MAX);
+
+        let mut z = 0b1100u8;
+        assert_eq!(__atomic_fetch_nand_1(&mut z, 0b1010, SEQ_CST), 0b1100);
+        assert_eq!(z, !0b1000);
+
+        let mut w = 0b01u16;
+        assert_eq!(__atomic_or_fetch_2(&mut w, 0b10, SEQ_CST), 0b11);
+    }
+}
+
+/// `memory_order_consume` synchronizes like `memory_order_acquire`.
+fn consume() {
+    static mut DATA: u32 = 0;
+    static FLAG: AtomicU32 = AtomicU32::new(0);
+
+    let t = thread::spawn(|| unsafe {
+        DATA = 42;
+        __atomic_store_4(FLAG.as_ptr(), 1, RELEASE);
+    });
+    unsafe {
+        while __atomic_load_4(FLAG.as_ptr(), CONSUME) == 0 {
+            thread::yield_now();
+        }
+        let data = DATA;
+        assert_eq!(data, 42);
+    }
+    t.join().unwrap();
+}
+
+fn main() {
+    operations();
+    consume();
+}
@@ -0,0 +1,48 @@
+//@compile-flags: -Zmiri-disable-isolation -Zmiri-virtual-time
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Sleeps of many seconds complete instantly, and both clocks observe the time that passed.
+fn test_sleep() {
+    let before = Instant::now();
+    let system_before = SystemTime::now();
+    thread::sleep(Duration::from_secs(3600));
+    assert!(before.elapsed() >= Duration::from_secs(3600));
+    assert!(system_before.elapsed().unwrap() >= Duration::from_secs(3600));
+}
+
+fn test_condvar_timeout() {
+    let pair = Mutex::new(());
+    let cond = Condvar::new();
+    let before = Instant::now();
+    let (_guard, result) =
+        cond.wait_timeout(pair.lock().unwrap(), Duration::from_secs(60)).unwrap();
+    assert!(result.timed_out());
+    assert!(before.elapsed() >= Duration::from_secs(60));
+}
+
+/// Threads with different deadlines still wake up in the order of their deadlines.
+fn test_deadline_order() {
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let handles: Vec<_> = [30, 10, 20]
+        .into_iter()
+        .map(|secs| {
+            let order = Arc::clone(&order);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_secs(secs));
+                order.lock().unwrap().push(secs);
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(*order.lock().unwrap(), [10, 20, 30]);
+}
+
+fn main() {
+    test_sleep();
+    test_condvar_timeout();
+    test_deadline_order();
+}
@@ -0,0 +1,134 @@
+// We're testing wasm target specific features
+//@only-target: wasm32
+//@compile-flags: -C target-feature=+simd128
+
+use std::arch::wasm32::*;
+use std::mem::transmute;
+
+fn main() {
+    assert!(cfg!(target_feature = "simd128"));
+
+    test_swizzle();
+    test_bitselect();
+    test_boolean_reductions();
+    test_narrow();
+    test_avgr();
+    test_extadd_pairwise();
+    test_q15mulr_sat();
+    test_dot();
+}
+
+fn as_i8(v: v128) -> [i8; 16] {
+    unsafe { transmute(v) }
+}
+
+fn as_u8(v: v128) -> [u8; 16] {
+    unsafe { transmute(v) }
+}
+
+fn as_i16(v: v128) -> [i16; 8] {
+    unsafe { transmute(v) }
+}
+
+fn as_u16(v: v128) -> [u16; 8] {
+    unsafe { transmute(v) }
+}
+
+fn as_i32(v: v128) -> [i32; 4] {
+    unsafe { transmute(v) }
+}
+
+fn test_swizzle() {
+    let a = u8x16(10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25);
+    // Out-of-range indices select 0.
+    let s = u8x16(15, 0, 16, 255, 1, 14, 2, 13, 3, 12, 4, 11, 5, 10, 6, 128);
+    assert_eq!(
+        as_u8(i8x16_swizzle(a, s)),
+        [25, 10, 0, 0, 11, 24, 12, 23, 13, 22, 14, 21, 15, 20, 16, 0]
+    );
+}
+
+fn test_bitselect() {
+    let a = u32x4(0xffff_0000, 0x1234_5678, 0, u32::MAX);
+    let b = u32x4(0x0000_ffff, 0x8765_4321, u32::MAX, 0);
+    let c = u32x4(0xff00_ff00, 0xffff_ffff, 0x0f0f_0f0f, 0);
+    let r: [u32; 4] = unsafe { transmute(v128_bitselect(a, b, c)) };
+    assert_eq!(r, [0xff00_00ff, 0x1234_5678, 0xf0f0_f0f0, 0]);
+}
+
+fn test_boolean_reductions() {
+    let zero = u8x16_splat(0);
+    assert!(!v128_any_true(zero));
+    assert!(v128_any_true(u64x2(0, 1 << 63)));
+
+    assert!(i8x16_all_true(u8x16_splat(1)));
+    assert!(!i8x16_all_true(u8x16(1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0)));
+    // A lane is only false if all its bits are zero.
+    assert!(i16x8_all_true(u8x16(1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1)));
+    assert!(!i32x4_all_true(u32x4(1, 2, 0, 4)));
+    assert!(i64x2_all_true(u64x2(1 << 32, 1)));
+
+    assert_eq!(i8x16_bitmask(i8x16(-1, 0, -128, 127, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, -2)), 0x8005);
+    assert_eq!(i16x8_bitmask(i16x8(0, -1, 0, -1, 0, 0, 0, i16::MIN)), 0b1000_1010);
+    assert_eq!(i32x4_bitmask(i32x4(-1, 1, -1, 1)), 0b0101);
+    assert_eq!(i64x2_bitmask(i64x2(1, -1)), 0b10);
+}
+
+fn test_narrow() {
+    let a = i16x8(0, 1, -1, 127, 128, -128, -129, i16::MAX);
+    let b = i16x8(255, 256, i16::MIN, 2, 3, 4, 5, 6);
+    assert_eq!(
+        as_i8(i8x16_narrow_i16x8(a, b)),
+        [0, 1, -1, 127, 127, -128, -128, 127, 127, 127, -128, 2, 3, 4, 5, 6]
+    );
+    assert_eq!(
+        as_u8(u8x16_narrow_i16x8(a, b)),
+        [0, 1, 0, 127, 128, 0, 0, 255, 255, 255, 0, 2, 3, 4, 5, 6]
+    );
+
+    let a = i32x4(-40000, 40000, 70000, -1);
+    let b = i32x4(1, 2, 3, i32::MIN);
+    assert_eq!(
+        as_i16(i16x8_narrow_i32x4(a, b)),
+        [i16::MIN, i16::MAX, i16::MAX, -1, 1, 2, 3, i16::MIN]
+    );
+    assert_eq!(as_u16(u16x8_narrow_i32x4(a, b)), [0, 40000, u16::MAX, 0, 1, 2, 3, 0]);
+}
+
+fn test_avgr() {
+    let a = u8x16(0, 1, 2, 255, 255, 254, 10, 11, 0, 0, 0, 0, 0, 0, 0, 0);
+    let b = u8x16(0, 2, 2, 255, 0, 255, 20, 20, 1, 0, 0, 0, 0, 0, 0, 0);
+    assert_eq!(as_u8(u8x16_avgr(a, b)), [0, 2, 2, 255, 128, 255, 15, 16, 1, 0, 0, 0, 0, 0, 0, 0]);
+
+    let a = u16x8(u16::MAX, 1, 0, 0, 0, 0, 0, 0);
+    let b = u16x8(u16::MAX - 1, 2, 0, 0, 0, 0, 0, 0);
+    assert_eq!(as_u16(u16x8_avgr(a, b)), [u16::MAX, 2, 0, 0, 0, 0, 0, 0]);
+}
+
+fn test_extadd_pairwise() {
+    let a = i8x16(-128, -128, 127, 127, -1, 1, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0);
+    assert_eq!(as_i16(i16x8_extadd_pairwise_i8x16(a)), [-256, 254, 0, 5, 0, 0, 0, 0]);
+    assert_eq!(as_u16(i16x8_extadd_pairwise_u8x16(a)), [256, 254, 256, 5, 0, 0, 0, 0]);
+
+    let a = i16x8(i16::MIN, i16::MIN, i16::MAX, i16::MAX, -1, -1, 0, 0);
+    assert_eq!(as_i32(i32x4_extadd_pairwise_i16x8(a)), [-65536, 65534, -2, 0]);
+    let r: [u32; 4] = unsafe { transmute(i32x4_extadd_pairwise_u16x8(a)) };
+    assert_eq!(r, [65536, 65534, 131070, 0]);
+}
+
+fn test_q15mulr_sat() {
+    let a = i16x8(i16::MIN, 0x4000, 0x4000, -0x4000, 1, i16::MAX, 3, 0);
+    let b = i16x8(i16::MIN, 0x4000, -0x4000, -0x4000, 1, i16::MAX, -3, 0);
+    // 0.5 * 0.5 = 0.25, and `i16::MIN * i16::MIN` saturates.
+    assert_eq!(
+        as_i16(i16x8_q15mulr_sat(a, b)),
+        [i16::MAX, 0x2000, -0x2000, 0x2000, 0, i16::MAX - 1, 0, 0]
+    );
+}
+
+fn test_dot() {
+    let a = i16x8(1, 2, 3, 4, i16::MIN, i16::MIN, -1, 1);
+    let b = i16x8(5, 6, 7, 8, i16::MIN, i16::MIN, 1, 1);
+    // The sum of products wraps around.
+    assert_eq!(as_i32(i32x4_dot_i16x8(a, b)), [17, 53, i32::MIN, 0]);
+}
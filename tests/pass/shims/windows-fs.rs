@@ -0,0 +1,134 @@
+//@only-target: windows # this tests the Windows file shims
+//@compile-flags: -Zmiri-disable-isolation
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+#[path = "../../utils/mod.rs"]
+mod utils;
+
+fn main() {
+    test_create_write_read();
+    test_truncate();
+    test_seek();
+    test_open_errors();
+    test_read_dir();
+    test_metadata();
+}
+
+fn tmp_file(name: &str) -> PathBuf {
+    utils::tmp().join(name)
+}
+
+/// Reads the rest of the file in small chunks.
+fn read_rest(file: &mut File) -> Vec<u8> {
+    let mut contents = Vec::new();
+    let mut buf = [0u8; 4];
+    loop {
+        let n = file.read(&mut buf).unwrap();
+        if n == 0 {
+            return contents;
+        }
+        contents.extend_from_slice(&buf[..n]);
+    }
+}
+
+fn test_create_write_read() {
+    let path = tmp_file("miri_test_windows_fs_create.txt");
+    let mut file = File::create(&path).unwrap();
+    file.write_all(b"hello world").unwrap();
+    drop(file);
+
+    let mut file = File::open(&path).unwrap();
+    assert_eq!(read_rest(&mut file), b"hello world");
+    // Writing to a file that was only opened for reading fails.
+    assert!(file.write(b"x").is_err());
+}
+
+fn test_truncate() {
+    let path = tmp_file("miri_test_windows_fs_truncate.txt");
+    File::create(&path).unwrap().write_all(b"some content").unwrap();
+    // Creating an existing file truncates it.
+    let file = File::create(&path).unwrap();
+    drop(file);
+    assert_eq!(read_rest(&mut File::open(&path).unwrap()), b"");
+
+    let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+    file.write_all(b"0123456789").unwrap();
+    file.set_len(4).unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    assert_eq!(read_rest(&mut file), b"0123");
+}
+
+fn test_seek() {
+    let path = tmp_file("miri_test_windows_fs_seek.txt");
+    File::create(&path).unwrap().write_all(b"hello world").unwrap();
+
+    let mut file = File::open(&path).unwrap();
+    assert_eq!(file.seek(SeekFrom::Start(6)).unwrap(), 6);
+    assert_eq!(read_rest(&mut file), b"world");
+    assert_eq!(file.seek(SeekFrom::Current(-5)).unwrap(), 6);
+    assert_eq!(file.seek(SeekFrom::End(-3)).unwrap(), 8);
+    assert_eq!(file.stream_position().unwrap(), 8);
+    assert_eq!(read_rest(&mut file), b"rld");
+    // Seeking before the start of the file fails.
+    assert!(file.seek(SeekFrom::Current(-100)).is_err());
+}
+
+fn test_open_errors() {
+    let path = tmp_file("miri_test_windows_fs_does_not_exist.txt");
+    assert_eq!(File::open(&path).unwrap_err().kind(), ErrorKind::NotFound);
+
+    let path = tmp_file("miri_test_windows_fs_exists.txt");
+    File::create(&path).unwrap();
+    let err = OpenOptions::new().write(true).create_new(true).open(&path).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+}
+
+fn test_read_dir() {
+    let prefix = "miri_test_windows_fs_read_dir_";
+    for (name, contents) in [("a.txt", "a"), ("b.txt", "bb")] {
+        File::create(tmp_file(&format!("{prefix}{name}")))
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+    }
+    let mut entries: Vec<_> = fs::read_dir(utils::tmp())
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_name().to_str().unwrap().starts_with(prefix))
+        .map(|entry| {
+            let metadata = entry.metadata().unwrap();
+            assert!(entry.file_type().unwrap().is_file());
+            (entry.file_name().into_string().unwrap(), metadata.len())
+        })
+        .collect();
+    entries.sort();
+    assert_eq!(entries, [(format!("{prefix}a.txt"), 1), (format!("{prefix}b.txt"), 2)]);
+
+    let path = tmp_file("miri_test_windows_fs_no_such_dir");
+    assert_eq!(fs::read_dir(&path).unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+fn test_metadata() {
+    let path = tmp_file("miri_test_windows_fs_metadata.txt");
+    let before = SystemTime::now() - Duration::from_secs(60);
+    let mut file = File::create(&path).unwrap();
+    file.write_all(b"hello").unwrap();
+
+    let metadata = file.metadata().unwrap();
+    assert!(metadata.is_file());
+    assert!(!metadata.is_dir());
+    assert_eq!(metadata.len(), 5);
+    assert!(!metadata.permissions().readonly());
+    let modified = metadata.modified().unwrap();
+    assert!(modified > before);
+    assert!(modified < SystemTime::now() + Duration::from_secs(60));
+
+    // A second handle to the same file sees the same metadata.
+    let metadata = File::open(&path).unwrap().metadata().unwrap();
+    assert_eq!(metadata.len(), 5);
+    assert_eq!(metadata.modified().unwrap(), modified);
+}
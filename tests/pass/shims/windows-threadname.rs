@@ -3,6 +3,8 @@
 use core::ffi::c_void;
 use std::ffi::OsStr;
 use std::os::windows::ffi::OsStrExt;
+use std::os::windows::io::AsRawHandle;
+use std::thread;
 type HANDLE = *mut c_void;
 type PWSTR = *mut u16;
 type PCWSTR = *const u16;
@@ -21,6 +23,22 @@ fn to_u16s<S: AsRef<OsStr>>(s: S) -> Vec<u16> {
     result
 }
 
+fn get_description(thread: HANDLE) -> Vec<u16> {
+    unsafe {
+        let mut ptr = core::ptr::null_mut::<u16>();
+        let result = GetThreadDescription(thread, &mut ptr);
+        assert!(result >= 0);
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        let description = core::slice::from_raw_parts(ptr, len).to_vec();
+        let r = LocalFree(ptr.cast());
+        assert!(r.is_null());
+        description
+    }
+}
+
 fn main() {
     unsafe {
         let name = c"mythreadname";
@@ -28,18 +46,34 @@ fn main() {
         let utf16 = to_u16s(name.to_str().unwrap());
         SetThreadDescription(GetCurrentThread(), utf16.as_ptr());
 
-        let mut ptr = core::ptr::null_mut::<u16>();
-        let result = GetThreadDescription(GetCurrentThread(), &mut ptr);
-        assert!(result >= 0);
-        let name_gotten = String::from_utf16_lossy({
-            let mut len = 0;
-            while *ptr.add(len) != 0 {
-                len += 1;
-            }
-            core::slice::from_raw_parts(ptr, len)
-        });
+        let name_gotten = String::from_utf16_lossy(&get_description(GetCurrentThread()));
         assert_eq!(name_gotten, name.to_str().unwrap());
-        let r = LocalFree(ptr.cast());
-        assert!(r.is_null());
     }
+
+    // Threads without a name have an empty description.
+    let handle = thread::spawn(|| get_description(unsafe { GetCurrentThread() }));
+    assert_eq!(handle.join().unwrap(), []);
+
+    // std names threads using `SetThreadDescription`.
+    let handle = thread::Builder::new()
+        .name("sneaky thread".to_owned())
+        .spawn(|| get_description(unsafe { GetCurrentThread() }))
+        .unwrap();
+    assert_eq!(String::from_utf16_lossy(&handle.join().unwrap()), "sneaky thread");
+
+    // The description of another thread can be set and read through its handle. It does not have
+    // to be valid UTF-16, and is returned unchanged.
+    let handle = thread::spawn(|| thread::park());
+    let raw = handle.as_raw_handle();
+    for name in [
+        "wörker 🦀".encode_utf16().collect::<Vec<_>>(),
+        vec![0x61, 0xD800, 0x62, 0xDFFF, 0xD83E, 0xDD80, 0xDBFF],
+    ] {
+        let mut with_nul = name.clone();
+        with_nul.push(0);
+        assert!(unsafe { SetThreadDescription(raw, with_nul.as_ptr()) } >= 0);
+        assert_eq!(get_description(raw), name);
+    }
+    handle.thread().unpark();
+    handle.join().unwrap();
 }
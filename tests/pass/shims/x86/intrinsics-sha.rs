@@ -40,6 +40,7 @@ fn main() {
         test_sha256msg1();
         test_sha256msg2();
         test_sha256();
+        test_sha1();
     }
 }
 
@@ -261,3 +262,95 @@ pub static K32: [u32; 64] = [
     0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
     0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
 ];
+
+#[target_feature(enable = "sha,sse2,ssse3,sse4.1")]
+unsafe fn test_sha1() {
+    use std::fmt::Write;
+
+    /// The initial state of the hash engine.
+    const INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+    // Same data as in `test_sha256`; SHA1 uses the same padding.
+    let first_block = *b"Rust is awesome!Rust is awesome!Rust is awesome!Rust is awesome!";
+    let mut final_block = [0; 64];
+    final_block[0] = 0x80;
+    final_block[(64 - 8)..].copy_from_slice(&(8u64 * 64).to_be_bytes());
+
+    let mut state = INITIAL_STATE;
+    sha1_digest_blocks(&mut state, &[first_block, final_block]);
+
+    let mut hash = String::new();
+    for chunk in &state {
+        write!(hash, "{:08x}", chunk).expect("writing to String doesn't fail");
+    }
+    assert_eq!(hash, "40e7a17b590b4eafb072bbef0c56f450e964bba6");
+}
+
+// SHA1 implementation following Intel's "Intel SHA Extensions" white paper.
+
+#[target_feature(enable = "sha,sse2,ssse3,sse4.1")]
+unsafe fn sha1_rounds4(abcd: __m128i, msg: __m128i, group: usize) -> __m128i {
+    match group / 5 {
+        0 => _mm_sha1rnds4_epu32::<0>(abcd, msg),
+        1 => _mm_sha1rnds4_epu32::<1>(abcd, msg),
+        2 => _mm_sha1rnds4_epu32::<2>(abcd, msg),
+        _ => _mm_sha1rnds4_epu32::<3>(abcd, msg),
+    }
+}
+
+// we use unaligned loads with `__m128i` pointers
+#[expect(clippy::cast_ptr_alignment)]
+#[target_feature(enable = "sha,sse2,ssse3,sse4.1")]
+unsafe fn sha1_digest_blocks(state: &mut [u32; 5], blocks: &[[u8; 64]]) {
+    #[allow(non_snake_case)]
+    let MASK: __m128i =
+        _mm_set_epi64x(0x0001_0203_0405_0607u64 as i64, 0x0809_0A0B_0C0D_0E0Fu64 as i64);
+
+    let state_ptr: *const __m128i = state.as_ptr().cast();
+    let mut abcd = _mm_shuffle_epi32(_mm_loadu_si128(state_ptr), 0x1B);
+    let mut e0 = _mm_set_epi32(state[4] as i32, 0, 0, 0);
+
+    for block in blocks {
+        let abcd_save = abcd;
+        let e_save = e0;
+
+        let block_ptr: *const __m128i = block.as_ptr().cast();
+        let mut w = [
+            _mm_shuffle_epi8(_mm_loadu_si128(block_ptr.add(0)), MASK),
+            _mm_shuffle_epi8(_mm_loadu_si128(block_ptr.add(1)), MASK),
+            _mm_shuffle_epi8(_mm_loadu_si128(block_ptr.add(2)), MASK),
+            _mm_shuffle_epi8(_mm_loadu_si128(block_ptr.add(3)), MASK),
+        ];
+
+        // Rounds 0-3.
+        e0 = _mm_add_epi32(e0, w[0]);
+        let mut e1 = abcd;
+        abcd = sha1_rounds4(abcd, e0, 0);
+
+        // Rounds 4-79, four at a time, scheduling the message words from round 16 on.
+        for group in 1..20 {
+            if group >= 4 {
+                let t = _mm_sha1msg1_epu32(w[group % 4], w[(group + 1) % 4]);
+                let t = _mm_xor_si128(t, w[(group + 2) % 4]);
+                w[group % 4] = _mm_sha1msg2_epu32(t, w[(group + 3) % 4]);
+            }
+            let msg = w[group % 4];
+            if group % 2 == 1 {
+                e1 = _mm_sha1nexte_epu32(e1, msg);
+                e0 = abcd;
+                abcd = sha1_rounds4(abcd, e1, group);
+            } else {
+                e0 = _mm_sha1nexte_epu32(e0, msg);
+                e1 = abcd;
+                abcd = sha1_rounds4(abcd, e0, group);
+            }
+        }
+
+        e0 = _mm_sha1nexte_epu32(e0, e_save);
+        abcd = _mm_add_epi32(abcd, abcd_save);
+    }
+
+    let state_ptr_mut: *mut __m128i = state.as_mut_ptr().cast();
+    _mm_storeu_si128(state_ptr_mut, _mm_shuffle_epi32(abcd, 0x1B));
+    state[4] = _mm_extract_epi32(e0, 3) as u32;
+}
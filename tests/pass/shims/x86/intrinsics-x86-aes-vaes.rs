@@ -82,6 +82,16 @@ unsafe fn test_aes() {
         assert_eq_m128i(r, e);
     }
     test_mm_aesimc_si128();
+
+    #[target_feature(enable = "aes")]
+    unsafe fn test_mm_aeskeygenassist_si128() {
+        // Constants taken from https://msdn.microsoft.com/en-us/library/cc664815.aspx.
+        let a = _mm_set_epi64x(0x0123456789abcdef, 0x8899aabbccddeeff);
+        let e = _mm_set_epi64x(0x857c266b7c266e85, 0xeac4eea9c4eeacea);
+        let r = _mm_aeskeygenassist_si128::<5>(a);
+        assert_eq_m128i(r, e);
+    }
+    test_mm_aeskeygenassist_si128();
 }
 
 // The constants in the tests below are just bit patterns. They should not
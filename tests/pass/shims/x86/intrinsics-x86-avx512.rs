@@ -18,11 +18,250 @@ fn main() {
     assert!(is_x86_feature_detected!("avx512vpopcntdq"));
 
     unsafe {
+        test_avx512f();
         test_avx512bitalg();
         test_avx512vpopcntdq();
     }
 }
 
+#[target_feature(enable = "avx512f,avx512vl")]
+unsafe fn test_avx512f() {
+    #[target_feature(enable = "avx512f")]
+    unsafe fn test_mm512_cmp_ps_mask() {
+        let a = _mm512_setr_ps(
+            0.,
+            1.,
+            -1.,
+            f32::NAN,
+            0.,
+            1.,
+            -1.,
+            f32::NAN,
+            0.,
+            1.,
+            -1.,
+            f32::NAN,
+            0.,
+            1.,
+            -1.,
+            f32::NAN,
+        );
+        let b = _mm512_set1_ps(0.);
+        assert_eq!(_mm512_cmp_ps_mask::<_CMP_LT_OQ>(a, b), 0b0100_0100_0100_0100);
+        assert_eq!(_mm512_cmp_ps_mask::<_CMP_NLT_UQ>(a, b), 0b1011_1011_1011_1011);
+        assert_eq!(
+            _mm512_mask_cmp_ps_mask::<_CMP_EQ_OQ>(0b1111_1111_0000_0000, a, b),
+            0b0001_0001_0000_0000
+        );
+    }
+    test_mm512_cmp_ps_mask();
+
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn test_mm256_cmp_pd_mask() {
+        let a = _mm256_setr_pd(0., 1., -1., f64::NAN);
+        let b = _mm256_set1_pd(0.);
+        assert_eq!(_mm256_cmp_pd_mask::<_CMP_LE_OQ>(a, b), 0b0101);
+        assert_eq!(_mm256_cmp_pd_mask::<_CMP_UNORD_Q>(a, b), 0b1000);
+    }
+    test_mm256_cmp_pd_mask();
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn test_mm512_max_ps() {
+        let a: [f32; 16] = [
+            f32::NAN,
+            0.,
+            -0.,
+            1.,
+            f32::NAN,
+            0.,
+            -0.,
+            1.,
+            f32::NAN,
+            0.,
+            -0.,
+            1.,
+            f32::NAN,
+            0.,
+            -0.,
+            1.,
+        ];
+        let b: [f32; 16] = [
+            0.,
+            f32::NAN,
+            0.,
+            2.,
+            0.,
+            f32::NAN,
+            0.,
+            2.,
+            0.,
+            f32::NAN,
+            0.,
+            2.,
+            0.,
+            f32::NAN,
+            0.,
+            2.,
+        ];
+        // If either operand is NaN or both are zero, the second operand is returned.
+        let r: [f32; 16] = transmute(_mm512_max_ps(transmute(a), transmute(b)));
+        for i in (0..16).step_by(4) {
+            assert_eq!(r[i].to_bits(), 0f32.to_bits());
+            assert!(r[i + 1].is_nan());
+            assert_eq!(r[i + 2].to_bits(), 0f32.to_bits());
+            assert_eq!(r[i + 3], 2.);
+        }
+        let r: [f32; 16] = transmute(_mm512_min_ps(transmute(a), transmute(b)));
+        for i in (0..16).step_by(4) {
+            assert_eq!(r[i].to_bits(), 0f32.to_bits());
+            assert!(r[i + 1].is_nan());
+            assert_eq!(r[i + 2].to_bits(), 0f32.to_bits());
+            assert_eq!(r[i + 3], 1.);
+        }
+    }
+    test_mm512_max_ps();
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn test_mm512_add_round_ps() {
+        let a = _mm512_set1_ps(1.);
+        // Three quarters of the distance to the next `f32` after 1.
+        let b = _mm512_set1_ps(f32::EPSILON * 0.75);
+
+        let r: [f32; 16] = transmute(_mm512_add_round_ps::<
+            { _MM_FROUND_TO_NEAREST_INT | _MM_FROUND_NO_EXC },
+        >(a, b));
+        assert_eq!(r, [1. + f32::EPSILON; 16]);
+        let r: [f32; 16] =
+            transmute(_mm512_add_round_ps::<{ _MM_FROUND_TO_ZERO | _MM_FROUND_NO_EXC }>(a, b));
+        assert_eq!(r, [1.; 16]);
+        let r: [f32; 16] = transmute(_mm512_add_round_ps::<_MM_FROUND_CUR_DIRECTION>(a, b));
+        assert_eq!(r, [1. + f32::EPSILON; 16]);
+    }
+    test_mm512_add_round_ps();
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn test_mm512_div_round_pd() {
+        let a = _mm512_set1_pd(1.);
+        let b = _mm512_set1_pd(3.);
+
+        let up: [f64; 8] =
+            transmute(_mm512_div_round_pd::<{ _MM_FROUND_TO_POS_INF | _MM_FROUND_NO_EXC }>(a, b));
+        let down: [f64; 8] =
+            transmute(_mm512_div_round_pd::<{ _MM_FROUND_TO_NEG_INF | _MM_FROUND_NO_EXC }>(a, b));
+        for i in 0..8 {
+            assert_eq!(up[i].to_bits(), down[i].to_bits() + 1);
+        }
+    }
+    test_mm512_div_round_pd();
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn test_mm512_sqrt_round_ps() {
+        let a = _mm512_setr_ps(
+            0., 1., 4., 9., 16., 25., 36., 49., 64., 81., 100., 121., 144., 169., 196., 225.,
+        );
+        let r = _mm512_sqrt_round_ps::<{ _MM_FROUND_TO_NEAREST_INT | _MM_FROUND_NO_EXC }>(a);
+        let e =
+            _mm512_setr_ps(0., 1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11., 12., 13., 14., 15.);
+        assert_eq!(transmute::<_, [f32; 16]>(r), transmute::<_, [f32; 16]>(e));
+    }
+    test_mm512_sqrt_round_ps();
+
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn test_ternarylogic() {
+        let a = _mm512_set1_epi32(0b1100);
+        let b = _mm512_set1_epi32(0b1010);
+        let c = _mm512_set1_epi32(0b0110);
+        // The truth table of `a ^ b ^ c`.
+        let r = _mm512_ternarylogic_epi32::<0b1001_0110>(a, b, c);
+        assert_eq_m512i(r, _mm512_set1_epi32(0b0000));
+        // The truth table of "at least two of `a`, `b` and `c`".
+        let r = _mm512_ternarylogic_epi32::<0b1110_1000>(a, b, c);
+        assert_eq_m512i(r, _mm512_set1_epi32(0b1110));
+
+        let a = _mm_set_epi64x(-1, 0b1100);
+        let b = _mm_set_epi64x(0, 0b1010);
+        let c = _mm_set_epi64x(0, 0b0110);
+        let r = _mm_ternarylogic_epi64::<0b1110_1000>(a, b, c);
+        assert_eq_m128i(r, _mm_set_epi64x(0, 0b1110));
+    }
+    test_ternarylogic();
+
+    #[target_feature(enable = "avx512f,avx512vl")]
+    unsafe fn test_shifts() {
+        let a = _mm512_set1_epi64(-8);
+        assert_eq_m512i(_mm512_sra_epi64(a, _mm_set_epi64x(0, 2)), _mm512_set1_epi64(-2));
+        assert_eq_m512i(_mm512_sra_epi64(a, _mm_set_epi64x(0, 100)), _mm512_set1_epi64(-1));
+        assert_eq_m128i(
+            _mm_sra_epi64(_mm_set1_epi64x(-8), _mm_set_epi64x(0, 1)),
+            _mm_set1_epi64x(-4),
+        );
+
+        let a = _mm512_set1_epi32(1);
+        let count = _mm512_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 31, 32);
+        let e = _mm512_setr_epi32(
+            1,
+            2,
+            4,
+            8,
+            16,
+            32,
+            64,
+            128,
+            256,
+            512,
+            1024,
+            2048,
+            4096,
+            8192,
+            i32::MIN,
+            0,
+        );
+        assert_eq_m512i(_mm512_sllv_epi32(a, count), e);
+
+        let a = _mm512_set1_epi64(i64::MIN);
+        let count = _mm512_setr_epi64(0, 1, 2, 62, 63, 64, 65, 1000);
+        let e = _mm512_setr_epi64(i64::MIN, i64::MIN / 2, i64::MIN / 4, -2, -1, -1, -1, -1);
+        assert_eq_m512i(_mm512_srav_epi64(a, count), e);
+    }
+    test_shifts();
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn test_permutes() {
+        let a = _mm512_setr_epi32(
+            100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115,
+        );
+        // Only the low 4 bits of the indices are used.
+        let idx = _mm512_setr_epi32(15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 16);
+        let e = _mm512_setr_epi32(
+            115, 114, 113, 112, 111, 110, 109, 108, 107, 106, 105, 104, 103, 102, 101, 100,
+        );
+        assert_eq_m512i(_mm512_permutexvar_epi32(idx, a), e);
+
+        let a = _mm512_setr_epi64(0, 1, 2, 3, 4, 5, 6, 7);
+        let b = _mm512_setr_epi64(10, 11, 12, 13, 14, 15, 16, 17);
+        // Bit 3 of the indices selects `b`; higher bits are ignored.
+        let idx = _mm512_setr_epi64(0, 8, 1, 9, 7, 15, 16, 24);
+        let e = _mm512_setr_epi64(0, 10, 1, 11, 7, 17, 0, 10);
+        assert_eq_m512i(_mm512_permutex2var_epi64(a, idx, b), e);
+    }
+    test_permutes();
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn test_masks() {
+        let a = _mm512_set1_epi32(1);
+        let b = _mm512_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+        let k = _kand_mask16(_mm512_cmplt_epi32_mask(a, b), 0b0000_0000_1111_1111);
+        assert_eq!(k, 0b0000_0000_1111_1100);
+        let r = _mm512_mask_add_epi32(a, k, a, b);
+        let e = _mm512_setr_epi32(1, 1, 3, 4, 5, 6, 7, 8, 1, 1, 1, 1, 1, 1, 1, 1);
+        assert_eq_m512i(r, e);
+        let r = _mm512_maskz_add_epi32(_knot_mask16(k), a, b);
+        let e = _mm512_setr_epi32(1, 2, 0, 0, 0, 0, 0, 0, 9, 10, 11, 12, 13, 14, 15, 16);
+        assert_eq_m512i(r, e);
+    }
+    test_masks();
+}
+
 // Some of the constants in the tests below are just bit patterns. They should not
 // be interpreted as integers; signedness does not make sense for them, but
 // __mXXXi happens to be defined in terms of signed integers.
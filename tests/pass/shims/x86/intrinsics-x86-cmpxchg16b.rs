@@ -0,0 +1,70 @@
+// We're testing x86 target specific features
+//@only-target: x86_64
+//@compile-flags: -C target-feature=+cmpxchg16b -Zmiri-preemption-rate=0
+
+use std::arch::x86_64::cmpxchg16b;
+use std::cell::UnsafeCell;
+use std::sync::atomic::Ordering::*;
+use std::thread;
+
+#[repr(align(16))]
+struct Aligned(UnsafeCell<u128>);
+
+unsafe impl Sync for Aligned {}
+
+impl Aligned {
+    fn cas(&self, old: u128, new: u128, success: std::sync::atomic::Ordering) -> u128 {
+        let failure = if success == Release || success == Relaxed { Relaxed } else { Acquire };
+        unsafe { cmpxchg16b(self.0.get(), old, new, success, failure) }
+    }
+
+    fn load(&self) -> u128 {
+        // A compare-exchange with a value the location is unlikely to hold acts as a load.
+        self.cas(u128::MAX, u128::MAX, Acquire)
+    }
+}
+
+fn test_basic() {
+    let x = Aligned(UnsafeCell::new(0));
+    // Successful exchange.
+    assert_eq!(x.cas(0, 1 << 100 | 1, SeqCst), 0);
+    // Failed exchange, both halves are compared.
+    assert_eq!(x.cas(1, 2, SeqCst), 1 << 100 | 1);
+    assert_eq!(x.cas(1 << 100, 2, SeqCst), 1 << 100 | 1);
+    assert_eq!(x.load(), 1 << 100 | 1);
+    assert_eq!(x.cas(1 << 100 | 1, u128::MAX, AcqRel), 1 << 100 | 1);
+    assert_eq!(unsafe { *x.0.get() }, u128::MAX);
+}
+
+/// A pointer with an ABA-protection tag in the upper half, as used by lock-free stacks.
+fn test_tagged_pointer_sync() {
+    static SLOT: Aligned = Aligned(UnsafeCell::new(0));
+    static mut DATA: u64 = 0;
+
+    let writer = thread::spawn(|| {
+        unsafe { DATA = 42 };
+        let tagged = 1u128 << 64 | 0x1000;
+        assert_eq!(SLOT.cas(0, tagged, Release), 0);
+    });
+    let reader = thread::spawn(|| {
+        loop {
+            let current = SLOT.load();
+            if current >> 64 == 1 {
+                // The acquire synchronizes with the release of the writer, so this is not a race.
+                assert_eq!(unsafe { DATA }, 42);
+                assert_eq!(current as u64, 0x1000);
+                break;
+            }
+            thread::yield_now();
+        }
+    });
+    writer.join().unwrap();
+    reader.join().unwrap();
+}
+
+fn main() {
+    assert!(is_x86_feature_detected!("cmpxchg16b"));
+
+    test_basic();
+    test_tagged_pointer_sync();
+}
@@ -0,0 +1,45 @@
+// We're testing x86 target specific features
+//@only-target: x86_64 i686
+//@compile-flags: -C target-feature=+f16c
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+use std::mem::transmute;
+
+fn main() {
+    assert!(is_x86_feature_detected!("f16c"));
+
+    unsafe {
+        test_f16c();
+    }
+}
+
+#[target_feature(enable = "f16c")]
+unsafe fn test_f16c() {
+    // 1 + 2^-11 lies exactly between the half-precision floats 1 and 1 + 2^-10.
+    const TIE: f32 = 1.00048828125;
+
+    let a: __m128i = transmute([0x3c00u16, 0x4100, 0xb800, 0x7bff, 0x7c00, 0x0001, 0, 0]);
+    let r: [f32; 4] = transmute(_mm_cvtph_ps(a));
+    assert_eq!(r, [1.0, 2.5, -0.5, 65504.0]);
+    let r: [f32; 8] = transmute(_mm256_cvtph_ps(a));
+    assert_eq!(r, [1.0, 2.5, -0.5, 65504.0, f32::INFINITY, 2.0f32.powi(-24), 0.0, 0.0]);
+
+    let a = _mm_setr_ps(TIE, -TIE, 70000.0, 2.5);
+    let r: [u16; 8] = transmute(_mm_cvtps_ph::<_MM_FROUND_TO_NEAREST_INT>(a));
+    assert_eq!(r, [0x3c00, 0xbc00, 0x7c00, 0x4100, 0, 0, 0, 0]);
+    let r: [u16; 8] = transmute(_mm_cvtps_ph::<_MM_FROUND_TO_NEG_INF>(a));
+    assert_eq!(r, [0x3c00, 0xbc01, 0x7bff, 0x4100, 0, 0, 0, 0]);
+    let r: [u16; 8] = transmute(_mm_cvtps_ph::<_MM_FROUND_TO_POS_INF>(a));
+    assert_eq!(r, [0x3c01, 0xbc00, 0x7c00, 0x4100, 0, 0, 0, 0]);
+    let r: [u16; 8] = transmute(_mm_cvtps_ph::<_MM_FROUND_TO_ZERO>(a));
+    assert_eq!(r, [0x3c00, 0xbc00, 0x7bff, 0x4100, 0, 0, 0, 0]);
+    let r: [u16; 8] = transmute(_mm_cvtps_ph::<_MM_FROUND_CUR_DIRECTION>(a));
+    assert_eq!(r, [0x3c00, 0xbc00, 0x7c00, 0x4100, 0, 0, 0, 0]);
+
+    let a = _mm256_setr_ps(1.0, 2.5, -0.5, 65504.0, f32::INFINITY, TIE, -TIE, 0.0);
+    let r: [u16; 8] = transmute(_mm256_cvtps_ph::<_MM_FROUND_TO_POS_INF>(a));
+    assert_eq!(r, [0x3c00, 0x4100, 0xb800, 0x7bff, 0x7c00, 0x3c01, 0xbc00, 0]);
+}
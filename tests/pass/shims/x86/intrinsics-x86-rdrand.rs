@@ -0,0 +1,65 @@
+// We're testing x86 target specific features
+//@only-target: x86_64 i686
+//@compile-flags: -C target-feature=+rdrand,+rdseed -Zmiri-rdrand-failure-rate=0.2
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+fn main() {
+    assert!(is_x86_feature_detected!("rdrand"));
+    assert!(is_x86_feature_detected!("rdseed"));
+
+    unsafe {
+        test_rdrand();
+        test_rdseed();
+    }
+}
+
+/// Calls `step` until it succeeds, and returns the value along with whether any call failed.
+fn retry<T: Copy + Default + PartialEq>(step: impl Fn(&mut T) -> i32) -> (T, bool) {
+    let mut failed = false;
+    loop {
+        let mut val = T::default();
+        match step(&mut val) {
+            1 => return (val, failed),
+            0 => {
+                // The value is cleared on failure.
+                assert!(val == T::default());
+                failed = true;
+            }
+            r => panic!("unexpected return value {r}"),
+        }
+    }
+}
+
+/// Draws many values with `step` and checks that they are not all the same, and that some
+/// attempts failed (as requested by `-Zmiri-rdrand-failure-rate`) so that retrying was necessary.
+fn check<T: Copy + Default + PartialEq>(step: impl Fn(&mut T) -> i32) {
+    let (first, mut failed) = retry(&step);
+    let mut differs = false;
+    for _ in 0..1000 {
+        let (val, f) = retry(&step);
+        differs |= val != first;
+        failed |= f;
+    }
+    assert!(differs);
+    assert!(failed);
+}
+
+#[target_feature(enable = "rdrand")]
+unsafe fn test_rdrand() {
+    check(|val: &mut u16| unsafe { _rdrand16_step(val) });
+    check(|val: &mut u32| unsafe { _rdrand32_step(val) });
+    #[cfg(target_arch = "x86_64")]
+    check(|val: &mut u64| unsafe { _rdrand64_step(val) });
+}
+
+#[target_feature(enable = "rdseed")]
+unsafe fn test_rdseed() {
+    check(|val: &mut u16| unsafe { _rdseed16_step(val) });
+    check(|val: &mut u32| unsafe { _rdseed32_step(val) });
+    #[cfg(target_arch = "x86_64")]
+    check(|val: &mut u64| unsafe { _rdseed64_step(val) });
+}
@@ -0,0 +1,23 @@
+//@compile-flags: -Zmiri-aliasing-warn=aliasing_warn:stacked-borrows
+#![allow(invalid_reference_casting)]
+
+// Make sure that aliasing violations in a crate listed in `-Zmiri-aliasing-warn`
+// are only reported as warnings, and that the program keeps running.
+fn main() {
+    let target = 42;
+    let r#ref = &target; // freeze
+    let ptr = r#ref as *const _ as *mut _; // raw ptr, with raw tag
+    for _ in 0..2 {
+        // This is reported only once.
+        unsafe { *ptr = 42 };
+    }
+    assert_eq!(*r#ref, 42);
+
+    for _ in 0..3 {
+        // Memory allocated in the same place counts as the same violation, so this is also
+        // reported only once.
+        let target = 42;
+        let ptr = &target as *const i32 as *mut i32;
+        unsafe { *ptr = 42 };
+    }
+}
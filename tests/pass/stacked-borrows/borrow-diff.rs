@@ -0,0 +1,26 @@
+// We disable the GC for this test because it would change what is printed.
+//@compile-flags: -Zmiri-provenance-gc=0
+
+use std::alloc::{self, Layout};
+
+extern "Rust" {
+    fn miri_snapshot_borrows(ptr: *const ()) -> u64;
+    fn miri_diff_borrows(snapshot: u64);
+}
+
+fn main() {
+    let ptr = unsafe { alloc::alloc(Layout::new::<u8>()) };
+    let snapshot = unsafe { miri_snapshot_borrows(ptr as *const ()) };
+    unsafe { miri_diff_borrows(snapshot) };
+
+    let r = unsafe { &mut *ptr };
+    *r = 1;
+    unsafe { miri_diff_borrows(snapshot) };
+
+    // Writing through the raw pointer pops the reference again.
+    unsafe { *ptr = 2 };
+    unsafe { miri_diff_borrows(snapshot) };
+
+    unsafe { alloc::dealloc(ptr, Layout::new::<u8>()) };
+    unsafe { miri_diff_borrows(snapshot) };
+}
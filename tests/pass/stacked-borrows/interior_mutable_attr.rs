@@ -0,0 +1,37 @@
+//@revisions: stack tree
+//@[tree]compile-flags: -Zmiri-tree-borrows
+
+// Make sure that types marked `#[miri::interior_mutable]` can be mutated through shared
+// references, like an `UnsafeCell`, as foreign code often does.
+#[cfg_attr(miri, miri::interior_mutable)]
+struct Handle {
+    state: [u8; 4],
+}
+
+struct Wrapper {
+    id: u8,
+    handle: Handle,
+}
+
+// Stands in for a foreign function that updates the handle behind the program's back.
+fn foreign_update(handle: &Handle) {
+    let state = (&raw const handle.state).cast_mut();
+    unsafe { (*state)[0] += 1 };
+}
+
+fn main() {
+    let handle = Handle { state: [0; 4] };
+    let shared = &handle;
+    foreign_update(shared);
+    foreign_update(shared);
+    assert_eq!(handle.state[0], 2);
+
+    // Types that contain a marked type are interior mutable, too.
+    let wrapper = Wrapper { id: 7, handle: Handle { state: [0; 4] } };
+    let shared = &wrapper;
+    foreign_update(&shared.handle);
+    let whole = shared as *const Wrapper as *mut Wrapper;
+    unsafe { (*whole).handle.state[1] = 1 };
+    assert_eq!(wrapper.handle.state, [1, 1, 0, 0]);
+    assert_eq!(wrapper.id, 7);
+}
@@ -0,0 +1,36 @@
+//@revisions: stack tree
+//@[tree]compile-flags: -Zmiri-tree-borrows
+
+// Make sure that references in functions marked `#[miri::no_borrow_tracking]` are not retagged,
+// so aliasing violations through them go unnoticed.
+#[cfg_attr(miri, miri::no_borrow_tracking)]
+fn untracked(x: &mut u8) {
+    let a = &mut *x;
+    let b = unsafe { &mut *(a as *mut u8) };
+    *a = 1;
+    *b = 2;
+    // Closures defined in such a function are not tracked either.
+    let mut f = || {
+        let c = &mut *x;
+        let d = unsafe { &mut *(c as *mut u8) };
+        *c += 1;
+        *d += 1;
+    };
+    f();
+}
+
+fn tracked(x: &mut u8) {
+    // Properly nested uses of references are still fine.
+    let a = &mut *x;
+    let b = &mut *a;
+    *b = 1;
+    *a += 1;
+}
+
+fn main() {
+    let mut x = 0;
+    untracked(&mut x);
+    assert_eq!(x, 4);
+    tracked(&mut x);
+    assert_eq!(x, 2);
+}
@@ -0,0 +1,25 @@
+//@revisions: types crate
+//@[types]compile-flags: -Zmiri-retag-filter=Tracked
+//@[crate]compile-flags: -Zmiri-track-only-crate=std
+
+struct Tracked(u8);
+struct Untracked(u8);
+
+// Make sure that references that are filtered out are not retagged, so aliasing
+// violations through them go unnoticed.
+fn main() {
+    let mut x = Untracked(0);
+    let a = &mut x;
+    let b = unsafe { &mut *(a as *mut Untracked) };
+    a.0 = 1;
+    b.0 = 2;
+    assert_eq!(x.0, 2);
+
+    // Properly nested uses of retagged references are still fine.
+    let mut y = Tracked(0);
+    let c = &mut y;
+    let d = &mut *c;
+    d.0 = 1;
+    c.0 += 1;
+    assert_eq!(y.0, 2);
+}
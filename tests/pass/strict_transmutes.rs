@@ -0,0 +1,26 @@
+//@compile-flags: -Zmiri-strict-transmutes
+use std::{mem, ptr};
+
+union PtrOrInt {
+    ptr: *const i32,
+    int: usize,
+}
+
+fn main() {
+    // Pointers without provenance have nothing to lose.
+    let _addr: usize = unsafe { mem::transmute(ptr::null::<i32>()) };
+    let _addr: usize = unsafe { mem::transmute(ptr::without_provenance::<i32>(8)) };
+    let _addr: usize = unsafe { mem::transmute_copy(&ptr::without_provenance::<i32>(8)) };
+    let u = PtrOrInt { ptr: ptr::null() };
+    let _int = unsafe { u.int };
+
+    // Transmuting an integer to a pointer is the same as `ptr::without_provenance`.
+    let _ptr: *const i32 = unsafe { mem::transmute(8usize) };
+    let u = PtrOrInt { int: 8 };
+    let _ptr = unsafe { u.ptr };
+
+    // Transmutes that do not involve integers keep the provenance.
+    let x = 0i32;
+    let ptr: *const u8 = unsafe { mem::transmute(&x) };
+    assert_eq!(unsafe { *ptr }, 0);
+}
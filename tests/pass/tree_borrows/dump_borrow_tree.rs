@@ -0,0 +1,36 @@
+//@ignore-target: windows # File handling is not implemented yet
+//@compile-flags: -Zmiri-tree-borrows -Zmiri-disable-isolation -Zmiri-provenance-gc=0
+
+#[path = "../../utils/mod.rs"]
+#[macro_use]
+mod utils;
+
+use std::ffi::CString;
+use std::fs;
+
+fn main() {
+    // Miri writes the file on the host, and we read it back from the program.
+    let file = "miri_test_dump_borrow_tree.dot";
+    let path = utils::prepare(file);
+    let host_dir =
+        std::env::var_os("MIRI_TEMP").unwrap_or_else(|| std::env::temp_dir().into_os_string());
+    let host_path = CString::new(format!("{}/{file}", host_dir.to_str().unwrap())).unwrap();
+
+    let mut x = 0u8;
+    let ptr = &raw mut x;
+    unsafe {
+        let xref = &mut *ptr;
+        name!(xref as *mut u8, "xref");
+        let xshr = &*xref;
+        name!(xshr as *const u8, "xshr");
+        utils::miri_dump_borrow_tree(ptr as *const (), host_path.as_ptr());
+        let _val = *xshr;
+    }
+
+    let dot = fs::read_to_string(&path).unwrap();
+    assert!(dot.starts_with("digraph \"alloc"));
+    assert!(dot.contains("=xref>"));
+    assert!(dot.contains("=xshr>"));
+    assert!(dot.contains(" -> "));
+    fs::remove_file(&path).unwrap();
+}
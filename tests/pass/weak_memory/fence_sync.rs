@@ -0,0 +1,121 @@
+//@compile-flags: -Zmiri-ignore-leaks -Zmiri-preemption-rate=0
+
+// Tests that release and acquire fences combined with relaxed atomics establish
+// happens-before, also when a load reads a store that is not the latest one.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::Ordering::*;
+use std::sync::atomic::{AtomicUsize, fence};
+use std::thread::spawn;
+
+#[derive(Copy, Clone)]
+struct EvilSend<T>(pub T);
+
+unsafe impl<T> Send for EvilSend<T> {}
+unsafe impl<T> Sync for EvilSend<T> {}
+
+// We can't create static items because we need to run each test multiple times.
+fn static_atomic(val: usize) -> &'static AtomicUsize {
+    Box::leak(Box::new(AtomicUsize::new(val)))
+}
+
+fn static_var(val: usize) -> EvilSend<*mut usize> {
+    EvilSend(Box::leak(Box::new(val)))
+}
+
+/// A single-producer single-consumer queue that only uses relaxed accesses and fences.
+struct Queue {
+    slots: [UnsafeCell<usize>; 4],
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+unsafe impl Sync for Queue {}
+
+impl Queue {
+    fn push(&self, val: usize) {
+        let head = self.head.load(Relaxed);
+        while head.wrapping_sub(self.tail.load(Relaxed)) == self.slots.len() {
+            std::hint::spin_loop();
+        }
+        fence(Acquire);
+        unsafe { *self.slots[head % self.slots.len()].get() = val };
+        fence(Release);
+        self.head.store(head.wrapping_add(1), Relaxed);
+    }
+
+    fn pop(&self) -> usize {
+        let tail = self.tail.load(Relaxed);
+        while self.head.load(Relaxed) == tail {
+            std::hint::spin_loop();
+        }
+        fence(Acquire);
+        let val = unsafe { *self.slots[tail % self.slots.len()].get() };
+        fence(Release);
+        self.tail.store(tail.wrapping_add(1), Relaxed);
+        val
+    }
+}
+
+fn spsc_queue() {
+    let queue: &'static Queue = Box::leak(Box::new(Queue {
+        slots: Default::default(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    }));
+
+    let producer = spawn(move || {
+        for i in 0..10 {
+            queue.push(i);
+        }
+    });
+    let consumer = spawn(move || {
+        for i in 0..10 {
+            assert_eq!(queue.pop(), i);
+        }
+    });
+
+    producer.join().unwrap();
+    consumer.join().unwrap();
+}
+
+fn outdated_store() {
+    let flag = static_atomic(0);
+    let data = static_var(0);
+
+    let j1 = spawn(move || {
+        let data = data; // avoid field capturing
+        unsafe { *data.0 = 42 };
+        fence(Release);
+        flag.store(1, Relaxed);
+    });
+    // A later relaxed store by another thread does not carry the release fence of `j1`.
+    let j2 = spawn(move || {
+        while flag.load(Relaxed) != 1 {
+            std::hint::spin_loop();
+        }
+        flag.store(2, Relaxed);
+    });
+    // If this reads the store of `j1`, it synchronizes with `j1` even when that
+    // store is no longer the latest one.
+    let j3 = spawn(move || {
+        let data = data; // avoid field capturing
+        if flag.load(Relaxed) == 1 {
+            fence(Acquire);
+            assert_eq!(unsafe { *data.0 }, 42);
+        }
+    });
+
+    j1.join().unwrap();
+    j2.join().unwrap();
+    j3.join().unwrap();
+}
+
+pub fn main() {
+    for _ in 0..10 {
+        spsc_queue();
+    }
+    for _ in 0..50 {
+        outdated_store();
+    }
+}
@@ -0,0 +1,35 @@
+// We disable preemption to get a deterministic interleaving of the threads.
+//@compile-flags: -Zmiri-preemption-rate=0 -Zmiri-ignore-leaks
+
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering::*;
+use std::thread;
+
+#[path = "../../utils/mod.rs"]
+mod utils;
+
+fn main() {
+    let x: &'static AtomicU32 = Box::leak(Box::new(AtomicU32::new(0)));
+    let y: &'static AtomicU32 = Box::leak(Box::new(AtomicU32::new(0)));
+
+    // Nothing has been stored atomically yet.
+    unsafe { utils::miri_print_store_buffers(x as *const _ as *const ()) };
+
+    let writer = thread::Builder::new()
+        .name("writer".into())
+        .spawn(move || {
+            x.store(1, Relaxed);
+            x.store(2, Relaxed);
+            y.store(1, Release);
+        })
+        .unwrap();
+    // Let the writer run to completion without synchronizing with it,
+    // so that all its stores can still be read here.
+    thread::yield_now();
+    unsafe { utils::miri_print_store_buffers(x as *const _ as *const ()) };
+
+    // After synchronizing with the writer, only its last store can be read.
+    writer.join().unwrap();
+    unsafe { utils::miri_print_store_buffers(x as *const _ as *const ()) };
+    unsafe { utils::miri_print_store_buffers(y as *const _ as *const ()) };
+}
@@ -0,0 +1,115 @@
+//@revisions: x86 arm
+//@compile-flags: -Zmiri-ignore-leaks -Zmiri-preemption-rate=0
+//@[x86]compile-flags: -Zmiri-weak-memory-model=x86-tso
+//@[arm]compile-flags: -Zmiri-weak-memory-model=arm
+
+// Tests that the weak memory emulation only exhibits the behaviours of the selected hardware
+// memory model. Like in weak.rs, each test is run multiple times; tests of allowed behaviours
+// return true when the behaviour is seen.
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::*;
+use std::thread::spawn;
+
+// We can't create static items because we need to run each test multiple times.
+fn static_atomic(val: usize) -> &'static AtomicUsize {
+    Box::leak(Box::new(AtomicUsize::new(val)))
+}
+
+// Spins until it reads the given value
+fn reads_value(loc: &AtomicUsize, val: usize) -> usize {
+    while loc.load(Relaxed) != val {
+        std::hint::spin_loop();
+    }
+    val
+}
+
+// Both threads may read the initial value, as the stores can still sit in the store buffers.
+fn store_buffering() -> bool {
+    let x = static_atomic(0);
+    let y = static_atomic(0);
+
+    let j1 = spawn(move || {
+        x.store(1, Relaxed);
+        y.load(Relaxed)
+    });
+
+    let j2 = spawn(move || {
+        y.store(1, Relaxed);
+        x.load(Relaxed)
+    });
+
+    let r1 = j1.join().unwrap();
+    let r2 = j2.join().unwrap();
+
+    (r1, r2) == (0, 0)
+}
+
+// The relaxed stores may become visible out of order on ARM, but not on x86.
+fn message_passing() -> bool {
+    let data = static_atomic(0);
+    let flag = static_atomic(0);
+
+    let j1 = spawn(move || {
+        data.store(1, Relaxed);
+        flag.store(1, Relaxed);
+    });
+
+    let j2 = spawn(move || {
+        reads_value(flag, 1);
+        data.load(Relaxed)
+    });
+
+    j1.join().unwrap();
+    let r2 = j2.join().unwrap();
+
+    r2 == 0
+}
+
+// Once another thread observed a store, all threads observe it.
+fn multi_copy_atomic() -> bool {
+    let x = static_atomic(0);
+    let flag = static_atomic(0);
+
+    let j1 = spawn(move || {
+        x.store(1, Relaxed);
+        x.store(2, Relaxed);
+    });
+
+    let j2 = spawn(move || {
+        reads_value(x, 2);
+        flag.store(1, Relaxed);
+    });
+
+    let j3 = spawn(move || {
+        reads_value(flag, 1);
+        x.load(Relaxed)
+    });
+
+    j1.join().unwrap();
+    j2.join().unwrap();
+    let r3 = j3.join().unwrap();
+
+    r3 != 2
+}
+
+/// Asserts that the function returns true at least once in 100 runs
+#[track_caller]
+fn assert_once(f: fn() -> bool) {
+    assert!(std::iter::repeat_with(|| f()).take(100).any(|x| x));
+}
+
+/// Asserts that the function never returns true in 100 runs
+#[track_caller]
+fn assert_never(f: fn() -> bool) {
+    assert!(std::iter::repeat_with(|| f()).take(100).all(|x| !x));
+}
+
+pub fn main() {
+    assert_once(store_buffering);
+    #[cfg(x86)]
+    assert_never(message_passing);
+    #[cfg(arm)]
+    assert_once(message_passing);
+    assert_never(multi_copy_atomic);
+}
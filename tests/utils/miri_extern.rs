@@ -90,6 +90,44 @@ extern "Rust" {
     /// change, or it may be removed entirely.
     pub fn miri_print_borrow_state(alloc_id: u64, show_unnamed: bool);
 
+    /// Miri-provided extern function to capture the borrow state (stacks or tree) of the allocation
+    /// that `ptr` points to. Returns an ID to be passed to `miri_diff_borrows`. If `ptr` is dangling,
+    /// interpretation will abort.
+    ///
+    /// Note that casting a reference to `*const ()` to call this function can itself change the
+    /// borrow state, so it is best to pass a raw pointer that was created beforehand.
+    ///
+    /// This function is extremely unstable, like `miri_print_borrow_state`.
+    pub fn miri_snapshot_borrows(ptr: *const ()) -> u64;
+
+    /// Miri-provided extern function to write the borrow tree of the allocation that `ptr` points
+    /// to, with all tags and their permissions, as a graph in the DOT language of Graphviz to the
+    /// file `path`. The path is interpreted on the host, and the file is written even under isolation.
+    /// If `ptr` is dangling, interpretation will abort.
+    ///
+    /// This function only does something under Tree Borrows. It is extremely unstable, like
+    /// `miri_print_borrow_state`.
+    pub fn miri_dump_borrow_tree(ptr: *const (), path: *const core::ffi::c_char);
+
+    /// Miri-provided extern function to print (from the interpreter, not the program) how the
+    /// borrow state of the allocation captured by `miri_snapshot_borrows` changed since then,
+    /// as a line-based diff of what `miri_print_borrow_state(_, true)` shows.
+    ///
+    /// The format of what this emits is unstable and may change at any time. Callers of this
+    /// function may wish to pass `-Zmiri-provenance-gc=0`, so that the garbage collection of
+    /// tags does not show up in the diff.
+    pub fn miri_diff_borrows(snapshot: u64);
+
+    /// Miri-provided extern function to print (from the interpreter, not the program) the weak memory
+    /// store buffers of all atomic locations in the allocation that `ptr` points to.
+    ///
+    /// For every location, this lists the values that loads may still return, oldest first: the value,
+    /// the thread that stored it, and the live threads that could currently read it.
+    /// Nothing is printed for locations that have not been written atomically yet.
+    ///
+    /// The format of what this emits is unstable and may change at any time.
+    pub fn miri_print_store_buffers(ptr: *const ());
+
     /// Miri-provided extern function to associate a name to the nth parent of a tag.
     /// Typically the name given would be the name of the program variable that holds the pointer.
     /// Unreachable tags can still be named by using nonzero `nth_parent` and a child tag.
@@ -143,6 +181,15 @@ extern "Rust" {
     /// that it doesn't break anything.
     pub fn miri_run_provenance_gc();
 
+    /// Miri-provided extern function to mark a good place for a preemption, e.g. in the middle of
+    /// a lock-free algorithm. With random scheduling, the active thread is preempted here with a
+    /// high probability; when exploring schedules, this is a point where other threads may run.
+    pub fn miri_yield_hint();
+
+    /// Miri-provided extern function to preempt the active thread, letting the other threads run
+    /// before it continues.
+    pub fn miri_preempt_here();
+
     /// Miri-provided extern function to promise that a given pointer is properly aligned for
     /// "symbolic" alignment checks. Will fail if the pointer is not actually aligned or `align` is
     /// not a power of two. Has no effect when alignment checks are concrete (which is the default).